@@ -0,0 +1,138 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Host codec over a batch of 10,000 entries: encoding their inputs and strictly decoding their
+//! journals. Each is measured next to the allocating path it replaced, `abi_encode` per input and
+//! a decode that copies the journal and compares its whole re-encoding.
+//!
+//! Run with `cargo bench -p app --bench codec`.
+
+use std::hint::black_box;
+
+use alloy::{
+    primitives::{Address, B256, U256},
+    sol_types::SolValue,
+};
+use app::{
+    batch::BatchEntry,
+    journal::{evaluate, ComplianceInput, ComplianceJournal, JournalEncoding},
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const ENTRIES: u64 = 10_000;
+
+const DECISION_AT: u64 = 1_700_000_000;
+
+fn inputs() -> Vec<ComplianceInput> {
+    (1..=ENTRIES)
+        .map(|i| {
+            BatchEntry {
+                user: Address::left_padding_from(&i.to_be_bytes()),
+                product_id: B256::with_last_byte(1),
+                amount: U256::from(100),
+                kyc_passed: true,
+                kyc_tier: None,
+                required_tier: 1,
+                aml_passed: i % 7 != 0,
+                claims: U256::from(i),
+                required_claims: U256::from(1),
+                required_attestations: 0,
+                product_policy_version: 1,
+                quote: None,
+                risk_score: 0,
+                review_band: None,
+                regime_id: 0,
+                settlement_token: Address::ZERO,
+                case_id: B256::ZERO,
+                min_trade: U256::ZERO,
+                max_trade: None,
+                ttl_secs: 0,
+                attester: None,
+                attestation_signature: None,
+                governance: Vec::new(),
+                override_signature: None,
+                compact_journal: false,
+                sha256_commitments: false,
+                decision_uuid: B256::ZERO,
+                credential_hash: B256::ZERO,
+                settle_delay_secs: 0,
+                aml_sources: U256::ZERO,
+                required_aml_sources: U256::ZERO,
+                block_hash: B256::ZERO,
+                kyc_provider: 0,
+                allowed_kyc_providers: Vec::new(),
+                consent_given: false,
+                consent_at: 0,
+                consent_required: false,
+            }
+            .input(DECISION_AT)
+        })
+        .collect()
+}
+
+/// Strict decoding as it was: copy the journal, decode it, and compare its re-encoding.
+fn decode_by_reencoding(bytes: &[u8]) -> Option<ComplianceJournal> {
+    let words = bytes.to_vec();
+    let journal = ComplianceJournal::abi_decode(&words).ok()?;
+    (journal.abi_encode() == words).then_some(journal)
+}
+
+fn encode(c: &mut Criterion) {
+    let inputs = inputs();
+    let mut group = c.benchmark_group("encode 10k inputs");
+    group.bench_function("abi_encode", |b| {
+        b.iter(|| {
+            for input in &inputs {
+                black_box(input.abi_encode());
+            }
+        })
+    });
+    group.bench_function("encode_to", |b| {
+        let mut buffer = Vec::with_capacity(ComplianceInput::LEN);
+        b.iter(|| {
+            for input in &inputs {
+                buffer.clear();
+                input.encode_to(&mut buffer);
+                black_box(&buffer);
+            }
+        })
+    });
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let journals: Vec<Vec<u8>> = inputs()
+        .iter()
+        .map(|input| evaluate(input).encode(JournalEncoding::Abi))
+        .collect();
+    let mut group = c.benchmark_group("decode 10k journals");
+    group.bench_function("re-encoding", |b| {
+        b.iter(|| {
+            for journal in &journals {
+                black_box(decode_by_reencoding(journal).unwrap());
+            }
+        })
+    });
+    group.bench_function("try_from", |b| {
+        b.iter(|| {
+            for journal in &journals {
+                black_box(ComplianceJournal::try_from(journal.as_slice()).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
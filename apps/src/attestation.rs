@@ -0,0 +1,201 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+
+use alloy::{
+    eips::BlockId,
+    primitives::{fixed_bytes, Address, Bytes, FixedBytes, B256, U256},
+    providers::Provider,
+};
+use anyhow::{bail, ensure, Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use url::Url;
+
+use crate::{batch::BatchEntry, http::RetryPolicy, journal::kyc_tier_from_passed};
+
+alloy::sol! {
+    /// EIP-1271 signature validation implemented by contract wallets.
+    #[sol(rpc)]
+    interface IERC1271 {
+        function isValidSignature(bytes32 hash, bytes signature) external view returns (bytes4);
+    }
+}
+
+/// Value `isValidSignature` returns for a valid signature.
+pub const EIP1271_MAGIC_VALUE: FixedBytes<4> = fixed_bytes!("0x1626ba7e");
+
+/// Check that contract wallet `attester` accepts `signature` over `hash`, by simulating its
+/// EIP-1271 `isValidSignature` with `eth_call` at `block`.
+pub async fn check_eip1271_signature<P: Provider>(
+    provider: &P,
+    attester: Address,
+    hash: B256,
+    signature: Bytes,
+    block: BlockId,
+) -> Result<()> {
+    let magic = IERC1271::new(attester, provider)
+        .isValidSignature(hash, signature)
+        .call()
+        .block(block)
+        .await
+        .with_context(|| format!("failed to call isValidSignature on attester {attester}"))?;
+    ensure!(
+        magic == EIP1271_MAGIC_VALUE,
+        "attester {attester} rejected the signature over {hash} (isValidSignature returned {magic})"
+    );
+    tracing::info!("Attester {attester} accepted the signature over {hash}");
+    Ok(())
+}
+
+/// Compliance status of a user as reported by a KYC provider.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComplianceStatus {
+    /// Legacy pass/fail KYC flag, used when `kyc_tier` is not reported.
+    #[serde(default)]
+    pub kyc_passed: bool,
+    #[serde(default)]
+    pub kyc_tier: Option<u8>,
+    pub aml_passed: bool,
+    /// Bitmap of the questionnaire items the provider has verified for the user.
+    #[serde(default)]
+    pub claims: U256,
+    /// Risk score the user was screened with, if the source screens; see [crate::screening].
+    #[serde(default)]
+    pub risk_score: Option<u8>,
+    /// `keccak256` of the verifiable credential the status was read from, or zero when it came
+    /// from elsewhere.
+    #[serde(skip)]
+    pub credential_hash: B256,
+}
+
+impl ComplianceStatus {
+    /// KYC tier of the user, falling back to the tier implied by `kyc_passed`.
+    pub fn kyc_tier(&self) -> u8 {
+        self.kyc_tier
+            .unwrap_or_else(|| kyc_tier_from_passed(self.kyc_passed))
+    }
+
+    /// Replace the user's compliance fields in `entry` with this status, keeping its risk score
+    /// when the status has none. The product's requirements are left alone.
+    pub fn apply_to(&self, entry: &mut BatchEntry) {
+        entry.kyc_passed = self.kyc_passed;
+        entry.kyc_tier = self.kyc_tier;
+        entry.aml_passed = self.aml_passed;
+        entry.claims = self.claims;
+        if let Some(risk_score) = self.risk_score {
+            entry.risk_score = risk_score;
+        }
+        entry.credential_hash = self.credential_hash;
+    }
+}
+
+/// Where the compliance fields of a trade come from.
+pub trait AttestationSource: Send + Sync {
+    /// Look up the current compliance status of `user`.
+    fn fetch(&self, user: Address) -> impl Future<Output = Result<ComplianceStatus>> + Send;
+}
+
+/// Fill in the compliance fields of every entry from `source`, one lookup per entry.
+pub async fn fetch_compliance<S: AttestationSource>(
+    source: &S,
+    entries: &mut [BatchEntry],
+) -> Result<()> {
+    for entry in entries {
+        let status = source.fetch(entry.user).await?;
+        tracing::info!(
+            "KYC provider reports tier {} and AML {} for {}",
+            status.kyc_tier(),
+            if status.aml_passed {
+                "passed"
+            } else {
+                "failed"
+            },
+            entry.user
+        );
+        status.apply_to(entry);
+    }
+    Ok(())
+}
+
+/// Fetches compliance status from a KYC provider's REST API at `GET <url>/<user>`.
+#[derive(Clone, Debug)]
+pub struct HttpSource {
+    url: Url,
+    bearer_token: Option<String>,
+    headers: HeaderMap,
+    policy: RetryPolicy,
+    http: reqwest::Client,
+}
+
+impl HttpSource {
+    pub fn new(url: Url, bearer_token: Option<String>) -> Self {
+        Self {
+            url,
+            bearer_token,
+            headers: HeaderMap::new(),
+            policy: RetryPolicy::default(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Send `name: value` with every request, e.g. an API key header the provider requires.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Replace the default timeout and retry policy.
+    pub fn with_policy(self, policy: RetryPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    fn user_url(&self, user: Address) -> Result<Url> {
+        let mut url = self.url.clone();
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("KYC provider URL {} cannot be a base", self.url))?
+            .pop_if_empty()
+            .push(&user.to_string());
+        Ok(url)
+    }
+}
+
+impl AttestationSource for HttpSource {
+    async fn fetch(&self, user: Address) -> Result<ComplianceStatus> {
+        let url = self.user_url(user)?;
+        let response = self
+            .policy
+            .send(|| {
+                let request = self.http.get(url.clone()).headers(self.headers.clone());
+                match &self.bearer_token {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                }
+            })
+            .await
+            .with_context(|| format!("failed to fetch compliance status from {url}"))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("KYC provider returned {status} for {user}: {body}");
+        }
+        response
+            .json()
+            .await
+            .context("KYC provider response did not match the expected schema")
+    }
+}
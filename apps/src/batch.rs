@@ -0,0 +1,828 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Batch mode: prove and settle many trades from one input file.
+//!
+//! Proving and settlement are throttled independently. Each [Concurrency] limit is a semaphore
+//! whose permit is held for one stage of one entry only, so an entry waiting to settle does not
+//! occupy a proof slot and a slow market never holds up settlement of entries that are already
+//! proven. Neither stage retries yet; any retry added to a stage must run while holding that
+//! stage's permit, so the limit bounds in-flight attempts including retries and an entry backing
+//! off never blocks the other stage. A panic inside a stage is caught and reported as a failure
+//! of that entry, so one bad entry cannot take down the rest of the batch.
+
+use std::{
+    any::Any, future::Future, num::NonZeroUsize, panic::AssertUnwindSafe, path::Path, sync::Arc,
+};
+
+use alloy::{
+    eips::{BlockId, BlockNumberOrTag},
+    primitives::{keccak256, Address, Bytes, Signature, B256, U256},
+    providers::Provider,
+    sol_types::SolValue,
+};
+use anyhow::{anyhow, ensure, Context, Result};
+use boundless_market::{input::GuestEnv, request_builder::RequestParams};
+use futures_util::FutureExt;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{watch, Semaphore},
+    task::JoinSet,
+};
+use tracing::Instrument;
+
+use crate::{
+    attestation::check_eip1271_signature,
+    cache::ProofCache,
+    journal::{
+        attestation_hash, compliance_image_id, ensure_image_id, evaluate, fulfillment_journal,
+        kyc_tier_from_passed, unix_now, CommitmentHash, ComplianceInput, ComplianceJournal,
+        JournalEncoding, GOVERNANCE_SET_LEN, KYC_PROVIDER_SET_LEN,
+    },
+    market::{race_compliance, MarketClient},
+    notify::{Alert, Notifications},
+    schema,
+    settle::{Settlement, Settler},
+    webhook::WebhookSink,
+};
+
+/// One trade to check and settle.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchEntry {
+    pub user: Address,
+    pub product_id: B256,
+    pub amount: U256,
+    /// Legacy pass/fail KYC flag, used when `kyc_tier` is not set.
+    #[serde(default)]
+    pub kyc_passed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kyc_tier: Option<u8>,
+    /// Minimum KYC tier the product requires.
+    #[serde(default)]
+    pub required_tier: u8,
+    pub aml_passed: bool,
+    /// Bitmap of the questionnaire items the user has answered.
+    #[serde(default)]
+    pub claims: U256,
+    /// Questionnaire items the product requires.
+    #[serde(default)]
+    pub required_claims: U256,
+    /// Attesters the product requires to vouch for the user.
+    #[serde(default)]
+    pub required_attestations: u8,
+    /// Version of the product policy `required_tier` and `required_claims` come from.
+    #[serde(default)]
+    pub product_policy_version: u32,
+    /// Quote leg when the trade is of a pair, with `product_id` as its base.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote: Option<QuoteLeg>,
+    /// Risk score the screening provider gave the user.
+    #[serde(default)]
+    pub risk_score: u8,
+    /// Risk scores for which the product holds the trade for manual review.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_band: Option<ReviewBand>,
+    /// [Regime](compliance_core::Regime) of the product's jurisdiction.
+    #[serde(default)]
+    pub regime_id: u16,
+    /// Token the trade settles in, or zero when the product accepts any.
+    #[serde(default)]
+    pub settlement_token: Address,
+    /// Off-chain case-management record the decision belongs to, or zero when there is none.
+    #[serde(default)]
+    pub case_id: B256,
+    /// Smallest trade the product accepts, in the same unit as `amount`.
+    #[serde(default)]
+    pub min_trade: U256,
+    /// Largest trade the product accepts, or no limit when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_trade: Option<U256>,
+    /// Seconds the hook may keep accepting an allowed decision without a new proof, capped by the
+    /// product's maximum; zero for a decision good for one trade.
+    #[serde(default)]
+    pub ttl_secs: u64,
+    /// Contract wallet that attested the compliance fields.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attester: Option<Address>,
+    /// The attester's EIP-1271 signature over [attestation_hash] of the entry's input, whose
+    /// `keccak256` the guest commits. The attester only counts towards `required_attestations`
+    /// with one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation_signature: Option<Bytes>,
+    /// Governance keys allowed to override the decision, at most [GOVERNANCE_SET_LEN].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub governance: Vec<Address>,
+    /// A governance key's signature over [override_hash](crate::journal::override_hash) of the
+    /// entry's input, which allows the trade whatever the compliance facts are.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub override_signature: Option<Signature>,
+    /// Have the guest commit a [JournalEncoding::Compact] journal.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub compact_journal: bool,
+    /// Have the guest compute the journal's commitments with [CommitmentHash::Sha256] rather than
+    /// keccak.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sha256_commitments: bool,
+    /// Identifier of the decision, which the hook settles at most once; zero until one is drawn
+    /// with [Self::ensure_decision_uuid].
+    #[serde(default, skip_serializing_if = "B256::is_zero")]
+    pub decision_uuid: B256,
+    /// `keccak256` of the verifiable credential the compliance fields were taken from, which the
+    /// guest commits; zero when they were not. See [crate::credential].
+    #[serde(default, skip_serializing_if = "B256::is_zero")]
+    pub credential_hash: B256,
+    /// Seconds after the decision before which the hook must not settle it. The guest commits the
+    /// decision time plus this as `notBefore`; zero settles at once.
+    #[serde(default)]
+    pub settle_delay_secs: u64,
+    /// Bitmap of the AML list sources the user was screened against, such as
+    /// [AML_SOURCE_SANCTIONS](compliance_core::AML_SOURCE_SANCTIONS).
+    #[serde(default)]
+    pub aml_sources: U256,
+    /// AML list sources the product requires the screening to have covered.
+    #[serde(default)]
+    pub required_aml_sources: U256,
+    /// Hash of the block the entry's onchain state was read at. Zero lets
+    /// [check_attestation](Self::check_attestation) read it at the latest block and record that
+    /// block's hash here.
+    #[serde(default)]
+    pub block_hash: B256,
+    /// Id of the KYC vendor whose attestation `kyc_tier` and `claims` come from, or zero when
+    /// there is none to name.
+    #[serde(default)]
+    pub kyc_provider: u16,
+    /// KYC providers the product accepts, at most [KYC_PROVIDER_SET_LEN]; empty accepts any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_kyc_providers: Vec<u16>,
+    /// Whether the user consented to the processing of their data.
+    #[serde(default)]
+    pub consent_given: bool,
+    /// Unix time the user's consent was recorded at, or zero when there is none.
+    #[serde(default)]
+    pub consent_at: u64,
+    /// Whether the product requires recorded consent before a trade is allowed. Products require
+    /// it unless they set this to `false`.
+    #[serde(default = "default_consent_required")]
+    pub consent_required: bool,
+}
+
+fn default_consent_required() -> bool {
+    true
+}
+
+/// The quote product of a pair and its requirements, which the user must meet as well as the
+/// base product's.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuoteLeg {
+    pub product_id: B256,
+    #[serde(default)]
+    pub required_tier: u8,
+    #[serde(default)]
+    pub required_claims: U256,
+    #[serde(default)]
+    pub product_policy_version: u32,
+}
+
+/// Gray zone of risk scores, from `low` up to but excluding `high`, that needs manual review
+/// rather than an allow or deny.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReviewBand {
+    pub low: u8,
+    pub high: u8,
+}
+
+impl BatchEntry {
+    /// KYC tier of the user, falling back to the tier implied by `kyc_passed`.
+    pub fn kyc_tier(&self) -> u8 {
+        self.kyc_tier
+            .unwrap_or_else(|| kyc_tier_from_passed(self.kyc_passed))
+    }
+
+    /// Guest stdin for this entry: [Self::input] ABI-encoded and written as a single frame.
+    pub fn stdin(&self, decision_at: u64) -> Vec<u8> {
+        GuestEnv::builder()
+            .write_frame(&self.input(decision_at).abi_encode())
+            .stdin
+    }
+
+    /// Check what [Self::input] cannot represent, and a zero user, which the guest denies as a
+    /// configuration error.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            !self.user.is_zero(),
+            "user is the zero address; the guest would deny the trade"
+        );
+        ensure!(
+            self.governance.len() <= GOVERNANCE_SET_LEN,
+            "{} governance keys given, at most {GOVERNANCE_SET_LEN} are supported",
+            self.governance.len()
+        );
+        ensure!(
+            self.allowed_kyc_providers.len() <= KYC_PROVIDER_SET_LEN,
+            "{} allowed KYC providers given, at most {KYC_PROVIDER_SET_LEN} are supported",
+            self.allowed_kyc_providers.len()
+        );
+        ensure!(
+            !self.allowed_kyc_providers.contains(&0),
+            "KYC provider 0 names no provider and cannot be allowed"
+        );
+        ensure!(
+            self.min_trade <= self.max_trade(),
+            "min trade {} is above max trade {}; the guest would deny every trade",
+            self.min_trade,
+            self.max_trade()
+        );
+        Ok(())
+    }
+
+    /// Largest trade the product accepts, falling back to no limit.
+    pub fn max_trade(&self) -> U256 {
+        self.max_trade.unwrap_or(U256::MAX)
+    }
+
+    /// Unix time the hook may settle this entry's decision from, when decided at `decision_at`;
+    /// zero without a settle delay.
+    pub fn not_before(&self, decision_at: u64) -> u64 {
+        if self.settle_delay_secs == 0 {
+            0
+        } else {
+            decision_at.saturating_add(self.settle_delay_secs)
+        }
+    }
+
+    /// Encoding the guest commits this entry's journal in.
+    pub fn journal_encoding(&self) -> JournalEncoding {
+        if self.compact_journal {
+            JournalEncoding::Compact
+        } else {
+            JournalEncoding::Abi
+        }
+    }
+
+    /// Hash the guest computes this entry's journal commitments with.
+    pub fn commitment_hash(&self) -> CommitmentHash {
+        if self.sha256_commitments {
+            CommitmentHash::Sha256
+        } else {
+            CommitmentHash::Keccak
+        }
+    }
+
+    /// Draw a random [Self::decision_uuid] unless the entry already has one, so that a settlement
+    /// retried with the same proof is refused rather than settled twice.
+    pub fn ensure_decision_uuid(&mut self) -> B256 {
+        if self.decision_uuid.is_zero() {
+            self.decision_uuid = B256::random();
+        }
+        self.decision_uuid
+    }
+
+    /// Guest input for this entry, decided at `decision_at` (Unix seconds).
+    ///
+    /// Panics if the entry fails [Self::validate].
+    pub fn input(&self, decision_at: u64) -> ComplianceInput {
+        assert!(
+            self.governance.len() <= GOVERNANCE_SET_LEN,
+            "entry has {} governance keys, at most {GOVERNANCE_SET_LEN} fit the input",
+            self.governance.len()
+        );
+        let mut governance = [Address::ZERO; GOVERNANCE_SET_LEN];
+        governance[..self.governance.len()].copy_from_slice(&self.governance);
+        assert!(
+            self.allowed_kyc_providers.len() <= KYC_PROVIDER_SET_LEN,
+            "entry has {} allowed KYC providers, at most {KYC_PROVIDER_SET_LEN} fit the input",
+            self.allowed_kyc_providers.len()
+        );
+        let mut allowed_kyc_providers = [0; KYC_PROVIDER_SET_LEN];
+        allowed_kyc_providers[..self.allowed_kyc_providers.len()]
+            .copy_from_slice(&self.allowed_kyc_providers);
+        let compact = self
+            .override_signature
+            .map_or([0; 64], |signature| signature.as_erc2098());
+        ComplianceInput {
+            user: self.user,
+            productId: self.product_id,
+            kycTier: self.kyc_tier(),
+            requiredTier: self.required_tier,
+            amlPassed: self.aml_passed,
+            claims: self.claims,
+            requiredClaims: self.required_claims,
+            productPolicyVersion: self.product_policy_version,
+            quoteProduct: self
+                .quote
+                .as_ref()
+                .map_or(self.product_id, |quote| quote.product_id),
+            quoteRequiredTier: self
+                .quote
+                .as_ref()
+                .map_or(self.required_tier, |quote| quote.required_tier),
+            quoteRequiredClaims: self
+                .quote
+                .as_ref()
+                .map_or(self.required_claims, |quote| quote.required_claims),
+            quotePolicyVersion: self
+                .quote
+                .as_ref()
+                .map_or(self.product_policy_version, |quote| {
+                    quote.product_policy_version
+                }),
+            riskScore: self.risk_score,
+            reviewBandLow: self.review_band.map_or(0, |band| band.low),
+            reviewBandHigh: self.review_band.map_or(0, |band| band.high),
+            regimeId: self.regime_id,
+            settlementToken: self.settlement_token,
+            caseId: self.case_id,
+            tradeAmount: self.amount,
+            minTrade: self.min_trade,
+            maxTrade: self.max_trade(),
+            attester: self.attester.unwrap_or_default(),
+            requiredAttestations: self.required_attestations,
+            governance,
+            overrideR: B256::from_slice(&compact[..32]),
+            overrideYParityAndS: B256::from_slice(&compact[32..]),
+            journalVersion: self.journal_encoding() as u8 | self.commitment_hash().version_flag(),
+            now: decision_at,
+            ttlSecs: self.ttl_secs,
+            decisionUuid: self.decision_uuid,
+            credentialHash: self.credential_hash,
+            notBefore: self.not_before(decision_at),
+            amlSources: self.aml_sources,
+            requiredAmlSources: self.required_aml_sources,
+            blockHash: self.block_hash,
+            kycProvider: self.kyc_provider,
+            allowedKycProviders: allowed_kyc_providers,
+            consentGiven: self.consent_given,
+            consentAt: self.consent_at,
+            consentRequired: self.consent_required,
+            attestationSignatureHash: self
+                .attestation_signature
+                .as_ref()
+                .map_or(B256::ZERO, keccak256),
+            imageId: compliance_image_id(),
+        }
+    }
+
+    /// Simulate the attester's EIP-1271 check of the entry's signature, if it has an attester, at
+    /// the entry's `block_hash`. An entry without one is checked at the latest block, and commits
+    /// that block's hash.
+    pub async fn check_attestation<P: Provider>(&mut self, provider: &P) -> Result<()> {
+        let Some(attester) = self.attester else {
+            return Ok(());
+        };
+        let signature = self
+            .attestation_signature
+            .clone()
+            .with_context(|| format!("attester {attester} is set without a signature"))?;
+        if self.block_hash.is_zero() {
+            let block = provider
+                .get_block_by_number(BlockNumberOrTag::Latest)
+                .await
+                .context("failed to fetch the latest block")?
+                .context("the RPC endpoint returned no latest block")?;
+            self.block_hash = block.header.hash;
+        }
+        // The attestation does not cover the decision time.
+        let hash = attestation_hash(&self.input(0));
+        check_eip1271_signature(
+            provider,
+            attester,
+            hash,
+            signature,
+            BlockId::hash(self.block_hash),
+        )
+        .await
+    }
+
+    /// Journal the compliance guest commits for this entry when decided at `decision_at`.
+    pub fn expected_journal(&self, decision_at: u64) -> ComplianceJournal {
+        evaluate(&self.input(decision_at))
+    }
+}
+
+/// Version of the batch input format, and of the [schema](crate::schema) of the input and the
+/// summary. Bump it with any change a file written for the previous version would not survive.
+pub const BATCH_INPUT_VERSION: u32 = 1;
+
+/// Read batch entries from a JSON array, after checking it against
+/// [input_schema](crate::schema::input_schema).
+pub fn read_entries(path: &Path) -> Result<Vec<BatchEntry>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read input file {}", path.display()))?;
+    let value: serde_json::Value = serde_json::from_str(&contents)
+        .with_context(|| format!("failed to parse input file {}", path.display()))?;
+    schema::validate(&schema::input_schema(), &value).with_context(|| {
+        format!(
+            "input file {} does not match the v{BATCH_INPUT_VERSION} batch input schema",
+            path.display()
+        )
+    })?;
+    let serde_json::Value::Array(values) = value else {
+        unreachable!("the schema admits only an array");
+    };
+    let mut entries = Vec::with_capacity(values.len());
+    for (index, value) in values.into_iter().enumerate() {
+        // The schema allows some values its types do not, like decimal amounts past 2^256.
+        let entry: BatchEntry = serde_json::from_value(value)
+            .with_context(|| format!("invalid entry at /{index} in {}", path.display()))?;
+        entry
+            .validate()
+            .with_context(|| format!("invalid entry {index} in {}", path.display()))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Per-stage concurrency limits for [run_batch].
+#[derive(Clone, Copy, Debug)]
+pub struct Concurrency {
+    /// Entries being proven at once. With redundant markets each entry has one request open on
+    /// every market.
+    pub proofs: NonZeroUsize,
+    /// `beforeTrade` calls in flight at once.
+    pub settlements: NonZeroUsize,
+}
+
+/// How [run_batch] requests proofs and where it reports their decisions.
+#[derive(Clone, Debug, Default)]
+pub struct BatchOptions {
+    /// Submit requests through the offchain order stream instead of onchain.
+    pub offchain: bool,
+    /// Cache to look entries up in and add them to, as by [prove_entry].
+    pub cache: Option<Arc<ProofCache>>,
+    /// Webhook every settled decision is reported to; see [WebhookSink::report].
+    pub webhook: Option<Arc<WebhookSink>>,
+    /// Where settled and failed entries are alerted.
+    pub notifications: Option<Arc<Notifications>>,
+    /// Settle no entry unless every entry is proven and allowed; see [AllOrNothing].
+    pub all_or_nothing: bool,
+}
+
+/// A proven entry, ready to settle.
+#[derive(Clone, Debug)]
+pub struct Proof {
+    pub request_id: U256,
+    pub journal: Bytes,
+    pub seal: Bytes,
+}
+
+/// Request a proof for `entry` from `markets` and check the journal it comes back with.
+///
+/// `request` must carry `entry.stdin(decision_at)` as its stdin. With more than one market the
+/// request is raced across all of them; see [race_compliance]. With a `cache`, a proof of the
+/// entry made by the current guest is returned without a request, and a new proof is stored.
+pub async fn prove_entry<M: MarketClient>(
+    markets: &[M],
+    entry: &BatchEntry,
+    decision_at: u64,
+    request: RequestParams,
+    offchain: bool,
+    cache: Option<&ProofCache>,
+) -> Result<Proof> {
+    let image_id = compliance_image_id();
+    if let Some(cache) = cache {
+        if let Some(proof) = cache.get(entry, image_id)? {
+            ensure_image_id(&proof.journal)?;
+            tracing::info!(
+                "Using cached proof of request {:x} for user {}",
+                proof.request_id,
+                entry.user
+            );
+            return Ok(proof);
+        }
+    }
+
+    let expected = entry.expected_journal(decision_at);
+    if expected.needsReview {
+        tracing::warn!(
+            "Compliance policy holds user {} for product {} for manual review; the hook will \
+             revert",
+            entry.user,
+            entry.product_id
+        );
+    } else if !expected.allowed {
+        tracing::warn!(
+            "Compliance policy rejects user {} for product {}; the hook will revert",
+            entry.user,
+            entry.product_id
+        );
+    }
+
+    let (_, fulfillment) = race_compliance(markets, request, offchain).await?;
+    let journal = match fulfillment_journal(&fulfillment)? {
+        Some(journal) => journal,
+        None => expected.encode(entry.journal_encoding()),
+    };
+    ensure_image_id(&journal)?;
+    let proof = Proof {
+        request_id: fulfillment.id,
+        journal: journal.into(),
+        seal: fulfillment.seal,
+    };
+    if let Some(cache) = cache {
+        // The proof is paid for either way; failing to cache it must not fail the trade.
+        if let Err(err) = cache.insert(entry, image_id, &proof) {
+            tracing::warn!(
+                "Failed to cache proof of request {:x}: {err:#}",
+                proof.request_id
+            );
+        }
+    }
+    Ok(proof)
+}
+
+/// Pipeline stage an entry failed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Prove,
+    Settle,
+}
+
+/// Result of one entry.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Outcome {
+    Settled {
+        request_id: U256,
+        settlement: Settlement,
+    },
+    Failed {
+        stage: Stage,
+        error: String,
+    },
+}
+
+/// Per-entry line of the batch summary, in input order.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryReport {
+    pub index: usize,
+    pub user: Address,
+    pub product_id: B256,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+    /// What the [report](crate::report) of the run records beyond the summary.
+    #[serde(skip)]
+    pub audit: EntryAudit,
+}
+
+/// Facts of an entry's run that the JSON summary leaves out.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntryAudit {
+    /// Request the entry was proven by, or was submitted as when its id was precomputed.
+    pub request_id: Option<U256>,
+    /// Journal the entry was proven with, if it got that far.
+    pub journal: Option<Bytes>,
+    /// When the entry settled or failed, in Unix seconds.
+    pub finished_at: u64,
+}
+
+impl EntryReport {
+    pub fn is_settled(&self) -> bool {
+        matches!(self.outcome, Outcome::Settled { .. })
+    }
+}
+
+/// Prove and settle every entry, bounding each stage by its own [Concurrency] limit.
+///
+/// Every entry is decided at `decision_at`, which each request's stdin must match. A failed entry
+/// is reported and does not stop the others. Reports are returned in input order.
+pub async fn run_batch<M, S>(
+    markets: Arc<[M]>,
+    settler: Arc<S>,
+    entries: Vec<(BatchEntry, RequestParams)>,
+    decision_at: u64,
+    limits: Concurrency,
+    options: BatchOptions,
+) -> Vec<EntryReport>
+where
+    M: MarketClient + 'static,
+    S: Settler + 'static,
+{
+    let proofs = Arc::new(Semaphore::new(limits.proofs.get()));
+    let settlements = Arc::new(Semaphore::new(limits.settlements.get()));
+
+    let gate = options
+        .all_or_nothing
+        .then(|| Arc::new(AllOrNothing::new(entries.len())));
+
+    let mut tasks = JoinSet::new();
+    let mut reports: Vec<Option<EntryReport>> = vec![None; entries.len()];
+    let mut submitted = Vec::with_capacity(entries.len());
+
+    for (index, (entry, request)) in entries.into_iter().enumerate() {
+        let markets = markets.clone();
+        let settler = settler.clone();
+        let proofs = proofs.clone();
+        let settlements = settlements.clone();
+        let gate = gate.clone();
+        let BatchOptions {
+            offchain,
+            cache,
+            webhook,
+            notifications,
+            ..
+        } = options.clone();
+        submitted.push(entry.clone());
+        // Log lines of concurrent entries interleave, so each carries the entry it belongs to.
+        let span = tracing::info_span!("entry", index, user = %entry.user);
+
+        tasks.spawn(
+            async move {
+                let mut audit = EntryAudit {
+                    request_id: request.request_id.map(U256::from),
+                    ..EntryAudit::default()
+                };
+                let proof = {
+                    let _permit = proofs.acquire().await.expect("semaphore is never closed");
+                    catch_panic(prove_entry(
+                        &markets,
+                        &entry,
+                        decision_at,
+                        request,
+                        offchain,
+                        cache.as_deref(),
+                    ))
+                    .await
+                };
+                let proof = match proof {
+                    Ok(proof) => proof,
+                    Err(err) => {
+                        if let Some(gate) = &gate {
+                            gate.record(index, Some("failed to prove"));
+                        }
+                        if let Some(notifications) = &notifications {
+                            notifications
+                                .notify(Alert::proof_failed(audit.request_id, &err))
+                                .await;
+                        }
+                        audit.finished_at = unix_now();
+                        return (index, failed(Stage::Prove, err), audit);
+                    }
+                };
+                audit.request_id = Some(proof.request_id);
+                audit.journal = Some(proof.journal.clone());
+
+                if let Some(gate) = &gate {
+                    let blocker = match ComplianceJournal::try_from(proof.journal.as_ref()) {
+                        Ok(journal) if journal.allowed => None,
+                        Ok(_) => Some("is not allowed"),
+                        Err(_) => Some("has a journal that does not decode"),
+                    };
+                    gate.record(index, blocker);
+                    if let Some(reason) = gate.wait().await {
+                        audit.finished_at = unix_now();
+                        return (index, failed(Stage::Settle, anyhow!(reason)), audit);
+                    }
+                }
+
+                let _permit = settlements
+                    .acquire()
+                    .await
+                    .expect("semaphore is never closed");
+                let journal = proof.journal.clone();
+                let settled = catch_panic(settler.settle(&entry, proof.journal, proof.seal)).await;
+                let outcome = match settled {
+                    Ok(settlement) => {
+                        if let Some(webhook) = &webhook {
+                            webhook
+                                .report(proof.request_id, &journal, &settlement)
+                                .await;
+                        }
+                        if let Some(notifications) = &notifications {
+                            notifications
+                                .notify(Alert::settled(proof.request_id, &settlement))
+                                .await;
+                        }
+                        Outcome::Settled {
+                            request_id: proof.request_id,
+                            settlement,
+                        }
+                    }
+                    Err(err) => {
+                        if let Some(notifications) = &notifications {
+                            notifications
+                                .notify(Alert::settlement_failed(proof.request_id, &err))
+                                .await;
+                        }
+                        failed(Stage::Settle, err)
+                    }
+                };
+                audit.finished_at = unix_now();
+                (index, outcome, audit)
+            }
+            .instrument(span),
+        );
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        let (index, outcome, audit) = match joined {
+            Ok(joined) => joined,
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        };
+        let entry = &submitted[index];
+        reports[index] = Some(EntryReport {
+            index,
+            user: entry.user,
+            product_id: entry.product_id,
+            outcome,
+            audit,
+        });
+    }
+
+    reports
+        .into_iter()
+        .map(|report| report.expect("every entry task is joined"))
+        .collect()
+}
+
+/// Holds the proven entries of an all-or-nothing batch until every entry has been proven or has
+/// failed to, then tells each whether it may settle. One entry that failed or was not allowed
+/// keeps the whole batch from settling, as a group account whose members must all pass.
+struct AllOrNothing {
+    verdicts: watch::Sender<Verdicts>,
+}
+
+#[derive(Debug)]
+struct Verdicts {
+    /// Entries not yet proven.
+    pending: usize,
+    /// The lowest entry that keeps the batch from settling, and why.
+    blocker: Option<(usize, &'static str)>,
+}
+
+impl AllOrNothing {
+    fn new(entries: usize) -> Self {
+        Self {
+            verdicts: watch::Sender::new(Verdicts {
+                pending: entries,
+                blocker: None,
+            }),
+        }
+    }
+
+    /// Record that entry `index` is done proving, with why it keeps the batch from settling, if
+    /// it does.
+    fn record(&self, index: usize, blocker: Option<&'static str>) {
+        self.verdicts.send_modify(|verdicts| {
+            verdicts.pending -= 1;
+            if let Some(why) = blocker {
+                if verdicts.blocker.is_none_or(|(first, _)| index < first) {
+                    verdicts.blocker = Some((index, why));
+                }
+            }
+        });
+    }
+
+    /// Wait until every entry is recorded, then return why the batch may not settle, if it may
+    /// not.
+    async fn wait(&self) -> Option<String> {
+        let mut verdicts = self.verdicts.subscribe();
+        let verdicts = verdicts
+            .wait_for(|verdicts| verdicts.pending == 0)
+            .await
+            .expect("the sender outlives every entry");
+        verdicts.blocker.map(|(index, why)| {
+            format!("not settled: the batch is all-or-nothing and entry {index} {why}")
+        })
+    }
+}
+
+/// Run a stage, turning a panic into an error so the entry fails instead of the batch.
+async fn catch_panic<T>(stage: impl Future<Output = Result<T>>) -> Result<T> {
+    AssertUnwindSafe(stage)
+        .catch_unwind()
+        .await
+        .unwrap_or_else(|panic| Err(anyhow!("panicked: {}", panic_message(&*panic))))
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = panic.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = panic.downcast_ref::<String>() {
+        msg
+    } else {
+        "unknown panic payload"
+    }
+}
+
+fn failed(stage: Stage, err: anyhow::Error) -> Outcome {
+    tracing::warn!("Entry failed to {stage:?}: {err:#}");
+    Outcome::Failed {
+        stage,
+        error: format!("{err:#}"),
+    }
+}
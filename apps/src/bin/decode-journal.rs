@@ -0,0 +1,58 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decode a compliance journal in either encoding and print the decision it records. The
+//! journal's schema hash picks the layout it is decoded with.
+//!
+//! ```text
+//! cargo run -p app --bin decode-journal -- --file apps/tests/fixtures/golden/allowed.journal.bin
+//! cargo run -p app --bin decode-journal -- 0x0000…
+//! ```
+
+use std::path::PathBuf;
+
+use alloy::primitives::Bytes;
+use anyhow::{Context, Result};
+use app::journal::{explain, JournalEncoding, JournalSchema};
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Journal as hex.
+    #[clap(required_unless_present = "file", conflicts_with = "file")]
+    journal: Option<Bytes>,
+    /// File holding the raw journal bytes, e.g. a `.journal.bin` fixture.
+    #[clap(long)]
+    file: Option<PathBuf>,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let bytes = match (args.journal, &args.file) {
+        (Some(journal), _) => journal.to_vec(),
+        (None, Some(file)) => std::fs::read(file)
+            .with_context(|| format!("failed to read journal {}", file.display()))?,
+        (None, None) => unreachable!("clap requires a journal or --file"),
+    };
+    let encoding = JournalEncoding::of(&bytes).context("invalid journal")?;
+    let schema = JournalSchema::of(&bytes)?;
+    let journal = (schema.decode)(&bytes).context("invalid journal")?;
+
+    println!("encoding:    {} ({} bytes)", encoding.name(), bytes.len());
+    println!("schema:      v{} ({})", schema.version, schema.hash);
+    print!("{}", explain(&journal));
+    Ok(())
+}
@@ -0,0 +1,61 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Print the ABI-encoded guest input of every trade in an input file, one hex line per trade.
+//!
+//! ```text
+//! cargo run -p app --bin encode-input -- --input-file trades.json --decision-at 1700000000
+//! ```
+//!
+//! The trades are in the format of the app's `--input-file`. Nothing is read from the chain or
+//! the storage provider, so no RPC endpoint or key is needed.
+
+use std::path::PathBuf;
+
+use alloy::{hex, sol_types::SolValue};
+use anyhow::Result;
+use app::{batch::read_entries, journal::unix_now};
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// JSON file of trades.
+    #[clap(long)]
+    input_file: PathBuf,
+    /// Unix time in seconds the decisions are made at. Defaults to now.
+    #[clap(long)]
+    decision_at: Option<u64>,
+    /// Encode every trade for a compact journal, as the app's `--compact-journal` does.
+    #[clap(long)]
+    compact_journal: bool,
+    /// Encode every trade for SHA-256 commitments, as the app's `--sha256-commitments` does.
+    #[clap(long)]
+    sha256_commitments: bool,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let decision_at = args.decision_at.unwrap_or_else(unix_now);
+    for mut entry in read_entries(&args.input_file)? {
+        entry.compact_journal |= args.compact_journal;
+        entry.sha256_commitments |= args.sha256_commitments;
+        println!(
+            "{}",
+            hex::encode_prefixed(entry.input(decision_at).abi_encode())
+        );
+    }
+    Ok(())
+}
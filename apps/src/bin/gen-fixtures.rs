@@ -0,0 +1,72 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generate journal and seal fixtures for the hook contract and frontend tests.
+//!
+//! ```text
+//! cargo run -p app --bin gen-fixtures -- \
+//!   --spec apps/tests/fixtures/golden/spec.json --out apps/tests/fixtures/golden
+//! ```
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use app::{
+    executor::ExecutorOptions,
+    fixtures::{write_fixtures, Fixture, FixtureSpec},
+};
+use clap::Parser;
+use serde_json::json;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// JSON file with a `cases` array of named trades.
+    #[clap(long)]
+    spec: PathBuf,
+    /// Directory to write the fixtures and manifest to.
+    #[clap(long)]
+    out: PathBuf,
+    #[clap(flatten)]
+    executor: ExecutorOptions,
+}
+
+fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+    let args = Args::parse();
+
+    let spec = FixtureSpec::read(&args.spec)?;
+    let (fixtures, executions): (Vec<_>, Vec<_>) = spec
+        .cases
+        .into_iter()
+        .map(|case| Fixture::execute(case, &args.executor))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+    write_fixtures(&args.out, &fixtures)?;
+    let report: Vec<_> = fixtures
+        .iter()
+        .zip(&executions)
+        .map(|(fixture, execution)| json!({ "name": fixture.case.name, "execution": execution }))
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    tracing::info!(
+        "Wrote {} fixtures to {}",
+        fixtures.len(),
+        args.out.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,35 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Print the image id of the embedded compliance guest, which journals must commit and the hook
+//! is deployed with.
+//!
+//! ```text
+//! cargo run -p app --bin image-id
+//! ```
+
+use anyhow::Result;
+use app::journal::compliance_image_id;
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {}
+
+fn main() -> Result<()> {
+    Args::parse();
+
+    println!("{}", compliance_image_id());
+    Ok(())
+}
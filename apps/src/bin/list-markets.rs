@@ -0,0 +1,86 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! List the Boundless Market deployments the CLI can use without passing addresses.
+//!
+//! ```text
+//! cargo run -p app --bin list-markets
+//! cargo run -p app --bin list-markets -- --rpc-url https://ethereum-sepolia-rpc.publicnode.com
+//! ```
+//!
+//! With `--rpc-url` only the deployment for that RPC's chain is listed, and every contract is
+//! checked for code there. No private key is needed.
+
+use alloy::providers::{Provider, ProviderBuilder};
+use anyhow::{bail, Context, Result};
+use app::market::{deployment_contracts, describe_deployment, have_code, known_deployments};
+use boundless_market::{deployments::NamedChain, Deployment};
+use clap::Parser;
+use url::Url;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// URL of an Ethereum RPC endpoint. Lists the deployment for its chain and checks that each
+    /// contract has code there.
+    #[clap(short, long)]
+    rpc_url: Option<Url>,
+    /// Only list the deployment for this chain, e.g. `sepolia`, `base` or `base-sepolia`.
+    #[clap(long, conflicts_with = "rpc_url")]
+    chain: Option<NamedChain>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let Some(rpc_url) = args.rpc_url else {
+        let deployments: Vec<_> = known_deployments()
+            .into_iter()
+            .filter(|(chain, _)| args.chain.is_none_or(|wanted| wanted == *chain))
+            .collect();
+        if deployments.is_empty() {
+            bail!("no known Boundless deployment on {}", args.chain.unwrap());
+        }
+        let listing: Vec<_> = deployments
+            .iter()
+            .map(|(chain, deployment)| describe_deployment(chain.as_str(), deployment, None))
+            .collect();
+        print!("{}", listing.join("\n"));
+        return Ok(());
+    };
+
+    let provider = ProviderBuilder::new().connect_http(rpc_url);
+    let chain_id = provider
+        .get_chain_id()
+        .await
+        .context("failed to query chain id")?;
+    let Some(deployment) = Deployment::from_chain_id(chain_id) else {
+        bail!(
+            "no known Boundless deployment on chain {chain_id}; pass \
+             --boundless-market-address and --set-verifier-address instead"
+        );
+    };
+    let addresses: Vec<_> = deployment_contracts(&deployment)
+        .into_iter()
+        .map(|(_, address)| address)
+        .collect();
+    let deployed = have_code(&provider, &addresses).await?;
+    let chain = NamedChain::try_from(chain_id).expect("known deployments are on named chains");
+    print!(
+        "{}",
+        describe_deployment(chain.as_str(), &deployment, Some(&deployed))
+    );
+    Ok(())
+}
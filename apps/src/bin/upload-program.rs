@@ -0,0 +1,54 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Upload the compliance guest program and print its URL, for use with `--program-url`.
+//!
+//! ```text
+//! cargo run -p app --bin upload-program -- --program-index ./program-index
+//! ```
+//!
+//! The upload is recorded in the index directory by image id, and skipped when the storage
+//! provider already holds the same program. The app does the same under `--program-index`.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use app::program::{upload_program, IndexedStorage};
+use boundless_market::{StandardStorageProvider, StorageProviderConfig};
+use clap::Parser;
+use guests::COMPLIANCE_ELF;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Directory recording uploads of the guest program by image id.
+    #[clap(long, env)]
+    program_index: PathBuf,
+    #[clap(flatten, next_help_heading = "Storage Provider")]
+    storage_config: StorageProviderConfig,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+    let args = Args::parse();
+
+    let provider = StandardStorageProvider::from_config(&args.storage_config)
+        .context("failed to build storage provider")?;
+    let storage = IndexedStorage::new(provider, args.program_index);
+    println!("{}", upload_program(&storage, COMPLIANCE_ELF).await?);
+    Ok(())
+}
@@ -0,0 +1,173 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deployed addresses read from Foundry broadcast files.
+//!
+//! `forge script --broadcast` records every transaction of a run in
+//! `broadcast/<script>/<chain id>/run-latest.json`. `--broadcast-file` takes such a file, or the
+//! `broadcast` directory to find the files of the RPC's chain in, and the hook address is the one
+//! [HOOK_CONTRACT] was deployed at. Copying it by hand into `COMPLIANCE_HOOK_ADDRESS` is how a
+//! stale address ends up being settled against.
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use alloy::{primitives::Address, providers::Provider};
+use anyhow::{bail, ensure, Context, Result};
+use serde::Deserialize;
+
+/// Name of the hook contract in the broadcast files of its deployment.
+pub const HOOK_CONTRACT: &str = "ComplianceHook";
+
+/// The parts of a broadcast file deployments are found with.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Broadcast {
+    transactions: Vec<Transaction>,
+    /// Chain id the script ran on.
+    chain: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Transaction {
+    transaction_type: String,
+    #[serde(default)]
+    contract_name: Option<String>,
+    #[serde(default)]
+    contract_address: Option<Address>,
+}
+
+/// A contract deployment recorded in a broadcast file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Deployed {
+    pub address: Address,
+    /// Broadcast file that records it.
+    pub file: PathBuf,
+}
+
+/// The one deployment of `contract` on chain `chain_id` that `path` records. `path` is a
+/// broadcast file, or a directory whose `<script>/<chain_id>/run-latest.json` files are all
+/// searched. No deployment is an error, and so are several at different addresses, which are
+/// listed so the operator can pick one.
+pub fn find_deployment(path: &Path, chain_id: u64, contract: &str) -> Result<Deployed> {
+    let files = if path.is_dir() {
+        broadcast_files(path, chain_id)?
+    } else {
+        vec![path.to_owned()]
+    };
+
+    let mut found: Vec<Deployed> = Vec::new();
+    for file in files {
+        let broadcast: Broadcast = serde_json::from_slice(
+            &std::fs::read(&file)
+                .with_context(|| format!("failed to read broadcast file {}", file.display()))?,
+        )
+        .with_context(|| format!("invalid broadcast file {}", file.display()))?;
+        ensure!(
+            broadcast.chain == chain_id,
+            "broadcast file {} is for chain {}, but the RPC endpoint serves chain {chain_id}",
+            file.display(),
+            broadcast.chain
+        );
+        for tx in broadcast.transactions {
+            let created = matches!(tx.transaction_type.as_str(), "CREATE" | "CREATE2");
+            if !created || tx.contract_name.as_deref() != Some(contract) {
+                continue;
+            }
+            let address = tx.contract_address.with_context(|| {
+                format!(
+                    "broadcast file {} deploys {contract} without an address",
+                    file.display()
+                )
+            })?;
+            if found.iter().all(|deployed| deployed.address != address) {
+                found.push(Deployed {
+                    address,
+                    file: file.clone(),
+                });
+            }
+        }
+    }
+
+    match found.len() {
+        0 => bail!("{} records no deployment of {contract}", path.display()),
+        1 => Ok(found.remove(0)),
+        _ => {
+            let mut candidates = String::new();
+            for deployed in &found {
+                write!(
+                    candidates,
+                    "\n  {} ({})",
+                    deployed.address,
+                    deployed.file.display()
+                )
+                .unwrap();
+            }
+            bail!(
+                "{} records {} deployments of {contract}; pass the broadcast file of the one to \
+                 use:{candidates}",
+                path.display(),
+                found.len()
+            )
+        }
+    }
+}
+
+/// The `run-latest.json` of every script under the broadcast directory `dir` that ran on
+/// `chain_id`, in name order.
+fn broadcast_files(dir: &Path, chain_id: u64) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for script in std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read broadcast directory {}", dir.display()))?
+    {
+        let file = script?
+            .path()
+            .join(chain_id.to_string())
+            .join("run-latest.json");
+        if file.is_file() {
+            files.push(file);
+        }
+    }
+    ensure!(
+        !files.is_empty(),
+        "broadcast directory {} has no run of chain {chain_id}",
+        dir.display()
+    );
+    files.sort();
+    Ok(files)
+}
+
+/// The address of the hook that `path` records on the chain `provider` serves, checked to have
+/// code there.
+pub async fn deployed_hook<P: Provider>(provider: &P, path: &Path) -> Result<Deployed> {
+    let chain_id = provider
+        .get_chain_id()
+        .await
+        .context("failed to query chain ID")?;
+    let deployed = find_deployment(path, chain_id, HOOK_CONTRACT)?;
+    let code = provider
+        .get_code_at(deployed.address)
+        .await
+        .with_context(|| format!("failed to query the code of {}", deployed.address))?;
+    ensure!(
+        !code.is_empty(),
+        "{HOOK_CONTRACT} {} from {} has no code on chain {chain_id}",
+        deployed.address,
+        deployed.file.display()
+    );
+    Ok(deployed)
+}
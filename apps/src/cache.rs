@@ -0,0 +1,196 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! On-disk cache of proven trades, so a rerun does not pay the market again for a decision it
+//! already holds a proof of.
+//!
+//! An entry is keyed by the trade's guest input without its decision time and image id, so any
+//! change to the compliance facts, the product's requirements or the journal encoding misses the
+//! cache. So does another amount, since the hook only settles the amount a proof commits. A cached
+//! proof keeps the decision time and decision UUID it was made with: a trade without a UUID of its
+//! own takes the cached one from [ProofCache::assign_decision_uuid], and one that brings another
+//! UUID misses.
+//!
+//! Each proof is stored with the image id of the guest that made it. A proof from another image
+//! is never served: with `reprove_on_image_change` the trade is proven again and the entry
+//! replaced, otherwise the lookup fails so an upgrade cannot go unnoticed. With a maximum age, a
+//! proof decided longer ago than that is proven again too.
+
+use std::path::{Path, PathBuf};
+
+use alloy::{
+    primitives::{keccak256, Bytes, B256, U256},
+    sol_types::SolValue,
+};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    batch::{BatchEntry, Proof},
+    journal::{unix_now, ComplianceInput, ComplianceJournal},
+};
+
+/// A proof as stored in the cache.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedProof {
+    image_id: B256,
+    request_id: U256,
+    journal: Bytes,
+    seal: Bytes,
+}
+
+/// A directory of proofs, one `<key>.json` file per trade.
+#[derive(Clone, Debug)]
+pub struct ProofCache {
+    dir: PathBuf,
+    reprove_on_image_change: bool,
+    max_age_secs: Option<u64>,
+}
+
+impl ProofCache {
+    pub fn new(dir: impl Into<PathBuf>, reprove_on_image_change: bool) -> Self {
+        Self {
+            dir: dir.into(),
+            reprove_on_image_change,
+            max_age_secs: None,
+        }
+    }
+
+    /// Prove a trade again when its cached proof was decided more than `max_age_secs` ago, by the
+    /// `decisionAt` its journal commits, instead of settling it.
+    pub fn with_max_age(mut self, max_age_secs: Option<u64>) -> Self {
+        self.max_age_secs = max_age_secs;
+        self
+    }
+
+    /// Cache key of `entry`: the `keccak256` of its ABI-encoded input with the decision time,
+    /// decision UUID and image id cleared. [Self::get] checks the UUID against the cached journal.
+    pub fn key(entry: &BatchEntry) -> B256 {
+        let input = ComplianceInput {
+            decisionUuid: B256::ZERO,
+            imageId: B256::ZERO,
+            ..entry.input(0)
+        };
+        keccak256(input.abi_encode())
+    }
+
+    fn path(&self, entry: &BatchEntry) -> PathBuf {
+        self.dir.join(format!("{:x}.json", Self::key(entry)))
+    }
+
+    /// The cached proof of `entry` made by `image_id`, if there is one.
+    ///
+    /// A proof made by another image is a miss with `reprove_on_image_change` and an error
+    /// without it. A proof of another decision UUID than the one `entry` has, if any, or one older
+    /// than the maximum age is a miss.
+    pub fn get(&self, entry: &BatchEntry, image_id: B256) -> Result<Option<Proof>> {
+        let path = self.path(entry);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to read cached proof {}", path.display()))
+            }
+        };
+        let cached: CachedProof = serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse cached proof {}", path.display()))?;
+
+        if cached.image_id != image_id {
+            if !self.reprove_on_image_change {
+                bail!(
+                    "cached proof {} was made by image {}, not the current {image_id}; pass \
+                     --reprove-on-image-change or clear the cache",
+                    path.display(),
+                    cached.image_id
+                );
+            }
+            tracing::info!(
+                "Ignoring cached proof {} from image {}; proving again",
+                path.display(),
+                cached.image_id
+            );
+            return Ok(None);
+        }
+        let journal = ComplianceJournal::try_from(cached.journal.as_ref())
+            .with_context(|| format!("invalid cached proof {}", path.display()))?;
+        if !entry.decision_uuid.is_zero() && entry.decision_uuid != journal.decisionUuid {
+            tracing::info!(
+                "Ignoring cached proof {} of decision {}; proving decision {} again",
+                path.display(),
+                journal.decisionUuid,
+                entry.decision_uuid
+            );
+            return Ok(None);
+        }
+        if let Some(max_age_secs) = self.max_age_secs {
+            let age = unix_now().saturating_sub(journal.decisionAt);
+            if age > max_age_secs {
+                tracing::info!(
+                    "Ignoring cached proof {} decided {age}s ago, past the {max_age_secs}s \
+                     limit; proving again",
+                    path.display()
+                );
+                return Ok(None);
+            }
+        }
+        Ok(Some(Proof {
+            request_id: cached.request_id,
+            journal: cached.journal,
+            seal: cached.seal,
+        }))
+    }
+
+    /// Give `entry` the decision UUID of its cached proof by `image_id` if it has none, or a new
+    /// one if there is no such proof, so the UUID reported for the trade is the one it settles.
+    pub fn assign_decision_uuid(&self, entry: &mut BatchEntry, image_id: B256) -> Result<B256> {
+        if entry.decision_uuid.is_zero() {
+            if let Some(proof) = self.get(entry, image_id)? {
+                entry.decision_uuid = decision_uuid(&proof.journal)?;
+            }
+        }
+        Ok(entry.ensure_decision_uuid())
+    }
+
+    /// Store `proof` of `entry` as made by `image_id`, replacing any earlier entry.
+    pub fn insert(&self, entry: &BatchEntry, image_id: B256, proof: &Proof) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create proof cache {}", self.dir.display()))?;
+        let cached = CachedProof {
+            image_id,
+            request_id: proof.request_id,
+            journal: proof.journal.clone(),
+            seal: proof.seal.clone(),
+        };
+        let path = self.path(entry);
+        write_atomically(&path, &serde_json::to_vec_pretty(&cached)?)
+            .with_context(|| format!("failed to write cached proof {}", path.display()))
+    }
+}
+
+fn decision_uuid(journal: &[u8]) -> Result<B256> {
+    Ok(ComplianceJournal::try_from(journal)
+        .context("invalid journal")?
+        .decisionUuid)
+}
+
+/// Write through a temporary file next to `path` so a crash never leaves a truncated file
+/// behind.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
+}
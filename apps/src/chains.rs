@@ -0,0 +1,231 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deployments of the hook on several chains, kept in one TOML file.
+//!
+//! Each `[chains.<name>]` table of a [ChainsConfig] holds what a run on that chain needs: its RPC
+//! endpoint and chain id, the hook and verifier addresses, Boundless Market overrides and explorer
+//! URLs. `--chain <name>` fills the arguments a run leaves unset from the table, and checks that
+//! the RPC endpoint serves the chain id the table names before anything is proven.
+
+use std::{collections::BTreeMap, fmt::Write, path::Path, str::FromStr};
+
+use alloy::{
+    primitives::Address,
+    providers::{Provider, ProviderBuilder},
+    signers::local::PrivateKeySigner,
+};
+use anyhow::{ensure, Context, Result};
+use boundless_market::Deployment;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{cli::Args, ens::NameOrAddress};
+
+/// Chains the hook is deployed on, by name, as read from a TOML file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainsConfig {
+    #[serde(default)]
+    pub chains: BTreeMap<String, ChainConfig>,
+}
+
+/// One deployment of the hook.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainConfig {
+    pub rpc_url: Url,
+    /// Chain id the RPC endpoint must serve.
+    pub chain_id: u64,
+    pub hook_address: Address,
+    /// RISC Zero verifier the hook checks seals with, checked for code by `chains list`. Decisions
+    /// relayed to a chain without one carry the operator's signed result instead of the seal.
+    #[serde(default)]
+    pub verifier_address: Option<Address>,
+    /// `IComplianceReceiver` that `--relay-to` delivers decisions to on this chain.
+    #[serde(default)]
+    pub receiver_address: Option<Address>,
+    /// Environment variable holding the key to send transactions on this chain from, instead of
+    /// `--private-key`. Keys are not kept in the file itself.
+    #[serde(default)]
+    pub private_key_env: Option<String>,
+    /// Fee caps of transactions sent on this chain, in wei. Left to the RPC endpoint's estimate
+    /// when unset.
+    #[serde(default)]
+    pub max_fee_per_gas: Option<u128>,
+    #[serde(default)]
+    pub max_priority_fee_per_gas: Option<u128>,
+    /// Boundless Market deployment to use instead of the SDK's default for the chain. Set both
+    /// addresses or neither.
+    #[serde(default)]
+    pub boundless_market_address: Option<Address>,
+    #[serde(default)]
+    pub set_verifier_address: Option<Address>,
+    #[serde(default)]
+    pub order_stream_url: Option<Url>,
+    /// Block explorer settlement transactions are linked on, as `--explorer-url`.
+    #[serde(default)]
+    pub explorer_url: Option<Url>,
+    /// Explorer requests are linked on, as `--request-explorer-url`.
+    #[serde(default)]
+    pub request_explorer_url: Option<Url>,
+}
+
+impl FromStr for ChainsConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let config: Self = toml::from_str(s).context("invalid chains config")?;
+        for (name, chain) in &config.chains {
+            ensure!(
+                chain.boundless_market_address.is_some() == chain.set_verifier_address.is_some(),
+                "chain {name} sets only one of boundless_market_address and set_verifier_address"
+            );
+        }
+        Ok(config)
+    }
+}
+
+impl ChainsConfig {
+    /// Read the config from the TOML file at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read chains config {}", path.display()))?
+            .parse()
+            .with_context(|| format!("failed to load chains config {}", path.display()))
+    }
+
+    /// The chain called `name`.
+    pub fn chain(&self, name: &str) -> Result<&ChainConfig> {
+        self.chains.get(name).with_context(|| {
+            let names: Vec<&str> = self.chains.keys().map(String::as_str).collect();
+            format!(
+                "chain {name} is not in the chains config; it has {}",
+                match names.as_slice() {
+                    [] => "none".to_owned(),
+                    names => names.join(", "),
+                }
+            )
+        })
+    }
+
+    /// One line per chain: its name, chain id and hook address, and whether the hook and the
+    /// verifier have code there. A chain whose RPC endpoint fails or serves another chain id says
+    /// so instead of failing the listing.
+    pub async fn list(&self) -> String {
+        let mut out = String::new();
+        for (name, chain) in &self.chains {
+            let status = match chain.check().await {
+                Ok(status) => status,
+                Err(err) => format!("error: {err:#}"),
+            };
+            writeln!(
+                out,
+                "{name}\tchain {}\thook {}\t{status}",
+                chain.chain_id, chain.hook_address
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+impl ChainConfig {
+    /// The Boundless Market deployment the chain overrides, if any.
+    pub fn deployment(&self) -> Result<Option<Deployment>> {
+        let (Some(market), Some(set_verifier)) =
+            (self.boundless_market_address, self.set_verifier_address)
+        else {
+            return Ok(None);
+        };
+        let mut builder = Deployment::builder();
+        builder
+            .boundless_market_address(market)
+            .set_verifier_address(set_verifier);
+        if let Some(url) = &self.order_stream_url {
+            builder.order_stream_url(url.to_string());
+        }
+        Ok(Some(builder.build()?))
+    }
+
+    /// Fill the arguments `args` leaves unset from this chain, so flags and their environment
+    /// variables still take precedence.
+    pub fn apply_to(&self, args: &mut Args) -> Result<()> {
+        args.rpc_url.get_or_insert_with(|| self.rpc_url.clone());
+        args.compliance_hook_address
+            .get_or_insert(NameOrAddress::Address(self.hook_address));
+        if args.deployment.is_none() {
+            args.deployment = self.deployment()?;
+        }
+        if args.explorer_url.is_none() {
+            args.explorer_url = self.explorer_url.clone();
+        }
+        if args.request_explorer_url.is_none() {
+            args.request_explorer_url = self.request_explorer_url.clone();
+        }
+        Ok(())
+    }
+
+    /// The key to send transactions on this chain from: the one in [ChainConfig::private_key_env],
+    /// or `fallback`.
+    pub fn signer(&self, fallback: Option<&PrivateKeySigner>) -> Result<PrivateKeySigner> {
+        match &self.private_key_env {
+            Some(var) => std::env::var(var)
+                .with_context(|| format!("{var} is not set"))?
+                .parse()
+                .with_context(|| format!("{var} is not a private key")),
+            None => fallback
+                .cloned()
+                .context("the chain sets no private_key_env and --private-key is not given"),
+        }
+    }
+
+    /// Check that `provider` serves this chain's chain id.
+    pub async fn ensure_chain_id<P: Provider>(&self, provider: &P) -> Result<()> {
+        let chain_id = provider
+            .get_chain_id()
+            .await
+            .context("failed to query chain ID")?;
+        ensure!(
+            chain_id == self.chain_id,
+            "the RPC endpoint serves chain {chain_id}, but the chains config expects {}",
+            self.chain_id
+        );
+        Ok(())
+    }
+
+    /// Whether the RPC endpoint serves the chain and the hook and the verifier have code on it.
+    async fn check(&self) -> Result<String> {
+        let provider = ProviderBuilder::new().connect_http(self.rpc_url.clone());
+        self.ensure_chain_id(&provider).await?;
+        let mut status = vec![code_status(&provider, "hook", self.hook_address).await?];
+        if let Some(verifier) = self.verifier_address {
+            status.push(code_status(&provider, "verifier", verifier).await?);
+        }
+        Ok(status.join(", "))
+    }
+}
+
+/// `<what> deployed` when `address` has code, `<what> has no code` otherwise.
+async fn code_status<P: Provider>(provider: &P, what: &str, address: Address) -> Result<String> {
+    let code = provider
+        .get_code_at(address)
+        .await
+        .with_context(|| format!("failed to query the code of {what} {address}"))?;
+    Ok(if code.is_empty() {
+        format!("{what} has no code")
+    } else {
+        format!("{what} deployed")
+    })
+}
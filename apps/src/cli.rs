@@ -0,0 +1,1022 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fmt::Write, num::NonZeroUsize, path::PathBuf, str::FromStr, time::Duration};
+
+use alloy::{
+    primitives::{Address, Bytes, Signature, B256, U256},
+    signers::local::PrivateKeySigner,
+};
+use anyhow::{bail, ensure, Context};
+use boundless_market::{Deployment, StorageProviderConfig};
+use clap::{Parser, Subcommand, ValueEnum};
+use reqwest::header::{HeaderName, HeaderValue};
+use url::Url;
+
+use crate::{
+    credential::CredentialLocation,
+    ens::{NameOrAddress, ENS_REGISTRY},
+    executor::ExecutorOptions,
+    notify::Severity,
+    pool::{parse_pool_key, PoolKey},
+};
+
+/// Arguments of the compliance trading CLI.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None, subcommand_negates_reqs = true)]
+pub struct Args {
+    /// Something to do instead of proving and settling trades.
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+    /// Trade amount for the RWA asset.
+    #[clap(short, long, env, required_unless_present = "input_file")]
+    pub amount: Option<u32>,
+    /// URL of the Ethereum RPC endpoint.
+    #[clap(short, long, env, required_unless_present_any = ["dry_run", "compare_guest", "chain"])]
+    pub rpc_url: Option<Url>,
+    /// TOML file of the chains the hook is deployed on, as `[chains.<name>]` tables.
+    #[clap(long, env)]
+    pub chains_config: Option<PathBuf>,
+    /// Chain of `--chains-config` to run on. Its RPC URL, hook address, Boundless Market
+    /// deployment and explorer URLs fill in the flags that are not given, and the RPC endpoint
+    /// must serve its chain id.
+    #[clap(long, env, requires = "chains_config")]
+    pub chain: Option<String>,
+    /// Chain of `--chains-config` to relay each settled decision to, at its `receiver_address`,
+    /// from its RPC endpoint, key and fee caps.
+    #[clap(long, env, requires = "chains_config")]
+    pub relay_to: Option<String>,
+    /// Directory to record relayed decisions and their transactions in, for `relay retry`.
+    #[clap(long, env, default_value = "relay-store")]
+    pub relay_store: PathBuf,
+    /// Private key used to interact with the compliance hook contract and the Boundless Market.
+    #[clap(
+        long,
+        env,
+        hide_env_values = true,
+        required_unless_present_any = ["dry_run", "compare_guest", "estimate_proof_cost"]
+    )]
+    pub private_key: Option<PrivateKeySigner>,
+    /// Address or ENS name of the compliance hook contract.
+    #[clap(
+        short,
+        long,
+        env,
+        required_unless_present_any = [
+            "dry_run",
+            "compare_guest",
+            "estimate_proof_cost",
+            "attest_only",
+            "chain",
+            "broadcast_file"
+        ]
+    )]
+    pub compliance_hook_address: Option<NameOrAddress>,
+    /// Foundry broadcast file to take the hook address from, or a `broadcast` directory to search
+    /// for the runs on the RPC's chain. The hook must have been deployed once in them, and have
+    /// code on the chain.
+    #[clap(long, env)]
+    pub broadcast_file: Option<PathBuf>,
+    /// Address or ENS name of the trader being checked.
+    // NOTE: `USER` is the login name in most shells, and dotenvy never overrides variables that
+    // are already set, so the env fallback needs a name of its own.
+    #[clap(
+        long,
+        env = "USER_ADDRESS",
+        required_unless_present = "input_file",
+        value_parser = parse_user
+    )]
+    pub user: Option<NameOrAddress>,
+    /// Identifier of the RWA product being traded.
+    #[clap(long, env, required_unless_present_any = ["input_file", "pool"])]
+    pub product_id: Option<B256>,
+    /// Uniswap v4 pool being traded, as `<CURRENCY0>,<CURRENCY1>,<FEE>,<TICK_SPACING>,<HOOKS>`.
+    /// Its `PoolId` is printed and used as the product id.
+    #[clap(
+        long,
+        env,
+        value_name = "POOL_KEY",
+        value_parser = parse_pool_key,
+        conflicts_with_all = ["product_id", "input_file"]
+    )]
+    pub pool: Option<PoolKey>,
+    /// Whether the user passed KYC. Counts as KYC tier 1 when `--kyc-tier` is not given.
+    #[clap(long, env)]
+    pub kyc_passed: bool,
+    /// KYC tier of the user: 0 none, 1 basic, 2 intermediate, 3 full.
+    #[clap(long, env)]
+    pub kyc_tier: Option<u8>,
+    /// Minimum KYC tier the product requires.
+    #[clap(long, env, default_value_t = 1)]
+    pub required_tier: u8,
+    /// Id of the KYC vendor whose attestation the user's tier and claims come from, committed
+    /// with the decision. Zero names none.
+    #[clap(long, env, default_value_t = 0)]
+    pub kyc_provider: u16,
+    /// KYC providers the product accepts, comma-separated, at most four. The guest denies a trade
+    /// whose `--kyc-provider` is not among them; without them any provider is accepted.
+    #[clap(long, env, value_delimiter = ',')]
+    pub allowed_kyc_providers: Vec<u16>,
+    /// Whether the user consented to the processing of their data, committed with the decision.
+    #[clap(long, env)]
+    pub consent_given: bool,
+    /// Unix time the user's consent was recorded at, committed with the decision.
+    #[clap(long, env, default_value_t = 0)]
+    pub consent_at: u64,
+    /// The product does not require consent. Without it the guest denies the trade unless
+    /// `--consent-given` was recorded at a `--consent-at` no later than the decision.
+    #[clap(long, env)]
+    pub consent_optional: bool,
+    /// Whether the user passed AML screening.
+    #[clap(long, env)]
+    pub aml_passed: bool,
+    /// Bitmap of the AML list sources the user was screened against: 1 sanctions, 2 politically
+    /// exposed persons, 4 adverse media.
+    #[clap(long, env, default_value = "0")]
+    pub aml_sources: U256,
+    /// AML list sources the product requires; the trade needs all of them in `--aml-sources`.
+    #[clap(long, env, default_value = "0")]
+    pub required_aml_sources: U256,
+    /// Bitmap of the KYC questionnaire items the user has answered, e.g. `0b101` or `5`.
+    #[clap(long, env, default_value = "0")]
+    pub claims: U256,
+    /// Questionnaire items the product requires; the trade needs all of them in `--claims`.
+    #[clap(long, env, default_value = "0")]
+    pub required_claims: U256,
+    /// Attesters the product requires to vouch for the user; the trade is denied with fewer.
+    #[clap(long, env, default_value_t = 0)]
+    pub required_attestations: u8,
+    /// Version of the product's eligibility policy that `--required-tier` and `--required-claims`
+    /// come from. The hook only accepts decisions made under the product's current version.
+    #[clap(long, env, default_value_t = 0)]
+    pub product_policy_version: u32,
+    /// Quote product when trading a pair, with `--product-id` as the base. The user must meet the
+    /// requirements of both.
+    #[clap(long, env)]
+    pub quote_product_id: Option<B256>,
+    /// Minimum KYC tier the quote product requires.
+    #[clap(long, env, default_value_t = 0, requires = "quote_product_id")]
+    pub quote_required_tier: u8,
+    /// Questionnaire items the quote product requires.
+    #[clap(long, env, default_value = "0", requires = "quote_product_id")]
+    pub quote_required_claims: U256,
+    /// Version of the quote product's eligibility policy.
+    #[clap(long, env, default_value_t = 0, requires = "quote_product_id")]
+    pub quote_policy_version: u32,
+    /// Risk score the screening provider gave the user, higher meaning riskier.
+    #[clap(long, env, default_value_t = 0)]
+    pub risk_score: u8,
+    /// Lowest risk score for which the product holds the trade for manual review instead of
+    /// allowing it.
+    #[clap(long, env, requires = "review_band_high")]
+    pub review_band_low: Option<u8>,
+    /// Risk score from which the review band no longer applies; scores in
+    /// `[--review-band-low, --review-band-high)` need review.
+    #[clap(long, env, requires = "review_band_low")]
+    pub review_band_high: Option<u8>,
+    /// Regulatory regime of the product's jurisdiction: 0 for none, 1 for MiCA, 2 for SEC, 3 for
+    /// MAS. The regime's own questionnaire item is required on top of the product's claims.
+    #[clap(long, env, default_value_t = 0)]
+    pub regime_id: u16,
+    /// Token the trade settles in. The hook rejects it unless the product allows it, or the
+    /// product has no allowed settlement tokens configured.
+    #[clap(long, env, default_value_t = Address::ZERO)]
+    pub settlement_token: Address,
+    /// Id of the off-chain case-management record the decision belongs to. The guest commits it
+    /// as is, so a disputed decision can be traced to its case without revealing anything of it.
+    #[clap(long, env, default_value_t = B256::ZERO)]
+    pub case_id: B256,
+    /// Smallest trade the product accepts. The guest denies a smaller `--amount`.
+    #[clap(long, env, default_value = "0")]
+    pub min_trade: U256,
+    /// Largest trade the product accepts. The guest denies a larger `--amount`; without it there
+    /// is no limit.
+    #[clap(long, env)]
+    pub max_trade: Option<U256>,
+    /// Seconds after the decision the hook may keep accepting it without a new proof, when it
+    /// allows the trade. The hook caps it at the product's maximum, which is zero unless set.
+    #[clap(long, env, default_value_t = 0)]
+    pub ttl_secs: u64,
+    /// Seconds after the decision before which the hook must not settle it. The guest commits the
+    /// time, and the CLI waits until then to call `beforeTrade`.
+    #[clap(long, env, default_value_t = 0)]
+    pub settle_delay_secs: u64,
+    /// Identifier the guest commits for the decision, which the hook settles at most once. A
+    /// random one is drawn without it; pass the one printed by an earlier run to retry its
+    /// settlement without settling it twice.
+    #[clap(long, env, conflicts_with = "input_file")]
+    pub decision_uuid: Option<B256>,
+    /// Hash of the block the onchain state behind the trade was read at, committed with the
+    /// decision. Without it the attester's EIP-1271 check reads the latest block, whose hash is
+    /// committed instead.
+    #[clap(long, env, conflicts_with = "input_file")]
+    pub block_hash: Option<B256>,
+    /// Contract wallet (EIP-1271), by address or ENS name, that attested the user's compliance
+    /// fields.
+    #[clap(long, env, requires = "attestation_signature")]
+    pub attester: Option<NameOrAddress>,
+    /// The attester's signature over the attestation hash, checked with `isValidSignature`.
+    #[clap(long, env, requires = "attester")]
+    pub attestation_signature: Option<Bytes>,
+    /// Governance keys that may override the compliance decision, comma-separated. The hook only
+    /// honours an override when these match the governance set it was configured with.
+    #[clap(long, env, value_delimiter = ',', requires = "override_signature")]
+    pub governance: Vec<Address>,
+    /// A governance key's signature over the override hash of the trade, which allows it even
+    /// when the user fails the product's rules.
+    #[clap(long, env, requires = "governance")]
+    pub override_signature: Option<Signature>,
+    /// Have the guest commit a tightly packed journal instead of the ABI-encoded one, which is
+    /// cheaper to pass to and hash in the hook. Applies to every trade of an input file.
+    #[clap(long, env)]
+    pub compact_journal: bool,
+    /// Have the guest hash the journal's commitments, such as the attestations hash, with SHA-256
+    /// instead of keccak, for a verifier that checks them with SHA-256. Applies to every trade of an
+    /// input file.
+    #[clap(long, env)]
+    pub sha256_commitments: bool,
+    /// URL of an uploaded copy of the guest program, used instead of uploading the embedded one.
+    /// It is fetched first, where it can be, and must have the embedded guest's image id.
+    #[clap(long, env)]
+    pub program_url: Option<Url>,
+    /// Which program to request proofs of when `--program-url` is set, since the embedded guest
+    /// is always available too. Without it `--program-url` is used, with a warning.
+    #[clap(long, value_enum, requires = "program_url")]
+    pub prefer: Option<ProgramSource>,
+    /// Directory recording uploads of the guest program by image id. The embedded program is
+    /// only uploaded when the storage provider does not already hold it, instead of on every run.
+    #[clap(long, env, conflicts_with = "program_url")]
+    pub program_index: Option<PathBuf>,
+    /// Image id of a program already recorded in `--program-index`, requested by its URL without
+    /// uploading anything. The embedded program is used instead when the index does not hold it.
+    #[clap(long, env, requires = "program_index")]
+    pub program_image_id: Option<B256>,
+    /// Allow `--program-image-id` or `--program-url` to name a program other than the embedded
+    /// guest. Its journals must still commit the embedded guest's image id to be settled.
+    #[clap(long)]
+    pub allow_image_id_mismatch: bool,
+    /// Submit the request through the offchain order stream instead of onchain.
+    #[clap(short, long, requires = "order_stream_url")]
+    pub offchain: bool,
+    /// URL of a meta-transaction relayer that submits `beforeTrade` so the signer pays no gas.
+    #[clap(long, env = "RELAYER_URL")]
+    pub relayer: Option<Url>,
+    /// Sponsor API key sent to the relayer.
+    #[clap(long, env, hide_env_values = true, requires = "relayer")]
+    pub relayer_api_key: Option<String>,
+    /// URL to POST each settled decision to as JSON: the request id, user, product, whether the
+    /// trade is allowed, the denial reasons and the transaction hash. Failed deliveries are
+    /// retried, and a settled trade stays settled when they all fail.
+    #[clap(long, env)]
+    pub webhook: Option<Url>,
+    /// Secret the webhook bodies are signed with, sent as an HMAC-SHA256 in `X-Signature-256`.
+    #[clap(long, env, hide_env_values = true, requires = "webhook")]
+    pub webhook_secret: Option<String>,
+    /// Base URL of the KYC provider's REST API. The user's KYC tier, AML result and claims are
+    /// fetched from `GET <url>/<user>` instead of being taken from the arguments or input file.
+    #[clap(long, env)]
+    pub input_from_api: Option<Url>,
+    /// Bearer token sent to the KYC provider.
+    #[clap(long, env, hide_env_values = true, requires = "input_from_api")]
+    pub api_token: Option<String>,
+    /// Extra header sent to the KYC provider as `<NAME>: <VALUE>`, e.g. `X-Api-Key: ...`.
+    #[clap(long, value_name = "HEADER", requires = "input_from_api")]
+    pub api_header: Vec<ApiHeader>,
+    /// W3C Verifiable Credential of each user's compliance fields, a JWT-VC signed with ES256K,
+    /// read from a file or URL in which `{user}` stands for the user's address. The issuer's
+    /// signature, the validity period and any status list are checked, and the guest commits the
+    /// credential's hash.
+    #[clap(
+        long,
+        env,
+        value_name = "FILE_OR_URL",
+        conflicts_with = "input_from_api"
+    )]
+    pub input_from_credential: Option<CredentialLocation>,
+    /// TOML file describing an AML screening API, such as Chainalysis's or TRM's. Each user's AML
+    /// result and risk score are taken from their screening instead of the other sources.
+    #[clap(long, env)]
+    pub screening_config: Option<PathBuf>,
+    /// API key sent to the screening API in the header its config names.
+    #[clap(long, env, hide_env_values = true, requires = "screening_config")]
+    pub screening_api_key: Option<String>,
+    /// JSON file of trades to prove and settle in one run, instead of the single-trade arguments.
+    #[clap(long, env)]
+    pub input_file: Option<PathBuf>,
+    /// Batch mode: maximum number of proof requests in flight on the market at once.
+    #[clap(long, env, default_value = "4")]
+    pub max_concurrent_proofs: NonZeroUsize,
+    /// Batch mode: maximum number of `beforeTrade` calls in flight at once.
+    ///
+    /// Defaults to one so transactions from a single signer go out in nonce order.
+    #[clap(long, env, default_value = "1")]
+    pub max_concurrent_settlements: NonZeroUsize,
+    /// Batch mode: settle no trade unless every trade of the batch is proven and allowed, as for a
+    /// group account whose members must all pass.
+    #[clap(long, env, requires = "input_file")]
+    pub all_or_nothing: bool,
+    /// Directory to keep proven trades in. A trade already proven with the same compliance facts
+    /// is settled with its cached proof instead of being requested again.
+    #[clap(long, env)]
+    pub proof_cache: Option<PathBuf>,
+    /// Prove a trade again when its cached proof was made by a different guest image, instead of
+    /// failing. Proofs from another image are never settled.
+    #[clap(long, env, requires = "proof_cache")]
+    pub reprove_on_image_change: bool,
+    /// Batch mode: file to write an audit report of the run to, one row per trade, once every
+    /// trade has settled or failed. CSV, or Parquet when the name ends in `.parquet` and the CLI
+    /// is built with its `parquet` feature.
+    #[clap(long, env, requires = "input_file")]
+    pub report_out: Option<PathBuf>,
+    /// Check that the signer can pay for settlement before requesting any proof.
+    #[clap(long, env)]
+    pub check_balance: bool,
+    /// Balance in wei the signer must hold, instead of the estimated settlement gas.
+    ///
+    /// The estimate does not include the proof payment; set this when the signer also funds the
+    /// Boundless request.
+    #[clap(long, env, requires = "check_balance")]
+    pub min_balance_wei: Option<U256>,
+    /// After `beforeTrade` confirms, read the hook's `TradeAllowed` event back and fail the run if
+    /// it does not match the submitted trade and journal.
+    #[clap(long, env, conflicts_with = "relayer")]
+    pub verify_after_settle: bool,
+    /// Refuse to settle a trade decided more than this many seconds ago, judged by the
+    /// `decisionAt` its journal commits. `settle-bundle` fails on such a trade, and a proof that
+    /// old in `--proof-cache` is proven again.
+    #[clap(long, env)]
+    pub max_journal_age_secs: Option<u64>,
+    /// Send `beforeTrade` without first checking that the hook's verifier takes the seal: a
+    /// verifier with a `SELECTOR` must match the seal's, and a router must have a verifier for it.
+    /// Pass it for a verifier that is neither.
+    #[clap(long, env)]
+    pub skip_seal_check: bool,
+    /// After each journal is proven, also attest its decision with the Ethereum Attestation
+    /// Service, to the user, and record the attestation's UID in `--eas-store`.
+    #[clap(
+        long,
+        env,
+        requires_all = ["eas_address", "eas_schema_uid"],
+        help_heading = "EAS Attestation"
+    )]
+    pub attest_eas: bool,
+    /// Attest decisions instead of calling `beforeTrade`, so no hook is needed.
+    #[clap(long, env, requires = "attest_eas", help_heading = "EAS Attestation")]
+    pub attest_only: bool,
+    /// Address of the EAS contract.
+    #[clap(long, env, help_heading = "EAS Attestation")]
+    pub eas_address: Option<Address>,
+    /// UID of the registered, revocable schema
+    /// `address user,bytes32 productId,bool allowed,bytes32 journalDigest,uint64 validUntil`.
+    #[clap(long, env, help_heading = "EAS Attestation")]
+    pub eas_schema_uid: Option<B256>,
+    /// How long after its decision time an attestation stays valid, e.g. `30days`. Attestations
+    /// are valid until revoked without it.
+    #[clap(long, env, value_parser = humantime::parse_duration, help_heading = "EAS Attestation")]
+    pub eas_valid_for: Option<Duration>,
+    /// Directory recording the attestations made, one JSON file per UID.
+    #[clap(
+        long,
+        env,
+        default_value = "eas-attestations",
+        help_heading = "EAS Attestation"
+    )]
+    pub eas_store: PathBuf,
+    /// Slack incoming webhook to send alerts to: expired requests, failed proofs and
+    /// settlements, and an unreachable attestation source.
+    #[clap(long, env, hide_env_values = true, help_heading = "Notifications")]
+    pub slack_webhook_url: Option<Url>,
+    /// Least severe alert sent to Slack.
+    #[clap(
+        long,
+        env,
+        value_enum,
+        default_value = "warning",
+        help_heading = "Notifications"
+    )]
+    pub slack_min_severity: Severity,
+    /// Routing key of a PagerDuty Events API v2 integration to trigger incidents with.
+    #[clap(long, env, hide_env_values = true, help_heading = "Notifications")]
+    pub pagerduty_routing_key: Option<String>,
+    /// Least severe alert sent to PagerDuty.
+    #[clap(
+        long,
+        env,
+        value_enum,
+        default_value = "critical",
+        help_heading = "Notifications"
+    )]
+    pub pagerduty_min_severity: Severity,
+    /// Also post alerts of at least this severity to `--webhook`, next to the decisions.
+    #[clap(
+        long,
+        env,
+        value_enum,
+        requires = "webhook",
+        help_heading = "Notifications"
+    )]
+    pub webhook_min_severity: Option<Severity>,
+    /// Block explorer alerts link settlement transactions on, as `<url>/tx/<hash>`.
+    #[clap(long, env, help_heading = "Notifications")]
+    pub explorer_url: Option<Url>,
+    /// Explorer alerts link requests without a transaction on, as `<url>/<request id>`.
+    #[clap(long, env, help_heading = "Notifications")]
+    pub request_explorer_url: Option<Url>,
+    /// How long repeats of an alert for the same event and request are dropped for.
+    #[clap(
+        long,
+        env,
+        value_parser = humantime::parse_duration,
+        default_value = "10m",
+        help_heading = "Notifications"
+    )]
+    pub alert_dedup_window: Duration,
+    /// After each trade settles, pin its proof bundle, the journal and seal, and the guest program
+    /// to IPFS, and record their CIDs in `--ipfs-store` and the output.
+    #[clap(long, env, requires = "ipfs_api", help_heading = "IPFS")]
+    pub pin_ipfs: bool,
+    /// Kubo RPC API to pin bundles with and fetch them from, such as `http://127.0.0.1:5001` or a
+    /// pinning service's.
+    #[clap(long, env, help_heading = "IPFS")]
+    pub ipfs_api: Option<Url>,
+    /// Token of the pinning service, sent as a bearer token.
+    #[clap(long, env, hide_env_values = true, help_heading = "IPFS")]
+    pub ipfs_api_token: Option<String>,
+    /// Fail a trade whose bundle cannot be pinned. The trade has settled by then; without this a
+    /// failed pin is only a warning.
+    #[clap(long, env, requires = "pin_ipfs", help_heading = "IPFS")]
+    pub require_pin: bool,
+    /// Directory recording the bundles pinned, one JSON file per CID.
+    #[clap(long, env, default_value = "ipfs-pins", help_heading = "IPFS")]
+    pub ipfs_store: PathBuf,
+    /// After each journal is proven, sign its result as EIP-712 typed data with `--private-key`,
+    /// for partners that trust the operator instead of verifying the seal. The signature is in
+    /// the output, the webhook payload and the pinned bundle.
+    #[clap(long, env, help_heading = "Signed Results")]
+    pub sign_results: bool,
+    /// Name of the EIP-712 domain results are signed under.
+    #[clap(
+        long,
+        env,
+        default_value = "ComplianceHook",
+        help_heading = "Signed Results"
+    )]
+    pub result_domain_name: String,
+    /// Version of the EIP-712 domain results are signed under.
+    #[clap(long, env, default_value = "1", help_heading = "Signed Results")]
+    pub result_domain_version: String,
+    /// Chain id of the EIP-712 domain results are signed under. Defaults to that of `--rpc-url`.
+    #[clap(long, env, help_heading = "Signed Results")]
+    pub result_domain_chain_id: Option<u64>,
+    /// Verifying contract of the EIP-712 domain results are signed under. Defaults to the hook,
+    /// or the zero address without one.
+    #[clap(long, env, help_heading = "Signed Results")]
+    pub result_domain_verifying_contract: Option<Address>,
+    /// How long after its decision time a signed result stays valid, e.g. `1day`. Results are
+    /// valid for good without it.
+    #[clap(long, env, value_parser = humantime::parse_duration, help_heading = "Signed Results")]
+    pub result_valid_for: Option<Duration>,
+    /// Print the proven decision, including the date it was made, before settling.
+    #[clap(long, conflicts_with = "input_file")]
+    pub explain: bool,
+    /// Decimals of the traded token. Printed decisions and logs show amounts in whole units, e.g.
+    /// `1.5` rather than `1500000000000000000` at 18; `--amount` and input files stay in base
+    /// units.
+    #[clap(long, env, default_value_t = 0)]
+    pub decimals: u8,
+    /// Follow each address in printed decisions with its primary ENS name, looked up through
+    /// `--rpc-url`.
+    #[clap(long, env)]
+    pub ens_names: bool,
+    /// ENS registry that names are resolved with, for a chain that has it at another address.
+    #[clap(long, env, default_value_t = ENS_REGISTRY)]
+    pub ens_registry: Address,
+    /// Print the decision the guest would commit for each trade, evaluated on this machine, and
+    /// exit. Nothing is proven or settled, so no RPC endpoint, key or storage is needed.
+    /// Attester signatures are not checked.
+    #[clap(long, env)]
+    pub dry_run: bool,
+    /// Under `--dry-run`, also execute the guest on each trade and print the cycles of each part
+    /// of the decision. Needs the guest built with its `profile` feature: `--features profile`.
+    #[clap(long, env, requires = "dry_run")]
+    pub profile_cycles: bool,
+    /// Execute the guest on each trade on this machine, print the price range its proof would be
+    /// offered at on the market and exit. Only the RPC endpoint is read, for the gas price.
+    #[clap(long, env, conflicts_with = "dry_run")]
+    pub estimate_proof_cost: bool,
+    /// Guest ELF to migrate to. Execute it and the embedded guest on each trade on this machine,
+    /// report whether their decisions agree and exit, failing if any trade's do not. Nothing is
+    /// proven or settled.
+    #[clap(long, env, value_name = "ELF", conflicts_with_all = ["dry_run", "estimate_proof_cost"])]
+    pub compare_guest: Option<PathBuf>,
+    /// Prove on this machine instead of on the Boundless Market. With `RISC0_DEV_MODE=1` the
+    /// proofs are dev-mode receipts, which only a mock verifier accepts.
+    #[clap(
+        long,
+        env,
+        conflicts_with_all = ["offchain", "redundant_markets"],
+        help_heading = "Local Proving"
+    )]
+    pub local: bool,
+    /// Trades proven at once in local mode. Defaults to one per available core.
+    #[clap(long, env, requires = "local", help_heading = "Local Proving")]
+    pub prove_jobs: Option<NonZeroUsize>,
+    /// Prover memory in MiB that local proving may use. Fewer trades are proven at once when the
+    /// estimated memory of `--prove-jobs` segments would not fit.
+    #[clap(long, env, requires = "local", help_heading = "Local Proving")]
+    pub prove_memory_limit_mib: Option<u64>,
+    #[clap(flatten, next_help_heading = "Local Proving")]
+    pub executor: ExecutorOptions,
+    #[clap(flatten, next_help_heading = "Storage Provider")]
+    pub storage_config: StorageProviderConfig,
+
+    #[clap(flatten, next_help_heading = "Boundless Market Deployment")]
+    pub deployment: Option<Deployment>,
+
+    /// Additional deployments to race each request against, as
+    /// `<BOUNDLESS_MARKET_ADDRESS>,<SET_VERIFIER_ADDRESS>[,<ORDER_STREAM_URL>]`.
+    ///
+    /// The first market to fulfill wins and only its proof is settled. The other requests stay
+    /// open until they expire and may still be fulfilled and paid for.
+    #[clap(
+        long,
+        num_args = 1..,
+        value_name = "DEPLOYMENT",
+        help_heading = "Boundless Market Deployment"
+    )]
+    pub redundant_markets: Vec<MarketDeployment>,
+    /// Provers trusted to fulfill requests, comma-separated. A trade whose request another prover
+    /// fulfilled is not settled. Any prover is accepted by default.
+    #[clap(
+        long,
+        env,
+        value_delimiter = ',',
+        conflicts_with = "local",
+        help_heading = "Boundless Market Deployment"
+    )]
+    pub allowed_provers: Vec<Address>,
+}
+
+/// Source of the guest program that proofs are requested of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ProgramSource {
+    /// The `COMPLIANCE_ELF` built into the CLI.
+    Embedded,
+    /// The program at `--program-url`.
+    Url,
+}
+
+/// What the CLI does instead of proving and settling trades.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Print example invocations for submitting, dry-running, batching and proving locally, with
+    /// placeholders for the values.
+    Examples,
+    /// Print a `.env` template with every environment variable the CLI reads, what it sets and a
+    /// placeholder or its default.
+    DumpEnvExample {
+        /// File to write the template to instead of printing it.
+        #[clap(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Print the JSON Schema of `--input-file`, which input files are checked against.
+    Schema {
+        /// Print the schema of the JSON summary of a batch instead.
+        #[clap(long)]
+        output: bool,
+    },
+    /// Revoke an EAS attestation made under `--attest-eas`, and mark it revoked in `--eas-store`.
+    /// Needs `--rpc-url`, `--private-key` and `--eas-address`.
+    RevokeAttestation {
+        /// UID of the attestation.
+        uid: B256,
+    },
+    /// Print the allowed decision the hook has cached for a user and product, and how long it
+    /// remains valid. Needs `--rpc-url` and `--compliance-hook-address`.
+    Status {
+        /// Address or ENS name of the trader.
+        user: NameOrAddress,
+        /// Identifier of the product.
+        product_id: B256,
+    },
+    /// Work with the audit reports `--report-out` writes.
+    Report {
+        #[clap(subcommand)]
+        command: ReportCommand,
+    },
+    /// Build Merkle trees of address lists and prove addresses in or out of them.
+    Merkle {
+        #[clap(subcommand)]
+        command: MerkleCommand,
+    },
+    /// Work with the proof bundles `--pin-ipfs` pins. Needs `--ipfs-api`.
+    Bundle {
+        #[clap(subcommand)]
+        command: BundleCommand,
+    },
+    /// Check that a result signed under `--sign-results` was signed by an operator, and print its
+    /// domain. The file may hold the signed result, or a bundle or webhook payload carrying it.
+    VerifySignedResult {
+        /// JSON file holding the signed result.
+        file: PathBuf,
+        /// Address of the operator that must have signed it.
+        #[clap(long)]
+        operator: Address,
+    },
+    /// Prove the trades as usual, but write what settling them needs to a bundle file instead of
+    /// calling the hook, for another party to settle with `settle-bundle`. Needs
+    /// `--compliance-hook-address`.
+    ExportBundle {
+        /// File to write the bundle to. It is replaced, and rewritten as each trade is proven.
+        out: PathBuf,
+    },
+    /// Check a bundle written by `export-bundle` and send `beforeTrade` for each of its trades from
+    /// `--private-key`. Needs `--rpc-url`.
+    SettleBundle {
+        /// The bundle file.
+        file: PathBuf,
+    },
+    /// Prove the trades of a file as one batch with the batch guest on this machine, and send its
+    /// journal to the hook's `recordBatch` from `--private-key`. Needs `--rpc-url` and
+    /// `--compliance-hook-address`.
+    ///
+    /// The hook only records a batch whose every trade is allowed.
+    RecordBatch {
+        /// JSON file of the batch's trades, as `--input-file` takes it.
+        file: PathBuf,
+        /// Most the batch's allowed trades may add up to. The hook rejects a batch over its cap,
+        /// and a cap over the hook's `batchAggregateCap`, which is the default.
+        #[clap(long)]
+        aggregate_cap: Option<U256>,
+    },
+    /// Write proven trades as fixtures for the hook's Foundry tests: a JSON file and a Solidity
+    /// library of journals, seals and the decisions they record. The trades come from bundles
+    /// written by `export-bundle`, or from a fixture spec run through the guest with dev-mode
+    /// seals.
+    ExportForgeFixtures {
+        /// Bundles to take the trades of, named `<file stem>_<index>`.
+        #[clap(required_unless_present = "spec", conflicts_with = "spec")]
+        bundles: Vec<PathBuf>,
+        /// Fixture spec, as `gen-fixtures` takes, to execute instead. Nothing in the output then
+        /// depends on the time or the machine.
+        #[clap(long)]
+        spec: Option<PathBuf>,
+        /// Directory to write the fixtures to.
+        #[clap(long, default_value = "contracts/test/fixtures")]
+        out: PathBuf,
+    },
+    /// Work with the chains of `--chains-config`.
+    Chains {
+        #[clap(subcommand)]
+        command: ChainsCommand,
+    },
+    /// Work with the decisions `--relay-to` relayed, as recorded in `--relay-store`.
+    Relay {
+        #[clap(subcommand)]
+        command: RelayCommand,
+    },
+}
+
+/// What the `relay` subcommand does.
+#[derive(Subcommand, Debug)]
+pub enum RelayCommand {
+    /// Relay again every settled decision whose relay is not confirmed, each to the chain it was
+    /// meant for. Needs `--chains-config`.
+    Retry,
+}
+
+/// What the `chains` subcommand does.
+#[derive(Subcommand, Debug)]
+pub enum ChainsCommand {
+    /// Print every configured chain with its chain id and hook address, and whether its RPC
+    /// endpoint serves that chain and the hook and verifier have code there.
+    List,
+}
+
+/// What the `report` subcommand does.
+#[derive(Subcommand, Debug)]
+pub enum ReportCommand {
+    /// Combine reports into one with a row per request id, the row of the last report that has
+    /// it. Each report may be CSV or Parquet, by its extension.
+    Merge {
+        /// Reports to combine, oldest first.
+        #[clap(required = true)]
+        reports: Vec<PathBuf>,
+        /// File to write the combined report to, as CSV or Parquet by its extension.
+        #[clap(short, long)]
+        out: PathBuf,
+    },
+}
+
+/// What the `bundle` subcommand does.
+#[derive(Subcommand, Debug)]
+pub enum BundleCommand {
+    /// Download a pinned bundle and the program it names, check that the program has the
+    /// bundle's image id and that the journal commits it, and print the bundle.
+    Fetch {
+        /// CID of the bundle.
+        cid: String,
+        /// File to write the bundle to instead of printing it.
+        #[clap(short, long)]
+        out: Option<PathBuf>,
+    },
+}
+
+/// What the `merkle` subcommand does.
+#[derive(Subcommand, Debug)]
+pub enum MerkleCommand {
+    /// Build a tree of the addresses in a file, one per line, and write its root and every level
+    /// as JSON.
+    Build {
+        /// File of addresses, one per line; blank lines and `#` comments are skipped.
+        #[clap(long)]
+        input: PathBuf,
+        /// File to write the tree to.
+        #[clap(long)]
+        out: PathBuf,
+        /// Build a sorted tree of the gaps between the addresses, whose proofs show an address is
+        /// not in the list, instead of a tree of the addresses themselves.
+        #[clap(long)]
+        sorted: bool,
+    },
+    /// Print the proof of an address as JSON: that it is in a tree, or not in a sorted one.
+    Proof {
+        /// Tree written by `merkle build`.
+        #[clap(long)]
+        tree: PathBuf,
+        /// Address to prove.
+        #[clap(long)]
+        leaf: Address,
+    },
+    /// Print the proof, as JSON, that a paged batch allowed a user to trade a product, against
+    /// the `cohortRoot` its batch guest commits.
+    CohortProof {
+        /// JSON file of the batch's trades, as `--input-file` takes it.
+        #[clap(long)]
+        input_file: PathBuf,
+        /// Unix time in seconds the batch was decided at, as its inputs carry it.
+        #[clap(long)]
+        decision_at: u64,
+        /// User to prove.
+        #[clap(long)]
+        user: Address,
+        /// Product the user was allowed to trade.
+        #[clap(long)]
+        product_id: B256,
+    },
+    /// Check a proof printed by `merkle proof` or `merkle cohort-proof` against its root.
+    Verify {
+        /// File holding the proof.
+        #[clap(long)]
+        proof: PathBuf,
+        /// Tree written by `merkle build`, whose root the proof must also be for.
+        #[clap(long)]
+        tree: Option<PathBuf>,
+    },
+}
+
+/// Examples [examples] prints: what each does, and the ids of the [Args] it passes.
+pub const EXAMPLES: [(&str, &[&str]); 4] = [
+    (
+        "Prove a trade on the Boundless Market and settle it with the hook",
+        &[
+            "rpc_url",
+            "private_key",
+            "compliance_hook_address",
+            "amount",
+            "user",
+            "product_id",
+            "kyc_passed",
+            "aml_passed",
+            "consent_given",
+            "consent_at",
+        ],
+    ),
+    (
+        "Print the decision the guest would commit, without proving or settling anything",
+        &[
+            "dry_run",
+            "amount",
+            "user",
+            "product_id",
+            "kyc_passed",
+            "aml_passed",
+            "consent_given",
+            "consent_at",
+        ],
+    ),
+    (
+        "Prove and settle every trade of an input file, with a limit on the proofs in flight",
+        &[
+            "rpc_url",
+            "private_key",
+            "compliance_hook_address",
+            "input_file",
+            "max_concurrent_proofs",
+        ],
+    ),
+    (
+        "Prove a trade on this machine instead of on the market",
+        &[
+            "local",
+            "rpc_url",
+            "private_key",
+            "compliance_hook_address",
+            "amount",
+            "user",
+            "product_id",
+            "kyc_passed",
+            "aml_passed",
+            "consent_given",
+            "consent_at",
+        ],
+    ),
+];
+
+/// The [EXAMPLES] as invocations of `cmd`, each under a `#` comment. Every flag and value name
+/// comes from the argument definitions, so a renamed argument is renamed here too and a removed
+/// one panics instead of printing a stale example.
+pub fn examples(cmd: &clap::Command) -> String {
+    let bin = cmd.get_bin_name().unwrap_or(cmd.get_name());
+    let mut out = String::new();
+    for (index, (about, ids)) in EXAMPLES.iter().enumerate() {
+        if index > 0 {
+            writeln!(out).unwrap();
+        }
+        writeln!(out, "# {about}").unwrap();
+        write!(out, "{bin}").unwrap();
+        for id in *ids {
+            let arg = cmd
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .unwrap_or_else(|| panic!("example passes unknown argument `{id}`"));
+            let long = arg
+                .get_long()
+                .unwrap_or_else(|| panic!("example passes `{id}`, which has no long flag"));
+            write!(out, " \\\n    --{long}").unwrap();
+            if arg.get_action().takes_values() {
+                let name = arg
+                    .get_value_names()
+                    .and_then(|names| names.first())
+                    .map_or_else(|| id.to_uppercase(), ToString::to_string);
+                write!(out, " <{name}>").unwrap();
+            }
+        }
+        writeln!(out).unwrap();
+    }
+    out
+}
+
+/// Widest line of the comments [env_example] writes.
+const ENV_EXAMPLE_WIDTH: usize = 100;
+
+/// The `.env` template of `cmd`: every argument read from the environment, in the order `--help`
+/// lists them under a comment per help heading. Each variable is commented out, with its help
+/// as a comment above it and its default, or `true` for a flag, or its value name as the value.
+pub fn env_example(cmd: &clap::Command) -> String {
+    let bin = cmd.get_bin_name().unwrap_or(cmd.get_name());
+    let mut out = format!(
+        "# Environment variables {bin} reads, generated by `{bin} dump-env-example`.\n\
+         # Flags on the command line take precedence over them.\n"
+    );
+    let mut heading = None;
+    for arg in cmd.get_arguments() {
+        let Some(env) = arg.get_env() else {
+            continue;
+        };
+        if arg.get_help_heading() != heading {
+            heading = arg.get_help_heading();
+            if let Some(heading) = heading {
+                write!(out, "\n# --- {heading} ---\n").unwrap();
+            }
+        }
+        writeln!(out).unwrap();
+        let help = arg
+            .get_long_help()
+            .or(arg.get_help())
+            .map(ToString::to_string);
+        for line in wrap_comment(help.as_deref().unwrap_or_default()) {
+            writeln!(out, "# {line}").unwrap();
+        }
+        let value = if let Some(default) = arg.get_default_values().first() {
+            default.to_string_lossy().into_owned()
+        } else if arg.get_action().takes_values() {
+            let name = arg
+                .get_value_names()
+                .and_then(|names| names.first())
+                .map_or_else(|| arg.get_id().as_str().to_uppercase(), ToString::to_string);
+            format!("<{name}>")
+        } else {
+            "true".to_owned()
+        };
+        writeln!(out, "# {}={value}", env.to_string_lossy()).unwrap();
+    }
+    out
+}
+
+/// `text` broken into lines that fit [ENV_EXAMPLE_WIDTH] behind `# `, keeping its blank lines.
+fn wrap_comment(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split("\n\n") {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.len() + 1 + word.len() > ENV_EXAMPLE_WIDTH - 2 {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// An HTTP header given on the command line as `<NAME>: <VALUE>`.
+#[derive(Clone, Debug)]
+pub struct ApiHeader {
+    pub name: HeaderName,
+    pub value: HeaderValue,
+}
+
+impl FromStr for ApiHeader {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (name, value) = s.split_once(':').context("expected <NAME>: <VALUE>")?;
+        Ok(Self {
+            name: HeaderName::from_str(name.trim()).context("invalid header name")?,
+            value: HeaderValue::from_str(value.trim()).context("invalid header value")?,
+        })
+    }
+}
+
+/// Parse a trader address or ENS name. The zero address is refused: no trader holds it, so it can
+/// only be a missing or mistyped value.
+fn parse_user(s: &str) -> anyhow::Result<NameOrAddress> {
+    let user = NameOrAddress::from_str(s)?;
+    ensure!(
+        user != NameOrAddress::Address(Address::ZERO),
+        "the zero address is not a trader"
+    );
+    Ok(user)
+}
+
+/// A Boundless Market deployment given as a single command-line value.
+#[derive(Clone, Debug)]
+pub struct MarketDeployment(Deployment);
+
+impl MarketDeployment {
+    pub fn deployment(&self) -> &Deployment {
+        &self.0
+    }
+}
+
+impl FromStr for MarketDeployment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        let (market, set_verifier, order_stream_url) = match parts[..] {
+            [market, set_verifier] => (market, set_verifier, None),
+            [market, set_verifier, order_stream_url] => {
+                (market, set_verifier, Some(order_stream_url))
+            }
+            _ => bail!(
+                "expected <BOUNDLESS_MARKET_ADDRESS>,<SET_VERIFIER_ADDRESS>[,<ORDER_STREAM_URL>]"
+            ),
+        };
+
+        let mut builder = Deployment::builder();
+        builder
+            .boundless_market_address(
+                Address::from_str(market).context("invalid boundless market address")?,
+            )
+            .set_verifier_address(
+                Address::from_str(set_verifier).context("invalid set verifier address")?,
+            );
+        if let Some(url) = order_stream_url {
+            builder.order_stream_url(
+                Url::parse(url)
+                    .context("invalid order stream URL")?
+                    .to_string(),
+            );
+        }
+        Ok(Self(builder.build()?))
+    }
+}
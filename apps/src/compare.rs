@@ -0,0 +1,105 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Comparison of the decisions of two builds of the compliance guest, to check a new guest
+//! against the deployed one before migrating the hook to its image id.
+//!
+//! Both guests are executed on this machine on the same inputs. Each input carries the image id
+//! of the guest it is given to, so the journals always differ in `imageId`; only the decisions
+//! are compared.
+
+use alloy::primitives::B256;
+use anyhow::{Context, Result};
+use compliance_core::{ComplianceInput, ComplianceJournal};
+use risc0_zkvm::compute_image_id;
+
+use crate::{batch::BatchEntry, executor::ExecutorOptions, journal::explain};
+
+/// Decisions two guests committed for one trade.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuestComparison {
+    /// Journal of the current guest.
+    pub current: ComplianceJournal,
+    /// Journal of the candidate guest.
+    pub candidate: ComplianceJournal,
+}
+
+impl GuestComparison {
+    /// Whether both guests made the same decision: their journals are equal but for `imageId`.
+    pub fn agrees(&self) -> bool {
+        without_image_id(&self.current) == without_image_id(&self.candidate)
+    }
+
+    /// Lines of [explain] that differ between the two decisions: the current guest's prefixed
+    /// with `-`, the candidate's with `+`. Empty when they agree.
+    pub fn differences(&self) -> Vec<String> {
+        let current = explain(&without_image_id(&self.current));
+        let candidate = explain(&without_image_id(&self.candidate));
+        let removed = current
+            .lines()
+            .filter(|line| !candidate.lines().any(|other| other == *line))
+            .map(|line| format!("- {line}"));
+        let added = candidate
+            .lines()
+            .filter(|line| !current.lines().any(|other| other == *line))
+            .map(|line| format!("+ {line}"));
+        removed.chain(added).collect()
+    }
+}
+
+/// Image id of `elf`, as committed to the journal.
+pub fn image_id(elf: &[u8]) -> Result<B256> {
+    let image_id = compute_image_id(elf).context("failed to compute guest image id")?;
+    Ok(B256::from(<[u8; 32]>::from(image_id)))
+}
+
+/// Execute the `current` and `candidate` guests on each of `entries`, decided at `decision_at`,
+/// and decode both journals.
+pub fn compare_guests(
+    current: &[u8],
+    candidate: &[u8],
+    entries: &[BatchEntry],
+    decision_at: u64,
+    options: &ExecutorOptions,
+) -> Result<Vec<GuestComparison>> {
+    let current_id = image_id(current)?;
+    let candidate_id = image_id(candidate)?;
+    let mut input = Vec::with_capacity(ComplianceInput::LEN);
+    let mut decide = |elf: &[u8], image_id: B256, entry: &BatchEntry| {
+        let mut guest_input = entry.input(decision_at);
+        guest_input.imageId = image_id;
+        input.clear();
+        guest_input.encode_to(&mut input);
+        let execution = options.execute(elf, &input)?;
+        ComplianceJournal::try_from(execution.journal.as_slice()).context("invalid journal")
+    };
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let current = decide(current, current_id, entry)
+                .with_context(|| format!("current guest failed on trade {index}"))?;
+            let candidate = decide(candidate, candidate_id, entry)
+                .with_context(|| format!("candidate guest failed on trade {index}"))?;
+            Ok(GuestComparison { current, candidate })
+        })
+        .collect()
+}
+
+fn without_image_id(journal: &ComplianceJournal) -> ComplianceJournal {
+    ComplianceJournal {
+        imageId: B256::ZERO,
+        ..journal.clone()
+    }
+}
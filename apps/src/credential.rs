@@ -0,0 +1,695 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! W3C Verifiable Credentials as the source of a user's compliance fields, for identity providers
+//! that issue KYC results as signed credentials instead of serving them from an API.
+//!
+//! [CredentialSource] reads each user's credential, a JWT-VC signed with ES256K, from a file or
+//! URL. The signature is checked against the issuer's key, which [DidResolver] resolves from its
+//! `did:key` or `did:web` DID. The credential must be within its validity period and, when it
+//! names a status list, not revoked or suspended in it. The fields of its subject become the
+//! user's [ComplianceStatus], and the `keccak256` of the credential becomes the status's
+//! `credential_hash`, which the guest commits as `credentialHash`.
+
+use std::{io::Read, str::FromStr, time::UNIX_EPOCH};
+
+use alloy::primitives::{keccak256, Address, B256};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use base64::{
+    engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use flate2::read::GzDecoder;
+use k256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+use url::Url;
+
+use crate::{
+    attestation::{AttestationSource, ComplianceStatus},
+    http::RetryPolicy,
+    journal::{format_decision_at, unix_now},
+};
+
+/// Proofs [CredentialSource] verifies, as listed when a credential has another.
+pub const SUPPORTED_PROOFS: &str = "JWT-VC signed with ES256K";
+
+/// DID methods [DidResolver] resolves issuer keys of.
+pub const SUPPORTED_DID_METHODS: &str = "did:key, did:web";
+
+/// `credentialStatus` types checked against their status list.
+pub const SUPPORTED_STATUS_TYPES: &str = "StatusList2021Entry, BitstringStatusListEntry";
+
+/// Multicodec prefix of a compressed secp256k1 public key, as a `did:key` or a
+/// `publicKeyMultibase` holds it.
+const SECP256K1_PUB: [u8; 2] = [0xe7, 0x01];
+
+/// Where the users' credentials are read from: a file or an `http(s)` URL, in which `{user}` stands
+/// for the user's address. Without `{user}`, one credential is read for every trade, so they must
+/// all be of its subject.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CredentialLocation {
+    File(String),
+    Url(String),
+}
+
+impl CredentialLocation {
+    /// Placeholder replaced by the user's address.
+    pub const USER: &'static str = "{user}";
+
+    fn for_user(&self, user: Address) -> String {
+        match self {
+            Self::File(path) | Self::Url(path) => path.replace(Self::USER, &user.to_string()),
+        }
+    }
+}
+
+impl FromStr for CredentialLocation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if !(s.starts_with("http://") || s.starts_with("https://")) {
+            return Ok(Self::File(s.into()));
+        }
+        Url::parse(&s.replace(Self::USER, &Address::ZERO.to_string()))
+            .context("invalid credential URL")?;
+        Ok(Self::Url(s.into()))
+    }
+}
+
+/// A compact JWS, as a JWT-VC is serialized.
+struct Jws<'a> {
+    /// `<header>.<payload>`, which the signature is over.
+    signing_input: &'a str,
+    header: JwsHeader,
+    payload: Vec<u8>,
+    signature: Signature,
+}
+
+#[derive(Deserialize)]
+struct JwsHeader {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+impl<'a> Jws<'a> {
+    /// Split `token` into its parts, refusing any algorithm but ES256K.
+    fn parse(token: &'a str) -> Result<Self> {
+        let (signing_input, signature) = token
+            .rsplit_once('.')
+            .context("not a compact JWS of three parts")?;
+        let (header, payload) = signing_input
+            .split_once('.')
+            .context("not a compact JWS of three parts")?;
+        let header: JwsHeader =
+            serde_json::from_slice(&decode_base64url(header)?).context("invalid JWS header")?;
+        ensure!(
+            header.alg == "ES256K",
+            "unsupported JWS algorithm {}; supported: {SUPPORTED_PROOFS}",
+            header.alg
+        );
+        let signature = Signature::from_slice(&decode_base64url(signature)?)
+            .context("invalid ES256K signature")?;
+        // JWS allows either `s`, but k256 only verifies the low one.
+        let signature = signature.normalize_s().unwrap_or(signature);
+        Ok(Self {
+            signing_input,
+            header,
+            payload: decode_base64url(payload)?,
+            signature,
+        })
+    }
+
+    fn claims<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_slice(&self.payload).context("invalid JWT claims")
+    }
+}
+
+fn decode_base64url(encoded: &str) -> Result<Vec<u8>> {
+    URL_SAFE_NO_PAD
+        .decode(encoded.trim_end_matches('='))
+        .context("invalid base64url")
+}
+
+/// Claims of a JWT-VC that [CredentialSource] reads.
+#[derive(Deserialize)]
+struct CredentialClaims {
+    iss: String,
+    #[serde(default)]
+    sub: Option<String>,
+    #[serde(default)]
+    nbf: Option<u64>,
+    #[serde(default)]
+    exp: Option<u64>,
+    vc: Credential,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Credential {
+    #[serde(rename = "type")]
+    types: Vec<String>,
+    credential_subject: Subject,
+    /// One status entry or an array of them.
+    #[serde(default)]
+    credential_status: Option<Value>,
+    #[serde(default, alias = "validUntil")]
+    expiration_date: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Subject {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(flatten)]
+    status: ComplianceStatus,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CredentialStatus {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    status_purpose: Option<String>,
+    #[serde(default)]
+    status_list_index: Option<String>,
+    #[serde(default)]
+    status_list_credential: Option<Url>,
+}
+
+/// Claims of a status list credential.
+#[derive(Deserialize)]
+struct StatusListClaims {
+    iss: String,
+    vc: StatusListCredential,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StatusListCredential {
+    credential_subject: StatusListSubject,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StatusListSubject {
+    encoded_list: String,
+}
+
+/// Hash the guest commits of `credential`: the `keccak256` of the credential as serialized, without
+/// surrounding whitespace.
+pub fn credential_hash(credential: &str) -> B256 {
+    keccak256(credential.trim())
+}
+
+/// Whether bit `index` of a status list is set. The list is a GZIP-compressed bitstring in
+/// base64url, optionally with the multibase prefix `u`, whose first bit is the most significant
+/// bit of its first byte.
+pub fn status_list_bit(encoded_list: &str, index: usize) -> Result<bool> {
+    let encoded = encoded_list.strip_prefix('u').unwrap_or(encoded_list);
+    let compressed = decode_base64url(encoded)
+        .or_else(|_| STANDARD_NO_PAD.decode(encoded.trim_end_matches('=')))
+        .context("invalid encodedList")?;
+    let mut list = Vec::new();
+    GzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut list)
+        .context("encodedList is not GZIP-compressed")?;
+    let byte = list.get(index / 8).with_context(|| {
+        format!(
+            "status list index {index} is past the list's {} entries",
+            list.len() * 8
+        )
+    })?;
+    Ok(byte & (0x80 >> (index % 8)) != 0)
+}
+
+/// Address of an Ethereum account DID: `did:pkh:eip155:<chain>:<address>` or
+/// `did:ethr:[<network>:]<address>`.
+fn subject_address(did: &str) -> Result<Address> {
+    let address = did
+        .strip_prefix("did:pkh:eip155:")
+        .and_then(|rest| rest.split_once(':'))
+        .map(|(_, address)| address)
+        .or_else(|| {
+            did.strip_prefix("did:ethr:")
+                .map(|rest| rest.rsplit(':').next().unwrap_or(rest))
+        })
+        .with_context(|| {
+            format!("subject {did} is not an Ethereum account; supported: did:pkh:eip155, did:ethr")
+        })?;
+    address
+        .parse()
+        .with_context(|| format!("subject {did} has an invalid address"))
+}
+
+/// Unix time of an RFC 3339 date.
+fn unix_time(date: &str) -> Result<u64> {
+    let time =
+        humantime::parse_rfc3339_weak(date).with_context(|| format!("invalid date {date}"))?;
+    Ok(time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs()))
+}
+
+/// GET `url` as text, failing on any status but success.
+async fn fetch_text(http: &reqwest::Client, policy: &RetryPolicy, url: &Url) -> Result<String> {
+    let response = policy
+        .send(|| http.get(url.clone()))
+        .await
+        .with_context(|| format!("failed to fetch {url}"))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        bail!("{url} returned {status}: {body}");
+    }
+    response
+        .text()
+        .await
+        .with_context(|| format!("failed to read {url}"))
+}
+
+/// secp256k1 key of a `did:key` identifier or a `publicKeyMultibase`: base58btc, multibase prefix
+/// `z`, of the multicodec-prefixed compressed key.
+fn multibase_key(multibase: &str) -> Result<VerifyingKey> {
+    let encoded = multibase
+        .strip_prefix('z')
+        .with_context(|| format!("{multibase} is not base58btc multibase"))?;
+    let bytes = bs58::decode(encoded)
+        .into_vec()
+        .with_context(|| format!("{multibase} is not base58btc"))?;
+    let key = bytes.strip_prefix(&SECP256K1_PUB).with_context(|| {
+        format!("{multibase} is not a secp256k1 key; supported: {SUPPORTED_PROOFS}")
+    })?;
+    VerifyingKey::from_sec1_bytes(key).with_context(|| format!("invalid secp256k1 key {multibase}"))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidDocument {
+    id: String,
+    #[serde(default)]
+    verification_method: Vec<VerificationMethod>,
+    /// Ids of verification methods, or methods embedded in place.
+    #[serde(default)]
+    assertion_method: Vec<Value>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerificationMethod {
+    id: String,
+    #[serde(default)]
+    public_key_jwk: Option<Jwk>,
+    #[serde(default)]
+    public_key_multibase: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+struct Jwk {
+    kty: String,
+    crv: String,
+    x: String,
+    y: String,
+}
+
+impl VerificationMethod {
+    fn key(&self) -> Result<VerifyingKey> {
+        if let Some(jwk) = &self.public_key_jwk {
+            ensure!(
+                jwk.kty == "EC" && jwk.crv == "secp256k1",
+                "key {} is {} {}, not EC secp256k1; supported: {SUPPORTED_PROOFS}",
+                self.id,
+                jwk.kty,
+                jwk.crv
+            );
+            let mut point = vec![0x04];
+            point.extend(decode_base64url(&jwk.x)?);
+            point.extend(decode_base64url(&jwk.y)?);
+            return VerifyingKey::from_sec1_bytes(&point)
+                .with_context(|| format!("invalid secp256k1 key {}", self.id));
+        }
+        let multibase = self.public_key_multibase.as_deref().with_context(|| {
+            format!(
+                "key {} has neither publicKeyJwk nor publicKeyMultibase",
+                self.id
+            )
+        })?;
+        multibase_key(multibase)
+    }
+}
+
+impl DidDocument {
+    /// Verification methods the subject may issue credentials with, with absolute ids.
+    fn assertion_methods(&self) -> Result<Vec<VerificationMethod>> {
+        let absolute = |id: &str| match id.strip_prefix('#') {
+            Some(_) => format!("{}{id}", self.id),
+            None => id.to_string(),
+        };
+        self.assertion_method
+            .iter()
+            .map(|method| match method {
+                Value::String(id) => {
+                    let id = absolute(id);
+                    self.verification_method
+                        .iter()
+                        .find(|method| absolute(&method.id) == id)
+                        .map(|method| VerificationMethod {
+                            id: id.clone(),
+                            ..method.clone()
+                        })
+                        .with_context(|| format!("assertion method {id} is not in the document"))
+                }
+                embedded => {
+                    let method: VerificationMethod = serde_json::from_value(embedded.clone())
+                        .context("invalid assertion method")?;
+                    Ok(VerificationMethod {
+                        id: absolute(&method.id),
+                        ..method
+                    })
+                }
+            })
+            .collect()
+    }
+}
+
+/// Resolves the key an issuer signed with from its DID.
+#[derive(Clone, Debug)]
+pub struct DidResolver {
+    policy: RetryPolicy,
+    web_scheme: &'static str,
+    http: reqwest::Client,
+}
+
+impl Default for DidResolver {
+    fn default() -> Self {
+        Self {
+            policy: RetryPolicy::default(),
+            web_scheme: "https",
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+impl DidResolver {
+    /// Replace the default timeout and retry policy of DID document fetches.
+    pub fn with_policy(self, policy: RetryPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Fetch `did:web` documents over plain HTTP, as from a local test server.
+    pub fn over_http(self) -> Self {
+        Self {
+            web_scheme: "http",
+            ..self
+        }
+    }
+
+    /// URL of the DID document of `did:web` DID `did`: `/.well-known/did.json` on its host, or
+    /// `did.json` under its path.
+    pub fn web_document_url(&self, did: &str) -> Result<Url> {
+        let id = did
+            .strip_prefix("did:web:")
+            .with_context(|| format!("{did} is not a did:web DID"))?;
+        let mut segments = id.split(':').map(|segment| segment.replace("%3A", ":"));
+        let host = segments
+            .next()
+            .filter(|host| !host.is_empty())
+            .with_context(|| format!("{did} has no host"))?;
+        let path = segments.collect::<Vec<_>>();
+        let path = if path.is_empty() {
+            ".well-known".to_string()
+        } else {
+            path.join("/")
+        };
+        Url::parse(&format!("{}://{host}/{path}/did.json", self.web_scheme))
+            .with_context(|| format!("{did} does not name a URL"))
+    }
+
+    /// Key of `issuer` that `kid`, a DID URL or a fragment of the issuer's DID, names. Without a
+    /// `kid`, a `did:key` issuer's own key, or a `did:web` issuer's only assertion method.
+    pub async fn resolve_key(&self, issuer: &str, kid: Option<&str>) -> Result<VerifyingKey> {
+        let kid = kid.map(|kid| match kid.strip_prefix('#') {
+            Some(_) => format!("{issuer}{kid}"),
+            None => kid.to_string(),
+        });
+        if let Some(kid) = &kid {
+            let did = kid.split_once('#').map_or(kid.as_str(), |(did, _)| did);
+            ensure!(did == issuer, "key {kid} is not of issuer {issuer}");
+        }
+        if let Some(multibase) = issuer.strip_prefix("did:key:") {
+            return multibase_key(multibase);
+        }
+        if issuer.starts_with("did:web:") {
+            return self.web_key(issuer, kid.as_deref()).await;
+        }
+        let method = issuer.splitn(3, ':').take(2).collect::<Vec<_>>().join(":");
+        bail!("unsupported DID method {method} of issuer {issuer}; supported: {SUPPORTED_DID_METHODS}")
+    }
+
+    async fn web_key(&self, did: &str, kid: Option<&str>) -> Result<VerifyingKey> {
+        let url = self.web_document_url(did)?;
+        let document: DidDocument =
+            serde_json::from_str(&fetch_text(&self.http, &self.policy, &url).await?)
+                .with_context(|| format!("{url} is not a DID document"))?;
+        ensure!(
+            document.id == did,
+            "DID document {url} is of {}, not of {did}",
+            document.id
+        );
+        let methods = document.assertion_methods()?;
+        let method = match kid {
+            Some(kid) => methods
+                .iter()
+                .find(|method| method.id == kid)
+                .with_context(|| format!("{kid} is not an assertion method of {did}"))?,
+            None => match methods.as_slice() {
+                [method] => method,
+                _ => bail!(
+                    "the credential names no key and {did} has {} assertion methods",
+                    methods.len()
+                ),
+            },
+        };
+        method.key()
+    }
+
+    /// Check that `issuer` signed `jws`.
+    async fn verify(&self, jws: &Jws<'_>, issuer: &str) -> Result<()> {
+        let key = self.resolve_key(issuer, jws.header.kid.as_deref()).await?;
+        key.verify(jws.signing_input.as_bytes(), &jws.signature)
+            .map_err(|_| anyhow!("signature does not verify against the key of {issuer}"))
+    }
+}
+
+/// A credential that verified.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedCredential {
+    /// DID of the issuer.
+    pub issuer: String,
+    /// Account the credential is of.
+    pub subject: Address,
+    /// Fields of the subject, with the credential's `keccak256` as the `credential_hash`.
+    pub status: ComplianceStatus,
+}
+
+/// Reads compliance status from each user's verifiable credential.
+#[derive(Clone, Debug)]
+pub struct CredentialSource {
+    location: CredentialLocation,
+    resolver: DidResolver,
+    now: Option<u64>,
+    policy: RetryPolicy,
+    http: reqwest::Client,
+}
+
+impl CredentialSource {
+    pub fn new(location: CredentialLocation) -> Self {
+        Self {
+            location,
+            resolver: DidResolver::default(),
+            now: None,
+            policy: RetryPolicy::default(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Resolve issuer keys with `resolver` rather than the default one.
+    pub fn with_resolver(self, resolver: DidResolver) -> Self {
+        Self { resolver, ..self }
+    }
+
+    /// Replace the default timeout and retry policy of credential and status list fetches.
+    pub fn with_policy(self, policy: RetryPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Check validity periods at `now`, in Unix seconds, rather than at the current time.
+    pub fn checked_at(self, now: u64) -> Self {
+        Self {
+            now: Some(now),
+            ..self
+        }
+    }
+
+    async fn read(&self, user: Address) -> Result<String> {
+        let location = self.location.for_user(user);
+        match &self.location {
+            CredentialLocation::File(_) => std::fs::read_to_string(&location)
+                .with_context(|| format!("failed to read credential {location}")),
+            CredentialLocation::Url(_) => {
+                let url = Url::parse(&location)
+                    .with_context(|| format!("invalid credential URL {location}"))?;
+                fetch_text(&self.http, &self.policy, &url).await
+            }
+        }
+    }
+
+    /// Verify `credential`, a JWT-VC, and read the status of its subject.
+    pub async fn verify(&self, credential: &str) -> Result<VerifiedCredential> {
+        let credential = credential.trim();
+        if credential.starts_with('{') {
+            // A credential with an embedded proof, such as a Data Integrity proof.
+            let document: Value =
+                serde_json::from_str(credential).context("credential is not valid JSON")?;
+            let proof = document["proof"]["type"].as_str().unwrap_or("none");
+            bail!("unsupported proof type {proof}; supported: {SUPPORTED_PROOFS}");
+        }
+        let jws = Jws::parse(credential)?;
+        let claims: CredentialClaims = jws.claims()?;
+        ensure!(
+            claims
+                .vc
+                .types
+                .iter()
+                .any(|kind| kind == "VerifiableCredential"),
+            "not a VerifiableCredential, but {}",
+            claims.vc.types.join(", ")
+        );
+        self.resolver.verify(&jws, &claims.iss).await?;
+
+        let now = self.now.unwrap_or_else(unix_now);
+        if let Some(nbf) = claims.nbf {
+            ensure!(
+                nbf <= now,
+                "credential is not valid until {}",
+                format_decision_at(nbf)
+            );
+        }
+        let expiration_date = claims.vc.expiration_date.as_deref().map(unix_time);
+        for exp in claims.exp.into_iter().chain(expiration_date.transpose()?) {
+            ensure!(
+                now < exp,
+                "credential expired at {}",
+                format_decision_at(exp)
+            );
+        }
+        let statuses = match claims.vc.credential_status {
+            None => Vec::new(),
+            Some(Value::Array(statuses)) => statuses,
+            Some(status) => vec![status],
+        };
+        for status in statuses {
+            let status: CredentialStatus =
+                serde_json::from_value(status).context("invalid credentialStatus")?;
+            self.check_status(&claims.iss, &status).await?;
+        }
+
+        let subject = claims
+            .vc
+            .credential_subject
+            .id
+            .or(claims.sub)
+            .context("credential names no subject")?;
+        Ok(VerifiedCredential {
+            issuer: claims.iss,
+            subject: subject_address(&subject)?,
+            status: ComplianceStatus {
+                credential_hash: credential_hash(credential),
+                ..claims.vc.credential_subject.status
+            },
+        })
+    }
+
+    /// Check that the credential is not revoked or suspended in the status list `status` names,
+    /// which must be a JWT-VC of `issuer`.
+    async fn check_status(&self, issuer: &str, status: &CredentialStatus) -> Result<()> {
+        ensure!(
+            matches!(
+                status.kind.as_str(),
+                "StatusList2021Entry" | "BitstringStatusListEntry"
+            ),
+            "unsupported credential status {}; supported: {SUPPORTED_STATUS_TYPES}",
+            status.kind
+        );
+        let purpose = status.status_purpose.as_deref().unwrap_or("revocation");
+        let state = match purpose {
+            "revocation" => "revoked",
+            "suspension" => "suspended",
+            _ => bail!("unsupported status purpose {purpose}; supported: revocation, suspension"),
+        };
+        let (Some(index), Some(url)) = (&status.status_list_index, &status.status_list_credential)
+        else {
+            bail!(
+                "{} names no statusListIndex and statusListCredential",
+                status.kind
+            );
+        };
+        let index: usize = index
+            .parse()
+            .with_context(|| format!("invalid status list index {index}"))?;
+
+        let list = fetch_text(&self.http, &self.policy, url).await?;
+        let jws = Jws::parse(list.trim()).with_context(|| format!("invalid status list {url}"))?;
+        let claims: StatusListClaims = jws.claims()?;
+        ensure!(
+            claims.iss == issuer,
+            "status list {url} is issued by {}, not by the credential's issuer {issuer}",
+            claims.iss
+        );
+        self.resolver
+            .verify(&jws, issuer)
+            .await
+            .with_context(|| format!("status list {url} does not verify"))?;
+        let set = status_list_bit(&claims.vc.credential_subject.encoded_list, index)
+            .with_context(|| format!("invalid status list {url}"))?;
+        ensure!(
+            !set,
+            "credential is {state} (status list {url}, index {index})"
+        );
+        Ok(())
+    }
+}
+
+impl AttestationSource for CredentialSource {
+    async fn fetch(&self, user: Address) -> Result<ComplianceStatus> {
+        let credential = self.read(user).await?;
+        let verified = self
+            .verify(&credential)
+            .await
+            .with_context(|| format!("credential of {user} does not verify"))?;
+        ensure!(
+            verified.subject == user,
+            "credential is of {}, not of {user}",
+            verified.subject
+        );
+        tracing::info!(
+            "Verified credential {} of {user} issued by {}",
+            verified.status.credential_hash,
+            verified.issuer
+        );
+        Ok(verified.status)
+    }
+}
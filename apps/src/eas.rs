@@ -0,0 +1,359 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Publishing proven decisions as Ethereum Attestation Service attestations.
+//!
+//! Protocols that do not integrate the hook can read a decision from EAS instead. Once a trade's
+//! journal is proven and checked against the decision the host expects, [EasAttester] attests it to
+//! the user under a schema of [ATTESTATION_SCHEMA], either after the hook's `beforeTrade` or in its
+//! place. Every attestation is recorded in an [AttestationStore] by UID, so it can be found and
+//! revoked later.
+
+use std::{path::PathBuf, time::Duration};
+
+use alloy::{
+    primitives::{Address, Bytes, Log, B256, U256},
+    providers::Provider,
+    sol_types::{SolEvent, SolValue},
+};
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    batch::BatchEntry,
+    cache::write_atomically,
+    journal::ComplianceJournal,
+    settle::{Settlement, Settler},
+};
+
+alloy::sol! {
+    /// The part of the Ethereum Attestation Service the CLI calls.
+    #[sol(rpc)]
+    interface IEAS {
+        struct AttestationRequestData {
+            address recipient;
+            uint64 expirationTime;
+            bool revocable;
+            bytes32 refUID;
+            bytes data;
+            uint256 value;
+        }
+
+        struct AttestationRequest {
+            bytes32 schema;
+            AttestationRequestData data;
+        }
+
+        struct RevocationRequestData {
+            bytes32 uid;
+            uint256 value;
+        }
+
+        struct RevocationRequest {
+            bytes32 schema;
+            RevocationRequestData data;
+        }
+
+        event Attested(
+            address indexed recipient, address indexed attester, bytes32 uid, bytes32 indexed schemaUID
+        );
+
+        event Revoked(
+            address indexed recipient, address indexed attester, bytes32 uid, bytes32 indexed schemaUID
+        );
+
+        function attest(AttestationRequest calldata request) external payable returns (bytes32);
+
+        function revoke(RevocationRequest calldata request) external payable;
+    }
+
+    /// Data of a decision attestation, laid out as [ATTESTATION_SCHEMA].
+    #[derive(Debug, PartialEq, Eq)]
+    struct DecisionAttestation {
+        address user;
+        bytes32 productId;
+        bool allowed;
+        /// SHA-256 of the journal, as the hook's `TradeAllowed` event carries it.
+        bytes32 journalDigest;
+        /// Unix time the attestation expires at, or zero when it does not.
+        uint64 validUntil;
+    }
+}
+
+/// EAS schema the attestations are made under. Register it, revocable, to get the UID to pass
+/// as `--eas-schema-uid`.
+pub const ATTESTATION_SCHEMA: &str =
+    "address user,bytes32 productId,bool allowed,bytes32 journalDigest,uint64 validUntil";
+
+impl DecisionAttestation {
+    /// Attestation of the decision `journal` commits, valid for `valid_for` after its decision
+    /// time or, without it, until revoked.
+    pub fn new(journal: &[u8], valid_for: Option<Duration>) -> Result<Self> {
+        let decision = ComplianceJournal::try_from(journal).context("invalid journal")?;
+        Ok(Self {
+            user: decision.user,
+            productId: decision.productId,
+            allowed: decision.allowed,
+            journalDigest: B256::from_slice(&Sha256::digest(journal)),
+            validUntil: valid_for.map_or(0, |valid_for| {
+                decision.decisionAt.saturating_add(valid_for.as_secs())
+            }),
+        })
+    }
+}
+
+/// UID of the attestation under `schema` that `eas` made in `logs`.
+pub fn attested_uid<'a>(
+    logs: impl IntoIterator<Item = &'a Log>,
+    eas: Address,
+    schema: B256,
+) -> Result<B256> {
+    for log in logs {
+        if log.address != eas || log.topics().first() != Some(&IEAS::Attested::SIGNATURE_HASH) {
+            continue;
+        }
+        let attested = IEAS::Attested::decode_log(log)
+            .context("failed to decode Attested event")?
+            .data;
+        if attested.schemaUID == schema {
+            return Ok(attested.uid);
+        }
+    }
+    bail!("EAS {eas} emitted no Attested event for schema {schema}")
+}
+
+/// An attestation as recorded in the [AttestationStore].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttestationRecord {
+    pub uid: B256,
+    pub eas: Address,
+    pub schema: B256,
+    pub tx_hash: B256,
+    pub user: Address,
+    pub product_id: B256,
+    pub allowed: bool,
+    pub journal_digest: B256,
+    pub valid_until: u64,
+    /// Transaction that revoked the attestation, once it is revoked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_by: Option<B256>,
+}
+
+/// A directory of attestations, one `<uid>.json` file each.
+#[derive(Clone, Debug)]
+pub struct AttestationStore {
+    dir: PathBuf,
+}
+
+impl AttestationStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, uid: B256) -> PathBuf {
+        self.dir.join(format!("{uid:x}.json"))
+    }
+
+    /// The recorded attestation `uid`, if there is one.
+    pub fn get(&self, uid: B256) -> Result<Option<AttestationRecord>> {
+        let path = self.path(uid);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to read attestation {}", path.display()))
+            }
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .with_context(|| format!("failed to parse attestation {}", path.display()))
+    }
+
+    /// Record `record`, replacing any earlier record of its UID.
+    pub fn insert(&self, record: &AttestationRecord) -> Result<()> {
+        std::fs::create_dir_all(&self.dir).with_context(|| {
+            format!("failed to create attestation store {}", self.dir.display())
+        })?;
+        let path = self.path(record.uid);
+        write_atomically(&path, &serde_json::to_vec_pretty(record)?)
+            .with_context(|| format!("failed to write attestation {}", path.display()))
+    }
+}
+
+/// Attests decisions with an EAS contract from the configured signer.
+pub struct EasAttester<P> {
+    eas: IEAS::IEASInstance<P>,
+    schema: B256,
+    from: Address,
+    valid_for: Option<Duration>,
+    store: AttestationStore,
+}
+
+impl<P: Provider> EasAttester<P> {
+    pub fn new(
+        eas_address: Address,
+        schema: B256,
+        provider: P,
+        from: Address,
+        store: AttestationStore,
+    ) -> Self {
+        Self {
+            eas: IEAS::IEASInstance::new(eas_address, provider),
+            schema,
+            from,
+            valid_for: None,
+            store,
+        }
+    }
+
+    /// Let attestations expire `valid_for` after their decision time.
+    pub fn with_validity(mut self, valid_for: Option<Duration>) -> Self {
+        self.valid_for = valid_for;
+        self
+    }
+
+    /// Attest the decision `journal` commits, and record the attestation in the store.
+    pub async fn attest(&self, journal: &[u8]) -> Result<AttestationRecord> {
+        let attestation = DecisionAttestation::new(journal, self.valid_for)?;
+        let request = IEAS::AttestationRequest {
+            schema: self.schema,
+            data: IEAS::AttestationRequestData {
+                recipient: attestation.user,
+                expirationTime: attestation.validUntil,
+                revocable: true,
+                refUID: B256::ZERO,
+                data: attestation.abi_encode().into(),
+                value: U256::ZERO,
+            },
+        };
+        tracing::info!("Calling EAS attest function");
+        let receipt = self
+            .eas
+            .attest(request)
+            .from(self.from)
+            .send()
+            .await
+            .context("failed to broadcast attest tx")?
+            .with_timeout(Some(crate::settle::TX_TIMEOUT))
+            .get_receipt()
+            .await
+            .context("failed to confirm attest tx")?;
+        let tx_hash = receipt.transaction_hash;
+        ensure!(receipt.status(), "attest tx {tx_hash} reverted");
+        let uid = attested_uid(
+            receipt.inner.logs().iter().map(|log| &log.inner),
+            *self.eas.address(),
+            self.schema,
+        )
+        .with_context(|| format!("attest tx {tx_hash} made no attestation"))?;
+        tracing::info!("Attested decision as {uid} in tx {tx_hash}");
+
+        let record = AttestationRecord {
+            uid,
+            eas: *self.eas.address(),
+            schema: self.schema,
+            tx_hash,
+            user: attestation.user,
+            product_id: attestation.productId,
+            allowed: attestation.allowed,
+            journal_digest: attestation.journalDigest,
+            valid_until: attestation.validUntil,
+            revoked_by: None,
+        };
+        self.store.insert(&record)?;
+        Ok(record)
+    }
+
+    /// Revoke attestation `uid`, under the schema the store recorded it with or, for one the store
+    /// does not hold, the configured schema. Returns the hash of the revoking transaction.
+    pub async fn revoke(&self, uid: B256) -> Result<B256> {
+        let record = self.store.get(uid)?;
+        if let Some(revoked_by) = record.as_ref().and_then(|record| record.revoked_by) {
+            bail!("attestation {uid} was already revoked in tx {revoked_by}");
+        }
+        let schema = record.as_ref().map_or(self.schema, |record| record.schema);
+        ensure!(
+            !schema.is_zero(),
+            "attestation {uid} is not in the store; pass the schema UID it was made under"
+        );
+        let request = IEAS::RevocationRequest {
+            schema,
+            data: IEAS::RevocationRequestData {
+                uid,
+                value: U256::ZERO,
+            },
+        };
+        tracing::info!("Calling EAS revoke function");
+        let receipt = self
+            .eas
+            .revoke(request)
+            .from(self.from)
+            .send()
+            .await
+            .context("failed to broadcast revoke tx")?
+            .with_timeout(Some(crate::settle::TX_TIMEOUT))
+            .get_receipt()
+            .await
+            .context("failed to confirm revoke tx")?;
+        let tx_hash = receipt.transaction_hash;
+        ensure!(receipt.status(), "revoke tx {tx_hash} reverted");
+        tracing::info!("Revoked attestation {uid} in tx {tx_hash}");
+
+        match record {
+            Some(record) => self.store.insert(&AttestationRecord {
+                revoked_by: Some(tx_hash),
+                ..record
+            })?,
+            None => tracing::warn!("Attestation {uid} is not in the store; nothing to update"),
+        }
+        Ok(tx_hash)
+    }
+}
+
+/// Settles with `hook`, if there is one, and then attests the decision with EAS.
+pub struct AttestingSettler<S, P> {
+    hook: Option<S>,
+    attester: EasAttester<P>,
+}
+
+impl<S, P> AttestingSettler<S, P> {
+    pub fn new(hook: Option<S>, attester: EasAttester<P>) -> Self {
+        Self { hook, attester }
+    }
+}
+
+impl<S: Settler, P: Provider + Send + Sync> Settler for AttestingSettler<S, P> {
+    async fn settle(&self, entry: &BatchEntry, journal: Bytes, seal: Bytes) -> Result<Settlement> {
+        let hook = match &self.hook {
+            Some(hook) => Some(Box::new(hook.settle(entry, journal.clone(), seal).await?)),
+            None => None,
+        };
+        let record = self
+            .attester
+            .attest(&journal)
+            .await
+            .with_context(|| match &hook {
+                Some(hook) => format!("settled as {hook:?}, but failed to attest"),
+                None => "failed to attest".into(),
+            })?;
+        Ok(Settlement::Attested {
+            uid: record.uid,
+            tx_hash: record.tx_hash,
+            hook,
+        })
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! ENS names in place of addresses.
+//!
+//! Address arguments of the CLI take a [NameOrAddress], so an operator can pass `trader.acme.eth`
+//! instead of its hex address. Names are resolved through `--rpc-url` with an [EnsResolver]
+//! before anything is proven, and a name that does not resolve, or resolves to the zero address,
+//! stops the run. Subcommands that work offline never resolve anything.
+
+use std::{fmt, str::FromStr};
+
+use alloy::{
+    hex,
+    primitives::{address, keccak256, Address, B256},
+    providers::Provider,
+};
+use anyhow::{bail, ensure, Context, Result};
+
+alloy::sol! {
+    /// The ENS registry, which records the resolver of each name.
+    #[sol(rpc)]
+    interface IEnsRegistry {
+        function resolver(bytes32 node) external view returns (address);
+    }
+
+    /// The parts of an ENS resolver that map names to addresses and back.
+    #[sol(rpc)]
+    interface IEnsResolver {
+        function addr(bytes32 node) external view returns (address);
+        function name(bytes32 node) external view returns (string);
+    }
+}
+
+/// Address of the ENS registry on mainnet and its official testnets.
+pub const ENS_REGISTRY: Address = address!("0x00000000000C2E074eC69A0dFb2997BA6C7d2e1e");
+
+/// An address argument, given as a hex address or as an ENS name to resolve.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NameOrAddress {
+    Address(Address),
+    /// A lowercased ENS name, such as `trader.acme.eth`.
+    Name(String),
+}
+
+impl NameOrAddress {
+    /// The address, or an error for a name that was not resolved.
+    pub fn address(&self) -> Result<Address> {
+        match self {
+            Self::Address(address) => Ok(*address),
+            Self::Name(name) => {
+                bail!("ENS name {name} is not resolved; pass --rpc-url to resolve it")
+            }
+        }
+    }
+}
+
+impl FromStr for NameOrAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(address) = Address::from_str(s) {
+            return Ok(Self::Address(address));
+        }
+        ensure!(!s.starts_with("0x"), "invalid address {s}");
+        let name = s.to_lowercase();
+        ensure!(
+            name.contains('.')
+                && name
+                    .split('.')
+                    .all(|label| !label.is_empty() && !label.contains(char::is_whitespace)),
+            "{s} is neither an address nor an ENS name"
+        );
+        Ok(Self::Name(name))
+    }
+}
+
+impl fmt::Display for NameOrAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(address) => write!(f, "{address}"),
+            Self::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// EIP-137 `namehash` of `name`.
+pub fn namehash(name: &str) -> B256 {
+    name.rsplit('.')
+        .filter(|label| !label.is_empty())
+        .fold(B256::ZERO, |node, label| {
+            keccak256([node.as_slice(), keccak256(label).as_slice()].concat())
+        })
+}
+
+/// Name of the reverse record of `address`, under `addr.reverse`.
+pub fn reverse_name(address: Address) -> String {
+    format!("{}.addr.reverse", hex::encode(address))
+}
+
+/// Resolves ENS names with the registry at a configurable address.
+pub struct EnsResolver<P> {
+    provider: P,
+    registry: Address,
+}
+
+impl<P: Provider> EnsResolver<P> {
+    pub fn new(provider: P, registry: Address) -> Self {
+        Self { provider, registry }
+    }
+
+    /// The resolver the registry records for `node`, if any.
+    async fn resolver(&self, node: B256) -> Result<Option<Address>> {
+        let resolver = IEnsRegistry::new(self.registry, &self.provider)
+            .resolver(node)
+            .call()
+            .await
+            .with_context(|| format!("failed to query ENS registry {}", self.registry))?;
+        Ok((!resolver.is_zero()).then_some(resolver))
+    }
+
+    /// The address `name` resolves to. A name without a resolver or an address fails, and so
+    /// does one that resolves to the zero address.
+    pub async fn resolve(&self, name: &str) -> Result<Address> {
+        let node = namehash(name);
+        let resolver = self
+            .resolver(node)
+            .await?
+            .with_context(|| format!("ENS name {name} has no resolver"))?;
+        let address = IEnsResolver::new(resolver, &self.provider)
+            .addr(node)
+            .call()
+            .await
+            .with_context(|| format!("failed to resolve ENS name {name} with {resolver}"))?;
+        ensure!(
+            !address.is_zero(),
+            "ENS name {name} resolves to the zero address"
+        );
+        Ok(address)
+    }
+
+    /// Replace `value` by the address it names, printing the resolution so the operator can
+    /// check it. Addresses are left as they are.
+    pub async fn resolve_in_place(&self, value: &mut NameOrAddress) -> Result<()> {
+        if let NameOrAddress::Name(name) = value {
+            let address = self.resolve(name).await?;
+            println!("{name} resolves to {address}");
+            *value = NameOrAddress::Address(address);
+        }
+        Ok(())
+    }
+
+    /// The primary name of `address`, from its reverse record. A name that does not resolve back
+    /// to `address` is not its name, and gives none.
+    pub async fn lookup(&self, address: Address) -> Result<Option<String>> {
+        let node = namehash(&reverse_name(address));
+        let Some(resolver) = self.resolver(node).await? else {
+            return Ok(None);
+        };
+        let name = IEnsResolver::new(resolver, &self.provider)
+            .name(node)
+            .call()
+            .await
+            .with_context(|| format!("failed to look up the ENS name of {address}"))?;
+        if name.is_empty() {
+            return Ok(None);
+        }
+        match self.resolve(&name).await {
+            Ok(resolved) if resolved == address => Ok(Some(name)),
+            _ => Ok(None),
+        }
+    }
+
+    /// `text` with the primary ENS name after each address in it that has one, as
+    /// `0x… (trader.acme.eth)`. Addresses whose lookup fails are left bare.
+    pub async fn annotate(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("0x") {
+            let (before, candidate) = rest.split_at(start);
+            out.push_str(before);
+            let hex_len = candidate[2..]
+                .bytes()
+                .take_while(u8::is_ascii_hexdigit)
+                .count();
+            let (token, after) = candidate.split_at(2 + hex_len);
+            out.push_str(token);
+            if hex_len == 40 {
+                let address = Address::from_str(token).expect("40 hex digits are an address");
+                match self.lookup(address).await {
+                    Ok(Some(name)) => {
+                        out.push_str(" (");
+                        out.push_str(&name);
+                        out.push(')');
+                    }
+                    Ok(None) => {}
+                    Err(err) => tracing::debug!("No ENS name for {address}: {err:#}"),
+                }
+            }
+            rest = after;
+        }
+        out.push_str(rest);
+        out
+    }
+}
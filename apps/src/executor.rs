@@ -0,0 +1,177 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tuning of local guest executions: how sessions are split into segments and how long they may
+//! run.
+//!
+//! Only executions on this machine are affected. Market provers choose their own segment size.
+
+use std::fmt::Write;
+
+use alloy::primitives::U256;
+use anyhow::{Context, Result};
+use clap::{builder::RangedI64ValueParser, Args};
+use compliance_core::{CycleProfile, Section};
+use risc0_zkvm::{default_executor, ExecutorEnv, ExecutorEnvBuilder};
+use serde::Serialize;
+
+/// Smallest segment limit the zkVM accepts, as a power of two of cycles.
+pub const MIN_SEGMENT_PO2: u32 = 13;
+/// Largest segment limit the zkVM accepts.
+pub const MAX_SEGMENT_PO2: u32 = 24;
+/// Segment limit the zkVM uses when none is given.
+pub const DEFAULT_SEGMENT_PO2: u32 = 20;
+
+/// Options applied to every local execution.
+#[derive(Args, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutorOptions {
+    /// Most cycles per segment, as a power of two. Smaller segments need less prover memory;
+    /// larger ones mean fewer segments to prove and join. Defaults to 2^20.
+    #[clap(long, env, value_parser = segment_po2_parser())]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segment_limit_po2: Option<u32>,
+    /// Most cycles a whole session may run before the execution fails.
+    #[clap(long, env)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_limit: Option<u64>,
+}
+
+/// Outcome of one local execution.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Execution {
+    #[serde(skip)]
+    pub journal: Vec<u8>,
+    /// User cycles across all segments.
+    pub cycles: u64,
+    /// Cycles of all segments, each padded to its power of two. The market prices requests by
+    /// this count.
+    pub total_cycles: u64,
+    /// Number of segments the session was split into.
+    pub segments: usize,
+    /// [suggest_segment_po2] for the session's cycles.
+    pub suggested_segment_po2: u32,
+    #[serde(flatten)]
+    pub options: ExecutorOptions,
+}
+
+impl ExecutorOptions {
+    /// Executor environment with these options, for the caller to add the guest's input to.
+    pub fn env_builder<'a>(&self) -> ExecutorEnvBuilder<'a> {
+        let mut builder = ExecutorEnv::builder();
+        builder.session_limit(self.session_limit);
+        if let Some(po2) = self.segment_limit_po2 {
+            builder.segment_limit_po2(po2);
+        }
+        builder
+    }
+
+    /// Execute `elf` on `frame`, written to the guest as a single frame.
+    pub fn execute(&self, elf: &[u8], frame: &[u8]) -> Result<Execution> {
+        let mut builder = self.env_builder();
+        builder.write_frame(frame);
+        self.execute_env(elf, builder)
+    }
+
+    /// [Self::execute], also returning what the guest wrote to its stdout.
+    pub fn execute_with_stdout(&self, elf: &[u8], frame: &[u8]) -> Result<(Execution, Vec<u8>)> {
+        let mut stdout = Vec::new();
+        let mut builder = self.env_builder();
+        builder.write_frame(frame).stdout(&mut stdout);
+        let execution = self.execute_env(elf, builder)?;
+        Ok((execution, stdout))
+    }
+
+    /// Executor environment with these options holding a paged batch: the number of `pages` as a
+    /// `u32`, the `aggregate_cap` as 32 big-endian bytes, then each page as its own frame, as
+    /// `BatchFold` reads them.
+    pub fn paged_env_builder<'a>(
+        &self,
+        pages: &[Vec<u8>],
+        aggregate_cap: U256,
+    ) -> Result<ExecutorEnvBuilder<'a>> {
+        let mut builder = self.env_builder();
+        let count = u32::try_from(pages.len()).context("too many pages")?;
+        builder.write_slice(&[count]);
+        builder.write_slice(&aggregate_cap.to_be_bytes::<32>());
+        for page in pages {
+            builder.write_frame(page);
+        }
+        Ok(builder)
+    }
+
+    /// Execute `elf` on a paged batch; see [Self::paged_env_builder].
+    pub fn execute_paged(
+        &self,
+        elf: &[u8],
+        pages: &[Vec<u8>],
+        aggregate_cap: U256,
+    ) -> Result<Execution> {
+        let builder = self.paged_env_builder(pages, aggregate_cap)?;
+        self.execute_env(elf, builder)
+    }
+
+    fn execute_env(&self, elf: &[u8], mut builder: ExecutorEnvBuilder<'_>) -> Result<Execution> {
+        let env = builder.build().context("failed to build executor env")?;
+        let session = default_executor().execute(env, elf)?;
+        let cycles = session.cycles();
+        Ok(Execution {
+            journal: session.journal.bytes,
+            cycles,
+            total_cycles: session
+                .segments
+                .iter()
+                .map(|segment| 1 << segment.po2)
+                .sum(),
+            segments: session.segments.len(),
+            suggested_segment_po2: suggest_segment_po2(cycles),
+            options: *self,
+        })
+    }
+}
+
+/// Table of the cycles of each section in `profile`, out of the `session_cycles` user cycles of
+/// the execution that wrote it, followed by the guest's peak heap. The cycles outside every
+/// section are spent writing the profile.
+pub fn format_profile(profile: &CycleProfile, session_cycles: u64) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "cycles:      {} profiled of {session_cycles}",
+        profile.total()
+    )
+    .unwrap();
+    for section in Section::ALL {
+        let cycles = profile.get(section);
+        let share = 100.0 * cycles as f64 / session_cycles.max(1) as f64;
+        writeln!(out, "  {:<14}{cycles:>10}  {share:>5.1}%", section.name()).unwrap();
+    }
+    writeln!(out, "heap peak:   {} bytes", profile.heap_peak).unwrap();
+    out
+}
+
+/// Accepts segment limits from [MIN_SEGMENT_PO2] to [MAX_SEGMENT_PO2].
+fn segment_po2_parser() -> RangedI64ValueParser<u32> {
+    RangedI64ValueParser::new().range(i64::from(MIN_SEGMENT_PO2)..=i64::from(MAX_SEGMENT_PO2))
+}
+
+/// Segment limit for a session of `cycles` user cycles: the smallest one that holds them in a
+/// single segment, capped at [DEFAULT_SEGMENT_PO2] so long sessions keep the default's memory use.
+/// Paging adds cycles on top of the user cycles, so a session just under a power of two may still
+/// take two segments.
+pub fn suggest_segment_po2(cycles: u64) -> u32 {
+    let fits = cycles.max(1).next_power_of_two().trailing_zeros();
+    fits.clamp(MIN_SEGMENT_PO2, DEFAULT_SEGMENT_PO2)
+}
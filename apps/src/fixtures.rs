@@ -0,0 +1,251 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Journal and seal fixtures for testing the hook contract and frontends without a prover.
+//!
+//! Each case of a [FixtureSpec] is executed by the compliance guest and written out by
+//! [write_fixtures] as `<name>.input.bin` (the ABI-encoded input, which the guest reads as one
+//! frame), `<name>.journal.bin` and `<name>.seal.bin`, plus one
+//! `image_id.bin` and a `manifest.json` that repeats every file as hex. The seal is the dev-mode
+//! seal accepted by `RiscZeroMockVerifier`. Nothing in the output depends on the time or the
+//! machine, so the same spec and guest build always produce the same bytes.
+
+use std::{collections::HashSet, path::Path};
+
+use alloy::{primitives::B256, sol_types::SolValue};
+use anyhow::{bail, ensure, Context, Result};
+use compliance_core::ReasonCode;
+use guests::COMPLIANCE_ELF;
+use risc0_zkvm::{sha::Digestible, ReceiptClaim};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    batch::BatchEntry,
+    executor::{Execution, ExecutorOptions},
+    journal::{compliance_image_id, ComplianceInput, ComplianceJournal},
+};
+
+/// Seal selector of `RiscZeroMockVerifier`, which accepts dev-mode receipts.
+pub const DEV_MODE_SELECTOR: [u8; 4] = [0xFF; 4];
+
+/// Largest `manifest.json` [Manifest::parse] accepts. Checked-in sets are a few kilobytes; the cap
+/// keeps a corrupted or hostile file from being buffered into a huge document.
+pub const MAX_MANIFEST_LEN: usize = 1 << 20;
+
+/// Cases to generate fixtures for.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct FixtureSpec {
+    pub cases: Vec<FixtureCase>,
+}
+
+/// One named trade. The name is used as the file prefix.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixtureCase {
+    pub name: String,
+    /// Decision time supplied to the guest, fixed so regenerating gives the same journal.
+    #[serde(default)]
+    pub decision_at: u64,
+    #[serde(flatten)]
+    pub entry: BatchEntry,
+}
+
+impl FixtureSpec {
+    /// Read a spec from a JSON file and check its cases.
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read fixture spec {}", path.display()))?;
+        let spec: Self = serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse fixture spec {}", path.display()))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        for case in &self.cases {
+            let valid = !case.name.is_empty()
+                && case
+                    .name
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+            ensure!(
+                valid,
+                "fixture name {:?} must be non-empty lowercase ASCII, digits or underscores",
+                case.name
+            );
+            ensure!(
+                names.insert(&case.name),
+                "fixture name {:?} is used twice",
+                case.name
+            );
+            case.entry
+                .validate()
+                .with_context(|| format!("invalid fixture {}", case.name))?;
+        }
+        Ok(())
+    }
+}
+
+/// Generated artifacts of one case.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fixture {
+    pub case: FixtureCase,
+    pub image_id: B256,
+    pub input: Vec<u8>,
+    pub journal: Vec<u8>,
+    pub seal: Vec<u8>,
+}
+
+impl Fixture {
+    /// Build the fixture for a journal committed by the guest with `image_id`.
+    pub fn new(case: FixtureCase, image_id: B256, journal: Vec<u8>) -> Self {
+        let input = ComplianceInput {
+            imageId: image_id,
+            ..case.entry.input(case.decision_at)
+        }
+        .abi_encode();
+        let seal = dev_mode_seal(image_id, &journal);
+        Self {
+            case,
+            image_id,
+            input,
+            journal,
+            seal,
+        }
+    }
+
+    /// Execute the embedded compliance guest on `case` with `options`.
+    pub fn execute(case: FixtureCase, options: &ExecutorOptions) -> Result<(Self, Execution)> {
+        let input = case.entry.input(case.decision_at).abi_encode();
+        let execution = options
+            .execute(COMPLIANCE_ELF, &input)
+            .with_context(|| format!("failed to execute guest for fixture {}", case.name))?;
+        let fixture = Self::new(case, compliance_image_id(), execution.journal.clone());
+        Ok((fixture, execution))
+    }
+}
+
+/// Seal for a dev-mode receipt of a guest that halted normally with `journal`.
+///
+/// Matches `risc0_ethereum_contracts::encode_seal` for fake receipts: the mock verifier selector
+/// followed by the receipt claim digest.
+pub fn dev_mode_seal(image_id: B256, journal: &[u8]) -> Vec<u8> {
+    let image_id: [u8; 32] = image_id.into();
+    let claim = ReceiptClaim::ok(image_id, journal.to_vec()).digest();
+    [DEV_MODE_SELECTOR.as_slice(), claim.as_bytes()].concat()
+}
+
+/// `manifest.json` of a fixture directory.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub image_id: B256,
+    pub cases: Vec<ManifestCase>,
+}
+
+/// One case of the manifest: the trade, the decision, and every file as hex.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestCase {
+    pub name: String,
+    pub decision_at: u64,
+    #[serde(flatten)]
+    pub entry: BatchEntry,
+    pub allowed: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub needs_review: bool,
+    pub reasons: Vec<String>,
+    pub input: String,
+    pub journal: String,
+    pub seal: String,
+}
+
+impl Manifest {
+    /// Manifest for fixtures generated from one guest build.
+    pub fn new(fixtures: &[Fixture]) -> Result<Self> {
+        let Some(first) = fixtures.first() else {
+            bail!("no fixtures to write");
+        };
+        let cases = fixtures
+            .iter()
+            .map(|fixture| {
+                ensure!(
+                    fixture.image_id == first.image_id,
+                    "fixture {} was generated by a different guest",
+                    fixture.case.name
+                );
+                let journal = ComplianceJournal::try_from(fixture.journal.as_slice())
+                    .with_context(|| {
+                        format!("invalid journal for fixture {}", fixture.case.name)
+                    })?;
+                Ok(ManifestCase {
+                    name: fixture.case.name.clone(),
+                    decision_at: fixture.case.decision_at,
+                    entry: fixture.case.entry.clone(),
+                    allowed: journal.allowed,
+                    needs_review: journal.needsReview,
+                    reasons: ReasonCode::from_mask(journal.reasons)
+                        .map(|reason| reason.name().to_string())
+                        .collect(),
+                    input: alloy::hex::encode_prefixed(&fixture.input),
+                    journal: alloy::hex::encode_prefixed(&fixture.journal),
+                    seal: alloy::hex::encode_prefixed(&fixture.seal),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            image_id: first.image_id,
+            cases,
+        })
+    }
+
+    /// Parse a `manifest.json` read from disk, rejecting anything over [MAX_MANIFEST_LEN] bytes.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() <= MAX_MANIFEST_LEN,
+            "manifest is {} bytes, the limit is {MAX_MANIFEST_LEN}",
+            bytes.len()
+        );
+        serde_json::from_slice(bytes).context("invalid fixture manifest")
+    }
+
+    /// Pretty-printed JSON with a trailing newline, as written to disk.
+    pub fn render(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("manifest always serializes");
+        json.push('\n');
+        json
+    }
+}
+
+/// Write `fixtures` and their manifest into `dir`, creating it if needed.
+pub fn write_fixtures(dir: &Path, fixtures: &[Fixture]) -> Result<()> {
+    let manifest = Manifest::new(fixtures)?;
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("failed to create fixture directory {}", dir.display()))?;
+
+    let write = |file: String, contents: &[u8]| {
+        let path = dir.join(file);
+        std::fs::write(&path, contents)
+            .with_context(|| format!("failed to write {}", path.display()))
+    };
+    write("image_id.bin".into(), manifest.image_id.as_slice())?;
+    for fixture in fixtures {
+        let name = &fixture.case.name;
+        write(format!("{name}.input.bin"), &fixture.input)?;
+        write(format!("{name}.journal.bin"), &fixture.journal)?;
+        write(format!("{name}.seal.bin"), &fixture.seal)?;
+    }
+    write("manifest.json".into(), manifest.render().as_bytes())
+}
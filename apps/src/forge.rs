@@ -0,0 +1,368 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Journals and seals for the Foundry tests of the hook.
+//!
+//! `export-forge-fixtures` turns proven trades, from settlement bundles or from a [FixtureSpec]
+//! run through the guest in dev mode, into [ForgeFixtures]. They are written as
+//! [FORGE_FIXTURES_JSON], one object per fixture name that Solidity tests read with
+//! `vm.parseJsonBytes(json, ".<name>.journal")` and the like, and as [FORGE_FIXTURES_LIBRARY], a
+//! library with one function per fixture returning a `ComplianceFixture` struct. Every fixture
+//! carries the decision its journal records, so a test can check what `beforeTrade` did with it.
+//!
+//! [FixtureSpec]: crate::fixtures::FixtureSpec
+
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use alloy::primitives::{Address, Bytes, B256, U256};
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    batch::BatchEntry,
+    fixtures::Fixture,
+    handoff::SettlementBundle,
+    journal::{ComplianceJournal, JournalEncoding},
+};
+
+/// File name of the JSON fixtures.
+pub const FORGE_FIXTURES_JSON: &str = "compliance_fixtures.json";
+
+/// File name of the Solidity library of the fixtures.
+pub const FORGE_FIXTURES_LIBRARY: &str = "ComplianceFixtures.sol";
+
+/// A journal and seal for `beforeTrade`, with the call's arguments and the decision the journal
+/// records.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgeFixture {
+    pub user: Address,
+    pub product_id: B256,
+    pub amount: U256,
+    pub journal: Bytes,
+    pub seal: Bytes,
+    pub image_id: B256,
+    /// Whether the journal is in the compact encoding.
+    pub compact: bool,
+    pub allowed: bool,
+    pub needs_review: bool,
+    pub reasons: u32,
+    pub kyc_tier: u8,
+    pub decision_at: u64,
+    pub ttl_secs: u64,
+    pub not_before: u64,
+    pub decision_uuid: B256,
+}
+
+impl ForgeFixture {
+    /// Fixture of the trade `entry` proven by the guest with `image_id`.
+    pub fn new(image_id: B256, entry: &BatchEntry, journal: Bytes, seal: Bytes) -> Result<Self> {
+        let encoding = JournalEncoding::of(&journal).context("invalid journal")?;
+        let decoded = ComplianceJournal::try_from(journal.as_ref()).context("invalid journal")?;
+        ensure!(
+            decoded.imageId == image_id,
+            "journal commits image id {}, not {image_id}",
+            decoded.imageId
+        );
+        ensure!(
+            (decoded.user, decoded.productId, decoded.tradeAmount)
+                == (entry.user, entry.product_id, entry.amount),
+            "journal is of user {} product {} amount {}, but the trade is of user {} product {} \
+             amount {}",
+            decoded.user,
+            decoded.productId,
+            decoded.tradeAmount,
+            entry.user,
+            entry.product_id,
+            entry.amount
+        );
+        Ok(Self {
+            user: entry.user,
+            product_id: entry.product_id,
+            amount: entry.amount,
+            journal,
+            seal,
+            image_id,
+            compact: encoding == JournalEncoding::Compact,
+            allowed: decoded.allowed,
+            needs_review: decoded.needsReview,
+            reasons: decoded.reasons,
+            kyc_tier: decoded.kycTier,
+            decision_at: decoded.decisionAt,
+            ttl_secs: decoded.ttlSecs,
+            not_before: decoded.notBefore,
+            decision_uuid: decoded.decisionUuid,
+        })
+    }
+}
+
+/// Fixtures by name. Names are Solidity identifiers, and the map keeps them sorted so the same
+/// fixtures always render the same files.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ForgeFixtures {
+    pub fixtures: BTreeMap<String, ForgeFixture>,
+}
+
+impl ForgeFixtures {
+    /// Add `fixture` as `name`, which must be new and a valid function name.
+    pub fn insert(&mut self, name: String, fixture: ForgeFixture) -> Result<()> {
+        let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+        ensure!(
+            valid,
+            "fixture name {name:?} must start with a lowercase letter and hold only lowercase \
+             letters, digits or underscores"
+        );
+        ensure!(
+            !self.fixtures.contains_key(&name),
+            "fixture name {name:?} is used twice"
+        );
+        self.fixtures.insert(name, fixture);
+        Ok(())
+    }
+
+    /// Add the trades of `bundle` as `<name>_<index>`.
+    pub fn add_bundle(&mut self, name: &str, bundle: &SettlementBundle) -> Result<()> {
+        for (index, trade) in bundle.trades.iter().enumerate() {
+            let fixture = ForgeFixture::new(
+                bundle.image_id,
+                &trade.entry,
+                trade.journal.clone(),
+                trade.seal.clone(),
+            )
+            .with_context(|| format!("invalid trade {index} of bundle {name}"))?;
+            self.insert(format!("{name}_{index}"), fixture)?;
+        }
+        Ok(())
+    }
+
+    /// Add fixtures generated from a spec under the names of their cases.
+    pub fn add_fixtures(&mut self, fixtures: &[Fixture]) -> Result<()> {
+        for fixture in fixtures {
+            let name = &fixture.case.name;
+            let forge = ForgeFixture::new(
+                fixture.image_id,
+                &fixture.case.entry,
+                fixture.journal.clone().into(),
+                fixture.seal.clone().into(),
+            )
+            .with_context(|| format!("invalid fixture {name}"))?;
+            self.insert(name.clone(), forge)?;
+        }
+        Ok(())
+    }
+
+    /// Check that the trades of `bundle`, added with [ForgeFixtures::add_bundle], read back with
+    /// the bundle's journals, seals and image id and the decisions its journals record.
+    pub fn check_bundle(&self, name: &str, bundle: &SettlementBundle) -> Result<()> {
+        for (index, trade) in bundle.trades.iter().enumerate() {
+            let name = format!("{name}_{index}");
+            self.check(
+                &name,
+                bundle.image_id,
+                &trade.entry,
+                &trade.journal,
+                &trade.seal,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Check that `fixtures`, added with [ForgeFixtures::add_fixtures], read back unchanged.
+    pub fn check_fixtures(&self, fixtures: &[Fixture]) -> Result<()> {
+        for fixture in fixtures {
+            self.check(
+                &fixture.case.name,
+                fixture.image_id,
+                &fixture.case.entry,
+                &fixture.journal,
+                &fixture.seal,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn check(
+        &self,
+        name: &str,
+        image_id: B256,
+        entry: &BatchEntry,
+        journal: &[u8],
+        seal: &[u8],
+    ) -> Result<()> {
+        let fixture = self
+            .fixtures
+            .get(name)
+            .with_context(|| format!("fixture {name} is missing"))?;
+        let expected = ForgeFixture::new(
+            image_id,
+            entry,
+            journal.to_vec().into(),
+            seal.to_vec().into(),
+        )?;
+        ensure!(
+            *fixture == expected,
+            "fixture {name} does not read back as its source: got {fixture:?}, expected \
+             {expected:?}"
+        );
+        Ok(())
+    }
+
+    /// Parse the JSON written to [FORGE_FIXTURES_JSON].
+    pub fn parse(json: &[u8]) -> Result<Self> {
+        serde_json::from_slice(json).context("invalid forge fixtures")
+    }
+
+    /// Pretty-printed JSON with a trailing newline, as written to disk.
+    pub fn render_json(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("fixtures always serialize");
+        json.push('\n');
+        json
+    }
+
+    /// The Solidity library, with one `internal pure` function per fixture.
+    pub fn render_library(&self) -> String {
+        let mut sol = String::from(LIBRARY_HEADER);
+        for (name, fixture) in &self.fixtures {
+            write!(
+                sol,
+                r#"
+    function {name}() internal pure returns (ComplianceFixture memory) {{
+        return ComplianceFixture({{
+            user: {user},
+            productId: {product_id},
+            amount: {amount},
+            journal: hex"{journal}",
+            seal: hex"{seal}",
+            imageId: {image_id},
+            compact: {compact},
+            allowed: {allowed},
+            needsReview: {needs_review},
+            reasons: {reasons},
+            kycTier: {kyc_tier},
+            decisionAt: {decision_at},
+            ttlSecs: {ttl_secs},
+            notBefore: {not_before},
+            decisionUuid: {decision_uuid}
+        }});
+    }}
+"#,
+                user = fixture.user,
+                product_id = fixture.product_id,
+                amount = fixture.amount,
+                journal = alloy::hex::encode(&fixture.journal),
+                seal = alloy::hex::encode(&fixture.seal),
+                image_id = fixture.image_id,
+                compact = fixture.compact,
+                allowed = fixture.allowed,
+                needs_review = fixture.needs_review,
+                reasons = fixture.reasons,
+                kyc_tier = fixture.kyc_tier,
+                decision_at = fixture.decision_at,
+                ttl_secs = fixture.ttl_secs,
+                not_before = fixture.not_before,
+                decision_uuid = fixture.decision_uuid,
+            )
+            .unwrap();
+        }
+        sol.push_str("}\n");
+        sol
+    }
+
+    /// Write [FORGE_FIXTURES_JSON] and [FORGE_FIXTURES_LIBRARY] into `dir`, creating it if needed.
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        ensure!(!self.fixtures.is_empty(), "no fixtures to write");
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create fixture directory {}", dir.display()))?;
+        let write = |file: &str, contents: &str| {
+            let path = dir.join(file);
+            std::fs::write(&path, contents)
+                .with_context(|| format!("failed to write {}", path.display()))
+        };
+        write(FORGE_FIXTURES_JSON, &self.render_json())?;
+        write(FORGE_FIXTURES_LIBRARY, &self.render_library())
+    }
+
+    /// Read the [FORGE_FIXTURES_JSON] written into `dir`.
+    pub fn read(dir: &Path) -> Result<Self> {
+        let path = dir.join(FORGE_FIXTURES_JSON);
+        let json =
+            std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&json).with_context(|| format!("invalid fixtures {}", path.display()))
+    }
+}
+
+/// Fixture name of the bundle at `path`: its file stem, lowercased, with anything but letters and
+/// digits replaced by underscores.
+pub fn bundle_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let name: String = stem
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9') => c,
+            _ => '_',
+        })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_lowercase()) {
+        name
+    } else {
+        format!("bundle_{name}")
+    }
+}
+
+const LIBRARY_HEADER: &str = r#"// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Generated by `app export-forge-fixtures`. Do not edit; regenerate it instead.
+
+pragma solidity ^0.8.20;
+
+/// @notice A journal and seal for `beforeTrade`, with the call's arguments and the decision the
+///         journal records.
+struct ComplianceFixture {
+    address user;
+    bytes32 productId;
+    uint256 amount;
+    bytes journal;
+    bytes seal;
+    bytes32 imageId;
+    bool compact;
+    bool allowed;
+    bool needsReview;
+    uint32 reasons;
+    uint8 kycTier;
+    uint64 decisionAt;
+    uint64 ttlSecs;
+    uint64 notBefore;
+    bytes32 decisionUuid;
+}
+
+library ComplianceFixtures {"#;
@@ -0,0 +1,261 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hand-off of proven trades to another party to settle.
+//!
+//! Under `export-bundle`, [ExportingSettler] writes each proven trade to a [SettlementBundle] file
+//! instead of settling it. The bundle holds everything `beforeTrade` needs: the chain, the hook,
+//! and each trade with its journal and seal. `settle-bundle` reads it back, possibly on another
+//! machine, and settles the trades with [settle_bundle]. A checksum over the bundle's contents
+//! catches files that were altered or corrupted on the way.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use alloy::{
+    primitives::{Address, Bytes, B256},
+    sol_types::SolCall,
+};
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    batch::BatchEntry,
+    cache::write_atomically,
+    compliance_hook::IComplianceHook::beforeTradeCall,
+    journal::{compliance_image_id, format_decision_at, ComplianceJournal},
+    settle::{Settlement, Settler},
+};
+
+/// Version of the bundle format, bumped when its fields or checksum change.
+pub const SETTLEMENT_BUNDLE_VERSION: u32 = 1;
+
+/// A proven trade, ready for `beforeTrade`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedTrade {
+    /// The trade as it was proven. Its user, product and amount are the call's arguments.
+    pub entry: BatchEntry,
+    pub journal: Bytes,
+    pub seal: Bytes,
+}
+
+impl ExportedTrade {
+    /// Calldata of the `beforeTrade` call that settles the trade.
+    pub fn calldata(&self) -> Bytes {
+        beforeTradeCall {
+            user: self.entry.user,
+            productId: self.entry.product_id,
+            amount: self.entry.amount,
+            journal: self.journal.clone(),
+            seal: self.seal.clone(),
+        }
+        .abi_encode()
+        .into()
+    }
+}
+
+/// Proven trades of a run, with where to settle them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettlementBundle {
+    pub version: u32,
+    pub chain_id: u64,
+    /// Address of the compliance hook to call.
+    pub target: Address,
+    /// Image id of the guest the trades were proven with.
+    pub image_id: B256,
+    pub trades: Vec<ExportedTrade>,
+    /// [SettlementBundle::digest] of the rest of the bundle.
+    pub checksum: B256,
+}
+
+impl SettlementBundle {
+    /// Bundle `trades`, proven by the embedded guest, for the hook at `target` on `chain_id`.
+    pub fn new(chain_id: u64, target: Address, trades: Vec<ExportedTrade>) -> Self {
+        let mut bundle = Self {
+            version: SETTLEMENT_BUNDLE_VERSION,
+            chain_id,
+            target,
+            image_id: compliance_image_id(),
+            trades,
+            checksum: B256::ZERO,
+        };
+        bundle.checksum = bundle.digest();
+        bundle
+    }
+
+    /// SHA-256 of the version, the chain id, the target, the image id and the `beforeTrade`
+    /// calldata of each trade in order, each length-prefixed. That is everything settling sends.
+    pub fn digest(&self) -> B256 {
+        let mut hasher = Sha256::new();
+        hasher.update(self.version.to_be_bytes());
+        hasher.update(self.chain_id.to_be_bytes());
+        hasher.update(self.target);
+        hasher.update(self.image_id);
+        hasher.update((self.trades.len() as u64).to_be_bytes());
+        for trade in &self.trades {
+            let calldata = trade.calldata();
+            hasher.update((calldata.len() as u64).to_be_bytes());
+            hasher.update(&calldata);
+        }
+        B256::from_slice(&hasher.finalize())
+    }
+
+    /// Check that the checksum matches the contents, and that each journal commits the bundle's
+    /// image id and the user, product and amount its trade is settled with. The seals are left to
+    /// the verifier contract.
+    pub fn verify(&self) -> Result<()> {
+        ensure!(
+            self.version == SETTLEMENT_BUNDLE_VERSION,
+            "unsupported bundle version {}, expected {SETTLEMENT_BUNDLE_VERSION}",
+            self.version
+        );
+        let digest = self.digest();
+        ensure!(
+            self.checksum == digest,
+            "bundle checksum {} does not match its contents ({digest}); the bundle was altered or \
+             corrupted",
+            self.checksum
+        );
+        for (index, trade) in self.trades.iter().enumerate() {
+            let journal = ComplianceJournal::try_from(trade.journal.as_ref())
+                .with_context(|| format!("invalid journal of trade {index}"))?;
+            ensure!(
+                journal.imageId == self.image_id,
+                "journal of trade {index} commits image id {}, not the bundle's {}",
+                journal.imageId,
+                self.image_id
+            );
+            let entry = &trade.entry;
+            ensure!(
+                (journal.user, journal.productId, journal.tradeAmount)
+                    == (entry.user, entry.product_id, entry.amount),
+                "journal of trade {index} is of user {} product {} amount {}, but the trade is of \
+                 user {} product {} amount {}",
+                journal.user,
+                journal.productId,
+                journal.tradeAmount,
+                entry.user,
+                entry.product_id,
+                entry.amount
+            );
+        }
+        Ok(())
+    }
+
+    /// Check that no trade was decided more than `max_age_secs` before `now`, by the
+    /// `decisionAt` its journal commits. A stale decision may rest on checks that no longer hold,
+    /// or be past the hook's TTL, so it is proven again rather than settled.
+    pub fn ensure_fresh(&self, max_age_secs: u64, now: u64) -> Result<()> {
+        for (index, trade) in self.trades.iter().enumerate() {
+            let journal = ComplianceJournal::try_from(trade.journal.as_ref())
+                .with_context(|| format!("invalid journal of trade {index}"))?;
+            let age = now.saturating_sub(journal.decisionAt);
+            ensure!(
+                age <= max_age_secs,
+                "artifact too old, re-prove: trade {index} was decided at {} ({age}s ago), past \
+                 the {max_age_secs}s limit",
+                format_decision_at(journal.decisionAt)
+            );
+        }
+        Ok(())
+    }
+
+    /// Read the bundle at `path` and [verify](SettlementBundle::verify) it.
+    pub fn read(path: &Path) -> Result<Self> {
+        let json = std::fs::read(path)
+            .with_context(|| format!("failed to read bundle {}", path.display()))?;
+        let bundle: Self = serde_json::from_slice(&json)
+            .with_context(|| format!("{} is not a settlement bundle", path.display()))?;
+        bundle
+            .verify()
+            .with_context(|| format!("invalid bundle {}", path.display()))?;
+        Ok(bundle)
+    }
+
+    /// Write the bundle to `path`, replacing any file there at once.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self).context("failed to encode bundle")?;
+        write_atomically(path, &json)
+            .with_context(|| format!("failed to write bundle {}", path.display()))
+    }
+}
+
+/// Settles by adding each trade to a [SettlementBundle] file instead of calling the hook.
+///
+/// The file is rewritten after every trade, so it always holds the trades exported so far, in the
+/// order they were proven.
+pub struct ExportingSettler {
+    path: PathBuf,
+    chain_id: u64,
+    target: Address,
+    trades: Mutex<Vec<ExportedTrade>>,
+}
+
+impl ExportingSettler {
+    /// Export to `path` for the hook at `target` on `chain_id`, replacing the file's contents.
+    pub fn new(path: impl Into<PathBuf>, chain_id: u64, target: Address) -> Self {
+        Self {
+            path: path.into(),
+            chain_id,
+            target,
+            trades: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl Settler for ExportingSettler {
+    async fn settle(&self, entry: &BatchEntry, journal: Bytes, seal: Bytes) -> Result<Settlement> {
+        let mut trades = self.trades.lock().unwrap();
+        trades.push(ExportedTrade {
+            entry: entry.clone(),
+            journal,
+            seal,
+        });
+        let bundle = SettlementBundle::new(self.chain_id, self.target, trades.clone());
+        if let Err(err) = bundle.write(&self.path) {
+            trades.pop();
+            return Err(err);
+        }
+        tracing::info!(
+            "Exported trade of user {} to {} for another party to settle",
+            entry.user,
+            self.path.display()
+        );
+        Ok(Settlement::Exported {
+            path: self.path.clone(),
+        })
+    }
+}
+
+/// Settle every trade of `bundle` with `settler`, in order. A failed trade does not stop the
+/// others; the results are returned in the bundle's order.
+pub async fn settle_bundle<S: Settler>(
+    bundle: &SettlementBundle,
+    settler: &S,
+) -> Vec<Result<Settlement>> {
+    let mut results = Vec::with_capacity(bundle.trades.len());
+    for (index, trade) in bundle.trades.iter().enumerate() {
+        let settled = settler
+            .settle(&trade.entry, trade.journal.clone(), trade.seal.clone())
+            .await
+            .with_context(|| format!("failed to settle trade {index}"));
+        results.push(settled);
+    }
+    results
+}
@@ -0,0 +1,67 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use reqwest::{RequestBuilder, Response};
+
+/// Timeout and retry policy for calls to external HTTP services.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Timeout of each attempt.
+    pub timeout: Duration,
+    /// Attempts after the first before giving up.
+    pub retries: u32,
+    /// Delay before the first retry, doubled after each further attempt.
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            retries: 3,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Send the request built by `build`, retrying timeouts, connection failures and 5xx
+    /// responses. Other error statuses are returned to the caller without retrying.
+    pub async fn send(&self, mut build: impl FnMut() -> RequestBuilder) -> Result<Response> {
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            let error = match build().timeout(self.timeout).send().await {
+                Ok(response) if !response.status().is_server_error() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    let body = response.text().await.unwrap_or_default();
+                    anyhow!("server responded with {status}: {body}")
+                }
+                Err(err) => anyhow::Error::new(err),
+            };
+
+            if attempt == self.retries {
+                return Err(error.context(format!("giving up after {} attempts", attempt + 1)));
+            }
+            attempt += 1;
+            tracing::warn!("HTTP attempt {attempt} failed, retrying in {backoff:?}: {error:#}");
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+}
@@ -0,0 +1,364 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pinning of proofs and the guest program to IPFS, so a decision can be retrieved and checked
+//! long after the run, whatever happens to the storage provider.
+//!
+//! After a trade settles, [PinningSettler] pins the embedded guest program, once per run, and a
+//! [ProofBundle] of the journal and seal that names the program by its CID. Both go through the
+//! kubo RPC API, which pinning services also serve; their token is sent as a bearer token. The
+//! CIDs are recorded in the [PinStore] and in the trade's [Settlement].
+
+use std::path::PathBuf;
+
+use alloy::primitives::{Address, Bytes, B256};
+use anyhow::{bail, ensure, Context, Result};
+use guests::COMPLIANCE_ELF;
+use risc0_zkvm::compute_image_id;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+use url::Url;
+
+use crate::{
+    batch::BatchEntry,
+    cache::write_atomically,
+    http::RetryPolicy,
+    journal::{compliance_image_id, unix_now, ComplianceJournal},
+    settle::{Settlement, Settler},
+    signing::SignedResult,
+};
+
+/// Separates the file from the rest of an `add` request. Guest programs and bundles never hold
+/// it.
+const BOUNDARY: &str = "compliance-hook-ipfs-0f4e7c1a9b2d";
+
+/// A proven decision as pinned: enough to check the seal onchain, the CID of the program the
+/// journal's image id belongs to and, when it was signed, the operator's signed result.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofBundle {
+    pub image_id: B256,
+    pub program_cid: String,
+    pub journal: Bytes,
+    pub seal: Bytes,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signed_result: Option<SignedResult>,
+}
+
+impl ProofBundle {
+    /// Check the bundle against `program`, the ELF pinned as its `program_cid`: the program must
+    /// have the bundle's image id, and the journal must decode and commit it. A signed result must
+    /// be of the journal; who signed it is for the reader to check. The seal is left to the
+    /// verifier contract.
+    pub fn verify(&self, program: &[u8]) -> Result<()> {
+        let image_id = compute_image_id(program).context("failed to compute program image id")?;
+        let image_id = B256::from(<[u8; 32]>::from(image_id));
+        ensure!(
+            image_id == self.image_id,
+            "program {} has image id {image_id}, not the bundle's {}",
+            self.program_cid,
+            self.image_id
+        );
+        let journal =
+            ComplianceJournal::try_from(self.journal.as_ref()).context("invalid journal")?;
+        ensure!(
+            journal.imageId == self.image_id,
+            "journal commits image id {}, not the bundle's {}",
+            journal.imageId,
+            self.image_id
+        );
+        if let Some(signed) = &self.signed_result {
+            let digest = B256::from_slice(&Sha256::digest(&self.journal));
+            ensure!(
+                signed.journal_digest == digest,
+                "signed result is of journal {}, not the bundle's {digest}",
+                signed.journal_digest
+            );
+        }
+        Ok(())
+    }
+}
+
+/// What an `add` call returns.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Added {
+    hash: String,
+}
+
+/// Client of a kubo RPC API, such as a local node's `http://127.0.0.1:5001`.
+#[derive(Clone, Debug)]
+pub struct IpfsClient {
+    api: Url,
+    token: Option<String>,
+    policy: RetryPolicy,
+    http: reqwest::Client,
+}
+
+impl IpfsClient {
+    pub fn new(api: Url, token: Option<String>) -> Self {
+        Self {
+            api,
+            token,
+            policy: RetryPolicy::default(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Replace the default timeout and retry policy.
+    pub fn with_policy(self, policy: RetryPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    fn endpoint(&self, call: &str) -> String {
+        format!("{}/api/v0/{call}", self.api.as_str().trim_end_matches('/'))
+    }
+
+    /// POST `body` to the API's `call`, retrying transient failures.
+    async fn post(
+        &self,
+        call: &str,
+        query: &[(&str, &str)],
+        body: Option<(&str, Vec<u8>)>,
+    ) -> Result<Vec<u8>> {
+        let url = self.endpoint(call);
+        let response = self
+            .policy
+            .send(|| {
+                let mut request = self.http.post(&url).query(query);
+                if let Some(token) = &self.token {
+                    request = request.bearer_auth(token);
+                }
+                match &body {
+                    Some((content_type, body)) => request
+                        .header(reqwest::header::CONTENT_TYPE, *content_type)
+                        .body(body.clone()),
+                    None => request,
+                }
+            })
+            .await
+            .with_context(|| format!("failed to call {url}"))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("IPFS API rejected {call} with {status}: {body}");
+        }
+        let bytes = response
+            .bytes()
+            .await
+            .with_context(|| format!("failed to read {call} response"))?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Add and pin `contents` as a file called `name`, returning its CID.
+    pub async fn add(&self, name: &str, contents: &[u8]) -> Result<String> {
+        let mut body = format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{name}\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend_from_slice(contents);
+        body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+        let content_type = format!("multipart/form-data; boundary={BOUNDARY}");
+
+        let response = self
+            .post(
+                "add",
+                &[("pin", "true"), ("cid-version", "1")],
+                Some((&content_type, body)),
+            )
+            .await
+            .with_context(|| format!("failed to pin {name}"))?;
+        let added: Added = serde_json::from_slice(&response)
+            .with_context(|| format!("invalid response to pinning {name}"))?;
+        Ok(added.hash)
+    }
+
+    /// Contents of the file with `cid`.
+    pub async fn cat(&self, cid: &str) -> Result<Vec<u8>> {
+        self.post("cat", &[("arg", cid)], None)
+            .await
+            .with_context(|| format!("failed to fetch {cid}"))
+    }
+
+    /// The bundle pinned as `cid`, once it is checked against the program it names; see
+    /// [ProofBundle::verify].
+    pub async fn fetch_bundle(&self, cid: &str) -> Result<ProofBundle> {
+        let bundle: ProofBundle = serde_json::from_slice(&self.cat(cid).await?)
+            .with_context(|| format!("{cid} is not a proof bundle"))?;
+        let program = self.cat(&bundle.program_cid).await?;
+        bundle
+            .verify(&program)
+            .with_context(|| format!("bundle {cid} does not verify"))?;
+        Ok(bundle)
+    }
+}
+
+/// A pinned bundle as recorded in the [PinStore].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinRecord {
+    pub bundle_cid: String,
+    pub program_cid: String,
+    pub image_id: B256,
+    pub user: Address,
+    pub product_id: B256,
+    /// SHA-256 of the journal, as the hook records it.
+    pub journal_digest: B256,
+    /// When the bundle was pinned, in Unix seconds.
+    pub pinned_at: u64,
+}
+
+/// A directory of pinned bundles, one `<cid>.json` file each.
+#[derive(Clone, Debug)]
+pub struct PinStore {
+    dir: PathBuf,
+}
+
+impl PinStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, cid: &str) -> PathBuf {
+        self.dir.join(format!("{cid}.json"))
+    }
+
+    /// The recorded pin of bundle `cid`, if there is one.
+    pub fn get(&self, cid: &str) -> Result<Option<PinRecord>> {
+        let path = self.path(cid);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read pin {}", path.display()))
+            }
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .with_context(|| format!("failed to parse pin {}", path.display()))
+    }
+
+    /// Record `record`, replacing any earlier record of its bundle.
+    pub fn insert(&self, record: &PinRecord) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create pin store {}", self.dir.display()))?;
+        let path = self.path(&record.bundle_cid);
+        write_atomically(&path, &serde_json::to_vec_pretty(record)?)
+            .with_context(|| format!("failed to write pin {}", path.display()))
+    }
+}
+
+/// Settles with `settler` and then pins the proof bundle and the program. A bundle that cannot be
+/// pinned leaves the trade settled with a warning, unless pinning is required.
+pub struct PinningSettler<S> {
+    settler: S,
+    ipfs: IpfsClient,
+    store: PinStore,
+    program_cid: OnceCell<String>,
+    require: bool,
+}
+
+impl<S> PinningSettler<S> {
+    /// Pin bundles through `ipfs`, recording them in `store`.
+    pub fn new(settler: S, ipfs: IpfsClient, store: PinStore) -> Self {
+        Self {
+            settler,
+            ipfs,
+            store,
+            program_cid: OnceCell::new(),
+            require: false,
+        }
+    }
+
+    /// Fail a settled trade whose bundle cannot be pinned, instead of warning.
+    pub fn requiring_pin(mut self, require: bool) -> Self {
+        self.require = require;
+        self
+    }
+
+    async fn pin(
+        &self,
+        entry: &BatchEntry,
+        journal: Bytes,
+        seal: Bytes,
+        signed_result: Option<SignedResult>,
+    ) -> Result<PinRecord> {
+        let image_id = ComplianceJournal::try_from(journal.as_ref())
+            .context("invalid journal")?
+            .imageId;
+        // The embedded program is the one pinned, so a bundle of another's journal would not
+        // verify.
+        ensure!(
+            image_id == compliance_image_id(),
+            "journal commits image id {image_id}, not the embedded guest program's"
+        );
+        let program_cid = self
+            .program_cid
+            .get_or_try_init(|| self.ipfs.add("program.elf", COMPLIANCE_ELF))
+            .await?
+            .clone();
+        let journal_digest = B256::from_slice(&Sha256::digest(&journal));
+        let bundle = ProofBundle {
+            image_id,
+            program_cid: program_cid.clone(),
+            journal,
+            seal,
+            signed_result,
+        };
+        let bundle_cid = self
+            .ipfs
+            .add("bundle.json", &serde_json::to_vec(&bundle)?)
+            .await?;
+        let record = PinRecord {
+            bundle_cid,
+            program_cid,
+            image_id,
+            user: entry.user,
+            product_id: entry.product_id,
+            journal_digest,
+            pinned_at: unix_now(),
+        };
+        self.store.insert(&record)?;
+        Ok(record)
+    }
+}
+
+impl<S: Settler> Settler for PinningSettler<S> {
+    async fn settle(&self, entry: &BatchEntry, journal: Bytes, seal: Bytes) -> Result<Settlement> {
+        let settlement = self
+            .settler
+            .settle(entry, journal.clone(), seal.clone())
+            .await?;
+        let signed_result = settlement.signed_result().cloned();
+        match self.pin(entry, journal, seal, signed_result).await {
+            Ok(record) => {
+                tracing::info!("Pinned proof bundle {}", record.bundle_cid);
+                Ok(Settlement::Pinned {
+                    bundle_cid: record.bundle_cid,
+                    program_cid: record.program_cid,
+                    settlement: Box::new(settlement),
+                })
+            }
+            Err(err) if self.require => {
+                Err(err.context(format!("settled as {settlement:?}, but failed to pin")))
+            }
+            Err(err) => {
+                tracing::warn!("Settled as {settlement:?}, but failed to pin: {err:#}");
+                Ok(settlement)
+            }
+        }
+    }
+}
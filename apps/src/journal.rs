@@ -0,0 +1,304 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fmt::Write,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alloy::primitives::{b256, B256, U256};
+use anyhow::{bail, ensure, Context, Result};
+use boundless_market::contracts::{Fulfillment, FulfillmentData};
+pub use compliance_core::{
+    address_leaf, aggregate_attestation_hash, aggregate_attestation_hash_with, attestation_hash,
+    attestation_hashes, batch_pages, cohort_leaf, evaluate, fold_digest, gap_leaf, governance_hash,
+    hash_pair, is_in_cohort, is_member, is_non_member, kyc_tier_from_passed, merkle_root,
+    merkle_root_with, override_hash, BatchFold, BatchJournal, CommitmentHash, ComplianceInput,
+    ComplianceJournal, InputError, JournalEncoding, JournalError, Keccak, MerkleFrontier,
+    MerkleHash, MerkleTree, PageError, ReasonCode, Regime, Sha256, AML_SOURCE_ADVERSE_MEDIA,
+    AML_SOURCE_PEP, AML_SOURCE_SANCTIONS, CLAIM_MAS_KNOWLEDGE_ASSESSMENT,
+    CLAIM_MICA_APPROPRIATENESS, CLAIM_SEC_ACCREDITED_INVESTOR, GOVERNANCE_SET_LEN,
+    JOURNAL_SCHEMA_HASH, JOURNAL_SCHEMA_VERSION, KYC_PROVIDER_SET_LEN, MAX_BATCH_PAGES,
+    MAX_PAGE_INPUTS, SHA256_COMMITMENTS,
+};
+use guests::COMPLIANCE_ID;
+use risc0_zkvm::sha::Digest;
+
+/// Image id of the embedded compliance guest, as committed to the journal.
+pub fn compliance_image_id() -> B256 {
+    B256::from_slice(Digest::from(COMPLIANCE_ID).as_bytes())
+}
+
+/// Check that a journal was committed by the embedded compliance guest.
+pub fn ensure_image_id(journal: &[u8]) -> Result<()> {
+    let committed = ComplianceJournal::try_from(journal)
+        .context("invalid journal")?
+        .imageId;
+    let expected = compliance_image_id();
+    if committed != expected {
+        bail!("journal commits image id {committed}, expected COMPLIANCE_ELF id {expected}");
+    }
+    Ok(())
+}
+
+/// A journal layout with a published document, and how journals in it are decoded.
+#[derive(Clone, Copy, Debug)]
+pub struct JournalSchema {
+    /// The document's `version`.
+    pub version: u32,
+    /// SHA-256 of the document, as committed to the journal.
+    pub hash: B256,
+    pub decode: fn(&[u8]) -> Result<ComplianceJournal, JournalError>,
+}
+
+/// Every journal layout this build decodes. Journals in an older layout only decode once its
+/// decoder is added here.
+pub const JOURNAL_SCHEMAS: [JournalSchema; 3] = [
+    JournalSchema {
+        version: 1,
+        hash: b256!("0xee2a21db295e784ca465f870054ff5e8fbe4321821d87c12614ad2b2c5f88575"),
+        decode: decode_v1,
+    },
+    JournalSchema {
+        version: 2,
+        hash: b256!("0x1fffe2749623bc9bfb3517a91e719dabd457f01921d1ed5f06b4fb43ee5f59da"),
+        decode: decode_v2,
+    },
+    JournalSchema {
+        version: JOURNAL_SCHEMA_VERSION,
+        hash: JOURNAL_SCHEMA_HASH,
+        decode: decode_current,
+    },
+];
+
+impl JournalSchema {
+    /// Schema of `journal`, by the schema hash it commits. The hash is the second-to-last word of
+    /// every layout and encoding, so it is found before knowing where any other field is.
+    pub fn of(journal: &[u8]) -> Result<Self> {
+        let len = journal.len();
+        ensure!(
+            len >= 64,
+            "journal is {len} bytes, too short to commit a schema hash"
+        );
+        let hash = B256::from_slice(&journal[len - 64..len - 32]);
+        JOURNAL_SCHEMAS
+            .into_iter()
+            .find(|schema| schema.hash == hash)
+            .with_context(|| format!("journal commits unknown schema hash {hash}"))
+    }
+}
+
+fn decode_current(journal: &[u8]) -> Result<ComplianceJournal, JournalError> {
+    ComplianceJournal::try_from(journal)
+}
+
+/// Decode a journal of layout 1, which ends `kycProvider`, `schemaHash`, `imageId`. Zeros are
+/// put where `consentGiven` and `consentAt` go since, so it decodes as one with no consent.
+fn decode_v1(journal: &[u8]) -> Result<ComplianceJournal, JournalError> {
+    // Where the consent fields go, and how many bytes they take, in each encoding.
+    let (at, len) = match journal.len() {
+        1088 => (32 * 32, 2 * 32),
+        639 => (575, 1 + 8),
+        len => return Err(JournalError::Length(len)),
+    };
+    let mut widened = journal.to_vec();
+    widened.splice(at..at, std::iter::repeat_n(0, len));
+    decode_v2(&widened)
+}
+
+/// Decode a journal of layout 2, which ends `consentAt`, `schemaHash`, `imageId`. A zero is put
+/// where `attestationSignatureHash` goes since, so it decodes as one whose attestation, if it
+/// counted one, is not bound to the proof.
+fn decode_v2(journal: &[u8]) -> Result<ComplianceJournal, JournalError> {
+    let at = match journal.len() {
+        1152 => 34 * 32,
+        648 => 584,
+        len => return Err(JournalError::Length(len)),
+    };
+    let mut widened = journal.to_vec();
+    widened.splice(at..at, [0; 32]);
+    ComplianceJournal::try_from(widened.as_slice())
+}
+
+/// Journal carried by the fulfillment, if the request asked for it.
+pub fn fulfillment_journal(fulfillment: &Fulfillment) -> Result<Option<Vec<u8>>> {
+    match fulfillment
+        .data()
+        .context("failed to decode fulfillment data")?
+    {
+        FulfillmentData::ImageIdAndJournal(_, journal) => Ok(Some(journal.to_vec())),
+        _ => Ok(None),
+    }
+}
+
+/// Current Unix time in seconds, supplied to the guest as the decision time.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before the Unix epoch")
+        .as_secs()
+}
+
+/// Render a committed `decisionAt` as an RFC 3339 UTC date.
+pub fn format_decision_at(decision_at: u64) -> String {
+    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(decision_at)).to_string()
+}
+
+/// Render an amount in base units as whole units of a token with `decimals` decimals, e.g.
+/// `1.5` for `1500000000000000000` at 18. Trailing zeros of the fraction are dropped; at zero
+/// decimals the amount prints as is.
+pub fn format_amount(amount: U256, decimals: u8) -> String {
+    let Some(unit) = U256::from(10).checked_pow(U256::from(decimals)) else {
+        return amount.to_string();
+    };
+    let (whole, fraction) = amount.div_rem(unit);
+    if fraction.is_zero() {
+        return whole.to_string();
+    }
+    let width = usize::from(decimals);
+    let fraction = format!("{:0>width$}", fraction.to_string());
+    format!("{whole}.{}", fraction.trim_end_matches('0'))
+}
+
+/// Human-readable account of a committed decision, one field per line.
+pub fn explain(journal: &ComplianceJournal) -> String {
+    explain_with_decimals(journal, 0)
+}
+
+/// [explain], with the trade amount and size band in whole units of a token with `decimals`
+/// decimals. See [format_amount].
+pub fn explain_with_decimals(journal: &ComplianceJournal, decimals: u8) -> String {
+    let reasons = ReasonCode::from_mask(journal.reasons)
+        .map(ReasonCode::name)
+        .collect::<Vec<_>>();
+    let mut out = String::new();
+    writeln!(out, "user:        {}", journal.user).unwrap();
+    writeln!(out, "product:     {}", journal.productId).unwrap();
+    writeln!(out, "policy:      v{}", journal.productPolicyVersion).unwrap();
+    if journal.quoteProduct != journal.productId {
+        writeln!(
+            out,
+            "quote:       {} (policy v{})",
+            journal.quoteProduct, journal.quotePolicyVersion
+        )
+        .unwrap();
+    }
+    if journal.regimeId != Regime::None as u16 {
+        let regime = Regime::from_id(journal.regimeId).map_or("unknown", Regime::name);
+        writeln!(out, "regime:      {regime} ({})", journal.regimeId).unwrap();
+    }
+    if !journal.settlementToken.is_zero() {
+        writeln!(out, "settles in:  {}", journal.settlementToken).unwrap();
+    }
+    if !journal.caseId.is_zero() {
+        writeln!(out, "case:        {}", journal.caseId).unwrap();
+    }
+    if !journal.decisionUuid.is_zero() {
+        writeln!(out, "decision:    {}", journal.decisionUuid).unwrap();
+    }
+    if !journal.credentialHash.is_zero() {
+        writeln!(out, "credential:  {}", journal.credentialHash).unwrap();
+    }
+    if !journal.blockHash.is_zero() {
+        writeln!(out, "block hash:  {}", journal.blockHash).unwrap();
+    }
+    writeln!(
+        out,
+        "amount:      {}",
+        format_amount(journal.tradeAmount, decimals)
+    )
+    .unwrap();
+    if !journal.minTrade.is_zero() || journal.maxTrade != U256::MAX {
+        writeln!(
+            out,
+            "size band:   {} to {}",
+            format_amount(journal.minTrade, decimals),
+            format_amount(journal.maxTrade, decimals)
+        )
+        .unwrap();
+    }
+    writeln!(out, "allowed:     {}", journal.allowed).unwrap();
+    if journal.needsReview {
+        writeln!(out, "review:      required").unwrap();
+    }
+    if !reasons.is_empty() {
+        writeln!(out, "reasons:     {}", reasons.join(", ")).unwrap();
+    }
+    writeln!(out, "kyc tier:    {}", journal.kycTier).unwrap();
+    if journal.kycProvider != 0 {
+        writeln!(out, "kyc provider: {}", journal.kycProvider).unwrap();
+    }
+    if journal.consentGiven {
+        writeln!(
+            out,
+            "consent:     given at {} ({})",
+            format_decision_at(journal.consentAt),
+            journal.consentAt
+        )
+        .unwrap();
+    }
+    writeln!(out, "claims:      {:#b}", journal.claims).unwrap();
+    if !journal.amlSources.is_zero() {
+        writeln!(out, "aml sources: {:#b}", journal.amlSources).unwrap();
+    }
+    if !journal.attester.is_zero() {
+        writeln!(out, "attester:    {}", journal.attester).unwrap();
+        writeln!(out, "attestation: {}", journal.attestationHash).unwrap();
+        if !journal.attestationSignatureHash.is_zero() {
+            writeln!(out, "signature:   {}", journal.attestationSignatureHash).unwrap();
+        }
+    }
+    if !journal.overrideSigner.is_zero() {
+        writeln!(out, "override:    {}", journal.overrideSigner).unwrap();
+    }
+    writeln!(out, "provenance:  {}", journal.attestationsHash).unwrap();
+    let commitments =
+        CommitmentHash::from_id(journal.commitmentHash).map_or("unknown", CommitmentHash::name);
+    writeln!(out, "commitments: {commitments}").unwrap();
+    if journal.requiredAttestations != 0 {
+        writeln!(
+            out,
+            "quorum:      {} of {} attestations",
+            journal.achievedAttestations, journal.requiredAttestations
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "decided at:  {} ({})",
+        format_decision_at(journal.decisionAt),
+        journal.decisionAt
+    )
+    .unwrap();
+    if journal.notBefore != 0 {
+        writeln!(
+            out,
+            "not before:  {} ({})",
+            format_decision_at(journal.notBefore),
+            journal.notBefore
+        )
+        .unwrap();
+    }
+    if journal.ttlSecs != 0 {
+        writeln!(
+            out,
+            "valid until: {} ({}s)",
+            format_decision_at(journal.decisionAt.saturating_add(journal.ttlSecs)),
+            journal.ttlSecs
+        )
+        .unwrap();
+    }
+    writeln!(out, "image id:    {}", journal.imageId).unwrap();
+    out
+}
@@ -0,0 +1,66 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Host-side building blocks for the compliance trading CLI.
+
+pub mod attestation;
+pub mod batch;
+pub mod broadcast;
+pub mod cache;
+pub mod chains;
+pub mod cli;
+pub mod compare;
+pub mod credential;
+pub mod eas;
+pub mod ens;
+pub mod executor;
+pub mod fixtures;
+pub mod forge;
+pub mod handoff;
+pub mod http;
+pub mod ipfs;
+pub mod journal;
+pub mod local;
+pub mod market;
+pub mod merkle;
+pub mod notify;
+pub mod pool;
+pub mod preflight;
+pub mod program;
+pub mod relay;
+pub mod relayer;
+pub mod report;
+pub mod schema;
+pub mod screening;
+pub mod settle;
+pub mod signing;
+pub mod webhook;
+
+/// Rust bindings for the `IComplianceHook` contract interface.
+pub mod compliance_hook {
+    alloy::sol!(
+        #![sol(rpc, all_derives)]
+        "../contracts/src/IComplianceHook.sol"
+    );
+}
+
+/// Rust bindings for the `IComplianceReceiver` contract interface.
+pub mod compliance_receiver {
+    alloy::sol!(
+        #![sol(rpc, all_derives)]
+        "../contracts/src/IComplianceReceiver.sol"
+    );
+}
+
+pub use market::{prove_compliance, race_compliance, MarketClient, FULFILLMENT_CHECK_INTERVAL};
@@ -0,0 +1,224 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Local proving: trades proven on this machine instead of on the Boundless Market.
+//!
+//! [LocalProver] stands in for a market, so single trades and batches go through the same
+//! pipeline as market requests. Proofs run on blocking threads, at most `jobs` at once. Each job
+//! proves one segment at a time, so prover memory grows with the number of jobs and the segment
+//! limit. [prove_jobs] picks a number of jobs that fits a memory budget. Cancelling the prover's
+//! token fails every proof still waiting for a job. A proof that is already running cannot be
+//! interrupted; it is abandoned and its thread stops when the proof ends.
+//!
+//! [prove_batch] instead proves a whole batch in one execution of the batch guest, for the hook's
+//! `recordBatch`.
+
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use alloy::primitives::{Address, Bytes, B256, U256};
+use anyhow::{anyhow, bail, Context, Result};
+use boundless_market::{
+    contracts::{Fulfillment, FulfillmentData},
+    request_builder::RequestParams,
+};
+use guests::{BATCH_ELF, COMPLIANCE_ELF, COMPLIANCE_ID};
+use risc0_ethereum_contracts::encode_seal;
+use risc0_zkvm::{default_prover, ProverOpts};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    executor::{ExecutorOptions, DEFAULT_SEGMENT_PO2},
+    market::MarketClient,
+};
+
+/// Rough prover memory per cycle of segment: about 9 GiB for a 2^20-cycle segment on the CPU
+/// prover.
+const PROVER_BYTES_PER_CYCLE: u64 = 9 << 10;
+
+/// Estimated prover memory, in MiB, of one job proving segments of up to `2^po2` cycles.
+pub fn segment_memory_mib(po2: u32) -> u64 {
+    (PROVER_BYTES_PER_CYCLE << po2) >> 20
+}
+
+/// Jobs to prove with: `requested` if given, otherwise one per available core. With a
+/// `memory_limit_mib` the count is lowered until every job's [segment_memory_mib] fits, but never
+/// below one.
+pub fn prove_jobs(
+    requested: Option<NonZeroUsize>,
+    memory_limit_mib: Option<u64>,
+    options: &ExecutorOptions,
+) -> NonZeroUsize {
+    let jobs = requested
+        .unwrap_or_else(|| std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN));
+    let Some(limit) = memory_limit_mib else {
+        return jobs;
+    };
+    let per_job = segment_memory_mib(options.segment_limit_po2.unwrap_or(DEFAULT_SEGMENT_PO2));
+    let fit = usize::try_from(limit / per_job).unwrap_or(usize::MAX);
+    jobs.min(NonZeroUsize::new(fit).unwrap_or(NonZeroUsize::MIN))
+}
+
+/// Prove a paged batch with the batch guest on this machine, returning its journal and the seal
+/// encoded for the verifier router. The pages and `aggregate_cap` are written as
+/// [ExecutorOptions::paged_env_builder] writes them.
+pub async fn prove_batch(
+    options: ExecutorOptions,
+    pages: Vec<Vec<u8>>,
+    aggregate_cap: U256,
+) -> Result<(Bytes, Bytes)> {
+    tracing::info!("Proving a batch of {} pages locally", pages.len());
+    let started = Instant::now();
+    let span = tracing::Span::current();
+    let info = tokio::task::spawn_blocking(move || {
+        span.in_scope(|| {
+            let env = options
+                .paged_env_builder(&pages, aggregate_cap)?
+                .build()
+                .context("failed to build executor env")?;
+            default_prover().prove_with_opts(env, BATCH_ELF, &ProverOpts::groth16())
+        })
+    })
+    .await
+    .map_err(|err| anyhow!("prover thread failed: {err}"))??;
+    tracing::info!(
+        "Proved the batch in {:.1?}: {} user cycles in {} segments",
+        started.elapsed(),
+        info.stats.user_cycles,
+        info.stats.segments
+    );
+    let seal = encode_seal(&info.receipt)?;
+    Ok((info.receipt.journal.bytes.into(), seal.into()))
+}
+
+/// A [MarketClient] that proves every request itself once it is waited on.
+///
+/// Requests are accepted from either channel and never expire, and keep the id they carry or are
+/// numbered in submission order. The fulfillment carries the journal and the seal encoded for the
+/// verifier router, which is the mock verifier's seal in dev mode.
+pub struct LocalProver {
+    jobs: Semaphore,
+    options: ExecutorOptions,
+    cancel: CancellationToken,
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<U256, Vec<u8>>>,
+}
+
+impl LocalProver {
+    pub fn new(jobs: NonZeroUsize, options: ExecutorOptions, cancel: CancellationToken) -> Self {
+        Self {
+            jobs: Semaphore::new(jobs.get()),
+            options,
+            cancel,
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn submit(&self, request: RequestParams) -> Result<(U256, u64)> {
+        let stdin = request
+            .env
+            .map(|env| env.stdin)
+            .context("request has no guest input")?;
+        let request_id = match request.request_id {
+            Some(request_id) => request_id.into(),
+            None => U256::from(self.next_id.fetch_add(1, Ordering::Relaxed)),
+        };
+        self.pending.lock().unwrap().insert(request_id, stdin);
+        Ok((request_id, u64::MAX))
+    }
+
+    async fn prove(&self, request_id: U256, stdin: Vec<u8>) -> Result<Fulfillment> {
+        let _permit = tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => bail!("cancelled before proving started"),
+            permit = self.jobs.acquire() => permit.expect("semaphore is never closed"),
+        };
+        tracing::info!("Proving request {request_id:x} locally");
+        let started = Instant::now();
+        let options = self.options;
+        let span = tracing::Span::current();
+        let proving = tokio::task::spawn_blocking(move || {
+            span.in_scope(|| {
+                let env = options
+                    .env_builder()
+                    .write_slice(&stdin)
+                    .build()
+                    .context("failed to build executor env")?;
+                default_prover().prove_with_opts(env, COMPLIANCE_ELF, &ProverOpts::groth16())
+            })
+        });
+        let info = tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => bail!("cancelled while proving"),
+            joined = proving => joined.map_err(|err| anyhow!("prover thread failed: {err}"))??,
+        };
+        tracing::info!(
+            "Proved request {request_id:x} in {:.1?}: {} user cycles in {} segments",
+            started.elapsed(),
+            info.stats.user_cycles,
+            info.stats.segments
+        );
+
+        let seal = encode_seal(&info.receipt)?;
+        let (fulfillment_data_type, fulfillment_data) =
+            FulfillmentData::from_image_id_and_journal(COMPLIANCE_ID, info.receipt.journal.bytes)
+                .fulfillment_type_and_data();
+        Ok(Fulfillment {
+            id: request_id,
+            requestDigest: B256::ZERO,
+            claimDigest: B256::ZERO,
+            fulfillmentDataType: fulfillment_data_type,
+            fulfillmentData: fulfillment_data.into(),
+            seal: Bytes::from(seal),
+        })
+    }
+}
+
+impl MarketClient for LocalProver {
+    async fn submit_onchain(&self, request: RequestParams) -> Result<(U256, u64)> {
+        self.submit(request)
+    }
+
+    async fn submit_offchain(&self, request: RequestParams) -> Result<(U256, u64)> {
+        self.submit(request)
+    }
+
+    async fn wait_for_request_fulfillment(
+        &self,
+        request_id: U256,
+        _check_interval: Duration,
+        _expires_at: u64,
+    ) -> Result<Fulfillment> {
+        let stdin = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(&request_id)
+            .with_context(|| format!("request {request_id:x} was not submitted"))?;
+        self.prove(request_id, stdin).await
+    }
+
+    async fn fulfillment_prover(&self, _request_id: U256) -> Result<Option<Address>> {
+        Ok(None)
+    }
+}
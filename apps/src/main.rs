@@ -12,64 +12,72 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::Duration;
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use crate::compliance_hook::IComplianceHook::IComplianceHookInstance;
 use alloy::{
-    primitives::{Address, Bytes, B256, U256},
-    signers::local::PrivateKeySigner,
-    sol_types::SolValue,
+    primitives::{utils::format_ether, Address, B256, U256},
+    providers::{DynProvider, Provider, ProviderBuilder},
+};
+use anyhow::{bail, ensure, Context, Result};
+use app::{
+    attestation::{fetch_compliance, AttestationSource, HttpSource},
+    batch::{
+        prove_entry, read_entries, run_batch, BatchEntry, BatchOptions, Concurrency, QuoteLeg,
+        ReviewBand,
+    },
+    broadcast::deployed_hook,
+    cache::ProofCache,
+    chains::{ChainConfig, ChainsConfig},
+    cli::{
+        env_example, examples, Args, BundleCommand, ChainsCommand, Command, MerkleCommand,
+        ProgramSource, RelayCommand, ReportCommand,
+    },
+    compare::compare_guests,
+    compliance_hook::IComplianceHook::IComplianceHookInstance,
+    credential::CredentialSource,
+    eas::{AttestationStore, AttestingSettler, EasAttester},
+    ens::{EnsResolver, NameOrAddress},
+    executor::format_profile,
+    fixtures::{Fixture, FixtureSpec},
+    forge::{bundle_name, ForgeFixtures, FORGE_FIXTURES_JSON, FORGE_FIXTURES_LIBRARY},
+    handoff::{settle_bundle, ExportingSettler, SettlementBundle},
+    ipfs::{IpfsClient, PinStore, PinningSettler},
+    journal::{
+        batch_pages, compliance_image_id, explain_with_decimals, format_amount, unix_now,
+        BatchFold, ComplianceInput, ComplianceJournal,
+    },
+    local::{prove_batch, prove_jobs, LocalProver},
+    market::{request_index, TrustedProvers},
+    merkle::{cohort_proof, parse_addresses, AddressProof, TreeFile, TreeKind},
+    notify::{Alert, Links, Notifications, PagerDutyNotifier, SlackNotifier},
+    preflight::{check_balance, estimate_proof_cost},
+    program::{
+        check_program_image_id, check_program_url, find_program, upload_program, IndexedStorage,
+        ProgramRef,
+    },
+    relay::{ensure_relayed, retry_relays, Relay, RelayStore, RelayingSettler},
+    relayer::RelayerClient,
+    report::{merge_reports, read_report, write_report, ReportRow},
+    schema::{input_schema, output_schema},
+    screening::{screen_entries, Screened, ScreeningConfig, ScreeningSource},
+    settle::{CachedDecision, DelayingSettler, HookSettler, RelayerSettler, Settler, TX_TIMEOUT},
+    signing::{ResultDomain, ResultSigner, SignedResult, SigningSettler},
+    webhook::WebhookSink,
+    MarketClient,
 };
-use anyhow::{bail, Context, Result};
-use boundless_market::{Client, Deployment, StorageProviderConfig};
-use clap::Parser;
+use boundless_market::{
+    contracts::RequestId,
+    request_builder::{OfferLayerConfig, RequestParams},
+    Client, Deployment, StandardClient, StandardStorageProvider,
+};
+use clap::{CommandFactory, Parser};
+use compliance_core::CycleProfile;
 use guests::COMPLIANCE_ELF;
-use url::Url;
-
-/// Timeout for the transaction to be confirmed.
-pub const TX_TIMEOUT: Duration = Duration::from_secs(30);
-
-mod compliance_hook {
-    alloy::sol!(
-        #![sol(rpc, all_derives)]
-        "../contracts/src/IComplianceHook.sol"
-    );
-}
-
-/// Arguments of the compliance trading CLI.
-#[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
-struct Args {
-    /// Trade amount for the RWA asset.
-    #[clap(short, long, env)]
-    amount: u32,
-    /// URL of the Ethereum RPC endpoint.
-    #[clap(short, long, env)]
-    rpc_url: Url,
-    /// Private key used to interact with the compliance hook contract and the Boundless Market.
-    #[clap(long, env)]
-    private_key: PrivateKeySigner,
-    /// Address of the compliance hook contract.
-    #[clap(short, long, env)]
-    compliance_hook_address: Address,
-    #[clap(long, env)]
-    user: Address,
-    #[clap(long, env)]
-    product_id: B256,
-    #[clap(long, env)]
-    kyc_passed: bool,
-    #[clap(long, env)]
-    aml_passed: bool,
-    #[clap(long, env)]
-    program_url: Option<Url>,
-    #[clap(short, long, requires = "order_stream_url")]
-    offchain: bool,
-    #[clap(flatten, next_help_heading = "Storage Provider")]
-    storage_config: StorageProviderConfig,
-
-    #[clap(flatten, next_help_heading = "Boundless Market Deployment")]
-    deployment: Option<Deployment>,
-}
+use tokio_util::sync::CancellationToken;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -82,72 +90,1304 @@ async fn main() -> Result<()> {
         Err(e) if e.not_found() => tracing::debug!("No .env file found"),
         Err(e) => bail!("failed to load .env file: {}", e),
     }
-    let args = Args::parse();
+    let mut args = Args::parse();
+    if let Some(Command::Chains {
+        command: ChainsCommand::List,
+    }) = &args.command
+    {
+        return list_chains(&args).await;
+    }
+    let chain = select_chain(&mut args)?;
+    let offline = matches!(
+        args.command,
+        Some(
+            Command::Examples
+                | Command::DumpEnvExample { .. }
+                | Command::Schema { .. }
+                | Command::Report { .. }
+                | Command::Merkle { .. }
+                | Command::Bundle { .. }
+                | Command::VerifySignedResult { .. }
+                | Command::ExportForgeFixtures { .. }
+        )
+    );
+    if !offline {
+        if let (Some((name, chain)), Some(rpc_url)) = (&chain, &args.rpc_url) {
+            chain
+                .ensure_chain_id(&ProviderBuilder::new().connect_http(rpc_url.clone()))
+                .await
+                .with_context(|| format!("--chain {name}"))?;
+        }
+        resolve_names(&mut args).await?;
+        hook_from_broadcast(&mut args).await?;
+    }
+    match args.command {
+        Some(Command::Examples) => {
+            print!("{}", examples(&Args::command()));
+            return Ok(());
+        }
+        Some(Command::DumpEnvExample { out }) => {
+            let template = env_example(&Args::command());
+            match out {
+                Some(out) => {
+                    std::fs::write(&out, template)
+                        .with_context(|| format!("failed to write {}", out.display()))?;
+                    eprintln!("wrote {}", out.display());
+                }
+                None => print!("{template}"),
+            }
+            return Ok(());
+        }
+        Some(Command::Schema { output }) => {
+            let schema = if output {
+                output_schema()
+            } else {
+                input_schema()
+            };
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            return Ok(());
+        }
+        Some(Command::RevokeAttestation { uid }) => return revoke_attestation(&args, uid).await,
+        Some(Command::Status {
+            ref user,
+            product_id,
+        }) => return status(&args, user.address()?, product_id).await,
+        Some(Command::Report {
+            command: ReportCommand::Merge { reports, out },
+        }) => return merge(&reports, &out),
+        Some(Command::Merkle { command }) => return merkle(command),
+        Some(Command::Bundle {
+            command: BundleCommand::Fetch { cid, out },
+        }) => return fetch_bundle(&args, &cid, out.as_deref()).await,
+        Some(Command::VerifySignedResult { file, operator }) => {
+            return verify_signed_result(&file, operator)
+        }
+        Some(Command::SettleBundle { file }) => return settle_bundle_file(&args, &file).await,
+        Some(Command::RecordBatch {
+            ref file,
+            aggregate_cap,
+        }) => return record_batch(&args, file, aggregate_cap).await,
+        Some(Command::ExportForgeFixtures {
+            ref bundles,
+            ref spec,
+            ref out,
+        }) => return export_forge_fixtures(&args, bundles, spec.as_deref(), out),
+        Some(Command::Relay {
+            command: RelayCommand::Retry,
+        }) => return retry_relay(&args).await,
+        Some(Command::Chains { .. }) => unreachable!("chains commands return before this"),
+        Some(Command::ExportBundle { .. }) | None => {}
+    }
+    if args.dry_run {
+        return dry_run(&args).await;
+    }
+    if args.estimate_proof_cost {
+        return print_proof_cost(&args).await;
+    }
+    if let Some(elf) = &args.compare_guest {
+        return compare_guest(&args, elf).await;
+    }
 
-    let client = Client::builder()
-        .with_rpc_url(args.rpc_url)
-        .with_deployment(args.deployment)
-        .with_storage_provider_config(&args.storage_config)?
-        .with_private_key(args.private_key)
-        .build()
+    let client = build_client(&args, args.deployment.clone())
         .await
         .context("failed to build boundless client")?;
+    let mut markets = vec![client.clone()];
+    for (index, market) in args.redundant_markets.iter().enumerate() {
+        let deployment = market.deployment().clone();
+        markets.push(
+            build_client(&args, Some(deployment))
+                .await
+                .with_context(|| {
+                    format!("failed to build client for redundant market #{}", index + 1)
+                })?,
+        );
+    }
 
-    tracing::info!("Attempting trade with amount: {}", args.amount);
-    type Input = (Address, B256, bool, bool);
-    let input = (args.user, args.product_id, args.kyc_passed, args.aml_passed);
-    let input_bytes = <Input>::abi_encode(&input);
-
-    let request = if let Some(program_url) = args.program_url {
-        // Use the provided URL
-        client
-            .new_request()
-            .with_program_url(program_url)?
-            .with_stdin(input_bytes.clone())
+    if let Some(Command::ExportBundle { out }) = &args.command {
+        let chain_id = client
+            .provider()
+            .get_chain_id()
+            .await
+            .context("failed to query chain ID")?;
+        tracing::info!("Exporting proven trades to {}", out.display());
+        let settler = ExportingSettler::new(out, chain_id, hook_address(&args)?);
+        return prove_with(&client, markets, settler, &args).await;
+    }
+    if args.attest_only {
+        let attester = eas_attester(&args, client.provider().clone(), client.caller())?;
+        let settler = AttestingSettler::<HookSettler<DynProvider>, _>::new(None, attester);
+        return sign_with(&client, markets, settler, &args).await;
+    }
+    if let Some(relayer_url) = args.relayer.clone() {
+        let chain_id = client
+            .provider()
+            .get_chain_id()
+            .await
+            .context("failed to query chain ID")?;
+        tracing::info!("Settling through relayer {relayer_url}");
+        let relayer = RelayerClient::new(relayer_url, args.relayer_api_key.clone());
+        let settler = RelayerSettler::new(relayer, chain_id, hook_address(&args)?);
+        attest_with(&client, markets, DelayingSettler::new(settler), &args).await
     } else {
-        client
-            .new_request()
-            .with_program(COMPLIANCE_ELF)
-            .with_stdin(input_bytes)
+        let settler = HookSettler::new(
+            hook_address(&args)?,
+            client.provider().clone(),
+            client.caller(),
+        )
+        .with_verification(args.verify_after_settle)
+        .with_seal_check(!args.skip_seal_check);
+        attest_with(&client, markets, DelayingSettler::new(settler), &args).await
+    }
+}
+
+/// Run with `hook`, followed under `--attest-eas` by an EAS attestation of each decision.
+async fn attest_with<S: Settler + 'static>(
+    client: &StandardClient,
+    markets: Vec<StandardClient>,
+    hook: S,
+    args: &Args,
+) -> Result<()> {
+    if !args.attest_eas {
+        return sign_with(client, markets, hook, args).await;
+    }
+    let attester = eas_attester(args, client.provider().clone(), client.caller())?;
+    let settler = AttestingSettler::new(Some(hook), attester);
+    sign_with(client, markets, settler, args).await
+}
+
+/// Run with `settler`, preceded under `--sign-results` by signing each proven result.
+async fn sign_with<S: Settler + 'static>(
+    client: &StandardClient,
+    markets: Vec<StandardClient>,
+    settler: S,
+    args: &Args,
+) -> Result<()> {
+    if !args.sign_results {
+        return relay_with(client, markets, settler, args).await;
+    }
+    let signer = args
+        .private_key
+        .clone()
+        .context("--private-key is required")?;
+    let chain_id = match args.result_domain_chain_id {
+        Some(chain_id) => chain_id,
+        None => client
+            .provider()
+            .get_chain_id()
+            .await
+            .context("failed to query chain ID")?,
+    };
+    let domain = ResultDomain {
+        name: args.result_domain_name.clone(),
+        version: args.result_domain_version.clone(),
+        chain_id,
+        verifying_contract: args
+            .result_domain_verifying_contract
+            .or(args
+                .compliance_hook_address
+                .as_ref()
+                .map(NameOrAddress::address)
+                .transpose()?)
+            .unwrap_or_default(),
     };
+    let signer = ResultSigner::new(signer, domain).with_validity(args.result_valid_for);
+    tracing::info!("Signing results as operator {}", signer.address());
+    relay_with(client, markets, SigningSettler::new(settler, signer), args).await
+}
 
-    let (request_id, expires_at) = client.submit_onchain(request).await?;
+/// Run with `settler`, followed under `--relay-to` by relaying each settled decision.
+async fn relay_with<S: Settler + 'static>(
+    client: &StandardClient,
+    markets: Vec<StandardClient>,
+    settler: S,
+    args: &Args,
+) -> Result<()> {
+    let Some(name) = &args.relay_to else {
+        return pin_with(client, markets, settler, args).await;
+    };
+    let relay = Relay::connect(
+        name,
+        chains_config(args)?.chain(name)?,
+        args.private_key.as_ref(),
+    )?;
+    let chain_id = client
+        .provider()
+        .get_chain_id()
+        .await
+        .context("failed to query chain ID")?;
+    tracing::info!("Relaying settled decisions to chain {name}");
+    let store = RelayStore::new(&args.relay_store);
+    let settler = RelayingSettler::new(settler, chain_id, relay, store);
+    pin_with(client, markets, settler, args).await
+}
 
-    tracing::info!("Waiting for request {:x} to be fulfilled", request_id);
-    let fulfillment = client
-        .wait_for_request_fulfillment(request_id, Duration::from_secs(5), expires_at)
-        .await?;
-    tracing::info!("Request {:x} fulfilled", request_id);
-
-    let allowed = args.kyc_passed && args.aml_passed;
-    type Output = (Address, B256, bool);
-    let journal_bytes = <Output>::abi_encode(&(args.user, args.product_id, allowed));
-    let journal = Bytes::from(journal_bytes);
-
-    let hook =
-        IComplianceHookInstance::new(args.compliance_hook_address, client.provider().clone());
-    let call_before_trade = hook
-        .beforeTrade(
-            args.user,
-            args.product_id,
-            U256::from(args.amount),
-            journal,
-            fulfillment.seal,
-        )
-        .from(client.caller());
+/// Run with `settler`, followed under `--pin-ipfs` by pinning each proof bundle.
+async fn pin_with<S: Settler + 'static>(
+    client: &StandardClient,
+    markets: Vec<StandardClient>,
+    settler: S,
+    args: &Args,
+) -> Result<()> {
+    if !args.pin_ipfs {
+        return prove_with(client, markets, settler, args).await;
+    }
+    let settler = PinningSettler::new(settler, ipfs_client(args)?, PinStore::new(&args.ipfs_store))
+        .requiring_pin(args.require_pin);
+    prove_with(client, markets, settler, args).await
+}
+
+fn ipfs_client(args: &Args) -> Result<IpfsClient> {
+    let api = args.ipfs_api.clone().context("--ipfs-api is required")?;
+    Ok(IpfsClient::new(api, args.ipfs_api_token.clone()))
+}
+
+fn eas_attester<P: Provider>(args: &Args, provider: P, from: Address) -> Result<EasAttester<P>> {
+    let eas_address = args.eas_address.context("--eas-address is required")?;
+    // Revocations read the schema from the store, so only attestations need it.
+    let schema = args.eas_schema_uid.unwrap_or_default();
+    let store = AttestationStore::new(&args.eas_store);
+    Ok(EasAttester::new(eas_address, schema, provider, from, store)
+        .with_validity(args.eas_valid_for))
+}
+
+/// `report merge`: combine `reports` into `out` with [merge_reports].
+fn merge(reports: &[PathBuf], out: &Path) -> Result<()> {
+    let rows = reports
+        .iter()
+        .map(|path| read_report(path))
+        .collect::<Result<Vec<_>>>()?;
+    let merged = merge_reports(rows);
+    write_report(out, &merged)?;
+    println!(
+        "merged {} reports into {} rows in {}",
+        reports.len(),
+        merged.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+/// `bundle fetch`: download and verify the bundle pinned as `cid`.
+async fn fetch_bundle(args: &Args, cid: &str, out: Option<&Path>) -> Result<()> {
+    let bundle = ipfs_client(args)?.fetch_bundle(cid).await?;
+    let json = serde_json::to_string_pretty(&bundle)?;
+    match out {
+        Some(out) => {
+            std::fs::write(out, json + "\n")
+                .with_context(|| format!("failed to write bundle {}", out.display()))?;
+            eprintln!("bundle {cid} verified and written to {}", out.display());
+        }
+        None => println!("{json}"),
+    }
+    Ok(())
+}
+
+/// `verify-signed-result`: check that `operator` signed the result in `file`.
+fn verify_signed_result(file: &Path, operator: Address) -> Result<()> {
+    let contents =
+        std::fs::read(file).with_context(|| format!("failed to read {}", file.display()))?;
+    let value: serde_json::Value = serde_json::from_slice(&contents)
+        .with_context(|| format!("failed to parse {}", file.display()))?;
+    // A bundle or webhook payload carries the result under `signedResult`.
+    let value = match value.get("signedResult") {
+        Some(result) => result.clone(),
+        None => value,
+    };
+    let signed: SignedResult = serde_json::from_value(value)
+        .with_context(|| format!("{} holds no signed result", file.display()))?;
+    signed.verify(operator)?;
+    let domain = &signed.domain;
+    println!(
+        "result signed by {operator} under {} v{} on chain {} at {}",
+        domain.name, domain.version, domain.chain_id, domain.verifying_contract
+    );
+    Ok(())
+}
+
+/// `export-forge-fixtures`: write the trades of `bundles`, or of the cases of `spec` executed in
+/// dev mode, as fixtures for the Foundry tests, and check they read back as their sources.
+fn export_forge_fixtures(
+    args: &Args,
+    bundles: &[PathBuf],
+    spec: Option<&Path>,
+    out: &Path,
+) -> Result<()> {
+    let mut forge = ForgeFixtures::default();
+    if let Some(spec) = spec {
+        let fixtures = FixtureSpec::read(spec)?
+            .cases
+            .into_iter()
+            .map(|case| Fixture::execute(case, &args.executor).map(|(fixture, _)| fixture))
+            .collect::<Result<Vec<_>>>()?;
+        forge.add_fixtures(&fixtures)?;
+        forge.write(out)?;
+        ForgeFixtures::read(out)?.check_fixtures(&fixtures)?;
+    } else {
+        let bundles = bundles
+            .iter()
+            .map(|path| Ok((bundle_name(path), SettlementBundle::read(path)?)))
+            .collect::<Result<Vec<_>>>()?;
+        for (name, bundle) in &bundles {
+            forge.add_bundle(name, bundle)?;
+        }
+        forge.write(out)?;
+        let written = ForgeFixtures::read(out)?;
+        for (name, bundle) in &bundles {
+            written.check_bundle(name, bundle)?;
+        }
+    }
+    println!(
+        "wrote {} fixtures to {} and {}",
+        forge.fixtures.len(),
+        out.join(FORGE_FIXTURES_JSON).display(),
+        out.join(FORGE_FIXTURES_LIBRARY).display()
+    );
+    Ok(())
+}
+
+/// `merkle`: build a tree, or prove or verify an address against one or a user against a
+/// batch's cohort.
+fn merkle(command: MerkleCommand) -> Result<()> {
+    match command {
+        MerkleCommand::Build { input, out, sorted } => {
+            let text = std::fs::read_to_string(&input)
+                .with_context(|| format!("failed to read {}", input.display()))?;
+            let addresses = parse_addresses(&text)
+                .with_context(|| format!("invalid address list {}", input.display()))?;
+            let kind = if sorted {
+                TreeKind::Sorted
+            } else {
+                TreeKind::Membership
+            };
+            let tree = TreeFile::build(addresses, kind)?;
+            tree.write(&out)?;
+            println!(
+                "root {} of {} addresses written to {}",
+                tree.root,
+                tree.addresses.len(),
+                out.display()
+            );
+        }
+        MerkleCommand::Proof { tree, leaf } => {
+            let proof = TreeFile::read(&tree)?.proof(leaf)?;
+            println!("{}", serde_json::to_string_pretty(&proof)?);
+        }
+        MerkleCommand::CohortProof {
+            input_file,
+            decision_at,
+            user,
+            product_id,
+        } => {
+            let inputs: Vec<_> = read_entries(&input_file)?
+                .iter()
+                .map(|entry| entry.input(decision_at))
+                .collect();
+            let proof = cohort_proof(&inputs, user, product_id)?;
+            println!("{}", serde_json::to_string_pretty(&proof)?);
+        }
+        MerkleCommand::Verify { proof, tree } => {
+            let contents = std::fs::read(&proof)
+                .with_context(|| format!("failed to read proof {}", proof.display()))?;
+            let proof: AddressProof = serde_json::from_slice(&contents)
+                .with_context(|| format!("failed to parse proof {}", proof.display()))?;
+            if let Some(tree) = tree {
+                let root = TreeFile::read(&tree)?.root;
+                ensure!(
+                    proof.root() == root,
+                    "proof is for root {}, not the tree's {root}",
+                    proof.root()
+                );
+            }
+            ensure!(proof.verify(), "proof does not reach root {}", proof.root());
+            println!("proof reaches root {}", proof.root());
+        }
+    }
+    Ok(())
+}
+
+/// `revoke-attestation`: revoke attestation `uid` from the signer, which must be its attester.
+async fn revoke_attestation(args: &Args, uid: B256) -> Result<()> {
+    let rpc_url = args.rpc_url.clone().context("--rpc-url is required")?;
+    let signer = args
+        .private_key
+        .clone()
+        .context("--private-key is required")?;
+    let from = signer.address();
+    let provider = ProviderBuilder::new().wallet(signer).connect_http(rpc_url);
+    let tx_hash = eas_attester(args, provider, from)?.revoke(uid).await?;
+    println!("revoked {uid} in tx {tx_hash}");
+    Ok(())
+}
+
+/// `settle-bundle`: settle the trades of the bundle in `file` from `--private-key`, and fail if any
+/// of them failed.
+async fn settle_bundle_file(args: &Args, file: &Path) -> Result<()> {
+    let bundle = SettlementBundle::read(file)?;
+    if let Some(max_age_secs) = args.max_journal_age_secs {
+        bundle.ensure_fresh(max_age_secs, unix_now())?;
+    }
+    let rpc_url = args.rpc_url.clone().context("--rpc-url is required")?;
+    let signer = args
+        .private_key
+        .clone()
+        .context("--private-key is required")?;
+    let from = signer.address();
+    let provider = ProviderBuilder::new().wallet(signer).connect_http(rpc_url);
+    let chain_id = provider
+        .get_chain_id()
+        .await
+        .context("failed to query chain ID")?;
+    ensure!(
+        chain_id == bundle.chain_id,
+        "bundle is for chain {}, but --rpc-url serves chain {chain_id}",
+        bundle.chain_id
+    );
+    if let Some(hook) = &args.compliance_hook_address {
+        let hook = hook.address()?;
+        ensure!(
+            hook == bundle.target,
+            "bundle targets hook {}, not --compliance-hook-address {hook}",
+            bundle.target
+        );
+    }
+
+    let settler = DelayingSettler::new(
+        HookSettler::new(bundle.target, provider, from)
+            .with_verification(args.verify_after_settle)
+            .with_seal_check(!args.skip_seal_check),
+    );
+    let mut failed = 0;
+    for (trade, result) in bundle
+        .trades
+        .iter()
+        .zip(settle_bundle(&bundle, &settler).await)
+    {
+        match result {
+            Ok(settlement) => match settlement.tx_hash() {
+                Some(tx_hash) => println!("settled {} in tx {tx_hash}", trade.entry.user),
+                None => println!("settled {}", trade.entry.user),
+            },
+            Err(err) => {
+                failed += 1;
+                eprintln!("Error: {err:#}");
+            }
+        }
+    }
+    ensure!(
+        failed == 0,
+        "{failed} of {} trades failed to settle",
+        bundle.trades.len()
+    );
+    Ok(())
+}
+
+/// `record-batch`: prove the trades of `file` as one batch on this machine and send its journal to
+/// the hook's `recordBatch` from `--private-key`, with the hook's `batchAggregateCap` unless
+/// `aggregate_cap` is given.
+async fn record_batch(args: &Args, file: &Path, aggregate_cap: Option<U256>) -> Result<()> {
+    let rpc_url = args.rpc_url.clone().context("--rpc-url is required")?;
+    let signer = args
+        .private_key
+        .clone()
+        .context("--private-key is required")?;
+    let from = signer.address();
+    let provider = ProviderBuilder::new().wallet(signer).connect_http(rpc_url);
+    let hook = IComplianceHookInstance::new(hook_address(args)?, provider);
+    let aggregate_cap = match aggregate_cap {
+        Some(cap) => cap,
+        None => hook
+            .batchAggregateCap()
+            .call()
+            .await
+            .context("failed to query batchAggregateCap")?,
+    };
 
-    tracing::info!("Calling ComplianceHook beforeTrade function");
-    let pending_tx = call_before_trade
+    let decision_at = unix_now();
+    let inputs: Vec<_> = read_entries(file)?
+        .iter()
+        .map(|entry| entry.input(decision_at))
+        .collect();
+    let pages = batch_pages(&inputs);
+    // The hook rejects a batch over its cap or with a denied trade, so neither is worth proving.
+    let mut fold = BatchFold::new(u32::try_from(pages.len())?, aggregate_cap);
+    for page in &pages {
+        fold.push_page(page)?;
+    }
+    let expected = fold.finish();
+    ensure!(
+        !expected.capExceeded,
+        "the batch's allowed trades add up to {}, over its aggregate cap of {aggregate_cap}",
+        expected.aggregateAmount
+    );
+    ensure!(
+        expected.allAllowed,
+        "not every trade of the batch is allowed"
+    );
+
+    let (journal, seal) = prove_batch(args.executor, pages, aggregate_cap).await?;
+    let pending_tx = hook
+        .recordBatch(journal, seal)
+        .from(from)
         .send()
         .await
         .context("failed to broadcast tx")?;
-    tracing::info!("Broadcasting tx {}", pending_tx.tx_hash());
-    let tx_hash = pending_tx
+    let receipt = pending_tx
         .with_timeout(Some(TX_TIMEOUT))
-        .watch()
+        .get_receipt()
         .await
         .context("failed to confirm tx")?;
-    tracing::info!("Tx {:?} confirmed", tx_hash);
+    let tx_hash = receipt.transaction_hash;
+    ensure!(receipt.status(), "tx {tx_hash} reverted");
+    println!(
+        "recorded cohort {} of {} trades decided at {decision_at}, {} in total, in tx {tx_hash}",
+        expected.cohortRoot, expected.count, expected.aggregateAmount
+    );
+    Ok(())
+}
 
+/// `status`: print the decision the hook cached for `user` and `product_id`.
+async fn status(args: &Args, user: Address, product_id: B256) -> Result<()> {
+    let rpc_url = args.rpc_url.clone().context("--rpc-url is required")?;
+    let provider = ProviderBuilder::new().connect_http(rpc_url);
+    let cached = CachedDecision::fetch(hook_address(args)?, provider, user, product_id).await?;
+    print!("{}", with_ens_names(args, cached.explain()).await);
     Ok(())
 }
+
+/// Run with the Boundless `markets`, whose fulfillments are checked against `--allowed-provers`,
+/// or with a [LocalProver] in their place under `--local`.
+async fn prove_with<S: Settler + 'static>(
+    client: &StandardClient,
+    markets: Vec<StandardClient>,
+    settler: S,
+    args: &Args,
+) -> Result<()> {
+    if !args.local {
+        let markets = markets
+            .into_iter()
+            .map(|market| TrustedProvers::new(market, args.allowed_provers.clone()))
+            .collect();
+        return run(client, markets, settler, args, args.max_concurrent_proofs).await;
+    }
+
+    let jobs = prove_jobs(args.prove_jobs, args.prove_memory_limit_mib, &args.executor);
+    tracing::info!("Proving locally with {jobs} jobs");
+    let cancel = CancellationToken::new();
+    tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                tracing::warn!("Interrupted; failing the trades that are still being proven");
+                cancel.cancel();
+            }
+        }
+    });
+    let prover = LocalProver::new(jobs, args.executor, cancel.clone());
+    let result = run(client, vec![prover], settler, args, jobs).await;
+    if cancel.is_cancelled() {
+        // Abandoned proofs keep their blocking threads until they finish, and the runtime would
+        // wait for them on shutdown.
+        if let Err(err) = &result {
+            eprintln!("Error: {err:?}");
+        }
+        std::process::exit(130);
+    }
+    result
+}
+
+async fn build_client(args: &Args, deployment: Option<Deployment>) -> Result<StandardClient> {
+    Client::builder()
+        .with_rpc_url(args.rpc_url.clone().context("--rpc-url is required")?)
+        .with_deployment(deployment)
+        .with_storage_provider_config(&args.storage_config)?
+        .with_private_key(
+            args.private_key
+                .clone()
+                .context("--private-key is required")?,
+        )
+        .build()
+        .await
+}
+
+/// Load `--chains-config` and fill the arguments left unset from the `--chain` it names, if any.
+fn select_chain(args: &mut Args) -> Result<Option<(String, ChainConfig)>> {
+    let Some(name) = args.chain.clone() else {
+        return Ok(None);
+    };
+    let chain = chains_config(args)?.chain(&name)?.clone();
+    chain.apply_to(args)?;
+    tracing::info!("Running on chain {name} ({})", chain.chain_id);
+    Ok(Some((name, chain)))
+}
+
+/// `chains list`: print the registry with the state of each chain's deployment.
+async fn list_chains(args: &Args) -> Result<()> {
+    print!("{}", chains_config(args)?.list().await);
+    Ok(())
+}
+
+fn chains_config(args: &Args) -> Result<ChainsConfig> {
+    let path = args
+        .chains_config
+        .as_deref()
+        .context("--chains-config is required")?;
+    ChainsConfig::load(path)
+}
+
+/// `relay retry`: deliver every relay of `--relay-store` that is not confirmed.
+async fn retry_relay(args: &Args) -> Result<()> {
+    let config = chains_config(args)?;
+    let store = RelayStore::new(&args.relay_store);
+    let results = retry_relays(&store, |name| {
+        Relay::connect(name, config.chain(name)?, args.private_key.as_ref())
+    })
+    .await?;
+    for (record, result) in &results {
+        match result {
+            Ok(tx_hash) => println!(
+                "{}\trelayed to {}\t{tx_hash}",
+                record.journal_digest, record.relay.chain
+            ),
+            Err(err) => println!(
+                "{}\tfailed to relay to {}\t{err:#}",
+                record.journal_digest, record.relay.chain
+            ),
+        }
+    }
+    ensure_relayed(&results)
+}
+
+fn hook_address(args: &Args) -> Result<Address> {
+    args.compliance_hook_address
+        .as_ref()
+        .context("--compliance-hook-address is required")?
+        .address()
+}
+
+/// Resolve the ENS names among the address arguments through `--rpc-url`, printing what each
+/// resolves to. Without `--rpc-url` names stay unresolved, and fail where they are used.
+async fn resolve_names(args: &mut Args) -> Result<()> {
+    let Some(rpc_url) = args.rpc_url.clone() else {
+        return Ok(());
+    };
+    let resolver = EnsResolver::new(
+        ProviderBuilder::new().connect_http(rpc_url),
+        args.ens_registry,
+    );
+    let status_user = match &mut args.command {
+        Some(Command::Status { user, .. }) => Some(user),
+        _ => None,
+    };
+    for value in [
+        args.compliance_hook_address.as_mut(),
+        args.user.as_mut(),
+        args.attester.as_mut(),
+        status_user,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        resolver.resolve_in_place(value).await?;
+    }
+    Ok(())
+}
+
+/// Take the hook address from `--broadcast-file`, refusing a hook address given otherwise that
+/// differs from it.
+async fn hook_from_broadcast(args: &mut Args) -> Result<()> {
+    let Some(path) = &args.broadcast_file else {
+        return Ok(());
+    };
+    let rpc_url = args
+        .rpc_url
+        .clone()
+        .context("--broadcast-file needs --rpc-url to find the chain")?;
+    let deployed = deployed_hook(&ProviderBuilder::new().connect_http(rpc_url), path).await?;
+    if let Some(given) = &args.compliance_hook_address {
+        let given = given.address()?;
+        ensure!(
+            given == deployed.address,
+            "the hook address is {given}, but {} records the hook at {}",
+            deployed.file.display(),
+            deployed.address
+        );
+    }
+    tracing::info!(
+        "Using the hook at {} from {}",
+        deployed.address,
+        deployed.file.display()
+    );
+    args.compliance_hook_address = Some(NameOrAddress::Address(deployed.address));
+    Ok(())
+}
+
+/// `text` with the ENS names of its addresses under `--ens-names`, as is otherwise.
+async fn with_ens_names(args: &Args, text: String) -> String {
+    match (&args.rpc_url, args.ens_names) {
+        (Some(rpc_url), true) => {
+            EnsResolver::new(
+                ProviderBuilder::new().connect_http(rpc_url.clone()),
+                args.ens_registry,
+            )
+            .annotate(&text)
+            .await
+        }
+        _ => text,
+    }
+}
+
+/// `--dry-run`: print the decision the host copy of the policy makes for every trade, and under
+/// `--profile-cycles` the guest's cycles for it. Only the KYC provider or the credentials are
+/// fetched, and only under `--input-from-api` or `--input-from-credential`.
+async fn dry_run(args: &Args) -> Result<()> {
+    let decision_at = unix_now();
+    let mut entries = entries(args)?;
+    fill_from_api(args, &mut entries, None).await?;
+    let mut input = Vec::with_capacity(ComplianceInput::LEN);
+    for (index, entry) in entries.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        print!(
+            "{}",
+            explain_with_decimals(&entry.expected_journal(decision_at), args.decimals)
+        );
+        if args.profile_cycles {
+            input.clear();
+            entry.input(decision_at).encode_to(&mut input);
+            let (execution, stdout) = args
+                .executor
+                .execute_with_stdout(COMPLIANCE_ELF, &input)
+                .with_context(|| format!("failed to execute guest for trade {index}"))?;
+            let profile = CycleProfile::decode(&stdout).context(
+                "the guest wrote no cycle profile; build it with its profile feature \
+                 (`--features profile`)",
+            )?;
+            print!("{}", format_profile(&profile, execution.cycles));
+        }
+    }
+    Ok(())
+}
+
+/// `--compare-guest`: execute the embedded guest and the one in `elf` on every trade and print
+/// whether their decisions agree, with the lines of those that do not.
+async fn compare_guest(args: &Args, elf: &Path) -> Result<()> {
+    let candidate =
+        std::fs::read(elf).with_context(|| format!("failed to read guest {}", elf.display()))?;
+    let decision_at = unix_now();
+    let mut entries = entries(args)?;
+    fill_from_api(args, &mut entries, None).await?;
+    let comparisons = compare_guests(
+        COMPLIANCE_ELF,
+        &candidate,
+        &entries,
+        decision_at,
+        &args.executor,
+    )?;
+    let mut disagreements = 0;
+    for (index, comparison) in comparisons.iter().enumerate() {
+        if comparison.agrees() {
+            println!("trade {index}: agree");
+            continue;
+        }
+        disagreements += 1;
+        println!("trade {index}: disagree");
+        for line in comparison.differences() {
+            println!("  {line}");
+        }
+    }
+    ensure!(
+        disagreements == 0,
+        "{disagreements} of {} trades decided differently by {}",
+        comparisons.len(),
+        elf.display()
+    );
+    Ok(())
+}
+
+/// `--estimate-proof-cost`: execute the guest on every trade and print the price range the
+/// market would be offered its proof at, using the offer layer's default prices per cycle and the
+/// current gas price. Nothing is signed or submitted.
+async fn print_proof_cost(args: &Args) -> Result<()> {
+    let rpc_url = args.rpc_url.clone().context("--rpc-url is required")?;
+    let provider = ProviderBuilder::new().connect_http(rpc_url);
+    let gas_price = provider
+        .get_gas_price()
+        .await
+        .context("failed to query gas price")?;
+    let config = OfferLayerConfig::default();
+    tracing::info!(
+        "Assuming {} to {} wei per cycle and a gas price of {gas_price} wei",
+        config.min_price_per_cycle,
+        config.max_price_per_cycle
+    );
+
+    let decision_at = unix_now();
+    let mut entries = entries(args)?;
+    fill_from_api(args, &mut entries, None).await?;
+    let (mut min_total, mut max_total) = (U256::ZERO, U256::ZERO);
+    let mut input = Vec::with_capacity(ComplianceInput::LEN);
+    for (index, entry) in entries.iter().enumerate() {
+        input.clear();
+        entry.input(decision_at).encode_to(&mut input);
+        let execution = args
+            .executor
+            .execute(COMPLIANCE_ELF, &input)
+            .with_context(|| format!("failed to execute guest for trade {index}"))?;
+        tracing::info!(
+            "Trade {index}: {} user cycles, {} cycles over {} segments",
+            execution.cycles,
+            execution.total_cycles,
+            execution.segments
+        );
+        let estimate = estimate_proof_cost(&config, execution.total_cycles, gas_price);
+        println!(
+            "trade {index}: {} to {} ETH",
+            format_ether(estimate.min_price),
+            format_ether(estimate.max_price)
+        );
+        min_total += estimate.min_price;
+        max_total += estimate.max_price;
+    }
+    if entries.len() > 1 {
+        println!(
+            "total:   {} to {} ETH",
+            format_ether(min_total),
+            format_ether(max_total)
+        );
+    }
+    Ok(())
+}
+
+/// Trades of the run: those of `--input-file`, or the single trade the arguments describe.
+fn entries(args: &Args) -> Result<Vec<BatchEntry>> {
+    if let Some(input_file) = &args.input_file {
+        let mut entries = read_entries(input_file)?;
+        for entry in &mut entries {
+            entry.compact_journal |= args.compact_journal;
+            entry.sha256_commitments |= args.sha256_commitments;
+            if entry.settle_delay_secs == 0 {
+                entry.settle_delay_secs = args.settle_delay_secs;
+            }
+        }
+        tracing::info!("Processing {} trades from {:?}", entries.len(), input_file);
+        return Ok(entries);
+    }
+    let entry = BatchEntry {
+        user: args
+            .user
+            .as_ref()
+            .context("--user is required")?
+            .address()?,
+        product_id: match &args.pool {
+            Some(pool) => {
+                let product_id = pool.id();
+                println!("product id: {product_id}");
+                product_id
+            }
+            None => args.product_id.context("--product-id is required")?,
+        },
+        amount: U256::from(args.amount.context("--amount is required")?),
+        kyc_passed: args.kyc_passed,
+        kyc_tier: args.kyc_tier,
+        required_tier: args.required_tier,
+        aml_passed: args.aml_passed,
+        claims: args.claims,
+        required_claims: args.required_claims,
+        required_attestations: args.required_attestations,
+        product_policy_version: args.product_policy_version,
+        quote: args.quote_product_id.map(|product_id| QuoteLeg {
+            product_id,
+            required_tier: args.quote_required_tier,
+            required_claims: args.quote_required_claims,
+            product_policy_version: args.quote_policy_version,
+        }),
+        risk_score: args.risk_score,
+        review_band: args
+            .review_band_low
+            .zip(args.review_band_high)
+            .map(|(low, high)| ReviewBand { low, high }),
+        regime_id: args.regime_id,
+        settlement_token: args.settlement_token,
+        case_id: args.case_id,
+        min_trade: args.min_trade,
+        max_trade: args.max_trade,
+        ttl_secs: args.ttl_secs,
+        attester: args
+            .attester
+            .as_ref()
+            .map(NameOrAddress::address)
+            .transpose()?,
+        attestation_signature: args.attestation_signature.clone(),
+        governance: args.governance.clone(),
+        override_signature: args.override_signature,
+        compact_journal: args.compact_journal,
+        sha256_commitments: args.sha256_commitments,
+        decision_uuid: args.decision_uuid.unwrap_or_default(),
+        credential_hash: B256::ZERO,
+        settle_delay_secs: args.settle_delay_secs,
+        aml_sources: args.aml_sources,
+        required_aml_sources: args.required_aml_sources,
+        block_hash: args.block_hash.unwrap_or_default(),
+        kyc_provider: args.kyc_provider,
+        allowed_kyc_providers: args.allowed_kyc_providers.clone(),
+        consent_given: args.consent_given,
+        consent_at: args.consent_at,
+        consent_required: !args.consent_optional,
+    };
+    entry.validate()?;
+    Ok(vec![entry])
+}
+
+/// Give each entry without a decision UUID the one its cached proof commits, or a new one.
+fn assign_decision_uuids(cache: Option<&ProofCache>, entries: &mut [BatchEntry]) -> Result<()> {
+    for entry in entries {
+        match cache {
+            Some(cache) => {
+                cache.assign_decision_uuid(entry, compliance_image_id())?;
+            }
+            None => {
+                entry.ensure_decision_uuid();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prove and settle the trades of the run. `client` provides the signer and builds the requests,
+/// which are raced across `markets` with at most `proofs` trades being proven at once.
+async fn run<M: MarketClient + 'static, S: Settler + 'static>(
+    client: &StandardClient,
+    markets: Vec<M>,
+    settler: S,
+    args: &Args,
+    proofs: NonZeroUsize,
+) -> Result<()> {
+    let decision_at = unix_now();
+    let cache = args.proof_cache.as_ref().map(|dir| {
+        ProofCache::new(dir, args.reprove_on_image_change).with_max_age(args.max_journal_age_secs)
+    });
+    let webhook = args
+        .webhook
+        .clone()
+        .map(|url| WebhookSink::new(url, args.webhook_secret.clone()));
+    let notifications = notifications(args, webhook.as_ref());
+    let mut entries = entries(args)?;
+
+    if args.check_balance {
+        let hook_settlements = if args.attest_only || args.relayer.is_some() {
+            0
+        } else {
+            entries.len().max(1)
+        };
+        let attestations = if args.attest_eas { entries.len() } else { 0 };
+        let onchain_settlements = hook_settlements + attestations;
+        check_balance(
+            &client.provider(),
+            client.caller(),
+            args.min_balance_wei,
+            onchain_settlements,
+        )
+        .await?;
+    }
+
+    let program = program(args).await?;
+    if args.input_file.is_none() {
+        let mut entry = entries.remove(0);
+        fill_from_api(
+            args,
+            std::slice::from_mut(&mut entry),
+            notifications.as_ref(),
+        )
+        .await?;
+        entry.check_attestation(&client.provider()).await?;
+        assign_decision_uuids(cache.as_ref(), std::slice::from_mut(&mut entry))?;
+        if !entry.block_hash.is_zero() {
+            println!("block hash: {}", entry.block_hash);
+        }
+        println!("decision uuid: {}", entry.decision_uuid);
+        if !entry.credential_hash.is_zero() {
+            println!("credential: {}", entry.credential_hash);
+        }
+        tracing::info!(
+            "Attempting trade with amount: {}",
+            format_amount(entry.amount, args.decimals)
+        );
+        let request = new_request(client, &entry, 0, decision_at, program.as_ref())?;
+        let request_id = request.request_id.map(U256::from);
+        let proof = prove_entry(
+            &markets,
+            &entry,
+            decision_at,
+            request,
+            args.offchain,
+            cache.as_ref(),
+        )
+        .await;
+        let proof = match proof {
+            Ok(proof) => proof,
+            Err(err) => {
+                if let Some(notifications) = &notifications {
+                    notifications
+                        .notify(Alert::proof_failed(request_id, &err))
+                        .await;
+                }
+                return Err(err);
+            }
+        };
+        if args.explain {
+            let journal = ComplianceJournal::try_from(proof.journal.as_ref())?;
+            let explanation = explain_with_decimals(&journal, args.decimals);
+            print!("{}", with_ens_names(args, explanation).await);
+        }
+        let settlement = match settler
+            .settle(&entry, proof.journal.clone(), proof.seal)
+            .await
+        {
+            Ok(settlement) => settlement,
+            Err(err) => {
+                if let Some(notifications) = &notifications {
+                    notifications
+                        .notify(Alert::settlement_failed(proof.request_id, &err))
+                        .await;
+                }
+                return Err(err);
+            }
+        };
+        if let Some(uid) = settlement.attestation_uid() {
+            println!("attestation: {uid}");
+        }
+        if let Some(cid) = settlement.bundle_cid() {
+            println!("bundle: {cid}");
+        }
+        if let Some(signed) = settlement.signed_result() {
+            println!("signature: {}", signed.signature);
+        }
+        if let Some((chain, tx_hash)) = settlement.forwarded() {
+            println!("relayed to {chain}: {tx_hash}");
+        }
+        if let Some(webhook) = &webhook {
+            webhook
+                .report(proof.request_id, &proof.journal, &settlement)
+                .await;
+        }
+        if let Some(notifications) = &notifications {
+            notifications
+                .notify(Alert::settled(proof.request_id, &settlement))
+                .await;
+        }
+        return Ok(());
+    }
+
+    fill_from_api(args, &mut entries, notifications.as_ref()).await?;
+    for entry in &mut entries {
+        entry.check_attestation(&client.provider()).await?;
+    }
+    assign_decision_uuids(cache.as_ref(), &mut entries)?;
+    let requests = entries
+        .into_iter()
+        .enumerate()
+        .map(|(position, entry)| {
+            let request = new_request(client, &entry, position, decision_at, program.as_ref())?;
+            Ok((entry, request))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let limits = Concurrency {
+        proofs,
+        settlements: args.max_concurrent_settlements,
+    };
+
+    let reports = run_batch(
+        Arc::from(markets),
+        Arc::new(settler),
+        requests,
+        decision_at,
+        limits,
+        BatchOptions {
+            offchain: args.offchain,
+            cache: cache.map(Arc::new),
+            webhook: webhook.map(Arc::new),
+            notifications: notifications.map(Arc::new),
+            all_or_nothing: args.all_or_nothing,
+        },
+    )
+    .await;
+    println!("{}", serde_json::to_string_pretty(&reports)?);
+    if let Some(path) = &args.report_out {
+        let rows: Vec<_> = reports.iter().map(ReportRow::new).collect();
+        write_report(path, &rows)?;
+        tracing::info!(
+            "Wrote report of {} trades to {}",
+            rows.len(),
+            path.display()
+        );
+    }
+
+    let failed = reports.iter().filter(|report| !report.is_settled()).count();
+    if failed > 0 {
+        bail!("{failed} of {} trades failed", reports.len());
+    }
+    Ok(())
+}
+
+/// Alert sinks configured by the arguments, or `None` when there are none. `webhook` receives
+/// alerts only under `--webhook-min-severity`.
+fn notifications(args: &Args, webhook: Option<&WebhookSink>) -> Option<Notifications> {
+    let mut notifications = Notifications::new(args.alert_dedup_window).with_links(Links {
+        explorer: args.explorer_url.clone(),
+        request_explorer: args.request_explorer_url.clone(),
+    });
+    if let Some(url) = &args.slack_webhook_url {
+        notifications = notifications.route(
+            "Slack",
+            args.slack_min_severity,
+            SlackNotifier::new(url.clone()),
+        );
+    }
+    if let Some(routing_key) = &args.pagerduty_routing_key {
+        notifications = notifications.route(
+            "PagerDuty",
+            args.pagerduty_min_severity,
+            PagerDutyNotifier::new(routing_key.clone()),
+        );
+    }
+    if let (Some(webhook), Some(min_severity)) = (webhook, args.webhook_min_severity) {
+        notifications = notifications.route("the webhook", min_severity, webhook.clone());
+    }
+    (!notifications.is_empty()).then_some(notifications)
+}
+
+/// With `--input-from-api`, replace the compliance fields of `entries` with the KYC provider's,
+/// and with `--input-from-credential` with those of each user's verifiable credential. With
+/// `--screening-config`, the AML result and risk score come from each user's screening instead. A
+/// failure is alerted to `notifications`, since no trade can run without the source.
+async fn fill_from_api(
+    args: &Args,
+    entries: &mut [BatchEntry],
+    notifications: Option<&Notifications>,
+) -> Result<()> {
+    let screening = args
+        .screening_config
+        .as_deref()
+        .map(ScreeningConfig::load)
+        .transpose()?
+        .map(|config| ScreeningSource::new(config, args.screening_api_key.clone()));
+    let fetched = if let Some(url) = &args.input_from_api {
+        let mut source = HttpSource::new(url.clone(), args.api_token.clone());
+        for header in &args.api_header {
+            source = source.with_header(header.name.clone(), header.value.clone());
+        }
+        fetch_screened(source, screening, entries).await
+    } else if let Some(location) = &args.input_from_credential {
+        fetch_screened(CredentialSource::new(location.clone()), screening, entries).await
+    } else if let Some(screening) = &screening {
+        screen_entries(screening, entries).await
+    } else {
+        return Ok(());
+    };
+    let fetched = fetched.context("failed to fetch compliance data");
+    if let (Err(err), Some(notifications)) = (&fetched, notifications) {
+        notifications
+            .notify(Alert::attestation_source_down(err))
+            .await;
+    }
+    fetched
+}
+
+/// Fill in the compliance fields of `entries` from `source`, with the AML result and risk score
+/// from `screening` if given.
+async fn fetch_screened<S: AttestationSource>(
+    source: S,
+    screening: Option<ScreeningSource>,
+    entries: &mut [BatchEntry],
+) -> Result<()> {
+    match screening {
+        Some(screening) => fetch_compliance(&Screened::new(source, screening), entries).await,
+        None => fetch_compliance(&source, entries).await,
+    }
+}
+
+/// Program to request proofs of: `--program-url`, the upload `--program-image-id` names in
+/// `--program-index`, or the embedded program's upload under `--program-index`, which is only made
+/// when the storage provider does not hold it yet. Otherwise the program is uploaded along with
+/// each request. Local proving never fetches it.
+async fn program(args: &Args) -> Result<Option<ProgramRef>> {
+    let Some(index) = args.program_index.as_ref().filter(|_| !args.local) else {
+        return program_url(args).await;
+    };
+    let provider = StandardStorageProvider::from_config(&args.storage_config)
+        .context("failed to build storage provider")?;
+    let storage = IndexedStorage::new(provider, index);
+
+    if let Some(image_id) = args.program_image_id {
+        check_program_image_id(image_id, args.allow_image_id_mismatch)?;
+        let program = find_program(&storage, image_id).await?;
+        match &program {
+            Some(program) => tracing::info!("Using program {image_id} at {}", program.url),
+            None => tracing::warn!(
+                "Program {image_id} is not in {}; embedding the guest program instead",
+                index.display()
+            ),
+        }
+        return Ok(program);
+    }
+    let url = upload_program(&storage, COMPLIANCE_ELF).await?;
+    Ok(Some(ProgramRef {
+        url,
+        image_id: None,
+    }))
+}
+
+/// The program at `--program-url`, unless there is none or `--prefer embedded` picks the
+/// embedded guest instead.
+async fn program_url(args: &Args) -> Result<Option<ProgramRef>> {
+    let embedded = compliance_image_id();
+    let Some(url) = args.program_url.clone() else {
+        tracing::info!("Requesting proofs of the embedded guest program {embedded}");
+        return Ok(None);
+    };
+    let prefer = args.prefer.unwrap_or_else(|| {
+        tracing::warn!(
+            "Both --program-url {url} and the embedded guest program are available; using the \
+             URL. Pass --prefer url or --prefer embedded to choose"
+        );
+        ProgramSource::Url
+    });
+    if prefer == ProgramSource::Embedded {
+        tracing::info!("Requesting proofs of the embedded guest program {embedded}, not {url}");
+        return Ok(None);
+    }
+    let program = check_program_url(url, args.allow_image_id_mismatch).await?;
+    match program.image_id {
+        Some(image_id) => {
+            tracing::info!("Requesting proofs of program {image_id} at {}", program.url)
+        }
+        None => tracing::info!("Requesting proofs of the program at {}", program.url),
+    }
+    Ok(Some(program))
+}
+
+/// Request a proof of `entry`, the trade at `position` of the run, numbered by [request_index] so
+/// its id is known before submission.
+fn new_request(
+    client: &StandardClient,
+    entry: &BatchEntry,
+    position: usize,
+    decision_at: u64,
+    program: Option<&ProgramRef>,
+) -> Result<RequestParams> {
+    let stdin = entry.stdin(decision_at);
+    let index = request_index(&stdin, position);
+    let request = client
+        .new_request()
+        .with_request_id(RequestId::new(client.caller(), index))
+        .with_stdin(stdin);
+    let Some(program) = program else {
+        return Ok(request.with_program(COMPLIANCE_ELF));
+    };
+    let request = request.with_program_url(program.url.clone())?;
+    Ok(match program.image_id {
+        Some(image_id) => request.with_image_id(<[u8; 32]>::from(image_id)),
+        None => request,
+    })
+}
@@ -0,0 +1,355 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{future::Future, time::Duration};
+
+use alloy::{
+    primitives::{keccak256, Address, B256, U256},
+    providers::Provider,
+};
+use anyhow::{bail, ensure, Context, Result};
+use boundless_market::{
+    contracts::{Fulfillment, RequestId},
+    deployments::NamedChain,
+    request_builder::RequestParams,
+    Deployment, StandardClient,
+};
+use futures_util::{stream::FuturesUnordered, StreamExt};
+
+/// Interval between checks for the fulfillment of a submitted request.
+pub const FULFILLMENT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Blocks back from the latest that the event recording a request's prover is looked for in. The
+/// request was just fulfilled, so the event is recent.
+pub const FULFILLMENT_LOOKBACK_BLOCKS: u64 = 1_000;
+
+/// Chains the Boundless library ships a [Deployment] for.
+pub const KNOWN_CHAINS: [NamedChain; 3] = [
+    NamedChain::Sepolia,
+    NamedChain::Base,
+    NamedChain::BaseSepolia,
+];
+
+/// The deployments the Boundless library knows, by chain.
+pub fn known_deployments() -> Vec<(NamedChain, Deployment)> {
+    KNOWN_CHAINS
+        .into_iter()
+        .filter_map(|chain| Some((chain, Deployment::from_chain(chain)?)))
+        .collect()
+}
+
+/// The contracts of `deployment` by name, leaving out the ones it does not set.
+pub fn deployment_contracts(deployment: &Deployment) -> Vec<(&'static str, Address)> {
+    [
+        (
+            "boundless market",
+            Some(deployment.boundless_market_address),
+        ),
+        ("verifier router", deployment.verifier_router_address),
+        ("set verifier", Some(deployment.set_verifier_address)),
+        ("collateral token", deployment.collateral_token_address),
+    ]
+    .into_iter()
+    .filter_map(|(name, address)| Some((name, address?)))
+    .collect()
+}
+
+/// Whether the chain `provider` is connected to has code at each of `addresses`.
+pub async fn have_code<P: Provider>(provider: &P, addresses: &[Address]) -> Result<Vec<bool>> {
+    let mut deployed = Vec::with_capacity(addresses.len());
+    for &address in addresses {
+        let code = provider
+            .get_code_at(address)
+            .await
+            .with_context(|| format!("failed to get code at {address}"))?;
+        deployed.push(!code.is_empty());
+    }
+    Ok(deployed)
+}
+
+/// Human-readable account of `deployment` on `chain`, one contract per line. With `deployed`,
+/// from [have_code] over [deployment_contracts], each contract is marked with whether it has code.
+pub fn describe_deployment(
+    chain: &str,
+    deployment: &Deployment,
+    deployed: Option<&[bool]>,
+) -> String {
+    let mut out = match deployment.chain_id {
+        Some(chain_id) => format!("{chain} (chain id {chain_id})\n"),
+        None => format!("{chain}\n"),
+    };
+    for (index, (name, address)) in deployment_contracts(deployment).into_iter().enumerate() {
+        let status = match deployed.map(|deployed| deployed[index]) {
+            Some(true) => "  deployed",
+            Some(false) => "  no code",
+            None => "",
+        };
+        out.push_str(&format!("  {:<18}{address}{status}\n", format!("{name}:")));
+    }
+    if let Some(url) = &deployment.order_stream_url {
+        out.push_str(&format!("  {:<18}{url}\n", "order stream:"));
+    }
+    out
+}
+
+/// Index of the request for guest input `stdin` at `position` in a run.
+///
+/// The market identifies a request by its signer and a 32-bit index of the signer's choosing,
+/// and derives neither from the request's fields. Deriving the index from the input, instead of
+/// drawing it at random as the request builder does, makes the id known before submission and
+/// the same for every retry of the request. The position keeps identical trades of one run apart.
+pub fn request_index(stdin: &[u8], position: usize) -> u32 {
+    let mut preimage = stdin.to_vec();
+    preimage.extend_from_slice(&(position as u64).to_be_bytes());
+    let digest = keccak256(preimage);
+    u32::from_be_bytes(digest[..4].try_into().unwrap())
+}
+
+/// Id of request `index` of `signer`, as the market computes it.
+pub fn request_id(signer: Address, index: u32) -> U256 {
+    RequestId::new(signer, index).into()
+}
+
+/// The subset of the Boundless Market client used by the compliance flow.
+///
+/// Abstracting over the client lets the proving pipeline run against a scripted market in tests.
+pub trait MarketClient: Send + Sync {
+    /// Build and submit a request onchain, returning its id and expiry timestamp.
+    fn submit_onchain(
+        &self,
+        request: RequestParams,
+    ) -> impl Future<Output = Result<(U256, u64)>> + Send;
+
+    /// Build and submit a request through the offchain order stream.
+    fn submit_offchain(
+        &self,
+        request: RequestParams,
+    ) -> impl Future<Output = Result<(U256, u64)>> + Send;
+
+    /// Wait until the request is fulfilled or expires.
+    fn wait_for_request_fulfillment(
+        &self,
+        request_id: U256,
+        check_interval: Duration,
+        expires_at: u64,
+    ) -> impl Future<Output = Result<Fulfillment>> + Send;
+
+    /// Prover that fulfilled the request, or `None` if the market does not record one.
+    fn fulfillment_prover(
+        &self,
+        request_id: U256,
+    ) -> impl Future<Output = Result<Option<Address>>> + Send;
+}
+
+impl MarketClient for StandardClient {
+    async fn submit_onchain(&self, request: RequestParams) -> Result<(U256, u64)> {
+        Ok(StandardClient::submit_onchain(self, request).await?)
+    }
+
+    async fn submit_offchain(&self, request: RequestParams) -> Result<(U256, u64)> {
+        Ok(StandardClient::submit_offchain(self, request).await?)
+    }
+
+    async fn wait_for_request_fulfillment(
+        &self,
+        request_id: U256,
+        check_interval: Duration,
+        expires_at: u64,
+    ) -> Result<Fulfillment> {
+        Ok(StandardClient::wait_for_request_fulfillment(
+            self,
+            request_id,
+            check_interval,
+            expires_at,
+        )
+        .await?)
+    }
+
+    async fn fulfillment_prover(&self, request_id: U256) -> Result<Option<Address>> {
+        let market = self.boundless_market.instance();
+        let latest = market
+            .provider()
+            .get_block_number()
+            .await
+            .context("failed to get the latest block")?;
+        let fulfilled = market
+            .RequestFulfilled_filter()
+            .topic1(B256::from(request_id))
+            .from_block(latest.saturating_sub(FULFILLMENT_LOOKBACK_BLOCKS))
+            .query()
+            .await
+            .context("failed to query fulfillment events")?;
+        Ok(fulfilled.last().map(|(event, _)| event.prover))
+    }
+}
+
+/// A [MarketClient] that logs the prover of each fulfillment and, given a list of allowed
+/// provers, rejects fulfillments by any other.
+///
+/// A rejected fulfillment fails like an expired request, so its trade is not settled, and when
+/// requests are raced the other markets are still awaited. Its proof is paid for all the same.
+pub struct TrustedProvers<M> {
+    market: M,
+    allowed: Vec<Address>,
+}
+
+impl<M: MarketClient> TrustedProvers<M> {
+    /// Check the fulfillments of `market` against `allowed`, or only log their provers if it is
+    /// empty.
+    pub fn new(market: M, allowed: Vec<Address>) -> Self {
+        Self { market, allowed }
+    }
+
+    async fn check(&self, request_id: U256) -> Result<()> {
+        let prover = match self.market.fulfillment_prover(request_id).await {
+            Ok(prover) => prover,
+            // Without a list there is nothing to enforce, so the lookup is only informative.
+            Err(err) if self.allowed.is_empty() => {
+                tracing::warn!("Failed to look up the prover of request {request_id:x}: {err:#}");
+                return Ok(());
+            }
+            Err(err) => {
+                return Err(err.context(format!(
+                    "failed to look up the prover of request {request_id:x}"
+                )))
+            }
+        };
+        let Some(prover) = prover else {
+            ensure!(
+                self.allowed.is_empty(),
+                "the market does not record the prover of request {request_id:x}, so it cannot \
+                 be checked against the allowed provers"
+            );
+            return Ok(());
+        };
+        tracing::info!("Request {request_id:x} was fulfilled by prover {prover}");
+        ensure!(
+            self.allowed.is_empty() || self.allowed.contains(&prover),
+            "prover {prover} is not one of the allowed provers"
+        );
+        Ok(())
+    }
+}
+
+impl<M: MarketClient> MarketClient for TrustedProvers<M> {
+    async fn submit_onchain(&self, request: RequestParams) -> Result<(U256, u64)> {
+        self.market.submit_onchain(request).await
+    }
+
+    async fn submit_offchain(&self, request: RequestParams) -> Result<(U256, u64)> {
+        self.market.submit_offchain(request).await
+    }
+
+    async fn wait_for_request_fulfillment(
+        &self,
+        request_id: U256,
+        check_interval: Duration,
+        expires_at: u64,
+    ) -> Result<Fulfillment> {
+        let fulfillment = self
+            .market
+            .wait_for_request_fulfillment(request_id, check_interval, expires_at)
+            .await?;
+        self.check(request_id).await?;
+        Ok(fulfillment)
+    }
+
+    async fn fulfillment_prover(&self, request_id: U256) -> Result<Option<Address>> {
+        self.market.fulfillment_prover(request_id).await
+    }
+}
+
+/// Submit a compliance request to the market and wait for it to be fulfilled.
+///
+/// A request that carries its id, such as one numbered by [request_index], is logged under it
+/// before submission, and the market must assign it that id.
+pub async fn prove_compliance<M: MarketClient>(
+    market: &M,
+    request: RequestParams,
+    offchain: bool,
+) -> Result<Fulfillment> {
+    let expected = request.request_id.map(U256::from);
+    if let Some(expected) = expected {
+        tracing::info!("Submitting request {expected:x}");
+    }
+    let (request_id, expires_at) = if offchain {
+        market
+            .submit_offchain(request)
+            .await
+            .context("failed to submit request offchain")?
+    } else {
+        market
+            .submit_onchain(request)
+            .await
+            .context("failed to submit request onchain")?
+    };
+    if let Some(expected) = expected {
+        ensure!(
+            request_id == expected,
+            "market assigned request {request_id:x}, not the precomputed {expected:x}"
+        );
+    }
+
+    tracing::info!("Waiting for request {:x} to be fulfilled", request_id);
+    let fulfillment = market
+        .wait_for_request_fulfillment(request_id, FULFILLMENT_CHECK_INTERVAL, expires_at)
+        .await
+        .with_context(|| format!("request {request_id:x} was not fulfilled"))?;
+    tracing::info!("Request {:x} fulfilled", request_id);
+
+    Ok(fulfillment)
+}
+
+/// Submit the same request to every market and return the first fulfillment, with the index of
+/// the market that produced it.
+///
+/// Once one market fulfills, the others are no longer awaited, so exactly one journal and seal
+/// come back and the trade is settled at most once. Their requests stay open onchain until they
+/// expire and may still be fulfilled, so racing costs up to one payment per market.
+pub async fn race_compliance<M: MarketClient>(
+    markets: &[M],
+    request: RequestParams,
+    offchain: bool,
+) -> Result<(usize, Fulfillment)> {
+    if let [market] = markets {
+        return Ok((0, prove_compliance(market, request, offchain).await?));
+    }
+
+    let mut racing: FuturesUnordered<_> = markets
+        .iter()
+        .enumerate()
+        .map(|(index, market)| {
+            let request = request.clone();
+            async move { (index, prove_compliance(market, request, offchain).await) }
+        })
+        .collect();
+
+    let mut errors = Vec::new();
+    while let Some((index, result)) = racing.next().await {
+        match result {
+            Ok(fulfillment) => {
+                tracing::info!(
+                    "Market #{index} fulfilled request {:x} first",
+                    fulfillment.id
+                );
+                return Ok((index, fulfillment));
+            }
+            Err(err) => {
+                tracing::warn!("Market #{index} failed: {err:#}");
+                errors.push(format!("market #{index}: {err:#}"));
+            }
+        }
+    }
+    bail!("no market fulfilled the request: {}", errors.join("; "))
+}
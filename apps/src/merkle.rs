@@ -0,0 +1,285 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Merkle trees of address lists, such as whitelists and sanctions lists, built with the leaf
+//! encoding and pair hashing that [is_member] and [is_non_member] check.
+//!
+//! A membership tree has an [address_leaf] per address and proves an address is listed. A sorted
+//! tree has a [gap_leaf] per gap between adjacent addresses of the sorted list and proves an
+//! address is not listed.
+//!
+//! A cohort tree has a [cohort_leaf] per `(user, productId)` pair a paged batch allowed, and its
+//! root is the `cohortRoot` the batch guest commits. It is rebuilt from the batch's inputs rather
+//! than written to a file.
+
+use std::{collections::HashSet, path::Path};
+
+use alloy::primitives::{Address, B256};
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::write_atomically,
+    journal::{
+        address_leaf, cohort_leaf, evaluate, gap_leaf, is_in_cohort, is_member, is_non_member,
+        ComplianceInput, MerkleTree,
+    },
+};
+
+/// What a tree proves about an address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TreeKind {
+    /// An [address_leaf] per address, in the list's order, proving an address is listed.
+    Membership,
+    /// A [gap_leaf] per gap of the sorted list, proving an address is not listed.
+    Sorted,
+}
+
+/// A tree as `merkle build` writes it: the list it was built from, its root and every level.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeFile {
+    pub kind: TreeKind,
+    pub root: B256,
+    /// The list, sorted for a [TreeKind::Sorted] tree.
+    pub addresses: Vec<Address>,
+    /// Every level of the tree, from the leaves up to the root.
+    pub levels: Vec<Vec<B256>>,
+}
+
+/// Proof that an address is or is not in a tree, as `merkle proof` prints it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum AddressProof {
+    /// `address` is in the membership tree with `root`.
+    Membership {
+        root: B256,
+        address: Address,
+        proof: Vec<B256>,
+    },
+    /// `address` lies strictly between `low` and `high`, adjacent addresses of the sorted tree
+    /// with `root`.
+    NonMembership {
+        root: B256,
+        address: Address,
+        low: Address,
+        high: Address,
+        proof: Vec<B256>,
+    },
+    /// `user` was allowed to trade `productId` by the batch whose cohort tree has `root`.
+    Cohort {
+        root: B256,
+        user: Address,
+        #[serde(rename = "productId")]
+        product_id: B256,
+        proof: Vec<B256>,
+    },
+}
+
+impl AddressProof {
+    pub fn root(&self) -> B256 {
+        match self {
+            Self::Membership { root, .. }
+            | Self::NonMembership { root, .. }
+            | Self::Cohort { root, .. } => *root,
+        }
+    }
+
+    /// Whether the proof reaches its root, checked as the guest checks it.
+    pub fn verify(&self) -> bool {
+        match self {
+            Self::Membership {
+                root,
+                address,
+                proof,
+            } => is_member(*root, *address, proof),
+            Self::NonMembership {
+                root,
+                address,
+                low,
+                high,
+                proof,
+            } => is_non_member(*root, *address, *low, *high, proof),
+            Self::Cohort {
+                root,
+                user,
+                product_id,
+                proof,
+            } => is_in_cohort(*root, *user, *product_id, proof),
+        }
+    }
+}
+
+/// The cohort of a paged batch of `inputs`: the `(user, productId)` pairs of the decisions it
+/// allows, in order, as [BatchFold](crate::journal::BatchFold) adds them to `cohortRoot`.
+pub fn cohort(inputs: &[ComplianceInput]) -> Vec<(Address, B256)> {
+    inputs
+        .iter()
+        .map(evaluate)
+        .filter(|journal| journal.allowed)
+        .map(|journal| (journal.user, journal.productId))
+        .collect()
+}
+
+/// Proof that the paged batch of `inputs` allowed `user` to trade `product_id`, against the
+/// `cohortRoot` it commits.
+pub fn cohort_proof(
+    inputs: &[ComplianceInput],
+    user: Address,
+    product_id: B256,
+) -> Result<AddressProof> {
+    let cohort = cohort(inputs);
+    let Some(index) = cohort.iter().position(|&pair| pair == (user, product_id)) else {
+        bail!(
+            "{user} was not allowed to trade {product_id} in the batch, so it is not in the cohort"
+        );
+    };
+    let tree = MerkleTree::new(
+        cohort
+            .iter()
+            .map(|&(user, product_id)| cohort_leaf(user, product_id))
+            .collect(),
+    );
+    Ok(AddressProof::Cohort {
+        root: tree.root(),
+        user,
+        product_id,
+        proof: tree.proof(index).expect("every pair has a leaf"),
+    })
+}
+
+/// Addresses of a list file: one per line, ignoring blank lines and `#` comments.
+pub fn parse_addresses(text: &str) -> Result<Vec<Address>> {
+    let mut seen = HashSet::new();
+    let mut addresses = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let address: Address = line
+            .parse()
+            .with_context(|| format!("line {}: invalid address {line:?}", number + 1))?;
+        ensure!(
+            seen.insert(address),
+            "line {}: {address} is listed twice",
+            number + 1
+        );
+        addresses.push(address);
+    }
+    Ok(addresses)
+}
+
+/// Gaps between adjacent addresses of `sorted`, from the zero address to `0xff..ff`.
+///
+/// Neither bound is ever strictly inside a gap, so neither can be proven absent.
+fn gaps(sorted: &[Address]) -> Vec<(Address, Address)> {
+    let bounds: Vec<Address> = [Address::ZERO]
+        .into_iter()
+        .chain(sorted.iter().copied())
+        .chain([Address::repeat_byte(0xff)])
+        .collect();
+    bounds
+        .windows(2)
+        .map(|pair| (pair[0], pair[1]))
+        .filter(|(low, high)| low < high)
+        .collect()
+}
+
+impl TreeFile {
+    /// Build a tree of `kind` over `addresses`, which must hold no duplicates.
+    pub fn build(mut addresses: Vec<Address>, kind: TreeKind) -> Result<Self> {
+        let leaves = match kind {
+            TreeKind::Membership => {
+                ensure!(
+                    !addresses.is_empty(),
+                    "a membership tree needs at least one address"
+                );
+                addresses.iter().copied().map(address_leaf).collect()
+            }
+            TreeKind::Sorted => {
+                addresses.sort();
+                gaps(&addresses)
+                    .into_iter()
+                    .map(|(low, high)| gap_leaf(low, high))
+                    .collect()
+            }
+        };
+        let tree = MerkleTree::new(leaves);
+        Ok(Self {
+            kind,
+            root: tree.root(),
+            addresses,
+            levels: tree.levels().to_vec(),
+        })
+    }
+
+    /// Read a tree `merkle build` wrote, checking that its levels and root are those of its list.
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = std::fs::read(path)
+            .with_context(|| format!("failed to read tree {}", path.display()))?;
+        let tree: Self = serde_json::from_slice(&contents)
+            .with_context(|| format!("failed to parse tree {}", path.display()))?;
+        let rebuilt = Self::build(tree.addresses.clone(), tree.kind)
+            .with_context(|| format!("invalid tree {}", path.display()))?;
+        ensure!(
+            rebuilt == tree,
+            "tree {} does not match its address list",
+            path.display()
+        );
+        Ok(tree)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut json = serde_json::to_string_pretty(self).expect("trees always serialize");
+        json.push('\n');
+        write_atomically(path, json.as_bytes())
+            .with_context(|| format!("failed to write tree {}", path.display()))
+    }
+
+    /// Proof of `address`: that it is in a membership tree, or not in a sorted one.
+    pub fn proof(&self, address: Address) -> Result<AddressProof> {
+        let tree = MerkleTree::new(self.levels[0].clone());
+        match self.kind {
+            TreeKind::Membership => {
+                let Some(index) = self.addresses.iter().position(|&a| a == address) else {
+                    bail!("{address} is not in the tree");
+                };
+                Ok(AddressProof::Membership {
+                    root: self.root,
+                    address,
+                    proof: tree.proof(index).expect("every address has a leaf"),
+                })
+            }
+            TreeKind::Sorted => {
+                let gaps = gaps(&self.addresses);
+                let Some(index) = gaps
+                    .iter()
+                    .position(|&(low, high)| low < address && address < high)
+                else {
+                    bail!("{address} is in the list or is a bound, so it cannot be proven absent");
+                };
+                let (low, high) = gaps[index];
+                Ok(AddressProof::NonMembership {
+                    root: self.root,
+                    address,
+                    low,
+                    high,
+                    proof: tree.proof(index).expect("every gap has a leaf"),
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,429 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, fmt, sync::Mutex, time::Duration};
+
+use alloy::primitives::{B256, U256};
+use anyhow::{bail, Context, Result};
+use boundless_market::contracts::boundless_market::MarketError;
+use clap::ValueEnum;
+use futures_util::future::BoxFuture;
+use serde::Serialize;
+use serde_json::json;
+use tokio::time::Instant;
+use url::Url;
+
+use crate::{http::RetryPolicy, settle::Settlement, webhook::WebhookSink};
+
+/// Endpoint of the PagerDuty Events API v2.
+pub const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// How urgently an alert needs a human.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Nothing to do, such as a settled trade.
+    Info,
+    /// A trade failed and may need a retry.
+    Warning,
+    /// Funds were spent for nothing or trades cannot run at all.
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        })
+    }
+}
+
+/// What an alert is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    Settled,
+    RequestExpired,
+    ProofFailed,
+    SettlementFailed,
+    AttestationSourceDown,
+}
+
+impl Event {
+    pub fn severity(self) -> Severity {
+        match self {
+            Self::Settled => Severity::Info,
+            Self::RequestExpired | Self::ProofFailed => Severity::Warning,
+            Self::SettlementFailed | Self::AttestationSourceDown => Severity::Critical,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::Settled => "Trade settled",
+            Self::RequestExpired => "Request expired",
+            Self::ProofFailed => "Proof failed",
+            Self::SettlementFailed => "Settlement failed",
+            Self::AttestationSourceDown => "Attestation source down",
+        }
+    }
+}
+
+/// Something a human may need to act on.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    pub event: Event,
+    pub severity: Severity,
+    pub request_id: Option<U256>,
+    pub tx_hash: Option<B256>,
+    /// What happened, as the error or outcome reads.
+    pub message: String,
+    /// Explorer page of the transaction or request; see [Links].
+    pub link: Option<Url>,
+}
+
+impl Alert {
+    pub fn new(event: Event, request_id: Option<U256>, message: impl Into<String>) -> Self {
+        Self {
+            event,
+            severity: event.severity(),
+            request_id,
+            tx_hash: None,
+            message: message.into(),
+            link: None,
+        }
+    }
+
+    /// Request `request_id` settled as `settlement`.
+    pub fn settled(request_id: U256, settlement: &Settlement) -> Self {
+        let message = match settlement.tx_hash() {
+            Some(tx_hash) => format!("settled in transaction {tx_hash}"),
+            None => "settled".into(),
+        };
+        Self {
+            tx_hash: settlement.tx_hash(),
+            ..Self::new(Event::Settled, Some(request_id), message)
+        }
+    }
+
+    /// Proving failed with `err`, as [Event::RequestExpired] when the market let the request
+    /// expire.
+    pub fn proof_failed(request_id: Option<U256>, err: &anyhow::Error) -> Self {
+        let expired = err.chain().any(|cause| {
+            matches!(
+                cause.downcast_ref::<MarketError>(),
+                Some(MarketError::RequestHasExpired(_))
+            )
+        });
+        let event = if expired {
+            Event::RequestExpired
+        } else {
+            Event::ProofFailed
+        };
+        Self::new(event, request_id, format!("{err:#}"))
+    }
+
+    /// Settling the proof of request `request_id` failed with `err`.
+    pub fn settlement_failed(request_id: U256, err: &anyhow::Error) -> Self {
+        Self::new(
+            Event::SettlementFailed,
+            Some(request_id),
+            format!("{err:#}"),
+        )
+    }
+
+    /// The attestation source could not be read, so no trade can run.
+    pub fn attestation_source_down(err: &anyhow::Error) -> Self {
+        Self::new(Event::AttestationSourceDown, None, format!("{err:#}"))
+    }
+
+    /// One line naming the severity, the event and the request, e.g.
+    /// `[critical] Settlement failed for request 0x...`.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("[{}] {}", self.severity, self.event.title());
+        if let Some(request_id) = self.request_id {
+            summary.push_str(&format!(" for request 0x{request_id:x}"));
+        }
+        summary
+    }
+}
+
+/// Base URLs alerts link to.
+#[derive(Clone, Debug, Default)]
+pub struct Links {
+    /// Block explorer, linked to as `<url>/tx/<hash>` for alerts with a transaction.
+    pub explorer: Option<Url>,
+    /// Request explorer, linked to as `<url>/<request id>` for alerts without one.
+    pub request_explorer: Option<Url>,
+}
+
+impl Links {
+    fn link(&self, alert: &Alert) -> Option<Url> {
+        let (base, path) = match (alert.tx_hash, alert.request_id) {
+            (Some(tx_hash), _) => (self.explorer.as_ref()?, format!("tx/{tx_hash}")),
+            (None, Some(request_id)) => {
+                (self.request_explorer.as_ref()?, format!("0x{request_id:x}"))
+            }
+            (None, None) => return None,
+        };
+        let mut base = base.clone();
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        base.join(&path).ok()
+    }
+}
+
+/// A place alerts are sent to.
+///
+/// Sinks are boxed to be routed together, so the future is boxed as well.
+pub trait Notifier: Send + Sync {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<()>>;
+}
+
+/// POST `body` as JSON to `url` under `policy`, failing on a non-success status.
+async fn post_json(
+    http: &reqwest::Client,
+    policy: &RetryPolicy,
+    url: &Url,
+    body: &serde_json::Value,
+) -> Result<()> {
+    let response = policy
+        .send(|| http.post(url.clone()).json(body))
+        .await
+        .with_context(|| format!("failed to send alert to {url}"))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        bail!("alert rejected with {status}: {body}");
+    }
+    Ok(())
+}
+
+/// Posts alerts to a Slack incoming webhook.
+#[derive(Clone, Debug)]
+pub struct SlackNotifier {
+    url: Url,
+    policy: RetryPolicy,
+    http: reqwest::Client,
+}
+
+impl SlackNotifier {
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            policy: RetryPolicy::default(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Replace the default timeout and retry policy.
+    pub fn with_policy(self, policy: RetryPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Message text in Slack's `mrkdwn`: the summary in bold, the message, and the link.
+    pub fn text(alert: &Alert) -> String {
+        let mut text = format!("*{}*\n{}", alert.summary(), alert.message);
+        if let Some(link) = &alert.link {
+            text.push_str(&format!("\n<{link}|View in explorer>"));
+        }
+        text
+    }
+}
+
+impl Notifier for SlackNotifier {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let body = json!({ "text": Self::text(alert) });
+            post_json(&self.http, &self.policy, &self.url, &body).await
+        })
+    }
+}
+
+/// Triggers PagerDuty incidents through the Events API v2.
+///
+/// Alerts of the same event and request share a dedup key, so PagerDuty folds repeats into one
+/// incident as well.
+#[derive(Clone, Debug)]
+pub struct PagerDutyNotifier {
+    routing_key: String,
+    url: Url,
+    policy: RetryPolicy,
+    http: reqwest::Client,
+}
+
+impl PagerDutyNotifier {
+    pub fn new(routing_key: String) -> Self {
+        Self {
+            routing_key,
+            url: PAGERDUTY_EVENTS_URL.parse().expect("valid URL"),
+            policy: RetryPolicy::default(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Send events to `url` instead of [PAGERDUTY_EVENTS_URL].
+    pub fn with_url(self, url: Url) -> Self {
+        Self { url, ..self }
+    }
+
+    /// Replace the default timeout and retry policy.
+    pub fn with_policy(self, policy: RetryPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// The trigger event for `alert`.
+    pub fn event(&self, alert: &Alert) -> serde_json::Value {
+        let mut summary = format!("{}: {}", alert.summary(), alert.message);
+        // The Events API rejects longer summaries.
+        if summary.len() > 1024 {
+            let mut end = 1024;
+            while !summary.is_char_boundary(end) {
+                end -= 1;
+            }
+            summary.truncate(end);
+        }
+        let dedup_key = match alert.request_id {
+            Some(request_id) => format!("{}:0x{request_id:x}", alert.event.title()),
+            None => alert.event.title().to_owned(),
+        };
+        let links: Vec<_> = alert
+            .link
+            .iter()
+            .map(|link| json!({ "href": link, "text": "View in explorer" }))
+            .collect();
+        json!({
+            "routing_key": self.routing_key,
+            "event_action": "trigger",
+            "dedup_key": dedup_key,
+            "payload": {
+                "summary": summary,
+                "source": "trading-hooks",
+                "severity": alert.severity,
+                "custom_details": alert,
+            },
+            "links": links,
+        })
+    }
+}
+
+impl Notifier for PagerDutyNotifier {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<()>> {
+        Box::pin(
+            async move { post_json(&self.http, &self.policy, &self.url, &self.event(alert)).await },
+        )
+    }
+}
+
+impl Notifier for WebhookSink {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.post(alert, "alert"))
+    }
+}
+
+struct Route {
+    name: &'static str,
+    min_severity: Severity,
+    sink: Box<dyn Notifier>,
+}
+
+/// Sends each alert to the sinks whose minimum severity it reaches.
+///
+/// An alert for the same event and request as one sent within the last `window` is dropped, so a
+/// request that keeps failing does not page on every attempt.
+pub struct Notifications {
+    routes: Vec<Route>,
+    links: Links,
+    window: Duration,
+    sent: Mutex<HashMap<(Event, Option<U256>), Instant>>,
+}
+
+impl fmt::Debug for Notifications {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let routes: Vec<_> = self
+            .routes
+            .iter()
+            .map(|route| format!("{} >= {}", route.name, route.min_severity))
+            .collect();
+        f.debug_struct("Notifications")
+            .field("routes", &routes)
+            .field("links", &self.links)
+            .field("window", &self.window)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Notifications {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            routes: Vec::new(),
+            links: Links::default(),
+            window,
+            sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Link alerts to the explorers in `links`.
+    pub fn with_links(self, links: Links) -> Self {
+        Self { links, ..self }
+    }
+
+    /// Send alerts of at least `min_severity` to `sink`, named `name` in the log.
+    pub fn route(
+        mut self,
+        name: &'static str,
+        min_severity: Severity,
+        sink: impl Notifier + 'static,
+    ) -> Self {
+        self.routes.push(Route {
+            name,
+            min_severity,
+            sink: Box::new(sink),
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// Send `alert` to its sinks. Failures are only logged: an alert never fails a trade.
+    pub async fn notify(&self, mut alert: Alert) {
+        let key = (alert.event, alert.request_id);
+        {
+            let now = Instant::now();
+            let mut sent = self.sent.lock().unwrap();
+            sent.retain(|_, at| now.duration_since(*at) < self.window);
+            if sent.contains_key(&key) {
+                tracing::debug!("Dropping repeated alert: {}", alert.summary());
+                return;
+            }
+            sent.insert(key, now);
+        }
+
+        alert.link = self.links.link(&alert);
+        for route in &self.routes {
+            if alert.severity < route.min_severity {
+                continue;
+            }
+            if let Err(err) = route.sink.notify(&alert).await {
+                tracing::warn!("Failed to send alert to {}: {err:#}", route.name);
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Uniswap v4 pools given on the command line.
+//!
+//! The product id of a pool is derived by [PoolKey::id] in `compliance-core`. This module only reads
+//! keys, and rejects the ones `PoolManager` would refuse to initialize, since they name no pool.
+
+use std::str::FromStr;
+
+use alloy::primitives::{
+    aliases::{I24, U24},
+    Address,
+};
+use anyhow::{ensure, Context, Result};
+pub use compliance_core::pool::PoolKey;
+
+/// Largest static LP fee, in hundredths of a bip (`LPFeeLibrary.MAX_LP_FEE`).
+pub const MAX_LP_FEE: u32 = 1_000_000;
+/// Fee of a pool whose hook sets the fee (`LPFeeLibrary.DYNAMIC_FEE_FLAG`).
+pub const DYNAMIC_FEE_FLAG: u32 = 0x80_0000;
+/// Tick spacings `PoolManager` accepts (`TickMath.MIN_TICK_SPACING` to `MAX_TICK_SPACING`).
+pub const TICK_SPACINGS: std::ops::RangeInclusive<i32> = 1..=32767;
+
+/// Parse a pool key written as `<CURRENCY0>,<CURRENCY1>,<FEE>,<TICK_SPACING>,<HOOKS>`.
+pub fn parse_pool_key(s: &str) -> Result<PoolKey> {
+    let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+    let [currency0, currency1, fee, tick_spacing, hooks] = parts[..] else {
+        anyhow::bail!("expected <CURRENCY0>,<CURRENCY1>,<FEE>,<TICK_SPACING>,<HOOKS>");
+    };
+    let currency0 = Address::from_str(currency0).context("invalid currency0")?;
+    let currency1 = Address::from_str(currency1).context("invalid currency1")?;
+    let fee: u32 = fee.parse().context("invalid fee")?;
+    let tick_spacing: i32 = tick_spacing.parse().context("invalid tick spacing")?;
+    let hooks = Address::from_str(hooks).context("invalid hooks address")?;
+
+    ensure!(
+        currency0 < currency1,
+        "currency0 must sort below currency1, as PoolManager requires"
+    );
+    ensure!(
+        fee <= MAX_LP_FEE || fee == DYNAMIC_FEE_FLAG,
+        "fee {fee} is above {MAX_LP_FEE} and is not the dynamic fee flag {DYNAMIC_FEE_FLAG:#x}"
+    );
+    ensure!(
+        TICK_SPACINGS.contains(&tick_spacing),
+        "tick spacing {tick_spacing} is outside {TICK_SPACINGS:?}"
+    );
+    Ok(PoolKey {
+        currency0,
+        currency1,
+        fee: U24::from(fee),
+        tickSpacing: I24::try_from(tick_spacing)?,
+        hooks,
+    })
+}
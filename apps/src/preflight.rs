@@ -0,0 +1,229 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloy::{
+    primitives::{utils::format_ether, Address, FixedBytes, U256},
+    providers::Provider,
+};
+use anyhow::{bail, ensure, Context, Result};
+use boundless_market::request_builder::OfferLayerConfig;
+
+use crate::fixtures::DEV_MODE_SELECTOR;
+
+alloy::sol! {
+    /// The verifier `ComplianceHook` checks seals with.
+    #[sol(rpc)]
+    interface IVerifierOwner {
+        function VERIFIER() external view returns (address);
+    }
+
+    /// Selector of a single RISC Zero verifier, such as the Groth16, set or mock verifier.
+    #[sol(rpc)]
+    interface ISelectorVerifier {
+        function SELECTOR() external view returns (bytes4);
+    }
+
+    /// `RiscZeroVerifierRouter`, which dispatches each seal to the verifier of its selector.
+    #[sol(rpc)]
+    interface IVerifierRouter {
+        function getVerifier(bytes4 selector) external view returns (address);
+    }
+}
+
+/// Length of a Groth16 seal: the selector and the proof's eight words.
+pub const GROTH16_SEAL_LEN: usize = 4 + 8 * 32;
+
+/// Kind of a seal, told apart by its selector and length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SealKind {
+    /// A dev-mode seal for `RiscZeroMockVerifier`: its selector and the claim digest.
+    DevMode,
+    Groth16,
+    /// An inclusion proof in a set the set verifier has verified, as Boundless fulfills with.
+    SetInclusion,
+    Unknown,
+}
+
+impl SealKind {
+    /// Kind of `seal`. Anything that is neither dev-mode nor Groth16 but ABI-encodes words after
+    /// its selector is taken for a set inclusion seal.
+    pub fn of(seal: &[u8]) -> Self {
+        match seal.len() {
+            36 if seal[..4] == DEV_MODE_SELECTOR => Self::DevMode,
+            GROTH16_SEAL_LEN => Self::Groth16,
+            len if len > 4 && (len - 4) % 32 == 0 => Self::SetInclusion,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::DevMode => "dev-mode",
+            Self::Groth16 => "groth16",
+            Self::SetInclusion => "set inclusion",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Check that the verifier of the hook at `hook` takes `seal`, before `beforeTrade` is sent with
+/// it and reverts onchain.
+///
+/// A verifier with a `SELECTOR` takes only seals of that selector; a router takes seals of any
+/// selector it has a verifier for. A seal of another selector, or a verifier that is neither,
+/// fails the check.
+pub async fn check_seal<P: Provider>(provider: &P, hook: Address, seal: &[u8]) -> Result<SealKind> {
+    ensure!(
+        seal.len() >= 4,
+        "seal is {} bytes, too short to carry a verifier selector",
+        seal.len()
+    );
+    let kind = SealKind::of(seal);
+    let selector = FixedBytes::<4>::from_slice(&seal[..4]);
+    let verifier = IVerifierOwner::new(hook, provider)
+        .VERIFIER()
+        .call()
+        .await
+        .with_context(|| format!("failed to read the verifier of hook {hook}"))?;
+    if let Ok(expected) = ISelectorVerifier::new(verifier, provider)
+        .SELECTOR()
+        .call()
+        .await
+    {
+        tracing::info!(
+            "Seal is {} with selector {selector}; verifier {verifier} expects selector {expected}",
+            kind.name()
+        );
+        ensure!(
+            selector == expected,
+            "seal is {} with selector {selector}, but the hook's verifier {verifier} only takes \
+             selector {expected}",
+            kind.name()
+        );
+        return Ok(kind);
+    }
+    let routed = IVerifierRouter::new(verifier, provider)
+        .getVerifier(selector)
+        .call()
+        .await
+        .with_context(|| {
+            format!(
+                "seal is {} with selector {selector}, which the hook's verifier {verifier} has no \
+                 verifier for, or it is neither a RISC Zero verifier nor a router",
+                kind.name()
+            )
+        })?;
+    ensure!(
+        !routed.is_zero(),
+        "seal is {} with selector {selector}, which the hook's verifier router {verifier} has no \
+         verifier for",
+        kind.name()
+    );
+    tracing::info!(
+        "Seal is {} with selector {selector}; verifier router {verifier} routes it to {routed}",
+        kind.name()
+    );
+    Ok(kind)
+}
+
+/// Gas budgeted for one `beforeTrade` call when estimating the balance a run needs.
+///
+/// A conservative budget for verifying the seal through the router and running the hook checks.
+pub const SETTLEMENT_GAS_ESTIMATE: u64 = 300_000;
+
+/// Rough balance needed to settle `settlements` trades from the signer at `gas_price`.
+///
+/// The proof payment is not included: the market price of a request is only known once the offer
+/// is built, so runs that pay for proofs from the same account should pass an explicit minimum.
+pub fn estimate_required_balance(settlements: usize, gas_price: u128) -> U256 {
+    U256::from(settlements) * U256::from(SETTLEMENT_GAS_ESTIMATE) * U256::from(gas_price)
+}
+
+/// Fail with the shortfall if `balance` is below `required`.
+pub fn ensure_sufficient_balance(account: Address, balance: U256, required: U256) -> Result<()> {
+    if balance < required {
+        bail!(
+            "signer {account} holds {} ETH but the run needs about {} ETH (short by {} ETH)",
+            format_ether(balance),
+            format_ether(required),
+            format_ether(required - balance),
+        );
+    }
+    Ok(())
+}
+
+/// Check that `account` can pay for the run before any proof is requested.
+///
+/// `min_balance` replaces the estimate from [estimate_required_balance] when given.
+pub async fn check_balance<P: Provider>(
+    provider: &P,
+    account: Address,
+    min_balance: Option<U256>,
+    onchain_settlements: usize,
+) -> Result<()> {
+    let balance = provider
+        .get_balance(account)
+        .await
+        .context("failed to query signer balance")?;
+    let required = match min_balance {
+        Some(min_balance) => min_balance,
+        None => {
+            let gas_price = provider
+                .get_gas_price()
+                .await
+                .context("failed to query gas price")?;
+            estimate_required_balance(onchain_settlements, gas_price)
+        }
+    };
+    tracing::info!(
+        "Signer {account} balance: {} ETH, estimated requirement: {} ETH",
+        format_ether(balance),
+        format_ether(required)
+    );
+    ensure_sufficient_balance(account, balance, required)
+}
+
+/// Range a request for a proof of `cycles` cycles is offered at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProofCostEstimate {
+    pub cycles: u64,
+    pub gas_price: u128,
+    /// Price the offer starts at.
+    pub min_price: U256,
+    /// Price the offer ramps up to, which covers the prover's gas as well.
+    pub max_price: U256,
+}
+
+/// Estimate what a request of `cycles` cycles costs at `gas_price`, priced the way the offer
+/// layer of `config` prices it: a price per cycle, with the prover's lock and fulfill gas plus 10%
+/// added to the maximum.
+///
+/// The market has no price feed of its own, so this is the range the request is offered at, not
+/// the price a prover ends up locking it at.
+pub fn estimate_proof_cost(
+    config: &OfferLayerConfig,
+    cycles: u64,
+    gas_price: u128,
+) -> ProofCostEstimate {
+    let gas = config.lock_gas_estimate + config.fulfill_gas_estimate;
+    let gas_cost = U256::from(gas) * U256::from(gas_price);
+    ProofCostEstimate {
+        cycles,
+        gas_price,
+        min_price: config.min_price_per_cycle * U256::from(cycles),
+        max_price: config.max_price_per_cycle * U256::from(cycles)
+            + gas_cost
+            + gas_cost / U256::from(10),
+    }
+}
@@ -0,0 +1,283 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Content-addressed upload of the guest program.
+//!
+//! A program is stored under a key derived from its image id, so an ELF that was uploaded before
+//! is found again instead of being written again. [upload_program] asks the storage for the
+//! object under the key first and reuses it when it has the program's size and SHA-256. Anything
+//! else under the key is a damaged or foreign upload and is replaced.
+
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+};
+
+use alloy::primitives::B256;
+use anyhow::{anyhow, bail, Context, Result};
+use boundless_market::StorageProvider;
+use reqwest::header::CONTENT_LENGTH;
+use risc0_zkvm::{compute_image_id, sha::Digest};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use url::Url;
+
+use crate::{cache::write_atomically, journal::compliance_image_id};
+
+/// Storage key of the program with `image_id`.
+pub fn program_key(image_id: Digest) -> String {
+    format!("program/{image_id}")
+}
+
+/// A program that requests name by URL, with its image id when it is known up front. Provers
+/// and preflight then check the program they fetch against it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProgramRef {
+    pub url: Url,
+    pub image_id: Option<B256>,
+}
+
+/// Check that a program referenced by `image_id` is the embedded compliance guest, unless
+/// `allow_mismatch`.
+pub fn check_program_image_id(image_id: B256, allow_mismatch: bool) -> Result<()> {
+    let embedded = compliance_image_id();
+    if image_id == embedded {
+        return Ok(());
+    }
+    if !allow_mismatch {
+        bail!(
+            "program image id {image_id} is not the embedded guest's {embedded}; pass \
+             --allow-image-id-mismatch to use it anyway"
+        );
+    }
+    tracing::warn!("Requesting proofs of program {image_id} instead of the embedded {embedded}");
+    Ok(())
+}
+
+/// The program at `url`, or `None` if it is at a URL that cannot be read from here, such as
+/// `s3://`.
+pub async fn fetch_program(http: &reqwest::Client, url: &Url) -> Result<Option<Vec<u8>>> {
+    match url.scheme() {
+        "file" => {
+            let path = url
+                .to_file_path()
+                .map_err(|()| anyhow!("{url} is not a file path"))?;
+            std::fs::read(&path)
+                .map(Some)
+                .with_context(|| format!("failed to read program {}", path.display()))
+        }
+        "http" | "https" => {
+            let response = http
+                .get(url.clone())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .with_context(|| format!("failed to fetch program {url}"))?;
+            let program = response
+                .bytes()
+                .await
+                .with_context(|| format!("failed to fetch program {url}"))?;
+            Ok(Some(program.to_vec()))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The program at `url`, once it is fetched and found to be the embedded compliance guest; see
+/// [check_program_image_id]. A program that cannot be fetched from here is used unchecked, with a
+/// warning.
+pub async fn check_program_url(url: Url, allow_mismatch: bool) -> Result<ProgramRef> {
+    let Some(program) = fetch_program(&reqwest::Client::new(), &url).await? else {
+        tracing::warn!("Cannot fetch {url} to check that it is the embedded guest program");
+        return Ok(ProgramRef {
+            url,
+            image_id: None,
+        });
+    };
+    let image_id = compute_image_id(&program)
+        .with_context(|| format!("failed to compute image id of program {url}"))?;
+    let image_id = B256::from(<[u8; 32]>::from(image_id));
+    check_program_image_id(image_id, allow_mismatch)?;
+    Ok(ProgramRef {
+        url,
+        image_id: Some(image_id),
+    })
+}
+
+/// The program with `image_id` if `storage` already holds it. Nothing is uploaded.
+pub async fn find_program(
+    storage: &impl ProgramStorage,
+    image_id: B256,
+) -> Result<Option<ProgramRef>> {
+    let key = program_key(Digest::from(<[u8; 32]>::from(image_id)));
+    Ok(storage.head(&key).await?.map(|stored| ProgramRef {
+        url: stored.url,
+        image_id: Some(image_id),
+    }))
+}
+
+/// An object held by a [ProgramStorage].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredProgram {
+    pub url: Url,
+    pub size: u64,
+    pub sha256: B256,
+}
+
+/// Storage that programs can be looked up in by key before they are uploaded.
+pub trait ProgramStorage: Send + Sync {
+    /// The object stored under `key`, if there is one.
+    fn head(&self, key: &str) -> impl Future<Output = Result<Option<StoredProgram>>> + Send;
+
+    /// Store `program` under `key`, replacing any object there, and return its URL.
+    fn put(&self, key: &str, program: &[u8]) -> impl Future<Output = Result<Url>> + Send;
+}
+
+/// Upload `program` to `storage` unless it already holds it, and return its URL.
+pub async fn upload_program(storage: &impl ProgramStorage, program: &[u8]) -> Result<Url> {
+    let image_id = compute_image_id(program).context("failed to compute program image id")?;
+    let key = program_key(image_id);
+    let size = program.len() as u64;
+    let sha256 = B256::from_slice(&Sha256::digest(program));
+
+    match storage.head(&key).await? {
+        Some(stored) if stored.size == size && stored.sha256 == sha256 => {
+            tracing::info!("Program {image_id} is already uploaded to {}", stored.url);
+            return Ok(stored.url);
+        }
+        Some(stored) => tracing::warn!(
+            "Object under {key} at {} does not match the program ({} bytes, sha256 {}); \
+             uploading it again",
+            stored.url,
+            stored.size,
+            stored.sha256
+        ),
+        None => {}
+    }
+    let url = storage.put(&key, program).await?;
+    tracing::info!("Uploaded program {image_id} to {url}");
+    Ok(url)
+}
+
+/// [ProgramStorage] over a Boundless storage provider, which can only upload.
+///
+/// Uploads are recorded in an index directory, one `<key>.json` file per program, and an indexed
+/// object is looked for at its URL: on disk for `file://` URLs and with a `HEAD` request for HTTP
+/// ones. An object that is gone, or that the server will not confirm, is uploaded again. Objects
+/// at other URLs, such as `s3://`, are taken to still exist.
+pub struct IndexedStorage<St> {
+    provider: St,
+    index: PathBuf,
+    http: reqwest::Client,
+}
+
+impl<St> IndexedStorage<St> {
+    pub fn new(provider: St, index: impl Into<PathBuf>) -> Self {
+        Self {
+            provider,
+            index: index.into(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    fn record_path(&self, key: &str) -> PathBuf {
+        self.index.join(format!("{key}.json"))
+    }
+
+    /// Size of the object at `url`, `None` if it is gone, or the `recorded` size if it cannot be
+    /// checked.
+    async fn object_size(&self, url: &Url, recorded: u64) -> Result<Option<u64>> {
+        match url.scheme() {
+            "file" => {
+                let path = url
+                    .to_file_path()
+                    .map_err(|()| anyhow!("{url} is not a file path"))?;
+                file_size(&path)
+            }
+            "http" | "https" => {
+                let response = self
+                    .http
+                    .head(url.clone())
+                    .send()
+                    .await
+                    .with_context(|| format!("failed to look up {url}"))?;
+                if !response.status().is_success() {
+                    tracing::debug!("{url} responded with {}", response.status());
+                    return Ok(None);
+                }
+                // `HEAD` responses have no body, so the length is only in the header.
+                let size = response
+                    .headers()
+                    .get(CONTENT_LENGTH)
+                    .and_then(|value| value.to_str().ok()?.parse().ok());
+                Ok(Some(size.unwrap_or(recorded)))
+            }
+            _ => Ok(Some(recorded)),
+        }
+    }
+}
+
+fn file_size(path: &Path) -> Result<Option<u64>> {
+    match std::fs::metadata(path) {
+        Ok(metadata) => Ok(Some(metadata.len())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("failed to stat {}", path.display())),
+    }
+}
+
+impl<St> ProgramStorage for IndexedStorage<St>
+where
+    St: StorageProvider + Send + Sync,
+{
+    async fn head(&self, key: &str) -> Result<Option<StoredProgram>> {
+        let path = self.record_path(key);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to read upload record {}", path.display()))
+            }
+        };
+        let record: StoredProgram = serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse upload record {}", path.display()))?;
+        let Some(size) = self.object_size(&record.url, record.size).await? else {
+            tracing::info!("Program under {key} is no longer at {}", record.url);
+            return Ok(None);
+        };
+        Ok(Some(StoredProgram { size, ..record }))
+    }
+
+    async fn put(&self, key: &str, program: &[u8]) -> Result<Url> {
+        let url = self
+            .provider
+            .upload_program(program)
+            .await
+            .map_err(|err| anyhow!("failed to upload program: {err:?}"))?;
+        let record = StoredProgram {
+            url: url.clone(),
+            size: program.len() as u64,
+            sha256: B256::from_slice(&Sha256::digest(program)),
+        };
+        let path = self.record_path(key);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create upload index {}", dir.display()))?;
+        }
+        write_atomically(&path, &serde_json::to_vec_pretty(&record)?)
+            .with_context(|| format!("failed to write upload record {}", path.display()))?;
+        Ok(url)
+    }
+}
@@ -0,0 +1,446 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Relaying settled decisions to a second chain.
+//!
+//! Under `--relay-to <chain>`, [RelayingSettler] settles each trade with the hook as usual and then
+//! delivers the same decision to the `IComplianceReceiver` of that chain of `--chains-config`,
+//! from the chain's own RPC endpoint, key and fee caps. A chain with a RISC Zero verifier gets the
+//! journal and seal; one without gets the operator's signed result, so `--sign-results` is needed
+//! for it.
+//!
+//! Each decision is a [RelayRecord] in a [RelayStore], which tracks the settlement and the relay
+//! transaction apart: the relay is recorded as sent before it is confirmed, and as failed with its
+//! error when it does not go through. `relay retry` delivers every relay that is not confirmed,
+//! checking first whether a transaction it sent earlier did confirm after all.
+
+use std::path::PathBuf;
+
+use alloy::{
+    network::TransactionBuilder,
+    primitives::{Address, Bytes, B256, U256},
+    providers::{DynProvider, Provider, ProviderBuilder},
+    rpc::types::TransactionRequest,
+    signers::local::PrivateKeySigner,
+    sol_types::SolCall,
+};
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    batch::BatchEntry,
+    cache::write_atomically,
+    chains::ChainConfig,
+    compliance_receiver::IComplianceReceiver::{
+        receiveDecisionCall, receiveSignedResultCall, ComplianceResult,
+    },
+    settle::{Settlement, Settler, TX_TIMEOUT},
+    signing::SignedResult,
+};
+
+/// Where a transaction of a [RelayRecord] stands.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TxStatus {
+    /// Not sent yet.
+    #[default]
+    Pending,
+    /// Sent, but not known to be confirmed.
+    Sent,
+    Confirmed,
+    /// Reverted, or could not be sent or confirmed.
+    Failed,
+}
+
+/// The settlement of a decision with the hook, on the chain the run proves for.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrimaryTx {
+    pub chain_id: u64,
+    /// Hash of the `beforeTrade` transaction, when the settlement knows it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<B256>,
+    pub status: TxStatus,
+}
+
+/// The delivery of a decision to the receiver on the second chain.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayTx {
+    /// Name of the chain in `--chains-config`.
+    pub chain: String,
+    pub chain_id: u64,
+    pub receiver: Address,
+    /// Hash of the last transaction sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<B256>,
+    pub status: TxStatus,
+    /// Why the last attempt failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A decision relayed to a second chain, as recorded in the [RelayStore].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayRecord {
+    /// SHA-256 of the journal, which the record is stored under.
+    pub journal_digest: B256,
+    pub user: Address,
+    pub product_id: B256,
+    pub journal: Bytes,
+    pub seal: Bytes,
+    /// The operator's signed result, relayed to chains without a verifier.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signed_result: Option<SignedResult>,
+    pub primary: PrimaryTx,
+    pub relay: RelayTx,
+}
+
+impl RelayRecord {
+    /// Record of the decision `journal` commits, settled by `primary`, to relay to `relay`.
+    pub fn new(
+        entry: &BatchEntry,
+        journal: Bytes,
+        seal: Bytes,
+        signed_result: Option<SignedResult>,
+        primary: PrimaryTx,
+        relay: RelayTx,
+    ) -> Self {
+        Self {
+            journal_digest: B256::from_slice(&Sha256::digest(&journal)),
+            user: entry.user,
+            product_id: entry.product_id,
+            journal,
+            seal,
+            signed_result,
+            primary,
+            relay,
+        }
+    }
+
+    /// Calldata delivering the decision to the receiver: the journal and seal when the chain has
+    /// a verifier, the signed result otherwise.
+    pub fn calldata(&self, with_verifier: bool) -> Result<Bytes> {
+        if with_verifier {
+            return Ok(receiveDecisionCall {
+                journal: self.journal.clone(),
+                seal: self.seal.clone(),
+            }
+            .abi_encode()
+            .into());
+        }
+        let signed = self.signed_result.as_ref().with_context(|| {
+            format!(
+                "chain {} has no verifier, so the decision needs a signed result; pass \
+                 --sign-results",
+                self.relay.chain
+            )
+        })?;
+        Ok(receiveSignedResultCall {
+            result: ComplianceResult {
+                user: signed.user,
+                productId: signed.product_id,
+                allowed: signed.allowed,
+                journalDigest: signed.journal_digest,
+                imageId: signed.image_id,
+                validUntil: signed.valid_until,
+            },
+            signature: signed.signature.clone(),
+        }
+        .abi_encode()
+        .into())
+    }
+}
+
+/// A directory of relayed decisions, one `<journal digest>.json` file each.
+#[derive(Clone, Debug)]
+pub struct RelayStore {
+    dir: PathBuf,
+}
+
+impl RelayStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, journal_digest: B256) -> PathBuf {
+        self.dir.join(format!("{journal_digest:x}.json"))
+    }
+
+    /// Record `record`, replacing any earlier record of its journal.
+    pub fn insert(&self, record: &RelayRecord) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create relay store {}", self.dir.display()))?;
+        let path = self.path(record.journal_digest);
+        write_atomically(&path, &serde_json::to_vec_pretty(record)?)
+            .with_context(|| format!("failed to write relay record {}", path.display()))
+    }
+
+    /// Every record, in journal digest order.
+    pub fn records(&self) -> Result<Vec<RelayRecord>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to read relay store {}", self.dir.display()))
+            }
+        };
+        let mut records = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let contents = std::fs::read(&path)
+                .with_context(|| format!("failed to read relay record {}", path.display()))?;
+            let record: RelayRecord = serde_json::from_slice(&contents)
+                .with_context(|| format!("failed to parse relay record {}", path.display()))?;
+            records.push(record);
+        }
+        records.sort_by_key(|record| record.journal_digest);
+        Ok(records)
+    }
+
+    /// Records whose settlement confirmed but whose relay did not.
+    pub fn unconfirmed(&self) -> Result<Vec<RelayRecord>> {
+        Ok(self
+            .records()?
+            .into_iter()
+            .filter(|record| {
+                record.primary.status == TxStatus::Confirmed
+                    && record.relay.status != TxStatus::Confirmed
+            })
+            .collect())
+    }
+}
+
+/// Sends decisions to the receiver of one chain of `--chains-config`.
+pub struct Relay {
+    name: String,
+    chain: ChainConfig,
+    receiver: Address,
+    provider: DynProvider,
+    from: Address,
+}
+
+impl Relay {
+    /// Relay to the chain `name`, sending from its key or else `fallback`.
+    pub fn connect(
+        name: &str,
+        chain: &ChainConfig,
+        fallback: Option<&PrivateKeySigner>,
+    ) -> Result<Self> {
+        let receiver = chain
+            .receiver_address
+            .with_context(|| format!("chain {name} sets no receiver_address to relay to"))?;
+        let signer = chain
+            .signer(fallback)
+            .with_context(|| format!("no key to relay to chain {name} with"))?;
+        let from = signer.address();
+        let provider = ProviderBuilder::new()
+            .wallet(signer)
+            .connect_http(chain.rpc_url.clone())
+            .erased();
+        Ok(Self {
+            name: name.to_owned(),
+            chain: chain.clone(),
+            receiver,
+            provider,
+            from,
+        })
+    }
+
+    /// The part of a [RelayRecord] this relay fills in, before anything is sent.
+    pub fn target(&self) -> RelayTx {
+        RelayTx {
+            chain: self.name.clone(),
+            chain_id: self.chain.chain_id,
+            receiver: self.receiver,
+            tx_hash: None,
+            status: TxStatus::Pending,
+            error: None,
+        }
+    }
+
+    /// Deliver the decision of `record` and keep `store` up to date as it goes: the transaction
+    /// is recorded once sent, and again once confirmed or failed. A transaction sent by an
+    /// earlier attempt that did confirm is taken as the delivery instead of sending another.
+    pub async fn deliver(&self, record: &mut RelayRecord, store: &RelayStore) -> Result<B256> {
+        match self.try_deliver(record, store).await {
+            Ok(tx_hash) => {
+                record.relay.status = TxStatus::Confirmed;
+                record.relay.error = None;
+                store.insert(record)?;
+                Ok(tx_hash)
+            }
+            Err(err) => {
+                record.relay.status = TxStatus::Failed;
+                record.relay.error = Some(format!("{err:#}"));
+                store.insert(record)?;
+                Err(err)
+            }
+        }
+    }
+
+    async fn try_deliver(&self, record: &mut RelayRecord, store: &RelayStore) -> Result<B256> {
+        ensure!(
+            record.relay.chain_id == self.chain.chain_id && record.relay.receiver == self.receiver,
+            "the decision is to be relayed to receiver {} on chain {}, but chain {} has receiver \
+             {} on chain {}",
+            record.relay.receiver,
+            record.relay.chain_id,
+            self.name,
+            self.receiver,
+            self.chain.chain_id
+        );
+        self.chain.ensure_chain_id(&self.provider).await?;
+        if let Some(tx_hash) = record.relay.tx_hash {
+            let receipt = self
+                .provider
+                .get_transaction_receipt(tx_hash)
+                .await
+                .with_context(|| format!("failed to query relay tx {tx_hash}"))?;
+            if receipt.is_some_and(|receipt| receipt.status()) {
+                tracing::info!("Relay tx {tx_hash} to chain {} had confirmed", self.name);
+                return Ok(tx_hash);
+            }
+        }
+
+        let calldata = record.calldata(self.chain.verifier_address.is_some())?;
+        let mut tx = TransactionRequest::default()
+            .with_from(self.from)
+            .with_to(self.receiver)
+            .with_input(calldata)
+            .with_value(U256::ZERO);
+        if let Some(max_fee) = self.chain.max_fee_per_gas {
+            tx.set_max_fee_per_gas(max_fee);
+        }
+        if let Some(max_priority_fee) = self.chain.max_priority_fee_per_gas {
+            tx.set_max_priority_fee_per_gas(max_priority_fee);
+        }
+        tracing::info!(
+            "Relaying decision {} to chain {}",
+            record.journal_digest,
+            self.name
+        );
+        let pending_tx = self
+            .provider
+            .send_transaction(tx)
+            .await
+            .context("failed to broadcast relay tx")?;
+        let tx_hash = *pending_tx.tx_hash();
+        record.relay.tx_hash = Some(tx_hash);
+        record.relay.status = TxStatus::Sent;
+        store.insert(record)?;
+
+        let receipt = pending_tx
+            .with_timeout(Some(TX_TIMEOUT))
+            .get_receipt()
+            .await
+            .with_context(|| format!("failed to confirm relay tx {tx_hash}"))?;
+        ensure!(receipt.status(), "relay tx {tx_hash} reverted");
+        tracing::info!("Relay tx {tx_hash} to chain {} confirmed", self.name);
+        Ok(tx_hash)
+    }
+}
+
+/// Settles with the wrapped settler, then relays the decision with [Relay].
+pub struct RelayingSettler<S> {
+    settler: S,
+    chain_id: u64,
+    relay: Relay,
+    store: RelayStore,
+}
+
+impl<S> RelayingSettler<S> {
+    /// Relay decisions settled on `chain_id` by `settler`.
+    pub fn new(settler: S, chain_id: u64, relay: Relay, store: RelayStore) -> Self {
+        Self {
+            settler,
+            chain_id,
+            relay,
+            store,
+        }
+    }
+}
+
+impl<S: Settler> Settler for RelayingSettler<S> {
+    async fn settle(&self, entry: &BatchEntry, journal: Bytes, seal: Bytes) -> Result<Settlement> {
+        let settlement = self
+            .settler
+            .settle(entry, journal.clone(), seal.clone())
+            .await?;
+        let mut record = RelayRecord::new(
+            entry,
+            journal,
+            seal,
+            settlement.signed_result().cloned(),
+            PrimaryTx {
+                chain_id: self.chain_id,
+                tx_hash: settlement.tx_hash(),
+                status: TxStatus::Confirmed,
+            },
+            self.relay.target(),
+        );
+        self.store.insert(&record)?;
+        let tx_hash = self
+            .relay
+            .deliver(&mut record, &self.store)
+            .await
+            .with_context(|| {
+                format!(
+                    "settled as {settlement:?}, but failed to relay to chain {}; run `relay \
+                     retry` to send it again",
+                    self.relay.name
+                )
+            })?;
+        Ok(Settlement::Forwarded {
+            chain: self.relay.name.clone(),
+            tx_hash,
+            settlement: Box::new(settlement),
+        })
+    }
+}
+
+/// Deliver every relay of `store` that is not confirmed, each with the relay of its chain from
+/// `relay_for`. A failed relay does not stop the others; the results are returned in store order.
+pub async fn retry_relays(
+    store: &RelayStore,
+    relay_for: impl Fn(&str) -> Result<Relay>,
+) -> Result<Vec<(RelayRecord, Result<B256>)>> {
+    let mut results = Vec::new();
+    for mut record in store.unconfirmed()? {
+        let delivered = match relay_for(&record.relay.chain) {
+            Ok(relay) => relay.deliver(&mut record, store).await,
+            Err(err) => Err(err),
+        };
+        results.push((record, delivered));
+    }
+    if results.is_empty() {
+        tracing::info!("No relay to retry");
+    }
+    Ok(results)
+}
+
+/// Fail with the number of relays that failed, if any did.
+pub fn ensure_relayed(results: &[(RelayRecord, Result<B256>)]) -> Result<()> {
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    if failed > 0 {
+        bail!("{failed} of {} relays failed", results.len());
+    }
+    Ok(())
+}
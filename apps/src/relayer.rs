@@ -0,0 +1,102 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloy::primitives::{Address, Bytes, B256};
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// A call for the relayer to forward to the target contract.
+///
+/// This follows the sponsored-call shape used by Gelato-style relayers and ERC-2771 forwarders:
+/// the relayer pays gas and sends `data` to `target` on `chainId`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayRequest {
+    pub chain_id: u64,
+    pub target: Address,
+    pub data: Bytes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sponsor_api_key: Option<String>,
+}
+
+/// Relayer acknowledgement of a forwarded call.
+///
+/// Relayers that broadcast synchronously return the transaction hash; queue-based relayers return
+/// a task id that resolves to a transaction later. At least one of the two must be present.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayResponse {
+    #[serde(default)]
+    pub tx_hash: Option<B256>,
+    #[serde(default)]
+    pub task_id: Option<String>,
+}
+
+/// Client for a meta-transaction relayer that submits `beforeTrade` on the user's behalf.
+#[derive(Clone, Debug)]
+pub struct RelayerClient {
+    url: Url,
+    api_key: Option<String>,
+    http: reqwest::Client,
+}
+
+impl RelayerClient {
+    pub fn new(url: Url, api_key: Option<String>) -> Self {
+        Self {
+            url,
+            api_key,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Forward `data` to `target` through the relayer.
+    pub async fn relay(
+        &self,
+        chain_id: u64,
+        target: Address,
+        data: Bytes,
+    ) -> Result<RelayResponse> {
+        let request = RelayRequest {
+            chain_id,
+            target,
+            data,
+            sponsor_api_key: self.api_key.clone(),
+        };
+
+        let response = self
+            .http
+            .post(self.url.clone())
+            .json(&request)
+            .send()
+            .await
+            .with_context(|| format!("failed to reach relayer at {}", self.url))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("relayer rejected the call with {status}: {body}");
+        }
+
+        let relayed: RelayResponse = response
+            .json()
+            .await
+            .context("failed to decode relayer response")?;
+        ensure!(
+            relayed.tx_hash.is_some() || relayed.task_id.is_some(),
+            "relayer response contained neither a tx hash nor a task id"
+        );
+        Ok(relayed)
+    }
+}
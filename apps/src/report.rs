@@ -0,0 +1,374 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Audit reports of batch runs: one [ReportRow] per entry, as CSV or, with the `parquet` feature,
+//! as Parquet.
+//!
+//! Both formats have the columns of [REPORT_COLUMNS], in that order and with those types.
+//! Dashboards read them by name, so a column may be added at the end, but renaming, retyping or
+//! removing one breaks every report consumer.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    path::Path,
+};
+
+use alloy::primitives::{Address, B256, U256};
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    batch::{EntryReport, Outcome, Stage},
+    cache::write_atomically,
+    journal::{ComplianceJournal, ReasonCode},
+};
+
+/// Type of a report column. Every column may be empty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    Utf8,
+    UInt64,
+    Boolean,
+}
+
+/// Columns of a report, in order.
+pub const REPORT_COLUMNS: [(&str, ColumnType); 14] = [
+    ("index", ColumnType::UInt64),
+    ("user", ColumnType::Utf8),
+    ("product_id", ColumnType::Utf8),
+    ("status", ColumnType::Utf8),
+    ("stage", ColumnType::Utf8),
+    ("error", ColumnType::Utf8),
+    ("allowed", ColumnType::Boolean),
+    ("reasons", ColumnType::Utf8),
+    ("request_id", ColumnType::Utf8),
+    ("cycles", ColumnType::UInt64),
+    ("price_paid_wei", ColumnType::Utf8),
+    ("tx_hash", ColumnType::Utf8),
+    ("decided_at", ColumnType::UInt64),
+    ("finished_at", ColumnType::UInt64),
+];
+
+/// Whether an entry settled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Settled,
+    Failed,
+}
+
+/// One entry of a batch run, as a report row. Fields are the columns of [REPORT_COLUMNS].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportRow {
+    /// Position of the entry in its input file.
+    pub index: u64,
+    pub user: Address,
+    pub product_id: B256,
+    pub status: Status,
+    /// Stage a failed entry failed in.
+    pub stage: Option<Stage>,
+    pub error: Option<String>,
+    /// Decision of the proven journal; empty when the entry was not proven.
+    pub allowed: Option<bool>,
+    /// Names of the proven journal's reasons, separated by `|`.
+    pub reasons: Option<String>,
+    pub request_id: Option<U256>,
+    /// Cycles of the proof. The market client does not report them yet, so this is empty.
+    pub cycles: Option<u64>,
+    /// Price paid for the proof, in decimal wei. The market client does not report it yet, so this
+    /// is empty.
+    #[serde(with = "decimal")]
+    pub price_paid_wei: Option<U256>,
+    pub tx_hash: Option<B256>,
+    /// `decisionAt` of the proven journal, in Unix seconds.
+    pub decided_at: Option<u64>,
+    /// When the entry settled or failed, in Unix seconds.
+    pub finished_at: u64,
+}
+
+impl ReportRow {
+    /// The row of `report`.
+    pub fn new(report: &EntryReport) -> Self {
+        // The journal was checked when the entry was proven.
+        let journal = report
+            .audit
+            .journal
+            .as_ref()
+            .and_then(|journal| ComplianceJournal::try_from(journal.as_ref()).ok());
+        let (status, stage, error, tx_hash) = match &report.outcome {
+            Outcome::Settled { settlement, .. } => {
+                (Status::Settled, None, None, settlement.tx_hash())
+            }
+            Outcome::Failed { stage, error } => {
+                (Status::Failed, Some(*stage), Some(error.clone()), None)
+            }
+        };
+        Self {
+            index: report.index as u64,
+            user: report.user,
+            product_id: report.product_id,
+            status,
+            stage,
+            error,
+            allowed: journal.as_ref().map(|journal| journal.allowed),
+            reasons: journal.as_ref().map(|journal| {
+                ReasonCode::from_mask(journal.reasons)
+                    .map(ReasonCode::name)
+                    .collect::<Vec<_>>()
+                    .join("|")
+            }),
+            request_id: report.audit.request_id,
+            cycles: None,
+            price_paid_wei: None,
+            tx_hash,
+            decided_at: journal.as_ref().map(|journal| journal.decisionAt),
+            finished_at: report.audit.finished_at,
+        }
+    }
+}
+
+/// File format of a report.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Csv,
+    Parquet,
+}
+
+impl ReportFormat {
+    /// The format the extension of `path` names: `.csv` or `.parquet`.
+    pub fn of(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => Ok(ReportFormat::Csv),
+            Some("parquet") => Ok(ReportFormat::Parquet),
+            _ => bail!("report {} is neither .csv nor .parquet", path.display()),
+        }
+    }
+}
+
+/// Write `rows` to `path` in the format of its extension, replacing the file in one step so a
+/// reader never sees part of a report.
+pub fn write_report(path: &Path, rows: &[ReportRow]) -> Result<()> {
+    let contents = match ReportFormat::of(path)? {
+        ReportFormat::Csv => to_csv(rows)?,
+        ReportFormat::Parquet => to_parquet(rows)?,
+    };
+    write_atomically(path, &contents)
+        .with_context(|| format!("failed to write report {}", path.display()))
+}
+
+/// Read the rows of the report at `path`, in the format of its extension.
+pub fn read_report(path: &Path) -> Result<Vec<ReportRow>> {
+    let format = ReportFormat::of(path)?;
+    let contents =
+        std::fs::read(path).with_context(|| format!("failed to read report {}", path.display()))?;
+    match format {
+        ReportFormat::Csv => from_csv(&contents),
+        ReportFormat::Parquet => from_parquet(&contents),
+    }
+    .with_context(|| format!("invalid report {}", path.display()))
+}
+
+/// Rows of `reports` in order, with one row per request id: a row of a request seen before
+/// replaces the earlier one in its place, so overlapping reports merge to the latest outcome of
+/// each request. Rows without a request id are all kept.
+pub fn merge_reports(reports: impl IntoIterator<Item = Vec<ReportRow>>) -> Vec<ReportRow> {
+    let mut merged: Vec<ReportRow> = Vec::new();
+    let mut positions = HashMap::new();
+    for row in reports.into_iter().flatten() {
+        let Some(request_id) = row.request_id else {
+            merged.push(row);
+            continue;
+        };
+        match positions.entry(request_id) {
+            Entry::Occupied(position) => merged[*position.get()] = row,
+            Entry::Vacant(position) => {
+                position.insert(merged.len());
+                merged.push(row);
+            }
+        }
+    }
+    merged
+}
+
+/// `rows` as CSV, with a header of the column names even when there are no rows.
+pub fn to_csv(rows: &[ReportRow]) -> Result<Vec<u8>> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer.write_record(REPORT_COLUMNS.map(|(name, _)| name))?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.into_inner().map_err(|err| err.into_error().into())
+}
+
+/// Rows of a CSV report, whose header must be the column names.
+pub fn from_csv(contents: &[u8]) -> Result<Vec<ReportRow>> {
+    let mut reader = csv::Reader::from_reader(contents);
+    let header = reader.headers()?;
+    ensure!(
+        header.iter().eq(REPORT_COLUMNS.map(|(name, _)| name)),
+        "header is {:?}, not the report columns",
+        header.iter().collect::<Vec<_>>()
+    );
+    reader
+        .deserialize()
+        .enumerate()
+        .map(|(index, row)| row.with_context(|| format!("invalid row {index}")))
+        .collect()
+}
+
+/// `rows` as Parquet, in one row group.
+#[cfg(feature = "parquet")]
+pub fn to_parquet(rows: &[ReportRow]) -> Result<Vec<u8>> {
+    parquet_format::write(rows)
+}
+
+/// Rows of a Parquet report, whose schema must be the columns'.
+#[cfg(feature = "parquet")]
+pub fn from_parquet(contents: &[u8]) -> Result<Vec<ReportRow>> {
+    parquet_format::read(contents)
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn to_parquet(_rows: &[ReportRow]) -> Result<Vec<u8>> {
+    bail!("Parquet reports need the app built with its `parquet` feature")
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn from_parquet(_contents: &[u8]) -> Result<Vec<ReportRow>> {
+    bail!("Parquet reports need the app built with its `parquet` feature")
+}
+
+/// Conversion of rows to and from Arrow columns, through their serde form, which holds each
+/// cell as the JSON type of its [ColumnType].
+#[cfg(feature = "parquet")]
+mod parquet_format {
+    use std::sync::Arc;
+
+    use anyhow::{ensure, Result};
+    use arrow::{
+        array::{Array, ArrayRef, AsArray, BooleanArray, StringArray, UInt64Array},
+        datatypes::{DataType, Field, Schema, UInt64Type},
+        record_batch::RecordBatch,
+    };
+    use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter};
+    use serde_json::{Map, Value};
+
+    use super::{ColumnType, ReportRow, REPORT_COLUMNS};
+
+    fn schema() -> Schema {
+        Schema::new(
+            REPORT_COLUMNS
+                .map(|(name, column_type)| {
+                    let data_type = match column_type {
+                        ColumnType::Utf8 => DataType::Utf8,
+                        ColumnType::UInt64 => DataType::UInt64,
+                        ColumnType::Boolean => DataType::Boolean,
+                    };
+                    Field::new(name, data_type, true)
+                })
+                .to_vec(),
+        )
+    }
+
+    pub(super) fn write(rows: &[ReportRow]) -> Result<Vec<u8>> {
+        let values = rows
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        let columns: Vec<ArrayRef> = REPORT_COLUMNS
+            .iter()
+            .map(|&(name, column_type)| {
+                let cells = values.iter().map(|row| &row[name]);
+                match column_type {
+                    ColumnType::Utf8 => {
+                        Arc::new(cells.map(Value::as_str).collect::<StringArray>()) as ArrayRef
+                    }
+                    ColumnType::UInt64 => {
+                        Arc::new(cells.map(Value::as_u64).collect::<UInt64Array>())
+                    }
+                    ColumnType::Boolean => {
+                        Arc::new(cells.map(Value::as_bool).collect::<BooleanArray>())
+                    }
+                }
+            })
+            .collect();
+        let schema = Arc::new(schema());
+        let batch = RecordBatch::try_new(schema.clone(), columns)?;
+        let mut contents = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut contents, schema, None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(contents)
+    }
+
+    pub(super) fn read(contents: &[u8]) -> Result<Vec<ReportRow>> {
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::copy_from_slice(contents))?;
+        ensure!(
+            builder.schema().fields() == schema().fields(),
+            "columns are {:?}, not the report columns",
+            builder.schema().fields()
+        );
+        let mut rows = Vec::new();
+        for batch in builder.build()? {
+            let batch = batch?;
+            for row in 0..batch.num_rows() {
+                let mut value = Map::new();
+                for (&(name, column_type), column) in REPORT_COLUMNS.iter().zip(batch.columns()) {
+                    let cell = if column.is_null(row) {
+                        Value::Null
+                    } else {
+                        match column_type {
+                            ColumnType::Utf8 => column.as_string::<i32>().value(row).into(),
+                            ColumnType::UInt64 => {
+                                column.as_primitive::<UInt64Type>().value(row).into()
+                            }
+                            ColumnType::Boolean => column.as_boolean().value(row).into(),
+                        }
+                    };
+                    value.insert(name.to_string(), cell);
+                }
+                rows.push(serde_json::from_value(Value::Object(value))?);
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// Serde format of an optional amount as a decimal string, which spreadsheets and BI tools read
+/// as a number, unlike the hex of [U256]'s own format.
+mod decimal {
+    use alloy::primitives::U256;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<U256>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&value.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<U256>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| value.parse().map_err(D::Error::custom))
+            .transpose()
+    }
+}
@@ -0,0 +1,793 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! JSON Schemas of the batch input file and the batch summary.
+//!
+//! The schemas follow the serde formats of [BatchEntry] and [EntryReport] field for field, and
+//! carry [BATCH_INPUT_VERSION] in their `$id`. The alloy primitives have no schemas of their own,
+//! so theirs are written here to the hex and integer forms their serde impls accept. An input file
+//! is checked against [input_schema] before it is deserialized, so a mistake is reported at the
+//! JSON pointer of the offending field rather than as a line and column.
+
+use std::{
+    borrow::Cow,
+    cmp::{Ordering, Reverse},
+    collections::HashMap,
+    fmt,
+};
+
+use regex::Regex;
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde_json::{Number, Value};
+
+use crate::{
+    batch::{BatchEntry, EntryReport, Outcome, QuoteLeg, ReviewBand, Stage, BATCH_INPUT_VERSION},
+    journal::{GOVERNANCE_SET_LEN, KYC_PROVIDER_SET_LEN},
+    settle::Settlement,
+    signing::SignedResult,
+};
+
+/// Schema of an `--input-file`: an array of [BatchEntry].
+pub fn input_schema() -> Schema {
+    root_schema::<Vec<BatchEntry>>("batch-input", "Trades to check and settle, in batch mode")
+}
+
+/// Schema of the JSON summary a batch prints: an array of [EntryReport].
+pub fn output_schema() -> Schema {
+    root_schema::<Vec<EntryReport>>("batch-output", "Outcome of each trade of a batch")
+}
+
+fn root_schema<T: JsonSchema>(name: &str, title: &str) -> Schema {
+    let mut schema = SchemaGenerator::default().into_root_schema_for::<T>();
+    schema.insert(
+        "$id".into(),
+        format!("{name}.v{BATCH_INPUT_VERSION}.schema.json").into(),
+    );
+    schema.insert("title".into(), title.into());
+    schema
+}
+
+/// A value that does not match its schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaError {
+    /// JSON pointer of the offending value, empty for the document itself.
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.pointer.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{}: {}", self.pointer, self.message)
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+/// Check `value` against a schema of this module.
+///
+/// Only the keywords these schemas use are understood; any other keyword is ignored.
+pub fn validate(schema: &Schema, value: &Value) -> Result<(), SchemaError> {
+    Validator {
+        root: schema.as_value(),
+        patterns: HashMap::new(),
+    }
+    .check(schema.as_value(), value, "")
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+    /// Compiled `pattern`s, as a batch checks the same few against every entry.
+    patterns: HashMap<&'a str, Regex>,
+}
+
+impl<'a> Validator<'a> {
+    fn check(
+        &mut self,
+        schema: &'a Value,
+        value: &Value,
+        pointer: &str,
+    ) -> Result<(), SchemaError> {
+        let schema = match schema {
+            Value::Bool(true) => return Ok(()),
+            Value::Bool(false) => return Err(error(pointer, "no value is allowed here")),
+            Value::Object(schema) => schema,
+            _ => panic!("schema at {pointer:?} is neither an object nor a boolean"),
+        };
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let target = reference
+                .strip_prefix('#')
+                .and_then(|target| self.root.pointer(target))
+                .unwrap_or_else(|| panic!("unresolvable $ref {reference}"));
+            self.check(target, value, pointer)?;
+        }
+        if let Some(types) = schema.get("type") {
+            let types = match types {
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                types => types.as_str().into_iter().collect::<Vec<_>>(),
+            };
+            if !types.iter().any(|ty| has_type(value, ty)) {
+                return Err(error(
+                    pointer,
+                    format!("expected {}, found {}", types.join(" or "), describe(value)),
+                ));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if value != expected {
+                return Err(error(
+                    pointer,
+                    format!("expected {expected}, found {}", describe(value)),
+                ));
+            }
+        }
+        if let Some(Value::Array(allowed)) = schema.get("enum") {
+            if !allowed.contains(value) {
+                let allowed = allowed.iter().map(Value::to_string).collect::<Vec<_>>();
+                return Err(error(
+                    pointer,
+                    format!(
+                        "expected one of {}, found {}",
+                        allowed.join(", "),
+                        describe(value)
+                    ),
+                ));
+            }
+        }
+
+        match value {
+            Value::String(string) => {
+                if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                    let regex = self.patterns.entry(pattern).or_insert_with(|| {
+                        Regex::new(pattern).unwrap_or_else(|err| panic!("bad pattern: {err}"))
+                    });
+                    if !regex.is_match(string) {
+                        return Err(error(
+                            pointer,
+                            format!("{} does not match {pattern}", describe(value)),
+                        ));
+                    }
+                }
+            }
+            Value::Number(number) => {
+                if let Some(Value::Number(minimum)) = schema.get("minimum") {
+                    if compare(number, minimum) == Some(Ordering::Less) {
+                        return Err(error(
+                            pointer,
+                            format!("{number} is below the minimum {minimum}"),
+                        ));
+                    }
+                }
+                if let Some(Value::Number(maximum)) = schema.get("maximum") {
+                    if compare(number, maximum) == Some(Ordering::Greater) {
+                        return Err(error(
+                            pointer,
+                            format!("{number} is above the maximum {maximum}"),
+                        ));
+                    }
+                }
+            }
+            Value::Array(items) => {
+                if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                    if items.len() as u64 > max {
+                        return Err(error(
+                            pointer,
+                            format!("{} items is more than the maximum {max}", items.len()),
+                        ));
+                    }
+                }
+                if let Some(item_schema) = schema.get("items") {
+                    for (index, item) in items.iter().enumerate() {
+                        self.check(item_schema, item, &format!("{pointer}/{index}"))?;
+                    }
+                }
+            }
+            Value::Object(fields) => {
+                if let Some(Value::Array(required)) = schema.get("required") {
+                    for key in required.iter().filter_map(Value::as_str) {
+                        if !fields.contains_key(key) {
+                            return Err(error(&child(pointer, key), "missing required field"));
+                        }
+                    }
+                }
+                let properties = schema.get("properties").and_then(Value::as_object);
+                for (key, field) in fields {
+                    let field_pointer = child(pointer, key);
+                    match properties.and_then(|properties| properties.get(key)) {
+                        Some(field_schema) => self.check(field_schema, field, &field_pointer)?,
+                        None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                            return Err(error(&field_pointer, "unknown field"));
+                        }
+                        None => {}
+                    }
+                }
+            }
+            Value::Null | Value::Bool(_) => {}
+        }
+
+        if let Some(Value::Array(all)) = schema.get("allOf") {
+            for branch in all {
+                self.check(branch, value, pointer)?;
+            }
+        }
+        for (keyword, exactly_one) in [("anyOf", false), ("oneOf", true)] {
+            let Some(Value::Array(branches)) = schema.get(keyword) else {
+                continue;
+            };
+            let mut errors = Vec::new();
+            for branch in branches {
+                if let Err(err) = self.check(branch, value, pointer) {
+                    errors.push(err);
+                }
+            }
+            let matched = branches.len() - errors.len();
+            if matched > 1 && exactly_one {
+                return Err(error(pointer, "matches more than one of the allowed forms"));
+            }
+            if matched == 0 {
+                // The branch that got furthest into the value is the one the author meant, and the first
+                // of those when none got past the value itself.
+                let deepest = errors
+                    .into_iter()
+                    .min_by_key(|err| Reverse(err.pointer.len()))
+                    .expect("no branches to match");
+                if deepest.pointer.len() > pointer.len() {
+                    return Err(deepest);
+                }
+                return Err(match schema.get("description").and_then(Value::as_str) {
+                    Some(description) => error(
+                        pointer,
+                        format!("expected {description}, found {}", describe(value)),
+                    ),
+                    None => deepest,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn error(pointer: &str, message: impl Into<String>) -> SchemaError {
+    SchemaError {
+        pointer: pointer.into(),
+        message: message.into(),
+    }
+}
+
+/// Pointer to field `key` of the object at `pointer`, escaped as RFC 6901 requires.
+fn child(pointer: &str, key: &str) -> String {
+    format!("{pointer}/{}", key.replace('~', "~0").replace('/', "~1"))
+}
+
+fn has_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        // serde_json reads an integer too large for 64 bits as a float, which no integer field
+        // accepts either.
+        "integer" => value.is_u64() || value.is_i64(),
+        _ => panic!("unknown schema type {ty}"),
+    }
+}
+
+fn compare(a: &Number, b: &Number) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (a.as_u64(), b.as_u64()) {
+        return Some(a.cmp(&b));
+    }
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        return Some(a.cmp(&b));
+    }
+    a.as_f64()?.partial_cmp(&b.as_f64()?)
+}
+
+/// A value as it reads in an error message: scalars in full, containers by kind only.
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Array(_) => "an array".into(),
+        Value::Object(_) => "an object".into(),
+        scalar => scalar.to_string(),
+    }
+}
+
+/// Schema of [Address](alloy::primitives::Address).
+struct AddressSchema;
+
+impl JsonSchema for AddressSchema {
+    fn schema_name() -> Cow<'static, str> {
+        "Address".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "a 20-byte address as 0x-prefixed hex, in either letter case \
+                (the EIP-55 checksum is not checked)",
+            "type": "string",
+            "pattern": "^0x[0-9a-fA-F]{40}$",
+        })
+    }
+}
+
+/// Schema of [B256](alloy::primitives::B256).
+struct B256Schema;
+
+impl JsonSchema for B256Schema {
+    fn schema_name() -> Cow<'static, str> {
+        "B256".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "32 bytes as 0x-prefixed hex",
+            "type": "string",
+            "pattern": "^0x[0-9a-fA-F]{64}$",
+        })
+    }
+}
+
+/// Schema of [U256](alloy::primitives::U256).
+struct U256Schema;
+
+impl JsonSchema for U256Schema {
+    fn schema_name() -> Cow<'static, str> {
+        "U256".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        // JSON numbers lose precision past 2^53 in most parsers, so large values belong in strings.
+        json_schema!({
+            "description": "an unsigned 256-bit integer as a 0x-prefixed hex or decimal string, \
+                or a JSON integer below 2^64",
+            "anyOf": [
+                { "type": "string", "pattern": "^(0x[0-9a-fA-F]{1,64}|[0-9]{1,78})$" },
+                { "type": "integer", "minimum": 0, "maximum": u64::MAX },
+            ],
+        })
+    }
+}
+
+/// Schema of [Bytes](alloy::primitives::Bytes).
+struct BytesSchema;
+
+impl JsonSchema for BytesSchema {
+    fn schema_name() -> Cow<'static, str> {
+        "Bytes".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "bytes as 0x-prefixed hex",
+            "type": "string",
+            "pattern": "^0x([0-9a-fA-F]{2})*$",
+        })
+    }
+}
+
+/// Schema of [Signature](alloy::primitives::Signature), which serializes as an RPC signature.
+struct SignatureSchema;
+
+impl JsonSchema for SignatureSchema {
+    fn schema_name() -> Cow<'static, str> {
+        "Signature".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let scalar = generator.subschema_for::<U256Schema>();
+        json_schema!({
+            "description": "an ECDSA signature with its y parity, or a legacy or EIP-155 `v`",
+            "type": "object",
+            "properties": {
+                "r": scalar,
+                "s": scalar,
+                "yParity": { "anyOf": [{ "enum": [0, 1, "0x0", "0x1"] }, { "type": "null" }] },
+                "v": scalar,
+            },
+            "required": ["r", "s"],
+        })
+    }
+}
+
+/// `schema` with a description, as the doc comment of a field gives it.
+fn described(mut schema: Schema, description: &str) -> Schema {
+    schema.insert("description".into(), description.into());
+    schema
+}
+
+impl JsonSchema for BatchEntry {
+    fn schema_name() -> Cow<'static, str> {
+        "BatchEntry".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "One trade to check and settle.",
+            "type": "object",
+            "properties": {
+                "user": generator.subschema_for::<AddressSchema>(),
+                "productId": generator.subschema_for::<B256Schema>(),
+                "amount": generator.subschema_for::<U256Schema>(),
+                "kycPassed": described(
+                    generator.subschema_for::<bool>(),
+                    "Legacy pass/fail KYC flag, used when `kycTier` is not set.",
+                ),
+                "kycTier": generator.subschema_for::<Option<u8>>(),
+                "requiredTier": described(
+                    generator.subschema_for::<u8>(),
+                    "Minimum KYC tier the product requires.",
+                ),
+                "amlPassed": generator.subschema_for::<bool>(),
+                "claims": described(
+                    generator.subschema_for::<U256Schema>(),
+                    "Bitmap of the questionnaire items the user has answered.",
+                ),
+                "requiredClaims": described(
+                    generator.subschema_for::<U256Schema>(),
+                    "Questionnaire items the product requires.",
+                ),
+                "requiredAttestations": described(
+                    generator.subschema_for::<u8>(),
+                    "Attesters the product requires to vouch for the user.",
+                ),
+                "productPolicyVersion": described(
+                    generator.subschema_for::<u32>(),
+                    "Version of the product policy `requiredTier` and `requiredClaims` come from.",
+                ),
+                "quote": generator.subschema_for::<Option<QuoteLeg>>(),
+                "riskScore": described(
+                    generator.subschema_for::<u8>(),
+                    "Risk score the screening provider gave the user.",
+                ),
+                "reviewBand": generator.subschema_for::<Option<ReviewBand>>(),
+                "regimeId": described(
+                    generator.subschema_for::<u16>(),
+                    "Regime of the product's jurisdiction.",
+                ),
+                "settlementToken": generator.subschema_for::<AddressSchema>(),
+                "caseId": described(
+                    generator.subschema_for::<B256Schema>(),
+                    "Off-chain case-management record the decision belongs to.",
+                ),
+                "minTrade": described(
+                    generator.subschema_for::<U256Schema>(),
+                    "Smallest trade the product accepts.",
+                ),
+                "maxTrade": described(
+                    generator.subschema_for::<Option<U256Schema>>(),
+                    "Largest trade the product accepts, or no limit when unset.",
+                ),
+                "ttlSecs": described(
+                    generator.subschema_for::<u64>(),
+                    "Seconds the hook may cache an allowed decision for.",
+                ),
+                "attester": generator.subschema_for::<Option<AddressSchema>>(),
+                "attestationSignature": generator.subschema_for::<Option<BytesSchema>>(),
+                "governance": {
+                    "description": "Governance keys allowed to override the decision.",
+                    "type": "array",
+                    "items": generator.subschema_for::<AddressSchema>(),
+                    "maxItems": GOVERNANCE_SET_LEN,
+                },
+                "overrideSignature": generator.subschema_for::<Option<SignatureSchema>>(),
+                "compactJournal": described(
+                    generator.subschema_for::<bool>(),
+                    "Have the guest commit a compact journal.",
+                ),
+                "sha256Commitments": described(
+                    generator.subschema_for::<bool>(),
+                    "Have the guest hash the journal's commitments with SHA-256.",
+                ),
+                "decisionUuid": described(
+                    generator.subschema_for::<B256Schema>(),
+                    "Identifier of the decision, which the hook settles at most once.",
+                ),
+                "credentialHash": described(
+                    generator.subschema_for::<B256Schema>(),
+                    "keccak256 of the verifiable credential the compliance fields were taken from.",
+                ),
+                "settleDelaySecs": described(
+                    generator.subschema_for::<u64>(),
+                    "Seconds after the decision before which the hook must not settle it.",
+                ),
+                "amlSources": described(
+                    generator.subschema_for::<U256Schema>(),
+                    "Bitmap of the AML list sources the user was screened against.",
+                ),
+                "requiredAmlSources": described(
+                    generator.subschema_for::<U256Schema>(),
+                    "AML list sources the product requires the screening to have covered.",
+                ),
+                "blockHash": described(
+                    generator.subschema_for::<B256Schema>(),
+                    "Hash of the block the onchain state behind the trade was read at.",
+                ),
+                "kycProvider": described(
+                    generator.subschema_for::<u16>(),
+                    "Id of the KYC vendor whose attestation the tier and claims come from.",
+                ),
+                "allowedKycProviders": {
+                    "description": "KYC providers the product accepts; empty accepts any.",
+                    "type": "array",
+                    "items": generator.subschema_for::<u16>(),
+                    "maxItems": KYC_PROVIDER_SET_LEN,
+                },
+                "consentGiven": described(
+                    generator.subschema_for::<bool>(),
+                    "Whether the user consented to the processing of their data.",
+                ),
+                "consentAt": described(
+                    generator.subschema_for::<u64>(),
+                    "Unix time the user's consent was recorded at, or zero when there is none.",
+                ),
+                "consentRequired": described(
+                    generator.subschema_for::<bool>(),
+                    "Whether the product requires recorded consent before a trade is allowed. \
+                     Defaults to true.",
+                ),
+            },
+            "required": ["user", "productId", "amount", "amlPassed"],
+        })
+    }
+}
+
+impl JsonSchema for QuoteLeg {
+    fn schema_name() -> Cow<'static, str> {
+        "QuoteLeg".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "Quote leg when the trade is of a pair, with `productId` as its base.",
+            "type": "object",
+            "properties": {
+                "productId": generator.subschema_for::<B256Schema>(),
+                "requiredTier": generator.subschema_for::<u8>(),
+                "requiredClaims": generator.subschema_for::<U256Schema>(),
+                "productPolicyVersion": generator.subschema_for::<u32>(),
+            },
+            "required": ["productId"],
+        })
+    }
+}
+
+impl JsonSchema for ReviewBand {
+    fn schema_name() -> Cow<'static, str> {
+        "ReviewBand".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "Risk scores from `low` up to but excluding `high` that need manual \
+                review.",
+            "type": "object",
+            "properties": {
+                "low": generator.subschema_for::<u8>(),
+                "high": generator.subschema_for::<u8>(),
+            },
+            "required": ["low", "high"],
+        })
+    }
+}
+
+impl JsonSchema for EntryReport {
+    fn schema_name() -> Cow<'static, str> {
+        "EntryReport".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        // The outcome is flattened into the report, so its variants' fields sit beside these.
+        let mut schema = json_schema!({
+            "description": "Per-entry line of the batch summary, in input order.",
+            "type": "object",
+            "properties": {
+                "index": generator.subschema_for::<usize>(),
+                "user": generator.subschema_for::<AddressSchema>(),
+                "productId": generator.subschema_for::<B256Schema>(),
+            },
+            "required": ["index", "user", "productId"],
+        });
+        let mut outcome = Outcome::json_schema(generator);
+        schema.insert("oneOf".into(), outcome.remove("oneOf").unwrap());
+        schema
+    }
+}
+
+impl JsonSchema for Outcome {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        "Outcome".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "oneOf": [
+                {
+                    "type": "object",
+                    "properties": {
+                        "status": { "const": "settled" },
+                        "request_id": generator.subschema_for::<U256Schema>(),
+                        "settlement": generator.subschema_for::<Settlement>(),
+                    },
+                    "required": ["status", "request_id", "settlement"],
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "status": { "const": "failed" },
+                        "stage": generator.subschema_for::<Stage>(),
+                        "error": generator.subschema_for::<String>(),
+                    },
+                    "required": ["status", "stage", "error"],
+                },
+            ],
+        })
+    }
+}
+
+impl JsonSchema for Stage {
+    fn schema_name() -> Cow<'static, str> {
+        "Stage".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "Pipeline stage an entry failed in.",
+            "type": "string",
+            "enum": ["prove", "settle"],
+        })
+    }
+}
+
+impl JsonSchema for Settlement {
+    fn schema_name() -> Cow<'static, str> {
+        "Settlement".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let tx_hash = generator.subschema_for::<B256Schema>();
+        let maybe_tx_hash = generator.subschema_for::<Option<B256Schema>>();
+        let hook = generator.subschema_for::<Option<Settlement>>();
+        json_schema!({
+            "description": "How a `beforeTrade` call, or an attestation of its decision, reached the chain, where its proof was pinned and how its result was signed.",
+            "oneOf": [
+                {
+                    "type": "object",
+                    "properties": {
+                        "confirmed": {
+                            "type": "object",
+                            "properties": { "tx_hash": tx_hash },
+                            "required": ["tx_hash"],
+                        },
+                    },
+                    "required": ["confirmed"],
+                    "additionalProperties": false,
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "relayed": {
+                            "type": "object",
+                            "properties": {
+                                "tx_hash": maybe_tx_hash,
+                                "task_id": generator.subschema_for::<Option<String>>(),
+                            },
+                            "required": ["tx_hash", "task_id"],
+                        },
+                    },
+                    "required": ["relayed"],
+                    "additionalProperties": false,
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "attested": {
+                            "type": "object",
+                            "properties": {
+                                "uid": tx_hash,
+                                "tx_hash": tx_hash,
+                                "hook": hook,
+                            },
+                            "required": ["uid", "tx_hash", "hook"],
+                        },
+                    },
+                    "required": ["attested"],
+                    "additionalProperties": false,
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "pinned": {
+                            "type": "object",
+                            "properties": {
+                                "bundle_cid": { "type": "string" },
+                                "program_cid": { "type": "string" },
+                                "settlement": generator.subschema_for::<Settlement>(),
+                            },
+                            "required": ["bundle_cid", "program_cid", "settlement"],
+                        },
+                    },
+                    "required": ["pinned"],
+                    "additionalProperties": false,
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "signed": {
+                            "type": "object",
+                            "properties": {
+                                "result": generator.subschema_for::<SignedResult>(),
+                                "settlement": generator.subschema_for::<Settlement>(),
+                            },
+                            "required": ["result", "settlement"],
+                        },
+                    },
+                    "required": ["signed"],
+                    "additionalProperties": false,
+                },
+            ],
+        })
+    }
+}
+
+impl JsonSchema for SignedResult {
+    fn schema_name() -> Cow<'static, str> {
+        "SignedResult".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let address = generator.subschema_for::<AddressSchema>();
+        let hash = generator.subschema_for::<B256Schema>();
+        json_schema!({
+            "description": "The operator's EIP-712 signature over the result of a decision.",
+            "type": "object",
+            "properties": {
+                "user": address,
+                "productId": hash,
+                "allowed": generator.subschema_for::<bool>(),
+                "journalDigest": hash,
+                "imageId": hash,
+                "validUntil": generator.subschema_for::<u64>(),
+                "domain": {
+                    "type": "object",
+                    "properties": {
+                        "name": generator.subschema_for::<String>(),
+                        "version": generator.subschema_for::<String>(),
+                        "chainId": generator.subschema_for::<u64>(),
+                        "verifyingContract": address,
+                    },
+                    "required": ["name", "version", "chainId", "verifyingContract"],
+                },
+                "signature": generator.subschema_for::<BytesSchema>(),
+            },
+            "required": [
+                "user",
+                "productId",
+                "allowed",
+                "journalDigest",
+                "imageId",
+                "validUntil",
+                "domain",
+                "signature",
+            ],
+        })
+    }
+}
@@ -0,0 +1,353 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! AML screening of addresses with a vendor risk API, such as Chainalysis's or TRM's.
+//!
+//! Vendors differ in where their responses put the risk score and the risk categories, so a
+//! [ScreeningConfig] maps them with JSON pointers. An address passes AML when its score is below
+//! the configured threshold and none of its categories is a sanctioned one. A vendor that cannot
+//! be reached fails with a [ScreeningOutage], never with a failed AML result.
+
+use std::{collections::HashMap, fmt, path::Path, str::FromStr, sync::Mutex, time::Duration};
+
+use alloy::primitives::Address;
+use anyhow::{bail, ensure, Context, Result};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::time::Instant;
+use url::Url;
+
+use crate::{
+    attestation::{AttestationSource, ComplianceStatus},
+    batch::BatchEntry,
+    http::RetryPolicy,
+};
+
+/// Placeholder for the screened address in [ScreeningConfig::url].
+pub const ADDRESS_PLACEHOLDER: &str = "{address}";
+
+/// How to call a screening API and read its responses, as read from a TOML file.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScreeningConfig {
+    /// URL to `GET` the screening of an address from, with `{address}` in its place.
+    pub url: String,
+    /// Header the API key is sent in, such as `Token` or `Authorization`.
+    #[serde(default)]
+    pub auth_header: Option<String>,
+    /// Text put before the API key in `auth_header`, such as `Bearer `.
+    #[serde(default)]
+    pub auth_prefix: String,
+    /// Lowest risk score that fails AML.
+    pub risk_threshold: u8,
+    /// Categories that fail AML whatever the score, compared ignoring case.
+    #[serde(default)]
+    pub sanctioned_categories: Vec<String>,
+    /// How long a screening is reused for the same address, in seconds. Zero screens every time.
+    #[serde(default = "default_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+    /// Most requests sent to the API per second, or unlimited when zero.
+    #[serde(default)]
+    pub requests_per_sec: u32,
+    pub mapping: ResponseMapping,
+    /// Scores of risk levels the API reports by name, such as `Severe = 100`, compared ignoring
+    /// case.
+    #[serde(default)]
+    pub risk_levels: HashMap<String, u8>,
+}
+
+/// Where a screening response keeps the fields AML is decided on, as JSON pointers.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResponseMapping {
+    /// The risk score: a number from 0 to 255, or the name of a level in `risk_levels`.
+    pub risk_score: String,
+    /// The categories of risk found: an array of names, or of objects naming theirs in
+    /// `category_field`. A response without them has none.
+    #[serde(default)]
+    pub categories: Option<String>,
+    #[serde(default)]
+    pub category_field: Option<String>,
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    3600
+}
+
+impl FromStr for ScreeningConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let config: Self = toml::from_str(s).context("invalid screening config")?;
+        ensure!(
+            config.url.contains(ADDRESS_PLACEHOLDER),
+            "screening URL {} has no {ADDRESS_PLACEHOLDER} placeholder",
+            config.url
+        );
+        Ok(config)
+    }
+}
+
+impl ScreeningConfig {
+    /// Read the config from the TOML file at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read screening config {}", path.display()))?
+            .parse()
+            .with_context(|| format!("failed to load screening config {}", path.display()))
+    }
+
+    /// URL of the screening of `address`.
+    pub fn address_url(&self, address: Address) -> Result<Url> {
+        let url = self.url.replace(ADDRESS_PLACEHOLDER, &address.to_string());
+        Url::parse(&url).with_context(|| format!("invalid screening URL {url}"))
+    }
+
+    /// Score of the risk level named `level`.
+    fn level_score(&self, level: &str) -> Option<u8> {
+        self.risk_levels
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(level))
+            .map(|(_, score)| *score)
+    }
+
+    /// Read the screening of a user out of `response`.
+    pub fn screening(&self, response: &Value) -> Result<Screening> {
+        let pointer = &self.mapping.risk_score;
+        let risk_score = match response.pointer(pointer) {
+            Some(Value::Number(score)) => score
+                .as_f64()
+                .filter(|score| (0.0..=f64::from(u8::MAX)).contains(score))
+                .map(|score| score.round() as u8)
+                .with_context(|| format!("risk score {score} is not between 0 and 255"))?,
+            Some(Value::String(level)) => self
+                .level_score(level)
+                .with_context(|| format!("risk level {level:?} is not in risk_levels"))?,
+            Some(other) => {
+                bail!("risk score at {pointer} is neither a number nor a level: {other}")
+            }
+            None => bail!("response has no risk score at {pointer}"),
+        };
+
+        let categories: Vec<String> = match &self.mapping.categories {
+            Some(pointer) => match response.pointer(pointer) {
+                None | Some(Value::Null) => Vec::new(),
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .map(|item| self.category(item))
+                    .collect::<Result<_>>()
+                    .with_context(|| format!("invalid categories at {pointer}"))?,
+                Some(other) => bail!("categories at {pointer} are not an array: {other}"),
+            },
+            None => Vec::new(),
+        };
+
+        let sanctioned = categories.iter().any(|category| {
+            self.sanctioned_categories
+                .iter()
+                .any(|sanctioned| sanctioned.eq_ignore_ascii_case(category))
+        });
+        Ok(Screening {
+            aml_passed: risk_score < self.risk_threshold && !sanctioned,
+            risk_score,
+            categories,
+        })
+    }
+
+    fn category(&self, item: &Value) -> Result<String> {
+        let name = match (&self.mapping.category_field, item) {
+            (None, Value::String(name)) => Some(name.as_str()),
+            (Some(field), Value::Object(fields)) => fields.get(field).and_then(Value::as_str),
+            _ => None,
+        };
+        name.map(str::to_owned)
+            .with_context(|| format!("unexpected category {item}"))
+    }
+}
+
+/// Result of screening an address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Screening {
+    pub risk_score: u8,
+    /// Risk categories the vendor found, as it names them.
+    pub categories: Vec<String>,
+    /// Whether the score is below the threshold and no category is sanctioned.
+    pub aml_passed: bool,
+}
+
+impl Screening {
+    /// Replace the AML result and the risk score of `entry` with this screening.
+    pub fn apply_to(&self, entry: &mut BatchEntry) {
+        entry.aml_passed = self.aml_passed;
+        entry.risk_score = self.risk_score;
+    }
+}
+
+/// The screening API could not be reached or was unavailable, so the AML status of the user is
+/// unknown. A [ScreeningSource] attaches it as context, so `downcast_ref` finds it in the error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScreeningOutage {
+    pub url: Url,
+}
+
+impl fmt::Display for ScreeningOutage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "screening API {} is unavailable", self.url)
+    }
+}
+
+/// Screens addresses with the API a [ScreeningConfig] describes.
+///
+/// Screenings are cached for the configured TTL and requests are spaced to the configured rate,
+/// so a batch of trades by few users costs few calls.
+#[derive(Debug)]
+pub struct ScreeningSource {
+    config: ScreeningConfig,
+    api_key: Option<String>,
+    policy: RetryPolicy,
+    http: reqwest::Client,
+    cache: Mutex<HashMap<Address, (Instant, Screening)>>,
+    next_request: Mutex<Instant>,
+}
+
+impl ScreeningSource {
+    /// Screen with `config`, sending `api_key` in its `auth_header`.
+    pub fn new(config: ScreeningConfig, api_key: Option<String>) -> Self {
+        Self {
+            config,
+            api_key,
+            policy: RetryPolicy::default(),
+            http: reqwest::Client::new(),
+            cache: Mutex::new(HashMap::new()),
+            next_request: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Replace the default timeout and retry policy.
+    pub fn with_policy(self, policy: RetryPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Screen `user`, reusing a screening made within the cache TTL.
+    pub async fn screen(&self, user: Address) -> Result<Screening> {
+        let ttl = Duration::from_secs(self.config.cache_ttl_secs);
+        if let Some((at, screening)) = self.cache.lock().unwrap().get(&user) {
+            if at.elapsed() < ttl {
+                return Ok(screening.clone());
+            }
+        }
+
+        let screening = self.request(user).await?;
+        tracing::info!(
+            "Screening API scores {user} at {} ({})",
+            screening.risk_score,
+            match screening.categories.as_slice() {
+                [] => "no risk categories".to_owned(),
+                categories => categories.join(", "),
+            }
+        );
+        if !ttl.is_zero() {
+            self.cache
+                .lock()
+                .unwrap()
+                .insert(user, (Instant::now(), screening.clone()));
+        }
+        Ok(screening)
+    }
+
+    /// Wait until a request fits within the rate limit.
+    async fn throttle(&self) {
+        if self.config.requests_per_sec == 0 {
+            return;
+        }
+        let interval = Duration::from_secs(1) / self.config.requests_per_sec;
+        let at = {
+            let mut next = self.next_request.lock().unwrap();
+            let at = (*next).max(Instant::now());
+            *next = at + interval;
+            at
+        };
+        tokio::time::sleep_until(at).await;
+    }
+
+    async fn request(&self, user: Address) -> Result<Screening> {
+        let url = self.config.address_url(user)?;
+        let outage = || ScreeningOutage { url: url.clone() };
+        self.throttle().await;
+        let response =
+            self.policy
+                .send(|| {
+                    let request = self.http.get(url.clone());
+                    match (&self.config.auth_header, &self.api_key) {
+                        (Some(header), Some(key)) => request
+                            .header(header.as_str(), format!("{}{key}", self.config.auth_prefix)),
+                        _ => request,
+                    }
+                })
+                .await
+                .with_context(outage)?;
+
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(anyhow::anyhow!("screening API returned {status}").context(outage()));
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("screening API returned {status} for {user}: {body}");
+        }
+        let body: Value = response
+            .json()
+            .await
+            .context("screening API response is not JSON")?;
+        self.config
+            .screening(&body)
+            .with_context(|| format!("failed to read the screening of {user}"))
+    }
+}
+
+/// Screen the user of every entry with `source`, replacing the AML result and the risk score
+/// taken from the arguments or the input file.
+pub async fn screen_entries(source: &ScreeningSource, entries: &mut [BatchEntry]) -> Result<()> {
+    for entry in entries {
+        source.screen(entry.user).await?.apply_to(entry);
+    }
+    Ok(())
+}
+
+/// An [AttestationSource] that takes the KYC fields from `kyc` and the AML result and the risk
+/// score from a screening API.
+#[derive(Debug)]
+pub struct Screened<S> {
+    kyc: S,
+    screening: ScreeningSource,
+}
+
+impl<S: AttestationSource> Screened<S> {
+    pub fn new(kyc: S, screening: ScreeningSource) -> Self {
+        Self { kyc, screening }
+    }
+}
+
+impl<S: AttestationSource> AttestationSource for Screened<S> {
+    async fn fetch(&self, user: Address) -> Result<ComplianceStatus> {
+        let status = self.kyc.fetch(user).await?;
+        let screening = self.screening.screen(user).await?;
+        Ok(ComplianceStatus {
+            aml_passed: screening.aml_passed,
+            risk_score: Some(screening.risk_score),
+            ..status
+        })
+    }
+}
@@ -0,0 +1,398 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{future::Future, path::PathBuf, time::Duration};
+
+use alloy::{
+    primitives::{Address, Bytes, Log, B256, U256},
+    providers::Provider,
+    sol_types::{SolCall, SolEvent},
+};
+use anyhow::{bail, ensure, Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    batch::BatchEntry,
+    compliance_hook::IComplianceHook::{beforeTradeCall, IComplianceHookInstance, TradeAllowed},
+    journal::{format_decision_at, unix_now, ComplianceJournal},
+    preflight::check_seal,
+    relayer::RelayerClient,
+    signing::SignedResult,
+};
+
+/// Timeout for the transaction to be confirmed.
+pub const TX_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How a `beforeTrade` call, or an attestation of its decision, reached the chain or was handed
+/// off, where its proof was pinned and how its result was signed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Settlement {
+    /// The transaction was sent by the signer and confirmed.
+    Confirmed { tx_hash: B256 },
+    /// The call was handed to a relayer.
+    Relayed {
+        tx_hash: Option<B256>,
+        task_id: Option<String>,
+    },
+    /// The decision was attested with EAS, after the hook settlement, if any; see
+    /// [crate::eas::AttestingSettler].
+    Attested {
+        uid: B256,
+        tx_hash: B256,
+        hook: Option<Box<Settlement>>,
+    },
+    /// The proof bundle and the guest program were pinned to IPFS after `settlement`; see
+    /// [crate::ipfs::PinningSettler].
+    Pinned {
+        bundle_cid: String,
+        program_cid: String,
+        settlement: Box<Settlement>,
+    },
+    /// The operator signed the result of the decision before `settlement`; see
+    /// [crate::signing::SigningSettler].
+    Signed {
+        result: SignedResult,
+        settlement: Box<Settlement>,
+    },
+    /// The trade was written to a bundle for another party to settle; see
+    /// [crate::handoff::ExportingSettler].
+    Exported { path: PathBuf },
+    /// The decision was relayed to the receiver on `chain` in `tx_hash` after `settlement`; see
+    /// [crate::relay::RelayingSettler].
+    Forwarded {
+        chain: String,
+        tx_hash: B256,
+        settlement: Box<Settlement>,
+    },
+}
+
+impl Settlement {
+    /// Hash of the `beforeTrade` transaction, or of the attestation without one, when it is
+    /// known.
+    pub fn tx_hash(&self) -> Option<B256> {
+        match self {
+            Settlement::Confirmed { tx_hash } => Some(*tx_hash),
+            Settlement::Relayed { tx_hash, .. } => *tx_hash,
+            Settlement::Attested { tx_hash, hook, .. } => match hook {
+                Some(hook) => hook.tx_hash(),
+                None => Some(*tx_hash),
+            },
+            Settlement::Pinned { settlement, .. }
+            | Settlement::Signed { settlement, .. }
+            | Settlement::Forwarded { settlement, .. } => settlement.tx_hash(),
+            Settlement::Exported { .. } => None,
+        }
+    }
+
+    /// UID of the EAS attestation of the decision, if it was attested.
+    pub fn attestation_uid(&self) -> Option<B256> {
+        match self {
+            Settlement::Attested { uid, .. } => Some(*uid),
+            Settlement::Pinned { settlement, .. }
+            | Settlement::Signed { settlement, .. }
+            | Settlement::Forwarded { settlement, .. } => settlement.attestation_uid(),
+            _ => None,
+        }
+    }
+
+    /// CID of the pinned proof bundle, if it was pinned.
+    pub fn bundle_cid(&self) -> Option<&str> {
+        match self {
+            Settlement::Pinned { bundle_cid, .. } => Some(bundle_cid),
+            _ => None,
+        }
+    }
+
+    /// The operator's signed result of the decision, if it was signed.
+    pub fn signed_result(&self) -> Option<&SignedResult> {
+        match self {
+            Settlement::Signed { result, .. } => Some(result),
+            Settlement::Pinned { settlement, .. } | Settlement::Forwarded { settlement, .. } => {
+                settlement.signed_result()
+            }
+            _ => None,
+        }
+    }
+
+    /// Chain the decision was relayed to, and the hash of the relay transaction, if it was.
+    pub fn forwarded(&self) -> Option<(&str, B256)> {
+        match self {
+            Settlement::Forwarded { chain, tx_hash, .. } => Some((chain, *tx_hash)),
+            Settlement::Pinned { settlement, .. } => settlement.forwarded(),
+            _ => None,
+        }
+    }
+}
+
+/// Delivers a proven journal and seal to the compliance hook.
+pub trait Settler: Send + Sync {
+    fn settle(
+        &self,
+        entry: &BatchEntry,
+        journal: Bytes,
+        seal: Bytes,
+    ) -> impl Future<Output = Result<Settlement>> + Send;
+}
+
+/// Settles by sending `beforeTrade` from the configured signer.
+pub struct HookSettler<P> {
+    hook: IComplianceHookInstance<P>,
+    from: Address,
+    verify: bool,
+    check_seal: bool,
+}
+
+impl<P: Provider> HookSettler<P> {
+    pub fn new(hook_address: Address, provider: P, from: Address) -> Self {
+        Self {
+            hook: IComplianceHookInstance::new(hook_address, provider),
+            from,
+            verify: false,
+            check_seal: false,
+        }
+    }
+
+    /// After confirmation, check that the hook recorded the decision that was submitted; see
+    /// [verify_recorded_decision].
+    pub fn with_verification(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Before sending, check that the hook's verifier takes the seal; see [check_seal].
+    pub fn with_seal_check(mut self, check_seal: bool) -> Self {
+        self.check_seal = check_seal;
+        self
+    }
+}
+
+impl<P: Provider + Send + Sync> Settler for HookSettler<P> {
+    async fn settle(&self, entry: &BatchEntry, journal: Bytes, seal: Bytes) -> Result<Settlement> {
+        if self.check_seal {
+            check_seal(self.hook.provider(), *self.hook.address(), &seal)
+                .await
+                .context("seal preflight failed; pass --skip-seal-check to send it anyway")?;
+        }
+        tracing::info!("Calling ComplianceHook beforeTrade function");
+        let journal_digest = B256::from_slice(&Sha256::digest(&journal));
+        let pending_tx = self
+            .hook
+            .beforeTrade(entry.user, entry.product_id, entry.amount, journal, seal)
+            .from(self.from)
+            .send()
+            .await
+            .context("failed to broadcast tx")?;
+        tracing::info!("Broadcasting tx {}", pending_tx.tx_hash());
+        let pending_tx = pending_tx.with_timeout(Some(TX_TIMEOUT));
+        if !self.verify {
+            let tx_hash = pending_tx.watch().await.context("failed to confirm tx")?;
+            tracing::info!("Tx {:?} confirmed", tx_hash);
+            return Ok(Settlement::Confirmed { tx_hash });
+        }
+
+        let receipt = pending_tx
+            .get_receipt()
+            .await
+            .context("failed to confirm tx")?;
+        let tx_hash = receipt.transaction_hash;
+        ensure!(receipt.status(), "tx {tx_hash} reverted");
+        tracing::info!("Tx {:?} confirmed", tx_hash);
+        verify_recorded_decision(
+            receipt.inner.logs().iter().map(|log| &log.inner),
+            *self.hook.address(),
+            entry,
+            journal_digest,
+        )
+        .with_context(|| format!("tx {tx_hash} did not record the submitted decision"))?;
+        Ok(Settlement::Confirmed { tx_hash })
+    }
+}
+
+/// Holds each trade until the hook may settle it, then settles it with the wrapped settler.
+///
+/// The hook refuses a decision before the `notBefore` its journal commits, so this waits for that
+/// time on the local clock. A journal without one is settled at once.
+pub struct DelayingSettler<S> {
+    settler: S,
+}
+
+impl<S> DelayingSettler<S> {
+    pub fn new(settler: S) -> Self {
+        Self { settler }
+    }
+}
+
+impl<S: Settler> Settler for DelayingSettler<S> {
+    async fn settle(&self, entry: &BatchEntry, journal: Bytes, seal: Bytes) -> Result<Settlement> {
+        let not_before = ComplianceJournal::try_from(journal.as_ref())
+            .context("invalid journal")?
+            .notBefore;
+        let wait = not_before.saturating_sub(unix_now());
+        if wait != 0 {
+            tracing::info!(
+                "Waiting {wait}s until {} to settle the decision for {}",
+                format_decision_at(not_before),
+                entry.user
+            );
+            tokio::time::sleep(Duration::from_secs(wait)).await;
+        }
+        self.settler.settle(entry, journal, seal).await
+    }
+}
+
+/// Check that `logs` hold the hook's `TradeAllowed` record of `entry`, proven in the journal with
+/// SHA-256 digest `journal_digest`.
+///
+/// Logs from other contracts are ignored. Both the onchain and the submitted values are logged, and
+/// any difference is an error.
+pub fn verify_recorded_decision<'a>(
+    logs: impl IntoIterator<Item = &'a Log>,
+    hook_address: Address,
+    entry: &BatchEntry,
+    journal_digest: B256,
+) -> Result<()> {
+    let submitted = TradeAllowed {
+        user: entry.user,
+        productId: entry.product_id,
+        amount: entry.amount,
+        journalDigest: journal_digest,
+    };
+    let Some(log) = logs.into_iter().find(|log| {
+        log.address == hook_address && log.topics().first() == Some(&TradeAllowed::SIGNATURE_HASH)
+    }) else {
+        bail!("hook {hook_address} emitted no TradeAllowed event");
+    };
+    let recorded = TradeAllowed::decode_log(log)
+        .context("failed to decode TradeAllowed event")?
+        .data;
+
+    tracing::info!(
+        "Onchain decision: user {} product {} amount {} journal digest {}",
+        recorded.user,
+        recorded.productId,
+        recorded.amount,
+        recorded.journalDigest
+    );
+    tracing::info!(
+        "Submitted decision: user {} product {} amount {} journal digest {}",
+        submitted.user,
+        submitted.productId,
+        submitted.amount,
+        submitted.journalDigest
+    );
+    ensure!(
+        recorded == submitted,
+        "onchain decision {recorded:?} does not match submitted {submitted:?}"
+    );
+    Ok(())
+}
+
+/// The allowed decision the hook cached for a user and product, as `cachedDecision` returns it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CachedDecision {
+    /// SHA-256 digest of the journal the decision was proven in, zero when none is cached.
+    pub journal_digest: B256,
+    /// Unix time in seconds from which the hook no longer accepts the decision.
+    pub expires_at: u64,
+    /// Seconds left until `expires_at` at the latest block.
+    pub remaining: U256,
+}
+
+impl CachedDecision {
+    /// Read the decision `hook_address` cached for `user` and `product_id`.
+    pub async fn fetch<P: Provider>(
+        hook_address: Address,
+        provider: P,
+        user: Address,
+        product_id: B256,
+    ) -> Result<Self> {
+        let cached = IComplianceHookInstance::new(hook_address, provider)
+            .cachedDecision(user, product_id)
+            .call()
+            .await
+            .context("failed to call cachedDecision")?;
+        Ok(Self {
+            journal_digest: cached.journalDigest,
+            expires_at: cached.expiresAt,
+            remaining: cached.remaining,
+        })
+    }
+
+    /// Human-readable account of the cached decision, one field per line.
+    pub fn explain(&self) -> String {
+        if self.journal_digest.is_zero() {
+            return "cached:      no\n".into();
+        }
+        let remaining = if self.remaining.is_zero() {
+            "expired".into()
+        } else {
+            let secs = self.remaining.saturating_to::<u64>();
+            humantime::format_duration(Duration::from_secs(secs)).to_string()
+        };
+        format!(
+            "cached:      {}\nexpires at:  {} ({})\nremaining:   {remaining}\n",
+            self.journal_digest,
+            format_decision_at(self.expires_at),
+            self.expires_at
+        )
+    }
+}
+
+/// Settles by handing the `beforeTrade` calldata to a meta-transaction relayer.
+pub struct RelayerSettler {
+    relayer: RelayerClient,
+    chain_id: u64,
+    hook_address: Address,
+}
+
+impl RelayerSettler {
+    pub fn new(relayer: RelayerClient, chain_id: u64, hook_address: Address) -> Self {
+        Self {
+            relayer,
+            chain_id,
+            hook_address,
+        }
+    }
+}
+
+impl Settler for RelayerSettler {
+    async fn settle(&self, entry: &BatchEntry, journal: Bytes, seal: Bytes) -> Result<Settlement> {
+        let calldata = beforeTradeCall {
+            user: entry.user,
+            productId: entry.product_id,
+            amount: entry.amount,
+            journal,
+            seal,
+        }
+        .abi_encode();
+
+        tracing::info!("Submitting ComplianceHook beforeTrade call through relayer");
+        let relayed = self
+            .relayer
+            .relay(self.chain_id, self.hook_address, calldata.into())
+            .await?;
+        if let Some(tx_hash) = relayed.tx_hash {
+            tracing::info!("Relayer broadcast tx {tx_hash}");
+        }
+        if let Some(task_id) = &relayed.task_id {
+            tracing::info!("Relayer accepted call as task {task_id}");
+        }
+        Ok(Settlement::Relayed {
+            tx_hash: relayed.tx_hash,
+            task_id: relayed.task_id,
+        })
+    }
+}
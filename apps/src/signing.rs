@@ -0,0 +1,224 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Operator signatures over proven decisions, for partners that cannot verify a RISC Zero seal but
+//! trust the operator's key.
+//!
+//! Once a trade's journal is proven and checked against the decision the host expects,
+//! [SigningSettler] signs a [ComplianceResult] of it as EIP-712 typed data under the configured
+//! [ResultDomain]. The [SignedResult] is carried by the trade's [Settlement], and from there by
+//! the JSON output, the webhook payload and the pinned proof bundle.
+
+use std::time::Duration;
+
+use alloy::{
+    primitives::{Address, Bytes, Signature, B256, U256},
+    signers::{local::PrivateKeySigner, SignerSync},
+    sol_types::{Eip712Domain, SolStruct},
+};
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    batch::BatchEntry,
+    journal::ComplianceJournal,
+    settle::{Settlement, Settler},
+};
+
+alloy::sol! {
+    /// What the operator signs of a proven decision.
+    #[derive(Debug, PartialEq, Eq)]
+    struct ComplianceResult {
+        address user;
+        bytes32 productId;
+        bool allowed;
+        /// SHA-256 of the journal, as the hook's `TradeAllowed` event carries it.
+        bytes32 journalDigest;
+        /// Image id the journal commits, that of the guest that proved it.
+        bytes32 imageId;
+        /// Unix time the result expires at, or zero when it does not.
+        uint64 validUntil;
+    }
+}
+
+impl ComplianceResult {
+    /// Result of the decision `journal` commits, valid for `valid_for` after its decision time
+    /// or, without it, for good.
+    pub fn new(journal: &[u8], valid_for: Option<Duration>) -> Result<Self> {
+        let decision = ComplianceJournal::try_from(journal).context("invalid journal")?;
+        Ok(Self {
+            user: decision.user,
+            productId: decision.productId,
+            allowed: decision.allowed,
+            journalDigest: B256::from_slice(&Sha256::digest(journal)),
+            imageId: decision.imageId,
+            validUntil: valid_for.map_or(0, |valid_for| {
+                decision.decisionAt.saturating_add(valid_for.as_secs())
+            }),
+        })
+    }
+}
+
+/// EIP-712 domain results are signed under. A verifier should check it is the one it expects, so
+/// a result signed for one deployment is not taken for another's.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultDomain {
+    pub name: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub verifying_contract: Address,
+}
+
+impl ResultDomain {
+    pub fn eip712_domain(&self) -> Eip712Domain {
+        Eip712Domain::new(
+            Some(self.name.clone().into()),
+            Some(self.version.clone().into()),
+            Some(U256::from(self.chain_id)),
+            Some(self.verifying_contract),
+            None,
+        )
+    }
+
+    /// Hash the operator signs for `result` under this domain.
+    pub fn signing_hash(&self, result: &ComplianceResult) -> B256 {
+        result.eip712_signing_hash(&self.eip712_domain())
+    }
+}
+
+/// A [ComplianceResult] and the operator's signature over it, as the output, the webhook payload
+/// and the proof bundle carry it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedResult {
+    pub user: Address,
+    pub product_id: B256,
+    pub allowed: bool,
+    pub journal_digest: B256,
+    pub image_id: B256,
+    pub valid_until: u64,
+    pub domain: ResultDomain,
+    /// The 65-byte signature, `r`, `s` and a `v` of 27 or 28, over [ResultDomain::signing_hash]
+    /// of the result.
+    pub signature: Bytes,
+}
+
+impl SignedResult {
+    pub fn new(result: ComplianceResult, domain: ResultDomain, signature: Signature) -> Self {
+        Self {
+            user: result.user,
+            product_id: result.productId,
+            allowed: result.allowed,
+            journal_digest: result.journalDigest,
+            image_id: result.imageId,
+            valid_until: result.validUntil,
+            domain,
+            signature: signature.as_bytes().into(),
+        }
+    }
+
+    pub fn result(&self) -> ComplianceResult {
+        ComplianceResult {
+            user: self.user,
+            productId: self.product_id,
+            allowed: self.allowed,
+            journalDigest: self.journal_digest,
+            imageId: self.image_id,
+            validUntil: self.valid_until,
+        }
+    }
+
+    /// Address that signed the result under its domain.
+    pub fn signer(&self) -> Result<Address> {
+        let signature =
+            Signature::try_from(self.signature.as_ref()).context("invalid signature")?;
+        signature
+            .recover_address_from_prehash(&self.domain.signing_hash(&self.result()))
+            .context("failed to recover the signer")
+    }
+
+    /// Check that `operator` signed the result.
+    pub fn verify(&self, operator: Address) -> Result<()> {
+        let signer = self.signer()?;
+        ensure!(
+            signer == operator,
+            "result is signed by {signer}, not by operator {operator}"
+        );
+        Ok(())
+    }
+}
+
+/// Signs results with the operator's key.
+#[derive(Clone, Debug)]
+pub struct ResultSigner {
+    signer: PrivateKeySigner,
+    domain: ResultDomain,
+    valid_for: Option<Duration>,
+}
+
+impl ResultSigner {
+    pub fn new(signer: PrivateKeySigner, domain: ResultDomain) -> Self {
+        Self {
+            signer,
+            domain,
+            valid_for: None,
+        }
+    }
+
+    /// Let results expire `valid_for` after their decision time.
+    pub fn with_validity(mut self, valid_for: Option<Duration>) -> Self {
+        self.valid_for = valid_for;
+        self
+    }
+
+    pub fn address(&self) -> Address {
+        self.signer.address()
+    }
+
+    /// Sign the decision `journal` commits.
+    pub fn sign(&self, journal: &[u8]) -> Result<SignedResult> {
+        let result = ComplianceResult::new(journal, self.valid_for)?;
+        let signature = self
+            .signer
+            .sign_hash_sync(&self.domain.signing_hash(&result))
+            .context("failed to sign result")?;
+        Ok(SignedResult::new(result, self.domain.clone(), signature))
+    }
+}
+
+/// Signs the result of each proven journal and then settles with `settler`.
+pub struct SigningSettler<S> {
+    settler: S,
+    signer: ResultSigner,
+}
+
+impl<S> SigningSettler<S> {
+    pub fn new(settler: S, signer: ResultSigner) -> Self {
+        Self { settler, signer }
+    }
+}
+
+impl<S: Settler> Settler for SigningSettler<S> {
+    async fn settle(&self, entry: &BatchEntry, journal: Bytes, seal: Bytes) -> Result<Settlement> {
+        let result = self.signer.sign(&journal)?;
+        tracing::info!("Signed result as operator {}", self.signer.address());
+        let settlement = self.settler.settle(entry, journal, seal).await?;
+        Ok(Settlement::Signed {
+            result,
+            settlement: Box::new(settlement),
+        })
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloy::{
+    hex,
+    primitives::{Address, B256, U256},
+};
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use url::Url;
+
+use crate::{
+    http::RetryPolicy,
+    journal::{ComplianceJournal, ReasonCode},
+    settle::Settlement,
+    signing::SignedResult,
+};
+
+/// Header carrying the HMAC-SHA256 of the request body, as `sha256=<hex>`.
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// A compliance decision reported to a webhook.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Decision {
+    pub request_id: U256,
+    pub user: Address,
+    pub product_id: B256,
+    pub allowed: bool,
+    /// Names of the denial reasons; empty when allowed.
+    pub reasons: Vec<&'static str>,
+    pub tx_hash: Option<B256>,
+    /// The operator's signed result, when results are signed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signed_result: Option<SignedResult>,
+}
+
+impl Decision {
+    /// The decision `journal` records, proven by request `request_id` and settled as
+    /// `settlement`.
+    pub fn settled(request_id: U256, journal: &ComplianceJournal, settlement: &Settlement) -> Self {
+        Self {
+            request_id,
+            user: journal.user,
+            product_id: journal.productId,
+            allowed: journal.allowed,
+            reasons: ReasonCode::from_mask(journal.reasons)
+                .map(ReasonCode::name)
+                .collect(),
+            tx_hash: settlement.tx_hash(),
+            signed_result: settlement.signed_result().cloned(),
+        }
+    }
+}
+
+/// Signature of `body` under `secret`, in the format of [SIGNATURE_HEADER].
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Posts decisions as JSON to a webhook, signing each body when a secret is configured.
+#[derive(Clone, Debug)]
+pub struct WebhookSink {
+    url: Url,
+    secret: Option<String>,
+    policy: RetryPolicy,
+    http: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(url: Url, secret: Option<String>) -> Self {
+        Self {
+            url,
+            secret,
+            policy: RetryPolicy::default(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Replace the default timeout and retry policy.
+    pub fn with_policy(self, policy: RetryPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Deliver `decision`, retrying transient failures.
+    pub async fn deliver(&self, decision: &Decision) -> Result<()> {
+        self.post(decision, "decision").await
+    }
+
+    /// POST `payload`, a `what`, as signed JSON, retrying transient failures.
+    pub(crate) async fn post(&self, payload: &(impl Serialize + Sync), what: &str) -> Result<()> {
+        let body =
+            serde_json::to_vec(payload).with_context(|| format!("failed to encode {what}"))?;
+        let signature = self
+            .secret
+            .as_ref()
+            .map(|secret| sign(secret.as_bytes(), &body));
+
+        let response = self
+            .policy
+            .send(|| {
+                let request = self
+                    .http
+                    .post(self.url.clone())
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body.clone());
+                match &signature {
+                    Some(signature) => request.header(SIGNATURE_HEADER, signature),
+                    None => request,
+                }
+            })
+            .await
+            .with_context(|| format!("failed to deliver {what} to {}", self.url))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("webhook rejected the {what} with {status}: {body}");
+        }
+        Ok(())
+    }
+
+    /// Deliver the decision of a settled trade. The trade stays settled when delivery fails, so
+    /// the failure is only logged, by request id: the user is left out of the log.
+    pub async fn report(&self, request_id: U256, journal: &[u8], settlement: &Settlement) {
+        let delivered = match ComplianceJournal::try_from(journal) {
+            Ok(journal) => {
+                self.deliver(&Decision::settled(request_id, &journal, settlement))
+                    .await
+            }
+            Err(err) => Err(anyhow::Error::new(err).context("failed to decode journal")),
+        };
+        match delivered {
+            Ok(()) => tracing::info!("Reported request {request_id:x} to the webhook"),
+            Err(err) => {
+                tracing::warn!("Failed to report request {request_id:x} to the webhook: {err:#}")
+            }
+        }
+    }
+}
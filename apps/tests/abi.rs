@@ -0,0 +1,69 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conformance checks pinning the `IComplianceHook` bindings to the deployed interface.
+//!
+//! A failure here means `contracts/src/IComplianceHook.sol` changed shape. Either revert the
+//! interface change, or update the expected values below and redeploy the hook, since calls built
+//! from the new bindings will not match the contract already onchain.
+
+use alloy::{
+    hex,
+    primitives::{address, b256, bytes, U256},
+    sol_types::{SolCall, SolEvent},
+};
+use app::compliance_hook::IComplianceHook::{beforeTradeCall, TradeAllowed};
+
+#[test]
+fn before_trade_selector() {
+    assert_eq!(
+        beforeTradeCall::SIGNATURE,
+        "beforeTrade(address,bytes32,uint256,bytes,bytes)"
+    );
+    assert_eq!(beforeTradeCall::SELECTOR, hex!("938f8a7a"));
+}
+
+#[test]
+fn trade_allowed_event_signature() {
+    assert_eq!(
+        TradeAllowed::SIGNATURE,
+        "TradeAllowed(address,bytes32,uint256,bytes32)"
+    );
+    assert_eq!(
+        TradeAllowed::SIGNATURE_HASH,
+        b256!("0x3aafcd49cb11696b3837e6d5f92344b38c95a9e91fa76e277c66a67fe47a3bf3")
+    );
+}
+
+/// The fixture was captured with:
+///
+/// ```text
+/// cast calldata "beforeTrade(address,bytes32,uint256,bytes,bytes)" \
+///   0x0000000000000000000000000000000000001234 \
+///   0x0000000000000000000000000000000000000000000000000000000000000001 \
+///   100 0xaabb 0xccddee
+/// ```
+#[test]
+fn before_trade_calldata_matches_fixture() {
+    let call = beforeTradeCall {
+        user: address!("0x0000000000000000000000000000000000001234"),
+        productId: b256!("0x0000000000000000000000000000000000000000000000000000000000000001"),
+        amount: U256::from(100),
+        journal: bytes!("aabb"),
+        seal: bytes!("ccddee"),
+    };
+
+    let fixture = include_str!("fixtures/before_trade.calldata").trim();
+    assert_eq!(hex::encode_prefixed(call.abi_encode()), fixture);
+}
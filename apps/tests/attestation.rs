@@ -0,0 +1,304 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use alloy::{
+    eips::BlockId,
+    primitives::{address, b256, B256, U256},
+    providers::ProviderBuilder,
+};
+use app::{
+    attestation::{
+        check_eip1271_signature, fetch_compliance, AttestationSource, ComplianceStatus, HttpSource,
+        EIP1271_MAGIC_VALUE,
+    },
+    batch::BatchEntry,
+};
+use common::fast_retries;
+use serde_json::{json, Value};
+use url::Url;
+use wiremock::{
+    matchers::{bearer_token, header, method, path},
+    Mock, MockServer, Request, ResponseTemplate,
+};
+
+const USER: alloy::primitives::Address = address!("0x00000000000000000000000000000000000000aa");
+
+fn source(server: &MockServer) -> HttpSource {
+    let url = Url::parse(&format!("{}/kyc", server.uri())).unwrap();
+    HttpSource::new(url, Some("token".into())).with_policy(fast_retries())
+}
+
+fn user_path() -> String {
+    format!("/kyc/{USER}")
+}
+
+const ATTESTER: alloy::primitives::Address = address!("0x00000000000000000000000000000000000000bb");
+const HASH: B256 = b256!("0x1111111111111111111111111111111111111111111111111111111111111111");
+
+/// Answer every JSON-RPC call with `result`, as an `isValidSignature` eth_call would.
+async fn rpc_returning(result: B256) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(move |request: &Request| {
+            let request: Value = serde_json::from_slice(&request.body).unwrap();
+            ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": result,
+            }))
+        })
+        .mount(&server)
+        .await;
+    server
+}
+
+async fn check_signature(server: &MockServer) -> anyhow::Result<()> {
+    let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+    check_eip1271_signature(
+        &provider,
+        ATTESTER,
+        HASH,
+        vec![0xab; 65].into(),
+        BlockId::latest(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn maps_provider_response() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(user_path()))
+        .and(bearer_token("token"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "kycTier": 2, "amlPassed": true })),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let status = source(&server).fetch(USER).await.unwrap();
+
+    assert_eq!(
+        status,
+        ComplianceStatus {
+            kyc_passed: false,
+            kyc_tier: Some(2),
+            aml_passed: true,
+            claims: U256::ZERO,
+            risk_score: None,
+            credential_hash: B256::ZERO,
+        }
+    );
+    assert_eq!(status.kyc_tier(), 2);
+}
+
+#[tokio::test]
+async fn sends_extra_headers() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(user_path()))
+        .and(header("x-api-key", "secret"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "kycTier": 1, "amlPassed": true })),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let source =
+        source(&server).with_header("x-api-key".parse().unwrap(), "secret".parse().unwrap());
+    source.fetch(USER).await.unwrap();
+}
+
+#[tokio::test]
+async fn fills_entries_from_provider() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(user_path()))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "kycTier": 3, "amlPassed": true, "claims": "0x5" })),
+        )
+        .mount(&server)
+        .await;
+    let mut entries: Vec<BatchEntry> = serde_json::from_value(json!([{
+        "user": USER,
+        "productId": B256::with_last_byte(1),
+        "amount": "0x64",
+        "requiredTier": 2,
+        "requiredClaims": "0x1",
+        "amlPassed": false,
+        "consentRequired": false,
+    }]))
+    .unwrap();
+
+    fetch_compliance(&source(&server), &mut entries)
+        .await
+        .unwrap();
+
+    assert_eq!(entries[0].kyc_tier, Some(3));
+    assert!(entries[0].aml_passed);
+    assert_eq!(entries[0].claims, U256::from(5));
+    assert_eq!(entries[0].required_tier, 2);
+    assert_eq!(entries[0].required_claims, U256::from(1));
+    assert!(entries[0].expected_journal(0).allowed);
+}
+
+#[tokio::test]
+async fn retries_server_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(user_path()))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(2)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(user_path()))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "kycPassed": true, "amlPassed": true })),
+        )
+        .mount(&server)
+        .await;
+
+    let status = source(&server).fetch(USER).await.unwrap();
+
+    assert_eq!(status.kyc_tier(), 1);
+    assert_eq!(server.received_requests().await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn gives_up_after_retries() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(503))
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let err = source(&server).fetch(USER).await.unwrap_err();
+
+    assert!(
+        format!("{err:#}").contains("giving up after 3 attempts"),
+        "{err:#}"
+    );
+}
+
+#[tokio::test]
+async fn times_out_slow_responses() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "kycPassed": true, "amlPassed": true }))
+                .set_delay(fast_retries().timeout * 10),
+        )
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let err = source(&server).fetch(USER).await.unwrap_err();
+
+    assert!(
+        format!("{err:#}").contains("giving up after 3 attempts"),
+        "{err:#}"
+    );
+}
+
+#[tokio::test]
+async fn does_not_retry_client_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(404).set_body_string("unknown user"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let err = source(&server).fetch(USER).await.unwrap_err();
+
+    assert!(err.to_string().contains("404 Not Found"), "{err}");
+}
+
+#[tokio::test]
+async fn rejects_schema_mismatch() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "status": "ok" })))
+        .mount(&server)
+        .await;
+
+    let err = source(&server).fetch(USER).await.unwrap_err();
+
+    assert!(
+        err.to_string()
+            .contains("did not match the expected schema"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn accepts_valid_eip1271_signature() {
+    let mut word = B256::ZERO;
+    word[..4].copy_from_slice(EIP1271_MAGIC_VALUE.as_slice());
+    let server = rpc_returning(word).await;
+
+    check_signature(&server).await.unwrap();
+
+    let calls = server.received_requests().await.unwrap();
+    let call: Value = serde_json::from_slice(&calls[0].body).unwrap();
+    assert_eq!(call["method"], "eth_call");
+    assert_eq!(call["params"][0]["to"], json!(ATTESTER));
+}
+
+#[tokio::test]
+async fn rejects_invalid_eip1271_signature() {
+    let server = rpc_returning(B256::ZERO).await;
+
+    let err = check_signature(&server).await.unwrap_err();
+
+    assert!(err.to_string().contains("rejected the signature"), "{err}");
+}
+
+#[tokio::test]
+async fn checks_the_attestation_at_the_entry_block() {
+    let mut word = B256::ZERO;
+    word[..4].copy_from_slice(EIP1271_MAGIC_VALUE.as_slice());
+    let server = rpc_returning(word).await;
+    let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+    let block_hash = B256::repeat_byte(0xb1);
+    let mut entry: BatchEntry = serde_json::from_value(json!({
+        "user": USER,
+        "productId": B256::with_last_byte(1),
+        "amount": "0x64",
+        "amlPassed": true,
+        "attester": ATTESTER,
+        "attestationSignature": format!("0x{}", "ab".repeat(65)),
+        "blockHash": block_hash,
+    }))
+    .unwrap();
+
+    entry.check_attestation(&provider).await.unwrap();
+
+    let calls = server.received_requests().await.unwrap();
+    let call: Value = serde_json::from_slice(&calls[0].body).unwrap();
+    assert_eq!(call["method"], "eth_call");
+    assert_eq!(call["params"][1]["blockHash"], json!(block_hash));
+    assert_eq!(entry.block_hash, block_hash);
+    assert_eq!(entry.expected_journal(0).blockHash, block_hash);
+}
@@ -0,0 +1,571 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+
+use alloy::{
+    primitives::{Address, B256, U256},
+    sol_types::SolValue,
+};
+use app::{
+    batch::{run_batch, BatchEntry, BatchOptions, Concurrency, EntryReport, Outcome, Stage},
+    journal::{ComplianceInput, ComplianceJournal, ReasonCode},
+    webhook::WebhookSink,
+};
+use boundless_market::request_builder::RequestParams;
+use common::{fast_retries, MockMarket, MockSettler, Step};
+use serde_json::{json, Value};
+use url::Url;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+const DECISION_AT: u64 = 1_700_000_000;
+
+fn entries(count: u8) -> Vec<(BatchEntry, RequestParams)> {
+    (1..=count)
+        .map(|i| {
+            let entry = BatchEntry {
+                product_id: B256::with_last_byte(i),
+                ..common::entry(i)
+            };
+            let request = RequestParams::new().with_stdin(entry.stdin(DECISION_AT));
+            (entry, request)
+        })
+        .collect()
+}
+
+fn single_market(script: impl IntoIterator<Item = Step>) -> Arc<[MockMarket]> {
+    Arc::new([MockMarket::new(script)])
+}
+
+fn limits(proofs: usize, settlements: usize) -> Concurrency {
+    Concurrency {
+        proofs: NonZeroUsize::new(proofs).unwrap(),
+        settlements: NonZeroUsize::new(settlements).unwrap(),
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn bounds_each_stage_independently() {
+    let markets = single_market(vec![Step::Fulfill(Duration::from_secs(10)); 8]);
+    let settler = Arc::new(MockSettler::new(Duration::from_secs(1)));
+
+    let reports = run_batch(
+        markets.clone(),
+        settler.clone(),
+        entries(8),
+        DECISION_AT,
+        limits(3, 1),
+        BatchOptions::default(),
+    )
+    .await;
+
+    assert!(reports.iter().all(|report| report.is_settled()));
+    assert_eq!(markets[0].in_flight.peak(), 3);
+    assert_eq!(settler.in_flight.peak(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn settlement_limit_does_not_throttle_proving() {
+    let markets = single_market(vec![Step::Fulfill(Duration::from_secs(1)); 4]);
+    let settler = Arc::new(MockSettler::new(Duration::from_secs(60)));
+
+    let reports = run_batch(
+        markets.clone(),
+        settler.clone(),
+        entries(4),
+        DECISION_AT,
+        limits(4, 2),
+        BatchOptions::default(),
+    )
+    .await;
+
+    assert!(reports.iter().all(|report| report.is_settled()));
+    assert_eq!(markets[0].in_flight.peak(), 4);
+    assert_eq!(settler.in_flight.peak(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn reports_failures_per_entry_in_input_order() {
+    let markets = single_market([
+        Step::Fulfill(Duration::from_secs(3)),
+        Step::Expire(Duration::from_secs(1)),
+        Step::Fulfill(Duration::from_secs(2)),
+    ]);
+    let settler = Arc::new(MockSettler::new(Duration::ZERO).rejecting(Address::with_last_byte(3)));
+
+    let reports = run_batch(
+        markets,
+        settler.clone(),
+        entries(3),
+        DECISION_AT,
+        limits(1, 1),
+        BatchOptions::default(),
+    )
+    .await;
+
+    let indices: Vec<_> = reports.iter().map(|report| report.index).collect();
+    assert_eq!(indices, [0, 1, 2]);
+    assert!(reports[0].is_settled());
+    assert!(matches!(
+        &reports[1].outcome,
+        Outcome::Failed { stage: Stage::Prove, error } if error.contains("Request has expired")
+    ));
+    assert!(matches!(
+        &reports[2].outcome,
+        Outcome::Failed { stage: Stage::Settle, error } if error == "execution reverted"
+    ));
+
+    let settled = settler.settled();
+    assert_eq!(settled.len(), 1);
+    assert_eq!(settled[0].0.user, Address::with_last_byte(1));
+    assert_eq!(settled[0].2.as_ref(), settled[0].0.stdin(DECISION_AT));
+}
+
+/// Guest input carried by a request's framed stdin.
+fn input_of(stdin: &[u8]) -> ComplianceInput {
+    let (len, payload) = stdin.split_at(4);
+    assert_eq!(
+        u32::from_le_bytes(len.try_into().unwrap()) as usize,
+        payload.len()
+    );
+    ComplianceInput::abi_decode(payload).unwrap()
+}
+
+/// 1-based position of the entry a request was built for, as numbered by [entries].
+fn entry_number(stdin: &[u8]) -> u8 {
+    input_of(stdin).user.0[19]
+}
+
+fn failed_with(report: &EntryReport, expected: Stage, needle: &str) -> bool {
+    matches!(&report.outcome, Outcome::Failed { stage, error } if *stage == expected && error.contains(needle))
+}
+
+/// Per-entry market behaviour for the 50-entry batches: staggered delays, with every tenth entry
+/// expiring and every tenth (offset by four) losing its connection while waiting.
+fn staggered(stdin: &[u8]) -> Step {
+    let number = entry_number(stdin);
+    let delay = Duration::from_millis(u64::from(number % 7) * 3);
+    match number % 10 {
+        3 => Step::Expire(delay),
+        7 => Step::WaitError(delay, "connection reset"),
+        _ => Step::Fulfill(delay),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn large_batch_respects_limits_and_reports_every_entry_in_order() {
+    let markets: Arc<[MockMarket]> = Arc::new([MockMarket::picking(staggered)]);
+    let settler =
+        Arc::new(MockSettler::new(Duration::from_millis(2)).rejecting(Address::with_last_byte(25)));
+
+    let reports = run_batch(
+        markets.clone(),
+        settler.clone(),
+        entries(50),
+        DECISION_AT,
+        limits(5, 2),
+        BatchOptions::default(),
+    )
+    .await;
+
+    assert_eq!(reports.len(), 50);
+    for (index, report) in reports.iter().enumerate() {
+        let number = index as u8 + 1;
+        assert_eq!(report.index, index);
+        assert_eq!(report.user, Address::with_last_byte(number));
+        let ok = match number {
+            n if n % 10 == 3 => failed_with(report, Stage::Prove, "Request has expired"),
+            n if n % 10 == 7 => failed_with(report, Stage::Prove, "connection reset"),
+            25 => failed_with(report, Stage::Settle, "execution reverted"),
+            _ => report.is_settled(),
+        };
+        assert!(ok, "entry {index}: {report:?}");
+    }
+    assert_eq!(markets[0].submissions().len(), 50);
+    assert_eq!(settler.settled().len(), 39);
+    assert!(markets[0].in_flight.peak() <= 5);
+    assert!(settler.in_flight.peak() <= 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn panicking_entries_fail_without_stopping_the_batch() {
+    let markets: Arc<[MockMarket]> =
+        Arc::new([MockMarket::picking(|stdin| match entry_number(stdin) {
+            17 => Step::Panic("market bug"),
+            number => Step::Fulfill(Duration::from_millis(u64::from(number % 5))),
+        })]);
+    let settler = Arc::new(MockSettler::new(Duration::ZERO).panicking(Address::with_last_byte(33)));
+
+    let reports = run_batch(
+        markets.clone(),
+        settler.clone(),
+        entries(50),
+        DECISION_AT,
+        limits(4, 1),
+        BatchOptions::default(),
+    )
+    .await;
+
+    assert_eq!(reports.len(), 50);
+    assert_eq!(
+        reports[16].outcome,
+        Outcome::Failed {
+            stage: Stage::Prove,
+            error: "panicked: market bug".into()
+        }
+    );
+    assert_eq!(
+        reports[32].outcome,
+        Outcome::Failed {
+            stage: Stage::Settle,
+            error: "panicked: settler bug".into()
+        }
+    );
+    // With a single settlement slot, a permit leaked by the panicking settle would have left every
+    // later entry waiting forever.
+    let settled = reports.iter().filter(|report| report.is_settled()).count();
+    assert_eq!(settled, 48);
+    assert!(markets[0].in_flight.peak() <= 4);
+    assert!(settler.in_flight.peak() <= 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn commits_the_supplied_decision_time() {
+    let markets = single_market([Step::Fulfill(Duration::from_secs(1))]);
+    let settler = Arc::new(MockSettler::new(Duration::ZERO));
+
+    let reports = run_batch(
+        markets,
+        settler.clone(),
+        entries(1),
+        DECISION_AT,
+        limits(1, 1),
+        BatchOptions::default(),
+    )
+    .await;
+
+    assert!(reports[0].is_settled());
+    let (_, journal, seal) = &settler.settled()[0];
+    assert_eq!(input_of(seal).now, DECISION_AT);
+    assert_eq!(
+        ComplianceJournal::abi_decode(journal).unwrap().decisionAt,
+        DECISION_AT
+    );
+}
+
+#[test]
+fn parses_input_file_entries() {
+    let entries: Vec<BatchEntry> = serde_json::from_str(
+        r#"[{
+            "user": "0x0000000000000000000000000000000000000001",
+            "productId": "0x0000000000000000000000000000000000000000000000000000000000000002",
+            "amount": "0x64",
+            "kycPassed": true,
+            "amlPassed": false
+        }]"#,
+    )
+    .unwrap();
+
+    assert_eq!(entries[0].user, Address::with_last_byte(1));
+    assert_eq!(entries[0].amount, U256::from(100));
+    assert_eq!(entries[0].kyc_tier(), 1);
+    assert!(!entries[0].aml_passed);
+}
+
+#[test]
+fn keeps_a_supplied_decision_uuid_and_draws_the_rest() {
+    let mut entries: Vec<BatchEntry> = serde_json::from_str(
+        r#"[{
+            "user": "0x0000000000000000000000000000000000000001",
+            "productId": "0x0000000000000000000000000000000000000000000000000000000000000002",
+            "amount": "0x64",
+            "decisionUuid": "0x00000000000000000000000000000000000000000000000000000000000000d1"
+        }, {
+            "user": "0x0000000000000000000000000000000000000001",
+            "productId": "0x0000000000000000000000000000000000000000000000000000000000000002",
+            "amount": "0x64"
+        }]"#,
+    )
+    .unwrap();
+
+    let supplied = B256::with_last_byte(0xd1);
+    assert_eq!(entries[0].ensure_decision_uuid(), supplied);
+    let drawn = entries[1].ensure_decision_uuid();
+    assert!(!drawn.is_zero());
+    assert_ne!(drawn, supplied);
+    // Once drawn, the identifier stays with the entry.
+    assert_eq!(entries[1].ensure_decision_uuid(), drawn);
+    assert_eq!(entries[1].expected_journal(DECISION_AT).decisionUuid, drawn);
+    assert_eq!(
+        input_of(&entries[0].stdin(DECISION_AT)).decisionUuid,
+        supplied
+    );
+}
+
+#[test]
+fn kyc_tier_overrides_legacy_flag() {
+    let entries: Vec<BatchEntry> = serde_json::from_str(
+        r#"[{
+            "user": "0x0000000000000000000000000000000000000001",
+            "productId": "0x0000000000000000000000000000000000000000000000000000000000000002",
+            "amount": "0x64",
+            "kycTier": 2,
+            "requiredTier": 3,
+            "amlPassed": true
+        }]"#,
+    )
+    .unwrap();
+
+    let journal = entries[0].expected_journal(DECISION_AT);
+    assert_eq!(journal.kycTier, 2);
+    assert!(!journal.allowed);
+}
+
+#[test]
+fn claims_are_checked_per_product() {
+    let entries: Vec<BatchEntry> = serde_json::from_str(
+        r#"[{
+            "user": "0x0000000000000000000000000000000000000001",
+            "productId": "0x0000000000000000000000000000000000000000000000000000000000000002",
+            "amount": "0x64",
+            "kycPassed": true,
+            "amlPassed": true,
+            "consentRequired": false,
+            "claims": "0x3",
+            "requiredClaims": "0x1"
+        }, {
+            "user": "0x0000000000000000000000000000000000000001",
+            "productId": "0x0000000000000000000000000000000000000000000000000000000000000003",
+            "amount": "0x64",
+            "kycPassed": true,
+            "amlPassed": true,
+            "consentRequired": false,
+            "claims": "0x3",
+            "requiredClaims": "0x5"
+        }]"#,
+    )
+    .unwrap();
+
+    let satisfied = entries[0].expected_journal(DECISION_AT);
+    assert!(satisfied.allowed);
+    assert_eq!(satisfied.claims, U256::from(3));
+
+    let missing = entries[1].expected_journal(DECISION_AT);
+    assert!(!missing.allowed);
+    assert_eq!(missing.reasons, ReasonCode::ClaimsMissing.bit());
+    assert_eq!(missing.claims, U256::from(3));
+}
+
+#[test]
+fn pair_requires_both_legs_to_be_eligible() {
+    let entries: Vec<BatchEntry> = serde_json::from_str(
+        r#"[{
+            "user": "0x0000000000000000000000000000000000000001",
+            "productId": "0x0000000000000000000000000000000000000000000000000000000000000002",
+            "amount": "0x64",
+            "kycTier": 2,
+            "amlPassed": true,
+            "consentRequired": false,
+            "claims": "0x1",
+            "productPolicyVersion": 1
+        }, {
+            "user": "0x0000000000000000000000000000000000000001",
+            "productId": "0x0000000000000000000000000000000000000000000000000000000000000002",
+            "amount": "0x64",
+            "kycTier": 2,
+            "amlPassed": true,
+            "consentRequired": false,
+            "claims": "0x1",
+            "productPolicyVersion": 1,
+            "quote": {
+                "productId": "0x0000000000000000000000000000000000000000000000000000000000000003",
+                "requiredTier": 2,
+                "productPolicyVersion": 5
+            }
+        }, {
+            "user": "0x0000000000000000000000000000000000000001",
+            "productId": "0x0000000000000000000000000000000000000000000000000000000000000002",
+            "amount": "0x64",
+            "kycTier": 2,
+            "amlPassed": true,
+            "consentRequired": false,
+            "claims": "0x1",
+            "productPolicyVersion": 1,
+            "quote": {
+                "productId": "0x0000000000000000000000000000000000000000000000000000000000000003",
+                "requiredTier": 3,
+                "requiredClaims": "0x2"
+            }
+        }]"#,
+    )
+    .unwrap();
+
+    // A single product is its own quote leg.
+    let single = entries[0].expected_journal(DECISION_AT);
+    assert!(single.allowed);
+    assert_eq!(single.quoteProduct, single.productId);
+    assert_eq!(single.quotePolicyVersion, 1);
+
+    let both = entries[1].expected_journal(DECISION_AT);
+    assert!(both.allowed);
+    assert_eq!(both.productId, B256::with_last_byte(2));
+    assert_eq!(both.quoteProduct, B256::with_last_byte(3));
+    assert_eq!((both.productPolicyVersion, both.quotePolicyVersion), (1, 5));
+
+    let quote_ineligible = entries[2].expected_journal(DECISION_AT);
+    assert!(!quote_ineligible.allowed);
+    assert_eq!(
+        quote_ineligible.reasons,
+        ReasonCode::KycTierTooLow.bit() | ReasonCode::ClaimsMissing.bit()
+    );
+    assert_eq!(quote_ineligible.quoteProduct, B256::with_last_byte(3));
+}
+
+#[tokio::test]
+async fn reports_settled_decisions_to_webhook() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&server)
+        .await;
+    let webhook =
+        WebhookSink::new(Url::parse(&server.uri()).unwrap(), None).with_policy(fast_retries());
+    let markets = single_market(vec![Step::Fulfill(Duration::ZERO); 3]);
+    let settler = Arc::new(MockSettler::new(Duration::ZERO).rejecting(Address::with_last_byte(2)));
+    let options = BatchOptions {
+        webhook: Some(Arc::new(webhook)),
+        ..Default::default()
+    };
+
+    let reports = run_batch(
+        markets,
+        settler,
+        entries(3),
+        DECISION_AT,
+        limits(1, 1),
+        options,
+    )
+    .await;
+
+    let mut posted: Vec<Value> = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect();
+    posted.sort_by_key(|decision| decision["user"].as_str().unwrap().to_string());
+    let settled: Vec<_> = reports
+        .iter()
+        .filter_map(|report| match &report.outcome {
+            Outcome::Settled {
+                request_id,
+                settlement,
+            } => Some(json!({
+                "requestId": request_id,
+                "user": report.user,
+                "productId": report.product_id,
+                "allowed": true,
+                "reasons": [],
+                "txHash": settlement.tx_hash(),
+            })),
+            Outcome::Failed { .. } => None,
+        })
+        .collect();
+    assert_eq!(settled.len(), 2);
+    assert_eq!(posted, settled);
+}
+
+#[tokio::test(start_paused = true)]
+async fn all_or_nothing_batch_settles_nothing_when_one_entry_is_rejected() {
+    let mut entries = entries(3);
+    entries[1].0.aml_passed = false;
+    entries[1].1 = RequestParams::new().with_stdin(entries[1].0.stdin(DECISION_AT));
+    let markets = single_market(vec![Step::Fulfill(Duration::from_secs(1)); 3]);
+    let settler = Arc::new(MockSettler::new(Duration::ZERO));
+    let options = BatchOptions {
+        all_or_nothing: true,
+        ..Default::default()
+    };
+
+    let reports = run_batch(
+        markets,
+        settler.clone(),
+        entries,
+        DECISION_AT,
+        limits(3, 1),
+        options,
+    )
+    .await;
+
+    assert!(settler.settled().is_empty());
+    for report in &reports {
+        assert!(
+            failed_with(report, Stage::Settle, "entry 1 is not allowed"),
+            "{report:?}"
+        );
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn all_or_nothing_batch_waits_for_every_proof() {
+    let options = BatchOptions {
+        all_or_nothing: true,
+        ..Default::default()
+    };
+
+    let markets = single_market(vec![Step::Fulfill(Duration::from_secs(1)); 3]);
+    let settler = Arc::new(MockSettler::new(Duration::ZERO));
+    let reports = run_batch(
+        markets,
+        settler.clone(),
+        entries(3),
+        DECISION_AT,
+        limits(1, 1),
+        options.clone(),
+    )
+    .await;
+    assert!(reports.iter().all(|report| report.is_settled()));
+    assert_eq!(settler.settled().len(), 3);
+
+    // The last entry fails to prove after the others are proven, and none of them settles.
+    let markets = single_market([
+        Step::Fulfill(Duration::from_secs(1)),
+        Step::Fulfill(Duration::from_secs(1)),
+        Step::Expire(Duration::from_secs(5)),
+    ]);
+    let settler = Arc::new(MockSettler::new(Duration::ZERO));
+    let reports = run_batch(
+        markets,
+        settler.clone(),
+        entries(3),
+        DECISION_AT,
+        limits(1, 1),
+        options,
+    )
+    .await;
+    assert!(settler.settled().is_empty());
+    assert!(failed_with(
+        &reports[2],
+        Stage::Prove,
+        "Request has expired"
+    ));
+    assert!(failed_with(
+        &reports[0],
+        Stage::Settle,
+        "entry 2 failed to prove"
+    ));
+}
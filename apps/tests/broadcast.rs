@@ -0,0 +1,125 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::{Path, PathBuf};
+
+use alloy::{
+    primitives::{address, Address},
+    providers::ProviderBuilder,
+};
+use app::broadcast::{deployed_hook, find_deployment, HOOK_CONTRACT};
+use serde_json::{json, Value};
+use wiremock::{matchers::method, Mock, MockServer, Request, ResponseTemplate};
+
+const SEPOLIA: u64 = 11155111;
+const HOOK: Address = address!("0x00000000000000000000000000000000000000a1");
+
+/// Fixture broadcast files, laid out as `forge script --broadcast` writes them: `Deploy.s.sol`
+/// deploys the hook once on Sepolia and once on a local devnet, where `Upgrade.s.sol` deploys it
+/// again.
+fn broadcast(path: &str) -> PathBuf {
+    Path::new("tests/fixtures/broadcast").join(path)
+}
+
+/// A JSON-RPC endpoint serving `chain_id`, where only `deployed` has code.
+async fn chain_serving(chain_id: u64, deployed: Address) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(move |request: &Request| {
+            let request: Value = serde_json::from_slice(&request.body).unwrap();
+            let result = match request["method"].as_str().unwrap() {
+                "eth_chainId" => json!(format!("{chain_id:#x}")),
+                "eth_getCode" => {
+                    let address: Address = request["params"][0].as_str().unwrap().parse().unwrap();
+                    json!(if address == deployed { "0x6080" } else { "0x" })
+                }
+                other => panic!("unexpected RPC method {other}"),
+            };
+            ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": result,
+            }))
+        })
+        .mount(&server)
+        .await;
+    server
+}
+
+#[test]
+fn finds_the_hook_in_a_broadcast_file() {
+    let file = broadcast("Deploy.s.sol/11155111/run-latest.json");
+
+    let deployed = find_deployment(&file, SEPOLIA, HOOK_CONTRACT).unwrap();
+
+    assert_eq!(deployed.address, HOOK);
+    assert_eq!(deployed.file, file);
+    let err = find_deployment(&file, 1, HOOK_CONTRACT).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("is for chain 11155111, but the RPC endpoint serves chain 1"),
+        "{err}"
+    );
+    let err = find_deployment(&file, SEPOLIA, "Other").unwrap_err();
+    assert!(
+        err.to_string().contains("records no deployment of Other"),
+        "{err}"
+    );
+}
+
+#[test]
+fn searches_a_broadcast_directory_for_the_chain() {
+    let dir = broadcast("");
+
+    let deployed = find_deployment(&dir, SEPOLIA, HOOK_CONTRACT).unwrap();
+    assert_eq!(deployed.address, HOOK);
+
+    let err = find_deployment(&dir, 1, HOOK_CONTRACT).unwrap_err();
+    assert!(err.to_string().contains("has no run of chain 1"), "{err}");
+}
+
+#[test]
+fn several_deployments_are_ambiguous() {
+    let err = find_deployment(&broadcast(""), 31337, HOOK_CONTRACT).unwrap_err();
+
+    let message = err.to_string();
+    assert!(
+        message.contains("records 2 deployments of ComplianceHook"),
+        "{message}"
+    );
+    for candidate in [
+        address!("0x00000000000000000000000000000000000000b1").to_string(),
+        address!("0x00000000000000000000000000000000000000b2").to_string(),
+        "Upgrade.s.sol".to_owned(),
+    ] {
+        assert!(message.contains(&candidate), "{message}");
+    }
+}
+
+#[tokio::test]
+async fn deployed_hook_must_have_code() {
+    let file = broadcast("Deploy.s.sol/11155111/run-latest.json");
+
+    let server = chain_serving(SEPOLIA, HOOK).await;
+    let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+    assert_eq!(deployed_hook(&provider, &file).await.unwrap().address, HOOK);
+
+    let server = chain_serving(SEPOLIA, Address::ZERO).await;
+    let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+    let err = deployed_hook(&provider, &file).await.unwrap_err();
+    assert!(
+        err.to_string().contains("has no code on chain 11155111"),
+        "{err}"
+    );
+}
@@ -0,0 +1,201 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::{path::PathBuf, time::Duration};
+
+use alloy::primitives::{Bytes, B256, U256};
+use app::{
+    batch::{prove_entry, BatchEntry, Proof},
+    cache::ProofCache,
+    journal::{compliance_image_id, ComplianceJournal},
+};
+use boundless_market::request_builder::RequestParams;
+use common::{MockMarket, Step};
+
+const DECISION_AT: u64 = 1_700_000_000;
+
+fn entry() -> BatchEntry {
+    BatchEntry {
+        product_id: B256::with_last_byte(1),
+        ..common::entry(1)
+    }
+}
+
+/// An empty cache directory of its own for each test.
+fn cache_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("proof-cache-{name}"));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+async fn prove(market: &MockMarket, entry: &BatchEntry, cache: &ProofCache) -> Proof {
+    let request = RequestParams::new().with_stdin(entry.stdin(DECISION_AT));
+    prove_entry(
+        std::slice::from_ref(market),
+        entry,
+        DECISION_AT,
+        request,
+        false,
+        Some(cache),
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test(start_paused = true)]
+async fn reuses_cached_proof_from_current_image() {
+    let cache = ProofCache::new(cache_dir("reuse"), false);
+    let market = MockMarket::new([Step::Fulfill(Duration::from_secs(1))]);
+
+    let first = BatchEntry {
+        decision_uuid: B256::repeat_byte(1),
+        ..entry()
+    };
+    let proven = prove(&market, &first, &cache).await;
+    // A rerun without a decision UUID of its own takes the cached one, so the UUID it reports is
+    // the one the cached journal settles, which the hook refuses if it was settled already.
+    let mut rerun = entry();
+    let reported = cache
+        .assign_decision_uuid(&mut rerun, compliance_image_id())
+        .unwrap();
+    let cached = prove(&market, &rerun, &cache).await;
+
+    assert_eq!(market.submissions().len(), 1);
+    assert_eq!(cached.request_id, proven.request_id);
+    assert_eq!(cached.journal, proven.journal);
+    assert_eq!(cached.seal, proven.seal);
+    let settled = ComplianceJournal::try_from(cached.journal.as_ref()).unwrap();
+    assert_eq!(reported, first.decision_uuid);
+    assert_eq!(settled.decisionUuid, reported);
+    assert_eq!(rerun.decision_uuid, reported);
+
+    // The hook only settles the amount a proof commits, so another amount misses.
+    let resized = BatchEntry {
+        amount: U256::from(5),
+        ..entry()
+    };
+    assert_ne!(ProofCache::key(&resized), ProofCache::key(&first));
+}
+
+#[tokio::test(start_paused = true)]
+async fn another_decision_uuid_forces_a_reprove() {
+    let cache = ProofCache::new(cache_dir("another-uuid"), false);
+    let market = MockMarket::new([
+        Step::Fulfill(Duration::from_secs(1)),
+        Step::Fulfill(Duration::from_secs(1)),
+    ]);
+    let first = BatchEntry {
+        decision_uuid: B256::repeat_byte(1),
+        ..entry()
+    };
+    prove(&market, &first, &cache).await;
+
+    // A retry that names its own decision UUID must settle that UUID, not the cached one.
+    let mut retry = BatchEntry {
+        decision_uuid: B256::repeat_byte(2),
+        ..entry()
+    };
+    let reported = cache
+        .assign_decision_uuid(&mut retry, compliance_image_id())
+        .unwrap();
+    let proof = prove(&market, &retry, &cache).await;
+
+    assert_eq!(market.submissions().len(), 2);
+    let settled = ComplianceJournal::try_from(proof.journal.as_ref()).unwrap();
+    assert_eq!(reported, B256::repeat_byte(2));
+    assert_eq!(settled.decisionUuid, reported);
+}
+
+#[test]
+fn draws_a_decision_uuid_without_a_cached_proof() {
+    let cache = ProofCache::new(cache_dir("draw-uuid"), false);
+    let mut fresh = entry();
+
+    let drawn = cache
+        .assign_decision_uuid(&mut fresh, compliance_image_id())
+        .unwrap();
+
+    assert!(!drawn.is_zero());
+    assert_eq!(fresh.decision_uuid, drawn);
+}
+
+#[tokio::test(start_paused = true)]
+async fn proof_past_max_age_forces_a_reprove() {
+    let dir = cache_dir("max-age");
+    let market = MockMarket::new([
+        Step::Fulfill(Duration::from_secs(1)),
+        Step::Fulfill(Duration::from_secs(1)),
+    ]);
+    // Proofs in these tests are decided at DECISION_AT, long ago.
+    let proven = prove(&market, &entry(), &ProofCache::new(&dir, false)).await;
+
+    let lenient = ProofCache::new(&dir, false).with_max_age(Some(u64::MAX));
+    let cached = prove(&market, &entry(), &lenient).await;
+    assert_eq!(market.submissions().len(), 1);
+    assert_eq!(cached.request_id, proven.request_id);
+
+    let strict = ProofCache::new(&dir, false).with_max_age(Some(60));
+    assert!(strict
+        .get(&entry(), compliance_image_id())
+        .unwrap()
+        .is_none());
+    let reproven = prove(&market, &entry(), &strict).await;
+    assert_eq!(market.submissions().len(), 2);
+    assert_ne!(reproven.request_id, proven.request_id);
+}
+
+#[tokio::test(start_paused = true)]
+async fn image_id_change_forces_a_reprove() {
+    let cache = ProofCache::new(cache_dir("image-change"), true);
+    let stale = Proof {
+        request_id: U256::from(99),
+        journal: Bytes::from_static(b"journal of the old guest"),
+        seal: Bytes::from_static(b"seal of the old guest"),
+    };
+    cache
+        .insert(&entry(), B256::repeat_byte(0xaa), &stale)
+        .unwrap();
+    let market = MockMarket::new([Step::Fulfill(Duration::from_secs(1))]);
+
+    let proof = prove(&market, &entry(), &cache).await;
+
+    assert_eq!(market.submissions().len(), 1);
+    assert_ne!(proof.request_id, stale.request_id);
+    let cached = cache.get(&entry(), compliance_image_id()).unwrap().unwrap();
+    assert_eq!(cached.request_id, proof.request_id);
+}
+
+#[test]
+fn refuses_proof_from_another_image_without_reprove() {
+    let dir = cache_dir("refuse");
+    let stale = Proof {
+        request_id: U256::from(99),
+        journal: Bytes::new(),
+        seal: Bytes::new(),
+    };
+    ProofCache::new(&dir, true)
+        .insert(&entry(), B256::repeat_byte(0xaa), &stale)
+        .unwrap();
+
+    let err = ProofCache::new(&dir, false)
+        .get(&entry(), compliance_image_id())
+        .unwrap_err();
+
+    assert!(
+        format!("{err:#}").contains("--reprove-on-image-change"),
+        "{err:#}"
+    );
+}
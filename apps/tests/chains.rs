@@ -0,0 +1,175 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloy::{
+    primitives::{address, Address},
+    providers::ProviderBuilder,
+};
+use app::{chains::ChainsConfig, cli::Args, ens::NameOrAddress};
+use clap::Parser;
+use serde_json::{json, Value};
+use wiremock::{matchers::method, Mock, MockServer, Request, ResponseTemplate};
+
+const HOOK: Address = address!("0x00000000000000000000000000000000000000a1");
+const VERIFIER: Address = address!("0x00000000000000000000000000000000000000a2");
+
+const CONFIG: &str = r#"
+[chains.sepolia]
+rpc_url = "https://sepolia.example.com"
+chain_id = 11155111
+hook_address = "0x00000000000000000000000000000000000000a1"
+boundless_market_address = "0x00000000000000000000000000000000000000b1"
+set_verifier_address = "0x00000000000000000000000000000000000000b2"
+explorer_url = "https://sepolia.etherscan.io"
+
+[chains.base]
+rpc_url = "https://base.example.com"
+chain_id = 8453
+hook_address = "0x00000000000000000000000000000000000000c1"
+"#;
+
+/// A JSON-RPC endpoint serving `chain_id`, where only [HOOK] has code.
+async fn chain_serving(chain_id: u64) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(move |request: &Request| {
+            let request: Value = serde_json::from_slice(&request.body).unwrap();
+            let result = match request["method"].as_str().unwrap() {
+                "eth_chainId" => json!(format!("{chain_id:#x}")),
+                "eth_getCode" => {
+                    let address: Address = request["params"][0].as_str().unwrap().parse().unwrap();
+                    json!(if address == HOOK { "0x6080" } else { "0x" })
+                }
+                other => panic!("unexpected RPC method {other}"),
+            };
+            ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": result,
+            }))
+        })
+        .mount(&server)
+        .await;
+    server
+}
+
+fn chain_config(server: &MockServer, chain_id: u64) -> ChainsConfig {
+    format!(
+        "[chains.local]\nrpc_url = \"{}\"\nchain_id = {chain_id}\n\
+         hook_address = \"{HOOK}\"\nverifier_address = \"{VERIFIER}\"\n",
+        server.uri()
+    )
+    .parse()
+    .unwrap()
+}
+
+#[test]
+fn parses_chains() {
+    let config: ChainsConfig = CONFIG.parse().unwrap();
+
+    let sepolia = config.chain("sepolia").unwrap();
+    assert_eq!(sepolia.chain_id, 11155111);
+    assert_eq!(sepolia.hook_address, HOOK);
+    let deployment = sepolia.deployment().unwrap().unwrap();
+    assert_eq!(
+        deployment.boundless_market_address,
+        address!("0x00000000000000000000000000000000000000b1")
+    );
+    assert!(config
+        .chain("base")
+        .unwrap()
+        .deployment()
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn rejects_unknown_chains_and_partial_deployments() {
+    let config: ChainsConfig = CONFIG.parse().unwrap();
+    let err = config.chain("mainnet").unwrap_err();
+    assert!(err.to_string().contains("it has base, sepolia"), "{err}");
+
+    let err = CONFIG
+        .replace("set_verifier_address", "# set_verifier_address")
+        .parse::<ChainsConfig>()
+        .unwrap_err();
+    assert!(err.to_string().contains("sets only one of"), "{err}");
+}
+
+#[test]
+fn flags_take_precedence_over_the_chain() {
+    let mut args = Args::try_parse_from([
+        "app",
+        "--chains-config=chains.toml",
+        "--chain=sepolia",
+        "--rpc-url=http://localhost:8545",
+        "--private-key=0x0000000000000000000000000000000000000000000000000000000000000001",
+        "--input-file=trades.json",
+    ])
+    .unwrap();
+    let config: ChainsConfig = CONFIG.parse().unwrap();
+
+    config
+        .chain("sepolia")
+        .unwrap()
+        .apply_to(&mut args)
+        .unwrap();
+
+    assert_eq!(args.rpc_url.unwrap().as_str(), "http://localhost:8545/");
+    assert_eq!(
+        args.compliance_hook_address,
+        Some(NameOrAddress::Address(HOOK))
+    );
+    assert!(args.deployment.is_some());
+    assert_eq!(
+        args.explorer_url.unwrap().as_str(),
+        "https://sepolia.etherscan.io/"
+    );
+}
+
+#[tokio::test]
+async fn refuses_an_endpoint_serving_another_chain() {
+    let server = chain_serving(1).await;
+    let config = chain_config(&server, 8453);
+    let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+
+    let err = config
+        .chain("local")
+        .unwrap()
+        .ensure_chain_id(&provider)
+        .await
+        .unwrap_err();
+
+    assert!(
+        err.to_string()
+            .contains("serves chain 1, but the chains config expects 8453"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn lists_each_chain_with_its_deployment() {
+    let server = chain_serving(8453).await;
+    let listed = chain_config(&server, 8453).list().await;
+    assert_eq!(
+        listed,
+        format!("local\tchain 8453\thook {HOOK}\thook deployed, verifier has no code\n")
+    );
+
+    let listed = chain_config(&server, 1).list().await;
+    assert!(
+        listed.contains("error: the RPC endpoint serves chain 8453"),
+        "{listed}"
+    );
+}
@@ -0,0 +1,166 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloy::primitives::address;
+use app::cli::{env_example, examples, ApiHeader, Args, MarketDeployment, EXAMPLES};
+use clap::{builder::NonEmptyStringValueParser, CommandFactory, Parser};
+
+#[test]
+fn args_are_well_formed() {
+    Args::command().debug_assert();
+}
+
+// NOTE: Snapshots live in tests/cmd. After an intentional change to the CLI, regenerate them with
+// `TRYCMD=overwrite cargo test -p app --test cli` and review the diff.
+#[test]
+fn cli_snapshots() {
+    trycmd::TestCases::new()
+        .register_bin("app", trycmd::cargo::cargo_bin!("app"))
+        .register_bin(
+            "decode-journal",
+            trycmd::cargo::cargo_bin!("decode-journal"),
+        )
+        .register_bin("encode-input", trycmd::cargo::cargo_bin!("encode-input"))
+        .register_bin("image-id", trycmd::cargo::cargo_bin!("image-id"))
+        .register_bin("list-markets", trycmd::cargo::cargo_bin!("list-markets"))
+        .case("tests/cmd/*.toml");
+}
+
+#[test]
+fn examples_satisfy_the_argument_rules() {
+    // Placeholders are not valid values, so every value is taken as a string, which leaves the
+    // required, conflicting and requiring arguments to check.
+    let cmd = Args::command().mut_args(|arg| {
+        if arg.get_action().takes_values() {
+            arg.value_parser(NonEmptyStringValueParser::new())
+        } else {
+            arg
+        }
+    });
+    let printed = examples(&cmd);
+
+    let invocations: Vec<_> = printed.split("\n\n").collect();
+    assert_eq!(invocations.len(), EXAMPLES.len(), "{printed}");
+    for invocation in invocations {
+        let argv: Vec<_> = invocation
+            .lines()
+            .skip(1)
+            .flat_map(str::split_whitespace)
+            .filter(|word| *word != "\\")
+            .collect();
+        if let Err(err) = cmd.clone().try_get_matches_from(&argv) {
+            panic!("{invocation}\n{err}");
+        }
+    }
+}
+
+#[test]
+fn env_example_lists_every_environment_variable() {
+    let cmd = Args::command();
+    let template = env_example(&cmd);
+
+    for arg in cmd.get_arguments() {
+        let Some(env) = arg.get_env() else {
+            continue;
+        };
+        let prefix = format!("# {}=", env.to_string_lossy());
+        let count = template
+            .lines()
+            .filter(|line| line.starts_with(&prefix))
+            .count();
+        assert_eq!(count, 1, "{prefix}\n{template}");
+    }
+    for line in [
+        "# AMOUNT=<AMOUNT>",
+        "# REQUIRED_TIER=1",
+        "# KYC_PASSED=true",
+    ] {
+        assert!(template.lines().any(|l| l == line), "{line}\n{template}");
+    }
+}
+
+#[test]
+fn parses_redundant_market_deployments() {
+    let args = Args::try_parse_from([
+        "app",
+        "--rpc-url=http://localhost:8545",
+        "--private-key=0x0000000000000000000000000000000000000000000000000000000000000001",
+        "--compliance-hook-address=0x0000000000000000000000000000000000000001",
+        "--input-file=trades.json",
+        "--redundant-markets",
+        "0x00000000000000000000000000000000000000aa,0x00000000000000000000000000000000000000bb",
+        "0x00000000000000000000000000000000000000cc,0x00000000000000000000000000000000000000dd,https://order-stream.example.com",
+    ])
+    .unwrap();
+
+    let deployments: Vec<_> = args
+        .redundant_markets
+        .iter()
+        .map(|market| market.deployment())
+        .collect();
+    assert_eq!(deployments.len(), 2);
+    assert_eq!(
+        deployments[0].boundless_market_address,
+        address!("0x00000000000000000000000000000000000000aa")
+    );
+    assert_eq!(deployments[0].order_stream_url, None);
+    assert_eq!(
+        deployments[1].set_verifier_address,
+        address!("0x00000000000000000000000000000000000000dd")
+    );
+    assert_eq!(
+        deployments[1].order_stream_url.as_deref(),
+        Some("https://order-stream.example.com/")
+    );
+}
+
+#[test]
+fn rejects_malformed_redundant_market() {
+    let err = "0x00000000000000000000000000000000000000aa"
+        .parse::<MarketDeployment>()
+        .unwrap_err();
+
+    assert!(err
+        .to_string()
+        .contains("<BOUNDLESS_MARKET_ADDRESS>,<SET_VERIFIER_ADDRESS>"));
+}
+
+#[test]
+fn parses_api_headers() {
+    let args = Args::try_parse_from([
+        "app",
+        "--rpc-url=http://localhost:8545",
+        "--private-key=0x0000000000000000000000000000000000000000000000000000000000000001",
+        "--compliance-hook-address=0x0000000000000000000000000000000000000001",
+        "--input-file=trades.json",
+        "--input-from-api=https://kyc.example.com/v1/status",
+        "--api-header=X-Api-Key: secret",
+        "--api-header=X-Tenant:acme",
+    ])
+    .unwrap();
+
+    let headers: Vec<_> = args
+        .api_header
+        .iter()
+        .map(|header| (header.name.as_str(), header.value.to_str().unwrap()))
+        .collect();
+    assert_eq!(headers, [("x-api-key", "secret"), ("x-tenant", "acme")]);
+}
+
+#[test]
+fn rejects_header_without_separator() {
+    let err = "X-Api-Key secret".parse::<ApiHeader>().unwrap_err();
+
+    assert!(err.to_string().contains("<NAME>: <VALUE>"));
+}
@@ -0,0 +1,344 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![allow(dead_code)]
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use alloy::primitives::{Address, Bytes, B256, U256};
+use anyhow::{anyhow, bail, Result};
+use app::{
+    batch::BatchEntry,
+    http::RetryPolicy,
+    settle::{Settlement, Settler},
+    MarketClient,
+};
+use boundless_market::{
+    contracts::{boundless_market::MarketError, Fulfillment, FulfillmentData},
+    request_builder::RequestParams,
+};
+
+/// Retry policy with short timeouts so HTTP failure tests finish quickly.
+pub fn fast_retries() -> RetryPolicy {
+    RetryPolicy {
+        timeout: Duration::from_millis(100),
+        retries: 2,
+        backoff: Duration::from_millis(10),
+    }
+}
+
+/// A passing trade of 100 by `user`, for tests to adjust with struct update syntax.
+pub fn entry(user: u8) -> BatchEntry {
+    BatchEntry {
+        user: Address::with_last_byte(user),
+        product_id: B256::with_last_byte(2),
+        amount: U256::from(100),
+        kyc_passed: true,
+        kyc_tier: None,
+        required_tier: 1,
+        aml_passed: true,
+        claims: U256::ZERO,
+        required_claims: U256::ZERO,
+        required_attestations: 0,
+        product_policy_version: 0,
+        quote: None,
+        risk_score: 0,
+        review_band: None,
+        regime_id: 0,
+        settlement_token: Address::ZERO,
+        case_id: B256::ZERO,
+        min_trade: U256::ZERO,
+        max_trade: None,
+        ttl_secs: 0,
+        attester: None,
+        attestation_signature: None,
+        governance: Vec::new(),
+        override_signature: None,
+        compact_journal: false,
+        sha256_commitments: false,
+        decision_uuid: B256::ZERO,
+        credential_hash: B256::ZERO,
+        settle_delay_secs: 0,
+        aml_sources: U256::ZERO,
+        required_aml_sources: U256::ZERO,
+        block_hash: B256::ZERO,
+        kyc_provider: 0,
+        allowed_kyc_providers: Vec::new(),
+        consent_given: false,
+        consent_at: 0,
+        consent_required: false,
+    }
+}
+
+/// How the mock market responds to the next submitted request.
+#[derive(Clone, Debug)]
+pub enum Step {
+    /// Accept the request and fulfill it after the given delay.
+    Fulfill(Duration),
+    /// Accept the request and report it as expired after the given delay.
+    Expire(Duration),
+    /// Reject the submission with a transport error.
+    SubmitError(&'static str),
+    /// Accept the request, then fail while waiting with a transport error.
+    WaitError(Duration, &'static str),
+    /// Accept the request, then panic while waiting.
+    Panic(&'static str),
+}
+
+/// Picks the [Step] for a request from its stdin.
+type Picker = Box<dyn Fn(&[u8]) -> Step + Send + Sync>;
+
+/// How a request reached the mock market.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Onchain,
+    Offchain,
+}
+
+/// A request accepted by the mock market.
+#[derive(Clone, Debug)]
+pub struct Submission {
+    pub request_id: U256,
+    pub channel: Channel,
+    pub stdin: Vec<u8>,
+}
+
+#[derive(Default)]
+struct State {
+    script: VecDeque<Step>,
+    submissions: Vec<Submission>,
+    pending: HashMap<U256, (Step, Vec<u8>)>,
+}
+
+/// Counts calls in flight and remembers the highest count seen.
+#[derive(Default)]
+pub struct Gauge {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl Gauge {
+    fn enter(&self) {
+        let current = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(current, Ordering::SeqCst);
+    }
+
+    fn exit(&self) {
+        self.current.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+}
+
+/// A [MarketClient] that replays a fixed script of responses.
+///
+/// Each submitted request consumes the next [Step]. Fulfilled requests carry their stdin as the
+/// seal so tests can check that every caller received its own fulfillment.
+/// Requests keep the id they carry, as on the real market, or are numbered in submission order.
+#[derive(Default)]
+pub struct MockMarket {
+    state: Mutex<State>,
+    pick: Option<Picker>,
+    prover: Option<Address>,
+    /// Requests being waited on.
+    pub in_flight: Gauge,
+}
+
+impl MockMarket {
+    pub fn new(script: impl IntoIterator<Item = Step>) -> Self {
+        Self {
+            state: Mutex::new(State {
+                script: script.into_iter().collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Respond to each request with the step `pick` chooses from its stdin instead of a script,
+    /// so concurrent callers get a deterministic response whatever order they submit in.
+    pub fn picking(pick: impl Fn(&[u8]) -> Step + Send + Sync + 'static) -> Self {
+        Self {
+            pick: Some(Box::new(pick)),
+            ..Default::default()
+        }
+    }
+
+    /// Report `prover` as the prover of every request, instead of none.
+    pub fn with_prover(mut self, prover: Address) -> Self {
+        self.prover = Some(prover);
+        self
+    }
+
+    /// Requests accepted so far, in submission order.
+    pub fn submissions(&self) -> Vec<Submission> {
+        self.state.lock().unwrap().submissions.clone()
+    }
+
+    fn submit(&self, request: RequestParams, channel: Channel) -> Result<(U256, u64)> {
+        let stdin = request.env.map(|env| env.stdin).unwrap_or_default();
+        let mut state = self.state.lock().unwrap();
+        let step = match &self.pick {
+            Some(pick) => pick(&stdin),
+            None => state
+                .script
+                .pop_front()
+                .ok_or_else(|| anyhow!("mock market script exhausted"))?,
+        };
+        if let Step::SubmitError(msg) = step {
+            return Err(anyhow!(msg));
+        }
+
+        let request_id = match request.request_id {
+            Some(request_id) => request_id.into(),
+            None => U256::from(state.submissions.len() + 1),
+        };
+        state.submissions.push(Submission {
+            request_id,
+            channel,
+            stdin: stdin.clone(),
+        });
+        state.pending.insert(request_id, (step, stdin));
+        Ok((request_id, u64::MAX))
+    }
+}
+
+impl MarketClient for MockMarket {
+    async fn submit_onchain(&self, request: RequestParams) -> Result<(U256, u64)> {
+        self.submit(request, Channel::Onchain)
+    }
+
+    async fn submit_offchain(&self, request: RequestParams) -> Result<(U256, u64)> {
+        self.submit(request, Channel::Offchain)
+    }
+
+    async fn wait_for_request_fulfillment(
+        &self,
+        request_id: U256,
+        _check_interval: Duration,
+        _expires_at: u64,
+    ) -> Result<Fulfillment> {
+        let (step, stdin) = self
+            .state
+            .lock()
+            .unwrap()
+            .pending
+            .remove(&request_id)
+            .ok_or_else(|| anyhow!(MarketError::RequestNotFound(request_id)))?;
+        if let Step::Panic(msg) = step {
+            panic!("{msg}");
+        }
+
+        self.in_flight.enter();
+        let result = match step {
+            Step::Fulfill(delay) => {
+                tokio::time::sleep(delay).await;
+                Ok(fulfillment(request_id, stdin))
+            }
+            Step::Expire(delay) => {
+                tokio::time::sleep(delay).await;
+                Err(MarketError::RequestHasExpired(request_id).into())
+            }
+            Step::WaitError(delay, msg) => {
+                tokio::time::sleep(delay).await;
+                Err(anyhow!(msg))
+            }
+            Step::SubmitError(_) | Step::Panic(_) => unreachable!("handled before waiting"),
+        };
+        self.in_flight.exit();
+        result
+    }
+
+    async fn fulfillment_prover(&self, _request_id: U256) -> Result<Option<Address>> {
+        Ok(self.prover)
+    }
+}
+
+/// A [Settler] that confirms every call after a fixed delay.
+#[derive(Default)]
+pub struct MockSettler {
+    delay: Duration,
+    reject: Option<Address>,
+    panic: Option<Address>,
+    settled: Mutex<Vec<(BatchEntry, Bytes, Bytes)>>,
+    /// Settlements in progress.
+    pub in_flight: Gauge,
+}
+
+impl MockSettler {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            ..Default::default()
+        }
+    }
+
+    /// Fail every settlement for `user`.
+    pub fn rejecting(mut self, user: Address) -> Self {
+        self.reject = Some(user);
+        self
+    }
+
+    /// Panic while settling for `user`.
+    pub fn panicking(mut self, user: Address) -> Self {
+        self.panic = Some(user);
+        self
+    }
+
+    /// Entries settled so far with their journals and seals, in completion order.
+    pub fn settled(&self) -> Vec<(BatchEntry, Bytes, Bytes)> {
+        self.settled.lock().unwrap().clone()
+    }
+}
+
+impl Settler for MockSettler {
+    async fn settle(&self, entry: &BatchEntry, journal: Bytes, seal: Bytes) -> Result<Settlement> {
+        self.in_flight.enter();
+        tokio::time::sleep(self.delay).await;
+        self.in_flight.exit();
+        if self.panic == Some(entry.user) {
+            panic!("settler bug");
+        }
+        if self.reject == Some(entry.user) {
+            bail!("execution reverted");
+        }
+        let mut settled = self.settled.lock().unwrap();
+        settled.push((entry.clone(), journal, seal));
+        Ok(Settlement::Confirmed {
+            tx_hash: B256::with_last_byte(settled.len() as u8),
+        })
+    }
+}
+
+fn fulfillment(request_id: U256, seal: Vec<u8>) -> Fulfillment {
+    let (fulfillment_data_type, fulfillment_data) =
+        FulfillmentData::None.fulfillment_type_and_data();
+    Fulfillment {
+        id: request_id,
+        requestDigest: B256::ZERO,
+        claimDigest: B256::ZERO,
+        fulfillmentDataType: fulfillment_data_type,
+        fulfillmentData: fulfillment_data.into(),
+        seal: Bytes::from(seal),
+    }
+}
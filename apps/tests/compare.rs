@@ -0,0 +1,97 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use alloy::primitives::B256;
+use app::{
+    batch::BatchEntry,
+    compare::{compare_guests, image_id, GuestComparison},
+    executor::ExecutorOptions,
+    journal::compliance_image_id,
+};
+use guests::COMPLIANCE_ELF;
+
+const DECISION_AT: u64 = 1_700_000_000;
+
+fn entry(aml_passed: bool) -> BatchEntry {
+    BatchEntry {
+        aml_passed,
+        ..common::entry(1)
+    }
+}
+
+#[test]
+fn identical_guests_agree() {
+    // A copy of the embedded guest, as `--compare-guest` reads it from disk.
+    let path = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("compare-guest.elf");
+    std::fs::write(&path, COMPLIANCE_ELF).unwrap();
+    let candidate = std::fs::read(&path).unwrap();
+    let entries = [entry(true), entry(false)];
+
+    let comparisons = compare_guests(
+        COMPLIANCE_ELF,
+        &candidate,
+        &entries,
+        DECISION_AT,
+        &ExecutorOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(comparisons.len(), 2);
+    for (comparison, entry) in comparisons.iter().zip(&entries) {
+        assert!(comparison.agrees(), "{:?}", comparison.differences());
+        assert!(comparison.differences().is_empty());
+        assert_eq!(comparison.current, entry.expected_journal(DECISION_AT));
+    }
+    assert!(comparisons[0].current.allowed && !comparisons[1].current.allowed);
+}
+
+#[test]
+fn image_ids_do_not_count_as_a_disagreement() {
+    assert_eq!(image_id(COMPLIANCE_ELF).unwrap(), compliance_image_id());
+    let current = entry(true).expected_journal(DECISION_AT);
+    let mut candidate = current.clone();
+    candidate.imageId = B256::repeat_byte(0x11);
+
+    let comparison = GuestComparison { current, candidate };
+
+    assert!(comparison.agrees());
+    assert!(comparison.differences().is_empty());
+}
+
+#[test]
+fn reports_the_fields_the_decisions_differ_in() {
+    let comparison = GuestComparison {
+        current: entry(true).expected_journal(DECISION_AT),
+        candidate: entry(false).expected_journal(DECISION_AT),
+    };
+
+    assert!(!comparison.agrees());
+    let differences = comparison.differences();
+    assert!(
+        differences.iter().any(|line| line.starts_with("- ")),
+        "{differences:?}"
+    );
+    assert!(
+        differences.iter().any(|line| line.starts_with("+ ")),
+        "{differences:?}"
+    );
+    assert!(
+        differences
+            .iter()
+            .all(|line| !line.contains(&compliance_image_id().to_string())),
+        "{differences:?}"
+    );
+}
@@ -0,0 +1,390 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::io::Write;
+
+use alloy::primitives::{address, b256, Address, B256, U256};
+use app::{
+    attestation::{fetch_compliance, AttestationSource},
+    batch::BatchEntry,
+    credential::{
+        credential_hash, status_list_bit, CredentialLocation, CredentialSource, DidResolver,
+    },
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use common::fast_retries;
+use flate2::{write::GzEncoder, Compression};
+use k256::{
+    ecdsa::{signature::Signer, Signature, SigningKey},
+    elliptic_curve::sec1::ToEncodedPoint,
+};
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+/// Subject of the fixture credential.
+const USER: Address = address!("0x0000000000000000000000000000000000001234");
+
+/// `did:key` of the key in `tests/fixtures/credentials/issuer.key`, which issued the fixtures.
+const ISSUER: &str = "did:key:zQ3shmpfjHE95wAnyHX49hkuB3PFbgBYLtwVxiSQFtqDDWcLF";
+
+/// `keccak256` of `tests/fixtures/credentials/kyc.jwt`.
+const KYC_CREDENTIAL_HASH: B256 =
+    b256!("0xefacb2d9243d4c3161e534e90d6864aaa7e7b4ed8c7f260c421a5dd6ef7bdec7");
+
+/// A time within the validity of the fixture credential.
+const NOW: u64 = 1_700_000_000;
+
+fn fixture(name: &str) -> String {
+    format!("tests/fixtures/credentials/{name}")
+}
+
+fn issuer_key() -> SigningKey {
+    let key = std::fs::read_to_string(fixture("issuer.key")).unwrap();
+    let key = alloy::primitives::hex::decode(key.trim()).unwrap();
+    SigningKey::from_slice(&key).unwrap()
+}
+
+fn file_source(name: &str) -> CredentialSource {
+    CredentialSource::new(CredentialLocation::File(fixture(name))).checked_at(NOW)
+}
+
+/// A JWT-VC signed by the fixture issuer's key, whoever `claims` name as the issuer.
+fn mint(kid: &str, claims: &Value) -> String {
+    let header = json!({ "alg": "ES256K", "typ": "JWT", "kid": kid });
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string())
+    );
+    let signature: Signature = issuer_key().sign(signing_input.as_bytes());
+    format!(
+        "{signing_input}.{}",
+        URL_SAFE_NO_PAD.encode(signature.to_bytes())
+    )
+}
+
+fn kyc_claims(issuer: &str, user: Address, status: Value) -> Value {
+    let subject = format!("did:pkh:eip155:1:{user}");
+    json!({
+        "iss": issuer,
+        "sub": subject,
+        "nbf": NOW,
+        "exp": NOW + 3600,
+        "vc": {
+            "@context": ["https://www.w3.org/2018/credentials/v1"],
+            "type": ["VerifiableCredential", "KycCredential"],
+            "credentialSubject": { "id": subject, "kycTier": 3, "amlPassed": true },
+            "credentialStatus": status,
+        },
+    })
+}
+
+/// The fixture issuer as `did:web` of `server`, with its key as a JWK under `#key-1`.
+async fn did_web_issuer(server: &MockServer) -> String {
+    let host = server.address().to_string().replace(':', "%3A");
+    let did = format!("did:web:{host}");
+    let point = issuer_key().verifying_key().to_encoded_point(false);
+    Mock::given(method("GET"))
+        .and(path("/.well-known/did.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "@context": ["https://www.w3.org/ns/did/v1"],
+            "id": did,
+            "verificationMethod": [{
+                "id": "#key-1",
+                "type": "JsonWebKey2020",
+                "controller": did,
+                "publicKeyJwk": {
+                    "kty": "EC",
+                    "crv": "secp256k1",
+                    "x": URL_SAFE_NO_PAD.encode(point.x().unwrap()),
+                    "y": URL_SAFE_NO_PAD.encode(point.y().unwrap()),
+                },
+            }],
+            "assertionMethod": ["#key-1"],
+        })))
+        .mount(server)
+        .await;
+    did
+}
+
+fn web_source(server: &MockServer) -> CredentialSource {
+    let location = format!("{}/credentials/{{user}}", server.uri());
+    CredentialSource::new(location.parse().unwrap())
+        .with_resolver(
+            DidResolver::default()
+                .over_http()
+                .with_policy(fast_retries()),
+        )
+        .with_policy(fast_retries())
+        .checked_at(NOW)
+}
+
+async fn serve(server: &MockServer, at: String, body: String) {
+    Mock::given(method("GET"))
+        .and(path(at))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .mount(server)
+        .await;
+}
+
+/// A status list with the bits at `set` set, GZIP-compressed and multibase base64url-encoded.
+fn encoded_list(set: &[usize]) -> String {
+    let mut list = vec![0u8; 16];
+    for index in set {
+        list[index / 8] |= 0x80 >> (index % 8);
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&list).unwrap();
+    format!("u{}", URL_SAFE_NO_PAD.encode(encoder.finish().unwrap()))
+}
+
+#[tokio::test]
+async fn reads_the_fixture_credential() {
+    let status = file_source("kyc.jwt").fetch(USER).await.unwrap();
+
+    assert_eq!(status.kyc_tier(), 2);
+    assert!(status.aml_passed);
+    assert_eq!(status.claims, U256::from(5));
+    assert_eq!(status.credential_hash, KYC_CREDENTIAL_HASH);
+    let credential = std::fs::read_to_string(fixture("kyc.jwt")).unwrap();
+    assert_eq!(credential_hash(&credential), KYC_CREDENTIAL_HASH);
+}
+
+#[tokio::test]
+async fn commits_the_credential_hash() {
+    let mut entries: Vec<BatchEntry> = serde_json::from_value(json!([{
+        "user": USER,
+        "productId": B256::with_last_byte(1),
+        "amount": "0x64",
+        "requiredTier": 2,
+        "amlPassed": false,
+        "consentRequired": false,
+    }]))
+    .unwrap();
+
+    fetch_compliance(&file_source("kyc.jwt"), &mut entries)
+        .await
+        .unwrap();
+
+    assert_eq!(entries[0].credential_hash, KYC_CREDENTIAL_HASH);
+    let journal = entries[0].expected_journal(NOW);
+    assert!(journal.allowed);
+    assert_eq!(journal.credentialHash, KYC_CREDENTIAL_HASH);
+}
+
+#[tokio::test]
+async fn rejects_credential_of_another_user() {
+    let other = Address::with_last_byte(0xaa);
+
+    let err = file_source("kyc.jwt").fetch(other).await.unwrap_err();
+
+    assert!(
+        format!("{err:#}").contains(&format!("credential is of {USER}, not of {other}")),
+        "{err:#}"
+    );
+}
+
+#[tokio::test]
+async fn rejects_credential_outside_its_validity() {
+    let location = CredentialLocation::File(fixture("kyc.jwt"));
+
+    let expired = CredentialSource::new(location.clone())
+        .checked_at(4_102_444_800)
+        .fetch(USER)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{expired:#}").contains("credential expired at 2100-01-01T00:00:00Z"),
+        "{expired:#}"
+    );
+
+    let early = CredentialSource::new(location)
+        .checked_at(NOW - 1)
+        .fetch(USER)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{early:#}").contains("not valid until 2023-11-14T22:13:20Z"),
+        "{early:#}"
+    );
+}
+
+#[tokio::test]
+async fn rejects_credential_signed_by_another_key() {
+    let credential = mint(
+        &format!("{ISSUER}#{}", &ISSUER["did:key:".len()..]),
+        &kyc_claims(ISSUER, USER, Value::Null),
+    );
+    file_source("kyc.jwt").verify(&credential).await.unwrap();
+
+    // The same claims, signed by the fixture key but attributed to another did:key.
+    let impostor = "did:key:zQ3shu8spUoybUxeByGSck2fbiHiGRRvmw1c4yGmThw1jqHGr";
+    let forged = mint(
+        &format!("{impostor}#{}", &impostor["did:key:".len()..]),
+        &kyc_claims(impostor, USER, Value::Null),
+    );
+    let err = file_source("kyc.jwt").verify(&forged).await.unwrap_err();
+    assert!(
+        format!("{err:#}").contains("signature does not verify"),
+        "{err:#}"
+    );
+}
+
+#[tokio::test]
+async fn lists_supported_proofs_for_unsupported_ones() {
+    let err = file_source("kyc_ldp.json").fetch(USER).await.unwrap_err();
+    assert!(
+        format!("{err:#}").contains(
+            "unsupported proof type Ed25519Signature2020; supported: JWT-VC signed with ES256K"
+        ),
+        "{err:#}"
+    );
+
+    let header = URL_SAFE_NO_PAD.encode(json!({ "alg": "EdDSA" }).to_string());
+    let credential = format!("{header}.e30.c2ln");
+    let err = file_source("kyc.jwt")
+        .verify(&credential)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{err:#}")
+            .contains("unsupported JWS algorithm EdDSA; supported: JWT-VC signed with ES256K"),
+        "{err:#}"
+    );
+
+    let ion = "did:ion:EiClkZMDxPKqC9c-umQfTkR8vvZ9JPhl_xLDI9Nfk38w5w";
+    let credential = mint(&format!("{ion}#key-1"), &kyc_claims(ion, USER, Value::Null));
+    let err = file_source("kyc.jwt")
+        .verify(&credential)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("unsupported DID method did:ion"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn maps_did_web_to_document_urls() {
+    let resolver = DidResolver::default();
+    for (did, url) in [
+        (
+            "did:web:example.com",
+            "https://example.com/.well-known/did.json",
+        ),
+        (
+            "did:web:example.com:issuers:kyc",
+            "https://example.com/issuers/kyc/did.json",
+        ),
+        (
+            "did:web:localhost%3A8443",
+            "https://localhost:8443/.well-known/did.json",
+        ),
+    ] {
+        assert_eq!(resolver.web_document_url(did).unwrap().as_str(), url);
+    }
+    assert!(resolver.web_document_url("did:key:z6Mk").is_err());
+}
+
+#[tokio::test]
+async fn resolves_did_web_issuers() {
+    let server = MockServer::start().await;
+    let issuer = did_web_issuer(&server).await;
+    let credential = mint(
+        &format!("{issuer}#key-1"),
+        &kyc_claims(&issuer, USER, Value::Null),
+    );
+    serve(&server, format!("/credentials/{USER}"), credential.clone()).await;
+
+    let status = web_source(&server).fetch(USER).await.unwrap();
+
+    assert_eq!(status.kyc_tier(), 3);
+    assert_eq!(status.credential_hash, credential_hash(&credential));
+
+    // A key the document does not list as an assertion method is refused.
+    let unlisted = mint(
+        &format!("{issuer}#key-2"),
+        &kyc_claims(&issuer, USER, Value::Null),
+    );
+    let err = web_source(&server).verify(&unlisted).await.unwrap_err();
+    assert!(
+        format!("{err:#}").contains("key-2 is not an assertion method"),
+        "{err:#}"
+    );
+}
+
+#[tokio::test]
+async fn checks_the_status_list() {
+    let server = MockServer::start().await;
+    let issuer = did_web_issuer(&server).await;
+    let status_list = mint(
+        "#key-1",
+        &json!({
+            "iss": issuer,
+            "vc": {
+                "type": ["VerifiableCredential", "BitstringStatusListCredential"],
+                "credentialSubject": {
+                    "type": "BitstringStatusList",
+                    "statusPurpose": "revocation",
+                    "encodedList": encoded_list(&[3]),
+                },
+            },
+        }),
+    );
+    serve(&server, "/status/1".into(), status_list).await;
+    let status = |index: usize| {
+        json!({
+            "id": format!("{}/status/1#{index}", server.uri()),
+            "type": "BitstringStatusListEntry",
+            "statusPurpose": "revocation",
+            "statusListIndex": index.to_string(),
+            "statusListCredential": format!("{}/status/1", server.uri()),
+        })
+    };
+
+    let current = mint("#key-1", &kyc_claims(&issuer, USER, status(2)));
+    web_source(&server).verify(&current).await.unwrap();
+
+    let revoked = mint("#key-1", &kyc_claims(&issuer, USER, status(3)));
+    let err = web_source(&server).verify(&revoked).await.unwrap_err();
+    assert!(
+        format!("{err:#}").contains("credential is revoked"),
+        "{err:#}"
+    );
+
+    let unknown = mint(
+        "#key-1",
+        &kyc_claims(&issuer, USER, json!({ "type": "CredentialStatusList2017" })),
+    );
+    let err = web_source(&server).verify(&unknown).await.unwrap_err();
+    assert!(
+        format!("{err:#}").contains("supported: StatusList2021Entry, BitstringStatusListEntry"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn reads_status_list_bits_from_the_left() {
+    let list = encoded_list(&[0, 9]);
+    let set = (0..16)
+        .filter(|&index| status_list_bit(&list, index).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(set, [0, 9]);
+    assert!(status_list_bit(&list, 128).is_err());
+}
@@ -0,0 +1,255 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decoders fed by untrusted bytes must return errors, never panic or over-allocate.
+//!
+//! Journals arrive from market fulfillments and fixture files, inputs from whoever requests a
+//! proof, and manifests from disk. The seeds are the golden vectors in `fixtures/golden`, with
+//! every single-bit flip checked exhaustively and arbitrary inputs covered by proptest.
+
+use std::path::{Path, PathBuf};
+
+use alloy::sol_types::SolValue;
+use app::{
+    fixtures::{Manifest, MAX_MANIFEST_LEN},
+    journal::{ComplianceInput, ComplianceJournal, InputError, JournalEncoding, JournalError},
+};
+use proptest::prelude::*;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden")
+}
+
+fn golden_journals() -> Vec<Vec<u8>> {
+    let manifest =
+        Manifest::parse(&std::fs::read(golden_dir().join("manifest.json")).unwrap()).unwrap();
+    manifest
+        .cases
+        .iter()
+        .map(|case| std::fs::read(golden_dir().join(format!("{}.journal.bin", case.name))).unwrap())
+        .collect()
+}
+
+fn golden_inputs() -> Vec<Vec<u8>> {
+    let manifest =
+        Manifest::parse(&std::fs::read(golden_dir().join("manifest.json")).unwrap()).unwrap();
+    manifest
+        .cases
+        .iter()
+        .map(|case| std::fs::read(golden_dir().join(format!("{}.input.bin", case.name))).unwrap())
+        .collect()
+}
+
+fn golden_compact_journal() -> Vec<u8> {
+    std::fs::read(golden_dir().join("allowed_compact.journal.bin")).unwrap()
+}
+
+fn golden_manifest() -> Vec<u8> {
+    std::fs::read(golden_dir().join("manifest.json")).unwrap()
+}
+
+/// Decode `bytes`, checking that anything accepted is the canonical encoding of the result.
+fn decode(bytes: &[u8]) -> Result<ComplianceJournal, JournalError> {
+    let journal = ComplianceJournal::try_from(bytes)?;
+    assert_eq!(
+        journal.encode(JournalEncoding::of(bytes)?),
+        bytes,
+        "accepted a non-canonical journal"
+    );
+    Ok(journal)
+}
+
+#[test]
+fn golden_journals_decode() {
+    for journal in golden_journals() {
+        decode(&journal).unwrap();
+    }
+}
+
+#[test]
+fn single_bit_flips_of_golden_journals_never_panic() {
+    for journal in golden_journals() {
+        for index in 0..journal.len() {
+            for bit in 0..8 {
+                let mut mutated = journal.clone();
+                mutated[index] ^= 1 << bit;
+                let _ = decode(&mutated);
+            }
+        }
+    }
+}
+
+#[test]
+fn rejects_non_canonical_bool() {
+    let mut journal = golden_journals()[0].clone();
+    // `allowed` is the third word.
+    journal[3 * 32 - 1] = 3;
+    assert!(matches!(decode(&journal), Err(JournalError::NonCanonical)));
+
+    let mut compact = golden_compact_journal();
+    // `allowed` follows the version byte, `user` and `productId`.
+    compact[1 + 20 + 32] = 3;
+    assert!(matches!(decode(&compact), Err(JournalError::NonCanonical)));
+}
+
+/// Strictly decode `bytes`, checking that anything accepted is the encoding of the result.
+fn decode_input(bytes: &[u8]) -> Result<ComplianceInput, InputError> {
+    let input = ComplianceInput::try_from(bytes)?;
+    assert_eq!(input.abi_encode(), bytes, "accepted a non-canonical input");
+    Ok(input)
+}
+
+#[test]
+fn golden_inputs_decode() {
+    for input in golden_inputs() {
+        decode_input(&input).unwrap();
+    }
+}
+
+#[test]
+fn rejects_non_canonical_inputs() {
+    let input = &golden_inputs()[0];
+    // Each case sets a bit a canonical encoding leaves clear, and is still accepted by the
+    // lenient `abi_decode`.
+    for (name, byte, value) in [
+        ("address padding of `user`", 0, 1),
+        ("high bits of `kycTier`", 2 * 32 + 30, 1),
+        ("`amlPassed` of 2", 5 * 32 - 1, 2),
+        ("high bits of `productPolicyVersion`", 7 * 32, 0x80),
+        ("address padding of `governance[0]`", 23 * 32 + 11, 1),
+        ("high byte of `governance[3]`", 26 * 32, 0x80),
+        ("high bits of `journalVersion`", 29 * 32, 1),
+        ("high bits of `now`", 30 * 32 + 23, 1),
+        ("high bits of `ttlSecs`", 31 * 32 + 23, 1),
+        ("high bits of `notBefore`", 34 * 32 + 23, 1),
+        ("high bits of `allowedKycProviders[0]`", 39 * 32 + 29, 1),
+        ("high byte of `allowedKycProviders[3]`", 42 * 32, 0x80),
+    ] {
+        let mut dirty = input.clone();
+        dirty[byte] |= value;
+        assert!(
+            ComplianceInput::abi_decode(&dirty).is_ok(),
+            "{name} is rejected leniently"
+        );
+        assert!(
+            matches!(decode_input(&dirty), Err(InputError::NonCanonical)),
+            "accepted {name}"
+        );
+    }
+}
+
+#[test]
+fn rejects_inputs_of_the_wrong_length() {
+    let input = &golden_inputs()[0];
+    for len in [0, 31, ComplianceInput::LEN - 1, ComplianceInput::LEN + 32] {
+        let mut resized = input.clone();
+        resized.resize(len, 0);
+        assert!(matches!(decode_input(&resized), Err(InputError::Length(l)) if l == len));
+    }
+}
+
+#[test]
+fn rejects_compact_journals_of_unknown_version() {
+    let mut compact = golden_compact_journal();
+    compact[0] = 2;
+    assert!(matches!(decode(&compact), Err(JournalError::Version(2))));
+}
+
+#[test]
+fn rejects_journals_of_the_wrong_length() {
+    let journal = &golden_journals()[0];
+    for len in [
+        0,
+        1,
+        31,
+        ComplianceJournal::COMPACT_LEN - 1,
+        ComplianceJournal::COMPACT_LEN + 1,
+        ComplianceJournal::LEN - 1,
+        ComplianceJournal::LEN + 1,
+    ] {
+        let mut resized = journal.clone();
+        resized.resize(len, 0);
+        assert!(matches!(decode(&resized), Err(JournalError::Length(l)) if l == len));
+    }
+}
+
+#[test]
+fn huge_inputs_are_rejected_before_decoding() {
+    // Every word reads as the largest possible offset or length; a decoder that trusted them would
+    // try to allocate far more than exists.
+    let huge = vec![0xff; 16 << 20];
+    assert!(matches!(decode(&huge), Err(JournalError::Length(len)) if len == huge.len()));
+    assert!(decode(&[0xff; ComplianceJournal::LEN]).is_err());
+    assert!(decode(&[0xff; ComplianceJournal::COMPACT_LEN]).is_err());
+}
+
+#[test]
+fn single_byte_mutations_of_golden_manifest_never_panic() {
+    let manifest = golden_manifest();
+    for index in 0..manifest.len() {
+        for byte in [b'"', b'{', b'0', 0xff] {
+            let mut mutated = manifest.clone();
+            mutated[index] = byte;
+            let _ = Manifest::parse(&mutated);
+        }
+    }
+}
+
+#[test]
+fn oversized_manifests_are_rejected() {
+    let mut manifest = golden_manifest();
+    manifest.resize(MAX_MANIFEST_LEN + 1, b' ');
+    let err = Manifest::parse(&manifest).unwrap_err();
+    assert!(err.to_string().contains("the limit is"), "{err}");
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_journal_bytes_never_panic(bytes in proptest::collection::vec(any::<u8>(), 0..1024)) {
+        let _ = decode(&bytes);
+    }
+
+    #[test]
+    fn arbitrary_journal_length_bytes_never_panic(
+        bytes in proptest::collection::vec(any::<u8>(), ComplianceJournal::LEN)
+    ) {
+        let _ = decode(&bytes);
+    }
+
+    #[test]
+    fn arbitrary_compact_journal_bytes_never_panic(
+        bytes in proptest::collection::vec(any::<u8>(), ComplianceJournal::COMPACT_LEN - 1)
+    ) {
+        let compact = [&[JournalEncoding::Compact as u8], bytes.as_slice()].concat();
+        let _ = decode(&compact);
+    }
+
+    #[test]
+    fn mutated_golden_journals_never_panic(
+        case in any::<prop::sample::Index>(),
+        edits in proptest::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+    ) {
+        let journals = golden_journals();
+        let mut journal = case.get(&journals).clone();
+        for (index, byte) in edits {
+            *index.get_mut(&mut journal) = byte;
+        }
+        let _ = decode(&journal);
+    }
+
+    #[test]
+    fn arbitrary_manifest_bytes_never_panic(bytes in proptest::collection::vec(any::<u8>(), 0..4096)) {
+        let _ = Manifest::parse(&bytes);
+    }
+}
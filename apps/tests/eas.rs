@@ -0,0 +1,151 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::{path::PathBuf, time::Duration};
+
+use alloy::{
+    primitives::{Address, Log, B256},
+    sol_types::{SolEvent, SolValue},
+};
+use app::{
+    batch::BatchEntry,
+    eas::{attested_uid, AttestationRecord, AttestationStore, DecisionAttestation, IEAS::Attested},
+};
+use common::entry;
+use sha2::{Digest, Sha256};
+
+const EAS: Address = Address::repeat_byte(0xea);
+const SCHEMA: B256 = B256::repeat_byte(0x5c);
+const DECISION_AT: u64 = 1_700_000_000;
+
+fn journal(entry: &BatchEntry) -> Vec<u8> {
+    entry
+        .expected_journal(DECISION_AT)
+        .encode(entry.journal_encoding())
+}
+
+fn attested(address: Address, uid: B256, schema: B256) -> Log {
+    let event = Attested {
+        recipient: Address::with_last_byte(1),
+        attester: Address::with_last_byte(2),
+        uid,
+        schemaUID: schema,
+    };
+    Log {
+        address,
+        data: event.encode_log_data(),
+    }
+}
+
+fn store(name: &str) -> AttestationStore {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("eas-{name}"));
+    let _ = std::fs::remove_dir_all(&dir);
+    AttestationStore::new(dir)
+}
+
+#[test]
+fn attestation_data_carries_the_proven_decision() {
+    for compact_journal in [false, true] {
+        let entry = BatchEntry {
+            compact_journal,
+            ..entry(1)
+        };
+        let journal = journal(&entry);
+        let attestation =
+            DecisionAttestation::new(&journal, Some(Duration::from_secs(3600))).unwrap();
+
+        assert_eq!(attestation.user, entry.user);
+        assert_eq!(attestation.productId, entry.product_id);
+        assert!(attestation.allowed);
+        assert_eq!(
+            attestation.journalDigest.as_slice(),
+            Sha256::digest(&journal).as_slice()
+        );
+        assert_eq!(attestation.validUntil, DECISION_AT + 3600);
+        assert_eq!(
+            DecisionAttestation::abi_decode(&attestation.abi_encode()).unwrap(),
+            attestation
+        );
+    }
+}
+
+#[test]
+fn attestations_without_validity_do_not_expire() {
+    let attestation = DecisionAttestation::new(&journal(&entry(1)), None).unwrap();
+    assert_eq!(attestation.validUntil, 0);
+}
+
+#[test]
+fn denied_decisions_are_attested_as_denied() {
+    let entry = BatchEntry {
+        aml_passed: false,
+        ..entry(1)
+    };
+    let attestation = DecisionAttestation::new(&journal(&entry), None).unwrap();
+    assert!(!attestation.allowed);
+}
+
+#[test]
+fn uid_is_taken_from_the_eas_event_of_the_schema() {
+    let uid = B256::repeat_byte(0x11);
+    let logs = [
+        attested(Address::repeat_byte(0xbb), B256::repeat_byte(0x22), SCHEMA),
+        attested(EAS, B256::repeat_byte(0x33), B256::repeat_byte(0x44)),
+        attested(EAS, uid, SCHEMA),
+    ];
+
+    assert_eq!(attested_uid(&logs, EAS, SCHEMA).unwrap(), uid);
+}
+
+#[test]
+fn missing_attestation_event_is_an_error() {
+    let logs = [attested(Address::repeat_byte(0xbb), B256::ZERO, SCHEMA)];
+
+    let err = attested_uid(&logs, EAS, SCHEMA).unwrap_err();
+    assert!(
+        err.to_string().contains("emitted no Attested event"),
+        "{err}"
+    );
+}
+
+#[test]
+fn store_records_attestations_and_their_revocation() {
+    let store = store("revocation");
+    let record = AttestationRecord {
+        uid: B256::repeat_byte(0x11),
+        eas: EAS,
+        schema: SCHEMA,
+        tx_hash: B256::repeat_byte(0x22),
+        user: Address::with_last_byte(1),
+        product_id: B256::with_last_byte(2),
+        allowed: true,
+        journal_digest: B256::repeat_byte(0x33),
+        valid_until: 0,
+        revoked_by: None,
+    };
+    assert_eq!(store.get(record.uid).unwrap(), None);
+
+    store.insert(&record).unwrap();
+    assert_eq!(store.get(record.uid).unwrap().as_ref(), Some(&record));
+
+    let revoked = AttestationRecord {
+        revoked_by: Some(B256::repeat_byte(0x44)),
+        ..record.clone()
+    };
+    store.insert(&revoked).unwrap();
+    assert_eq!(store.get(record.uid).unwrap(), Some(revoked));
+    assert_eq!(store.get(B256::repeat_byte(0x55)).unwrap(), None);
+}
@@ -0,0 +1,212 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, str::FromStr};
+
+use alloy::{
+    primitives::{address, b256, keccak256, Address, Bytes, B256},
+    providers::{Provider, ProviderBuilder},
+    sol_types::SolValue,
+};
+use app::ens::{namehash, reverse_name, EnsResolver, NameOrAddress, ENS_REGISTRY};
+use serde_json::{json, Value};
+use wiremock::{matchers::method, Mock, MockServer, Request, ResponseTemplate};
+
+const TRADER: Address = address!("0x00000000000000000000000000000000000000aa");
+const RESOLVER: Address = address!("0x00000000000000000000000000000000000000e5");
+
+fn selector(signature: &str) -> [u8; 4] {
+    keccak256(signature)[..4].try_into().unwrap()
+}
+
+/// ENS records served over JSON-RPC: the registry at [ENS_REGISTRY] points every known node at
+/// [RESOLVER], which answers `addr` and `name` from the maps.
+#[derive(Default)]
+struct Records {
+    addrs: HashMap<B256, Address>,
+    names: HashMap<B256, String>,
+}
+
+impl Records {
+    fn with_name(mut self, name: &str, address: Address) -> Self {
+        self.addrs.insert(namehash(name), address);
+        self
+    }
+
+    fn with_reverse(mut self, address: Address, name: &str) -> Self {
+        self.names
+            .insert(namehash(&reverse_name(address)), name.to_owned());
+        self
+    }
+
+    /// Return data of an `eth_call` of `input` on `to`.
+    fn call(&self, to: Address, input: &[u8]) -> Bytes {
+        let node = B256::from_slice(&input[4..36]);
+        let known = self.addrs.contains_key(&node) || self.names.contains_key(&node);
+        let function: [u8; 4] = input[..4].try_into().unwrap();
+        if to == ENS_REGISTRY && function == selector("resolver(bytes32)") {
+            let resolver = if known { RESOLVER } else { Address::ZERO };
+            return resolver.abi_encode().into();
+        }
+        assert_eq!(to, RESOLVER, "call to an unknown contract");
+        if function == selector("addr(bytes32)") {
+            return self
+                .addrs
+                .get(&node)
+                .copied()
+                .unwrap_or_default()
+                .abi_encode()
+                .into();
+        }
+        assert_eq!(function, selector("name(bytes32)"));
+        self.names
+            .get(&node)
+            .cloned()
+            .unwrap_or_default()
+            .abi_encode()
+            .into()
+    }
+
+    async fn serve(self) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(move |request: &Request| {
+                let request: Value = serde_json::from_slice(&request.body).unwrap();
+                let result = match request["method"].as_str().unwrap() {
+                    "eth_call" => {
+                        let call = &request["params"][0];
+                        let to = Address::from_str(call["to"].as_str().unwrap()).unwrap();
+                        let input = call.get("input").unwrap_or(&call["data"]);
+                        let input = Bytes::from_str(input.as_str().unwrap()).unwrap();
+                        json!({ "result": self.call(to, &input) })
+                    }
+                    "eth_chainId" => json!({ "result": "0x1" }),
+                    other => json!({ "error": { "code": -32601, "message": other } }),
+                };
+                let mut response = json!({ "jsonrpc": "2.0", "id": request["id"] });
+                response
+                    .as_object_mut()
+                    .unwrap()
+                    .extend(result.as_object().unwrap().clone());
+                ResponseTemplate::new(200).set_body_json(response)
+            })
+            .mount(&server)
+            .await;
+        server
+    }
+}
+
+fn resolver(server: &MockServer) -> EnsResolver<impl Provider> {
+    let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+    EnsResolver::new(provider, ENS_REGISTRY)
+}
+
+#[test]
+fn parses_addresses_and_names() {
+    assert_eq!(
+        NameOrAddress::from_str("0x00000000000000000000000000000000000000aa").unwrap(),
+        NameOrAddress::Address(TRADER)
+    );
+    assert_eq!(
+        NameOrAddress::from_str("Trader.Acme.eth").unwrap(),
+        NameOrAddress::Name("trader.acme.eth".into())
+    );
+    for (value, message) in [
+        ("0x00aa", "invalid address"),
+        ("trader", "neither an address nor an ENS name"),
+        ("trader..eth", "neither an address nor an ENS name"),
+    ] {
+        let err = NameOrAddress::from_str(value).unwrap_err();
+        assert!(err.to_string().contains(message), "{value}: {err}");
+    }
+}
+
+#[test]
+fn unresolved_names_have_no_address() {
+    let err = NameOrAddress::Name("trader.acme.eth".into())
+        .address()
+        .unwrap_err();
+
+    assert!(err.to_string().contains("pass --rpc-url"), "{err}");
+}
+
+#[test]
+fn namehash_matches_eip137() {
+    assert_eq!(namehash(""), B256::ZERO);
+    assert_eq!(
+        namehash("eth"),
+        b256!("0x93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae")
+    );
+    assert_eq!(
+        namehash("foo.eth"),
+        b256!("0xde9b09fd7c5f901e23a3f19fecc54828e9c848539801e86591bd9801b019f84f")
+    );
+}
+
+#[tokio::test]
+async fn resolves_names_in_place() {
+    let server = Records::default()
+        .with_name("trader.acme.eth", TRADER)
+        .serve()
+        .await;
+    let mut value = NameOrAddress::Name("trader.acme.eth".into());
+
+    resolver(&server)
+        .resolve_in_place(&mut value)
+        .await
+        .unwrap();
+
+    assert_eq!(value, NameOrAddress::Address(TRADER));
+}
+
+#[tokio::test]
+async fn refuses_names_that_do_not_resolve() {
+    let server = Records::default()
+        .with_name("burned.acme.eth", Address::ZERO)
+        .serve()
+        .await;
+    let resolver = resolver(&server);
+
+    for (name, message) in [
+        ("unknown.acme.eth", "has no resolver"),
+        ("burned.acme.eth", "resolves to the zero address"),
+    ] {
+        let err = resolver.resolve(name).await.unwrap_err();
+        assert!(err.to_string().contains(message), "{name}: {err}");
+    }
+}
+
+#[tokio::test]
+async fn annotates_addresses_with_their_primary_names() {
+    let impostor = Address::with_last_byte(0xbb);
+    let server = Records::default()
+        .with_name("trader.acme.eth", TRADER)
+        .with_reverse(TRADER, "trader.acme.eth")
+        // A reverse record is only a claim; the name must resolve back to the address.
+        .with_reverse(impostor, "trader.acme.eth")
+        .serve()
+        .await;
+    let product = B256::repeat_byte(1);
+    let text = format!("user:        {TRADER}\nattester:    {impostor}\nproduct:     {product}\n");
+
+    let annotated = resolver(&server).annotate(&text).await;
+
+    assert_eq!(
+        annotated,
+        format!(
+            "user:        {TRADER} (trader.acme.eth)\nattester:    {impostor}\n\
+             product:     {product}\n"
+        )
+    );
+}
@@ -0,0 +1,114 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use alloy::sol_types::SolValue;
+use app::executor::{format_profile, suggest_segment_po2, ExecutorOptions, MIN_SEGMENT_PO2};
+use clap::Parser;
+use compliance_core::{CycleProfile, Section};
+use guests::BATCH_ELF;
+
+#[derive(Parser)]
+struct Cli {
+    #[clap(flatten)]
+    executor: ExecutorOptions,
+}
+
+fn frame(entries: usize) -> Vec<u8> {
+    let entry = common::entry(1);
+    entry.input(1_700_000_000).abi_encode().repeat(entries)
+}
+
+#[test]
+fn segment_limit_reaches_the_executor() {
+    let frame = frame(20);
+    let default = ExecutorOptions::default()
+        .execute(BATCH_ELF, &frame)
+        .unwrap();
+    let small = ExecutorOptions {
+        segment_limit_po2: Some(MIN_SEGMENT_PO2 + 1),
+        ..Default::default()
+    }
+    .execute(BATCH_ELF, &frame)
+    .unwrap();
+
+    assert_eq!(small.journal, default.journal);
+    assert!(
+        small.segments > default.segments,
+        "{} segments at 2^{}, {} at the default",
+        small.segments,
+        MIN_SEGMENT_PO2 + 1,
+        default.segments
+    );
+}
+
+#[test]
+fn session_limit_reaches_the_executor() {
+    let options = ExecutorOptions {
+        session_limit: Some(1 << 10),
+        ..Default::default()
+    };
+
+    let err = options.execute(BATCH_ELF, &frame(1)).unwrap_err();
+    assert!(
+        format!("{err:#}").contains("Session limit exceeded"),
+        "unexpected error: {err:#}"
+    );
+}
+
+#[test]
+fn rejects_segment_limit_out_of_range() {
+    for po2 in ["12", "25"] {
+        assert!(Cli::try_parse_from(["test", "--segment-limit-po2", po2]).is_err());
+    }
+    let cli = Cli::try_parse_from(["test", "--segment-limit-po2", "18"]).unwrap();
+    assert_eq!(cli.executor.segment_limit_po2, Some(18));
+}
+
+#[test]
+fn suggests_the_smallest_single_segment_limit() {
+    assert_eq!(suggest_segment_po2(0), 13);
+    assert_eq!(suggest_segment_po2(5_000), 13);
+    assert_eq!(suggest_segment_po2(70_000), 17);
+    assert_eq!(suggest_segment_po2(1 << 18), 18);
+    assert_eq!(suggest_segment_po2(1 << 30), 20);
+}
+
+#[test]
+fn profile_table_lists_every_section_with_its_share() {
+    let mut profile = CycleProfile::default();
+    profile.add(Section::Decode, 300);
+    profile.add(Section::Override, 600);
+    profile.heap_peak = 4096;
+
+    let table = format_profile(&profile, 1000);
+
+    let lines: Vec<_> = table.lines().collect();
+    assert_eq!(lines[0], "cycles:      900 profiled of 1000");
+    assert_eq!(lines.len(), 2 + Section::ALL.len());
+    assert_eq!(lines.last(), Some(&"heap peak:   4096 bytes"));
+    assert!(
+        lines.contains(&"  decode               300   30.0%"),
+        "{table}"
+    );
+    assert!(
+        lines.contains(&"  override             600   60.0%"),
+        "{table}"
+    );
+    assert!(
+        lines.contains(&"  aml                    0    0.0%"),
+        "{table}"
+    );
+}
@@ -0,0 +1,156 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use alloy::primitives::{Address, Bytes, B256};
+use app::{
+    batch::BatchEntry,
+    forge::{bundle_name, ForgeFixtures, FORGE_FIXTURES_JSON, FORGE_FIXTURES_LIBRARY},
+    handoff::{ExportedTrade, SettlementBundle},
+};
+
+const DECISION_AT: u64 = 1_700_000_000;
+const CHAIN_ID: u64 = 11155111;
+
+fn trade(user: u8, compact_journal: bool) -> ExportedTrade {
+    let entry = BatchEntry {
+        product_id: B256::with_last_byte(1),
+        aml_passed: user % 2 == 1,
+        ttl_secs: 3600,
+        compact_journal,
+        decision_uuid: B256::with_last_byte(user),
+        ..common::entry(user)
+    };
+    ExportedTrade {
+        journal: entry
+            .expected_journal(DECISION_AT)
+            .encode(entry.journal_encoding())
+            .into(),
+        seal: Bytes::from(vec![user; 4]),
+        entry,
+    }
+}
+
+fn bundle() -> SettlementBundle {
+    SettlementBundle::new(
+        CHAIN_ID,
+        Address::with_last_byte(0xcc),
+        vec![trade(1, false), trade(2, true)],
+    )
+}
+
+fn out_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("forge-{name}"));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn exports_bundles_and_reads_them_back() {
+    let bundle = bundle();
+    let mut forge = ForgeFixtures::default();
+    forge.add_bundle("trades", &bundle).unwrap();
+    let dir = out_dir("round-trip");
+
+    forge.write(&dir).unwrap();
+
+    let written = ForgeFixtures::read(&dir).unwrap();
+    assert_eq!(written, forge);
+    written.check_bundle("trades", &bundle).unwrap();
+
+    let allowed = &written.fixtures["trades_0"];
+    assert!(allowed.allowed && !allowed.compact);
+    assert_eq!(allowed.journal, bundle.trades[0].journal);
+    assert_eq!(allowed.seal, bundle.trades[0].seal);
+    assert_eq!(allowed.image_id, bundle.image_id);
+    assert_eq!((allowed.decision_at, allowed.ttl_secs), (DECISION_AT, 3600));
+    let denied = &written.fixtures["trades_1"];
+    assert!(!denied.allowed && denied.compact);
+    assert_ne!(denied.reasons, 0);
+
+    let library = std::fs::read_to_string(dir.join(FORGE_FIXTURES_LIBRARY)).unwrap();
+    assert!(library.contains("library ComplianceFixtures {"));
+    for name in ["trades_0", "trades_1"] {
+        assert!(
+            library.contains(&format!(
+                "function {name}() internal pure returns (ComplianceFixture memory)"
+            )),
+            "{library}"
+        );
+    }
+    assert!(library.contains(&format!(
+        "journal: hex\"{}\"",
+        alloy::hex::encode(&bundle.trades[1].journal)
+    )));
+}
+
+#[test]
+fn renders_the_same_files_for_the_same_trades() {
+    let render = || {
+        let mut forge = ForgeFixtures::default();
+        forge.add_bundle("trades", &bundle()).unwrap();
+        (forge.render_json(), forge.render_library())
+    };
+
+    assert_eq!(render(), render());
+    let (json, _) = render();
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        parsed["trades_0"]["journal"],
+        alloy::hex::encode_prefixed(&bundle().trades[0].journal)
+    );
+    assert_eq!(parsed["trades_1"]["compact"], true);
+}
+
+#[test]
+fn check_catches_fixtures_that_differ_from_the_bundle() {
+    let bundle = bundle();
+    let mut forge = ForgeFixtures::default();
+    forge.add_bundle("trades", &bundle).unwrap();
+    let dir = out_dir("altered");
+    forge.write(&dir).unwrap();
+    let path = dir.join(FORGE_FIXTURES_JSON);
+    let mut json: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    json["trades_1"]["seal"] = "0xdeadbeef".into();
+    std::fs::write(&path, serde_json::to_vec(&json).unwrap()).unwrap();
+
+    let err = ForgeFixtures::read(&dir)
+        .unwrap()
+        .check_bundle("trades", &bundle)
+        .unwrap_err();
+
+    assert!(
+        err.to_string()
+            .contains("fixture trades_1 does not read back as its source"),
+        "{err}"
+    );
+}
+
+#[test]
+fn names_fixtures_after_their_bundle() {
+    assert_eq!(
+        bundle_name(Path::new("out/Trades-2024.json")),
+        "trades_2024"
+    );
+    assert_eq!(bundle_name(Path::new("2024.json")), "bundle_2024");
+
+    let mut forge = ForgeFixtures::default();
+    forge.add_bundle("trades", &bundle()).unwrap();
+    let err = forge.add_bundle("trades", &bundle()).unwrap_err();
+    assert!(err.to_string().contains("is used twice"), "{err}");
+}
@@ -0,0 +1,176 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Golden vectors for the checked-in fixture set in `fixtures/golden`.
+//!
+//! The fixtures are written by `gen-fixtures` from `fixtures/golden/spec.json`. These tests
+//! rebuild every case from the spec with the host copy of the policy and the image id recorded in
+//! the manifest, so they pass for any guest build. If the journal layout or the policy changes,
+//! regenerate the set with:
+//!
+//! ```text
+//! cargo run -p app --bin gen-fixtures -- \
+//!   --spec apps/tests/fixtures/golden/spec.json --out apps/tests/fixtures/golden
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use alloy::{hex, primitives::B256, sol_types::SolValue};
+use app::{
+    fixtures::{dev_mode_seal, Fixture, FixtureSpec, Manifest, DEV_MODE_SELECTOR},
+    journal::{evaluate, ComplianceInput, ComplianceJournal, JournalEncoding},
+};
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden")
+}
+
+fn read(file: &str) -> Vec<u8> {
+    let path = golden_dir().join(file);
+    std::fs::read(&path).unwrap_or_else(|err| panic!("failed to read {}: {err}", path.display()))
+}
+
+fn manifest() -> Manifest {
+    Manifest::parse(&read("manifest.json")).expect("manifest.json does not parse")
+}
+
+/// Fixtures the spec would produce for the guest build recorded in the manifest.
+fn expected_fixtures(image_id: B256) -> Vec<Fixture> {
+    let spec = FixtureSpec::read(&golden_dir().join("spec.json")).unwrap();
+    spec.cases
+        .into_iter()
+        .map(|case| {
+            let input = ComplianceInput {
+                imageId: image_id,
+                ..case.entry.input(case.decision_at)
+            };
+            let journal = evaluate(&input).encode(case.entry.journal_encoding());
+            Fixture::new(case, image_id, journal)
+        })
+        .collect()
+}
+
+#[test]
+fn manifest_matches_spec() {
+    let manifest = manifest();
+    let expected = Manifest::new(&expected_fixtures(manifest.image_id)).unwrap();
+    assert_eq!(manifest, expected);
+    assert_eq!(
+        String::from_utf8(read("manifest.json")).unwrap(),
+        expected.render(),
+        "manifest.json is not in the generator's canonical form"
+    );
+}
+
+#[test]
+fn binary_files_match_manifest() {
+    let manifest = manifest();
+    assert_eq!(read("image_id.bin"), manifest.image_id.as_slice());
+    for case in &manifest.cases {
+        let name = &case.name;
+        assert_eq!(
+            hex::encode_prefixed(read(&format!("{name}.input.bin"))),
+            case.input,
+            "{name}"
+        );
+        assert_eq!(
+            hex::encode_prefixed(read(&format!("{name}.journal.bin"))),
+            case.journal,
+            "{name}"
+        );
+        assert_eq!(
+            hex::encode_prefixed(read(&format!("{name}.seal.bin"))),
+            case.seal,
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn journals_decode_to_the_recorded_decision() {
+    let manifest = manifest();
+    for case in &manifest.cases {
+        let name = &case.name;
+        let input = ComplianceInput::abi_decode(&read(&format!("{name}.input.bin"))).unwrap();
+        let bytes = read(&format!("{name}.journal.bin"));
+        let journal = ComplianceJournal::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            JournalEncoding::of(&bytes).unwrap(),
+            case.entry.journal_encoding(),
+            "{name}"
+        );
+        assert_eq!(journal.user, case.entry.user, "{name}");
+        assert_eq!(journal.productId, case.entry.product_id, "{name}");
+        assert_eq!(journal.allowed, case.allowed, "{name}");
+        assert_eq!(journal.needsReview, case.needs_review, "{name}");
+        assert_eq!(journal.kycTier, case.entry.kyc_tier(), "{name}");
+        assert_eq!(
+            journal.productPolicyVersion, case.entry.product_policy_version,
+            "{name}"
+        );
+        let quote_product = case
+            .entry
+            .quote
+            .as_ref()
+            .map_or(case.entry.product_id, |quote| quote.product_id);
+        assert_eq!(journal.quoteProduct, quote_product, "{name}");
+        assert_eq!(input.now, case.decision_at, "{name}");
+        assert_eq!(journal.decisionAt, case.decision_at, "{name}");
+        assert_eq!(input.imageId, manifest.image_id, "{name}");
+        assert_eq!(journal.imageId, manifest.image_id, "{name}");
+    }
+}
+
+#[test]
+fn seals_are_dev_mode_seals_over_the_journal() {
+    let manifest = manifest();
+    for case in &manifest.cases {
+        let name = &case.name;
+        let journal = read(&format!("{name}.journal.bin"));
+        let seal = read(&format!("{name}.seal.bin"));
+        assert_eq!(seal.len(), 36, "{name}");
+        assert_eq!(seal[..4], DEV_MODE_SELECTOR, "{name}");
+        assert_eq!(seal, dev_mode_seal(manifest.image_id, &journal), "{name}");
+    }
+}
+
+#[test]
+fn compact_vectors_pack_their_abi_twins() {
+    let manifest = manifest();
+    let mut twins = 0;
+    for case in &manifest.cases {
+        let Some(abi_name) = case.name.strip_suffix("_compact") else {
+            continue;
+        };
+        let compact = read(&format!("{}.journal.bin", case.name));
+        let abi = read(&format!("{abi_name}.journal.bin"));
+        assert_eq!(
+            compact.len(),
+            ComplianceJournal::COMPACT_LEN,
+            "{}",
+            case.name
+        );
+        assert_eq!(compact[0], JournalEncoding::Compact as u8, "{}", case.name);
+        let journal = ComplianceJournal::abi_decode(&abi).unwrap();
+        assert_eq!(compact[1..], journal.abi_encode_packed(), "{}", case.name);
+        assert_eq!(
+            ComplianceJournal::try_from(compact.as_slice()).unwrap(),
+            journal,
+            "{}",
+            case.name
+        );
+        twins += 1;
+    }
+    assert!(twins >= 2, "expected compact twins of the ABI vectors");
+}
@@ -0,0 +1,196 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::{path::PathBuf, time::Duration};
+
+use alloy::primitives::{Address, Bytes, B256, U256};
+use app::{
+    batch::BatchEntry,
+    handoff::{
+        settle_bundle, ExportedTrade, ExportingSettler, SettlementBundle, SETTLEMENT_BUNDLE_VERSION,
+    },
+    journal::compliance_image_id,
+    settle::{Settlement, Settler},
+};
+use common::MockSettler;
+use serde_json::Value;
+
+const DECISION_AT: u64 = 1_700_000_000;
+const CHAIN_ID: u64 = 11155111;
+
+fn hook() -> Address {
+    Address::with_last_byte(0xcc)
+}
+
+fn entry(user: u8) -> BatchEntry {
+    BatchEntry {
+        product_id: B256::with_last_byte(1),
+        decision_uuid: B256::with_last_byte(user),
+        ..common::entry(user)
+    }
+}
+
+fn trade(user: u8) -> ExportedTrade {
+    let entry = entry(user);
+    ExportedTrade {
+        journal: entry
+            .expected_journal(DECISION_AT)
+            .encode(entry.journal_encoding())
+            .into(),
+        seal: Bytes::from(vec![user; 4]),
+        entry,
+    }
+}
+
+fn bundle_path(name: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("bundle-{name}.json"));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[tokio::test]
+async fn exports_trades_and_reads_them_back() {
+    let path = bundle_path("round-trip");
+    let exporter = ExportingSettler::new(&path, CHAIN_ID, hook());
+    for user in [1, 2] {
+        let trade = trade(user);
+        let settlement = exporter
+            .settle(&trade.entry, trade.journal, trade.seal)
+            .await
+            .unwrap();
+        assert_eq!(settlement, Settlement::Exported { path: path.clone() });
+        assert_eq!(settlement.tx_hash(), None);
+    }
+
+    let bundle = SettlementBundle::read(&path).unwrap();
+    assert_eq!(
+        bundle,
+        SettlementBundle::new(CHAIN_ID, hook(), vec![trade(1), trade(2)])
+    );
+    assert_eq!(bundle.version, SETTLEMENT_BUNDLE_VERSION);
+    assert_eq!(bundle.image_id, compliance_image_id());
+    assert_eq!(bundle.checksum, bundle.digest());
+
+    // Settling the bundle makes the same calls proving and settling directly would have.
+    let settler = MockSettler::new(Duration::ZERO);
+    let results = settle_bundle(&bundle, &settler).await;
+    assert!(results.iter().all(Result::is_ok));
+    let settled = settler.settled();
+    assert_eq!(settled.len(), 2);
+    for ((entry, journal, seal), trade) in settled.into_iter().zip([trade(1), trade(2)]) {
+        assert_eq!(entry, trade.entry);
+        assert_eq!(journal, trade.journal);
+        assert_eq!(seal, trade.seal);
+    }
+}
+
+#[tokio::test]
+async fn keeps_settling_after_a_failed_trade() {
+    let bundle = SettlementBundle::new(CHAIN_ID, hook(), vec![trade(1), trade(2), trade(3)]);
+    let settler = MockSettler::new(Duration::ZERO).rejecting(Address::with_last_byte(2));
+
+    let results = settle_bundle(&bundle, &settler).await;
+
+    assert!(results[0].is_ok() && results[2].is_ok());
+    let err = results[1].as_ref().unwrap_err();
+    assert!(
+        format!("{err:#}").contains("failed to settle trade 1"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn rejects_altered_bundles() {
+    let path = bundle_path("altered");
+    SettlementBundle::new(CHAIN_ID, hook(), vec![trade(1)])
+        .write(&path)
+        .unwrap();
+    let mut json: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    json["trades"][0]["seal"] = "0xdeadbeef".into();
+    std::fs::write(&path, serde_json::to_vec(&json).unwrap()).unwrap();
+
+    let err = SettlementBundle::read(&path).unwrap_err();
+
+    assert!(
+        format!("{err:#}").contains("does not match its contents"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn checksum_covers_where_the_trades_settle() {
+    let bundle = SettlementBundle::new(CHAIN_ID, hook(), vec![trade(1)]);
+    for altered in [
+        SettlementBundle {
+            chain_id: 1,
+            ..bundle.clone()
+        },
+        SettlementBundle {
+            target: Address::with_last_byte(0xdd),
+            ..bundle.clone()
+        },
+        SettlementBundle {
+            trades: vec![trade(1), trade(2)],
+            ..bundle.clone()
+        },
+    ] {
+        assert!(altered.verify().is_err());
+    }
+}
+
+#[test]
+fn rejects_trades_their_journal_does_not_prove() {
+    let mut other = trade(1);
+    other.entry.amount = U256::from(1_000_000);
+    // A consistent checksum does not make up for a journal of another trade.
+    let bundle = SettlementBundle::new(CHAIN_ID, hook(), vec![other]);
+
+    let err = bundle.verify().unwrap_err();
+
+    assert!(
+        format!("{err:#}").contains("journal of trade 0 is of user"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn rejects_other_versions() {
+    let mut bundle = SettlementBundle::new(CHAIN_ID, hook(), vec![trade(1)]);
+    bundle.version = SETTLEMENT_BUNDLE_VERSION + 1;
+    bundle.checksum = bundle.digest();
+
+    let err = bundle.verify().unwrap_err();
+
+    assert!(
+        err.to_string().contains("unsupported bundle version"),
+        "{err}"
+    );
+}
+
+#[test]
+fn refuses_decisions_older_than_the_limit() {
+    let bundle = SettlementBundle::new(CHAIN_ID, hook(), vec![trade(1), trade(2)]);
+
+    bundle.ensure_fresh(60, DECISION_AT + 60).unwrap();
+    let err = bundle.ensure_fresh(60, DECISION_AT + 61).unwrap_err();
+
+    assert!(
+        err.to_string()
+            .contains("artifact too old, re-prove: trade 0"),
+        "{err}"
+    );
+    assert!(err.to_string().contains("(61s ago)"), "{err}");
+}
@@ -0,0 +1,234 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alloy::{
+    hex,
+    primitives::{Address, Bytes, B256},
+};
+use app::{
+    batch::BatchEntry,
+    ipfs::{IpfsClient, PinStore, PinningSettler, ProofBundle},
+    settle::{Settlement, Settler},
+};
+use common::{entry, fast_retries, MockSettler};
+use guests::KECCAK_BENCH_ELF;
+use sha2::{Digest, Sha256};
+use url::Url;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, Request, Respond, ResponseTemplate,
+};
+
+const DECISION_AT: u64 = 1_700_000_000;
+
+fn journal(entry: &BatchEntry) -> Bytes {
+    entry
+        .expected_journal(DECISION_AT)
+        .encode(entry.journal_encoding())
+        .into()
+}
+
+/// A kubo API that keeps the files added to it in memory, under a CID made from their SHA-256.
+#[derive(Clone, Default)]
+struct Kubo {
+    files: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+impl Kubo {
+    async fn start() -> (Self, MockServer) {
+        let kubo = Self::default();
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(kubo.clone())
+            .mount(&server)
+            .await;
+        (kubo, server)
+    }
+
+    fn insert(&self, contents: &[u8]) -> String {
+        let cid = format!("bafk{}", hex::encode(Sha256::digest(contents)));
+        self.files
+            .lock()
+            .unwrap()
+            .insert(cid.clone(), contents.to_vec());
+        cid
+    }
+}
+
+impl Respond for Kubo {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        match request.url.path() {
+            "/api/v0/add" => {
+                let content_type = request.headers["content-type"].to_str().unwrap();
+                let boundary = content_type.split("boundary=").nth(1).unwrap();
+                let body = &request.body;
+                let start = body.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+                let end = body.len() - format!("\r\n--{boundary}--\r\n").len();
+                let cid = self.insert(&body[start..end]);
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "Name": "file", "Hash": cid, "Size": "0" }))
+            }
+            "/api/v0/cat" => {
+                let (_, cid) = request.url.query_pairs().find(|(k, _)| k == "arg").unwrap();
+                match self.files.lock().unwrap().get(cid.as_ref()) {
+                    Some(contents) => ResponseTemplate::new(200).set_body_bytes(contents.clone()),
+                    None => ResponseTemplate::new(404).set_body_string("not found"),
+                }
+            }
+            _ => ResponseTemplate::new(404),
+        }
+    }
+}
+
+fn client(server: &MockServer) -> IpfsClient {
+    IpfsClient::new(Url::parse(&server.uri()).unwrap(), Some("token".into()))
+        .with_policy(fast_retries())
+}
+
+/// A store of its own for each test.
+fn store(name: &str) -> PinStore {
+    PinStore::new(PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name))
+}
+
+#[tokio::test]
+async fn pins_bundles_and_the_program_once() {
+    let (kubo, server) = Kubo::start().await;
+    let store = store("pins");
+    let settler = PinningSettler::new(
+        MockSettler::new(Duration::ZERO),
+        client(&server),
+        store.clone(),
+    );
+
+    let mut settlements = Vec::new();
+    for i in 1..=2 {
+        let entry = entry(i);
+        let settlement = settler
+            .settle(&entry, journal(&entry), Bytes::from_static(b"seal"))
+            .await
+            .unwrap();
+        settlements.push(settlement);
+    }
+
+    let [Settlement::Pinned {
+        bundle_cid: first,
+        program_cid,
+        settlement,
+    }, Settlement::Pinned {
+        bundle_cid: second,
+        program_cid: again,
+        ..
+    }] = &settlements[..]
+    else {
+        panic!("expected pinned settlements, got {settlements:?}");
+    };
+    assert_eq!(program_cid, again);
+    assert_ne!(first, second);
+    assert_eq!(settlements[0].tx_hash(), settlement.tx_hash());
+    assert_eq!(settlements[0].bundle_cid(), Some(first.as_str()));
+    // The program is added once, and every call carries the token.
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 3);
+    assert!(requests
+        .iter()
+        .all(|request| request.headers["authorization"] == "Bearer token"));
+    assert_eq!(kubo.files.lock().unwrap().len(), 3);
+
+    let record = store.get(first).unwrap().unwrap();
+    assert_eq!(record.user, Address::with_last_byte(1));
+    assert_eq!(&record.program_cid, program_cid);
+    assert_eq!(
+        record.journal_digest,
+        B256::from_slice(&Sha256::digest(journal(&entry(1))))
+    );
+}
+
+#[tokio::test]
+async fn fetched_bundle_verifies_against_its_program() {
+    let (kubo, server) = Kubo::start().await;
+    let settler = PinningSettler::new(
+        MockSettler::new(Duration::ZERO),
+        client(&server),
+        store("fetch"),
+    );
+    let entry = entry(1);
+    let settlement = settler
+        .settle(&entry, journal(&entry), Bytes::from_static(b"seal"))
+        .await
+        .unwrap();
+
+    let bundle = client(&server)
+        .fetch_bundle(settlement.bundle_cid().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(bundle.journal, journal(&entry));
+    assert_eq!(bundle.seal, Bytes::from_static(b"seal"));
+
+    // The same journal, naming a program of another image.
+    let forged = ProofBundle {
+        program_cid: kubo.insert(KECCAK_BENCH_ELF),
+        ..bundle
+    };
+    let cid = kubo.insert(&serde_json::to_vec(&forged).unwrap());
+    let err = client(&server).fetch_bundle(&cid).await.unwrap_err();
+    assert!(format!("{err:#}").contains("does not verify"), "{err:#}");
+    let err = client(&server)
+        .fetch_bundle("bafkmissing")
+        .await
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("404"), "{err:#}");
+}
+
+#[tokio::test]
+async fn failed_pin_warns_unless_required() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+    let entry = entry(1);
+
+    let settler = PinningSettler::new(
+        MockSettler::new(Duration::ZERO),
+        client(&server),
+        store("unpinned"),
+    );
+    let settlement = settler
+        .settle(&entry, journal(&entry), Bytes::new())
+        .await
+        .unwrap();
+    assert!(matches!(settlement, Settlement::Confirmed { .. }));
+
+    let settler = PinningSettler::new(
+        MockSettler::new(Duration::ZERO),
+        client(&server),
+        store("unpinned"),
+    )
+    .requiring_pin(true);
+    let err = settler
+        .settle(&entry, journal(&entry), Bytes::new())
+        .await
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("but failed to pin"), "{err:#}");
+}
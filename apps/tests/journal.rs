@@ -0,0 +1,1128 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloy::{
+    primitives::{keccak256, Address, B256, U256},
+    signers::{local::PrivateKeySigner, SignerSync},
+    sol_types::{SolStruct, SolValue},
+};
+use app::journal::{
+    aggregate_attestation_hash, aggregate_attestation_hash_with, attestation_hash,
+    attestation_hashes, compliance_image_id, ensure_image_id, evaluate, explain,
+    explain_with_decimals, format_amount, format_decision_at, governance_hash, override_hash,
+    CommitmentHash, ComplianceInput, ComplianceJournal, JournalEncoding, JournalSchema, ReasonCode,
+    Regime, AML_SOURCE_PEP, AML_SOURCE_SANCTIONS, CLAIM_MICA_APPROPRIATENESS,
+    CLAIM_SEC_ACCREDITED_INVESTOR, GOVERNANCE_SET_LEN, JOURNAL_SCHEMAS, JOURNAL_SCHEMA_HASH,
+    JOURNAL_SCHEMA_VERSION, KYC_PROVIDER_SET_LEN, SHA256_COMMITMENTS,
+};
+use compliance_core::{
+    AccreditationAttestation, AmlAttestation, ComplianceAttestation, KycAttestation,
+};
+use sha2::{Digest, Sha256};
+
+fn journal(image_id: B256) -> Vec<u8> {
+    ComplianceJournal {
+        user: Address::from([1u8; 20]),
+        productId: B256::from([2u8; 32]),
+        allowed: true,
+        reasons: 0,
+        kycTier: 1,
+        claims: U256::from(0b101),
+        attester: Address::ZERO,
+        attestationHash: B256::ZERO,
+        attestationsHash: B256::ZERO,
+        overrideSigner: Address::ZERO,
+        governanceHash: B256::ZERO,
+        requiredAttestations: 0,
+        achievedAttestations: 0,
+        productPolicyVersion: 3,
+        quoteProduct: B256::from([2u8; 32]),
+        quotePolicyVersion: 3,
+        needsReview: false,
+        regimeId: 0,
+        settlementToken: Address::ZERO,
+        caseId: B256::ZERO,
+        tradeAmount: U256::from(100),
+        minTrade: U256::ZERO,
+        maxTrade: U256::MAX,
+        decisionAt: 1_700_000_000,
+        ttlSecs: 0,
+        commitmentHash: 0,
+        decisionUuid: B256::ZERO,
+        credentialHash: B256::ZERO,
+        notBefore: 0,
+        amlSources: U256::ZERO,
+        blockHash: B256::ZERO,
+        kycProvider: 0,
+        consentGiven: false,
+        consentAt: 0,
+        attestationSignatureHash: B256::ZERO,
+        schemaHash: JOURNAL_SCHEMA_HASH,
+        imageId: image_id,
+    }
+    .abi_encode()
+}
+
+#[test]
+fn encodes_into_the_end_of_a_reused_buffer() {
+    // Every field is non-zero and distinct, so a field encoded into the wrong word shows up.
+    let input = ComplianceInput {
+        user: Address::repeat_byte(1),
+        productId: B256::repeat_byte(2),
+        kycTier: 3,
+        requiredTier: 4,
+        amlPassed: true,
+        claims: U256::MAX,
+        requiredClaims: U256::from(5),
+        productPolicyVersion: u32::MAX,
+        quoteProduct: B256::repeat_byte(6),
+        quoteRequiredTier: 7,
+        quoteRequiredClaims: U256::from(8),
+        quotePolicyVersion: 9,
+        riskScore: 10,
+        reviewBandLow: 11,
+        reviewBandHigh: u8::MAX,
+        regimeId: u16::MAX,
+        settlementToken: Address::repeat_byte(22),
+        caseId: B256::ZERO,
+        tradeAmount: U256::from(23),
+        minTrade: U256::from(24),
+        maxTrade: U256::from(25),
+        attester: Address::repeat_byte(12),
+        requiredAttestations: 13,
+        governance: [14, 15, 16, 17].map(Address::repeat_byte),
+        overrideR: B256::repeat_byte(18),
+        overrideYParityAndS: B256::repeat_byte(19),
+        journalVersion: 20,
+        now: u64::MAX,
+        ttlSecs: 26,
+        decisionUuid: B256::repeat_byte(27),
+        credentialHash: B256::repeat_byte(28),
+        notBefore: 29,
+        amlSources: U256::from(30),
+        requiredAmlSources: U256::MAX,
+        blockHash: B256::repeat_byte(31),
+        kycProvider: u16::MAX,
+        allowedKycProviders: [32, 33, 34, u16::MAX],
+        consentGiven: true,
+        consentAt: 35,
+        consentRequired: true,
+        attestationSignatureHash: B256::repeat_byte(36),
+        imageId: B256::repeat_byte(21),
+    };
+    let journal = ComplianceJournal {
+        user: Address::repeat_byte(1),
+        productId: B256::repeat_byte(2),
+        allowed: true,
+        reasons: u32::MAX,
+        kycTier: 3,
+        claims: U256::MAX,
+        attester: Address::repeat_byte(4),
+        attestationHash: B256::repeat_byte(5),
+        attestationsHash: B256::repeat_byte(6),
+        overrideSigner: Address::repeat_byte(7),
+        governanceHash: B256::repeat_byte(8),
+        requiredAttestations: 9,
+        achievedAttestations: u8::MAX,
+        productPolicyVersion: 10,
+        quoteProduct: B256::repeat_byte(11),
+        quotePolicyVersion: u32::MAX,
+        needsReview: true,
+        regimeId: u16::MAX,
+        settlementToken: Address::repeat_byte(13),
+        caseId: B256::ZERO,
+        tradeAmount: U256::from(14),
+        minTrade: U256::from(15),
+        maxTrade: U256::from(16),
+        decisionAt: u64::MAX,
+        ttlSecs: 17,
+        commitmentHash: 0,
+        decisionUuid: B256::repeat_byte(18),
+        credentialHash: B256::repeat_byte(19),
+        notBefore: 20,
+        amlSources: U256::from(21),
+        blockHash: B256::repeat_byte(22),
+        kycProvider: u16::MAX,
+        consentGiven: true,
+        consentAt: u64::MAX,
+        attestationSignatureHash: B256::repeat_byte(24),
+        schemaHash: B256::repeat_byte(23),
+        imageId: B256::repeat_byte(12),
+    };
+
+    let mut buffer = b"frame".to_vec();
+    input.encode_to(&mut buffer);
+    assert_eq!(&buffer[..5], b"frame");
+    assert_eq!(buffer[5..], input.abi_encode());
+
+    let reused = buffer.as_ptr();
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        buffer.clear();
+        journal.encode_to(encoding, &mut buffer);
+        assert_eq!(buffer.as_ptr(), reused, "{}", encoding.name());
+        assert_eq!(
+            ComplianceJournal::try_from(buffer.as_slice()).unwrap(),
+            journal
+        );
+    }
+    buffer.clear();
+    journal.encode_to(JournalEncoding::Abi, &mut buffer);
+    assert_eq!(buffer, journal.abi_encode());
+}
+
+#[test]
+fn accepts_journal_from_embedded_guest() {
+    let journal = journal(compliance_image_id());
+
+    ensure_image_id(&journal).unwrap();
+}
+
+#[test]
+fn rejects_journal_from_other_guest() {
+    let journal = journal(B256::from([9u8; 32]));
+
+    let err = ensure_image_id(&journal).unwrap_err();
+    assert!(err.to_string().contains("expected COMPLIANCE_ELF id"));
+}
+
+#[test]
+fn formats_decision_time_as_utc_date() {
+    assert_eq!(format_decision_at(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_decision_at(1_700_000_000), "2023-11-14T22:13:20Z");
+}
+
+#[test]
+fn formats_amounts_in_whole_units() {
+    let wei = U256::from(1_500_000_000_000_000_000u64);
+    assert_eq!(format_amount(wei, 18), "1.5");
+    assert_eq!(format_amount(wei, 0), "1500000000000000000");
+    assert_eq!(format_amount(U256::from(1_000_000), 6), "1");
+    assert_eq!(format_amount(U256::from(1), 6), "0.000001");
+    assert_eq!(format_amount(U256::from(123_456), 2), "1234.56");
+    assert_eq!(format_amount(U256::ZERO, 18), "0");
+    assert_eq!(
+        format_amount(U256::MAX, 77),
+        "1.15792089237316195423570985008687907853269984665640564039457584007913129639935"
+    );
+    // 10^78 does not fit a U256, so there is no unit to divide by.
+    assert_eq!(format_amount(U256::from(7), 78), "7");
+}
+
+#[test]
+fn explains_amounts_in_whole_units() {
+    let journal = ComplianceJournal {
+        tradeAmount: U256::from(2_250_000),
+        minTrade: U256::from(1_000_000),
+        maxTrade: U256::from(5_000_000),
+        ..ComplianceJournal::abi_decode(&journal(B256::ZERO)).unwrap()
+    };
+
+    let explanation = explain_with_decimals(&journal, 6);
+    assert!(explanation.contains("amount:      2.25\n"), "{explanation}");
+    assert!(
+        explanation.contains("size band:   1 to 5\n"),
+        "{explanation}"
+    );
+    assert!(explain(&journal).contains("amount:      2250000\n"));
+}
+
+#[test]
+fn explains_denied_decision() {
+    let journal = ComplianceJournal {
+        allowed: false,
+        reasons: 0b11,
+        ..ComplianceJournal::abi_decode(&journal(B256::ZERO)).unwrap()
+    };
+
+    let explanation = explain(&journal);
+    assert!(explanation.contains("allowed:     false\n"));
+    assert!(explanation.contains("reasons:     aml_failed, kyc_tier_too_low\n"));
+    assert!(explanation.contains("policy:      v3\n"));
+    assert!(explanation.contains("claims:      0b101\n"));
+    assert!(!explanation.contains("attester:"));
+    assert!(explanation.contains("decided at:  2023-11-14T22:13:20Z (1700000000)\n"));
+}
+
+#[test]
+fn compact_and_standard_journals_decode_to_the_same_decision() {
+    let journal = ComplianceJournal::abi_decode(&journal(compliance_image_id())).unwrap();
+    let standard = journal.encode(JournalEncoding::Abi);
+    let compact = journal.encode(JournalEncoding::Compact);
+
+    assert_eq!(
+        JournalEncoding::of(&compact).unwrap(),
+        JournalEncoding::Compact
+    );
+    assert_eq!(
+        ComplianceJournal::try_from(standard.as_slice()).unwrap(),
+        journal
+    );
+    assert_eq!(
+        ComplianceJournal::try_from(compact.as_slice()).unwrap(),
+        journal
+    );
+    ensure_image_id(&compact).unwrap();
+}
+
+/// Calldata gas of `bytes` under EIP-2028: 4 per zero byte, 16 per non-zero byte.
+fn calldata_gas(bytes: &[u8]) -> usize {
+    bytes.iter().map(|&b| if b == 0 { 4 } else { 16 }).sum()
+}
+
+#[test]
+fn compact_journal_is_smaller() {
+    let journal = ComplianceJournal::abi_decode(&journal(compliance_image_id())).unwrap();
+    let standard = journal.encode(JournalEncoding::Abi);
+    let compact = journal.encode(JournalEncoding::Compact);
+
+    assert_eq!(standard.len(), ComplianceJournal::LEN);
+    assert_eq!(compact.len(), ComplianceJournal::COMPACT_LEN);
+    assert_eq!((standard.len(), compact.len()), (1184, 680));
+    assert!(calldata_gas(&compact) < calldata_gas(&standard));
+}
+
+#[test]
+fn product_policy_version_round_trips() {
+    let mut decision = ComplianceJournal::abi_decode(&journal(compliance_image_id())).unwrap();
+    decision.productPolicyVersion = u32::MAX;
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let encoded = decision.encode(encoding);
+        let decoded = ComplianceJournal::try_from(encoded.as_slice()).unwrap();
+        assert_eq!(
+            decoded.productPolicyVersion,
+            u32::MAX,
+            "{}",
+            encoding.name()
+        );
+    }
+}
+
+#[test]
+fn aggregate_attestation_hash_is_digest_of_concatenated_hashes() {
+    let hashes = [
+        B256::from([1u8; 32]),
+        B256::from([2u8; 32]),
+        B256::from([3u8; 32]),
+    ];
+    let concatenated = [[1u8; 32], [2u8; 32], [3u8; 32]].concat();
+
+    assert_eq!(
+        aggregate_attestation_hash(&hashes),
+        keccak256(&concatenated)
+    );
+    assert_eq!(aggregate_attestation_hash(&[]), keccak256([]));
+    assert_eq!(
+        aggregate_attestation_hash_with(&hashes, CommitmentHash::Sha256),
+        B256::from_slice(&Sha256::digest(&concatenated))
+    );
+}
+
+#[test]
+fn decision_commits_aggregate_of_its_attestations() {
+    let input = ComplianceInput {
+        user: Address::from([4u8; 20]),
+        productId: B256::from([5u8; 32]),
+        kycTier: 2,
+        requiredTier: 1,
+        amlPassed: true,
+        claims: U256::from(0b11),
+        requiredClaims: U256::ZERO,
+        productPolicyVersion: 0,
+        quoteProduct: B256::from([5u8; 32]),
+        quoteRequiredTier: 0,
+        quoteRequiredClaims: U256::ZERO,
+        quotePolicyVersion: 0,
+        riskScore: 0,
+        reviewBandLow: 0,
+        reviewBandHigh: 0,
+        regimeId: 0,
+        settlementToken: Address::ZERO,
+        caseId: B256::ZERO,
+        tradeAmount: U256::from(100),
+        minTrade: U256::ZERO,
+        maxTrade: U256::MAX,
+        attester: Address::ZERO,
+        requiredAttestations: 0,
+        governance: [Address::ZERO; GOVERNANCE_SET_LEN],
+        overrideR: B256::ZERO,
+        overrideYParityAndS: B256::ZERO,
+        journalVersion: 0,
+        now: 1_700_000_000,
+        ttlSecs: 0,
+        decisionUuid: B256::ZERO,
+        credentialHash: B256::ZERO,
+        notBefore: 0,
+        amlSources: U256::ZERO,
+        requiredAmlSources: U256::ZERO,
+        blockHash: B256::ZERO,
+        kycProvider: 0,
+        allowedKycProviders: [0; KYC_PROVIDER_SET_LEN],
+        consentGiven: false,
+        consentAt: 0,
+        consentRequired: false,
+        attestationSignatureHash: B256::ZERO,
+        imageId: compliance_image_id(),
+    };
+    let reference = keccak256(attestation_hashes(&input).map(|hash| hash.0).concat());
+
+    let journal = evaluate(&input);
+    assert_eq!(journal.attestationsHash, reference);
+    assert_eq!(journal.commitmentHash, CommitmentHash::Keccak as u8);
+    assert!(explain(&journal).contains(&format!("provenance:  {reference}\n")));
+    assert!(explain(&journal).contains("commitments: keccak\n"));
+
+    // The same decision for a verifier that checks commitments with SHA-256.
+    let concatenated = attestation_hashes(&input).map(|hash| hash.0).concat();
+    let reference = B256::from_slice(&Sha256::digest(concatenated));
+    let input = ComplianceInput {
+        journalVersion: JournalEncoding::Compact as u8 | SHA256_COMMITMENTS,
+        ..input
+    };
+    let journal = evaluate(&input);
+    assert_eq!(journal.attestationsHash, reference);
+    assert_eq!(journal.commitmentHash, CommitmentHash::Sha256 as u8);
+    assert!(explain(&journal).contains("commitments: sha256\n"));
+    let compact = journal.encode(JournalEncoding::from_version(input.journalVersion).unwrap());
+    assert_eq!(
+        JournalEncoding::of(&compact).unwrap(),
+        JournalEncoding::Compact
+    );
+    assert_eq!(
+        ComplianceJournal::try_from(compact.as_slice()).unwrap(),
+        journal
+    );
+}
+
+#[test]
+fn attestation_hashes_match_eip712_struct_hashes() {
+    let input = ComplianceInput {
+        user: Address::from([6u8; 20]),
+        productId: B256::ZERO,
+        kycTier: 3,
+        requiredTier: 1,
+        amlPassed: true,
+        claims: U256::from(0b1001),
+        requiredClaims: U256::ZERO,
+        productPolicyVersion: 0,
+        quoteProduct: B256::ZERO,
+        quoteRequiredTier: 0,
+        quoteRequiredClaims: U256::ZERO,
+        quotePolicyVersion: 0,
+        riskScore: 0,
+        reviewBandLow: 0,
+        reviewBandHigh: 0,
+        regimeId: 0,
+        settlementToken: Address::ZERO,
+        caseId: B256::ZERO,
+        tradeAmount: U256::from(100),
+        minTrade: U256::ZERO,
+        maxTrade: U256::MAX,
+        attester: Address::ZERO,
+        requiredAttestations: 0,
+        governance: [Address::ZERO; GOVERNANCE_SET_LEN],
+        overrideR: B256::ZERO,
+        overrideYParityAndS: B256::ZERO,
+        journalVersion: 0,
+        now: 0,
+        ttlSecs: 0,
+        decisionUuid: B256::ZERO,
+        credentialHash: B256::ZERO,
+        notBefore: 0,
+        amlSources: U256::ZERO,
+        requiredAmlSources: U256::ZERO,
+        blockHash: B256::ZERO,
+        kycProvider: 0,
+        allowedKycProviders: [0; KYC_PROVIDER_SET_LEN],
+        consentGiven: false,
+        consentAt: 0,
+        consentRequired: false,
+        attestationSignatureHash: B256::ZERO,
+        imageId: B256::ZERO,
+    };
+    let combined = ComplianceAttestation {
+        user: input.user,
+        kycTier: input.kycTier,
+        amlPassed: input.amlPassed,
+        claims: input.claims,
+    };
+    let kyc = KycAttestation {
+        user: input.user,
+        kycTier: input.kycTier,
+    };
+    let aml = AmlAttestation {
+        user: input.user,
+        amlPassed: input.amlPassed,
+    };
+    let accreditation = AccreditationAttestation {
+        user: input.user,
+        claims: input.claims,
+    };
+
+    assert_eq!(attestation_hash(&input), combined.eip712_hash_struct());
+    assert_eq!(
+        attestation_hashes(&input),
+        [
+            kyc.eip712_hash_struct(),
+            aml.eip712_hash_struct(),
+            accreditation.eip712_hash_struct()
+        ]
+    );
+}
+
+/// Input denied for failing AML, with `governance` as its governance set.
+fn denied_input(attester: Address, governance: [Address; GOVERNANCE_SET_LEN]) -> ComplianceInput {
+    ComplianceInput {
+        user: Address::from([7u8; 20]),
+        productId: B256::from([8u8; 32]),
+        kycTier: 1,
+        requiredTier: 1,
+        amlPassed: false,
+        claims: U256::ZERO,
+        requiredClaims: U256::ZERO,
+        productPolicyVersion: 2,
+        quoteProduct: B256::from([8u8; 32]),
+        quoteRequiredTier: 0,
+        quoteRequiredClaims: U256::ZERO,
+        quotePolicyVersion: 2,
+        riskScore: 0,
+        reviewBandLow: 0,
+        reviewBandHigh: 0,
+        regimeId: 0,
+        settlementToken: Address::ZERO,
+        caseId: B256::ZERO,
+        tradeAmount: U256::from(100),
+        minTrade: U256::ZERO,
+        maxTrade: U256::MAX,
+        attester,
+        requiredAttestations: 0,
+        governance,
+        overrideR: B256::ZERO,
+        overrideYParityAndS: B256::ZERO,
+        journalVersion: 0,
+        now: 1_700_000_000,
+        ttlSecs: 0,
+        decisionUuid: B256::ZERO,
+        credentialHash: B256::ZERO,
+        notBefore: 0,
+        amlSources: U256::ZERO,
+        requiredAmlSources: U256::ZERO,
+        blockHash: B256::ZERO,
+        kycProvider: 0,
+        allowedKycProviders: [0; KYC_PROVIDER_SET_LEN],
+        consentGiven: false,
+        consentAt: 0,
+        consentRequired: false,
+        attestationSignatureHash: B256::ZERO,
+        imageId: compliance_image_id(),
+    }
+}
+
+/// `input` carrying `signer`'s EIP-2098 signature over its override hash.
+fn overridden(input: ComplianceInput, signer: &PrivateKeySigner) -> ComplianceInput {
+    let compact = signer
+        .sign_hash_sync(&override_hash(&input))
+        .unwrap()
+        .as_erc2098();
+    ComplianceInput {
+        overrideR: B256::from_slice(&compact[..32]),
+        overrideYParityAndS: B256::from_slice(&compact[32..]),
+        ..input
+    }
+}
+
+#[test]
+fn governance_override_allows_denied_trade() {
+    let governor = PrivateKeySigner::random();
+    let mut governance = [Address::ZERO; GOVERNANCE_SET_LEN];
+    governance[2] = governor.address();
+    let input = overridden(denied_input(Address::ZERO, governance), &governor);
+
+    let journal = evaluate(&input);
+    assert!(journal.allowed);
+    assert_eq!(
+        journal.reasons,
+        ReasonCode::AmlFailed.bit() | ReasonCode::Override.bit()
+    );
+    assert_eq!(journal.overrideSigner, governor.address());
+    assert_eq!(journal.governanceHash, keccak256(governance.abi_encode()));
+    assert_eq!(journal.governanceHash, governance_hash(&input));
+
+    let explanation = explain(&journal);
+    assert!(explanation.contains("reasons:     aml_failed, override\n"));
+    assert!(explanation.contains(&format!("override:    {}\n", governor.address())));
+}
+
+#[test]
+fn override_outside_governance_authority_is_denied() {
+    let governor = PrivateKeySigner::random();
+    let outsider = PrivateKeySigner::random();
+    let governance = [governor.address(); GOVERNANCE_SET_LEN];
+    let denied = ReasonCode::AmlFailed.bit() | ReasonCode::OverrideUnauthorized.bit();
+
+    let unknown = evaluate(&overridden(
+        denied_input(Address::ZERO, governance),
+        &outsider,
+    ));
+    assert!(!unknown.allowed);
+    assert_eq!(unknown.reasons, denied);
+    assert_eq!(unknown.overrideSigner, Address::ZERO);
+
+    // Governance keys are a separate authority: the attester cannot override its own facts.
+    let attester = evaluate(&overridden(
+        denied_input(governor.address(), governance),
+        &governor,
+    ));
+    assert!(!attester.allowed);
+    assert_eq!(attester.reasons, denied);
+}
+
+#[test]
+fn decision_commits_attestation_quorum_counts() {
+    let attester = Address::from([9u8; 20]);
+    let input = ComplianceInput {
+        amlPassed: true,
+        requiredAttestations: 1,
+        attestationSignatureHash: B256::repeat_byte(10),
+        ..denied_input(attester, [Address::ZERO; GOVERNANCE_SET_LEN])
+    };
+
+    let met = evaluate(&input);
+    assert!(met.allowed);
+    assert_eq!((met.requiredAttestations, met.achievedAttestations), (1, 1));
+    assert!(met.achievedAttestations >= met.requiredAttestations);
+    assert!(explain(&met).contains("quorum:      1 of 1 attestations\n"));
+
+    assert_eq!(met.attestationSignatureHash, input.attestationSignatureHash);
+
+    // No attester, or one whose signature the proof does not commit to.
+    for missing in [
+        ComplianceInput {
+            attester: Address::ZERO,
+            ..input.clone()
+        },
+        ComplianceInput {
+            attestationSignatureHash: B256::ZERO,
+            ..input
+        },
+    ] {
+        let missing = evaluate(&missing);
+        assert!(!missing.allowed);
+        assert_eq!(missing.reasons, ReasonCode::AttestationsMissing.bit());
+        assert_eq!(
+            (missing.requiredAttestations, missing.achievedAttestations),
+            (1, 0)
+        );
+    }
+}
+
+#[test]
+fn gray_zone_risk_score_needs_review_instead_of_allow_or_deny() {
+    let input = ComplianceInput {
+        amlPassed: true,
+        riskScore: 55,
+        reviewBandLow: 40,
+        reviewBandHigh: 70,
+        ..denied_input(Address::ZERO, [Address::ZERO; GOVERNANCE_SET_LEN])
+    };
+
+    let held = evaluate(&input);
+    assert!(!held.allowed);
+    assert!(held.needsReview);
+    assert_eq!(held.reasons, ReasonCode::ReviewRequired.bit());
+    assert!(explain(&held).contains("review:      required\n"));
+
+    let clear = evaluate(&ComplianceInput {
+        riskScore: 70,
+        ..input.clone()
+    });
+    assert!(clear.allowed);
+    assert!(!clear.needsReview);
+
+    // An approved review is a governance override of the held decision.
+    let governor = PrivateKeySigner::random();
+    let approved = evaluate(&overridden(
+        ComplianceInput {
+            governance: [governor.address(); GOVERNANCE_SET_LEN],
+            ..input
+        },
+        &governor,
+    ));
+    assert!(approved.allowed);
+    assert!(!approved.needsReview);
+    assert_eq!(
+        approved.reasons,
+        ReasonCode::ReviewRequired.bit() | ReasonCode::Override.bit()
+    );
+}
+
+#[test]
+fn regimes_require_their_own_claims_and_are_committed() {
+    let product_claims = U256::from(0b0101);
+    let input = |regime: Regime, claims: U256| ComplianceInput {
+        amlPassed: true,
+        claims,
+        requiredClaims: product_claims,
+        regimeId: regime as u16,
+        ..denied_input(Address::ZERO, [Address::ZERO; GOVERNANCE_SET_LEN])
+    };
+    let mica_user = product_claims | CLAIM_MICA_APPROPRIATENESS;
+
+    let mica = evaluate(&input(Regime::Mica, mica_user));
+    assert!(mica.allowed);
+    assert_eq!(mica.regimeId, Regime::Mica as u16);
+    assert!(explain(&mica).contains("regime:      mica (1)\n"));
+
+    // The same user falls short under SEC rules, which require accreditation instead.
+    let sec = evaluate(&input(Regime::Sec, mica_user));
+    assert!(!sec.allowed);
+    assert_eq!(sec.reasons, ReasonCode::ClaimsMissing.bit());
+    assert_eq!(sec.regimeId, Regime::Sec as u16);
+    assert!(
+        evaluate(&input(
+            Regime::Sec,
+            product_claims | CLAIM_SEC_ACCREDITED_INVESTOR
+        ))
+        .allowed
+    );
+
+    // Without a regime only the product's claims count, and an unknown one denies the trade.
+    assert!(evaluate(&input(Regime::None, product_claims)).allowed);
+    let unknown = evaluate(&ComplianceInput {
+        regimeId: 99,
+        ..input(Regime::None, mica_user)
+    });
+    assert_eq!(unknown.reasons, ReasonCode::RegimeUnknown.bit());
+    assert!(explain(&unknown).contains("regime:      unknown (99)\n"));
+
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let decoded = ComplianceJournal::try_from(sec.encode(encoding).as_slice()).unwrap();
+        assert_eq!(decoded, sec, "{}", encoding.name());
+    }
+}
+
+#[test]
+fn settlement_token_is_committed_without_affecting_the_decision() {
+    let usdc = Address::repeat_byte(0xc0);
+    let input = ComplianceInput {
+        amlPassed: true,
+        settlementToken: usdc,
+        caseId: B256::ZERO,
+        ..denied_input(Address::ZERO, [Address::ZERO; GOVERNANCE_SET_LEN])
+    };
+
+    // Whether the token is allowed is the hook's call, per product.
+    let journal = evaluate(&input);
+    assert!(journal.allowed);
+    assert_eq!(journal.settlementToken, usdc);
+    assert!(explain(&journal).contains(&format!("settles in:  {usdc}\n")));
+
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let decoded = ComplianceJournal::try_from(journal.encode(encoding).as_slice()).unwrap();
+        assert_eq!(decoded.settlementToken, usdc, "{}", encoding.name());
+    }
+}
+
+#[test]
+fn case_id_is_echoed_without_affecting_the_decision() {
+    let case_id = B256::repeat_byte(0xca);
+    let without_case = ComplianceInput {
+        amlPassed: true,
+        ..denied_input(Address::ZERO, [Address::ZERO; GOVERNANCE_SET_LEN])
+    };
+    let input = ComplianceInput {
+        caseId: case_id,
+        ..without_case.clone()
+    };
+
+    let journal = evaluate(&input);
+    assert_eq!(journal.caseId, case_id);
+    assert_eq!(
+        ComplianceJournal {
+            caseId: B256::ZERO,
+            ..journal.clone()
+        },
+        evaluate(&without_case)
+    );
+    assert!(explain(&journal).contains(&format!("case:        {case_id}\n")));
+
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let decoded = ComplianceJournal::try_from(journal.encode(encoding).as_slice()).unwrap();
+        assert_eq!(decoded, journal, "{}", encoding.name());
+    }
+}
+
+#[test]
+fn trade_outside_the_size_band_is_denied() {
+    let band = ComplianceInput {
+        amlPassed: true,
+        minTrade: U256::from(100),
+        maxTrade: U256::from(1_000),
+        ..denied_input(Address::ZERO, [Address::ZERO; GOVERNANCE_SET_LEN])
+    };
+    let at = |amount: u64| {
+        evaluate(&ComplianceInput {
+            tradeAmount: U256::from(amount),
+            ..band.clone()
+        })
+    };
+
+    // Both bounds are inclusive.
+    for amount in [100, 550, 1_000] {
+        assert!(at(amount).allowed, "amount {amount}");
+    }
+    for amount in [0, 99, 1_001] {
+        let journal = at(amount);
+        assert!(!journal.allowed, "amount {amount}");
+        assert_eq!(journal.reasons, ReasonCode::TradeSizeOutOfRange.bit());
+    }
+
+    let journal = at(1_001);
+    assert_eq!(
+        (journal.tradeAmount, journal.minTrade, journal.maxTrade),
+        (U256::from(1_001), U256::from(100), U256::from(1_000))
+    );
+    let explained = explain(&journal);
+    assert!(explained.contains("amount:      1001\n"));
+    assert!(explained.contains("size band:   100 to 1000\n"));
+    assert!(explained.contains("reasons:     trade_size_out_of_range\n"));
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let decoded = ComplianceJournal::try_from(journal.encode(encoding).as_slice()).unwrap();
+        assert_eq!(decoded, journal, "{}", encoding.name());
+    }
+}
+
+#[test]
+fn screening_must_cover_the_required_aml_sources() {
+    let required = ComplianceInput {
+        amlPassed: true,
+        requiredAmlSources: AML_SOURCE_SANCTIONS | AML_SOURCE_PEP,
+        ..denied_input(Address::ZERO, [Address::ZERO; GOVERNANCE_SET_LEN])
+    };
+    let screened = |sources: U256| {
+        evaluate(&ComplianceInput {
+            amlSources: sources,
+            ..required.clone()
+        })
+    };
+
+    assert!(screened(AML_SOURCE_SANCTIONS | AML_SOURCE_PEP).allowed);
+    let journal = screened(AML_SOURCE_SANCTIONS);
+    assert!(!journal.allowed);
+    assert_eq!(journal.reasons, ReasonCode::AmlSourcesMissing.bit());
+    assert_eq!(journal.amlSources, AML_SOURCE_SANCTIONS);
+    let explained = explain(&journal);
+    assert!(explained.contains("reasons:     aml_sources_missing\n"));
+    assert!(explained.contains("aml sources: 0b1\n"));
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let decoded = ComplianceJournal::try_from(journal.encode(encoding).as_slice()).unwrap();
+        assert_eq!(decoded, journal, "{}", encoding.name());
+    }
+}
+
+#[test]
+fn ttl_round_trips_without_affecting_the_decision() {
+    let without_ttl = ComplianceInput {
+        amlPassed: true,
+        ..denied_input(Address::ZERO, [Address::ZERO; GOVERNANCE_SET_LEN])
+    };
+    for ttl_secs in [1, 3_600, u64::MAX] {
+        let input = ComplianceInput {
+            ttlSecs: ttl_secs,
+            ..without_ttl.clone()
+        };
+
+        let journal = evaluate(&input);
+        assert_eq!(journal.ttlSecs, ttl_secs);
+        assert_eq!(
+            ComplianceJournal {
+                ttlSecs: 0,
+                ..journal.clone()
+            },
+            evaluate(&without_ttl)
+        );
+        for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+            let decoded = ComplianceJournal::try_from(journal.encode(encoding).as_slice()).unwrap();
+            assert_eq!(decoded.ttlSecs, ttl_secs, "{}", encoding.name());
+        }
+    }
+
+    let journal = evaluate(&ComplianceInput {
+        ttlSecs: 3_600,
+        ..without_ttl.clone()
+    });
+    assert!(explain(&journal).contains("valid until: 2023-11-14T23:13:20Z (3600s)\n"));
+    assert!(!explain(&evaluate(&without_ttl)).contains("valid until"));
+}
+
+#[test]
+fn decision_uuid_round_trips_without_affecting_the_decision() {
+    let without_uuid = ComplianceInput {
+        amlPassed: true,
+        ..denied_input(Address::ZERO, [Address::ZERO; GOVERNANCE_SET_LEN])
+    };
+    let decision_uuid = B256::repeat_byte(0xd1);
+    let input = ComplianceInput {
+        decisionUuid: decision_uuid,
+        ..without_uuid.clone()
+    };
+
+    let journal = evaluate(&input);
+    assert_eq!(journal.decisionUuid, decision_uuid);
+    assert_eq!(
+        ComplianceJournal {
+            decisionUuid: B256::ZERO,
+            credentialHash: B256::ZERO,
+            ..journal.clone()
+        },
+        evaluate(&without_uuid)
+    );
+    assert!(explain(&journal).contains(&format!("decision:    {decision_uuid}\n")));
+    assert!(!explain(&evaluate(&without_uuid)).contains("decision:"));
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let decoded = ComplianceJournal::try_from(journal.encode(encoding).as_slice()).unwrap();
+        assert_eq!(decoded, journal, "{}", encoding.name());
+    }
+}
+
+#[test]
+fn block_hash_round_trips_without_affecting_the_decision() {
+    let without_block = ComplianceInput {
+        amlPassed: true,
+        ..denied_input(Address::ZERO, [Address::ZERO; GOVERNANCE_SET_LEN])
+    };
+    let block_hash = B256::repeat_byte(0xb1);
+    let input = ComplianceInput {
+        blockHash: block_hash,
+        ..without_block.clone()
+    };
+
+    let journal = evaluate(&input);
+    assert_eq!(journal.blockHash, block_hash);
+    assert_eq!(
+        ComplianceJournal {
+            blockHash: B256::ZERO,
+            ..journal.clone()
+        },
+        evaluate(&without_block)
+    );
+    assert!(explain(&journal).contains(&format!("block hash:  {block_hash}\n")));
+    assert!(!explain(&evaluate(&without_block)).contains("block hash:"));
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let decoded = ComplianceJournal::try_from(journal.encode(encoding).as_slice()).unwrap();
+        assert_eq!(decoded.blockHash, block_hash, "{}", encoding.name());
+    }
+}
+
+#[test]
+fn kyc_must_come_from_an_allowed_provider() {
+    let input = ComplianceInput {
+        amlPassed: true,
+        kycProvider: 7,
+        ..denied_input(Address::ZERO, [Address::ZERO; GOVERNANCE_SET_LEN])
+    };
+    let allowing = |providers: [u16; KYC_PROVIDER_SET_LEN], kyc_provider: u16| {
+        evaluate(&ComplianceInput {
+            kycProvider: kyc_provider,
+            allowedKycProviders: providers,
+            ..input.clone()
+        })
+    };
+
+    assert!(allowing([0; KYC_PROVIDER_SET_LEN], 7).allowed);
+    assert!(allowing([0; KYC_PROVIDER_SET_LEN], 0).allowed);
+    let journal = allowing([3, 7, 0, 0], 7);
+    assert!(journal.allowed);
+    assert_eq!(journal.kycProvider, 7);
+    assert!(explain(&journal).contains("kyc provider: 7\n"));
+
+    for kyc_provider in [9, 0] {
+        let journal = allowing([3, 7, 0, 0], kyc_provider);
+        assert!(!journal.allowed, "provider {kyc_provider}");
+        assert_eq!(journal.reasons, ReasonCode::KycProviderNotAllowed.bit());
+        assert_eq!(journal.kycProvider, kyc_provider);
+    }
+    assert!(!explain(&allowing([3, 7, 0, 0], 0)).contains("kyc provider:"));
+    assert!(explain(&allowing([3, 7, 0, 0], 9)).contains("reasons:     kyc_provider_not_allowed\n"));
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let decoded = ComplianceJournal::try_from(journal.encode(encoding).as_slice()).unwrap();
+        assert_eq!(decoded, journal, "{}", encoding.name());
+    }
+}
+
+#[test]
+fn commits_the_hash_of_the_published_layout() {
+    let document = include_bytes!("../../core/schemas/journal.v3.json");
+    assert_eq!(
+        B256::from_slice(&Sha256::digest(document)),
+        JOURNAL_SCHEMA_HASH
+    );
+    let layout: serde_json::Value = serde_json::from_slice(document).unwrap();
+    assert_eq!(layout["version"], JOURNAL_SCHEMA_VERSION);
+    assert_eq!(layout["encodings"]["abi"]["bytes"], ComplianceJournal::LEN);
+    assert_eq!(
+        layout["encodings"]["compact"]["bytes"],
+        ComplianceJournal::COMPACT_LEN
+    );
+    // The document lists the journal's fields, in order, as the guest commits them.
+    let fields = layout["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| {
+            format!(
+                "{} {}",
+                field["type"].as_str().unwrap(),
+                field["name"].as_str().unwrap()
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        format!("ComplianceJournal({})", fields.join(",")),
+        ComplianceJournal::eip712_encode_type()
+    );
+
+    let journal = evaluate(&denied_input(
+        Address::ZERO,
+        [Address::ZERO; GOVERNANCE_SET_LEN],
+    ));
+    assert_eq!(journal.schemaHash, JOURNAL_SCHEMA_HASH);
+
+    // Every published layout stays decodable.
+    let published: [&[u8]; 3] = [
+        include_bytes!("../../core/schemas/journal.v1.json"),
+        include_bytes!("../../core/schemas/journal.v2.json"),
+        document,
+    ];
+    for (schema, document) in JOURNAL_SCHEMAS.iter().zip(published) {
+        assert_eq!(B256::from_slice(&Sha256::digest(document)), schema.hash);
+        let layout: serde_json::Value = serde_json::from_slice(document).unwrap();
+        assert_eq!(layout["version"], schema.version);
+    }
+}
+
+#[test]
+fn schema_hash_picks_the_decoder() {
+    let journal = ComplianceJournal::abi_decode(&journal(compliance_image_id())).unwrap();
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let bytes = journal.encode(encoding);
+        let schema = JournalSchema::of(&bytes).unwrap();
+        assert_eq!(
+            schema.version,
+            JOURNAL_SCHEMA_VERSION,
+            "{}",
+            encoding.name()
+        );
+        assert_eq!(
+            (schema.decode)(&bytes).unwrap(),
+            journal,
+            "{}",
+            encoding.name()
+        );
+    }
+
+    let unknown = ComplianceJournal {
+        schemaHash: B256::repeat_byte(1),
+        ..journal
+    };
+    let err = JournalSchema::of(&unknown.abi_encode()).unwrap_err();
+    assert!(err.to_string().contains("unknown schema hash"), "{err}");
+    let err = JournalSchema::of(&[0; 63]).unwrap_err();
+    assert!(err.to_string().contains("too short"), "{err}");
+}
+
+#[test]
+fn decodes_journals_committed_before_consent() {
+    let journal = ComplianceJournal {
+        schemaHash: JOURNAL_SCHEMAS[0].hash,
+        ..ComplianceJournal::abi_decode(&journal(compliance_image_id())).unwrap()
+    };
+    // The v1 layout is the current one without `consentGiven`, `consentAt` and
+    // `attestationSignatureHash`.
+    let mut abi = journal.encode(JournalEncoding::Abi);
+    abi.drain(1024..1120);
+    let mut compact = journal.encode(JournalEncoding::Compact);
+    compact.drain(575..616);
+
+    for bytes in [abi, compact] {
+        let schema = JournalSchema::of(&bytes).unwrap();
+        assert_eq!(schema.version, 1);
+        assert_eq!((schema.decode)(&bytes).unwrap(), journal);
+    }
+    let err = (JOURNAL_SCHEMAS[0].decode)(&[0; 1000]).unwrap_err();
+    assert!(err.to_string().contains("1000"), "{err}");
+}
+
+#[test]
+fn decodes_journals_committed_before_the_attestation_signature() {
+    let journal = ComplianceJournal {
+        schemaHash: JOURNAL_SCHEMAS[1].hash,
+        ..ComplianceJournal::abi_decode(&journal(compliance_image_id())).unwrap()
+    };
+    // The v2 layout is the current one without `attestationSignatureHash`.
+    let mut abi = journal.encode(JournalEncoding::Abi);
+    abi.drain(1088..1120);
+    let mut compact = journal.encode(JournalEncoding::Compact);
+    compact.drain(584..616);
+
+    for bytes in [abi, compact] {
+        let schema = JournalSchema::of(&bytes).unwrap();
+        assert_eq!(schema.version, 2);
+        assert_eq!((schema.decode)(&bytes).unwrap(), journal);
+    }
+    let err = (JOURNAL_SCHEMAS[1].decode)(&[0; 1000]).unwrap_err();
+    assert!(err.to_string().contains("1000"), "{err}");
+}
+
+#[test]
+fn consent_is_required_where_the_product_asks_for_it() {
+    let input = ComplianceInput {
+        amlPassed: true,
+        ..denied_input(Address::ZERO, [Address::ZERO; GOVERNANCE_SET_LEN])
+    };
+    let deciding = |consent_given: bool, consent_at: u64, consent_required: bool| {
+        evaluate(&ComplianceInput {
+            consentGiven: consent_given,
+            consentAt: consent_at,
+            consentRequired: consent_required,
+            ..input.clone()
+        })
+    };
+
+    assert!(deciding(false, 0, false).allowed);
+    let journal = deciding(true, 1_699_999_000, true);
+    assert!(journal.allowed);
+    assert_eq!(
+        (journal.consentGiven, journal.consentAt),
+        (true, 1_699_999_000)
+    );
+    assert!(explain(&journal).contains("consent:     given at 2023-11-14T21:56:40Z (1699999000)\n"));
+
+    // Not given, given at no recorded time, or recorded after the decision.
+    for (consent_given, consent_at) in [(false, 1_699_999_000), (true, 0), (true, 1_700_000_001)] {
+        let journal = deciding(consent_given, consent_at, true);
+        assert!(!journal.allowed, "{consent_given} at {consent_at}");
+        assert_eq!(journal.reasons, ReasonCode::ConsentMissing.bit());
+        assert_eq!(
+            (journal.consentGiven, journal.consentAt),
+            (consent_given, consent_at)
+        );
+    }
+    let denied = deciding(false, 0, true);
+    assert!(explain(&denied).contains("reasons:     consent_missing\n"));
+    assert!(!explain(&denied).contains("consent:"));
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let decoded = ComplianceJournal::try_from(journal.encode(encoding).as_slice()).unwrap();
+        assert_eq!(decoded, journal, "{}", encoding.name());
+    }
+}
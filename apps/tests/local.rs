@@ -0,0 +1,159 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::{
+    io::Write,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alloy::primitives::B256;
+use app::{
+    batch::{run_batch, BatchEntry, BatchOptions, Concurrency, Outcome},
+    executor::ExecutorOptions,
+    fixtures::dev_mode_seal,
+    journal::{compliance_image_id, ComplianceJournal},
+    local::{prove_jobs, segment_memory_mib, LocalProver},
+    MarketClient,
+};
+use boundless_market::request_builder::RequestParams;
+use common::MockSettler;
+use tokio_util::sync::CancellationToken;
+
+const DECISION_AT: u64 = 1_700_000_000;
+
+/// Log output of the whole test binary.
+static LOGS: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+struct Logs;
+
+impl Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        LOGS.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn entry(i: u8) -> BatchEntry {
+    BatchEntry {
+        product_id: B256::with_last_byte(i),
+        ..common::entry(i)
+    }
+}
+
+fn jobs(count: usize) -> NonZeroUsize {
+    NonZeroUsize::new(count).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn proves_entries_in_parallel_with_logs_per_entry() {
+    std::env::set_var("RISC0_DEV_MODE", "1");
+    tracing_subscriber::fmt()
+        .with_writer(|| Logs)
+        .with_ansi(false)
+        .init();
+
+    const ENTRIES: u8 = 4;
+    let prover = LocalProver::new(
+        jobs(ENTRIES.into()),
+        ExecutorOptions::default(),
+        CancellationToken::new(),
+    );
+    let settler = Arc::new(MockSettler::new(Duration::ZERO));
+    let entries = (1..=ENTRIES)
+        .map(|i| {
+            let entry = entry(i);
+            let request = RequestParams::new().with_stdin(entry.stdin(DECISION_AT));
+            (entry, request)
+        })
+        .collect();
+    let limits = Concurrency {
+        proofs: jobs(ENTRIES.into()),
+        settlements: jobs(1),
+    };
+
+    let reports = run_batch(
+        Arc::new([prover]),
+        settler.clone(),
+        entries,
+        DECISION_AT,
+        limits,
+        BatchOptions::default(),
+    )
+    .await;
+
+    for (entry, journal, seal) in settler.settled() {
+        let decoded = ComplianceJournal::try_from(journal.as_ref()).unwrap();
+        assert_eq!(decoded.user, entry.user);
+        assert!(decoded.allowed);
+        assert_eq!(seal, dev_mode_seal(compliance_image_id(), &journal));
+    }
+    let logs = String::from_utf8(LOGS.lock().unwrap().clone()).unwrap();
+    for report in &reports {
+        let Outcome::Settled { request_id, .. } = &report.outcome else {
+            panic!("entry {} failed: {:?}", report.index, report.outcome);
+        };
+        let proved = format!("Proved request {request_id:x}");
+        let line = logs
+            .lines()
+            .find(|line| line.contains(&proved))
+            .unwrap_or_else(|| panic!("no log line for request {request_id:x}:\n{logs}"));
+        assert!(
+            line.contains(&format!("entry{{index={} ", report.index)),
+            "{line}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn cancellation_fails_requests_waiting_for_a_job() {
+    let cancel = CancellationToken::new();
+    let prover = LocalProver::new(jobs(1), ExecutorOptions::default(), cancel.clone());
+    let request = RequestParams::new().with_stdin(entry(1).stdin(DECISION_AT));
+    let (request_id, expires_at) = prover.submit_onchain(request).await.unwrap();
+
+    cancel.cancel();
+    let err = prover
+        .wait_for_request_fulfillment(request_id, Duration::ZERO, expires_at)
+        .await
+        .unwrap_err();
+
+    assert!(format!("{err:#}").contains("cancelled"), "{err:#}");
+}
+
+#[test]
+fn memory_limit_lowers_the_number_of_jobs() {
+    let options = ExecutorOptions {
+        segment_limit_po2: Some(18),
+        session_limit: None,
+    };
+    let per_job = segment_memory_mib(18);
+
+    assert_eq!(prove_jobs(Some(jobs(8)), None, &options), jobs(8));
+    assert_eq!(
+        prove_jobs(Some(jobs(8)), Some(3 * per_job + 1), &options),
+        jobs(3)
+    );
+    // Even a job that does not fit is run on its own rather than not at all.
+    assert_eq!(prove_jobs(Some(jobs(8)), Some(1), &options), jobs(1));
+    // Larger segments need more memory per job.
+    assert!(segment_memory_mib(20) > per_job);
+}
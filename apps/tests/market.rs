@@ -0,0 +1,345 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::time::Duration;
+
+use alloy::{
+    primitives::{address, Address, U256},
+    providers::ProviderBuilder,
+};
+use app::{
+    market::{
+        deployment_contracts, describe_deployment, have_code, known_deployments, request_id,
+        request_index, TrustedProvers,
+    },
+    prove_compliance, race_compliance,
+};
+use boundless_market::{
+    contracts::RequestId, deployments::NamedChain, request_builder::RequestParams, Deployment,
+};
+use common::{Channel, MockMarket, Step};
+use serde_json::{json, Value};
+use wiremock::{matchers::method, Mock, MockServer, Request, ResponseTemplate};
+
+fn request(stdin: &[u8]) -> RequestParams {
+    RequestParams::new().with_stdin(stdin.to_vec())
+}
+
+#[tokio::test(start_paused = true)]
+async fn fulfills_onchain_request() {
+    let market = MockMarket::new([Step::Fulfill(Duration::from_secs(1))]);
+
+    let fulfillment = prove_compliance(&market, request(b"input"), false)
+        .await
+        .unwrap();
+
+    assert_eq!(fulfillment.seal.as_ref(), b"input");
+    let submissions = market.submissions();
+    assert_eq!(submissions.len(), 1);
+    assert_eq!(submissions[0].channel, Channel::Onchain);
+    assert_eq!(submissions[0].stdin, b"input");
+}
+
+#[tokio::test(start_paused = true)]
+async fn offchain_flag_uses_order_stream() {
+    let market = MockMarket::new([Step::Fulfill(Duration::ZERO)]);
+
+    prove_compliance(&market, request(b"input"), true)
+        .await
+        .unwrap();
+
+    assert_eq!(market.submissions()[0].channel, Channel::Offchain);
+}
+
+#[tokio::test(start_paused = true)]
+async fn waits_through_slow_fulfillment() {
+    let market = MockMarket::new([Step::Fulfill(Duration::from_secs(60 * 60))]);
+
+    let fulfillment = prove_compliance(&market, request(b"slow"), false)
+        .await
+        .unwrap();
+
+    assert_eq!(fulfillment.seal.as_ref(), b"slow");
+}
+
+#[tokio::test(start_paused = true)]
+async fn reports_expired_request() {
+    let market = MockMarket::new([Step::Expire(Duration::from_secs(10))]);
+
+    let err = prove_compliance(&market, request(b"input"), false)
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().contains("was not fulfilled"));
+    assert!(format!("{err:#}").contains("Request has expired"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn reports_submission_transport_error() {
+    let market = MockMarket::new([Step::SubmitError("connection reset")]);
+
+    let err = prove_compliance(&market, request(b"input"), false)
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().contains("failed to submit request onchain"));
+    assert!(format!("{err:#}").contains("connection reset"));
+    assert!(market.submissions().is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn reports_transport_error_while_waiting() {
+    let market = MockMarket::new([Step::WaitError(Duration::from_secs(5), "rpc timed out")]);
+
+    let err = prove_compliance(&market, request(b"input"), false)
+        .await
+        .unwrap_err();
+
+    assert!(format!("{err:#}").contains("rpc timed out"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn out_of_order_fulfillments_reach_their_callers() {
+    let market = MockMarket::new([
+        Step::Fulfill(Duration::from_secs(30)),
+        Step::Fulfill(Duration::from_secs(1)),
+        Step::Fulfill(Duration::from_secs(10)),
+    ]);
+
+    let (first, second, third) = tokio::join!(
+        prove_compliance(&market, request(b"first"), false),
+        prove_compliance(&market, request(b"second"), false),
+        prove_compliance(&market, request(b"third"), false),
+    );
+
+    assert_eq!(first.unwrap().seal.as_ref(), b"first");
+    assert_eq!(second.unwrap().seal.as_ref(), b"second");
+    assert_eq!(third.unwrap().seal.as_ref(), b"third");
+}
+
+#[tokio::test(start_paused = true)]
+async fn submission_returns_the_precomputed_request_id() {
+    let signer = address!("0x00000000000000000000000000000000000000aa");
+    let index = request_index(b"input", 0);
+    let expected = request_id(signer, index);
+    let market = MockMarket::new([Step::Fulfill(Duration::ZERO)]);
+
+    let fulfillment = prove_compliance(
+        &market,
+        request(b"input").with_request_id(RequestId::new(signer, index)),
+        false,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(market.submissions()[0].request_id, expected);
+    assert_eq!(fulfillment.id, expected);
+}
+
+#[test]
+fn request_ids_are_derived_from_input_and_position() {
+    let signer = address!("0x00000000000000000000000000000000000000aa");
+
+    assert_eq!(request_index(b"input", 0), request_index(b"input", 0));
+    assert_ne!(request_index(b"input", 0), request_index(b"input", 1));
+    assert_ne!(request_index(b"input", 0), request_index(b"other", 0));
+    // The signer in the upper bits, the index in the lowest 32.
+    assert_eq!(request_id(signer, 7), U256::from(0xaa_0000_0007u64));
+}
+
+#[tokio::test(start_paused = true)]
+async fn fastest_redundant_market_wins() {
+    let markets = [
+        MockMarket::new([Step::Fulfill(Duration::from_secs(60))]),
+        MockMarket::new([Step::Fulfill(Duration::from_secs(5))]),
+    ];
+
+    let (winner, fulfillment) = race_compliance(&markets, request(b"input"), false)
+        .await
+        .unwrap();
+
+    assert_eq!(winner, 1);
+    assert_eq!(fulfillment.seal.as_ref(), b"input");
+    assert!(markets.iter().all(|market| market.submissions().len() == 1));
+}
+
+#[tokio::test(start_paused = true)]
+async fn race_survives_a_failing_market() {
+    let markets = [
+        MockMarket::new([Step::Expire(Duration::from_secs(1))]),
+        MockMarket::new([Step::Fulfill(Duration::from_secs(30))]),
+    ];
+
+    let (winner, _) = race_compliance(&markets, request(b"input"), false)
+        .await
+        .unwrap();
+
+    assert_eq!(winner, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn race_reports_every_market_failure() {
+    let markets = [
+        MockMarket::new([Step::SubmitError("connection reset")]),
+        MockMarket::new([Step::Expire(Duration::from_secs(1))]),
+    ];
+
+    let err = race_compliance(&markets, request(b"input"), false)
+        .await
+        .unwrap_err();
+
+    let message = err.to_string();
+    assert!(message.contains("market #0") && message.contains("connection reset"));
+    assert!(message.contains("market #1") && message.contains("Request has expired"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn accepts_fulfillments_by_allowed_provers() {
+    let prover = address!("0x00000000000000000000000000000000000000b1");
+    let market = TrustedProvers::new(
+        MockMarket::new([Step::Fulfill(Duration::ZERO)]).with_prover(prover),
+        vec![prover],
+    );
+
+    let fulfillment = prove_compliance(&market, request(b"input"), false)
+        .await
+        .unwrap();
+
+    assert_eq!(fulfillment.seal.as_ref(), b"input");
+}
+
+#[tokio::test(start_paused = true)]
+async fn rejects_fulfillments_by_untrusted_provers() {
+    let allowed = address!("0x00000000000000000000000000000000000000b1");
+    let untrusted = address!("0x00000000000000000000000000000000000000b2");
+    let market = TrustedProvers::new(
+        MockMarket::new([Step::Fulfill(Duration::ZERO)]).with_prover(untrusted),
+        vec![allowed],
+    );
+
+    let err = prove_compliance(&market, request(b"input"), false)
+        .await
+        .unwrap_err();
+
+    assert!(
+        format!("{err:#}").contains(&format!("prover {untrusted} is not one of the allowed")),
+        "{err:#}"
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn rejects_unknown_provers_only_when_provers_are_listed() {
+    let market = || MockMarket::new([Step::Fulfill(Duration::ZERO)]);
+    let listed = TrustedProvers::new(market(), vec![Address::with_last_byte(0xb1)]);
+
+    let err = prove_compliance(&listed, request(b"input"), false)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("does not record the prover"),
+        "{err:#}"
+    );
+
+    // Without a list, any prover is accepted and only logged.
+    let unlisted = TrustedProvers::new(market().with_prover(Address::with_last_byte(0xb2)), vec![]);
+    prove_compliance(&unlisted, request(b"input"), false)
+        .await
+        .unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn race_skips_an_untrusted_prover() {
+    let allowed = address!("0x00000000000000000000000000000000000000b1");
+    let markets = [
+        TrustedProvers::new(
+            MockMarket::new([Step::Fulfill(Duration::from_secs(1))])
+                .with_prover(Address::with_last_byte(0xb2)),
+            vec![allowed],
+        ),
+        TrustedProvers::new(
+            MockMarket::new([Step::Fulfill(Duration::from_secs(30))]).with_prover(allowed),
+            vec![allowed],
+        ),
+    ];
+
+    let (winner, _) = race_compliance(&markets, request(b"input"), false)
+        .await
+        .unwrap();
+
+    assert_eq!(winner, 1);
+}
+
+#[test]
+fn knows_the_library_deployments() {
+    let chains: Vec<_> = known_deployments()
+        .into_iter()
+        .map(|(chain, deployment)| {
+            assert_eq!(deployment.chain_id, Some(chain as u64));
+            chain
+        })
+        .collect();
+
+    assert_eq!(
+        chains,
+        [
+            NamedChain::Sepolia,
+            NamedChain::Base,
+            NamedChain::BaseSepolia
+        ]
+    );
+}
+
+#[tokio::test]
+async fn marks_contracts_without_code() {
+    let deployment = Deployment::from_chain(NamedChain::Sepolia).unwrap();
+    let set_verifier = deployment.set_verifier_address;
+    // Code everywhere except at the set verifier.
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(move |request: &Request| {
+            let request: Value = serde_json::from_slice(&request.body).unwrap();
+            let address: alloy::primitives::Address =
+                serde_json::from_value(request["params"][0].clone()).unwrap();
+            let code = if address == set_verifier {
+                "0x"
+            } else {
+                "0x6080"
+            };
+            ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": code,
+            }))
+        })
+        .mount(&server)
+        .await;
+    let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+
+    let addresses: Vec<_> = deployment_contracts(&deployment)
+        .into_iter()
+        .map(|(_, address)| address)
+        .collect();
+    let deployed = have_code(&provider, &addresses).await.unwrap();
+    let description = describe_deployment("sepolia", &deployment, Some(&deployed));
+
+    assert_eq!(deployed, [true, true, false, true]);
+    assert!(description.starts_with("sepolia (chain id 11155111)\n"));
+    assert!(description.contains(&format!("  set verifier:     {set_verifier}  no code\n")));
+    assert!(description.contains(&format!(
+        "  boundless market: {}  deployed\n",
+        deployment.boundless_market_address
+    )));
+}
@@ -0,0 +1,258 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use alloy::{
+    primitives::{keccak256, Address, B256},
+    sol_types::{sol_data, SolType},
+};
+use app::{
+    executor::ExecutorOptions,
+    journal::{
+        address_leaf, gap_leaf, hash_pair, merkle_root, merkle_root_with, Keccak, MerkleFrontier,
+        MerkleHash, MerkleTree, Sha256,
+    },
+    merkle::{parse_addresses, AddressProof, TreeFile, TreeKind},
+};
+use guests::KECCAK_BENCH_ELF;
+use sha2::Digest;
+
+fn leaves(count: u64) -> Vec<B256> {
+    (0..count).map(|i| keccak256(i.to_be_bytes())).collect()
+}
+
+#[test]
+fn hash_pair_sorts_before_hashing() {
+    let (low, high) = (B256::repeat_byte(1), B256::repeat_byte(2));
+    let expected = keccak256([low.0, high.0].concat());
+
+    assert_eq!(hash_pair(low, high), expected);
+    assert_eq!(hash_pair(high, low), expected);
+    let expected = B256::from_slice(&sha2::Sha256::digest([low.0, high.0].concat()));
+    assert_eq!(Sha256::hash_pair(high, low), expected);
+}
+
+#[test]
+fn proofs_from_host_built_tree_reach_its_root() {
+    // Seven leaves leave a node unpaired on the bottom two levels.
+    for count in [8, 7] {
+        let tree = MerkleTree::<Keccak>::new(leaves(count));
+
+        for (index, leaf) in tree.leaves().iter().enumerate() {
+            let proof = tree.proof(index).unwrap();
+            assert_eq!(
+                merkle_root(*leaf, &proof),
+                tree.root(),
+                "leaf {index} of {count}"
+            );
+        }
+        assert_eq!(tree.proof(count as usize), None);
+    }
+}
+
+#[test]
+fn frontier_reaches_the_root_of_the_same_tree() {
+    let mut frontier = MerkleFrontier::<Keccak>::default();
+    assert_eq!(frontier.root(), None);
+
+    for (count, leaf) in (1..).zip(leaves(40)) {
+        frontier.push(leaf);
+        let tree = MerkleTree::<Keccak>::new(leaves(count));
+        assert_eq!(frontier.root(), Some(tree.root()), "{count} leaves");
+    }
+}
+
+#[test]
+fn sha256_tree_proofs_reach_only_its_own_root() {
+    let keccak = MerkleTree::<Keccak>::new(leaves(8));
+    let sha256 = MerkleTree::<Sha256>::new(leaves(8));
+    assert_ne!(sha256.root(), keccak.root());
+
+    for (index, leaf) in sha256.leaves().iter().enumerate() {
+        let proof = sha256.proof(index).unwrap();
+        assert_eq!(merkle_root_with::<Sha256>(*leaf, &proof), sha256.root());
+        assert_eq!(proof.len(), keccak.proof(index).unwrap().len());
+        assert_ne!(merkle_root(*leaf, &proof), keccak.root(), "leaf {index}");
+    }
+}
+
+#[test]
+fn proof_through_sha256_subtree_reaches_keccak_root() {
+    // A guest-internal SHA-256 tree committed by its root as one leaf of an onchain keccak tree.
+    let subtree = MerkleTree::<Sha256>::new(leaves(4));
+    let mut anchored = leaves(8);
+    anchored[5] = subtree.root();
+    let tree = MerkleTree::<Keccak>::new(anchored);
+    let leaf = subtree.leaves()[2];
+    let (lower, upper) = (subtree.proof(2).unwrap(), tree.proof(5).unwrap());
+
+    let root = merkle_root(merkle_root_with::<Sha256>(leaf, &lower), &upper);
+    assert_eq!(root, tree.root());
+
+    // Either hash alone over the whole path misses the root.
+    let path = [lower, upper].concat();
+    assert_ne!(merkle_root_with::<Keccak>(leaf, &path), tree.root());
+    assert_ne!(merkle_root_with::<Sha256>(leaf, &path), tree.root());
+}
+
+fn listed() -> Vec<Address> {
+    [0x40, 0x10, 0x30, 0x20]
+        .map(|byte| Address::repeat_byte(byte))
+        .to_vec()
+}
+
+/// A path of its own for each test.
+fn tree_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+#[test]
+fn membership_proofs_verify_for_every_listed_address() {
+    let tree = TreeFile::build(listed(), TreeKind::Membership).unwrap();
+    assert_eq!(
+        tree.addresses,
+        listed(),
+        "membership trees keep the list's order"
+    );
+
+    for address in listed() {
+        let proof = tree.proof(address).unwrap();
+        assert!(proof.verify(), "{address}");
+        assert_eq!(proof.root(), tree.root);
+    }
+    let err = tree.proof(Address::repeat_byte(0x25)).unwrap_err();
+    assert!(err.to_string().contains("not in the tree"), "{err}");
+}
+
+#[test]
+fn sorted_tree_proves_unlisted_addresses_absent() {
+    let tree = TreeFile::build(listed(), TreeKind::Sorted).unwrap();
+    assert!(tree.addresses.windows(2).all(|pair| pair[0] < pair[1]));
+
+    // Below the first address, between two and above the last.
+    for byte in [0x01, 0x25, 0xfe] {
+        let address = Address::repeat_byte(byte);
+        let proof = tree.proof(address).unwrap();
+        assert!(proof.verify(), "{address}");
+        let AddressProof::NonMembership { low, high, .. } = proof else {
+            panic!("expected a non-membership proof");
+        };
+        assert!(low < address && address < high);
+    }
+    for address in listed() {
+        let err = tree.proof(address).unwrap_err();
+        assert!(err.to_string().contains("cannot be proven absent"), "{err}");
+    }
+}
+
+#[test]
+fn non_membership_proof_does_not_cover_listed_address() {
+    let tree = TreeFile::build(listed(), TreeKind::Sorted).unwrap();
+    let AddressProof::NonMembership {
+        root,
+        low,
+        high,
+        proof,
+        ..
+    } = tree.proof(Address::repeat_byte(0x25)).unwrap()
+    else {
+        panic!("expected a non-membership proof");
+    };
+
+    assert_eq!(
+        (low, high),
+        (Address::repeat_byte(0x20), Address::repeat_byte(0x30))
+    );
+
+    // The gap's bounds are listed, so its proof says nothing about them.
+    let forged = AddressProof::NonMembership {
+        root,
+        address: low,
+        low,
+        high,
+        proof,
+    };
+    assert!(!forged.verify());
+}
+
+#[test]
+fn read_rejects_tampered_trees() {
+    let tree = TreeFile::build(listed(), TreeKind::Membership).unwrap();
+    let path = tree_path("tree.json");
+    tree.write(&path).unwrap();
+    assert_eq!(TreeFile::read(&path).unwrap(), tree);
+
+    let mut tampered = tree.clone();
+    tampered.addresses.push(Address::repeat_byte(0x50));
+    let path = tree_path("tampered.json");
+    tampered.write(&path).unwrap();
+
+    let err = TreeFile::read(&path).unwrap_err();
+    assert!(
+        err.to_string().contains("does not match its address list"),
+        "{err}"
+    );
+}
+
+#[test]
+fn parses_address_lists() {
+    let text = "# sanctioned\n\n0x1010101010101010101010101010101010101010\n\
+                0x2020202020202020202020202020202020202020 # added 2024-05-01\n";
+
+    assert_eq!(
+        parse_addresses(text).unwrap(),
+        [Address::repeat_byte(0x10), Address::repeat_byte(0x20)]
+    );
+    let twice = format!("{text}0x1010101010101010101010101010101010101010\n");
+    let err = parse_addresses(&twice).unwrap_err();
+    assert!(err.to_string().contains("line 5"), "{err}");
+    assert!(err.to_string().contains("listed twice"), "{err}");
+    let err = parse_addresses("0x1234\n").unwrap_err();
+    assert!(err.to_string().contains("line 1: invalid address"), "{err}");
+}
+
+#[test]
+fn guest_reaches_root_of_generated_proofs() {
+    let membership = TreeFile::build(listed(), TreeKind::Membership).unwrap();
+    let sorted = TreeFile::build(listed(), TreeKind::Sorted).unwrap();
+    let proofs = [
+        membership.proof(Address::repeat_byte(0x30)).unwrap(),
+        sorted.proof(Address::repeat_byte(0x25)).unwrap(),
+    ];
+
+    for proof in proofs {
+        let (leaf, nodes) = match &proof {
+            AddressProof::Membership { address, proof, .. } => (address_leaf(*address), proof),
+            AddressProof::NonMembership {
+                low, high, proof, ..
+            } => (gap_leaf(*low, *high), proof),
+        };
+        let frame: Vec<u8> = [leaf].iter().chain(nodes).flat_map(|node| node.0).collect();
+
+        let execution = ExecutorOptions::default()
+            .execute(KECCAK_BENCH_ELF, &frame)
+            .unwrap();
+        let (root, ..) = <(
+            sol_data::FixedBytes<32>,
+            sol_data::FixedBytes<32>,
+            sol_data::FixedBytes<32>,
+            sol_data::Uint<64>,
+            sol_data::Uint<64>,
+            sol_data::Uint<64>,
+        )>::abi_decode(&execution.journal)
+        .unwrap();
+        assert_eq!(root, proof.root(), "{proof:?}");
+    }
+}
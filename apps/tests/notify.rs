@@ -0,0 +1,244 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::time::Duration;
+
+use alloy::primitives::U256;
+use anyhow::anyhow;
+use app::{
+    notify::{Alert, Event, Links, Notifications, PagerDutyNotifier, Severity, SlackNotifier},
+    webhook::{sign, WebhookSink, SIGNATURE_HEADER},
+};
+use boundless_market::contracts::boundless_market::MarketError;
+use common::fast_retries;
+use serde_json::{json, Value};
+use url::Url;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const WINDOW: Duration = Duration::from_secs(600);
+
+fn reverted(request_id: u64) -> Alert {
+    Alert::new(
+        Event::SettlementFailed,
+        Some(U256::from(request_id)),
+        "execution reverted",
+    )
+}
+
+fn links() -> Links {
+    Links {
+        explorer: Some(Url::parse("https://sepolia.etherscan.io").unwrap()),
+        request_explorer: Some(Url::parse("https://explorer.example/orders").unwrap()),
+    }
+}
+
+async fn server(status: u16) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(status))
+        .mount(&server)
+        .await;
+    server
+}
+
+async fn bodies(server: &MockServer) -> Vec<Value> {
+    server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect()
+}
+
+fn slack(server: &MockServer) -> SlackNotifier {
+    SlackNotifier::new(Url::parse(&server.uri()).unwrap()).with_policy(fast_retries())
+}
+
+fn pagerduty(server: &MockServer) -> PagerDutyNotifier {
+    PagerDutyNotifier::new("routing-key".into())
+        .with_url(Url::parse(&format!("{}/v2/enqueue", server.uri())).unwrap())
+        .with_policy(fast_retries())
+}
+
+#[tokio::test]
+async fn slack_message_carries_request_and_link() {
+    let server = server(200).await;
+    let notifications = Notifications::new(WINDOW).with_links(links()).route(
+        "Slack",
+        Severity::Warning,
+        slack(&server),
+    );
+
+    notifications.notify(reverted(7)).await;
+
+    assert_eq!(
+        bodies(&server).await,
+        [json!({
+            "text": "*[critical] Settlement failed for request 0x7*\nexecution reverted\n\
+                     <https://explorer.example/orders/0x7|View in explorer>",
+        })]
+    );
+}
+
+#[tokio::test]
+async fn pagerduty_event_is_deduplicated_by_request() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v2/enqueue"))
+        .respond_with(ResponseTemplate::new(202))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let notifications = Notifications::new(WINDOW).with_links(links()).route(
+        "PagerDuty",
+        Severity::Critical,
+        pagerduty(&server),
+    );
+
+    notifications.notify(reverted(7)).await;
+
+    let body = &bodies(&server).await[0];
+    assert_eq!(body["routing_key"], "routing-key");
+    assert_eq!(body["event_action"], "trigger");
+    assert_eq!(body["dedup_key"], "Settlement failed:0x7");
+    assert_eq!(
+        body["payload"]["summary"],
+        "[critical] Settlement failed for request 0x7: execution reverted"
+    );
+    assert_eq!(body["payload"]["severity"], "critical");
+    assert_eq!(body["payload"]["custom_details"]["requestId"], "0x7");
+    assert_eq!(
+        body["links"],
+        json!([{ "href": "https://explorer.example/orders/0x7", "text": "View in explorer" }])
+    );
+}
+
+#[tokio::test]
+async fn alerts_are_routed_by_severity() {
+    // Slack takes warnings and up but rejects everything; PagerDuty only takes critical alerts.
+    let slack_server = server(400).await;
+    let pagerduty_server = server(202).await;
+    let notifications = Notifications::new(WINDOW)
+        .route("Slack", Severity::Warning, slack(&slack_server))
+        .route(
+            "PagerDuty",
+            Severity::Critical,
+            pagerduty(&pagerduty_server),
+        );
+
+    notifications
+        .notify(Alert::new(Event::Settled, Some(U256::from(1)), "settled"))
+        .await;
+    notifications
+        .notify(Alert::new(
+            Event::ProofFailed,
+            Some(U256::from(2)),
+            "guest panicked",
+        ))
+        .await;
+    notifications.notify(reverted(3)).await;
+
+    let slack: Vec<_> = bodies(&slack_server).await;
+    assert_eq!(slack.len(), 2);
+    assert!(slack[0]["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("*[warning] Proof failed for request 0x2*"));
+    let pagerduty = bodies(&pagerduty_server).await;
+    assert_eq!(pagerduty.len(), 1);
+    assert_eq!(pagerduty[0]["dedup_key"], "Settlement failed:0x3");
+}
+
+#[tokio::test]
+async fn repeated_alerts_are_dropped_within_the_window() {
+    let windowed = server(200).await;
+    let notifications = Notifications::new(WINDOW).route("Slack", Severity::Info, slack(&windowed));
+
+    notifications.notify(reverted(7)).await;
+    notifications.notify(reverted(7)).await;
+    notifications.notify(reverted(8)).await;
+    notifications
+        .notify(Alert::new(
+            Event::ProofFailed,
+            Some(U256::from(7)),
+            "timed out",
+        ))
+        .await;
+
+    assert_eq!(bodies(&windowed).await.len(), 3);
+
+    let unwindowed = server(200).await;
+    let notifications =
+        Notifications::new(Duration::ZERO).route("Slack", Severity::Info, slack(&unwindowed));
+    notifications.notify(reverted(7)).await;
+    notifications.notify(reverted(7)).await;
+
+    assert_eq!(bodies(&unwindowed).await.len(), 2);
+}
+
+#[tokio::test]
+async fn webhook_receives_signed_alerts() {
+    let server = server(204).await;
+    let webhook = WebhookSink::new(Url::parse(&server.uri()).unwrap(), Some("shh".into()))
+        .with_policy(fast_retries());
+    let notifications = Notifications::new(WINDOW).with_links(links()).route(
+        "the webhook",
+        Severity::Info,
+        webhook,
+    );
+
+    notifications.notify(reverted(7)).await;
+
+    let requests = server.received_requests().await.unwrap();
+    let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(
+        body,
+        json!({
+            "event": "settlement_failed",
+            "severity": "critical",
+            "requestId": "0x7",
+            "txHash": null,
+            "message": "execution reverted",
+            "link": "https://explorer.example/orders/0x7",
+        })
+    );
+    assert_eq!(
+        requests[0].headers[SIGNATURE_HEADER],
+        sign(b"shh", &requests[0].body).as_str()
+    );
+}
+
+#[test]
+fn expired_requests_are_told_apart_from_failed_proofs() {
+    let expired = anyhow!(MarketError::RequestHasExpired(U256::from(7)))
+        .context("request 7 was not fulfilled");
+    let failed = anyhow!("guest panicked").context("request 7 was not fulfilled");
+
+    let expired = Alert::proof_failed(Some(U256::from(7)), &expired);
+    let failed = Alert::proof_failed(Some(U256::from(7)), &failed);
+
+    assert_eq!(expired.event, Event::RequestExpired);
+    assert_eq!(expired.severity, Severity::Warning);
+    assert_eq!(failed.event, Event::ProofFailed);
+    assert_eq!(
+        failed.message,
+        "request 7 was not fulfilled: guest panicked"
+    );
+}
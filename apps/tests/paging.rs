@@ -0,0 +1,256 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use alloy::{
+    primitives::{Address, B256, U256},
+    sol_types::SolValue,
+};
+use app::{
+    batch::BatchEntry,
+    journal::{
+        batch_pages, cohort_leaf, evaluate, fold_digest, BatchFold, BatchJournal, ComplianceInput,
+        InputError, JournalEncoding, MerkleTree, PageError, MAX_BATCH_PAGES, MAX_PAGE_INPUTS,
+    },
+    merkle::{cohort, cohort_proof, AddressProof},
+};
+
+const DECISION_AT: u64 = 1_700_000_000;
+
+fn inputs(count: usize) -> Vec<ComplianceInput> {
+    (0..count)
+        .map(|i| {
+            BatchEntry {
+                product_id: B256::with_last_byte(1),
+                aml_passed: i % 3 != 0,
+                compact_journal: i % 2 == 0,
+                ..common::entry(i as u8)
+            }
+            .input(DECISION_AT)
+        })
+        .collect()
+}
+
+fn fold(pages: u32, sent: &[Vec<u8>]) -> BatchJournal {
+    fold_capped(pages, sent, U256::MAX)
+}
+
+fn fold_capped(pages: u32, sent: &[Vec<u8>], cap: U256) -> BatchJournal {
+    let mut fold = BatchFold::new(pages, cap);
+    for page in sent {
+        fold.push_page(page).unwrap();
+    }
+    fold.finish()
+}
+
+#[test]
+fn multi_page_batch_folds_every_input_in_order() {
+    let inputs = inputs(2 * MAX_PAGE_INPUTS + 22);
+    let pages = batch_pages(&inputs);
+    assert_eq!(pages.len(), 3);
+    assert_eq!(pages[2].len(), 22 * ComplianceInput::LEN);
+
+    let journal = fold(3, &pages);
+
+    let journals = inputs
+        .iter()
+        .map(|input| {
+            let encoding = JournalEncoding::from_version(input.journalVersion);
+            evaluate(input).encode(encoding.unwrap())
+        })
+        .fold(B256::ZERO, |digest, journal| fold_digest(digest, &journal));
+    let pages_digest = pages
+        .iter()
+        .fold(B256::ZERO, |digest, page| fold_digest(digest, page));
+    let allowed = inputs
+        .iter()
+        .filter(|input| evaluate(input).allowed)
+        .count();
+    let cohort_leaves = inputs
+        .iter()
+        .filter(|input| evaluate(input).allowed)
+        .map(|input| cohort_leaf(input.user, input.productId))
+        .collect();
+    assert_eq!(
+        journal,
+        BatchJournal {
+            pages: 3,
+            count: inputs.len() as u32,
+            pagesDigest: pages_digest,
+            journalsDigest: journals,
+            exceeded: false,
+            heapCapExceeded: false,
+            aggregateCap: U256::MAX,
+            aggregateAmount: U256::from(100 * allowed),
+            capExceeded: false,
+            allAllowed: allowed == inputs.len(),
+            cohortRoot: MerkleTree::new(cohort_leaves).root(),
+        }
+    );
+}
+
+#[test]
+fn truncated_page_sequence_changes_the_pages_digest() {
+    let pages = batch_pages(&inputs(3 * MAX_PAGE_INPUTS));
+    let full = fold(3, &pages);
+
+    let dropped = fold(3, &pages[..2]);
+    assert_eq!(dropped.count as usize, 2 * MAX_PAGE_INPUTS);
+    assert_ne!(dropped.pagesDigest, full.pagesDigest);
+
+    let mut cut = pages.clone();
+    cut[2].truncate(10 * ComplianceInput::LEN);
+    assert_ne!(fold(3, &cut).pagesDigest, full.pagesDigest);
+
+    let mut fold = BatchFold::new(3, U256::MAX);
+    let err = fold.push_page(&pages[0][..ComplianceInput::LEN + 1]);
+    assert!(matches!(err, Err(PageError::Length(len)) if len == ComplianceInput::LEN + 1));
+
+    // Inputs are decoded as strictly as the compliance guest decodes them.
+    let mut dirty = pages[0].clone();
+    dirty[ComplianceInput::LEN + 5 * 32 - 1] = 2;
+    let err = BatchFold::new(3, U256::MAX).push_page(&dirty);
+    assert!(matches!(
+        err,
+        Err(PageError::Input(InputError::NonCanonical))
+    ));
+}
+
+#[test]
+fn oversized_pages_and_batches_are_refused() {
+    let page = inputs(MAX_PAGE_INPUTS + 1)
+        .iter()
+        .flat_map(SolValue::abi_encode)
+        .collect::<Vec<_>>();
+    let err = BatchFold::new(1, U256::MAX).push_page(&page);
+    assert!(matches!(err, Err(PageError::TooManyInputs(n)) if n == MAX_PAGE_INPUTS + 1));
+
+    let mut fold = BatchFold::new(MAX_BATCH_PAGES + 1, U256::MAX);
+    assert!(fold.exceeded());
+    let pages = batch_pages(&inputs(1));
+    assert!(matches!(
+        fold.push_page(&pages[0]),
+        Err(PageError::Exceeded)
+    ));
+    let journal = fold.finish();
+    assert!(journal.exceeded);
+    assert_eq!(journal.count, 0);
+    assert!(BatchFold::new(MAX_BATCH_PAGES, U256::MAX)
+        .push_page(&pages[0])
+        .is_ok());
+
+    // A page refused for the guest's heap cap stops the batch where it is.
+    let mut fold = BatchFold::new(2, U256::MAX);
+    fold.push_page(&pages[0]).unwrap();
+    fold.refuse_page();
+    assert!(matches!(fold.push_page(&pages[0]), Err(PageError::HeapCap)));
+    let journal = fold.finish();
+    assert!(journal.heapCapExceeded && !journal.exceeded);
+    assert_eq!(journal.count, 1);
+}
+
+#[test]
+fn aggregate_of_allowed_trades_is_checked_against_the_cap() {
+    // Inputs 0 and 3 fail AML, so four trades of 100 are allowed.
+    let pages = batch_pages(&inputs(6));
+
+    let within = fold_capped(1, &pages, U256::from(400));
+    assert_eq!(within.aggregateAmount, U256::from(400));
+    assert_eq!(within.aggregateCap, U256::from(400));
+    assert!(!within.capExceeded);
+
+    let over = fold_capped(1, &pages, U256::from(399));
+    assert_eq!(over.aggregateAmount, U256::from(400));
+    assert!(over.capExceeded);
+    // The cap flags the batch, not its decisions, which stay those of each input alone.
+    assert_eq!(over.journalsDigest, within.journalsDigest);
+
+    assert!(!fold(1, &pages).capExceeded);
+    assert!(fold_capped(1, &pages, U256::ZERO).capExceeded);
+}
+
+#[test]
+fn one_rejected_input_rejects_the_whole_batch() {
+    // Inputs 0 and 3 fail AML; the ones between them pass.
+    let inputs = inputs(4);
+
+    let passing = batch_pages(&inputs[1..3]);
+    assert!(fold(1, &passing).allAllowed);
+
+    let mixed = batch_pages(&inputs);
+    let journal = fold(1, &mixed);
+    assert!(!journal.allAllowed);
+    assert_eq!(journal.count, 4);
+    assert_eq!(journal.aggregateAmount, U256::from(200));
+
+    assert!(!fold(0, &[]).allAllowed);
+    // A batch over its cap is rejected as a whole, even when every decision allowed its trade.
+    assert!(!fold_capped(1, &passing, U256::from(199)).allAllowed);
+    let mut fold = BatchFold::new(2, U256::MAX);
+    fold.push_page(&passing[0]).unwrap();
+    fold.refuse_page();
+    assert!(!fold.finish().allAllowed);
+}
+
+#[test]
+fn cohort_proofs_reach_the_committed_root() {
+    // Every third input fails AML, so it is left out of the cohort.
+    let inputs = inputs(MAX_PAGE_INPUTS + 10);
+    let journal = fold(2, &batch_pages(&inputs));
+    let cohort = cohort(&inputs);
+    assert_eq!(cohort.len(), inputs.len() - inputs.len().div_ceil(3));
+
+    for &(user, product_id) in &cohort {
+        let proof = cohort_proof(&inputs, user, product_id).unwrap();
+        assert_eq!(proof.root(), journal.cohortRoot);
+        assert!(proof.verify(), "{user}");
+    }
+}
+
+#[test]
+fn cohort_excludes_denied_and_unknown_pairs() {
+    let inputs = inputs(10);
+    let journal = fold(1, &batch_pages(&inputs));
+    let denied = inputs[3].user;
+    let product_id = inputs[3].productId;
+
+    for (user, product_id) in [
+        (denied, product_id),
+        (Address::repeat_byte(0xaa), product_id),
+        (inputs[1].user, B256::repeat_byte(0xbb)),
+    ] {
+        let err = cohort_proof(&inputs, user, product_id).unwrap_err();
+        assert!(err.to_string().contains("not in the cohort"), "{err}");
+    }
+    // A member's path does not carry another user or product to the root.
+    let AddressProof::Cohort { proof, .. } =
+        cohort_proof(&inputs, inputs[1].user, product_id).unwrap()
+    else {
+        panic!("not a cohort proof");
+    };
+    for (user, product_id) in [(denied, product_id), (inputs[1].user, B256::ZERO)] {
+        let forged = AddressProof::Cohort {
+            root: journal.cohortRoot,
+            user,
+            product_id,
+            proof: proof.clone(),
+        };
+        assert!(!forged.verify(), "{user} {product_id}");
+    }
+
+    // A batch that allows no one has no cohort.
+    let rejected = inputs.iter().step_by(3).cloned().collect::<Vec<_>>();
+    assert_eq!(fold(1, &batch_pages(&rejected)).cohortRoot, B256::ZERO);
+}
@@ -0,0 +1,99 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conformance of the product ids of Uniswap v4 pools with the `PoolId`s of the v4 contracts.
+
+use alloy::primitives::{
+    aliases::{I24, U24},
+    Address, B256,
+};
+use app::pool::{parse_pool_key, PoolKey};
+use serde::Deserialize;
+
+/// A pool and the `PoolId` the v4 `PoolManager` emitted for it.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PoolIdFixture {
+    name: String,
+    key: String,
+    pool_id: B256,
+}
+
+#[test]
+fn product_ids_are_the_pool_ids_of_v4_core() {
+    let fixtures: Vec<PoolIdFixture> =
+        serde_json::from_str(include_str!("fixtures/v4_pool_ids.json")).unwrap();
+    assert!(!fixtures.is_empty());
+    for fixture in fixtures {
+        let key = parse_pool_key(&fixture.key).unwrap();
+        assert_eq!(key.id(), fixture.pool_id, "{}", fixture.name);
+    }
+}
+
+#[test]
+fn every_field_of_the_key_changes_the_id() {
+    let key = || PoolKey {
+        currency0: Address::with_last_byte(1),
+        currency1: Address::with_last_byte(2),
+        fee: U24::from(3000),
+        tickSpacing: I24::try_from(60).unwrap(),
+        hooks: Address::ZERO,
+    };
+    let changed = [
+        PoolKey {
+            currency0: Address::ZERO,
+            ..key()
+        },
+        PoolKey {
+            currency1: Address::with_last_byte(3),
+            ..key()
+        },
+        PoolKey {
+            fee: U24::from(500),
+            ..key()
+        },
+        PoolKey {
+            tickSpacing: I24::try_from(10).unwrap(),
+            ..key()
+        },
+        PoolKey {
+            hooks: Address::with_last_byte(0x80),
+            ..key()
+        },
+    ];
+    for other in changed {
+        assert_ne!(other.id(), key().id(), "{other:?}");
+    }
+}
+
+#[test]
+fn keys_pool_manager_would_refuse_are_rejected() {
+    let eth = "0x0000000000000000000000000000000000000000";
+    let usdc = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+    let cases = [
+        (format!("{usdc},{eth},500,10,{eth}"), "must sort below"),
+        (format!("{eth},{eth},500,10,{eth}"), "must sort below"),
+        (format!("{eth},{usdc},1000001,10,{eth}"), "is above"),
+        (format!("{eth},{usdc},500,0,{eth}"), "outside"),
+        (format!("{eth},{usdc},500,32768,{eth}"), "outside"),
+        (format!("{eth},{usdc},500,10"), "expected"),
+    ];
+    for (key, message) in cases {
+        let err = parse_pool_key(&key).unwrap_err();
+        assert!(err.to_string().contains(message), "{key}: {err}");
+    }
+
+    let dynamic = parse_pool_key(&format!("{eth},{usdc},8388608,60,{eth}")).unwrap();
+    assert_eq!(dynamic.fee, U24::from(0x80_0000));
+}
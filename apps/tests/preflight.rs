@@ -0,0 +1,203 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::str::FromStr;
+
+use alloy::{
+    primitives::{utils::parse_ether, Address, Bytes, FixedBytes, B256, U256},
+    providers::ProviderBuilder,
+    sol_types::{SolCall, SolValue},
+};
+use app::{
+    fixtures::{dev_mode_seal, DEV_MODE_SELECTOR},
+    preflight::{
+        check_seal, ensure_sufficient_balance, estimate_proof_cost, estimate_required_balance,
+        ISelectorVerifier::SELECTORCall, IVerifierOwner::VERIFIERCall,
+        IVerifierRouter::getVerifierCall, SealKind, GROTH16_SEAL_LEN, SETTLEMENT_GAS_ESTIMATE,
+    },
+};
+use boundless_market::request_builder::OfferLayerConfig;
+use serde_json::{json, Value};
+use wiremock::{matchers::method, Mock, MockServer, Request, ResponseTemplate};
+
+const HOOK: Address = Address::repeat_byte(0xaa);
+const VERIFIER: Address = Address::repeat_byte(0xbb);
+const GROTH16_SELECTOR: [u8; 4] = [0x9f, 0x39, 0x69, 0x6c];
+
+fn groth16_seal() -> Vec<u8> {
+    let mut seal = vec![7; GROTH16_SEAL_LEN];
+    seal[..4].copy_from_slice(&GROTH16_SELECTOR);
+    seal
+}
+
+/// A JSON-RPC endpoint where the verifier of [HOOK] is [VERIFIER]: a single verifier of
+/// `selector`, or without one a router with a verifier for each of `routes`. Calls it does not
+/// answer revert.
+async fn chain(selector: Option<[u8; 4]>, routes: Vec<[u8; 4]>) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(move |request: &Request| {
+            let request: Value = serde_json::from_slice(&request.body).unwrap();
+            assert_eq!(request["method"], "eth_call");
+            let call = &request["params"][0];
+            let to = Address::from_str(call["to"].as_str().unwrap()).unwrap();
+            let input = call.get("input").unwrap_or(&call["data"]);
+            let input = Bytes::from_str(input.as_str().unwrap()).unwrap();
+            let function: [u8; 4] = input[..4].try_into().unwrap();
+            let result = if to == HOOK && function == VERIFIERCall::SELECTOR {
+                Some(VERIFIER.abi_encode())
+            } else if to == VERIFIER && function == SELECTORCall::SELECTOR {
+                selector.map(|selector| FixedBytes(selector).abi_encode())
+            } else if to == VERIFIER && function == getVerifierCall::SELECTOR && selector.is_none()
+            {
+                let routed: [u8; 4] = input[4..8].try_into().unwrap();
+                routes
+                    .contains(&routed)
+                    .then(|| Address::repeat_byte(0xcc).abi_encode())
+            } else {
+                None
+            };
+            let mut response = json!({ "jsonrpc": "2.0", "id": request["id"] });
+            response.as_object_mut().unwrap().extend(
+                match result {
+                    Some(result) => json!({ "result": Bytes::from(result) }),
+                    None => json!({
+                        "error": { "code": 3, "message": "execution reverted", "data": "0x" }
+                    }),
+                }
+                .as_object()
+                .unwrap()
+                .clone(),
+            );
+            ResponseTemplate::new(200).set_body_json(response)
+        })
+        .mount(&server)
+        .await;
+    server
+}
+
+#[test]
+fn estimate_scales_with_settlements_and_gas_price() {
+    let gas_price = 2_000_000_000; // 2 gwei
+
+    assert_eq!(
+        estimate_required_balance(3, gas_price),
+        U256::from(3 * SETTLEMENT_GAS_ESTIMATE as u128 * gas_price)
+    );
+    assert_eq!(estimate_required_balance(0, gas_price), U256::ZERO);
+}
+
+#[test]
+fn accepts_exact_balance() {
+    let required = parse_ether("0.01").unwrap();
+
+    ensure_sufficient_balance(Address::ZERO, required, required).unwrap();
+}
+
+#[test]
+fn reports_shortfall() {
+    let err = ensure_sufficient_balance(
+        Address::ZERO,
+        parse_ether("0.25").unwrap(),
+        parse_ether("1").unwrap(),
+    )
+    .unwrap_err();
+
+    assert!(
+        err.to_string()
+            .contains("short by 0.750000000000000000 ETH"),
+        "{err}"
+    );
+}
+
+#[test]
+fn proof_cost_prices_cycles_and_prover_gas() {
+    let config = OfferLayerConfig::default();
+    let cycles = 1 << 20;
+    let gas_price = 1_000_000_000; // 1 gwei
+
+    let estimate = estimate_proof_cost(&config, cycles, gas_price);
+
+    let gas_cost =
+        U256::from(config.lock_gas_estimate + config.fulfill_gas_estimate) * U256::from(gas_price);
+    assert_eq!(
+        estimate.min_price,
+        config.min_price_per_cycle * U256::from(cycles)
+    );
+    assert_eq!(
+        estimate.max_price,
+        config.max_price_per_cycle * U256::from(cycles)
+            + gas_cost * U256::from(11) / U256::from(10)
+    );
+    // Only the cycle price grows with the program.
+    let longer = estimate_proof_cost(&config, 2 * cycles, gas_price);
+    assert_eq!(
+        longer.max_price - estimate.max_price,
+        config.max_price_per_cycle * U256::from(cycles)
+    );
+}
+
+#[test]
+fn tells_seals_apart_by_selector_and_length() {
+    let set_inclusion = [GROTH16_SELECTOR.as_slice(), &[0; 6 * 32]].concat();
+
+    assert_eq!(
+        SealKind::of(&dev_mode_seal(B256::ZERO, b"journal")),
+        SealKind::DevMode
+    );
+    assert_eq!(SealKind::of(&groth16_seal()), SealKind::Groth16);
+    assert_eq!(SealKind::of(&set_inclusion), SealKind::SetInclusion);
+    assert_eq!(SealKind::of(&[0xff; 9]), SealKind::Unknown);
+}
+
+#[tokio::test]
+async fn single_verifier_takes_only_its_selector() {
+    let server = chain(Some(DEV_MODE_SELECTOR), Vec::new()).await;
+    let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+
+    let kind = check_seal(&provider, HOOK, &dev_mode_seal(B256::ZERO, b"journal"))
+        .await
+        .unwrap();
+    assert_eq!(kind, SealKind::DevMode);
+
+    let err = check_seal(&provider, HOOK, &groth16_seal())
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("seal is groth16 with selector 0x9f39696c, but the hook's verifier"),
+        "{err}"
+    );
+    assert!(
+        err.to_string().contains("only takes selector 0xffffffff"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn router_takes_selectors_it_has_a_verifier_for() {
+    let server = chain(None, vec![GROTH16_SELECTOR]).await;
+    let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+
+    let kind = check_seal(&provider, HOOK, &groth16_seal()).await.unwrap();
+    assert_eq!(kind, SealKind::Groth16);
+
+    let err = check_seal(&provider, HOOK, &dev_mode_seal(B256::ZERO, b"journal"))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("has no verifier for"), "{err:#}");
+
+    let err = check_seal(&provider, HOOK, &[0xff; 3]).await.unwrap_err();
+    assert!(err.to_string().contains("too short"), "{err}");
+}
@@ -0,0 +1,249 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
+
+use alloy::primitives::B256;
+use anyhow::Result;
+use app::{
+    journal::compliance_image_id,
+    program::{
+        check_program_image_id, check_program_url, find_program, program_key, upload_program,
+        IndexedStorage, ProgramStorage, StoredProgram,
+    },
+};
+use boundless_market::storage::TempFileStorageProvider;
+use guests::{COMPLIANCE_ELF, COMPLIANCE_ID};
+use sha2::{Digest, Sha256};
+use url::Url;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+/// Storage that keeps objects in memory and records every upload.
+#[derive(Default)]
+struct MockStorage {
+    objects: Mutex<HashMap<String, StoredProgram>>,
+    puts: Mutex<Vec<String>>,
+}
+
+impl MockStorage {
+    fn puts(&self) -> Vec<String> {
+        self.puts.lock().unwrap().clone()
+    }
+}
+
+impl ProgramStorage for MockStorage {
+    async fn head(&self, key: &str) -> Result<Option<StoredProgram>> {
+        Ok(self.objects.lock().unwrap().get(key).cloned())
+    }
+
+    async fn put(&self, key: &str, program: &[u8]) -> Result<Url> {
+        let url = Url::parse(&format!("mock://storage/{key}"))?;
+        let stored = StoredProgram {
+            url: url.clone(),
+            size: program.len() as u64,
+            sha256: sha256(program),
+        };
+        self.objects.lock().unwrap().insert(key.to_string(), stored);
+        self.puts.lock().unwrap().push(key.to_string());
+        Ok(url)
+    }
+}
+
+fn sha256(bytes: &[u8]) -> B256 {
+    B256::from_slice(&Sha256::digest(bytes))
+}
+
+fn key() -> String {
+    program_key(COMPLIANCE_ID.into())
+}
+
+/// An empty index directory of its own for each test.
+fn index_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("program-index-{name}"));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[tokio::test]
+async fn skips_upload_of_stored_program() {
+    let storage = MockStorage::default();
+
+    let uploaded = upload_program(&storage, COMPLIANCE_ELF).await.unwrap();
+    let reused = upload_program(&storage, COMPLIANCE_ELF).await.unwrap();
+
+    assert_eq!(storage.puts(), [key()]);
+    assert_eq!(reused, uploaded);
+}
+
+#[tokio::test]
+async fn replaces_object_that_does_not_match_the_program() {
+    let storage = MockStorage::default();
+    let foreign = StoredProgram {
+        url: Url::parse("mock://storage/foreign").unwrap(),
+        size: COMPLIANCE_ELF.len() as u64,
+        sha256: B256::repeat_byte(0xaa),
+    };
+    storage
+        .objects
+        .lock()
+        .unwrap()
+        .insert(key(), foreign.clone());
+
+    let url = upload_program(&storage, COMPLIANCE_ELF).await.unwrap();
+
+    assert_eq!(storage.puts(), [key()]);
+    assert_ne!(url, foreign.url);
+    assert_eq!(
+        storage.objects.lock().unwrap()[&key()].sha256,
+        sha256(COMPLIANCE_ELF)
+    );
+}
+
+#[tokio::test]
+async fn finds_uploaded_program_by_image_id() {
+    let storage = MockStorage::default();
+    let uploaded = upload_program(&storage, COMPLIANCE_ELF).await.unwrap();
+
+    let found = find_program(&storage, compliance_image_id())
+        .await
+        .unwrap()
+        .unwrap();
+    let missing = find_program(&storage, B256::repeat_byte(0xaa))
+        .await
+        .unwrap();
+
+    assert_eq!(found.url, uploaded);
+    assert_eq!(found.image_id, Some(compliance_image_id()));
+    assert_eq!(missing, None);
+    assert_eq!(storage.puts().len(), 1);
+}
+
+#[test]
+fn referenced_image_id_must_be_the_embedded_guest_unless_allowed() {
+    let foreign = B256::repeat_byte(0xaa);
+
+    check_program_image_id(compliance_image_id(), false).unwrap();
+    let err = check_program_image_id(foreign, false).unwrap_err();
+    assert!(
+        format!("{err:#}").contains("--allow-image-id-mismatch"),
+        "{err:#}"
+    );
+    check_program_image_id(foreign, true).unwrap();
+}
+
+#[tokio::test]
+async fn index_uploads_again_when_object_is_gone_or_truncated() {
+    let storage = IndexedStorage::new(TempFileStorageProvider::new().unwrap(), index_dir("file"));
+    let url = upload_program(&storage, COMPLIANCE_ELF).await.unwrap();
+    let path = url.to_file_path().unwrap();
+    let uploaded_at = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+    assert_eq!(upload_program(&storage, COMPLIANCE_ELF).await.unwrap(), url);
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().modified().unwrap(),
+        uploaded_at
+    );
+
+    std::fs::write(&path, &COMPLIANCE_ELF[..16]).unwrap();
+    upload_program(&storage, COMPLIANCE_ELF).await.unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), COMPLIANCE_ELF);
+
+    std::fs::remove_file(&path).unwrap();
+    upload_program(&storage, COMPLIANCE_ELF).await.unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), COMPLIANCE_ELF);
+}
+
+#[tokio::test]
+async fn index_checks_http_objects_with_head() {
+    let server = MockServer::start().await;
+    Mock::given(method("HEAD"))
+        .and(path("/compliance.bin"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-length", COMPLIANCE_ELF.len().to_string().as_str()),
+        )
+        .mount(&server)
+        .await;
+    let dir = index_dir("http");
+    let record = |url: &str| {
+        let stored = StoredProgram {
+            url: Url::parse(url).unwrap(),
+            size: COMPLIANCE_ELF.len() as u64,
+            sha256: sha256(COMPLIANCE_ELF),
+        };
+        let path = dir.join(format!("{}.json", key()));
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, serde_json::to_vec(&stored).unwrap()).unwrap();
+        stored.url
+    };
+    let storage = IndexedStorage::new(TempFileStorageProvider::new().unwrap(), &dir);
+
+    let hosted = record(&format!("{}/compliance.bin", server.uri()));
+    assert_eq!(
+        upload_program(&storage, COMPLIANCE_ELF).await.unwrap(),
+        hosted
+    );
+
+    let gone = record(&format!("{}/gone.bin", server.uri()));
+    let uploaded = upload_program(&storage, COMPLIANCE_ELF).await.unwrap();
+    assert_ne!(uploaded, gone);
+    assert_eq!(uploaded.scheme(), "file");
+}
+
+#[tokio::test]
+async fn program_url_is_checked_against_the_embedded_guest() {
+    let dir = index_dir("program-url");
+    std::fs::create_dir_all(&dir).unwrap();
+    let guest = dir.join("compliance.bin");
+    std::fs::write(&guest, COMPLIANCE_ELF).unwrap();
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/compliance.bin"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(COMPLIANCE_ELF))
+        .mount(&server)
+        .await;
+
+    for url in [
+        Url::from_file_path(&guest).unwrap(),
+        Url::parse(&format!("{}/compliance.bin", server.uri())).unwrap(),
+    ] {
+        let program = check_program_url(url.clone(), false).await.unwrap();
+        assert_eq!(program.url, url);
+        assert_eq!(program.image_id, Some(compliance_image_id()));
+    }
+
+    let missing = Url::parse(&format!("{}/missing.bin", server.uri())).unwrap();
+    assert!(check_program_url(missing, true).await.is_err());
+
+    let other = dir.join("other.bin");
+    std::fs::write(&other, b"not a guest program").unwrap();
+    let err = check_program_url(Url::from_file_path(&other).unwrap(), true)
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("failed to compute image id"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn program_url_that_cannot_be_fetched_is_used_unchecked() {
+    let url = Url::parse("s3://bucket/compliance.bin").unwrap();
+    let program = check_program_url(url.clone(), false).await.unwrap();
+    assert_eq!(program.url, url);
+    assert_eq!(program.image_id, None);
+}
@@ -0,0 +1,249 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use alloy::{
+    primitives::{Address, Bytes, B256},
+    signers::local::PrivateKeySigner,
+    sol_types::SolCall,
+};
+use app::{
+    chains::ChainsConfig,
+    compliance_receiver::IComplianceReceiver::{receiveDecisionCall, receiveSignedResultCall},
+    relay::{
+        ensure_relayed, retry_relays, PrimaryTx, Relay, RelayRecord, RelayStore, RelayTx, TxStatus,
+    },
+    signing::{ResultDomain, ResultSigner},
+};
+use common::entry;
+use serde_json::{json, Value};
+use wiremock::{matchers::method, Mock, MockServer, Request, ResponseTemplate};
+
+const DECISION_AT: u64 = 1_700_000_000;
+const L1: u64 = 1;
+const RECEIVER: Address = Address::repeat_byte(0xee);
+const RELAY_TX: B256 = B256::repeat_byte(0x77);
+
+fn record(signed: bool) -> RelayRecord {
+    let entry = entry(1);
+    let journal: Bytes = entry
+        .expected_journal(DECISION_AT)
+        .encode(entry.journal_encoding())
+        .into();
+    let signed_result = signed.then(|| {
+        let domain = ResultDomain {
+            name: "ComplianceHook".into(),
+            version: "1".into(),
+            chain_id: 8453,
+            verifying_contract: Address::with_last_byte(0xcc),
+        };
+        ResultSigner::new(PrivateKeySigner::random(), domain)
+            .sign(&journal)
+            .unwrap()
+    });
+    RelayRecord::new(
+        &entry,
+        journal,
+        Bytes::from_static(&[0xab; 4]),
+        signed_result,
+        PrimaryTx {
+            chain_id: 8453,
+            tx_hash: Some(B256::repeat_byte(0x11)),
+            status: TxStatus::Confirmed,
+        },
+        RelayTx {
+            chain: "mainnet".into(),
+            chain_id: L1,
+            receiver: RECEIVER,
+            tx_hash: None,
+            status: TxStatus::Pending,
+            error: None,
+        },
+    )
+}
+
+fn store(name: &str) -> RelayStore {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("relay-{name}"));
+    let _ = std::fs::remove_dir_all(&dir);
+    RelayStore::new(dir)
+}
+
+fn config(rpc_url: &str, verifier: bool) -> ChainsConfig {
+    let mut toml = format!(
+        "[chains.mainnet]\nrpc_url = \"{rpc_url}\"\nchain_id = {L1}\n\
+         hook_address = \"{}\"\nreceiver_address = \"{RECEIVER}\"\n\
+         max_fee_per_gas = 30000000000\n",
+        Address::with_last_byte(0xa1)
+    );
+    if verifier {
+        toml += &format!("verifier_address = \"{}\"\n", Address::with_last_byte(0xa2));
+    }
+    toml.parse().unwrap()
+}
+
+/// A JSON-RPC endpoint of chain [L1] where [RELAY_TX] confirmed. Anything else is unexpected.
+async fn chain_with_confirmed_relay() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(|request: &Request| {
+            let request: Value = serde_json::from_slice(&request.body).unwrap();
+            let result = match request["method"].as_str().unwrap() {
+                "eth_chainId" => json!(format!("{L1:#x}")),
+                "eth_getTransactionReceipt" => json!({
+                    "transactionHash": RELAY_TX,
+                    "transactionIndex": "0x0",
+                    "blockHash": B256::repeat_byte(0xbb),
+                    "blockNumber": "0x10",
+                    "from": Address::with_last_byte(0xf0),
+                    "to": RECEIVER,
+                    "cumulativeGasUsed": "0x5208",
+                    "gasUsed": "0x5208",
+                    "effectiveGasPrice": "0x1",
+                    "contractAddress": null,
+                    "logs": [],
+                    "logsBloom": format!("0x{}", "00".repeat(256)),
+                    "type": "0x2",
+                    "status": "0x1",
+                }),
+                other => panic!("unexpected RPC method {other}"),
+            };
+            ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": result,
+            }))
+        })
+        .mount(&server)
+        .await;
+    server
+}
+
+#[test]
+fn relays_the_seal_to_chains_with_a_verifier() {
+    let record = record(false);
+
+    let calldata = record.calldata(true).unwrap();
+
+    let call = receiveDecisionCall::abi_decode(&calldata).unwrap();
+    assert_eq!(call.journal, record.journal);
+    assert_eq!(call.seal, record.seal);
+}
+
+#[test]
+fn relays_the_signed_result_to_chains_without_one() {
+    let err = record(false).calldata(false).unwrap_err();
+    assert!(err.to_string().contains("pass --sign-results"), "{err}");
+
+    let record = record(true);
+    let calldata = record.calldata(false).unwrap();
+
+    let call = receiveSignedResultCall::abi_decode(&calldata).unwrap();
+    let signed = record.signed_result.unwrap();
+    assert_eq!(call.result.user, signed.user);
+    assert_eq!(call.result.journalDigest, record.journal_digest);
+    assert_eq!(call.result.imageId, signed.image_id);
+    assert_eq!(call.signature, signed.signature);
+}
+
+#[test]
+fn store_tracks_relays_apart_from_their_settlement() {
+    let store = store("tracks");
+    assert!(store.records().unwrap().is_empty());
+
+    let pending = record(false);
+    let mut confirmed = record(true);
+    confirmed.journal_digest = B256::repeat_byte(0xff);
+    confirmed.relay.status = TxStatus::Confirmed;
+    confirmed.relay.tx_hash = Some(RELAY_TX);
+    let mut unsettled = record(false);
+    unsettled.journal_digest = B256::ZERO;
+    unsettled.primary.status = TxStatus::Failed;
+    for record in [&pending, &confirmed, &unsettled] {
+        store.insert(record).unwrap();
+    }
+
+    assert_eq!(
+        store.records().unwrap(),
+        vec![unsettled, pending.clone(), confirmed]
+    );
+    assert_eq!(store.unconfirmed().unwrap(), vec![pending]);
+}
+
+#[test]
+fn relay_needs_a_receiver_and_a_key() {
+    let config = config("http://localhost:8545", true);
+    let chain = config.chain("mainnet").unwrap();
+
+    let err = Relay::connect("mainnet", chain, None).err().unwrap();
+    assert!(format!("{err:#}").contains("no key to relay"), "{err:#}");
+
+    let without_receiver = app::chains::ChainConfig {
+        receiver_address: None,
+        ..chain.clone()
+    };
+    let key = PrivateKeySigner::random();
+    let err = Relay::connect("mainnet", &without_receiver, Some(&key))
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string().contains("sets no receiver_address"),
+        "{err}"
+    );
+    assert_eq!(chain.max_fee_per_gas, Some(30_000_000_000));
+}
+
+#[tokio::test]
+async fn retry_takes_a_relay_that_confirmed_after_all() {
+    let server = chain_with_confirmed_relay().await;
+    let config = config(&server.uri(), true);
+    let store = store("confirmed-after-all");
+    let mut sent = record(false);
+    sent.relay.tx_hash = Some(RELAY_TX);
+    sent.relay.status = TxStatus::Failed;
+    sent.relay.error = Some("failed to confirm relay tx".into());
+    store.insert(&sent).unwrap();
+    let key = PrivateKeySigner::random();
+
+    let results = retry_relays(&store, |name| {
+        Relay::connect(name, config.chain(name)?, Some(&key))
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(*results[0].1.as_ref().unwrap(), RELAY_TX);
+    ensure_relayed(&results).unwrap();
+    let stored = store.records().unwrap().remove(0);
+    assert_eq!(stored.relay.status, TxStatus::Confirmed);
+    assert_eq!(stored.relay.error, None);
+    assert!(store.unconfirmed().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn retry_keeps_going_past_a_chain_it_cannot_reach() {
+    let store = store("unreachable");
+    store.insert(&record(false)).unwrap();
+
+    let results = retry_relays(&store, |name| {
+        anyhow::bail!("chain {name} is not in the chains config")
+    })
+    .await
+    .unwrap();
+
+    assert!(results[0].1.is_err());
+    let err = ensure_relayed(&results).unwrap_err();
+    assert_eq!(err.to_string(), "1 of 1 relays failed");
+    assert_eq!(store.unconfirmed().unwrap().len(), 1);
+}
@@ -0,0 +1,123 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloy::primitives::{address, b256, bytes};
+use app::relayer::RelayerClient;
+use serde_json::json;
+use url::Url;
+use wiremock::{
+    matchers::{body_json, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn relayer_url(server: &MockServer) -> Url {
+    Url::parse(&format!("{}/relay", server.uri())).unwrap()
+}
+
+#[tokio::test]
+async fn forwards_calldata_and_returns_tx_hash() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/relay"))
+        .and(body_json(json!({
+            "chainId": 11155111,
+            "target": "0x0000000000000000000000000000000000001234",
+            "data": "0x938f8a7a",
+            "sponsorApiKey": "secret",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "txHash": "0x00000000000000000000000000000000000000000000000000000000000000aa",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let relayed = RelayerClient::new(relayer_url(&server), Some("secret".into()))
+        .relay(
+            11155111,
+            address!("0x0000000000000000000000000000000000001234"),
+            bytes!("938f8a7a"),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        relayed.tx_hash,
+        Some(b256!(
+            "0x00000000000000000000000000000000000000000000000000000000000000aa"
+        ))
+    );
+    assert_eq!(relayed.task_id, None);
+}
+
+#[tokio::test]
+async fn accepts_task_id_from_queueing_relayers() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "taskId": "task-7" })))
+        .mount(&server)
+        .await;
+
+    let relayed = RelayerClient::new(relayer_url(&server), None)
+        .relay(
+            1,
+            address!("0x0000000000000000000000000000000000001234"),
+            bytes!("00"),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(relayed.task_id.as_deref(), Some("task-7"));
+}
+
+#[tokio::test]
+async fn surfaces_relayer_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(402).set_body_string("sponsor balance exhausted"))
+        .mount(&server)
+        .await;
+
+    let err = RelayerClient::new(relayer_url(&server), None)
+        .relay(
+            1,
+            address!("0x0000000000000000000000000000000000001234"),
+            bytes!("00"),
+        )
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().contains("402"));
+    assert!(err.to_string().contains("sponsor balance exhausted"));
+}
+
+#[tokio::test]
+async fn rejects_empty_acknowledgement() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(&server)
+        .await;
+
+    let err = RelayerClient::new(relayer_url(&server), None)
+        .relay(
+            1,
+            address!("0x0000000000000000000000000000000000001234"),
+            bytes!("00"),
+        )
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().contains("neither a tx hash nor a task id"));
+}
@@ -0,0 +1,254 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::{num::NonZeroUsize, path::PathBuf, sync::Arc, time::Duration};
+
+use alloy::primitives::{Address, B256, U256};
+use app::{
+    batch::{run_batch, BatchEntry, BatchOptions, Concurrency, Stage},
+    market::request_id,
+    report::{
+        from_csv, merge_reports, read_report, to_csv, write_report, ColumnType, ReportRow, Status,
+        REPORT_COLUMNS,
+    },
+};
+use boundless_market::{contracts::RequestId, request_builder::RequestParams};
+use common::{MockMarket, MockSettler, Step};
+
+const DECISION_AT: u64 = 1_700_000_000;
+
+fn entry(i: u8) -> BatchEntry {
+    BatchEntry {
+        product_id: B256::with_last_byte(i),
+        ..common::entry(i)
+    }
+}
+
+fn row(index: u64, request_id: Option<u64>, status: Status) -> ReportRow {
+    ReportRow {
+        index,
+        user: Address::with_last_byte(1),
+        product_id: B256::with_last_byte(1),
+        status,
+        stage: (status == Status::Failed).then_some(Stage::Settle),
+        error: (status == Status::Failed).then(|| "execution reverted, twice".into()),
+        allowed: Some(false),
+        reasons: Some("aml_failed|kyc_tier_too_low".into()),
+        request_id: request_id.map(U256::from),
+        cycles: None,
+        price_paid_wei: Some(U256::from(10).pow(U256::from(18))),
+        tx_hash: (status == Status::Settled).then(|| B256::repeat_byte(7)),
+        decided_at: Some(DECISION_AT),
+        finished_at: DECISION_AT + 60,
+    }
+}
+
+/// A path of its own for each test.
+fn report_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+// Dashboards read reports by column name and type: change this list only by appending to it.
+#[test]
+fn report_columns_are_stable() {
+    use ColumnType::*;
+    assert_eq!(
+        REPORT_COLUMNS,
+        [
+            ("index", UInt64),
+            ("user", Utf8),
+            ("product_id", Utf8),
+            ("status", Utf8),
+            ("stage", Utf8),
+            ("error", Utf8),
+            ("allowed", Boolean),
+            ("reasons", Utf8),
+            ("request_id", Utf8),
+            ("cycles", UInt64),
+            ("price_paid_wei", Utf8),
+            ("tx_hash", Utf8),
+            ("decided_at", UInt64),
+            ("finished_at", UInt64),
+        ]
+    );
+    assert_eq!(
+        String::from_utf8(to_csv(&[]).unwrap()).unwrap(),
+        "index,user,product_id,status,stage,error,allowed,reasons,request_id,cycles,\
+         price_paid_wei,tx_hash,decided_at,finished_at\n"
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn batch_reports_have_a_row_per_entry() {
+    let markets: Arc<[MockMarket]> = Arc::new([MockMarket::new([
+        Step::Fulfill(Duration::from_secs(3)),
+        Step::Expire(Duration::from_secs(1)),
+        Step::Fulfill(Duration::from_secs(2)),
+    ])]);
+    let settler = Arc::new(MockSettler::new(Duration::ZERO).rejecting(Address::with_last_byte(3)));
+    let signer = Address::repeat_byte(0xaa);
+    let entries = (1..=3)
+        .map(|i| {
+            let entry = entry(i);
+            let request = RequestParams::new()
+                .with_request_id(RequestId::new(signer, i.into()))
+                .with_stdin(entry.stdin(DECISION_AT));
+            (entry, request)
+        })
+        .collect();
+    let limits = Concurrency {
+        proofs: NonZeroUsize::MIN,
+        settlements: NonZeroUsize::MIN,
+    };
+
+    let reports = run_batch(
+        markets,
+        settler,
+        entries,
+        DECISION_AT,
+        limits,
+        BatchOptions::default(),
+    )
+    .await;
+    let rows: Vec<_> = reports.iter().map(ReportRow::new).collect();
+
+    let request_ids: Vec<_> = rows.iter().map(|row| row.request_id).collect();
+    assert_eq!(
+        request_ids,
+        (1..=3)
+            .map(|i| Some(request_id(signer, i)))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(rows[0].status, Status::Settled);
+    assert_eq!(rows[0].allowed, Some(true));
+    assert_eq!(rows[0].reasons.as_deref(), Some(""));
+    assert_eq!(rows[0].tx_hash, Some(B256::with_last_byte(1)));
+    assert_eq!(rows[0].decided_at, Some(DECISION_AT));
+    // The second entry's request expired, so there is no decision to report.
+    assert_eq!(
+        (rows[1].status, rows[1].stage, rows[1].allowed),
+        (Status::Failed, Some(Stage::Prove), None)
+    );
+    assert_eq!(
+        (rows[2].status, rows[2].stage, rows[2].allowed),
+        (Status::Failed, Some(Stage::Settle), Some(true))
+    );
+    assert_eq!(rows[2].error.as_deref(), Some("execution reverted"));
+    assert!(rows.iter().all(|row| row.finished_at > DECISION_AT));
+}
+
+#[test]
+fn csv_reports_round_trip() {
+    let rows = [
+        row(0, Some(7), Status::Settled),
+        row(1, None, Status::Failed),
+    ];
+
+    let csv = to_csv(&rows).unwrap();
+
+    let lines: Vec<_> = std::str::from_utf8(&csv).unwrap().lines().collect();
+    assert_eq!(
+        lines[2],
+        "1,0x0000000000000000000000000000000000000001,\
+         0x0000000000000000000000000000000000000000000000000000000000000001,failed,settle,\
+         \"execution reverted, twice\",false,aml_failed|kyc_tier_too_low,,,\
+         1000000000000000000,,1700000000,1700000060"
+    );
+    assert_eq!(from_csv(&csv).unwrap(), rows);
+}
+
+#[test]
+fn rejects_reports_with_other_columns() {
+    let csv = String::from_utf8(to_csv(&[row(0, Some(7), Status::Settled)]).unwrap()).unwrap();
+    let renamed = csv.replacen("tx_hash", "txHash", 1);
+
+    let err = from_csv(renamed.as_bytes()).unwrap_err();
+
+    assert!(
+        format!("{err:#}").contains("not the report columns"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn merge_keeps_the_last_row_of_each_request() {
+    let first = vec![
+        row(0, Some(7), Status::Failed),
+        row(1, Some(8), Status::Settled),
+        row(2, None, Status::Failed),
+    ];
+    let second = vec![
+        row(0, Some(9), Status::Settled),
+        row(1, Some(7), Status::Settled),
+        row(2, None, Status::Failed),
+    ];
+
+    let merged = merge_reports([first, second]);
+
+    let merged: Vec<_> = merged
+        .iter()
+        .map(|row| (row.request_id.map(|id| id.to::<u64>()), row.status))
+        .collect();
+    assert_eq!(
+        merged,
+        [
+            (Some(7), Status::Settled),
+            (Some(8), Status::Settled),
+            (None, Status::Failed),
+            (Some(9), Status::Settled),
+            (None, Status::Failed),
+        ]
+    );
+}
+
+#[test]
+fn reports_are_written_by_extension() {
+    let rows = [row(0, Some(7), Status::Settled)];
+    let csv = report_path("report.csv");
+
+    write_report(&csv, &rows).unwrap();
+
+    assert_eq!(read_report(&csv).unwrap(), rows);
+    assert!(!report_path("report.csv.tmp").exists());
+    let err = write_report(&report_path("report.json"), &rows).unwrap_err();
+    assert!(format!("{err:#}").contains("neither .csv nor .parquet"));
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn parquet_reports_round_trip() {
+    let rows = [
+        row(0, Some(7), Status::Settled),
+        row(1, None, Status::Failed),
+    ];
+    let path = report_path("report.parquet");
+
+    write_report(&path, &rows).unwrap();
+
+    assert_eq!(read_report(&path).unwrap(), rows);
+}
+
+#[cfg(not(feature = "parquet"))]
+#[test]
+fn parquet_reports_need_the_feature() {
+    let err = write_report(
+        &report_path("report.parquet"),
+        &[row(0, Some(7), Status::Settled)],
+    )
+    .unwrap_err();
+
+    assert!(format!("{err:#}").contains("`parquet` feature"));
+}
@@ -0,0 +1,284 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::BTreeSet, path::PathBuf};
+
+use alloy::primitives::{Address, Bytes, Signature, B256, U256};
+use app::{
+    batch::{
+        read_entries, BatchEntry, EntryAudit, EntryReport, Outcome, QuoteLeg, ReviewBand, Stage,
+        BATCH_INPUT_VERSION,
+    },
+    schema::{input_schema, output_schema, validate},
+    settle::Settlement,
+    signing::{ResultDomain, SignedResult},
+};
+use schemars::Schema;
+use serde_json::{json, Value};
+
+/// An entry with every optional field set, so that it serializes every field of the format.
+fn full_entry() -> BatchEntry {
+    BatchEntry {
+        user: Address::repeat_byte(0xab),
+        product_id: B256::with_last_byte(1),
+        amount: U256::MAX,
+        kyc_passed: true,
+        kyc_tier: Some(2),
+        required_tier: 1,
+        aml_passed: true,
+        claims: U256::from(0b11),
+        required_claims: U256::from(0b01),
+        required_attestations: 1,
+        product_policy_version: 3,
+        quote: Some(QuoteLeg {
+            product_id: B256::with_last_byte(2),
+            required_tier: 1,
+            required_claims: U256::from(1),
+            product_policy_version: 1,
+        }),
+        risk_score: 40,
+        review_band: Some(ReviewBand { low: 30, high: 60 }),
+        regime_id: 1,
+        settlement_token: Address::with_last_byte(0xee),
+        case_id: B256::repeat_byte(0xca),
+        min_trade: U256::from(10),
+        max_trade: Some(U256::from(1_000)),
+        ttl_secs: 3_600,
+        attester: Some(Address::with_last_byte(0xa7)),
+        attestation_signature: Some(Bytes::from_static(&[0x12, 0x34])),
+        governance: vec![Address::with_last_byte(0x90)],
+        override_signature: Some(Signature::new(U256::from(1), U256::from(2), true)),
+        compact_journal: true,
+        sha256_commitments: true,
+        decision_uuid: B256::repeat_byte(0xdd),
+        credential_hash: B256::repeat_byte(0xee),
+        settle_delay_secs: 30,
+        aml_sources: U256::from(0b111),
+        required_aml_sources: U256::from(0b011),
+        block_hash: B256::repeat_byte(0xbb),
+        kyc_provider: 7,
+        allowed_kyc_providers: vec![7, 9],
+    }
+}
+
+fn minimal_entry() -> BatchEntry {
+    serde_json::from_value(json!({
+        "user": "0x0000000000000000000000000000000000000001",
+        "productId": "0x0000000000000000000000000000000000000000000000000000000000000002",
+        "amount": 100,
+        "amlPassed": true,
+    }))
+    .unwrap()
+}
+
+fn definition<'a>(schema: &'a Schema, name: &str) -> &'a Value {
+    schema
+        .pointer(&format!("/$defs/{name}"))
+        .unwrap_or_else(|| panic!("no definition of {name}"))
+}
+
+fn keys(value: &Value) -> BTreeSet<String> {
+    value.as_object().unwrap().keys().cloned().collect()
+}
+
+fn input_file(name: &str, contents: &Value) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("schema-{name}.json"));
+    std::fs::write(&path, contents.to_string()).unwrap();
+    path
+}
+
+#[test]
+fn schema_id_follows_the_input_version() {
+    let suffix = format!(".v{BATCH_INPUT_VERSION}.schema.json");
+    for schema in [input_schema(), output_schema()] {
+        let id = schema.get("$id").and_then(Value::as_str).unwrap();
+        assert!(id.ends_with(&suffix), "{id}");
+    }
+}
+
+#[test]
+fn input_schema_lists_every_serialized_field() {
+    let schema = input_schema();
+    let entry = serde_json::to_value(full_entry()).unwrap();
+
+    let properties = |name| keys(&definition(&schema, name)["properties"]);
+    assert_eq!(properties("BatchEntry"), keys(&entry));
+    assert_eq!(properties("QuoteLeg"), keys(&entry["quote"]));
+    assert_eq!(properties("ReviewBand"), keys(&entry["reviewBand"]));
+}
+
+#[test]
+fn required_fields_are_those_serde_requires() {
+    let schema = input_schema();
+    let required = definition(&schema, "BatchEntry")["required"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|key| key.as_str().unwrap().to_owned())
+        .collect::<BTreeSet<_>>();
+    let entry = serde_json::to_value(full_entry()).unwrap();
+
+    for key in keys(&entry) {
+        let mut without = entry.clone();
+        without.as_object_mut().unwrap().remove(&key);
+        let parses = serde_json::from_value::<BatchEntry>(without.clone()).is_ok();
+        let validates = validate(&schema, &json!([without])).is_ok();
+        assert_eq!(parses, !required.contains(&key), "serde on missing {key}");
+        assert_eq!(validates, parses, "schema on missing {key}");
+    }
+}
+
+#[test]
+fn serialized_entries_validate_and_parse_back() {
+    let schema = input_schema();
+    for entry in [full_entry(), minimal_entry()] {
+        let value = serde_json::to_value([&entry]).unwrap();
+        validate(&schema, &value).unwrap();
+        assert_eq!(
+            serde_json::from_value::<Vec<BatchEntry>>(value).unwrap(),
+            [entry]
+        );
+    }
+}
+
+#[test]
+fn output_schema_accepts_every_outcome() {
+    let report = |outcome| EntryReport {
+        index: 0,
+        user: Address::with_last_byte(1),
+        product_id: B256::with_last_byte(1),
+        outcome,
+        audit: EntryAudit::default(),
+    };
+    let reports = [
+        report(Outcome::Settled {
+            request_id: U256::from(7),
+            settlement: Settlement::Confirmed {
+                tx_hash: B256::repeat_byte(1),
+            },
+        }),
+        report(Outcome::Settled {
+            request_id: U256::from(8),
+            settlement: Settlement::Relayed {
+                tx_hash: None,
+                task_id: Some("task".into()),
+            },
+        }),
+        report(Outcome::Settled {
+            request_id: U256::from(9),
+            settlement: Settlement::Attested {
+                uid: B256::repeat_byte(2),
+                tx_hash: B256::repeat_byte(3),
+                hook: Some(Box::new(Settlement::Confirmed {
+                    tx_hash: B256::repeat_byte(1),
+                })),
+            },
+        }),
+        report(Outcome::Settled {
+            request_id: U256::from(10),
+            settlement: Settlement::Attested {
+                uid: B256::repeat_byte(2),
+                tx_hash: B256::repeat_byte(3),
+                hook: None,
+            },
+        }),
+        report(Outcome::Settled {
+            request_id: U256::from(11),
+            settlement: Settlement::Pinned {
+                bundle_cid: "bafkreibundle".into(),
+                program_cid: "bafkreiprogram".into(),
+                settlement: Box::new(Settlement::Confirmed {
+                    tx_hash: B256::repeat_byte(1),
+                }),
+            },
+        }),
+        report(Outcome::Settled {
+            request_id: U256::from(12),
+            settlement: Settlement::Signed {
+                result: SignedResult {
+                    user: Address::repeat_byte(0xab),
+                    product_id: B256::with_last_byte(1),
+                    allowed: true,
+                    journal_digest: B256::repeat_byte(4),
+                    image_id: B256::repeat_byte(5),
+                    valid_until: 0,
+                    domain: ResultDomain {
+                        name: "ComplianceHook".into(),
+                        version: "1".into(),
+                        chain_id: 1,
+                        verifying_contract: Address::ZERO,
+                    },
+                    signature: Bytes::from(vec![0x1b; 65]),
+                },
+                settlement: Box::new(Settlement::Confirmed {
+                    tx_hash: B256::repeat_byte(1),
+                }),
+            },
+        }),
+        report(Outcome::Failed {
+            stage: Stage::Settle,
+            error: "reverted".into(),
+        }),
+    ];
+
+    validate(&output_schema(), &serde_json::to_value(reports).unwrap()).unwrap();
+}
+
+#[test]
+fn mistakes_are_reported_at_their_json_pointer() {
+    let valid = serde_json::to_value(minimal_entry()).unwrap();
+    let with = |key: &str, value: Value| {
+        let mut entry = valid.clone();
+        entry[key] = value;
+        json!([valid, entry])
+    };
+    let cases = [
+        ("short-address", with("user", json!("0x1234")), "/1/user"),
+        ("float-amount", with("amount", json!(1.5)), "/1/amount"),
+        (
+            "string-tier",
+            with("requiredTier", json!("2")),
+            "/1/requiredTier",
+        ),
+        (
+            "quote-without-product",
+            with("quote", json!({ "requiredTier": 1 })),
+            "/1/quote/productId",
+        ),
+        (
+            "too-many-keys",
+            with("governance", json!(vec![Address::ZERO; 9])),
+            "/1/governance",
+        ),
+    ];
+
+    for (name, contents, pointer) in cases {
+        let err = read_entries(&input_file(name, &contents)).unwrap_err();
+        assert!(
+            format!("{err:#}").contains(&format!("{pointer}: ")),
+            "{name}: {err:#}"
+        );
+    }
+}
+
+#[test]
+fn addresses_are_accepted_in_either_letter_case() {
+    let lower = "0xabababababababababababababababababababab";
+    for user in [lower.to_owned(), lower.to_uppercase().replacen('X', "x", 1)] {
+        let mut entry = serde_json::to_value(minimal_entry()).unwrap();
+        entry["user"] = json!(user);
+        let entries = read_entries(&input_file("address-case", &json!([entry]))).unwrap();
+        assert_eq!(entries[0].user, Address::repeat_byte(0xab));
+    }
+}
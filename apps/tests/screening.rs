@@ -0,0 +1,368 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::time::Instant;
+
+use alloy::primitives::{address, Address, U256};
+use app::{
+    attestation::{AttestationSource, HttpSource},
+    batch::BatchEntry,
+    screening::{
+        screen_entries, Screened, Screening, ScreeningConfig, ScreeningOutage, ScreeningSource,
+    },
+};
+use common::fast_retries;
+use serde_json::{json, Value};
+use url::Url;
+use wiremock::{
+    matchers::{header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const USER: Address = address!("0x00000000000000000000000000000000000000aa");
+
+/// A TRM-style config: a numeric score and risk indicators naming their category.
+fn config(server: &MockServer) -> ScreeningConfig {
+    format!(
+        r#"
+        url = "{}/addresses/{{address}}"
+        auth_header = "Authorization"
+        auth_prefix = "Basic "
+        risk_threshold = 70
+        sanctioned_categories = ["Sanctions"]
+
+        [mapping]
+        risk_score = "/riskScore"
+        categories = "/addressRiskIndicators"
+        category_field = "category"
+        "#,
+        server.uri()
+    )
+    .parse()
+    .unwrap()
+}
+
+fn source(config: ScreeningConfig) -> ScreeningSource {
+    ScreeningSource::new(config, Some("key".into())).with_policy(fast_retries())
+}
+
+fn user_path(user: Address) -> String {
+    format!("/addresses/{user}")
+}
+
+async fn respond(server: &MockServer, body: Value) {
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(body))
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn maps_score_and_categories() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(user_path(USER)))
+        .and(header("authorization", "Basic key"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "riskScore": 42,
+            "addressRiskIndicators": [{ "category": "Gambling", "riskType": "COUNTERPARTY" }],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let screening = source(config(&server)).screen(USER).await.unwrap();
+
+    assert_eq!(
+        screening,
+        Screening {
+            risk_score: 42,
+            categories: vec!["Gambling".into()],
+            aml_passed: true,
+        }
+    );
+}
+
+#[tokio::test]
+async fn fails_aml_at_the_threshold_or_on_a_sanctioned_category() {
+    for (body, reason) in [
+        (json!({ "riskScore": 70 }), "score at the threshold"),
+        (
+            json!({ "riskScore": 5, "addressRiskIndicators": [{ "category": "sanctions" }] }),
+            "sanctioned category",
+        ),
+    ] {
+        let server = MockServer::start().await;
+        respond(&server, body).await;
+
+        let screening = source(config(&server)).screen(USER).await.unwrap();
+
+        assert!(!screening.aml_passed, "{reason}");
+    }
+}
+
+#[tokio::test]
+async fn maps_named_risk_levels() {
+    let server = MockServer::start().await;
+    respond(&server, json!({ "risk": "Severe", "cluster": null })).await;
+    // A Chainalysis-style config: the level by name and no categories.
+    let config: ScreeningConfig = format!(
+        r#"
+        url = "{}/entities/{{address}}"
+        auth_header = "Token"
+        risk_threshold = 60
+
+        [mapping]
+        risk_score = "/risk"
+
+        [risk_levels]
+        Low = 10
+        Medium = 40
+        High = 70
+        Severe = 100
+        "#,
+        server.uri()
+    )
+    .parse()
+    .unwrap();
+
+    let screening = source(config).screen(USER).await.unwrap();
+
+    assert_eq!(screening.risk_score, 100);
+    assert!(screening.categories.is_empty());
+    assert!(!screening.aml_passed);
+}
+
+#[test]
+fn rejects_responses_it_cannot_map() {
+    let config: ScreeningConfig = r#"
+        url = "https://screening.example/{address}"
+        risk_threshold = 70
+
+        [mapping]
+        risk_score = "/riskScore"
+        categories = "/categories"
+        "#
+    .parse()
+    .unwrap();
+
+    for (body, message) in [
+        (json!({}), "no risk score at /riskScore"),
+        (json!({ "riskScore": 300 }), "not between 0 and 255"),
+        (json!({ "riskScore": "High" }), "not in risk_levels"),
+        (
+            json!({ "riskScore": 1, "categories": "Sanctions" }),
+            "not an array",
+        ),
+        (
+            json!({ "riskScore": 1, "categories": [{ "name": "Sanctions" }] }),
+            "invalid categories",
+        ),
+    ] {
+        let err = config.screening(&body).unwrap_err();
+        assert!(format!("{err:#}").contains(message), "{err:#}");
+    }
+}
+
+#[test]
+fn rejects_configs_without_the_address_placeholder() {
+    let err = r#"
+        url = "https://screening.example/addresses"
+        risk_threshold = 70
+
+        [mapping]
+        risk_score = "/riskScore"
+        "#
+    .parse::<ScreeningConfig>()
+    .unwrap_err();
+
+    assert!(
+        err.to_string().contains("no {address} placeholder"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn caches_screenings_within_the_ttl() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(user_path(USER)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "riskScore": 1 })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let source = source(config(&server));
+
+    let first = source.screen(USER).await.unwrap();
+    let second = source.screen(USER).await.unwrap();
+
+    assert_eq!(first, second);
+}
+
+#[tokio::test]
+async fn screens_every_time_without_a_ttl() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "riskScore": 1 })))
+        .expect(2)
+        .mount(&server)
+        .await;
+    let source = source(ScreeningConfig {
+        cache_ttl_secs: 0,
+        ..config(&server)
+    });
+
+    source.screen(USER).await.unwrap();
+    source.screen(USER).await.unwrap();
+}
+
+#[tokio::test]
+async fn spaces_requests_to_the_rate_limit() {
+    let server = MockServer::start().await;
+    respond(&server, json!({ "riskScore": 1 })).await;
+    let source = source(ScreeningConfig {
+        requests_per_sec: 10,
+        ..config(&server)
+    });
+
+    let started = Instant::now();
+    for user in 1..=4 {
+        source.screen(Address::with_last_byte(user)).await.unwrap();
+    }
+
+    // The first request goes out at once and each of the others 100ms after the one before.
+    assert!(
+        started.elapsed().as_millis() >= 300,
+        "{:?}",
+        started.elapsed()
+    );
+}
+
+fn is_outage(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<ScreeningOutage>().is_some()
+}
+
+#[tokio::test]
+async fn reports_vendor_outages_as_such() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(503))
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let err = source(config(&server)).screen(USER).await.unwrap_err();
+
+    assert!(is_outage(&err), "{err:#}");
+    assert!(format!("{err:#}").contains("is unavailable"), "{err:#}");
+}
+
+#[tokio::test]
+async fn reports_rate_limiting_and_unreachable_vendors_as_outages() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(429))
+        .mount(&server)
+        .await;
+    let err = source(config(&server)).screen(USER).await.unwrap_err();
+    assert!(is_outage(&err), "{err:#}");
+
+    let config = config(&server);
+    drop(server);
+    let err = source(config).screen(USER).await.unwrap_err();
+    assert!(is_outage(&err), "{err:#}");
+}
+
+#[tokio::test]
+async fn other_failures_are_not_outages() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(404).set_body_string("unknown address"))
+        .mount(&server)
+        .await;
+
+    let err = source(config(&server)).screen(USER).await.unwrap_err();
+
+    assert!(!is_outage(&err), "{err:#}");
+    assert!(err.to_string().contains("404"), "{err:#}");
+}
+
+#[tokio::test]
+async fn outages_are_not_cached() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(3)
+        .mount(&server)
+        .await;
+    respond(&server, json!({ "riskScore": 1 })).await;
+    let source = source(config(&server));
+
+    source.screen(USER).await.unwrap_err();
+    assert!(source.screen(USER).await.unwrap().aml_passed);
+}
+
+#[tokio::test]
+async fn screened_source_keeps_kyc_and_replaces_aml() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/kyc/{USER}")))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "kycTier": 2, "amlPassed": true, "claims": "0x1" })),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(user_path(USER)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "riskScore": 90 })))
+        .mount(&server)
+        .await;
+    let kyc = HttpSource::new(Url::parse(&format!("{}/kyc", server.uri())).unwrap(), None)
+        .with_policy(fast_retries());
+
+    let status = Screened::new(kyc, source(config(&server)))
+        .fetch(USER)
+        .await
+        .unwrap();
+
+    assert_eq!(status.kyc_tier(), 2);
+    assert_eq!(status.claims, U256::from(1));
+    assert!(!status.aml_passed);
+    assert_eq!(status.risk_score, Some(90));
+}
+
+#[tokio::test]
+async fn screens_entries_in_place() {
+    let server = MockServer::start().await;
+    respond(&server, json!({ "riskScore": 12 })).await;
+    let mut entries: Vec<BatchEntry> = serde_json::from_value(json!([{
+        "user": USER,
+        "productId": "0x0000000000000000000000000000000000000000000000000000000000000001",
+        "amount": "0x64",
+        "kycTier": 3,
+        "amlPassed": false,
+    }]))
+    .unwrap();
+
+    screen_entries(&source(config(&server)), &mut entries)
+        .await
+        .unwrap();
+
+    assert_eq!(entries[0].kyc_tier, Some(3));
+    assert!(entries[0].aml_passed);
+    assert_eq!(entries[0].risk_score, 12);
+}
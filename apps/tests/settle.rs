@@ -0,0 +1,146 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::time::Duration;
+
+use alloy::{
+    primitives::{Address, Bytes, Log, B256, U256},
+    sol_types::SolEvent,
+};
+use app::{
+    batch::BatchEntry,
+    compliance_hook::IComplianceHook::TradeAllowed,
+    journal::unix_now,
+    settle::{verify_recorded_decision, CachedDecision, DelayingSettler, Settler},
+};
+use common::{entry, MockSettler};
+use tokio::time::Instant;
+
+const HOOK: Address = Address::repeat_byte(0xaa);
+
+fn trade_allowed(address: Address, entry: &BatchEntry, journal_digest: B256) -> Log {
+    let event = TradeAllowed {
+        user: entry.user,
+        productId: entry.product_id,
+        amount: entry.amount,
+        journalDigest: journal_digest,
+    };
+    Log {
+        address,
+        data: event.encode_log_data(),
+    }
+}
+
+#[test]
+fn accepts_matching_onchain_decision() {
+    let digest = B256::repeat_byte(7);
+    let logs = [trade_allowed(HOOK, &entry(1), digest)];
+
+    verify_recorded_decision(&logs, HOOK, &entry(1), digest).unwrap();
+}
+
+#[test]
+fn rejects_onchain_decision_for_another_journal() {
+    let logs = [trade_allowed(HOOK, &entry(1), B256::repeat_byte(7))];
+
+    let err = verify_recorded_decision(&logs, HOOK, &entry(1), B256::repeat_byte(8)).unwrap_err();
+    assert!(
+        err.to_string().contains("does not match submitted"),
+        "{err}"
+    );
+}
+
+#[test]
+fn ignores_events_from_other_contracts() {
+    let digest = B256::repeat_byte(7);
+    let logs = [trade_allowed(Address::repeat_byte(0xbb), &entry(1), digest)];
+
+    let err = verify_recorded_decision(&logs, HOOK, &entry(1), digest).unwrap_err();
+    assert!(
+        err.to_string().contains("emitted no TradeAllowed event"),
+        "{err}"
+    );
+}
+
+#[test]
+fn explains_remaining_ttl_of_cached_decision() {
+    let cached = CachedDecision {
+        journal_digest: B256::repeat_byte(7),
+        expires_at: 1_700_003_600,
+        remaining: U256::from(3_000),
+    };
+
+    assert_eq!(
+        cached.explain(),
+        format!(
+            "cached:      {}\nexpires at:  2023-11-14T23:13:20Z (1700003600)\n\
+             remaining:   50m\n",
+            B256::repeat_byte(7)
+        )
+    );
+    let expired = CachedDecision {
+        remaining: U256::ZERO,
+        ..cached
+    };
+    assert!(expired.explain().ends_with("remaining:   expired\n"));
+    let none = CachedDecision {
+        journal_digest: B256::ZERO,
+        expires_at: 0,
+        remaining: U256::ZERO,
+    };
+    assert_eq!(none.explain(), "cached:      no\n");
+}
+
+#[test]
+fn commits_the_settle_delay_as_not_before() {
+    let decision_at = 1_700_000_000;
+    let delayed = BatchEntry {
+        settle_delay_secs: 30,
+        ..entry(1)
+    };
+
+    assert_eq!(delayed.input(decision_at).notBefore, decision_at + 30);
+    assert_eq!(
+        delayed.expected_journal(decision_at).notBefore,
+        decision_at + 30
+    );
+    assert_eq!(entry(1).expected_journal(decision_at).notBefore, 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn waits_until_not_before_to_settle() {
+    for (delay, min_wait) in [(0, 0), (30, 29)] {
+        let entry = BatchEntry {
+            settle_delay_secs: delay,
+            ..entry(1)
+        };
+        let journal: Bytes = entry
+            .expected_journal(unix_now())
+            .encode(entry.journal_encoding())
+            .into();
+        let settler = DelayingSettler::new(MockSettler::new(Duration::ZERO));
+
+        let started = Instant::now();
+        settler
+            .settle(&entry, journal, Bytes::from_static(b"seal"))
+            .await
+            .unwrap();
+
+        // The delay is counted in whole seconds of the wall clock, so it may be one short.
+        let waited = started.elapsed().as_secs();
+        assert!((min_wait..=delay).contains(&waited), "{delay}s: {waited}s");
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::time::Duration;
+
+use alloy::{
+    primitives::{address, b256, hex, Address, Bytes, Signature, B256, U256},
+    signers::local::PrivateKeySigner,
+    sol_types::SolStruct,
+};
+use app::{
+    batch::BatchEntry,
+    ipfs::ProofBundle,
+    journal::{compliance_image_id, ComplianceJournal},
+    settle::{Settlement, Settler},
+    signing::{ComplianceResult, ResultDomain, ResultSigner, SignedResult, SigningSettler},
+    webhook::Decision,
+};
+use common::MockSettler;
+use guests::COMPLIANCE_ELF;
+use sha2::{Digest, Sha256};
+
+const DECISION_AT: u64 = 1_700_000_000;
+
+/// The key and address of the operator in the known vectors.
+const OPERATOR_KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const OPERATOR: Address = address!("0x2c7536e3605d9c16a7a3d7b1898e529396a65c23");
+
+fn operator() -> PrivateKeySigner {
+    OPERATOR_KEY.parse().unwrap()
+}
+
+fn domain() -> ResultDomain {
+    ResultDomain {
+        name: "ComplianceHook".into(),
+        version: "1".into(),
+        chain_id: 11155111,
+        verifying_contract: Address::with_last_byte(0xcc),
+    }
+}
+
+fn result() -> ComplianceResult {
+    ComplianceResult {
+        user: Address::with_last_byte(0xaa),
+        productId: B256::with_last_byte(1),
+        allowed: true,
+        journalDigest: B256::repeat_byte(0x11),
+        imageId: B256::repeat_byte(0x22),
+        validUntil: 1_700_003_600,
+    }
+}
+
+fn entry() -> BatchEntry {
+    BatchEntry {
+        product_id: B256::with_last_byte(1),
+        ..common::entry(0xaa)
+    }
+}
+
+fn journal(entry: &BatchEntry) -> Bytes {
+    entry
+        .expected_journal(DECISION_AT)
+        .encode(entry.journal_encoding())
+        .into()
+}
+
+#[test]
+fn hashes_match_known_vectors() {
+    assert_eq!(
+        result().eip712_type_hash(),
+        b256!("0xe263367e92abdd8eef6b235635517c7d3be430c61a6c5a329115c9bd7bcd88cd")
+    );
+    assert_eq!(
+        domain().eip712_domain().separator(),
+        b256!("0xc64e1ec781e48ffb62cd6e89a1683b8c988b87983071328893729b07a0d95ddf")
+    );
+    assert_eq!(
+        domain().signing_hash(&result()),
+        b256!("0x389c47470a6603f3c3440473cf684fe4eb8a930ea8636f62e6013b5154728d02")
+    );
+
+    // The same result signed for another chain is another message.
+    let mainnet = ResultDomain {
+        chain_id: 1,
+        ..domain()
+    };
+    assert_eq!(
+        mainnet.signing_hash(&result()),
+        b256!("0x804e79f37e4ab1dc1259692a47e2861452afaf0590bab9bdd487d39bfc3e241e")
+    );
+}
+
+#[test]
+fn signs_journals_and_recovers_known_vector() {
+    let journal = journal(&entry());
+    let signed = ResultSigner::new(operator(), domain())
+        .with_validity(Some(Duration::from_secs(3600)))
+        .sign(&journal)
+        .unwrap();
+    let decision = ComplianceJournal::try_from(journal.as_ref()).unwrap();
+    assert_eq!(signed.user, decision.user);
+    assert_eq!(signed.image_id, compliance_image_id());
+    assert_eq!(signed.valid_until, DECISION_AT + 3600);
+    assert_eq!(
+        signed.journal_digest,
+        B256::from_slice(&Sha256::digest(&journal))
+    );
+    signed.verify(OPERATOR).unwrap();
+    // Without a validity, results do not expire.
+    let unbounded = ResultSigner::new(operator(), domain())
+        .sign(&journal)
+        .unwrap();
+    assert_eq!(unbounded.valid_until, 0);
+
+    let signature = hex!(
+        "826063e0f4bdd15d2cb048a08ebb2934bf6bcd91beeb1e49d9ebfe97ea85c903"
+        "5a2e1517289383fd04886d30c0603d4d6677e2359dc0ec7c3fdc1aca60bf5ece1c"
+    );
+    let known = SignedResult::new(
+        result(),
+        domain(),
+        Signature::try_from(&signature[..]).unwrap(),
+    );
+    assert_eq!(known.signature, Bytes::from(signature));
+    assert_eq!(known.signer().unwrap(), OPERATOR);
+
+    let err = known.verify(Address::with_last_byte(1)).unwrap_err();
+    assert!(format!("{err:#}").contains("not by operator"), "{err:#}");
+    // A signature under another domain recovers someone else.
+    let moved = SignedResult {
+        domain: ResultDomain {
+            chain_id: 1,
+            ..domain()
+        },
+        ..known
+    };
+    assert!(moved.verify(OPERATOR).is_err());
+}
+
+#[test]
+fn signed_result_round_trips_as_json() {
+    let signed = ResultSigner::new(operator(), domain())
+        .sign(&journal(&entry()))
+        .unwrap();
+    let json = serde_json::to_value(&signed).unwrap();
+    assert_eq!(json["domain"]["chainId"], 11155111);
+    assert_eq!(
+        json["domain"]["verifyingContract"],
+        "0x00000000000000000000000000000000000000cc"
+    );
+    assert_eq!(json["signature"].as_str().unwrap().len(), 2 + 65 * 2);
+    let parsed: SignedResult = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed, signed);
+}
+
+#[tokio::test]
+async fn settler_signs_before_settling() {
+    let settler = SigningSettler::new(
+        MockSettler::new(Duration::ZERO),
+        ResultSigner::new(operator(), domain()),
+    );
+    let entry = entry();
+    let journal = journal(&entry);
+    let settlement = settler
+        .settle(&entry, journal.clone(), Bytes::from_static(b"seal"))
+        .await
+        .unwrap();
+
+    let Settlement::Signed {
+        result,
+        settlement: inner,
+    } = &settlement
+    else {
+        panic!("expected a signed settlement, got {settlement:?}");
+    };
+    assert!(matches!(**inner, Settlement::Confirmed { .. }));
+    assert_eq!(settlement.tx_hash(), inner.tx_hash());
+    result.verify(OPERATOR).unwrap();
+
+    // The webhook payload carries the signed result.
+    let decision = ComplianceJournal::try_from(journal.as_ref()).unwrap();
+    let payload = Decision::settled(U256::from(7), &decision, &settlement);
+    assert_eq!(
+        serde_json::to_value(payload).unwrap()["signedResult"],
+        serde_json::to_value(result).unwrap()
+    );
+}
+
+#[test]
+fn bundle_rejects_result_of_another_journal() {
+    let entry = entry();
+    let journal = journal(&entry);
+    let signed = ResultSigner::new(operator(), domain())
+        .sign(&journal)
+        .unwrap();
+    let bundle = ProofBundle {
+        image_id: compliance_image_id(),
+        program_cid: "bafkprogram".into(),
+        journal,
+        seal: Bytes::new(),
+        signed_result: Some(signed.clone()),
+    };
+    bundle.verify(COMPLIANCE_ELF).unwrap();
+
+    let forged = ProofBundle {
+        signed_result: Some(SignedResult {
+            journal_digest: B256::repeat_byte(0x11),
+            ..signed
+        }),
+        ..bundle
+    };
+    let err = forged.verify(COMPLIANCE_ELF).unwrap_err();
+    assert!(
+        format!("{err:#}").contains("signed result is of journal"),
+        "{err:#}"
+    );
+}
@@ -0,0 +1,118 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use alloy::primitives::{address, b256, U256};
+use app::webhook::{sign, Decision, WebhookSink, SIGNATURE_HEADER};
+use common::fast_retries;
+use serde_json::{json, Value};
+use url::Url;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn decision() -> Decision {
+    Decision {
+        request_id: U256::from(7),
+        user: address!("0x00000000000000000000000000000000000000aa"),
+        product_id: b256!("0x0000000000000000000000000000000000000000000000000000000000000001"),
+        allowed: false,
+        reasons: vec!["aml_failed"],
+        tx_hash: None,
+        signed_result: None,
+    }
+}
+
+fn sink(server: &MockServer, secret: Option<&str>) -> WebhookSink {
+    let url = Url::parse(&format!("{}/hooks/decision", server.uri())).unwrap();
+    WebhookSink::new(url, secret.map(Into::into)).with_policy(fast_retries())
+}
+
+#[tokio::test]
+async fn delivers_signed_decision() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/hooks/decision"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    sink(&server, Some("shh"))
+        .deliver(&decision())
+        .await
+        .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(
+        body,
+        json!({
+            "requestId": "0x7",
+            "user": "0x00000000000000000000000000000000000000aa",
+            "productId": "0x0000000000000000000000000000000000000000000000000000000000000001",
+            "allowed": false,
+            "reasons": ["aml_failed"],
+            "txHash": null,
+        })
+    );
+    assert_eq!(
+        requests[0].headers[SIGNATURE_HEADER],
+        sign(b"shh", &requests[0].body).as_str()
+    );
+}
+
+#[tokio::test]
+async fn retries_failed_deliveries() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+
+    sink(&server, Some("shh"))
+        .deliver(&decision())
+        .await
+        .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 3);
+    assert!(requests
+        .iter()
+        .all(|request| request.body == requests[0].body
+            && request.headers[SIGNATURE_HEADER] == requests[0].headers[SIGNATURE_HEADER]));
+}
+
+#[tokio::test]
+async fn omits_signature_without_secret() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    sink(&server, None).deliver(&decision()).await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    assert!(!requests[0].headers.contains_key(SIGNATURE_HEADER));
+}
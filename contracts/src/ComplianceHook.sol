@@ -20,9 +20,11 @@ import {IComplianceHook} from "./IComplianceHook.sol";
 
 /// @title RWA Compliance Trading Hook
 /// @notice Uses a RISC Zero proof to decide whether a user is allowed to trade a product.
-/// @dev The guest program computes a boolean `allowed` based on off-chain compliance data
-///      and commits `(user, productId, allowed)` to the journal. The hook verifies the
-///      proof and checks that `allowed == true` for the given user and product.
+/// @dev The guest commits the decision and the context it was made in to an ABI-encoded or compact journal; the
+///      compact form is a version byte followed by the same fields as `abi.encodePacked` lays them out. The hook
+///      verifies the proof against `IMAGE_ID` and accepts an allowed decision only if it still matches the
+///      product's configuration. The host checks the attester's EIP-1271 signature before requesting a proof.
+///      Notes on individual fields sit on `Decision` and the journal offsets below.
 contract ComplianceHook is IComplianceHook {
     /// @notice RISC Zero verifier contract address.
     IRiscZeroVerifier public immutable VERIFIER;
@@ -30,22 +32,430 @@ contract ComplianceHook is IComplianceHook {
     /// @notice Image ID of the compliance guest program.
     bytes32 public constant IMAGE_ID = ImageID.COMPLIANCE_ID;
 
+    /// @notice Image ID of the batch guest program, whose journals `recordBatch` takes.
+    bytes32 public constant BATCH_IMAGE_ID = ImageID.BATCH_ID;
+
+    /// @notice Version byte that starts a compact journal.
+    uint8 public constant COMPACT_JOURNAL_VERSION = 1;
+
+    /// @notice Length of a compact journal.
+    uint256 public constant COMPACT_JOURNAL_LENGTH = 680;
+
+    /// @notice Length of an ABI-encoded journal.
+    uint256 public constant ABI_JOURNAL_LENGTH = 1184;
+
+    /// @notice Length of a batch journal, which is always ABI-encoded.
+    uint256 public constant BATCH_JOURNAL_LENGTH = 11 * 32;
+
+    /// @notice Bit of the `reasons` mask set when a governance key overrode the decision.
+    uint32 public constant REASON_OVERRIDE = 1 << 3;
+
+    /// @dev Offset of `governanceHash` in an ABI-encoded journal; the fields after it are
+    ///      `requiredAttestations`, `achievedAttestations`, `productPolicyVersion`, `quoteProduct`,
+    ///      `quotePolicyVersion`, `needsReview`, `regimeId`, `settlementToken`, `caseId`, `tradeAmount`,
+    ///      `minTrade`, `maxTrade`, `decisionAt`, `ttlSecs`, `commitmentHash`, `decisionUuid`,
+    ///      `credentialHash`, `notBefore`, `amlSources`, `blockHash`, `kycProvider`, `consentGiven`,
+    ///      `consentAt`, `attestationSignatureHash`, `schemaHash` and `imageId`.
+    uint256 internal constant GOVERNANCE_HASH_OFFSET = 10 * 32;
+
+    /// @dev Offset of `settlementToken` in an ABI-encoded journal.
+    uint256 internal constant SETTLEMENT_TOKEN_OFFSET = 18 * 32;
+
+    /// @dev Offset of `tradeAmount` in an ABI-encoded journal; `minTrade`, `maxTrade`, `decisionAt` and
+    ///      `ttlSecs` follow it.
+    uint256 internal constant TRADE_AMOUNT_OFFSET = 20 * 32;
+
+    /// @dev Offset of `decisionUuid` in an ABI-encoded journal; `credentialHash`, `notBefore`,
+    ///      `amlSources`, `blockHash`, `kycProvider`, `consentGiven`, `consentAt`, `attestationSignatureHash`,
+    ///      `schemaHash` and `imageId` follow it to the end. The hook does not check `commitmentHash`, just before
+    ///      it, which only records whether `attestationsHash` was computed with keccak or SHA-256, nor
+    ///      `credentialHash`, the verifiable credential the compliance fields came from. `amlSources`,
+    ///      `kycProvider`, `consentGiven` and `consentAt` are enforced by the guest, which denies a decision the
+    ///      product's requirements are not met for. `blockHash` is the block onchain state was read at, or zero.
+    ///      `schemaHash` names the journal layout for off-chain readers; the hook leaves the layout to the image id.
+    uint256 internal constant DECISION_UUID_OFFSET = 26 * 32;
+
+    /// @dev Offset of `aggregateCap` in a batch journal; `aggregateAmount`, `capExceeded`, `allAllowed` and
+    ///      `cohortRoot` follow it to the end.
+    uint256 internal constant BATCH_AGGREGATE_CAP_OFFSET = 6 * 32;
+
+    /// @notice Account allowed to set product policy versions; the deployer.
+    address public immutable ADMIN;
+
+    /// @notice Current eligibility policy version of each product. Products never configured are at
+    ///         version 0.
+    mapping(bytes32 productId => uint32 version) public productPolicyVersion;
+
+    /// @notice Regulatory regime of each product's jurisdiction, which decisions for it must have
+    ///         been made under. Products never configured are under regime 0, no regional regime.
+    mapping(bytes32 productId => uint16 regimeId) public productRegime;
+
+    /// @notice Tokens each product's trades may settle in.
+    mapping(bytes32 productId => mapping(address token => bool allowed)) public settlementTokenAllowed;
+
+    /// @notice Number of tokens allowed for each product. A product with none allowed, as products never
+    ///         configured are, settles in any token.
+    mapping(bytes32 productId => uint256 count) public settlementTokenCount;
+
+    /// @notice `keccak256(abi.encode(address[4]))` of the governance keys allowed to override decisions.
+    ///         Zero until set, so no override is accepted.
+    bytes32 public governanceSetHash;
+
+    /// @notice Journal digests of the decisions held for manual review.
+    mapping(bytes32 journalDigest => bool held) public heldForReview;
+
+    /// @notice Longest each product's allowed decisions are cached for, whatever TTL they were proven
+    ///         with. Products never configured have none, so their decisions are not cached.
+    mapping(bytes32 productId => uint64 ttlSecs) public maxDecisionTtl;
+
+    /// @notice Decision UUIDs `beforeTrade` has settled.
+    mapping(bytes32 decisionUuid => bool settled) public settledDecisions;
+
+    /// @notice Most the aggregate cap a batch was proven with may be for `recordBatch` to accept it.
+    ///         Zero until set, so no batch with an allowed trade is accepted.
+    uint256 public batchAggregateCap;
+
+    /// @notice Cohort roots of the batches `recordBatch` has recorded. Each member of a cohort proves
+    ///         inclusion against its root off-chain.
+    mapping(bytes32 cohortRoot => bool recorded) public recordedCohorts;
+
+    /// @dev The allowed decision cached for each user and product.
+    mapping(address user => mapping(bytes32 productId => CachedDecision)) internal _cachedDecisions;
+
+    /// @notice Emitted when a product's policy version changes.
+    event ProductPolicyVersionSet(bytes32 indexed productId, uint32 version);
+
+    /// @notice Emitted when a product's regulatory regime changes.
+    event ProductRegimeSet(bytes32 indexed productId, uint16 regimeId);
+
+    /// @notice Emitted when a token is allowed or disallowed for settling a product's trades.
+    event SettlementTokenAllowedSet(bytes32 indexed productId, address indexed token, bool allowed);
+
+    /// @notice Emitted when the governance set changes.
+    event GovernanceSetHashSet(bytes32 governanceSetHash);
+
+    /// @notice Emitted when the longest a product's decisions are cached for changes.
+    event MaxDecisionTtlSet(bytes32 indexed productId, uint64 ttlSecs);
+
+    /// @notice Emitted when the most a batch's aggregate cap may be changes.
+    event BatchAggregateCapSet(uint256 cap);
+
+    /// @dev The fields of a journal the hook checks.
+    struct Decision {
+        address user;
+        bytes32 productId;
+        bool allowed;
+        uint32 reasons;
+        // Hash of the governance set the guest checked an override's key against; an overridden
+        // decision is only accepted when it is `governanceSetHash`.
+        bytes32 governanceHash;
+        // Must be the product's current version, and `quotePolicyVersion` that of `quoteProduct`, the
+        // product the trade was paired with or the product itself for a single-product trade.
+        uint32 policyVersion;
+        bytes32 quoteProduct;
+        uint32 quotePolicyVersion;
+        // A decision that needs review is neither accepted nor rejected: `beforeTrade` reverts with a
+        // distinct reason and the venue records it with `holdForReview`.
+        bool needsReview;
+        uint16 regimeId;
+        address settlementToken;
+        uint256 tradeAmount;
+        uint256 minTrade;
+        uint256 maxTrade;
+        uint64 decisionAt;
+        // How long after `decisionAt` an allowed decision is cached for `beforeTradeCached`, capped by
+        // the product's `maxDecisionTtl`.
+        uint64 ttlSecs;
+        // Settled once when nonzero, so a retried settlement reverts instead of emitting a second
+        // `TradeAllowed`.
+        bytes32 decisionUuid;
+        // `beforeTrade` reverts before it, giving the venue time to see the decision.
+        uint64 notBefore;
+        bytes32 imageId;
+    }
+
+    /// @dev An allowed decision `beforeTradeCached` accepts until `expiresAt`, with what it must still
+    ///      match of the product's configuration.
+    struct CachedDecision {
+        bytes32 journalDigest;
+        uint64 expiresAt;
+        uint32 policyVersion;
+        uint32 quotePolicyVersion;
+        uint16 regimeId;
+        address settlementToken;
+        bytes32 quoteProduct;
+        uint256 minTrade;
+        uint256 maxTrade;
+    }
+
     constructor(IRiscZeroVerifier _verifier) {
         VERIFIER = _verifier;
+        ADMIN = msg.sender;
+    }
+
+    /// @notice Set the policy version decisions for `productId` must have been made under.
+    function setProductPolicyVersion(bytes32 productId, uint32 version) external {
+        require(msg.sender == ADMIN, "ComplianceHook: not admin");
+        productPolicyVersion[productId] = version;
+        emit ProductPolicyVersionSet(productId, version);
+    }
+
+    /// @notice Set the regulatory regime decisions for `productId` must have been made under.
+    function setProductRegime(bytes32 productId, uint16 regimeId) external {
+        require(msg.sender == ADMIN, "ComplianceHook: not admin");
+        productRegime[productId] = regimeId;
+        emit ProductRegimeSet(productId, regimeId);
+    }
+
+    /// @notice Allow or disallow settling trades of `productId` in `token`.
+    function setSettlementTokenAllowed(bytes32 productId, address token, bool allowed) external {
+        require(msg.sender == ADMIN, "ComplianceHook: not admin");
+        if (settlementTokenAllowed[productId][token] != allowed) {
+            settlementTokenAllowed[productId][token] = allowed;
+            if (allowed) {
+                settlementTokenCount[productId] += 1;
+            } else {
+                settlementTokenCount[productId] -= 1;
+            }
+        }
+        emit SettlementTokenAllowedSet(productId, token, allowed);
+    }
+
+    /// @notice Set the hash of the governance set whose keys may override compliance decisions.
+    function setGovernanceSetHash(bytes32 _governanceSetHash) external {
+        require(msg.sender == ADMIN, "ComplianceHook: not admin");
+        governanceSetHash = _governanceSetHash;
+        emit GovernanceSetHashSet(_governanceSetHash);
+    }
+
+    /// @notice Set the longest allowed decisions for `productId` are cached for. Zero stops caching them;
+    ///         decisions already cached keep their expiry.
+    function setMaxDecisionTtl(bytes32 productId, uint64 ttlSecs) external {
+        require(msg.sender == ADMIN, "ComplianceHook: not admin");
+        maxDecisionTtl[productId] = ttlSecs;
+        emit MaxDecisionTtlSet(productId, ttlSecs);
+    }
+
+    /// @notice Set the most the aggregate cap of a batch `recordBatch` accepts may be.
+    function setBatchAggregateCap(uint256 cap) external {
+        require(msg.sender == ADMIN, "ComplianceHook: not admin");
+        batchAggregateCap = cap;
+        emit BatchAggregateCapSet(cap);
     }
 
     function beforeTrade(address user, bytes32 productId, uint256 amount, bytes calldata journal, bytes calldata seal)
         external
         override
     {
-        VERIFIER.verify(seal, IMAGE_ID, sha256(journal));
+        (Decision memory decision, bytes32 journalDigest) = _verifyDecision(user, productId, amount, journal, seal);
+        require(!decision.needsReview, "ComplianceHook: review required");
+        require(decision.allowed, "ComplianceHook: user not allowed");
+        require(block.timestamp >= decision.notBefore, "ComplianceHook: decision not yet valid");
+        // A zero UUID is from a host that does not deduplicate, and is never recorded.
+        if (decision.decisionUuid != bytes32(0)) {
+            require(!settledDecisions[decision.decisionUuid], "ComplianceHook: decision already settled");
+            settledDecisions[decision.decisionUuid] = true;
+        }
+        _cacheDecision(decision, journalDigest);
+
+        emit TradeAllowed(user, productId, amount, journalDigest);
+    }
 
-        (address journalUser, bytes32 journalProductId, bool allowed) = abi.decode(journal, (address, bytes32, bool));
+    function beforeTradeCached(address user, bytes32 productId, uint256 amount) external override {
+        CachedDecision storage cached = _cachedDecisions[user][productId];
+        require(block.timestamp < cached.expiresAt, "ComplianceHook: no cached decision");
+        require(cached.policyVersion == productPolicyVersion[productId], "ComplianceHook: policy version mismatch");
+        require(
+            cached.quotePolicyVersion == productPolicyVersion[cached.quoteProduct],
+            "ComplianceHook: quote policy version mismatch"
+        );
+        require(cached.regimeId == productRegime[productId], "ComplianceHook: regime mismatch");
+        require(
+            settlementTokenCount[productId] == 0 || settlementTokenAllowed[productId][cached.settlementToken],
+            "ComplianceHook: settlement token not allowed"
+        );
+        // The guest only checked the amount it was given against the size band.
+        require(
+            amount >= cached.minTrade && amount <= cached.maxTrade, "ComplianceHook: trade size out of range"
+        );
+
+        emit TradeAllowed(user, productId, amount, cached.journalDigest);
+    }
+
+    function cachedDecision(address user, bytes32 productId)
+        external
+        view
+        override
+        returns (bytes32 journalDigest, uint64 expiresAt, uint256 remaining)
+    {
+        CachedDecision storage cached = _cachedDecisions[user][productId];
+        journalDigest = cached.journalDigest;
+        expiresAt = cached.expiresAt;
+        remaining = block.timestamp < expiresAt ? expiresAt - block.timestamp : 0;
+    }
+
+    function holdForReview(address user, bytes32 productId, uint256 amount, bytes calldata journal, bytes calldata seal)
+        external
+        override
+    {
+        (Decision memory decision, bytes32 journalDigest) = _verifyDecision(user, productId, amount, journal, seal);
+        require(decision.needsReview, "ComplianceHook: review not required");
+        heldForReview[journalDigest] = true;
+
+        emit TradeHeldForReview(user, productId, amount, journalDigest);
+    }
+
+    function recordBatch(bytes calldata journal, bytes calldata seal) external override {
+        bytes32 journalDigest = sha256(journal);
+        VERIFIER.verify(seal, BATCH_IMAGE_ID, journalDigest);
+        require(journal.length == BATCH_JOURNAL_LENGTH, "ComplianceHook: invalid batch journal length");
+
+        // pages | count | pagesDigest | journalsDigest | exceeded | heapCapExceeded | aggregateCap |
+        // aggregateAmount | capExceeded | allAllowed | cohortRoot
+        (, uint32 count) = abi.decode(journal, (uint32, uint32));
+        (uint256 aggregateCap, uint256 aggregateAmount, bool capExceeded, bool allAllowed, bytes32 cohortRoot) =
+            abi.decode(journal[BATCH_AGGREGATE_CAP_OFFSET:], (uint256, uint256, bool, bool, bytes32));
+        // The host picks the cap the guest checks against, so it must be one the hook allows.
+        require(aggregateCap <= batchAggregateCap, "ComplianceHook: aggregate cap too high");
+        require(!capExceeded, "ComplianceHook: aggregate cap exceeded");
+        // Unset for a batch that was cut short or denied any of its trades.
+        require(allAllowed, "ComplianceHook: batch not all allowed");
+        require(!recordedCohorts[cohortRoot], "ComplianceHook: cohort already recorded");
+        recordedCohorts[cohortRoot] = true;
+
+        emit BatchRecorded(journalDigest, cohortRoot, count, aggregateAmount);
+    }
+
+    /// @dev Caches an allowed `decision` until its TTL, capped by the product's maximum, runs out. An
+    ///      override is for the trade it was given for, so it is never cached.
+    function _cacheDecision(Decision memory decision, bytes32 journalDigest) internal {
+        if (decision.reasons & REASON_OVERRIDE != 0) {
+            return;
+        }
+        uint64 ttlSecs = decision.ttlSecs;
+        uint64 maxTtl = maxDecisionTtl[decision.productId];
+        if (ttlSecs > maxTtl) {
+            ttlSecs = maxTtl;
+        }
+        uint256 expiresAt = uint256(decision.decisionAt) + ttlSecs;
+        if (ttlSecs == 0 || expiresAt <= block.timestamp) {
+            return;
+        }
+        _cachedDecisions[decision.user][decision.productId] = CachedDecision({
+            journalDigest: journalDigest,
+            expiresAt: uint64(expiresAt),
+            policyVersion: decision.policyVersion,
+            quotePolicyVersion: decision.quotePolicyVersion,
+            regimeId: decision.regimeId,
+            settlementToken: decision.settlementToken,
+            quoteProduct: decision.quoteProduct,
+            minTrade: decision.minTrade,
+            maxTrade: decision.maxTrade
+        });
+        emit DecisionCached(decision.user, decision.productId, journalDigest, uint64(expiresAt));
+    }
+
+    /// @dev Verifies the proof of `journal` and checks it decides a trade of `amount` for `user` and
+    ///      `productId` under the current policy versions, the product's regime and settlement tokens and,
+    ///      for an override, the configured governance set.
+    function _verifyDecision(
+        address user,
+        bytes32 productId,
+        uint256 amount,
+        bytes calldata journal,
+        bytes calldata seal
+    ) internal view returns (Decision memory decision, bytes32 journalDigest) {
+        journalDigest = sha256(journal);
+        VERIFIER.verify(seal, IMAGE_ID, journalDigest);
+
+        decision = _decodeJournal(journal);
+
+        require(decision.imageId == IMAGE_ID, "ComplianceHook: image id mismatch");
+        require(decision.user == user, "ComplianceHook: user mismatch");
+        require(decision.productId == productId, "ComplianceHook: product mismatch");
+        // The guest checked the size band against this amount, so a proof is for one trade size only.
+        require(decision.tradeAmount == amount, "ComplianceHook: trade amount mismatch");
+        require(decision.policyVersion == productPolicyVersion[productId], "ComplianceHook: policy version mismatch");
+        require(
+            decision.quotePolicyVersion == productPolicyVersion[decision.quoteProduct],
+            "ComplianceHook: quote policy version mismatch"
+        );
+        require(decision.regimeId == productRegime[productId], "ComplianceHook: regime mismatch");
+        require(
+            settlementTokenCount[productId] == 0 || settlementTokenAllowed[productId][decision.settlementToken],
+            "ComplianceHook: settlement token not allowed"
+        );
+        if (decision.reasons & REASON_OVERRIDE != 0) {
+            // The guest only checks the override key against the governance set it was given.
+            require(
+                governanceSetHash != bytes32(0) && decision.governanceHash == governanceSetHash,
+                "ComplianceHook: unknown governance set"
+            );
+        }
+    }
 
-        require(journalUser == user, "ComplianceHook: user mismatch");
-        require(journalProductId == productId, "ComplianceHook: product mismatch");
-        require(allowed, "ComplianceHook: user not allowed");
+    /// @dev Reads the fields the hook checks from a journal in either encoding.
+    function _decodeJournal(bytes calldata journal) internal pure returns (Decision memory decision) {
+        if (journal.length != COMPACT_JOURNAL_LENGTH) {
+            require(journal.length == ABI_JOURNAL_LENGTH, "ComplianceHook: invalid journal length");
+            // Decoded in two parts so only the fields the hook checks are ever on the stack.
+            (decision.user, decision.productId, decision.allowed, decision.reasons) =
+                abi.decode(journal, (address, bytes32, bool, uint32));
+            (
+                decision.governanceHash,
+                ,
+                ,
+                decision.policyVersion,
+                decision.quoteProduct,
+                decision.quotePolicyVersion,
+                decision.needsReview,
+                decision.regimeId
+            ) = abi.decode(
+                journal[GOVERNANCE_HASH_OFFSET:],
+                (bytes32, uint8, uint8, uint32, bytes32, uint32, bool, uint16)
+            );
+            decision.settlementToken = abi.decode(journal[SETTLEMENT_TOKEN_OFFSET:], (address));
+            (decision.tradeAmount, decision.minTrade, decision.maxTrade, decision.decisionAt, decision.ttlSecs) =
+                abi.decode(journal[TRADE_AMOUNT_OFFSET:], (uint256, uint256, uint256, uint64, uint64));
+            (decision.decisionUuid,, decision.notBefore,,,,,,,, decision.imageId) = abi.decode(
+                journal[DECISION_UUID_OFFSET:],
+                (bytes32, bytes32, uint64, uint256, bytes32, uint16, bool, uint64, bytes32, bytes32, bytes32)
+            );
+            return decision;
+        }
 
-        amount;
+        require(uint8(journal[0]) == COMPACT_JOURNAL_VERSION, "ComplianceHook: unknown journal version");
+        // version (1) | user (20) | productId (32) | allowed (1) | reasons (4) | kycTier (1) |
+        // claims (32) | attester (20) | attestationHash (32) | attestationsHash (32) |
+        // overrideSigner (20) | governanceHash (32) | requiredAttestations (1) |
+        // achievedAttestations (1) | productPolicyVersion (4) | quoteProduct (32) |
+        // quotePolicyVersion (4) | needsReview (1) | regimeId (2) | settlementToken (20) |
+        // caseId (32) | tradeAmount (32) | minTrade (32) | maxTrade (32) | decisionAt (8) |
+        // ttlSecs (8) | commitmentHash (1) | decisionUuid (32) | credentialHash (32) | notBefore (8) |
+        // amlSources (32) | blockHash (32) | kycProvider (2) | consentGiven (1) | consentAt (8) |
+        // attestationSignatureHash (32) | schemaHash (32) | imageId (32)
+        decision.user = address(bytes20(journal[1:21]));
+        decision.productId = bytes32(journal[21:53]);
+        uint8 allowedByte = uint8(journal[53]);
+        require(allowedByte <= 1, "ComplianceHook: invalid journal");
+        decision.allowed = allowedByte == 1;
+        decision.reasons = uint32(bytes4(journal[54:58]));
+        decision.governanceHash = bytes32(journal[195:227]);
+        decision.policyVersion = uint32(bytes4(journal[229:233]));
+        decision.quoteProduct = bytes32(journal[233:265]);
+        decision.quotePolicyVersion = uint32(bytes4(journal[265:269]));
+        uint8 needsReviewByte = uint8(journal[269]);
+        require(needsReviewByte <= 1, "ComplianceHook: invalid journal");
+        decision.needsReview = needsReviewByte == 1;
+        decision.regimeId = uint16(bytes2(journal[270:272]));
+        decision.settlementToken = address(bytes20(journal[272:292]));
+        decision.tradeAmount = uint256(bytes32(journal[324:356]));
+        decision.minTrade = uint256(bytes32(journal[356:388]));
+        decision.maxTrade = uint256(bytes32(journal[388:420]));
+        decision.decisionAt = uint64(bytes8(journal[420:428]));
+        decision.ttlSecs = uint64(bytes8(journal[428:436]));
+        decision.decisionUuid = bytes32(journal[437:469]);
+        decision.notBefore = uint64(bytes8(journal[501:509]));
+        decision.imageId = bytes32(journal[648:680]);
     }
 }
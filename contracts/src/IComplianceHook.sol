@@ -17,12 +17,83 @@ pragma solidity ^0.8.20;
 /// @title Interface for an RWA compliance trading hook.
 /// @notice The hook is called by a trading venue before executing a trade.
 interface IComplianceHook {
+    /// @notice Emitted when a trade passes the compliance check.
+    /// @param user Address of the trader.
+    /// @param productId Identifier of the RWA product.
+    /// @param amount Amount the user intends to trade.
+    /// @param journalDigest SHA-256 digest of the journal the decision was proven in.
+    event TradeAllowed(address indexed user, bytes32 indexed productId, uint256 amount, bytes32 journalDigest);
+
+    /// @notice Emitted when a trade is held because its decision needs manual review.
+    /// @param user Address of the trader.
+    /// @param productId Identifier of the RWA product.
+    /// @param amount Amount the user intends to trade.
+    /// @param journalDigest SHA-256 digest of the journal the decision was proven in.
+    event TradeHeldForReview(address indexed user, bytes32 indexed productId, uint256 amount, bytes32 journalDigest);
+
+    /// @notice Emitted when an allowed decision is cached for later trades of the user.
+    /// @param user Address of the trader.
+    /// @param productId Identifier of the RWA product.
+    /// @param journalDigest SHA-256 digest of the journal the decision was proven in.
+    /// @param expiresAt Unix time in seconds from which the decision is no longer accepted.
+    event DecisionCached(address indexed user, bytes32 indexed productId, bytes32 journalDigest, uint64 expiresAt);
+
+    /// @notice Emitted when a batch of decisions proven together by the batch guest is recorded.
+    /// @param journalDigest SHA-256 digest of the batch journal.
+    /// @param cohortRoot Merkle root of the `(user, productId)` pairs the batch allowed.
+    /// @param count Number of decisions in the batch.
+    /// @param aggregateAmount Sum of the amounts of the batch's allowed trades.
+    event BatchRecorded(
+        bytes32 indexed journalDigest, bytes32 indexed cohortRoot, uint32 count, uint256 aggregateAmount
+    );
+
     /// @notice Check whether a user is allowed to trade a given product.
     /// @param user Address of the trader.
     /// @param productId Identifier of the RWA product (e.g. GOLD_US, STOCK_XYZ).
     /// @param amount Amount the user intends to trade.
-    /// @param journal ABI-encoded journal produced by the RISC Zero guest.
+    /// @param journal Journal produced by the RISC Zero guest, ABI-encoded or compact.
     /// @param seal Zero-knowledge proof (seal) returned by the verifier.
     function beforeTrade(address user, bytes32 productId, uint256 amount, bytes calldata journal, bytes calldata seal)
         external;
+
+    /// @notice Check a trade against the allowed decision cached for the user and product, without a proof.
+    /// @dev Reverts when no decision is cached, it expired, or the product's configuration has changed
+    ///      since it was made.
+    /// @param user Address of the trader.
+    /// @param productId Identifier of the RWA product.
+    /// @param amount Amount the user intends to trade.
+    function beforeTradeCached(address user, bytes32 productId, uint256 amount) external;
+
+    /// @notice The allowed decision cached for a user and product.
+    /// @param user Address of the trader.
+    /// @param productId Identifier of the RWA product.
+    /// @return journalDigest SHA-256 digest of the journal the decision was proven in, or zero.
+    /// @return expiresAt Unix time in seconds from which the decision is no longer accepted.
+    /// @return remaining Seconds until `expiresAt`, zero once it has passed.
+    function cachedDecision(address user, bytes32 productId)
+        external
+        view
+        returns (bytes32 journalDigest, uint64 expiresAt, uint256 remaining);
+
+    /// @notice Record a trade whose decision needs manual review, which `beforeTrade` reverts on.
+    /// @dev A reviewer approves the trade with a governance override, which the guest proves as a new,
+    ///      allowed decision.
+    /// @param user Address of the trader.
+    /// @param productId Identifier of the RWA product.
+    /// @param amount Amount the user intends to trade.
+    /// @param journal Journal produced by the RISC Zero guest, ABI-encoded or compact.
+    /// @param seal Zero-knowledge proof (seal) returned by the verifier.
+    function holdForReview(address user, bytes32 productId, uint256 amount, bytes calldata journal, bytes calldata seal)
+        external;
+
+    /// @notice Record a batch of decisions the batch guest proved in one execution, and its cohort root.
+    /// @dev Reverts unless every decision of the batch was evaluated and allowed, or when the batch was
+    ///      proven with an aggregate cap over `batchAggregateCap` or its trades add up to more than that cap.
+    ///      A cohort root is recorded once.
+    /// @param journal ABI-encoded `BatchJournal` produced by the batch guest.
+    /// @param seal Zero-knowledge proof (seal) returned by the verifier.
+    function recordBatch(bytes calldata journal, bytes calldata seal) external;
+
+    /// @notice Most the allowed trades of a batch `recordBatch` accepts may add up to.
+    function batchAggregateCap() external view returns (uint256);
 }
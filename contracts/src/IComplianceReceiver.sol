@@ -0,0 +1,43 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pragma solidity ^0.8.20;
+
+/// @title Interface for a registry that records compliance decisions proven on another chain.
+/// @notice The host relays each decision it settled with the hook to the receiver on a second chain, with the journal
+///         and seal when that chain has a RISC Zero verifier, or with the operator's signed result when it has none.
+interface IComplianceReceiver {
+    /// @notice A decision as the operator signs it, under an EIP-712 domain of the operator's choosing.
+    struct ComplianceResult {
+        address user;
+        bytes32 productId;
+        bool allowed;
+        bytes32 journalDigest;
+        bytes32 imageId;
+        uint64 validUntil;
+    }
+
+    /// @notice Record the decision a journal commits, after checking its seal.
+    /// @dev Must accept the same journal twice, since the host may relay it again after a failure it cannot tell
+    ///      from a lost transaction.
+    /// @param journal Journal produced by the RISC Zero guest, ABI-encoded or compact.
+    /// @param seal Zero-knowledge proof (seal) returned by the verifier.
+    function receiveDecision(bytes calldata journal, bytes calldata seal) external;
+
+    /// @notice Record a decision the operator signed, after checking the signature.
+    /// @dev Must accept the same result twice, as `receiveDecision` must.
+    /// @param result The signed decision.
+    /// @param signature The operator's 65-byte signature over the EIP-712 hash of `result`.
+    function receiveSignedResult(ComplianceResult calldata result, bytes calldata signature) external;
+}
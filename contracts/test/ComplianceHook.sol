@@ -20,6 +20,7 @@ import {Receipt as RiscZeroReceipt} from "risc0/IRiscZeroVerifier.sol";
 import {RiscZeroMockVerifier} from "risc0/test/RiscZeroMockVerifier.sol";
 import {VerificationFailed} from "risc0/IRiscZeroVerifier.sol";
 import {ComplianceHook} from "../src/ComplianceHook.sol";
+import {IComplianceHook} from "../src/IComplianceHook.sol";
 import {ImageID} from "../src/ImageID.sol";
 
 contract ComplianceHookTest is RiscZeroCheats, Test {
@@ -29,6 +30,44 @@ contract ComplianceHookTest is RiscZeroCheats, Test {
     address public user;
     bytes32 public productId;
     uint256 public amount;
+    uint32 public policyVersion;
+    bytes32 public quoteProduct;
+    uint32 public quotePolicyVersion;
+    bool public overridden;
+    bool public needsReview;
+    uint16 public regimeId;
+    address public settlementToken;
+    uint64 public ttlSecs;
+    uint8 public commitmentHash;
+    bytes32 public decisionUuid;
+    uint64 public notBefore;
+    uint8 public constant KYC_TIER_BASIC = 1;
+    uint32 public constant REASON_AML_FAILED = 1 << 0;
+    uint32 public constant REASON_OVERRIDE = 1 << 3;
+    uint32 public constant REASON_REVIEW_REQUIRED = 1 << 6;
+    uint256 public constant CLAIMS = 0;
+    address public constant ATTESTER = address(0);
+    bytes32 public constant ATTESTATION_HASH = bytes32(0);
+    bytes32 public constant ATTESTATIONS_HASH = keccak256("attestations");
+    address public constant GOVERNOR = address(0x60);
+    bytes32 public constant GOVERNANCE_HASH = keccak256("governance");
+    uint8 public constant REQUIRED_ATTESTATIONS = 0;
+    uint8 public constant ACHIEVED_ATTESTATIONS = 0;
+    bytes32 public constant CASE_ID = keccak256("case");
+    bytes32 public constant CREDENTIAL_HASH = keccak256("credential");
+    uint256 public constant AML_SOURCES = 0b111;
+    bytes32 public constant BLOCK_HASH = keccak256("block");
+    uint16 public constant KYC_PROVIDER = 7;
+    bool public constant CONSENT_GIVEN = true;
+    uint64 public constant CONSENT_AT = 1_699_999_000;
+    bytes32 public constant ATTESTATION_SIGNATURE_HASH = keccak256("signature");
+    bytes32 public constant SCHEMA_HASH = 0xb5dff129d73e263d4d82d8c691e4bf153b4526d795292c2595b394d6bbe5bd6d;
+    uint256 public constant MIN_TRADE = 1;
+    uint256 public constant MAX_TRADE = 1_000_000;
+    uint64 public constant DECISION_AT = 1_700_000_000;
+    uint32 public constant BATCH_COUNT = 3;
+    uint256 public constant BATCH_CAP = 1_000;
+    bytes32 public constant COHORT_ROOT = keccak256("cohort");
 
     function setUp() public {
         verifier = new RiscZeroMockVerifier(0);
@@ -38,21 +77,588 @@ contract ComplianceHookTest is RiscZeroCheats, Test {
         amount = 100;
     }
 
+    /// @dev Reasons of a decision denied for AML, plus the override bit when `overridden`, or held for
+    ///      review when `needsReview`.
+    function _reasons(bool allowed) internal view returns (uint32) {
+        if (overridden) {
+            return REASON_AML_FAILED | REASON_OVERRIDE;
+        }
+        if (needsReview) {
+            return REASON_REVIEW_REQUIRED;
+        }
+        return allowed ? 0 : REASON_AML_FAILED;
+    }
+
+    function _overrideSigner() internal view returns (address) {
+        return overridden ? GOVERNOR : address(0);
+    }
+
+    /// @dev Quote leg of a journal: `quoteProduct` when a pair is traded, otherwise the product itself.
+    function _quoteProduct(bytes32 journalProductId) internal view returns (bytes32) {
+        return quoteProduct == bytes32(0) ? journalProductId : quoteProduct;
+    }
+
+    function _quotePolicyVersion() internal view returns (uint32) {
+        return quoteProduct == bytes32(0) ? policyVersion : quotePolicyVersion;
+    }
+
+    // The journals are encoded in parts to keep the fields within the stack limit.
     function _buildJournal(address journalUser, bytes32 journalProductId, bool allowed)
+        internal
+        view
+        returns (bytes memory)
+    {
+        return bytes.concat(
+            abi.encode(
+                journalUser, journalProductId, allowed, _reasons(allowed), KYC_TIER_BASIC, CLAIMS, ATTESTER
+            ),
+            abi.encode(
+                ATTESTATION_HASH,
+                ATTESTATIONS_HASH,
+                _overrideSigner(),
+                GOVERNANCE_HASH,
+                REQUIRED_ATTESTATIONS,
+                ACHIEVED_ATTESTATIONS
+            ),
+            abi.encode(
+                policyVersion,
+                _quoteProduct(journalProductId),
+                _quotePolicyVersion(),
+                needsReview,
+                regimeId,
+                settlementToken
+            ),
+            abi.encode(
+                CASE_ID,
+                amount,
+                MIN_TRADE,
+                MAX_TRADE,
+                DECISION_AT,
+                ttlSecs,
+                commitmentHash,
+                decisionUuid,
+                CREDENTIAL_HASH,
+                notBefore
+            ),
+            abi.encode(
+                AML_SOURCES,
+                BLOCK_HASH,
+                KYC_PROVIDER,
+                CONSENT_GIVEN,
+                CONSENT_AT,
+                ATTESTATION_SIGNATURE_HASH,
+                SCHEMA_HASH,
+                ImageID.COMPLIANCE_ID
+            )
+        );
+    }
+
+    function _buildCompactJournal(address journalUser, bytes32 journalProductId, bool allowed)
+        internal
+        view
+        returns (bytes memory)
+    {
+        return bytes.concat(
+            abi.encodePacked(
+                hook.COMPACT_JOURNAL_VERSION(),
+                journalUser,
+                journalProductId,
+                allowed,
+                _reasons(allowed),
+                KYC_TIER_BASIC,
+                CLAIMS,
+                ATTESTER
+            ),
+            abi.encodePacked(
+                ATTESTATION_HASH,
+                ATTESTATIONS_HASH,
+                _overrideSigner(),
+                GOVERNANCE_HASH,
+                REQUIRED_ATTESTATIONS,
+                ACHIEVED_ATTESTATIONS
+            ),
+            abi.encodePacked(
+                policyVersion,
+                _quoteProduct(journalProductId),
+                _quotePolicyVersion(),
+                needsReview,
+                regimeId,
+                settlementToken
+            ),
+            abi.encodePacked(
+                CASE_ID,
+                amount,
+                MIN_TRADE,
+                MAX_TRADE,
+                DECISION_AT,
+                ttlSecs,
+                commitmentHash,
+                decisionUuid,
+                CREDENTIAL_HASH,
+                notBefore
+            ),
+            abi.encodePacked(
+                AML_SOURCES,
+                BLOCK_HASH,
+                KYC_PROVIDER,
+                CONSENT_GIVEN,
+                CONSENT_AT,
+                ATTESTATION_SIGNATURE_HASH,
+                SCHEMA_HASH,
+                ImageID.COMPLIANCE_ID
+            )
+        );
+    }
+
+    /// @dev Journal of a batch of `BATCH_COUNT` decisions proven with `aggregateCap`, whose allowed
+    ///      trades add up to `aggregateAmount`. It is all allowed when `allowed` and within the cap.
+    function _buildBatchJournal(uint256 aggregateCap, uint256 aggregateAmount, bool allowed)
         internal
         pure
         returns (bytes memory)
     {
-        return abi.encode(journalUser, journalProductId, allowed);
+        bool capExceeded = aggregateAmount > aggregateCap;
+        return bytes.concat(
+            abi.encode(uint32(1), BATCH_COUNT, keccak256("pages"), keccak256("journals"), false, false),
+            abi.encode(aggregateCap, aggregateAmount, capExceeded, allowed && !capExceeded, COHORT_ROOT)
+        );
     }
 
     function test_AllowsWhenAllowedTrue() public {
+        bytes memory journal = _buildJournal(user, productId, true);
+        assertEq(journal.length, hook.ABI_JOURNAL_LENGTH());
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectEmit(true, true, false, true, address(hook));
+        emit IComplianceHook.TradeAllowed(user, productId, amount, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_AllowsCompactJournal() public {
+        bytes memory journal = _buildCompactJournal(user, productId, true);
+        assertEq(journal.length, hook.COMPACT_JOURNAL_LENGTH());
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectEmit(true, true, false, true, address(hook));
+        emit IComplianceHook.TradeAllowed(user, productId, amount, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenCompactJournalNotAllowed() public {
+        bytes memory journal = _buildCompactJournal(user, productId, false);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: user not allowed");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenCompactJournalVersionUnknown() public {
+        bytes memory journal = _buildCompactJournal(user, productId, true);
+        journal[0] = 0x02;
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: unknown journal version");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_AllowsSha256Commitments() public {
+        commitmentHash = 1;
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+
+        journal = _buildCompactJournal(user, productId, true);
+        receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenDecisionAlreadySettled() public {
+        decisionUuid = keccak256("decision");
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+        assertTrue(hook.settledDecisions(decisionUuid));
+
+        vm.expectRevert("ComplianceHook: decision already settled");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+
+        // The same decision in the other encoding is still the same decision.
+        journal = _buildCompactJournal(user, productId, true);
+        receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+        vm.expectRevert("ComplianceHook: decision already settled");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_AllowsDistinctDecisionUuids() public {
+        decisionUuid = keccak256("first");
+        bytes memory journal = _buildCompactJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+
+        decisionUuid = keccak256("second");
+        journal = _buildCompactJournal(user, productId, true);
+        receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+        assertTrue(hook.settledDecisions(keccak256("first")));
+        assertTrue(hook.settledDecisions(keccak256("second")));
+    }
+
+    function test_RevertWhenSettledBeforeNotBefore() public {
+        notBefore = DECISION_AT + 30;
+        vm.warp(DECISION_AT);
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+        vm.expectRevert("ComplianceHook: decision not yet valid");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+
+        journal = _buildCompactJournal(user, productId, true);
+        receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+        vm.expectRevert("ComplianceHook: decision not yet valid");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+
+        vm.warp(notBefore);
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_AllowsWhenPolicyVersionMatches() public {
+        hook.setProductPolicyVersion(productId, 2);
+        policyVersion = 2;
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenPolicyVersionStale() public {
+        hook.setProductPolicyVersion(productId, 2);
+        policyVersion = 1;
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: policy version mismatch");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenCompactJournalPolicyVersionStale() public {
+        hook.setProductPolicyVersion(productId, 2);
+        bytes memory journal = _buildCompactJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: policy version mismatch");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_AllowsPairWhenBothPolicyVersionsMatch() public {
+        quoteProduct = bytes32(uint256(4));
+        hook.setProductPolicyVersion(productId, 2);
+        hook.setProductPolicyVersion(quoteProduct, 5);
+        policyVersion = 2;
+        quotePolicyVersion = 5;
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectEmit(true, true, false, true, address(hook));
+        emit IComplianceHook.TradeAllowed(user, productId, amount, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenQuotePolicyVersionStale() public {
+        quoteProduct = bytes32(uint256(4));
+        hook.setProductPolicyVersion(quoteProduct, 5);
+        quotePolicyVersion = 4;
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: quote policy version mismatch");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenCompactJournalQuotePolicyVersionStale() public {
+        quoteProduct = bytes32(uint256(4));
+        hook.setProductPolicyVersion(quoteProduct, 5);
+        quotePolicyVersion = 4;
+        bytes memory journal = _buildCompactJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: quote policy version mismatch");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_AllowsWhenRegimeMatchesJurisdiction() public {
+        hook.setProductRegime(productId, 1);
+        regimeId = 1;
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectEmit(true, true, false, true, address(hook));
+        emit IComplianceHook.TradeAllowed(user, productId, amount, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenRegimeMismatch() public {
+        hook.setProductRegime(productId, 1);
+        regimeId = 2;
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: regime mismatch");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenCompactJournalRegimeMismatch() public {
+        regimeId = 3;
+        bytes memory journal = _buildCompactJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: regime mismatch");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_AllowsApprovedSettlementToken() public {
+        hook.setSettlementTokenAllowed(productId, address(0xc0), true);
+        settlementToken = address(0xc0);
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectEmit(true, true, false, true, address(hook));
+        emit IComplianceHook.TradeAllowed(user, productId, amount, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenSettlementTokenNotAllowed() public {
+        hook.setSettlementTokenAllowed(productId, address(0xc0), true);
+        settlementToken = address(0xd0);
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: settlement token not allowed");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenCompactJournalSettlementTokenDisallowed() public {
+        hook.setSettlementTokenAllowed(productId, address(0xc0), true);
+        hook.setSettlementTokenAllowed(productId, address(0xd0), true);
+        hook.setSettlementTokenAllowed(productId, address(0xd0), false);
+        settlementToken = address(0xd0);
+        bytes memory journal = _buildCompactJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: settlement token not allowed");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_AllowsAnySettlementTokenWhenNoneConfigured() public {
+        settlementToken = address(0xd0);
+        bytes memory journal = _buildCompactJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+        assertEq(hook.settlementTokenCount(productId), 0);
+    }
+
+    function test_AllowsGovernanceOverride() public {
+        hook.setGovernanceSetHash(GOVERNANCE_HASH);
+        overridden = true;
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectEmit(true, true, false, true, address(hook));
+        emit IComplianceHook.TradeAllowed(user, productId, amount, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_AllowsCompactJournalGovernanceOverride() public {
+        hook.setGovernanceSetHash(GOVERNANCE_HASH);
+        overridden = true;
+        bytes memory journal = _buildCompactJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenOverrideGovernanceSetUnknown() public {
+        hook.setGovernanceSetHash(keccak256("other governance"));
+        overridden = true;
         bytes memory journal = _buildJournal(user, productId, true);
         RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
 
+        vm.expectRevert("ComplianceHook: unknown governance set");
         hook.beforeTrade(user, productId, amount, journal, receipt.seal);
     }
 
+    function test_RevertWhenOverrideGovernanceSetNotConfigured() public {
+        overridden = true;
+        bytes memory journal = _buildCompactJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: unknown governance set");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenReviewRequired() public {
+        needsReview = true;
+        bytes memory journal = _buildJournal(user, productId, false);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: review required");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_HoldsTradeForReview() public {
+        needsReview = true;
+        bytes memory journal = _buildJournal(user, productId, false);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectEmit(true, true, false, true, address(hook));
+        emit IComplianceHook.TradeHeldForReview(user, productId, amount, sha256(journal));
+        hook.holdForReview(user, productId, amount, journal, receipt.seal);
+        assertTrue(hook.heldForReview(sha256(journal)));
+    }
+
+    function test_HoldsCompactJournalTradeForReview() public {
+        needsReview = true;
+        bytes memory journal = _buildCompactJournal(user, productId, false);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: review required");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+        hook.holdForReview(user, productId, amount, journal, receipt.seal);
+        assertTrue(hook.heldForReview(sha256(journal)));
+    }
+
+    function test_RevertHoldWhenReviewNotRequired() public {
+        bytes memory journal = _buildJournal(user, productId, false);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: review not required");
+        hook.holdForReview(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenNonAdminSetsGovernanceSet() public {
+        vm.prank(address(0xbeef));
+        vm.expectRevert("ComplianceHook: not admin");
+        hook.setGovernanceSetHash(GOVERNANCE_HASH);
+    }
+
+    function test_RevertWhenNonAdminSetsRegime() public {
+        vm.prank(address(0xbeef));
+        vm.expectRevert("ComplianceHook: not admin");
+        hook.setProductRegime(productId, 1);
+    }
+
+    function test_RevertWhenNonAdminSetsSettlementToken() public {
+        vm.prank(address(0xbeef));
+        vm.expectRevert("ComplianceHook: not admin");
+        hook.setSettlementTokenAllowed(productId, address(0xc0), true);
+    }
+
+    function test_CachesAllowedDecisionForItsTtl() public {
+        hook.setMaxDecisionTtl(productId, 1 days);
+        ttlSecs = 1 hours;
+        vm.warp(DECISION_AT + 10 minutes);
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectEmit(true, true, false, true, address(hook));
+        emit IComplianceHook.DecisionCached(user, productId, sha256(journal), DECISION_AT + 1 hours);
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+
+        (bytes32 journalDigest, uint64 expiresAt, uint256 remaining) = hook.cachedDecision(user, productId);
+        assertEq(journalDigest, sha256(journal));
+        assertEq(expiresAt, DECISION_AT + 1 hours);
+        assertEq(remaining, 50 minutes);
+
+        vm.expectEmit(true, true, false, true, address(hook));
+        emit IComplianceHook.TradeAllowed(user, productId, 500, sha256(journal));
+        hook.beforeTradeCached(user, productId, 500);
+
+        vm.warp(DECISION_AT + 1 hours);
+        (,, remaining) = hook.cachedDecision(user, productId);
+        assertEq(remaining, 0);
+        vm.expectRevert("ComplianceHook: no cached decision");
+        hook.beforeTradeCached(user, productId, 500);
+    }
+
+    function test_CachesCompactJournalDecisionUpToProductMaximum() public {
+        hook.setMaxDecisionTtl(productId, 1 hours);
+        ttlSecs = 1 days;
+        vm.warp(DECISION_AT);
+        bytes memory journal = _buildCompactJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+
+        (, uint64 expiresAt,) = hook.cachedDecision(user, productId);
+        assertEq(expiresAt, DECISION_AT + 1 hours);
+    }
+
+    function test_DoesNotCacheWithoutProductMaximum() public {
+        ttlSecs = 1 hours;
+        vm.warp(DECISION_AT);
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+
+        (bytes32 journalDigest,,) = hook.cachedDecision(user, productId);
+        assertEq(journalDigest, bytes32(0));
+        vm.expectRevert("ComplianceHook: no cached decision");
+        hook.beforeTradeCached(user, productId, amount);
+    }
+
+    function test_DoesNotCacheOverride() public {
+        hook.setGovernanceSetHash(GOVERNANCE_HASH);
+        hook.setMaxDecisionTtl(productId, 1 days);
+        overridden = true;
+        ttlSecs = 1 hours;
+        vm.warp(DECISION_AT);
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+
+        vm.expectRevert("ComplianceHook: no cached decision");
+        hook.beforeTradeCached(user, productId, amount);
+    }
+
+    function test_RevertCachedTradeWhenPolicyVersionChanges() public {
+        hook.setMaxDecisionTtl(productId, 1 days);
+        ttlSecs = 1 hours;
+        vm.warp(DECISION_AT);
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+
+        hook.setProductPolicyVersion(productId, 1);
+
+        vm.expectRevert("ComplianceHook: policy version mismatch");
+        hook.beforeTradeCached(user, productId, amount);
+    }
+
+    function test_RevertCachedTradeOutsideSizeBand() public {
+        hook.setMaxDecisionTtl(productId, 1 days);
+        ttlSecs = 1 hours;
+        vm.warp(DECISION_AT);
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+
+        vm.expectRevert("ComplianceHook: trade size out of range");
+        hook.beforeTradeCached(user, productId, MAX_TRADE + 1);
+    }
+
+    function test_RevertWhenNonAdminSetsMaxDecisionTtl() public {
+        vm.prank(address(0xbeef));
+        vm.expectRevert("ComplianceHook: not admin");
+        hook.setMaxDecisionTtl(productId, 1 hours);
+    }
+
+    function test_RevertWhenNonAdminSetsPolicyVersion() public {
+        vm.prank(address(0xbeef));
+        vm.expectRevert("ComplianceHook: not admin");
+        hook.setProductPolicyVersion(productId, 2);
+    }
+
     function test_RevertWhenUserNotAllowed() public {
         bytes memory journal = _buildJournal(user, productId, false);
         RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
@@ -70,6 +676,143 @@ contract ComplianceHookTest is RiscZeroCheats, Test {
         hook.beforeTrade(user, productId, amount, journal, receipt.seal);
     }
 
+    function test_RevertWhenAmountExceedsProvenAmount() public {
+        bytes memory journal = _buildJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: trade amount mismatch");
+        hook.beforeTrade(user, productId, amount + 1, journal, receipt.seal);
+    }
+
+    function test_RevertWhenCompactJournalAmountExceedsProvenAmount() public {
+        bytes memory journal = _buildCompactJournal(user, productId, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: trade amount mismatch");
+        hook.beforeTrade(user, productId, amount * 10, journal, receipt.seal);
+    }
+
+    function test_RevertWhenJournalLengthInvalid() public {
+        // A valid journal with a word of trailing bytes, which a lenient decoder would ignore.
+        bytes memory journal = bytes.concat(_buildJournal(user, productId, true), bytes32(0));
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: invalid journal length");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RevertWhenImageIdMismatch() public {
+        bytes memory journal = bytes.concat(
+            abi.encode(user, productId, true, uint32(0), KYC_TIER_BASIC, CLAIMS, ATTESTER),
+            abi.encode(
+                ATTESTATION_HASH,
+                ATTESTATIONS_HASH,
+                address(0),
+                GOVERNANCE_HASH,
+                REQUIRED_ATTESTATIONS,
+                ACHIEVED_ATTESTATIONS
+            ),
+            abi.encode(
+                policyVersion,
+                productId,
+                policyVersion,
+                false,
+                uint16(0),
+                address(0)
+            ),
+            abi.encode(
+                CASE_ID,
+                amount,
+                MIN_TRADE,
+                MAX_TRADE,
+                DECISION_AT,
+                ttlSecs,
+                commitmentHash,
+                decisionUuid,
+                CREDENTIAL_HASH,
+                notBefore
+            ),
+            abi.encode(
+                AML_SOURCES,
+                BLOCK_HASH,
+                KYC_PROVIDER,
+                CONSENT_GIVEN,
+                CONSENT_AT,
+                ATTESTATION_SIGNATURE_HASH,
+                SCHEMA_HASH,
+                bytes32(uint256(0xdead))
+            )
+        );
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: image id mismatch");
+        hook.beforeTrade(user, productId, amount, journal, receipt.seal);
+    }
+
+    function test_RecordsBatchWithinAggregateCap() public {
+        hook.setBatchAggregateCap(BATCH_CAP);
+        bytes memory journal = _buildBatchJournal(BATCH_CAP, BATCH_CAP, true);
+        assertEq(journal.length, hook.BATCH_JOURNAL_LENGTH());
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.BATCH_ID, sha256(journal));
+
+        vm.expectEmit(true, true, false, true, address(hook));
+        emit IComplianceHook.BatchRecorded(sha256(journal), COHORT_ROOT, BATCH_COUNT, BATCH_CAP);
+        hook.recordBatch(journal, receipt.seal);
+        assertTrue(hook.recordedCohorts(COHORT_ROOT));
+    }
+
+    function test_RevertWhenCohortAlreadyRecorded() public {
+        hook.setBatchAggregateCap(BATCH_CAP);
+        bytes memory journal = _buildBatchJournal(BATCH_CAP, 1, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.BATCH_ID, sha256(journal));
+        hook.recordBatch(journal, receipt.seal);
+
+        vm.expectRevert("ComplianceHook: cohort already recorded");
+        hook.recordBatch(journal, receipt.seal);
+    }
+
+    function test_RevertWhenBatchOverAggregateCap() public {
+        hook.setBatchAggregateCap(BATCH_CAP);
+        bytes memory journal = _buildBatchJournal(BATCH_CAP, BATCH_CAP + 1, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.BATCH_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: aggregate cap exceeded");
+        hook.recordBatch(journal, receipt.seal);
+    }
+
+    function test_RevertWhenBatchCapOverHookCap() public {
+        hook.setBatchAggregateCap(BATCH_CAP);
+        bytes memory journal = _buildBatchJournal(BATCH_CAP + 1, 1, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.BATCH_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: aggregate cap too high");
+        hook.recordBatch(journal, receipt.seal);
+    }
+
+    function test_RevertWhenBatchNotAllAllowed() public {
+        hook.setBatchAggregateCap(BATCH_CAP);
+        bytes memory journal = _buildBatchJournal(BATCH_CAP, 1, false);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.BATCH_ID, sha256(journal));
+
+        vm.expectRevert("ComplianceHook: batch not all allowed");
+        hook.recordBatch(journal, receipt.seal);
+    }
+
+    function test_RevertWhenBatchProvenByComplianceGuest() public {
+        hook.setBatchAggregateCap(BATCH_CAP);
+        bytes memory journal = _buildBatchJournal(BATCH_CAP, 1, true);
+        RiscZeroReceipt memory receipt = verifier.mockProve(ImageID.COMPLIANCE_ID, sha256(journal));
+
+        vm.expectRevert(VerificationFailed.selector);
+        hook.recordBatch(journal, receipt.seal);
+    }
+
+    function test_RevertWhenNonAdminSetsBatchAggregateCap() public {
+        vm.prank(address(0xbeef));
+        vm.expectRevert("ComplianceHook: not admin");
+        hook.setBatchAggregateCap(BATCH_CAP);
+    }
+
     // Try using a proof with a mismatched journal digest.
     function test_RejectInvalidProof() public {
         bytes memory journal = _buildJournal(user, productId, true);
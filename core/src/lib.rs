@@ -0,0 +1,1733 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compliance policy shared by the zkVM guest and the host.
+//!
+//! The guest commits `evaluate(input)` to its journal, and the host uses the same function to
+//! predict that journal. Keeping both on one implementation means the documented policy and the
+//! enforced policy cannot drift apart.
+//!
+//! The crate is `no_std` so the guest can be built without the standard library.
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod pool;
+
+use alloc::vec::Vec;
+
+/// No KYC on file.
+pub const KYC_TIER_NONE: u8 = 0;
+/// Basic KYC: identity verified.
+pub const KYC_TIER_BASIC: u8 = 1;
+/// Intermediate KYC: identity and address verified.
+pub const KYC_TIER_INTERMEDIATE: u8 = 2;
+/// Full KYC: enhanced due diligence completed.
+pub const KYC_TIER_FULL: u8 = 3;
+
+/// KYC tier equivalent to the legacy pass/fail flag: passing means at least [KYC_TIER_BASIC].
+pub fn kyc_tier_from_passed(kyc_passed: bool) -> u8 {
+    if kyc_passed {
+        KYC_TIER_BASIC
+    } else {
+        KYC_TIER_NONE
+    }
+}
+
+/// Questionnaire item: the user passed the MiCA appropriateness assessment. Items that regimes
+/// require are numbered down from the top bit, clear of the items products define.
+pub const CLAIM_MICA_APPROPRIATENESS: alloy_primitives::U256 =
+    alloy_primitives::U256::from_limbs([0, 0, 0, 1 << 63]);
+/// Questionnaire item: the user is an accredited investor under SEC Regulation D.
+pub const CLAIM_SEC_ACCREDITED_INVESTOR: alloy_primitives::U256 =
+    alloy_primitives::U256::from_limbs([0, 0, 0, 1 << 62]);
+/// Questionnaire item: the user passed the MAS customer knowledge assessment.
+pub const CLAIM_MAS_KNOWLEDGE_ASSESSMENT: alloy_primitives::U256 =
+    alloy_primitives::U256::from_limbs([0, 0, 0, 1 << 61]);
+
+/// AML list source: sanctions lists, such as OFAC's SDN list. Bits of
+/// [ComplianceInput::amlSources] record which kinds of list the screening covered.
+pub const AML_SOURCE_SANCTIONS: alloy_primitives::U256 =
+    alloy_primitives::U256::from_limbs([1, 0, 0, 0]);
+/// AML list source: politically exposed persons.
+pub const AML_SOURCE_PEP: alloy_primitives::U256 = alloy_primitives::U256::from_limbs([2, 0, 0, 0]);
+/// AML list source: adverse media.
+pub const AML_SOURCE_ADVERSE_MEDIA: alloy_primitives::U256 =
+    alloy_primitives::U256::from_limbs([4, 0, 0, 0]);
+
+/// Regulatory regime a decision is made under, selected by [ComplianceInput::regimeId]. A regime
+/// adds the questionnaire items it requires to those of the product.
+///
+/// The numeric values are part of the input and journal formats and must never be reused or
+/// renumbered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(u16)]
+pub enum Regime {
+    /// No regional regime; only the product's own requirements apply.
+    #[default]
+    None = 0,
+    /// EU Markets in Crypto-Assets regulation.
+    Mica = 1,
+    /// US Securities and Exchange Commission rules.
+    Sec = 2,
+    /// Monetary Authority of Singapore rules.
+    Mas = 3,
+}
+
+impl Regime {
+    /// Every regime, in numeric order.
+    pub const ALL: [Regime; 4] = [Regime::None, Regime::Mica, Regime::Sec, Regime::Mas];
+
+    /// Regime with id `id`, if there is one.
+    pub const fn from_id(id: u16) -> Option<Self> {
+        match id {
+            0 => Some(Regime::None),
+            1 => Some(Regime::Mica),
+            2 => Some(Regime::Sec),
+            3 => Some(Regime::Mas),
+            _ => None,
+        }
+    }
+
+    /// Stable lowercase name, used in logs and machine-readable output.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Regime::None => "none",
+            Regime::Mica => "mica",
+            Regime::Sec => "sec",
+            Regime::Mas => "mas",
+        }
+    }
+
+    /// Questionnaire items every trade under this regime requires.
+    pub const fn required_claims(self) -> alloy_primitives::U256 {
+        match self {
+            Regime::None => alloy_primitives::U256::ZERO,
+            Regime::Mica => CLAIM_MICA_APPROPRIATENESS,
+            Regime::Sec => CLAIM_SEC_ACCREDITED_INVESTOR,
+            Regime::Mas => CLAIM_MAS_KNOWLEDGE_ASSESSMENT,
+        }
+    }
+}
+
+/// Number of governance keys a [ComplianceInput] carries. Unused slots are the zero address.
+pub const GOVERNANCE_SET_LEN: usize = 4;
+
+/// Number of KYC providers a product may allow in [ComplianceInput::allowedKycProviders]. Unused
+/// slots are zero, which names no provider.
+pub const KYC_PROVIDER_SET_LEN: usize = 4;
+
+/// Version of the journal layout, the `version` of its published document
+/// `core/schemas/journal.v<version>.json`.
+pub const JOURNAL_SCHEMA_VERSION: u32 = 3;
+
+/// SHA-256 of the published document of the journal layout, committed as
+/// [ComplianceJournal::schemaHash]. A new layout is published as a new document rather than by
+/// editing this one, so the hash names one layout for good.
+pub const JOURNAL_SCHEMA_HASH: alloy_primitives::B256 =
+    alloy_primitives::b256!("0xb5dff129d73e263d4d82d8c691e4bf153b4526d795292c2595b394d6bbe5bd6d");
+
+/// Why a trade was denied or let through. Each code is a bit position in
+/// [ComplianceJournal::reasons]; every code except [ReasonCode::Override] is a denial.
+///
+/// The numeric values are part of the journal format and must never be reused or renumbered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum ReasonCode {
+    /// The user did not pass AML screening.
+    AmlFailed = 0,
+    /// The user's KYC tier is below the product's minimum.
+    KycTierTooLow = 1,
+    /// The user has not answered every questionnaire item the product requires.
+    ClaimsMissing = 2,
+    /// A governance key overrode the decision and the trade is allowed whatever else is set.
+    Override = 3,
+    /// The input carries an override signature that is not from a governance key.
+    OverrideUnauthorized = 4,
+    /// Fewer attesters vouched for the user than the product requires.
+    AttestationsMissing = 5,
+    /// The user's risk score is in the product's review band, so the trade waits for a human
+    /// decision; see [ComplianceJournal::needsReview].
+    ReviewRequired = 6,
+    /// The input names a regulatory regime the policy does not know.
+    RegimeUnknown = 7,
+    /// The input's user is the zero address, which no trader holds, so the input is a
+    /// configuration error. Unlike every other denial, an [ReasonCode::Override] does not lift it.
+    UserZero = 8,
+    /// The trade's size is below the product's minimum or above its maximum.
+    TradeSizeOutOfRange = 9,
+    /// The user's AML screening did not cover every list source the product requires.
+    AmlSourcesMissing = 10,
+    /// The user's KYC came from a provider the product does not allow.
+    KycProviderNotAllowed = 11,
+    /// The product requires the user's recorded consent, and there is none from before the
+    /// decision.
+    ConsentMissing = 12,
+    /// The guest could not decode its input, because its frame had the wrong length or more bytes
+    /// than the guest's heap cap leaves room for, or it was not canonically encoded, or because
+    /// it asks for a journal encoding the guest does not know. The guest decided nothing about
+    /// the user; see [ComplianceJournal::rejected].
+    InputInvalid = 13,
+}
+
+impl ReasonCode {
+    /// Every code, in numeric order.
+    pub const ALL: [ReasonCode; 14] = [
+        ReasonCode::AmlFailed,
+        ReasonCode::KycTierTooLow,
+        ReasonCode::ClaimsMissing,
+        ReasonCode::Override,
+        ReasonCode::OverrideUnauthorized,
+        ReasonCode::AttestationsMissing,
+        ReasonCode::ReviewRequired,
+        ReasonCode::RegimeUnknown,
+        ReasonCode::UserZero,
+        ReasonCode::TradeSizeOutOfRange,
+        ReasonCode::AmlSourcesMissing,
+        ReasonCode::KycProviderNotAllowed,
+        ReasonCode::ConsentMissing,
+        ReasonCode::InputInvalid,
+    ];
+
+    /// Stable snake_case name, used in logs and machine-readable output.
+    pub const fn name(self) -> &'static str {
+        match self {
+            ReasonCode::AmlFailed => "aml_failed",
+            ReasonCode::KycTierTooLow => "kyc_tier_too_low",
+            ReasonCode::ClaimsMissing => "claims_missing",
+            ReasonCode::Override => "override",
+            ReasonCode::OverrideUnauthorized => "override_unauthorized",
+            ReasonCode::AttestationsMissing => "attestations_missing",
+            ReasonCode::ReviewRequired => "review_required",
+            ReasonCode::RegimeUnknown => "regime_unknown",
+            ReasonCode::UserZero => "user_zero",
+            ReasonCode::TradeSizeOutOfRange => "trade_size_out_of_range",
+            ReasonCode::AmlSourcesMissing => "aml_sources_missing",
+            ReasonCode::KycProviderNotAllowed => "kyc_provider_not_allowed",
+            ReasonCode::ConsentMissing => "consent_missing",
+            ReasonCode::InputInvalid => "input_invalid",
+        }
+    }
+
+    /// Whether this code denies the trade when no [ReasonCode::Override] is set.
+    pub const fn denies(self) -> bool {
+        !matches!(self, ReasonCode::Override)
+    }
+
+    /// Codes set in `reasons`, in numeric order.
+    pub fn from_mask(reasons: u32) -> impl Iterator<Item = ReasonCode> {
+        Self::ALL
+            .into_iter()
+            .filter(move |code| code.is_set(reasons))
+    }
+
+    /// Bit of this code in a reasons mask.
+    pub const fn bit(self) -> u32 {
+        1 << self as u8
+    }
+
+    /// Whether `reasons` has this code set.
+    pub const fn is_set(self, reasons: u32) -> bool {
+        reasons & self.bit() != 0
+    }
+}
+
+/// How the guest encodes the journal it commits, selected by [ComplianceInput::journalVersion].
+///
+/// The version byte is part of the journal format and must never be reused or renumbered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum JournalEncoding {
+    /// Standard ABI encoding: one 32-byte word per field.
+    #[default]
+    Abi = 0,
+    /// The version byte followed by the tightly packed fields, as `abi.encodePacked` lays them
+    /// out. Smaller to pass and hash onchain.
+    Compact = 1,
+}
+
+impl JournalEncoding {
+    /// Encoding with version byte `version`, if there is one. The [SHA256_COMMITMENTS] flag is
+    /// ignored.
+    pub const fn from_version(version: u8) -> Option<Self> {
+        match version & !SHA256_COMMITMENTS {
+            0 => Some(JournalEncoding::Abi),
+            1 => Some(JournalEncoding::Compact),
+            _ => None,
+        }
+    }
+
+    /// Stable lowercase name, used in logs and machine-readable output.
+    pub const fn name(self) -> &'static str {
+        match self {
+            JournalEncoding::Abi => "abi",
+            JournalEncoding::Compact => "compact",
+        }
+    }
+
+    /// Encoding of `bytes`, told apart by length and, for compact journals, the version byte.
+    pub fn of(bytes: &[u8]) -> Result<Self, JournalError> {
+        match bytes.len() {
+            ComplianceJournal::LEN => Ok(JournalEncoding::Abi),
+            ComplianceJournal::COMPACT_LEN => match bytes[0] {
+                version if version == JournalEncoding::Compact as u8 => {
+                    Ok(JournalEncoding::Compact)
+                }
+                version => Err(JournalError::Version(version)),
+            },
+            len => Err(JournalError::Length(len)),
+        }
+    }
+}
+
+/// Flag of [ComplianceInput::journalVersion] that selects [CommitmentHash::Sha256]. The bits
+/// below it select the [JournalEncoding].
+pub const SHA256_COMMITMENTS: u8 = 0x80;
+
+/// Hash of the commitments the journal embeds over lists of hashes, such as `attestationsHash`,
+/// committed as `commitmentHash` so a reader knows how to recompute them.
+///
+/// Keccak is what the EVM hashes natively. A verifier that checks commitments with SHA-256 instead
+/// sets [SHA256_COMMITMENTS] in the input's `journalVersion`.
+///
+/// The id is part of the journal format and must never be reused or renumbered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum CommitmentHash {
+    #[default]
+    Keccak = 0,
+    Sha256 = 1,
+}
+
+impl CommitmentHash {
+    /// Hash selected by version byte `version`.
+    pub const fn from_version(version: u8) -> Self {
+        if version & SHA256_COMMITMENTS != 0 {
+            CommitmentHash::Sha256
+        } else {
+            CommitmentHash::Keccak
+        }
+    }
+
+    /// Bits this hash sets in [ComplianceInput::journalVersion].
+    pub const fn version_flag(self) -> u8 {
+        match self {
+            CommitmentHash::Keccak => 0,
+            CommitmentHash::Sha256 => SHA256_COMMITMENTS,
+        }
+    }
+
+    /// Hash with `id`, as committed in `commitmentHash`, if there is one.
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(CommitmentHash::Keccak),
+            1 => Some(CommitmentHash::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Stable lowercase name, used in logs and machine-readable output.
+    pub const fn name(self) -> &'static str {
+        match self {
+            CommitmentHash::Keccak => "keccak",
+            CommitmentHash::Sha256 => "sha256",
+        }
+    }
+
+    /// Digest of `bytes`, through the guest's accelerator for either hash.
+    pub fn digest(self, bytes: &[u8]) -> alloy_primitives::B256 {
+        match self {
+            CommitmentHash::Keccak => alloy_primitives::keccak256(bytes),
+            CommitmentHash::Sha256 => {
+                use risc0_zkvm::sha::Sha256 as _;
+
+                let digest = risc0_zkvm::sha::Impl::hash_bytes(bytes);
+                alloy_primitives::B256::from_slice(digest.as_bytes())
+            }
+        }
+    }
+}
+
+alloy_sol_types::sol! {
+    /// Input to the compliance guest.
+    ///
+    /// The host supplies the image id of the guest so it can be bound into the journal; the
+    /// contract rejects journals whose committed id differs from the one it verified against.
+    #[derive(Debug, PartialEq, Eq)]
+    struct ComplianceInput {
+        address user;
+        bytes32 productId;
+        uint8 kycTier;
+        /// Minimum KYC tier the product requires.
+        uint8 requiredTier;
+        bool amlPassed;
+        /// Bitmap of the KYC questionnaire items the user has answered and had verified.
+        uint256 claims;
+        /// Items the product requires; every bit set here must also be set in `claims`.
+        uint256 requiredClaims;
+        /// Version of the product's eligibility policy that `requiredTier` and `requiredClaims`
+        /// come from, committed so the decision records which rules it was made under.
+        uint32 productPolicyVersion;
+        /// Quote asset of a pair traded against `productId`, the base asset. A trade of a single
+        /// product sets it to `productId`, with quote requirements no stricter than the base's.
+        bytes32 quoteProduct;
+        /// Minimum KYC tier the quote product requires.
+        uint8 quoteRequiredTier;
+        /// Items the quote product requires.
+        uint256 quoteRequiredClaims;
+        /// Version of the quote product's eligibility policy.
+        uint32 quotePolicyVersion;
+        /// Risk score the screening provider gave the user, higher meaning riskier.
+        uint8 riskScore;
+        /// Gray zone of risk scores, from `reviewBandLow` up to but excluding `reviewBandHigh`,
+        /// that needs manual review. Empty when `reviewBandLow >= reviewBandHigh`.
+        uint8 reviewBandLow;
+        uint8 reviewBandHigh;
+        /// [Regime] of the product's jurisdiction, whose required items are added to the
+        /// product's.
+        uint16 regimeId;
+        /// Token the trade settles in, which the hook checks against the product's allowed
+        /// settlement tokens.
+        address settlementToken;
+        /// Opaque id of the off-chain case-management record the decision belongs to, or zero when
+        /// there is none. It does not affect the decision.
+        bytes32 caseId;
+        /// Size of the trade, in the smallest unit of the product.
+        uint256 tradeAmount;
+        /// Smallest and largest trade the product accepts, both inclusive. A `tradeAmount` outside
+        /// them denies the trade with `ReasonCode::TradeSizeOutOfRange`.
+        uint256 minTrade;
+        uint256 maxTrade;
+        /// Wallet that attested the user's compliance facts, or zero when there is no attestation.
+        address attester;
+        /// Attesters the product requires to vouch for the user's facts.
+        uint8 requiredAttestations;
+        /// Governance keys allowed to override the decision, committed as `governanceHash`.
+        address[4] governance;
+        /// EIP-2098 compact signature of a governance key over `override_hash`, or zero when the
+        /// decision is not overridden.
+        bytes32 overrideR;
+        bytes32 overrideYParityAndS;
+        /// [JournalEncoding] the guest commits the journal in, with [SHA256_COMMITMENTS] set to
+        /// hash its commitments with SHA-256 rather than keccak.
+        uint8 journalVersion;
+        /// Unix time in seconds the decision is made at, committed as `decisionAt`.
+        uint64 now;
+        /// Seconds after `now` the hook may keep accepting an allowed decision without a new
+        /// proof, committed as `ttlSecs`. Zero for a decision that is only good for one trade.
+        uint64 ttlSecs;
+        /// Identifier the host gives the decision, committed as `decisionUuid` so the hook settles
+        /// it at most once. It does not affect the decision.
+        bytes32 decisionUuid;
+        /// `keccak256` of the verifiable credential the user's compliance facts were taken from, or
+        /// zero when they were not. It does not affect the decision.
+        bytes32 credentialHash;
+        /// Unix time in seconds before which the hook must not settle the decision, committed as
+        /// `notBefore`. Zero to settle at once. It does not affect the decision.
+        uint64 notBefore;
+        /// Bitmap of the AML list sources, such as [AML_SOURCE_SANCTIONS], the user was screened
+        /// against.
+        uint256 amlSources;
+        /// Sources the product requires; every bit set here must also be set in `amlSources`.
+        uint256 requiredAmlSources;
+        /// Hash of the block the onchain state behind the input, such as a contract wallet's
+        /// EIP-1271 answer, was read at, committed as `blockHash`. Zero when no input came from
+        /// chain state. It does not affect the decision.
+        bytes32 blockHash;
+        /// Id of the KYC vendor whose attestation `kycTier` and `claims` come from, or zero when
+        /// the host does not name one.
+        uint16 kycProvider;
+        /// Providers the product accepts KYC from. Unused slots are zero; a product with no
+        /// provider set accepts any, including an unnamed one.
+        uint16[4] allowedKycProviders;
+        /// The user consented to the processing of their data, committed as `consentGiven`.
+        bool consentGiven;
+        /// Unix time in seconds the user's consent was recorded at, or zero when there is none.
+        uint64 consentAt;
+        /// The product requires consent recorded no later than `now` before a trade is allowed.
+        bool consentRequired;
+        /// `keccak256` of the attester's signature over `attestation_hash`, committed as
+        /// `attestationSignatureHash`, or zero when there is none. An attester only counts towards
+        /// `requiredAttestations` with a signature bound to the proof this way.
+        bytes32 attestationSignatureHash;
+        bytes32 imageId;
+    }
+
+    /// Compliance facts an attester signs for a user; see `attestation_hash`.
+    #[derive(Debug, PartialEq, Eq)]
+    struct ComplianceAttestation {
+        address user;
+        uint8 kycTier;
+        bool amlPassed;
+        uint256 claims;
+    }
+
+    /// KYC tier a KYC provider attests for a user; one of the `attestation_hashes`.
+    #[derive(Debug, PartialEq, Eq)]
+    struct KycAttestation {
+        address user;
+        uint8 kycTier;
+    }
+
+    /// AML screening result a screening provider attests for a user.
+    #[derive(Debug, PartialEq, Eq)]
+    struct AmlAttestation {
+        address user;
+        bool amlPassed;
+    }
+
+    /// Accreditation claims an accreditation provider attests for a user.
+    #[derive(Debug, PartialEq, Eq)]
+    struct AccreditationAttestation {
+        address user;
+        uint256 claims;
+    }
+
+    /// Break-glass approval a governance key signs to allow one user's trades in one product
+    /// under one policy version; see `override_hash`.
+    #[derive(Debug, PartialEq, Eq)]
+    struct ComplianceOverride {
+        address user;
+        bytes32 productId;
+        uint32 productPolicyVersion;
+    }
+
+    /// Journal committed by the compliance guest and decoded by `ComplianceHook`.
+    #[derive(Debug, Default, PartialEq, Eq)]
+    struct ComplianceJournal {
+        address user;
+        bytes32 productId;
+        bool allowed;
+        /// Mask of `ReasonCode` bits. `allowed` is true exactly when no denial code is set or the
+        /// override code is.
+        uint32 reasons;
+        uint8 kycTier;
+        /// The user's `claims`, so the decision records which items were answered.
+        uint256 claims;
+        address attester;
+        /// `attestation_hash` of the input, which the attester's signature must cover.
+        bytes32 attestationHash;
+        /// `aggregate_attestation_hash_with` the `commitmentHash` of the `attestation_hashes` of
+        /// the input: one provenance anchor for every attestation the decision relied on.
+        bytes32 attestationsHash;
+        /// Governance key that overrode the decision, or zero when it was not overridden.
+        address overrideSigner;
+        /// `governance_hash` of the input, which the hook checks against its governance set
+        /// before honouring an override.
+        bytes32 governanceHash;
+        /// The input's `requiredAttestations`, so the quorum margin is visible onchain.
+        uint8 requiredAttestations;
+        /// Attesters that vouched for the user's facts with a signature committed as
+        /// `attestationSignatureHash`; at most one while a single attester is supported. At least
+        /// `requiredAttestations` whenever the trade is allowed without an override.
+        uint8 achievedAttestations;
+        /// The product policy version the decision was evaluated under.
+        uint32 productPolicyVersion;
+        /// The input's `quoteProduct`, equal to `productId` unless a pair was traded.
+        bytes32 quoteProduct;
+        /// The quote product policy version the decision was evaluated under.
+        uint32 quotePolicyVersion;
+        /// The risk score is in the review band and nothing else denies the trade, so it is held
+        /// for a human decision instead of rejected. `allowed` is false whenever this is set.
+        bool needsReview;
+        /// The input's `regimeId`, which the hook checks against the product's jurisdiction.
+        uint16 regimeId;
+        /// The input's `settlementToken`.
+        address settlementToken;
+        /// The input's `caseId`, which links a disputed decision to its off-chain case.
+        bytes32 caseId;
+        /// The input's `tradeAmount`, `minTrade` and `maxTrade`, so the size band a trade was
+        /// checked against is on record next to its size.
+        uint256 tradeAmount;
+        uint256 minTrade;
+        uint256 maxTrade;
+        /// The `now` the decision was evaluated at, in Unix seconds.
+        uint64 decisionAt;
+        /// The input's `ttlSecs`. The hook caches an allowed decision until `decisionAt` plus this,
+        /// capped by the product's maximum.
+        uint64 ttlSecs;
+        /// Id of the [CommitmentHash] `attestationsHash` was computed with.
+        uint8 commitmentHash;
+        /// The input's `decisionUuid`, which the hook refuses to settle twice.
+        bytes32 decisionUuid;
+        /// The input's `credentialHash`, which ties the decision to the credential it relied on.
+        bytes32 credentialHash;
+        /// The input's `notBefore`. The hook refuses to settle the decision before this time.
+        uint64 notBefore;
+        /// The input's `amlSources`, so the decision records which lists the screening covered and
+        /// not only that it passed.
+        uint256 amlSources;
+        /// The input's `blockHash`, which ties a decision that relied on onchain state to the
+        /// block that state was read at.
+        bytes32 blockHash;
+        /// The input's `kycProvider`, so the decision records which vendor's KYC it relied on.
+        uint16 kycProvider;
+        /// The input's `consentGiven` and `consentAt`, so the decision records the consent it was
+        /// made under.
+        bool consentGiven;
+        uint64 consentAt;
+        /// The input's `attestationSignatureHash`. A verifier holding the attester's signature
+        /// checks that it hashes to this and that `attester` accepts it over `attestationHash`, with
+        /// EIP-1271 at `blockHash` for a contract wallet.
+        bytes32 attestationSignatureHash;
+        /// [JOURNAL_SCHEMA_HASH] of the guest, so a consumer can tell which layout the journal
+        /// is in. It is always the second-to-last word, and the 32 bytes before `imageId` in the
+        /// compact encoding, whatever fields come before it.
+        bytes32 schemaHash;
+        bytes32 imageId;
+    }
+
+    /// Journal committed by a guest that evaluates a paged batch; see [BatchFold].
+    #[derive(Debug, Default, PartialEq, Eq)]
+    struct BatchJournal {
+        /// Pages the host declared.
+        uint32 pages;
+        /// Inputs evaluated, across every page.
+        uint32 count;
+        /// Running digest of the pages read, which differs from the expected one when pages are
+        /// dropped or cut short.
+        bytes32 pagesDigest;
+        /// Running digest of the journals of the evaluated inputs, in order.
+        bytes32 journalsDigest;
+        /// More than [MAX_BATCH_PAGES] pages were declared, so nothing was evaluated.
+        bool exceeded;
+        /// A page declared more bytes than the guest's heap cap leaves room for, so it and the
+        /// pages after it were not read; see [BatchFold::refuse_page].
+        bool heapCapExceeded;
+        /// Most the allowed trades of the batch may add up to, as the host declared it.
+        /// `type(uint256).max` sets no cap.
+        uint256 aggregateCap;
+        /// Sum of the `tradeAmount`s of the allowed decisions, saturating at `type(uint256).max`.
+        uint256 aggregateAmount;
+        /// `aggregateAmount` is over `aggregateCap`, so the batch as a whole must be rejected,
+        /// whatever its decisions say one by one.
+        bool capExceeded;
+        /// Every input of the batch was evaluated and allowed, so a group action that needs all of
+        /// its members to pass can be gated on this one flag. An empty batch, one cut short by
+        /// `exceeded` or `heapCapExceeded`, or one over its cap, is not all allowed.
+        bool allAllowed;
+        /// Root of the [MerkleTree] of the [cohort_leaf]s of the allowed decisions, in order, so
+        /// one transaction can record a whole onboarded cohort and each member prove inclusion
+        /// later. Zero when no decision was allowed.
+        bytes32 cohortRoot;
+    }
+}
+
+impl ComplianceInput {
+    /// Length of every encoded input. Like the journal it only holds static fields, so the guest
+    /// can read it into a buffer of this size without looking at the contents first.
+    pub const LEN: usize = match <Self as alloy_sol_types::SolType>::ENCODED_SIZE {
+        Some(len) => len,
+        None => panic!("input must only hold static fields"),
+    };
+
+    /// ABI-encode the input onto the end of `out`, the same bytes as `abi_encode`. Callers that
+    /// encode many inputs reuse one buffer, which stops growing once it has held an input.
+    pub fn encode_to(&self, out: &mut Vec<u8>) {
+        encode_words_to(self, &INPUT_WIDTHS, out);
+    }
+}
+
+/// Packed width in bytes of each input word, in order. Each governance address and allowed KYC
+/// provider fills a whole word, as array elements do in packed encoding.
+const INPUT_WIDTHS: [usize; 48] = [
+    20, 32, 1, 1, 1, 32, 32, 4, 32, 1, 32, 4, 1, 1, 1, 2, 20, 32, 32, 32, 32, 20, 1, 32, 32, 32,
+    32, 32, 32, 1, 8, 8, 32, 32, 8, 32, 32, 32, 2, 32, 32, 32, 32, 1, 8, 1, 32, 32,
+];
+
+const _: () = assert!(INPUT_WIDTHS.len() * 32 == ComplianceInput::LEN);
+
+/// Width in bytes of the value in each input word. It is the packed width, except that each
+/// governance address only takes the low 20 bytes of its word and each allowed KYC provider the
+/// low 2, so the rest of those words is padding too.
+const INPUT_VALUE_WIDTHS: [usize; 48] = [
+    20, 32, 1, 1, 1, 32, 32, 4, 32, 1, 32, 4, 1, 1, 1, 2, 20, 32, 32, 32, 32, 20, 1, 20, 20, 20,
+    20, 32, 32, 1, 8, 8, 32, 32, 8, 32, 32, 32, 2, 2, 2, 2, 2, 1, 8, 1, 32, 32,
+];
+
+/// ABI-encode `value`, whose words hold fields of packed `widths`, onto the end of `out`.
+///
+/// The packed encoding is written past room for the words, then each field is copied into the
+/// zeroed word it is right-aligned in, and the packed bytes are dropped again.
+fn encode_words_to<T: alloy_sol_types::SolValue>(value: &T, widths: &[usize], out: &mut Vec<u8>) {
+    let start = out.len();
+    let len = widths.len() * 32;
+    out.resize(start + len, 0);
+    value.abi_encode_packed_to(out);
+    let (words, packed) = out[start..].split_at_mut(len);
+    let mut at = 0;
+    for (word, &width) in words.chunks_exact_mut(32).zip(widths) {
+        word[32 - width..].copy_from_slice(&packed[at..at + width]);
+        at += width;
+    }
+    out.truncate(start + len);
+}
+
+impl ComplianceJournal {
+    /// Length of every encoded journal. All fields are static, so the encoding has no offsets or
+    /// dynamic lengths to trust.
+    pub const LEN: usize = match <Self as alloy_sol_types::SolType>::ENCODED_SIZE {
+        Some(len) => len,
+        None => panic!("journal must only hold static fields"),
+    };
+
+    /// Length of every [JournalEncoding::Compact] journal: the version byte and the packed fields.
+    pub const COMPACT_LEN: usize = {
+        let mut len = 1;
+        let mut field = 0;
+        while field < COMPACT_WIDTHS.len() {
+            len += COMPACT_WIDTHS[field];
+            field += 1;
+        }
+        len
+    };
+
+    /// Journal of an input the guest could not decide on, denied for `reason` alone. It names no
+    /// user, product or image, so no hook accepts it, and is always [JournalEncoding::Abi] since
+    /// the input's encoding is not known.
+    pub fn rejected(reason: ReasonCode) -> Self {
+        Self {
+            reasons: reason.bit(),
+            schemaHash: JOURNAL_SCHEMA_HASH,
+            ..Self::default()
+        }
+    }
+
+    /// Encode the journal as `encoding`.
+    pub fn encode(&self, encoding: JournalEncoding) -> Vec<u8> {
+        use alloy_sol_types::SolValue;
+
+        match encoding {
+            JournalEncoding::Abi => self.abi_encode(),
+            JournalEncoding::Compact => {
+                let mut bytes = Vec::with_capacity(Self::COMPACT_LEN);
+                self.encode_to(encoding, &mut bytes);
+                bytes
+            }
+        }
+    }
+
+    /// Encode the journal as `encoding` onto the end of `out`, the same bytes as
+    /// [encode](Self::encode). Callers that encode many journals reuse one buffer, which stops
+    /// growing once it has held a journal.
+    pub fn encode_to(&self, encoding: JournalEncoding, out: &mut Vec<u8>) {
+        use alloy_sol_types::SolValue;
+
+        match encoding {
+            JournalEncoding::Abi => encode_words_to(self, &COMPACT_WIDTHS, out),
+            JournalEncoding::Compact => {
+                out.push(JournalEncoding::Compact as u8);
+                self.abi_encode_packed_to(out);
+            }
+        }
+    }
+
+    /// Widen a compact journal back to its ABI encoding in `words`. Every field is either a full
+    /// word or right-aligned in its word, so each one is left-padded with zeros.
+    fn widen(compact: &[u8], words: &mut [u8; Self::LEN]) {
+        let mut fields = &compact[1..];
+        for (word, width) in words.chunks_exact_mut(32).zip(COMPACT_WIDTHS) {
+            let (field, rest) = fields.split_at(width);
+            word[32 - width..].copy_from_slice(field);
+            fields = rest;
+        }
+    }
+}
+
+/// Packed width in bytes of each journal field, in order.
+const COMPACT_WIDTHS: [usize; 37] = [
+    20, 32, 1, 4, 1, 32, 20, 32, 32, 20, 32, 1, 1, 4, 32, 4, 1, 2, 20, 32, 32, 32, 32, 8, 8, 1, 32,
+    32, 8, 32, 32, 2, 1, 8, 32, 32, 32,
+];
+
+const _: () = assert!(COMPACT_WIDTHS.len() * 32 == ComplianceJournal::LEN);
+
+/// Journal fields that are `bool`s: `allowed`, `needsReview` and `consentGiven`.
+const BOOL_FIELDS: [usize; 3] = [2, 16, 32];
+
+/// Input fields that are `bool`s: `amlPassed`, `consentGiven` and `consentRequired`.
+const INPUT_BOOL_FIELDS: [usize; 3] = [4, 43, 45];
+
+/// Bits that are clear in every canonical ABI journal.
+const NON_CANONICAL_BITS: [u8; ComplianceJournal::LEN] =
+    non_canonical_bits(&COMPACT_WIDTHS, &BOOL_FIELDS);
+
+/// Bits that are clear in every canonical input.
+const NON_CANONICAL_INPUT_BITS: [u8; ComplianceInput::LEN] =
+    non_canonical_bits(&INPUT_VALUE_WIDTHS, &INPUT_BOOL_FIELDS);
+
+/// Mask of the bits a canonical encoding of words holding values `widths` bytes wide leaves clear:
+/// the padding of each value and all but the lowest bit of each of the `bools`.
+const fn non_canonical_bits<const LEN: usize>(widths: &[usize], bools: &[usize]) -> [u8; LEN] {
+    let mut mask = [0; LEN];
+    let mut field = 0;
+    while field < widths.len() {
+        let mut byte = 0;
+        while byte < 32 - widths[field] {
+            mask[field * 32 + byte] = 0xff;
+            byte += 1;
+        }
+        field += 1;
+    }
+    let mut index = 0;
+    while index < bools.len() {
+        mask[bools[index] * 32 + 31] = 0xfe;
+        index += 1;
+    }
+    mask
+}
+
+/// Whether `words` sets none of the bits of `mask`.
+fn is_canonical(words: &[u8], mask: &[u8]) -> bool {
+    words
+        .iter()
+        .zip(mask)
+        .fold(0, |dirty, (byte, mask)| dirty | (byte & mask))
+        == 0
+}
+
+/// Why bytes could not be decoded as a [ComplianceJournal].
+#[derive(Debug)]
+pub enum JournalError {
+    /// The input is neither [ComplianceJournal::LEN] nor [ComplianceJournal::COMPACT_LEN] bytes.
+    Length(usize),
+    /// A compact-length journal starts with an unknown version byte.
+    Version(u8),
+    /// The bytes do not decode as the journal tuple.
+    Abi(alloy_sol_types::Error),
+    /// A field is not the canonical encoding of its value, e.g. a `bool` word other than 0 or 1
+    /// or an `address` with dirty padding.
+    NonCanonical,
+}
+
+impl core::fmt::Display for JournalError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            JournalError::Length(len) => write!(
+                f,
+                "journal is {len} bytes, expected {} or {}",
+                ComplianceJournal::LEN,
+                ComplianceJournal::COMPACT_LEN
+            ),
+            JournalError::Version(version) => write!(f, "unknown journal version {version}"),
+            JournalError::Abi(err) => write!(f, "invalid journal encoding: {err}"),
+            JournalError::NonCanonical => write!(f, "journal is not canonically encoded"),
+        }
+    }
+}
+
+impl core::error::Error for JournalError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            JournalError::Length(_) | JournalError::Version(_) | JournalError::NonCanonical => None,
+            JournalError::Abi(err) => Some(err),
+        }
+    }
+}
+
+/// Strict decoding for journals read from untrusted sources.
+///
+/// Both [JournalEncoding]s are accepted. The length is checked before anything is decoded, and
+/// every field must be canonically encoded, so re-encoding the result in [JournalEncoding::of] the
+/// input gives back the input.
+impl TryFrom<&[u8]> for ComplianceJournal {
+    type Error = JournalError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        use alloy_sol_types::SolValue;
+
+        // An ABI journal is decoded where it lies; only a compact one is widened first, on the
+        // stack.
+        let mut widened = [0; Self::LEN];
+        let words = match JournalEncoding::of(bytes)? {
+            JournalEncoding::Abi => bytes,
+            JournalEncoding::Compact => {
+                Self::widen(bytes, &mut widened);
+                &widened
+            }
+        };
+        // `abi_decode_validate` still reads any non-zero word as `true`, so the padding and the
+        // bools are checked here instead, against a mask rather than a re-encoding.
+        if !is_canonical(words, &NON_CANONICAL_BITS) {
+            return Err(JournalError::NonCanonical);
+        }
+        Self::abi_decode(words).map_err(JournalError::Abi)
+    }
+}
+
+/// Why bytes could not be decoded as a [ComplianceInput].
+#[derive(Debug)]
+pub enum InputError {
+    /// The input is not [ComplianceInput::LEN] bytes.
+    Length(usize),
+    /// The bytes do not decode as the input tuple.
+    Abi(alloy_sol_types::Error),
+    /// A field is not the canonical encoding of its value, e.g. an `amlPassed` word other than 0
+    /// or 1, or a `uint8` or `address` with dirty high bits.
+    NonCanonical,
+}
+
+impl core::fmt::Display for InputError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            InputError::Length(len) => {
+                write!(f, "input is {len} bytes, expected {}", ComplianceInput::LEN)
+            }
+            InputError::Abi(err) => write!(f, "invalid input encoding: {err}"),
+            InputError::NonCanonical => write!(f, "input is not canonically encoded"),
+        }
+    }
+}
+
+impl core::error::Error for InputError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            InputError::Length(_) | InputError::NonCanonical => None,
+            InputError::Abi(err) => Some(err),
+        }
+    }
+}
+
+/// Strict decoding of inputs, which the guest applies under its `strict-decode` feature.
+///
+/// The input only holds static fields, so there are no offsets or lengths to be non-minimal; the
+/// length is checked, and every field must be canonically encoded, so re-encoding the result gives
+/// back the input. `abi_decode` alone reads any non-zero `bool` word as `true` and ignores the
+/// high bits of narrower fields, so many byte strings would decode to the same input.
+impl TryFrom<&[u8]> for ComplianceInput {
+    type Error = InputError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        use alloy_sol_types::SolValue;
+
+        if bytes.len() != Self::LEN {
+            return Err(InputError::Length(bytes.len()));
+        }
+        if !is_canonical(bytes, &NON_CANONICAL_INPUT_BITS) {
+            return Err(InputError::NonCanonical);
+        }
+        Self::abi_decode(bytes).map_err(InputError::Abi)
+    }
+}
+
+/// Hash the attester signs over the user's compliance facts in `input`.
+///
+/// This is the EIP-712 struct hash of [ComplianceAttestation], without a domain separator. A
+/// contract-wallet attester validates its signature over it with EIP-1271 `isValidSignature`.
+pub fn attestation_hash(input: &ComplianceInput) -> alloy_primitives::B256 {
+    hash_struct(&ComplianceAttestation {
+        user: input.user,
+        kycTier: input.kycTier,
+        amlPassed: input.amlPassed,
+        claims: input.claims,
+    })
+}
+
+/// EIP-712 struct hashes of the KYC, AML and accreditation attestations of the facts in `input`,
+/// in that order.
+///
+/// Each is what the provider of that fact signs when the facts come from separate attestations.
+pub fn attestation_hashes(input: &ComplianceInput) -> [alloy_primitives::B256; 3] {
+    [
+        hash_struct(&KycAttestation {
+            user: input.user,
+            kycTier: input.kycTier,
+        }),
+        hash_struct(&AmlAttestation {
+            user: input.user,
+            amlPassed: input.amlPassed,
+        }),
+        hash_struct(&AccreditationAttestation {
+            user: input.user,
+            claims: input.claims,
+        }),
+    ]
+}
+
+/// EIP-712 `hashStruct` of `value`, the same as [alloy_sol_types::SolStruct::eip712_hash_struct]
+/// but through `keccak256`, which the guest runs on the keccak accelerator. The incremental hasher
+/// `eip712_hash_struct` uses always runs in software.
+fn hash_struct<T: alloy_sol_types::SolStruct>(value: &T) -> alloy_primitives::B256 {
+    let mut encoded = value.eip712_type_hash().to_vec();
+    encoded.extend(value.eip712_encode_data());
+    alloy_primitives::keccak256(encoded)
+}
+
+/// `keccak256` of `hashes` concatenated, committed as `attestationsHash`. An auditor holding the
+/// individual attestations recomputes it to check they are the ones the decision used.
+pub fn aggregate_attestation_hash(hashes: &[alloy_primitives::B256]) -> alloy_primitives::B256 {
+    aggregate_attestation_hash_with(hashes, CommitmentHash::Keccak)
+}
+
+/// [aggregate_attestation_hash] with `hash` rather than keccak, as a journal that commits
+/// [CommitmentHash::Sha256] computes it.
+pub fn aggregate_attestation_hash_with(
+    hashes: &[alloy_primitives::B256],
+    hash: CommitmentHash,
+) -> alloy_primitives::B256 {
+    // One call over the concatenation rather than an incremental hasher, which the guest cannot
+    // accelerate.
+    let concatenated: Vec<u8> = hashes.iter().flat_map(|digest| digest.0).collect();
+    hash.digest(&concatenated)
+}
+
+/// Hash a governance key signs to override the decision for the user, product and policy version
+/// of `input`: the EIP-712 struct hash of [ComplianceOverride], without a domain separator.
+pub fn override_hash(input: &ComplianceInput) -> alloy_primitives::B256 {
+    hash_struct(&ComplianceOverride {
+        user: input.user,
+        productId: input.productId,
+        productPolicyVersion: input.productPolicyVersion,
+    })
+}
+
+/// `keccak256` of the ABI-encoded governance set of `input`, committed as `governanceHash`.
+pub fn governance_hash(input: &ComplianceInput) -> alloy_primitives::B256 {
+    use alloy_sol_types::SolValue;
+
+    alloy_primitives::keccak256(input.governance.abi_encode())
+}
+
+/// Governance key that signed the override in `input`, if there is a signature and it recovers
+/// to a member of the governance set other than the attester.
+///
+/// Governance keys are a separate authority from attesters, so an attester listed in the set
+/// still cannot override a decision on facts it attested itself.
+fn override_signer(input: &ComplianceInput) -> Result<Option<alloy_primitives::Address>, ()> {
+    if input.overrideR.is_zero() && input.overrideYParityAndS.is_zero() {
+        return Ok(None);
+    }
+    let mut compact = [0u8; 64];
+    compact[..32].copy_from_slice(input.overrideR.as_slice());
+    compact[32..].copy_from_slice(input.overrideYParityAndS.as_slice());
+    let signer = alloy_primitives::Signature::from_erc2098(&compact)
+        .recover_address_from_prehash(&override_hash(input))
+        .map_err(|_| ())?;
+    let authorized =
+        !signer.is_zero() && signer != input.attester && input.governance.contains(&signer);
+    if authorized {
+        Ok(Some(signer))
+    } else {
+        Err(())
+    }
+}
+
+/// Root of the Merkle tree in which `proof` places `leaf`, hashing each pair with [hash_pair].
+pub fn merkle_root(
+    leaf: alloy_primitives::B256,
+    proof: &[alloy_primitives::B256],
+) -> alloy_primitives::B256 {
+    merkle_root_with::<Keccak>(leaf, proof)
+}
+
+/// [merkle_root] of a tree whose pairs are hashed with `H`.
+pub fn merkle_root_with<H: MerkleHash>(
+    leaf: alloy_primitives::B256,
+    proof: &[alloy_primitives::B256],
+) -> alloy_primitives::B256 {
+    proof
+        .iter()
+        .fold(leaf, |node, sibling| H::hash_pair(node, *sibling))
+}
+
+/// Leaf of `address` in a membership tree: `keccak256(keccak256(abi.encode(address)))`, as
+/// OpenZeppelin's `StandardMerkleTree` encodes an `address`. Hashing twice keeps a leaf from being
+/// passed off as an inner node.
+pub fn address_leaf(address: alloy_primitives::Address) -> alloy_primitives::B256 {
+    use alloy_sol_types::SolValue;
+
+    alloy_primitives::keccak256(alloy_primitives::keccak256(address.abi_encode()))
+}
+
+/// Leaf of a cohort tree for `user` allowed to trade `product_id`:
+/// `keccak256(keccak256(abi.encode(user, productId)))`, as [address_leaf] encodes an address.
+pub fn cohort_leaf(
+    user: alloy_primitives::Address,
+    product_id: alloy_primitives::B256,
+) -> alloy_primitives::B256 {
+    use alloy_sol_types::SolValue;
+
+    alloy_primitives::keccak256(alloy_primitives::keccak256((user, product_id).abi_encode()))
+}
+
+/// Leaf of a sorted tree for the gap between two adjacent addresses of its list:
+/// `keccak256(keccak256(abi.encode(low, high)))`.
+///
+/// A sorted tree has a leaf for each gap, from the zero address to the first address of the list
+/// and from the last to `0xff..ff`, so an address outside the list falls strictly inside exactly
+/// one of them.
+pub fn gap_leaf(
+    low: alloy_primitives::Address,
+    high: alloy_primitives::Address,
+) -> alloy_primitives::B256 {
+    use alloy_sol_types::SolValue;
+
+    alloy_primitives::keccak256(alloy_primitives::keccak256((low, high).abi_encode()))
+}
+
+/// Whether `proof` places `address` in the membership tree with `root`.
+pub fn is_member(
+    root: alloy_primitives::B256,
+    address: alloy_primitives::Address,
+    proof: &[alloy_primitives::B256],
+) -> bool {
+    merkle_root(address_leaf(address), proof) == root
+}
+
+/// Whether `proof` places the gap from `low` to `high` in the sorted tree with `root` and
+/// `address` lies strictly inside it, so the tree's list does not hold `address`.
+pub fn is_non_member(
+    root: alloy_primitives::B256,
+    address: alloy_primitives::Address,
+    low: alloy_primitives::Address,
+    high: alloy_primitives::Address,
+    proof: &[alloy_primitives::B256],
+) -> bool {
+    low < address && address < high && merkle_root(gap_leaf(low, high), proof) == root
+}
+
+/// Whether `proof` places `user`, allowed to trade `product_id`, in the cohort tree with `root`.
+pub fn is_in_cohort(
+    root: alloy_primitives::B256,
+    user: alloy_primitives::Address,
+    product_id: alloy_primitives::B256,
+    proof: &[alloy_primitives::B256],
+) -> bool {
+    merkle_root(cohort_leaf(user, product_id), proof) == root
+}
+
+/// Parent of two Merkle tree nodes: `keccak256` of the pair in ascending order, as OpenZeppelin's
+/// `MerkleProof` computes it, so proofs carry no path bits and verify onchain as well.
+pub fn hash_pair(a: alloy_primitives::B256, b: alloy_primitives::B256) -> alloy_primitives::B256 {
+    Keccak::hash_pair(a, b)
+}
+
+/// Hash that the nodes of a Merkle tree are combined with.
+///
+/// Trees whose root is checked onchain use [Keccak], which the EVM has a precompile for. Trees
+/// that only the guest walks, such as a registry snapshot committed by its root, can use [Sha256]
+/// instead: the zkVM's SHA-256 accelerator hashes a pair in fewer cycles than its keccak one.
+pub trait MerkleHash {
+    /// Digest of the 64 bytes of two nodes.
+    fn hash(pair: &[u8; 64]) -> alloy_primitives::B256;
+
+    /// Parent of two nodes: [hash](MerkleHash::hash) of the pair in ascending order.
+    fn hash_pair(a: alloy_primitives::B256, b: alloy_primitives::B256) -> alloy_primitives::B256 {
+        let (low, high) = if a <= b { (a, b) } else { (b, a) };
+        let mut pair = [0u8; 64];
+        pair[..32].copy_from_slice(low.as_slice());
+        pair[32..].copy_from_slice(high.as_slice());
+        Self::hash(&pair)
+    }
+}
+
+/// `keccak256` Merkle trees, which verify onchain. The default of [merkle_root] and [MerkleTree].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Keccak;
+
+impl MerkleHash for Keccak {
+    fn hash(pair: &[u8; 64]) -> alloy_primitives::B256 {
+        alloy_primitives::keccak256(pair)
+    }
+}
+
+/// SHA-256 Merkle trees, accelerated in the guest, for trees that are never verified onchain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sha256;
+
+impl MerkleHash for Sha256 {
+    fn hash(pair: &[u8; 64]) -> alloy_primitives::B256 {
+        CommitmentHash::Sha256.digest(pair)
+    }
+}
+
+/// A Merkle tree over a list of leaves, hashed with `H`, that hands out proofs for [merkle_root]
+/// and [merkle_root_with].
+///
+/// A level of odd length carries its last node up unpaired, so a proof can be shorter than the
+/// depth of the tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleTree<H = Keccak> {
+    levels: Vec<Vec<alloy_primitives::B256>>,
+    hash: core::marker::PhantomData<H>,
+}
+
+impl<H: MerkleHash> MerkleTree<H> {
+    /// Build the tree over `leaves`, which must not be empty.
+    pub fn new(leaves: Vec<alloy_primitives::B256>) -> Self {
+        assert!(!leaves.is_empty(), "a Merkle tree needs at least one leaf");
+        let mut levels = alloc::vec![leaves];
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let parents = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => H::hash_pair(*a, *b),
+                    [odd] => *odd,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(parents);
+        }
+        Self {
+            levels,
+            hash: core::marker::PhantomData,
+        }
+    }
+
+    pub fn root(&self) -> alloy_primitives::B256 {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    pub fn leaves(&self) -> &[alloy_primitives::B256] {
+        &self.levels[0]
+    }
+
+    /// Every level of the tree, from the leaves up to the root.
+    pub fn levels(&self) -> &[Vec<alloy_primitives::B256>] {
+        &self.levels
+    }
+
+    /// Proof of the leaf at `index`, or `None` if the tree has no such leaf.
+    pub fn proof(&self, index: usize) -> Option<Vec<alloy_primitives::B256>> {
+        if index >= self.leaves().len() {
+            return None;
+        }
+        let levels = &self.levels[..self.levels.len() - 1];
+        let proof = levels
+            .iter()
+            .enumerate()
+            .filter_map(|(depth, level)| level.get((index >> depth) ^ 1).copied())
+            .collect();
+        Some(proof)
+    }
+}
+
+/// Root of a [MerkleTree] built one leaf at a time, holding a node per level instead of every
+/// leaf, so a guest can commit the root of more leaves than it keeps in memory.
+///
+/// The tree is the one [MerkleTree::new] builds over the same leaves in the same order: full
+/// subtrees are merged as soon as they pair up, and the root joins what is left from the smallest
+/// subtree up, as the unpaired last node of each level is carried up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleFrontier<H = Keccak> {
+    /// Root of the full subtree of `2^level` leaves at each level that is waiting for a sibling.
+    nodes: [Option<alloy_primitives::B256>; 64],
+    hash: core::marker::PhantomData<H>,
+}
+
+impl<H> Default for MerkleFrontier<H> {
+    fn default() -> Self {
+        Self {
+            nodes: [None; 64],
+            hash: core::marker::PhantomData,
+        }
+    }
+}
+
+impl<H: MerkleHash> MerkleFrontier<H> {
+    pub fn push(&mut self, leaf: alloy_primitives::B256) {
+        let mut node = leaf;
+        for slot in &mut self.nodes {
+            match slot.take() {
+                Some(left) => node = H::hash_pair(left, node),
+                None => {
+                    *slot = Some(node);
+                    return;
+                }
+            }
+        }
+        unreachable!("a frontier holds fewer than 2^64 leaves");
+    }
+
+    /// Root of the leaves pushed so far, or `None` if there are none.
+    pub fn root(&self) -> Option<alloy_primitives::B256> {
+        self.nodes.iter().flatten().fold(None, |right, &left| {
+            Some(right.map_or(left, |right| H::hash_pair(left, right)))
+        })
+    }
+}
+
+/// Apply the compliance policy to `input`.
+///
+/// A trade is allowed when the user passed AML screening against every list source the product
+/// requires, holds at least the product's required KYC tier, has every claim the product requires,
+/// and was vouched for by as many attesters as the product requires, each with a signature whose
+/// hash the input commits. Every product requires at least [KYC_TIER_BASIC], so a user without KYC
+/// is never allowed. A pair is only allowed when the user meets the tier and claims of both its
+/// base and quote products; a leg falling short sets the same code as a single product would.
+///
+/// The product's [Regime] adds the items it requires to the product's, and a regime the policy
+/// does not know denies the trade with [ReasonCode::RegimeUnknown].
+///
+/// Screening that passed without covering every source in `requiredAmlSources` denies the trade
+/// with [ReasonCode::AmlSourcesMissing].
+///
+/// KYC from a provider outside a non-empty `allowedKycProviders`, or from an unnamed one, denies
+/// the trade with [ReasonCode::KycProviderNotAllowed].
+///
+/// A product that sets `consentRequired` denies the trade with [ReasonCode::ConsentMissing] unless
+/// the user gave consent, recorded at a nonzero `consentAt` no later than `now`.
+///
+/// A trade smaller than the product's `minTrade` or larger than its `maxTrade` is denied with
+/// [ReasonCode::TradeSizeOutOfRange]; a trade of exactly either bound is within them.
+///
+/// A risk score in the product's review band denies the trade with [ReasonCode::ReviewRequired].
+/// When that is the only denial the trade needs review rather than being rejected outright.
+///
+/// A valid override signature from a governance key allows the trade regardless, keeping the
+/// denial codes it overrode next to [ReasonCode::Override]. An override signature from anyone else
+/// denies the trade with [ReasonCode::OverrideUnauthorized].
+///
+/// A zero user is denied with [ReasonCode::UserZero] even when overridden.
+pub fn evaluate(input: &ComplianceInput) -> ComplianceJournal {
+    evaluate_with(input, &mut ())
+}
+
+/// [evaluate], telling `probe` as each of its [Section]s starts.
+pub fn evaluate_with(input: &ComplianceInput, probe: &mut impl Probe) -> ComplianceJournal {
+    probe.enter(Section::Aml);
+    let mut reasons = 0;
+    if input.user.is_zero() {
+        reasons |= ReasonCode::UserZero.bit();
+    }
+    if !input.amlPassed {
+        reasons |= ReasonCode::AmlFailed.bit();
+    }
+    if input.amlSources & input.requiredAmlSources != input.requiredAmlSources {
+        reasons |= ReasonCode::AmlSourcesMissing.bit();
+    }
+    if !(input.minTrade..=input.maxTrade).contains(&input.tradeAmount) {
+        reasons |= ReasonCode::TradeSizeOutOfRange.bit();
+    }
+    if input.consentRequired && !consent_recorded(input) {
+        reasons |= ReasonCode::ConsentMissing.bit();
+    }
+    probe.enter(Section::KycTier);
+    let required_tier = input.requiredTier.max(input.quoteRequiredTier);
+    if input.kycTier < required_tier.max(KYC_TIER_BASIC) {
+        reasons |= ReasonCode::KycTierTooLow.bit();
+    }
+    if !kyc_provider_allowed(input) {
+        reasons |= ReasonCode::KycProviderNotAllowed.bit();
+    }
+    probe.enter(Section::Claims);
+    let regime = Regime::from_id(input.regimeId);
+    if regime.is_none() {
+        reasons |= ReasonCode::RegimeUnknown.bit();
+    }
+    let required_claims = input.requiredClaims
+        | input.quoteRequiredClaims
+        | regime.map_or(alloy_primitives::U256::ZERO, Regime::required_claims);
+    if input.claims & required_claims != required_claims {
+        reasons |= ReasonCode::ClaimsMissing.bit();
+    }
+    probe.enter(Section::Attestations);
+    // An attester whose signature the proof does not commit to has vouched for nothing.
+    let achieved_attestations =
+        u8::from(!input.attester.is_zero() && !input.attestationSignatureHash.is_zero());
+    if achieved_attestations < input.requiredAttestations {
+        reasons |= ReasonCode::AttestationsMissing.bit();
+    }
+    probe.enter(Section::ReviewBand);
+    if (input.reviewBandLow..input.reviewBandHigh).contains(&input.riskScore) {
+        reasons |= ReasonCode::ReviewRequired.bit();
+    }
+    probe.enter(Section::Override);
+    let override_signer = match override_signer(input) {
+        Ok(Some(signer)) => {
+            reasons |= ReasonCode::Override.bit();
+            signer
+        }
+        Ok(None) => alloy_primitives::Address::ZERO,
+        Err(()) => {
+            reasons |= ReasonCode::OverrideUnauthorized.bit();
+            alloy_primitives::Address::ZERO
+        }
+    };
+    let denied = ReasonCode::from_mask(reasons).any(ReasonCode::denies);
+    let overridden = ReasonCode::Override.is_set(reasons);
+    let needs_review = !overridden
+        && ReasonCode::from_mask(reasons)
+            .filter(|code| code.denies())
+            .all(|code| code == ReasonCode::ReviewRequired)
+        && ReasonCode::ReviewRequired.is_set(reasons);
+
+    probe.enter(Section::Hashes);
+    let attestation_hash = attestation_hash(input);
+    let commitment_hash = CommitmentHash::from_version(input.journalVersion);
+    let attestations_hash =
+        aggregate_attestation_hash_with(&attestation_hashes(input), commitment_hash);
+    let governance_hash = governance_hash(input);
+
+    ComplianceJournal {
+        user: input.user,
+        productId: input.productId,
+        allowed: (!denied || overridden) && !ReasonCode::UserZero.is_set(reasons),
+        reasons,
+        kycTier: input.kycTier,
+        claims: input.claims,
+        attester: input.attester,
+        attestationHash: attestation_hash,
+        attestationsHash: attestations_hash,
+        overrideSigner: override_signer,
+        governanceHash: governance_hash,
+        requiredAttestations: input.requiredAttestations,
+        achievedAttestations: achieved_attestations,
+        productPolicyVersion: input.productPolicyVersion,
+        quoteProduct: input.quoteProduct,
+        quotePolicyVersion: input.quotePolicyVersion,
+        needsReview: needs_review,
+        regimeId: input.regimeId,
+        settlementToken: input.settlementToken,
+        caseId: input.caseId,
+        tradeAmount: input.tradeAmount,
+        minTrade: input.minTrade,
+        maxTrade: input.maxTrade,
+        decisionAt: input.now,
+        ttlSecs: input.ttlSecs,
+        commitmentHash: commitment_hash as u8,
+        decisionUuid: input.decisionUuid,
+        credentialHash: input.credentialHash,
+        notBefore: input.notBefore,
+        amlSources: input.amlSources,
+        blockHash: input.blockHash,
+        kycProvider: input.kycProvider,
+        consentGiven: input.consentGiven,
+        consentAt: input.consentAt,
+        attestationSignatureHash: input.attestationSignatureHash,
+        schemaHash: JOURNAL_SCHEMA_HASH,
+        imageId: input.imageId,
+    }
+}
+
+/// Whether the product allows the provider `input`'s KYC came from: it names no provider at all,
+/// or it names the input's. Zero names no provider, so it is never in a non-empty set.
+fn kyc_provider_allowed(input: &ComplianceInput) -> bool {
+    let allowed = &input.allowedKycProviders;
+    allowed.iter().all(|&provider| provider == 0)
+        || (input.kycProvider != 0 && allowed.contains(&input.kycProvider))
+}
+
+/// Whether `input` records the user's consent from before the decision: given, at a nonzero
+/// `consentAt` no later than `now`.
+fn consent_recorded(input: &ComplianceInput) -> bool {
+    input.consentGiven && input.consentAt != 0 && input.consentAt <= input.now
+}
+
+/// Part of a decision that the guest's `profile` feature counts the cycles of. [evaluate_with]
+/// covers the rules and hashes; the guest itself marks the rest.
+///
+/// The numeric values are the order of [CycleProfile::encode] and must never be renumbered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Section {
+    /// From the start of the session to the first section, before `main` runs.
+    Startup = 0,
+    /// Reading the input frame.
+    Read = 1,
+    /// ABI-decoding the input.
+    Decode = 2,
+    /// The zero user, AML, trade size and consent rules.
+    Aml = 3,
+    /// The KYC tier rule for both legs and the KYC provider rule.
+    KycTier = 4,
+    /// The regime and claims rules for both legs.
+    Claims = 5,
+    /// Counting attesters.
+    Attestations = 6,
+    /// The review band rule.
+    ReviewBand = 7,
+    /// Recovering and checking the override signature.
+    Override = 8,
+    /// The attestation and governance hashes committed with the decision.
+    Hashes = 9,
+    /// Encoding the journal.
+    Encode = 10,
+    /// Committing the journal.
+    Commit = 11,
+}
+
+impl Section {
+    /// Every section, in the order a decision runs them.
+    pub const ALL: [Section; 12] = [
+        Section::Startup,
+        Section::Read,
+        Section::Decode,
+        Section::Aml,
+        Section::KycTier,
+        Section::Claims,
+        Section::Attestations,
+        Section::ReviewBand,
+        Section::Override,
+        Section::Hashes,
+        Section::Encode,
+        Section::Commit,
+    ];
+
+    /// Stable snake_case name, used in profiles printed by the host.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Section::Startup => "startup",
+            Section::Read => "read",
+            Section::Decode => "decode",
+            Section::Aml => "aml",
+            Section::KycTier => "kyc_tier",
+            Section::Claims => "claims",
+            Section::Attestations => "attestations",
+            Section::ReviewBand => "review_band",
+            Section::Override => "override",
+            Section::Hashes => "hashes",
+            Section::Encode => "encode",
+            Section::Commit => "commit",
+        }
+    }
+}
+
+/// Told as each [Section] of a decision starts; the previous one ends there. `()` ignores it,
+/// which is what [evaluate] passes.
+pub trait Probe {
+    fn enter(&mut self, section: Section);
+}
+
+impl Probe for () {
+    fn enter(&mut self, _section: Section) {}
+}
+
+/// Cycles one decision spent in each [Section], and the most heap it used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CycleProfile {
+    pub cycles: [u64; Section::ALL.len()],
+    /// Bytes of guest heap in use when the profile was taken. The guest's allocator never frees,
+    /// so this is also the peak.
+    pub heap_peak: u64,
+}
+
+impl CycleProfile {
+    /// Length of [Self::encode]: a little-endian `u64` per section, in [Section::ALL] order, then
+    /// `heap_peak`.
+    pub const LEN: usize = 8 * (Section::ALL.len() + 1);
+
+    /// Cycles spent in `section`.
+    pub fn get(&self, section: Section) -> u64 {
+        self.cycles[section as usize]
+    }
+
+    /// Count `cycles` more against `section`.
+    pub fn add(&mut self, section: Section, cycles: u64) {
+        self.cycles[section as usize] += cycles;
+    }
+
+    /// Cycles across all sections.
+    pub fn total(&self) -> u64 {
+        self.cycles.iter().sum()
+    }
+
+    pub fn encode(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0u8; Self::LEN];
+        let words = self.cycles.into_iter().chain([self.heap_peak]);
+        for (chunk, word) in bytes.chunks_exact_mut(8).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Profile encoded by [Self::encode], if `bytes` is one.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::LEN {
+            return None;
+        }
+        let mut profile = Self::default();
+        let words = profile.cycles.iter_mut().chain([&mut profile.heap_peak]);
+        for (word, chunk) in words.zip(bytes.chunks_exact(8)) {
+            *word = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+        Some(profile)
+    }
+}
+
+/// Most inputs a page of a paged batch may hold. A page is the most input a batch guest holds in
+/// memory at once.
+pub const MAX_PAGE_INPUTS: usize = 64;
+
+/// Most pages a paged batch may declare. A guest given a larger batch evaluates none of it and
+/// commits a [BatchJournal] with `exceeded` set.
+pub const MAX_BATCH_PAGES: u32 = 256;
+
+/// Split `inputs` into pages of at most [MAX_PAGE_INPUTS] ABI-encoded inputs each, in order.
+pub fn batch_pages(inputs: &[ComplianceInput]) -> Vec<Vec<u8>> {
+    use alloy_sol_types::SolValue;
+
+    inputs
+        .chunks(MAX_PAGE_INPUTS)
+        .map(|page| page.iter().flat_map(SolValue::abi_encode).collect())
+        .collect()
+}
+
+/// `keccak256(digest ‖ keccak256(item))`: one step of the running digests of a [BatchJournal],
+/// which start from zero.
+pub fn fold_digest(digest: alloy_primitives::B256, item: &[u8]) -> alloy_primitives::B256 {
+    let mut pair = [0u8; 64];
+    pair[..32].copy_from_slice(digest.as_slice());
+    pair[32..].copy_from_slice(alloy_primitives::keccak256(item).as_slice());
+    alloy_primitives::keccak256(pair)
+}
+
+/// Evaluation of a paged batch one page at a time, shared by the batch guest and the host that
+/// predicts its journal.
+///
+/// The host declares the number of pages up front and then writes each page of [batch_pages] as
+/// its own frame. Only the current page and the running digests are held, so memory stays bounded
+/// however many inputs the batch has. A guest that runs out of pages fails, and one given fewer
+/// pages than the full list commits a `pagesDigest` that does not match it.
+///
+/// A batch typically holds one user's trades across many products, so the sum of its allowed
+/// trades is that user's exposure. Each decision stays the one the compliance guest would commit
+/// for its input alone; a sum over the cap flags the whole batch instead.
+///
+/// When a batch onboards a cohort of users, the `(user, productId)` pairs of its allowed decisions
+/// are the cohort, committed as one `cohortRoot` rather than a record per user.
+#[derive(Debug)]
+pub struct BatchFold {
+    journal: BatchJournal,
+    /// Tree of the [cohort_leaf]s of the allowed decisions.
+    cohort: MerkleFrontier,
+    /// An evaluated input was not allowed.
+    rejected: bool,
+}
+
+impl BatchFold {
+    /// Start a batch that declares `pages` pages, whose allowed trades may add up to at most
+    /// `aggregate_cap`.
+    pub fn new(pages: u32, aggregate_cap: alloy_primitives::U256) -> Self {
+        Self {
+            journal: BatchJournal {
+                pages,
+                exceeded: pages > MAX_BATCH_PAGES,
+                aggregateCap: aggregate_cap,
+                ..Default::default()
+            },
+            cohort: MerkleFrontier::default(),
+            rejected: false,
+        }
+    }
+
+    /// Whether the batch declares more than [MAX_BATCH_PAGES] pages, in which case no page may
+    /// be pushed.
+    pub fn exceeded(&self) -> bool {
+        self.journal.exceeded
+    }
+
+    /// Record that the next page declared a length the guest's heap cap has no room for, so
+    /// neither it nor any later page may be pushed.
+    pub fn refuse_page(&mut self) {
+        self.journal.heapCapExceeded = true;
+    }
+
+    /// Evaluate every input of `page`, fold the page and their journals into the digests and add
+    /// the allowed trades to the aggregate.
+    pub fn push_page(&mut self, page: &[u8]) -> Result<(), PageError> {
+        if self.journal.exceeded {
+            return Err(PageError::Exceeded);
+        }
+        if self.journal.heapCapExceeded {
+            return Err(PageError::HeapCap);
+        }
+        if page.len() % ComplianceInput::LEN != 0 {
+            return Err(PageError::Length(page.len()));
+        }
+        let inputs = page.len() / ComplianceInput::LEN;
+        if inputs > MAX_PAGE_INPUTS {
+            return Err(PageError::TooManyInputs(inputs));
+        }
+        for bytes in page.chunks_exact(ComplianceInput::LEN) {
+            let input = ComplianceInput::try_from(bytes).map_err(PageError::Input)?;
+            let encoding =
+                JournalEncoding::from_version(input.journalVersion).ok_or(PageError::Version)?;
+            let journal = evaluate(&input);
+            if journal.allowed {
+                self.journal.aggregateAmount = self
+                    .journal
+                    .aggregateAmount
+                    .saturating_add(journal.tradeAmount);
+                self.journal.capExceeded = self.journal.aggregateAmount > self.journal.aggregateCap;
+                self.cohort
+                    .push(cohort_leaf(journal.user, journal.productId));
+            } else {
+                self.rejected = true;
+            }
+            let journal = journal.encode(encoding);
+            self.journal.journalsDigest = fold_digest(self.journal.journalsDigest, &journal);
+            self.journal.count += 1;
+        }
+        self.journal.pagesDigest = fold_digest(self.journal.pagesDigest, page);
+        Ok(())
+    }
+
+    /// The journal of the pages pushed so far.
+    pub fn finish(mut self) -> BatchJournal {
+        self.journal.allAllowed = self.journal.count > 0
+            && !self.rejected
+            && !self.journal.exceeded
+            && !self.journal.heapCapExceeded
+            && !self.journal.capExceeded;
+        self.journal.cohortRoot = self.cohort.root().unwrap_or_default();
+        self.journal
+    }
+}
+
+/// Why a page could not be added to a [BatchFold].
+#[derive(Debug)]
+pub enum PageError {
+    /// The batch declares more than [MAX_BATCH_PAGES] pages.
+    Exceeded,
+    /// An earlier page was refused for the guest's heap cap.
+    HeapCap,
+    /// The page is not a whole number of [ComplianceInput::LEN]-byte inputs.
+    Length(usize),
+    /// The page holds more than [MAX_PAGE_INPUTS] inputs.
+    TooManyInputs(usize),
+    /// An input is not the canonical encoding of one, as the compliance guest requires.
+    Input(InputError),
+    /// An input asks for an unknown [JournalEncoding].
+    Version,
+}
+
+impl core::fmt::Display for PageError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PageError::Exceeded => write!(f, "batch declares more than {MAX_BATCH_PAGES} pages"),
+            PageError::HeapCap => write!(f, "an earlier page was over the guest's heap cap"),
+            PageError::Length(len) => write!(
+                f,
+                "page is {len} bytes, not a whole number of {}-byte inputs",
+                ComplianceInput::LEN
+            ),
+            PageError::TooManyInputs(inputs) => {
+                write!(f, "page holds {inputs} inputs, more than {MAX_PAGE_INPUTS}")
+            }
+            PageError::Input(err) => write!(f, "invalid compliance input: {err}"),
+            PageError::Version => write!(f, "unknown journal version"),
+        }
+    }
+}
+
+impl core::error::Error for PageError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            PageError::Input(err) => Some(err),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Product ids of Uniswap v4 pools.
+//!
+//! A pool has no id of its own beyond its `PoolId`, so a pool traded through a v4 hook uses that id
+//! as its product id. Guests and the host derive it here, from the same code.
+
+use alloy_primitives::B256;
+use alloy_sol_types::SolValue;
+
+alloy_sol_types::sol! {
+    /// A Uniswap v4 pool, as `PoolManager` keys it. Currencies are token addresses, with the zero
+    /// address for the native currency, and `hooks` is the address of the pool's hook contract.
+    #[derive(Debug, PartialEq, Eq)]
+    struct PoolKey {
+        address currency0;
+        address currency1;
+        uint24 fee;
+        int24 tickSpacing;
+        address hooks;
+    }
+}
+
+impl PoolKey {
+    /// The pool's `PoolId`, which is its product id.
+    ///
+    /// `PoolIdLibrary.toId` hashes the five words the key occupies in memory. Each field fills a
+    /// word of its own, with `tickSpacing` sign-extended, which is exactly `abi.encode(key)`.
+    pub fn id(&self) -> B256 {
+        alloy_primitives::keccak256(self.abi_encode())
+    }
+}
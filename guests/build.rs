@@ -25,6 +25,8 @@ fn main() {
     // Builds can be made deterministic, and thereby reproducible, by using Docker to build the
     // guest. Check the RISC0_USE_DOCKER variable and use Docker to build the guest if set.
     println!("cargo:rerun-if-env-changed=RISC0_USE_DOCKER");
+    // The guests read their heap cap from it at compile time.
+    println!("cargo:rerun-if-env-changed=GUEST_HEAP_CAP");
     println!("cargo:rerun-if-changed=build.rs");
     let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
     let mut builder = GuestOptionsBuilder::default();
@@ -35,17 +37,32 @@ fn main() {
             .unwrap();
         builder.use_docker(docker_options);
     }
+    let profile = env::var_os("CARGO_FEATURE_PROFILE").is_some();
+    if profile {
+        builder.features(vec!["profile".to_string()]);
+    }
     let guest_options = builder.build().unwrap();
 
     // Generate Rust source files for the methods crate.
     let guests = embed_methods_with_options(HashMap::from([("compliance", guest_options)]));
 
+    // A profiling build is not the program the hook verifies.
+    if profile {
+        return;
+    }
+
     // Generate Solidity source files for use with Forge.
     let solidity_opts = risc0_build_ethereum::Options::default()
         .with_image_id_sol_path(SOLIDITY_IMAGE_ID_PATH)
         .with_elf_sol_path(SOLIDITY_ELF_PATH);
 
-    if let Err(e) = generate_solidity_files(guests.as_slice(), &solidity_opts) {
+    // Only the compliance and batch guests are deployed; the other binaries exist for the cycle
+    // tests.
+    let deployed: Vec<_> = guests
+        .into_iter()
+        .filter(|guest| matches!(&*guest.name, "compliance" | "batch"))
+        .collect();
+    if let Err(e) = generate_solidity_files(&deployed, &solidity_opts) {
         println!("cargo:warning=Failed to generate Solidity files: {e}");
     };
 }
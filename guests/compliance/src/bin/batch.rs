@@ -0,0 +1,58 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Evaluates a paged batch of compliance inputs in one execution and commits its
+//! `BatchJournal`, which the hook's `recordBatch` takes under `ImageID.BATCH_ID`. Compared against
+//! separate executions of the compliance guest in `guests/tests/cycles.rs`.
+//!
+//! The input is the number of pages as a `u32`, the aggregate cap as 32 big-endian bytes, then one
+//! frame per page holding up to `MAX_PAGE_INPUTS` ABI-encoded inputs back to back. Pages are read
+//! and evaluated one at a time into one buffer, so only one is in memory. A batch of more than
+//! `MAX_BATCH_PAGES` pages is not read at all, and reading stops at a page longer than the heap cap
+//! leaves room for; either way the journal records why.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use alloy_primitives::U256;
+use alloy_sol_types::SolValue;
+use compliance::read_frame_into;
+use compliance_core::BatchFold;
+use risc0_zkvm::guest::env;
+
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    let mut pages: u32 = 0;
+    env::read_slice(core::slice::from_mut(&mut pages));
+    let mut cap = [0u8; 32];
+    env::read_slice(&mut cap);
+
+    let mut batch = BatchFold::new(pages, U256::from_be_bytes(cap));
+    if !batch.exceeded() {
+        let mut page = Vec::new();
+        for _ in 0..pages {
+            if read_frame_into(&mut page).is_err() {
+                batch.refuse_page();
+                break;
+            }
+            batch.push_page(&page).expect("invalid batch page");
+        }
+    }
+    env::commit_slice(&batch.finish().abi_encode());
+}
@@ -0,0 +1,34 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reads the compliance input and commits it straight back, re-encoded, without evaluating it.
+//! Its cycle count is the cost of input handling alone; see `guests/tests/cycles.rs`.
+
+#![no_main]
+#![no_std]
+
+use alloy_sol_types::SolValue;
+use compliance::read_input;
+use compliance_core::ComplianceJournal;
+use risc0_zkvm::guest::env;
+
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    let committed = match read_input() {
+        Ok(input) => input.abi_encode(),
+        Err(reason) => ComplianceJournal::rejected(reason).abi_encode(),
+    };
+    env::commit_slice(&committed);
+}
@@ -0,0 +1,53 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recovers the signer of every signature in its input and commits the signers and the cycles
+//! recovery took as `(address[], uint64)`. See `guests/tests/cycles.rs`.
+//!
+//! The input is one frame of 97-byte records: a 32-byte prehash, then the 65-byte `r ‖ s ‖ v`
+//! signature over it.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use alloy_primitives::{Address, Signature, B256};
+use alloy_sol_types::SolValue;
+// Nothing else is used from it, but it provides `native_keccak256` for address derivation.
+use compliance as _;
+use risc0_zkvm::guest::env;
+
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    let records = env::read_frame();
+
+    let start = env::cycle_count();
+    let signers: Vec<Address> = records
+        .chunks_exact(97)
+        .map(|record| {
+            let (prehash, signature) = record.split_at(32);
+            Signature::try_from(signature)
+                .expect("invalid signature")
+                .recover_address_from_prehash(&B256::from_slice(prehash))
+                .expect("unrecoverable signature")
+        })
+        .collect();
+    let cycles = env::cycle_count() - start;
+
+    env::commit_slice(&(signers, cycles).abi_encode());
+}
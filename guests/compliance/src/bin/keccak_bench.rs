@@ -0,0 +1,78 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verifies a Merkle proof three times: with `keccak256` (the accelerator with `keccak-accel`), with
+//! the software `Keccak256` hasher, and as a SHA-256 tree on the SHA-256 accelerator. Commits the
+//! three roots and cycle counts as `(bytes32, bytes32, bytes32, uint64, uint64, uint64)`. See
+//! `guests/tests/cycles.rs`.
+//!
+//! The input is one frame holding the leaf followed by its proof, 32 bytes each.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use alloy_primitives::{Keccak256, B256};
+use alloy_sol_types::SolValue;
+// Nothing else is used from it, but it provides `native_keccak256`.
+use compliance as _;
+use compliance_core::{merkle_root, merkle_root_with, Sha256};
+use risc0_zkvm::guest::env;
+
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    let nodes: Vec<B256> = env::read_frame()
+        .chunks_exact(32)
+        .map(B256::from_slice)
+        .collect();
+    let (leaf, proof) = nodes.split_first().expect("missing leaf");
+
+    let start = env::cycle_count();
+    let accelerated = merkle_root(*leaf, proof);
+    let accelerated_cycles = env::cycle_count() - start;
+
+    let start = env::cycle_count();
+    let software = proof.iter().fold(*leaf, |node, sibling| {
+        let (low, high) = if node <= *sibling {
+            (node, *sibling)
+        } else {
+            (*sibling, node)
+        };
+        let mut hasher = Keccak256::new();
+        hasher.update(low);
+        hasher.update(high);
+        hasher.finalize()
+    });
+    let software_cycles = env::cycle_count() - start;
+
+    let start = env::cycle_count();
+    let sha256 = merkle_root_with::<Sha256>(*leaf, proof);
+    let sha256_cycles = env::cycle_count() - start;
+
+    env::commit_slice(
+        &(
+            accelerated,
+            software,
+            sha256,
+            accelerated_cycles,
+            software_cycles,
+            sha256_cycles,
+        )
+            .abi_encode(),
+    );
+}
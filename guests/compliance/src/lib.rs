@@ -0,0 +1,223 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Input handling shared by the compliance guest and its benchmarks, the accelerated `keccak256`
+//! every guest binary links with the `keccak-accel` feature, and the cycle and heap counting of
+//! the `profile` feature.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+#[cfg(not(feature = "strict-decode"))]
+use alloy_sol_types::SolValue;
+#[cfg(feature = "profile")]
+use compliance_core::CycleProfile;
+use compliance_core::{ComplianceInput, Probe, ReasonCode, Section};
+use risc0_zkvm::guest::env;
+
+/// Read the input frame and decode it, or the [ReasonCode] to reject it with.
+///
+/// The frame must be [ComplianceInput::LEN] bytes, so it is read straight into a buffer of that
+/// size on the stack and decoded from there, with no heap allocation or intermediate copy.
+pub fn read_input() -> Result<ComplianceInput, ReasonCode> {
+    read_input_with(&mut ())
+}
+
+/// [read_input], telling `probe` as reading and decoding start.
+pub fn read_input_with(probe: &mut impl Probe) -> Result<ComplianceInput, ReasonCode> {
+    probe.enter(Section::Read);
+    let mut len: u32 = 0;
+    env::read_slice(core::slice::from_mut(&mut len));
+    let len = len as usize;
+    // The host chooses the length prefix, so a frame the heap could not hold is refused before
+    // any of it is read, as [read_frame_into] refuses one, and so is any other wrong length.
+    if len > HEAP_CAP.saturating_sub(heap_used()) || len != ComplianceInput::LEN {
+        return Err(ReasonCode::InputInvalid);
+    }
+    let mut bytes = [0u8; ComplianceInput::LEN];
+    env::read_slice(&mut bytes);
+    probe.enter(Section::Decode);
+    decode(&bytes)
+}
+
+/// Decode an input, rejecting any that is not canonically encoded.
+#[cfg(feature = "strict-decode")]
+fn decode(bytes: &[u8]) -> Result<ComplianceInput, ReasonCode> {
+    ComplianceInput::try_from(bytes).map_err(|_| ReasonCode::InputInvalid)
+}
+
+/// Decode an input leniently, as images without `strict-decode` did.
+#[cfg(not(feature = "strict-decode"))]
+fn decode(bytes: &[u8]) -> Result<ComplianceInput, ReasonCode> {
+    ComplianceInput::abi_decode(bytes).map_err(|_| ReasonCode::InputInvalid)
+}
+
+/// Most bytes of heap a guest may have in use, set at compile time with the `GUEST_HEAP_CAP`
+/// environment variable and 64 MiB by default. The guest's allocator never frees, so this also
+/// caps everything it allocates. Each cap builds a guest with its own image id.
+pub const HEAP_CAP: usize = match option_env!("GUEST_HEAP_CAP") {
+    Some(cap) => parse_cap(cap),
+    None => 64 << 20,
+};
+
+/// `GUEST_HEAP_CAP` as a number of bytes.
+const fn parse_cap(cap: &str) -> usize {
+    let digits = cap.as_bytes();
+    assert!(!digits.is_empty(), "GUEST_HEAP_CAP is empty");
+    let mut bytes: usize = 0;
+    let mut at = 0;
+    while at < digits.len() {
+        let digit = digits[at];
+        assert!(
+            digit.is_ascii_digit(),
+            "GUEST_HEAP_CAP is not a number of bytes"
+        );
+        bytes = match bytes.checked_mul(10) {
+            Some(tens) => match tens.checked_add((digit - b'0') as usize) {
+                Some(bytes) => bytes,
+                None => panic!("GUEST_HEAP_CAP is too large"),
+            },
+            None => panic!("GUEST_HEAP_CAP is too large"),
+        };
+        at += 1;
+    }
+    bytes
+}
+
+/// Bytes of heap in use. The allocator never frees, so this is also the most ever in use.
+pub fn heap_used() -> usize {
+    risc0_zkvm_platform::heap::used()
+}
+
+/// A frame declared more bytes than [HEAP_CAP] leaves room for.
+#[derive(Debug)]
+pub struct HeapCapExceeded {
+    /// The length the frame declared.
+    pub len: usize,
+}
+
+/// Read a frame into `frame`, replacing what it held.
+///
+/// The host chooses the frame's length prefix, so a length that would take the heap past
+/// [HEAP_CAP] is refused before anything is allocated for it, leaving the rest of the frame
+/// unread. Otherwise `frame` is only reallocated, to exactly the frame's length, when the frame
+/// is longer than it has ever been.
+pub fn read_frame_into(frame: &mut Vec<u8>) -> Result<(), HeapCapExceeded> {
+    let mut len: u32 = 0;
+    env::read_slice(core::slice::from_mut(&mut len));
+    let len = len as usize;
+    // A reallocation never reuses the old buffer, so it takes the whole new length.
+    let allocates = if len > frame.capacity() { len } else { 0 };
+    if allocates > HEAP_CAP.saturating_sub(heap_used()) {
+        return Err(HeapCapExceeded { len });
+    }
+    frame.clear();
+    frame.reserve_exact(len);
+    frame.resize(len, 0);
+    env::read_slice(frame);
+    Ok(())
+}
+
+/// [Probe] that counts the cycles of each section with [env::cycle_count].
+#[cfg(feature = "profile")]
+pub struct CycleProbe {
+    profile: CycleProfile,
+    section: Section,
+    since: u64,
+}
+
+#[cfg(feature = "profile")]
+impl CycleProbe {
+    /// A probe in [Section::Startup], which began with the session.
+    pub fn start() -> Self {
+        Self {
+            profile: CycleProfile::default(),
+            section: Section::Startup,
+            since: 0,
+        }
+    }
+
+    /// End the current section and return the cycles of all of them, with the heap in use.
+    pub fn finish(mut self) -> CycleProfile {
+        let now = env::cycle_count();
+        self.profile.add(self.section, now - self.since);
+        self.profile.heap_peak = heap_used() as u64;
+        self.profile
+    }
+}
+
+#[cfg(feature = "profile")]
+impl Probe for CycleProbe {
+    fn enter(&mut self, section: Section) {
+        let now = env::cycle_count();
+        self.profile.add(self.section, now - self.since);
+        self.section = section;
+        self.since = now;
+    }
+}
+
+/// Bytes absorbed per Keccak-256 permutation.
+#[cfg(feature = "keccak-accel")]
+const KECCAK_RATE: usize = 136;
+
+/// The `keccak256` that alloy's `native-keccak` feature calls instead of its own.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes and `output` to 32 writable ones, as
+/// `alloy_primitives::keccak256` guarantees.
+#[cfg(feature = "keccak-accel")]
+#[no_mangle]
+unsafe extern "C" fn native_keccak256(bytes: *const u8, len: usize, output: *mut u8) {
+    let bytes = unsafe { core::slice::from_raw_parts(bytes, len) };
+    let output = unsafe { &mut *output.cast::<[u8; 32]>() };
+    *output = accelerated_keccak256(bytes);
+}
+
+/// Keccak-256 with each permutation run by the keccak accelerator through
+/// [env::risc0_keccak_update]. Only the permutation differs from the software hash; padding and
+/// the sponge are the standard ones, so the digests are identical.
+#[cfg(feature = "keccak-accel")]
+fn accelerated_keccak256(bytes: &[u8]) -> [u8; 32] {
+    let mut state = [0u64; 25];
+    let mut blocks = bytes.chunks_exact(KECCAK_RATE);
+    for block in &mut blocks {
+        absorb(&mut state, block);
+        env::risc0_keccak_update(&mut state);
+    }
+
+    let rest = blocks.remainder();
+    let mut last = [0u8; KECCAK_RATE];
+    last[..rest.len()].copy_from_slice(rest);
+    last[rest.len()] ^= 0x01;
+    last[KECCAK_RATE - 1] ^= 0x80;
+    absorb(&mut state, &last);
+    env::risc0_keccak_update(&mut state);
+
+    let mut digest = [0u8; 32];
+    for (chunk, lane) in digest.chunks_exact_mut(8).zip(state) {
+        chunk.copy_from_slice(&lane.to_le_bytes());
+    }
+    digest
+}
+
+#[cfg(feature = "keccak-accel")]
+fn absorb(state: &mut [u64; 25], block: &[u8]) {
+    for (lane, word) in state.iter_mut().zip(block.chunks_exact(8)) {
+        *lane ^= u64::from_le_bytes(word.try_into().unwrap());
+    }
+}
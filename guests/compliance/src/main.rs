@@ -12,26 +12,46 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::io::Read;
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
 
-use alloy_primitives::{Address, B256};
 use alloy_sol_types::SolValue;
+use compliance::read_input_with;
+use compliance_core::{
+    evaluate_with, ComplianceJournal, JournalEncoding, Probe, ReasonCode, Section,
+};
 use risc0_zkvm::guest::env;
 
+risc0_zkvm::guest::entry!(main);
 
 fn main() {
-    let mut input_bytes = Vec::<u8>::new();
-    env::stdin().read_to_end(&mut input_bytes).unwrap();
-
-    type Input = (Address, B256, bool, bool);
-
-    let (user, product_id, kyc_passed, aml_passed) =
-        <Input>::abi_decode(&input_bytes).expect("invalid compliance input");
-
-    let allowed = kyc_passed && aml_passed;
+    #[cfg(feature = "profile")]
+    let mut probe = compliance::CycleProbe::start();
+    #[cfg(not(feature = "profile"))]
+    let mut probe = ();
+
+    // An input the guest cannot decide on still gets a journal, one that allows nothing, rather
+    // than a failed session the host can only retry.
+    let journal = decide(&mut probe)
+        .unwrap_or_else(|reason| ComplianceJournal::rejected(reason).abi_encode());
+    probe.enter(Section::Commit);
+    env::commit_slice(&journal);
 
-    type Output = (Address, B256, bool);
-    let journal = <Output>::abi_encode(&(user, product_id, allowed));
+    // The profile goes to stdout rather than the journal, so profiling never changes the journal.
+    #[cfg(feature = "profile")]
+    env::write_slice(&probe.finish().encode());
+}
 
-    env::commit_slice(&journal);
+/// Read the input, evaluate it and encode the decision as the input asks.
+fn decide(probe: &mut impl Probe) -> Result<Vec<u8>, ReasonCode> {
+    let input = read_input_with(probe)?;
+    let encoding =
+        JournalEncoding::from_version(input.journalVersion).ok_or(ReasonCode::InputInvalid)?;
+    let decision = evaluate_with(&input, probe);
+    probe.enter(Section::Encode);
+    Ok(decision.encode(encoding))
 }
@@ -12,60 +12,735 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloy_primitives::{Address, B256};
+use alloy_primitives::{keccak256, Address, Signature, B256, U256};
 use alloy_sol_types::SolValue;
-use guests::COMPLIANCE_ELF;
-use risc0_zkvm::{default_executor, ExecutorEnv};
+use compliance_core::{
+    aggregate_attestation_hash_with, attestation_hash, attestation_hashes, evaluate,
+    governance_hash, override_hash, CommitmentHash, ComplianceInput, ComplianceJournal,
+    JournalEncoding, ReasonCode, GOVERNANCE_SET_LEN, KYC_PROVIDER_SET_LEN, SHA256_COMMITMENTS,
+};
+use guests::{COMPLIANCE_ELF, COMPLIANCE_ID};
+use risc0_zkvm::{default_executor, sha::Digest, ExecutorEnv};
 
-type Input = (Address, B256, bool, bool);
-type Output = (Address, B256, bool);
+/// Fixed `now` for tests that do not check it.
+const NOW: u64 = 1_700_000_000;
+
+fn image_id() -> B256 {
+    B256::from_slice(Digest::from(COMPLIANCE_ID).as_bytes())
+}
 
 #[test]
 fn allows_when_kyc_and_aml_pass() {
-    let user = Address::from([1u8; 20]);
-    let product_id = B256::from([2u8; 32]);
-    let kyc_passed = true;
-    let aml_passed = true;
-
-    let input = (user, product_id, kyc_passed, aml_passed);
+    let input = ComplianceInput {
+        amlPassed: true,
+        ..denied_input()
+    };
 
     let env = ExecutorEnv::builder()
-        .write_slice(&Input::abi_encode(&input))
+        .write_frame(&input.abi_encode())
         .build()
         .unwrap();
 
     // NOTE: Use the executor to run tests without proving.
     let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
 
-    let (journal_user, journal_product_id, allowed) =
-        <Output>::abi_decode(&session_info.journal.bytes).unwrap();
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
 
-    assert_eq!(journal_user, user);
-    assert_eq!(journal_product_id, product_id);
-    assert!(allowed);
+    assert_eq!(journal.user, input.user);
+    assert_eq!(journal.productId, input.productId);
+    assert!(journal.allowed);
 }
 
 #[test]
 fn rejects_when_kyc_or_aml_fail() {
-    let user = Address::from([3u8; 20]);
-    let product_id = B256::from([4u8; 32]);
-    let kyc_passed = true;
-    let aml_passed = false;
+    let input = denied_input();
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+
+    assert_eq!(journal.user, input.user);
+    assert_eq!(journal.productId, input.productId);
+    assert!(!journal.allowed);
+}
+
+#[test]
+fn commits_supplied_image_id() {
+    let input = ComplianceInput {
+        amlPassed: true,
+        ..denied_input()
+    };
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+
+    assert_eq!(journal.imageId, image_id());
+}
+
+#[test]
+fn commits_supplied_now_and_ttl() {
+    let now = 1_767_225_600;
+    let ttl_secs = 3_600;
+    let input = ComplianceInput {
+        amlPassed: true,
+        now,
+        ttlSecs: ttl_secs,
+        ..denied_input()
+    };
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+
+    assert_eq!((journal.decisionAt, journal.ttlSecs), (now, ttl_secs));
+}
+
+#[test]
+fn commits_attester_and_attestation_hash() {
+    let attester = Address::from([9u8; 20]);
+    let input = ComplianceInput {
+        kycTier: 2,
+        amlPassed: true,
+        claims: U256::from(0b101),
+        attester,
+        ..denied_input()
+    };
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+
+    assert_eq!(journal.attester, attester);
+    assert_eq!(journal.attestationHash, attestation_hash(&input));
+}
+
+#[test]
+fn commits_aggregate_of_attestation_hashes() {
+    let input = ComplianceInput {
+        kycTier: 3,
+        claims: U256::from(0b110),
+        attester: Address::ZERO,
+        ..denied_input()
+    };
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+
+    let reference = keccak256(attestation_hashes(&input).map(|hash| hash.0).concat());
+    assert_eq!(journal.attestationsHash, reference);
+}
+
+#[test]
+fn commits_compact_journal_when_requested() {
+    let input = ComplianceInput {
+        amlPassed: true,
+        journalVersion: JournalEncoding::Compact as u8,
+        ..denied_input()
+    };
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = &session_info.journal.bytes;
+    assert_eq!(journal.len(), ComplianceJournal::COMPACT_LEN);
+    assert_eq!(
+        ComplianceJournal::try_from(journal.as_slice()).unwrap(),
+        evaluate(&input)
+    );
+}
+
+#[test]
+fn commits_sha256_attestations_hash_when_requested() {
+    let input = ComplianceInput {
+        kycTier: 2,
+        amlPassed: true,
+        claims: U256::from(0b11),
+        attester: Address::ZERO,
+        journalVersion: JournalEncoding::Compact as u8 | SHA256_COMMITMENTS,
+        ..denied_input()
+    };
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
 
-    let input = (user, product_id, kyc_passed, aml_passed);
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+    assert_eq!(journal, evaluate(&input));
+    assert_eq!(journal.commitmentHash, CommitmentHash::Sha256 as u8);
+    assert_eq!(
+        journal.attestationsHash,
+        aggregate_attestation_hash_with(&attestation_hashes(&input), CommitmentHash::Sha256)
+    );
+}
+
+#[test]
+fn commits_the_decision_uuid() {
+    let decision_uuid = B256::from([16u8; 32]);
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let input = ComplianceInput {
+            amlPassed: true,
+            journalVersion: encoding as u8,
+            decisionUuid: decision_uuid,
+            ..denied_input()
+        };
+
+        let env = ExecutorEnv::builder()
+            .write_frame(&input.abi_encode())
+            .build()
+            .unwrap();
+
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+        let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+        assert_eq!(journal, evaluate(&input));
+        assert_eq!(journal.decisionUuid, decision_uuid);
+    }
+}
+
+#[test]
+fn commits_the_credential_hash() {
+    let credential_hash = B256::from([17u8; 32]);
+    let input = ComplianceInput {
+        amlPassed: true,
+        credentialHash: credential_hash,
+        ..denied_input()
+    };
 
     let env = ExecutorEnv::builder()
-        .write_slice(&Input::abi_encode(&input))
+        .write_frame(&input.abi_encode())
         .build()
         .unwrap();
 
     // NOTE: Use the executor to run tests without proving.
     let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
 
-    let (journal_user, journal_product_id, allowed) =
-        <Output>::abi_decode(&session_info.journal.bytes).unwrap();
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+    assert_eq!(journal, evaluate(&input));
+    assert_eq!(journal.credentialHash, credential_hash);
+}
+
+#[test]
+fn commits_the_not_before_time() {
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let input = ComplianceInput {
+            amlPassed: true,
+            journalVersion: encoding as u8,
+            notBefore: 1_700_000_030,
+            ..denied_input()
+        };
+
+        let env = ExecutorEnv::builder()
+            .write_frame(&input.abi_encode())
+            .build()
+            .unwrap();
+
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+        let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+        assert_eq!(journal, evaluate(&input));
+        assert_eq!(journal.notBefore, 1_700_000_030);
+    }
+}
+
+#[test]
+fn rejects_unknown_journal_version() {
+    let input = ComplianceInput {
+        amlPassed: true,
+        journalVersion: 2,
+        ..denied_input()
+    };
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+    assert!(!journal.allowed);
+    assert_eq!(journal.reasons, ReasonCode::InputInvalid.bit());
+}
+
+#[test]
+fn commits_product_policy_version() {
+    let input = ComplianceInput {
+        amlPassed: true,
+        productPolicyVersion: 7,
+        quotePolicyVersion: 7,
+        ..denied_input()
+    };
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+
+    assert_eq!(journal.productPolicyVersion, 7);
+}
+
+/// Sign the override of `input` with the secp256k1 key `key` and return the signer.
+fn sign_override(input: &mut ComplianceInput, key: u8) -> Address {
+    let signing_key = k256::ecdsa::SigningKey::from_slice(&B256::with_last_byte(key).0).unwrap();
+    let (signature, recovery_id) = signing_key
+        .sign_prehash_recoverable(&override_hash(input).0)
+        .unwrap();
+    let compact =
+        Signature::from_signature_and_parity(signature, recovery_id.is_y_odd()).as_erc2098();
+    input.overrideR = B256::from_slice(&compact[..32]);
+    input.overrideYParityAndS = B256::from_slice(&compact[32..]);
+    Address::from_public_key(signing_key.verifying_key())
+}
+
+fn denied_input() -> ComplianceInput {
+    ComplianceInput {
+        user: Address::from([18u8; 20]),
+        productId: B256::from([19u8; 32]),
+        kycTier: 1,
+        requiredTier: 1,
+        amlPassed: false,
+        claims: U256::ZERO,
+        requiredClaims: U256::ZERO,
+        productPolicyVersion: 0,
+        quoteProduct: B256::from([19u8; 32]),
+        quoteRequiredTier: 0,
+        quoteRequiredClaims: U256::ZERO,
+        quotePolicyVersion: 0,
+        riskScore: 0,
+        reviewBandLow: 0,
+        reviewBandHigh: 0,
+        regimeId: 0,
+        settlementToken: Address::ZERO,
+        caseId: B256::ZERO,
+        tradeAmount: U256::from(100),
+        minTrade: U256::ZERO,
+        maxTrade: U256::MAX,
+        attester: Address::from([20u8; 20]),
+        requiredAttestations: 0,
+        governance: [Address::ZERO; GOVERNANCE_SET_LEN],
+        overrideR: B256::ZERO,
+        overrideYParityAndS: B256::ZERO,
+        journalVersion: 0,
+        now: NOW,
+        ttlSecs: 0,
+        decisionUuid: B256::ZERO,
+        credentialHash: B256::ZERO,
+        notBefore: 0,
+        amlSources: U256::ZERO,
+        requiredAmlSources: U256::ZERO,
+        blockHash: B256::ZERO,
+        kycProvider: 0,
+        allowedKycProviders: [0; KYC_PROVIDER_SET_LEN],
+        consentGiven: false,
+        consentAt: 0,
+        consentRequired: false,
+        attestationSignatureHash: B256::ZERO,
+        imageId: image_id(),
+    }
+}
+
+#[test]
+fn governance_override_allows_denied_trade() {
+    let mut input = denied_input();
+    let signer = sign_override(&mut input, 1);
+    input.governance[1] = signer;
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+    assert!(journal.allowed);
+    assert_eq!(
+        journal.reasons,
+        ReasonCode::AmlFailed.bit() | ReasonCode::Override.bit()
+    );
+    assert_eq!(journal.overrideSigner, signer);
+    assert_eq!(journal.governanceHash, governance_hash(&input));
+}
+
+#[test]
+fn override_from_outside_governance_set_is_denied() {
+    let mut input = denied_input();
+    sign_override(&mut input, 1);
+    input.governance[0] = sign_override(&mut denied_input(), 2);
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+    assert!(!journal.allowed);
+    assert_eq!(
+        journal.reasons,
+        ReasonCode::AmlFailed.bit() | ReasonCode::OverrideUnauthorized.bit()
+    );
+    assert_eq!(journal.overrideSigner, Address::ZERO);
+}
+
+#[test]
+fn zero_user_is_denied_even_when_overridden() {
+    let mut input = ComplianceInput {
+        user: Address::ZERO,
+        ..denied_input()
+    };
+    input.governance[0] = sign_override(&mut input, 1);
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+    assert!(!journal.allowed);
+    assert_eq!(
+        journal.reasons,
+        ReasonCode::AmlFailed.bit() | ReasonCode::Override.bit() | ReasonCode::UserZero.bit()
+    );
+    assert_eq!(journal, evaluate(&input));
+}
+
+#[test]
+fn commits_required_and_achieved_attestation_counts() {
+    // An attester only counts with a signature the proof commits to.
+    for (attester, signature_hash, allowed, achieved) in [
+        (Address::from([21u8; 20]), B256::from([22u8; 32]), true, 1),
+        (Address::from([21u8; 20]), B256::ZERO, false, 0),
+        (Address::ZERO, B256::ZERO, false, 0),
+    ] {
+        let input = ComplianceInput {
+            attester,
+            attestationSignatureHash: signature_hash,
+            requiredAttestations: 1,
+            amlPassed: true,
+            ..denied_input()
+        };
+
+        let env = ExecutorEnv::builder()
+            .write_frame(&input.abi_encode())
+            .build()
+            .unwrap();
+
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+        let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+
+        assert_eq!(journal.allowed, allowed);
+        assert_eq!(
+            (journal.requiredAttestations, journal.achievedAttestations),
+            (1, achieved)
+        );
+        if journal.allowed {
+            assert!(journal.achievedAttestations >= journal.requiredAttestations);
+        } else {
+            assert_eq!(journal.reasons, ReasonCode::AttestationsMissing.bit());
+        }
+    }
+}
+
+#[test]
+fn pair_is_denied_when_either_leg_is_ineligible() {
+    let quote_product = B256::from([22u8; 32]);
+    for (kyc_tier, claims, reasons) in [
+        (2, U256::from(0b11), 0),
+        (1, U256::from(0b11), ReasonCode::KycTierTooLow.bit()),
+        (2, U256::from(0b01), ReasonCode::ClaimsMissing.bit()),
+    ] {
+        // The base leg wants claim 0; the quote leg wants tier 2 and claim 1.
+        let input = ComplianceInput {
+            amlPassed: true,
+            kycTier: kyc_tier,
+            claims,
+            requiredClaims: U256::from(0b01),
+            quoteProduct: quote_product,
+            quoteRequiredTier: 2,
+            quoteRequiredClaims: U256::from(0b10),
+            quotePolicyVersion: 4,
+            ..denied_input()
+        };
+
+        let env = ExecutorEnv::builder()
+            .write_frame(&input.abi_encode())
+            .build()
+            .unwrap();
+
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+        let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+        assert_eq!(journal, evaluate(&input));
+        assert_eq!(journal.allowed, reasons == 0);
+        assert_eq!(journal.reasons, reasons);
+        assert_eq!(journal.productId, input.productId);
+        assert_eq!(journal.quoteProduct, quote_product);
+        assert_eq!(journal.quotePolicyVersion, 4);
+    }
+}
+
+#[test]
+fn gray_zone_risk_score_is_held_for_review() {
+    for (risk_score, aml_passed, allowed, needs_review, reasons) in [
+        (39, true, true, false, 0),
+        (40, true, false, true, ReasonCode::ReviewRequired.bit()),
+        (69, true, false, true, ReasonCode::ReviewRequired.bit()),
+        (70, true, true, false, 0),
+        // A hard denial is rejected outright, whatever the risk score.
+        (
+            50,
+            false,
+            false,
+            false,
+            ReasonCode::AmlFailed.bit() | ReasonCode::ReviewRequired.bit(),
+        ),
+    ] {
+        let input = ComplianceInput {
+            amlPassed: aml_passed,
+            riskScore: risk_score,
+            reviewBandLow: 40,
+            reviewBandHigh: 70,
+            ..denied_input()
+        };
+
+        let env = ExecutorEnv::builder()
+            .write_frame(&input.abi_encode())
+            .build()
+            .unwrap();
+
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+        let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+        assert_eq!(journal, evaluate(&input));
+        assert_eq!(
+            (journal.allowed, journal.needsReview, journal.reasons),
+            (allowed, needs_review, reasons),
+            "risk score {risk_score}"
+        );
+    }
+}
+
+#[test]
+fn trade_size_band_is_inclusive() {
+    let (min_trade, max_trade) = (U256::from(100), U256::from(1_000));
+    for (amount, allowed) in [
+        (U256::from(99), false),
+        (min_trade, true),
+        (max_trade, true),
+        (U256::from(1_001), false),
+    ] {
+        let input = ComplianceInput {
+            amlPassed: true,
+            tradeAmount: amount,
+            minTrade: min_trade,
+            maxTrade: max_trade,
+            ..denied_input()
+        };
+
+        let env = ExecutorEnv::builder()
+            .write_frame(&input.abi_encode())
+            .build()
+            .unwrap();
+
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+        let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+        assert_eq!(journal, evaluate(&input));
+        assert_eq!(journal.allowed, allowed, "amount {amount}");
+        let reasons = if allowed {
+            0
+        } else {
+            ReasonCode::TradeSizeOutOfRange.bit()
+        };
+        assert_eq!(journal.reasons, reasons, "amount {amount}");
+        assert_eq!(
+            (journal.tradeAmount, journal.minTrade, journal.maxTrade),
+            (amount, min_trade, max_trade)
+        );
+    }
+}
+
+#[test]
+fn kyc_provider_must_be_allowed_by_the_product() {
+    let allowed_kyc_providers = [3, 7, 0, 0];
+    for (kyc_provider, allowed) in [(7, true), (3, true), (9, false), (0, false)] {
+        let input = ComplianceInput {
+            amlPassed: true,
+            kycProvider: kyc_provider,
+            allowedKycProviders: allowed_kyc_providers,
+            ..denied_input()
+        };
+
+        let env = ExecutorEnv::builder()
+            .write_frame(&input.abi_encode())
+            .build()
+            .unwrap();
+
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+        let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+        assert_eq!(journal, evaluate(&input));
+        assert_eq!(journal.allowed, allowed, "provider {kyc_provider}");
+        let reasons = if allowed {
+            0
+        } else {
+            ReasonCode::KycProviderNotAllowed.bit()
+        };
+        assert_eq!(journal.reasons, reasons, "provider {kyc_provider}");
+        assert_eq!(journal.kycProvider, kyc_provider);
+    }
+}
+
+#[test]
+fn consent_must_be_recorded_where_the_product_requires_it() {
+    let now = denied_input().now;
+    for (consent_given, consent_at, allowed) in [
+        (true, now - 1, true),
+        (true, now, true),
+        (true, now + 1, false),
+        (true, 0, false),
+        (false, now - 1, false),
+    ] {
+        let input = ComplianceInput {
+            amlPassed: true,
+            consentGiven: consent_given,
+            consentAt: consent_at,
+            consentRequired: true,
+            ..denied_input()
+        };
+
+        let env = ExecutorEnv::builder()
+            .write_frame(&input.abi_encode())
+            .build()
+            .unwrap();
+
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+        let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+        assert_eq!(journal, evaluate(&input));
+        assert_eq!(journal.allowed, allowed, "{consent_given} at {consent_at}");
+        let reasons = if allowed {
+            0
+        } else {
+            ReasonCode::ConsentMissing.bit()
+        };
+        assert_eq!(journal.reasons, reasons, "{consent_given} at {consent_at}");
+        assert_eq!(
+            (journal.consentGiven, journal.consentAt),
+            (consent_given, consent_at)
+        );
+    }
+}
+
+#[test]
+fn rejects_non_canonical_input() {
+    let canonical = denied_input().abi_encode();
+    // `amlPassed` is the fifth word; as 2 it would read as `true` to a lenient decoder.
+    let mut dirty = canonical.clone();
+    dirty[5 * 32 - 1] = 2;
+
+    for (bytes, reasons) in [
+        (canonical, ReasonCode::AmlFailed.bit()),
+        (dirty.clone(), ReasonCode::InputInvalid.bit()),
+    ] {
+        let env = ExecutorEnv::builder().write_frame(&bytes).build().unwrap();
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+        let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+        assert_eq!(journal.reasons, reasons);
+    }
+
+    // The rejection names nothing from the input it could not decode.
+    let env = ExecutorEnv::builder().write_frame(&dirty).build().unwrap();
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+    assert_eq!(
+        session_info.journal.bytes,
+        ComplianceJournal::rejected(ReasonCode::InputInvalid).abi_encode()
+    );
+}
+
+#[test]
+fn rejects_input_frames_of_the_wrong_length() {
+    let canonical = denied_input().abi_encode();
+    let short = ExecutorEnv::builder()
+        .write_frame(&canonical[..canonical.len() - 32])
+        .build()
+        .unwrap();
+    // A length prefix no heap could hold, and nothing after it.
+    let huge = ExecutorEnv::builder()
+        .write_slice(&[u32::MAX])
+        .build()
+        .unwrap();
 
-    assert_eq!(journal_user, user);
-    assert_eq!(journal_product_id, product_id);
-    assert!(!allowed);
+    for env in [short, huge] {
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+        assert_eq!(
+            session_info.journal.bytes,
+            ComplianceJournal::rejected(ReasonCode::InputInvalid).abi_encode()
+        );
+    }
 }
@@ -0,0 +1,439 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cycle and image-size budgets of the compliance guest, so input handling, dependency or policy
+//! changes that make proving more expensive show up in review instead of on the market bill, and
+//! the keccak, ECDSA and batch benchmarks that price hashing, signature checks and amortizing the
+//! guest's fixed costs over many decisions.
+
+use alloy_primitives::{keccak256, Address, Signature, B256, U256};
+use alloy_sol_types::{sol_data, SolType, SolValue};
+use compliance_core::{
+    batch_pages, merkle_root, merkle_root_with, BatchFold, BatchJournal, ComplianceInput,
+    JournalEncoding, Regime, Sha256, GOVERNANCE_SET_LEN, KYC_PROVIDER_SET_LEN, KYC_TIER_FULL,
+    MAX_BATCH_PAGES, MAX_PAGE_INPUTS,
+};
+use guests::{
+    BATCH_ELF, COMPLIANCE_ELF, COMPLIANCE_ID, DECODE_COMMIT_ELF, ECDSA_BENCH_ELF, KECCAK_BENCH_ELF,
+};
+use risc0_binfmt::{Program, ProgramBinary};
+use risc0_zkvm::{default_executor, sha::Digest, ExecutorEnv, SessionInfo, GUEST_MAX_MEM};
+
+/// Most user cycles one decision may take. Raise it only for a deliberate change to the guest and
+/// record the new count in the change.
+const CYCLE_BUDGET: u64 = 1 << 20;
+
+/// Most user cycles reading and decoding the input may take, measured with the `decode_commit`
+/// guest, which commits the decoded input without evaluating it.
+const INPUT_CYCLE_BUDGET: u64 = 1 << 17;
+
+/// Depth of the Merkle proof the keccak benchmark verifies.
+const MERKLE_DEPTH: usize = 20;
+
+/// How many times cheaper a Merkle proof must be with the keccak accelerator than without it.
+const KECCAK_SPEEDUP: u64 = 4;
+
+/// Signature counts the ECDSA benchmark recovers, covering one signer and small M-of-N policies.
+const SIGNATURE_COUNTS: [usize; 3] = [1, 2, 5];
+
+/// Decision counts the batch benchmark evaluates in one execution.
+const BATCH_SIZES: [usize; 4] = [1, 10, 100, 1000];
+
+/// Largest initial memory image of the user program, in bytes. It was 137,668 bytes while the
+/// guest linked `std`, 71,056 once it became `no_std`, 62,780 once it read its input into a
+/// stack buffer, 75,712 once it hashed on the keccak accelerator and 168,684 once it linked `k256`
+/// to recover governance override signatures.
+const IMAGE_BUDGET: usize = 192 << 10;
+
+fn input(journal_version: u8) -> ComplianceInput {
+    // Every field set, so every rule and the attestation hash do their full work. There is no
+    // override signature; recovering one is what `guest_recovers_the_same_signers_as_the_host`
+    // measures.
+    ComplianceInput {
+        user: Address::repeat_byte(0x11),
+        productId: B256::repeat_byte(0x22),
+        kycTier: KYC_TIER_FULL,
+        requiredTier: KYC_TIER_FULL,
+        amlPassed: true,
+        claims: U256::MAX,
+        requiredClaims: U256::MAX,
+        productPolicyVersion: u32::MAX,
+        quoteProduct: B256::repeat_byte(0x22),
+        quoteRequiredTier: 0,
+        quoteRequiredClaims: U256::ZERO,
+        quotePolicyVersion: u32::MAX,
+        riskScore: u8::MAX,
+        reviewBandLow: 0,
+        reviewBandHigh: 0,
+        regimeId: Regime::Mica as u16,
+        settlementToken: Address::ZERO,
+        caseId: B256::ZERO,
+        tradeAmount: U256::from(100),
+        minTrade: U256::ZERO,
+        maxTrade: U256::MAX,
+        attester: Address::repeat_byte(0x33),
+        requiredAttestations: 1,
+        governance: [Address::ZERO; GOVERNANCE_SET_LEN],
+        overrideR: B256::ZERO,
+        overrideYParityAndS: B256::ZERO,
+        journalVersion: journal_version,
+        now: 1_700_000_000,
+        ttlSecs: 0,
+        decisionUuid: B256::ZERO,
+        credentialHash: B256::ZERO,
+        notBefore: 0,
+        amlSources: U256::ZERO,
+        requiredAmlSources: U256::ZERO,
+        blockHash: B256::ZERO,
+        kycProvider: 0,
+        allowedKycProviders: [0; KYC_PROVIDER_SET_LEN],
+        consentGiven: false,
+        consentAt: 0,
+        consentRequired: false,
+        attestationSignatureHash: B256::ZERO,
+        imageId: B256::from_slice(Digest::from(COMPLIANCE_ID).as_bytes()),
+    }
+}
+
+/// Cycles the session is proven in: each segment padded to its power of two, so the fixed cost of
+/// starting a segment is included.
+fn proving_cycles(session_info: &SessionInfo) -> u64 {
+    session_info
+        .segments
+        .iter()
+        .map(|segment| 1 << segment.po2)
+        .sum()
+}
+
+/// User cycles and journal of `elf` run on `input`.
+fn execute(elf: &[u8], input: &ComplianceInput) -> (u64, Vec<u8>) {
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, elf).unwrap();
+    (session_info.cycles(), session_info.journal.bytes)
+}
+
+/// Calldata gas of `bytes` under EIP-2028: 4 per zero byte, 16 per non-zero byte.
+fn calldata_gas(bytes: &[u8]) -> usize {
+    bytes.iter().map(|&b| if b == 0 { 4 } else { 16 }).sum()
+}
+
+#[test]
+fn decision_stays_within_cycle_budget() {
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        let (cycles, journal) = execute(COMPLIANCE_ELF, &input(encoding as u8));
+        println!(
+            "{} journal: {} bytes, {} calldata gas, {cycles} user cycles",
+            encoding.name(),
+            journal.len(),
+            calldata_gas(&journal)
+        );
+        assert!(
+            cycles <= CYCLE_BUDGET,
+            "{} journal took {cycles} user cycles, over the budget of {CYCLE_BUDGET}",
+            encoding.name()
+        );
+    }
+}
+
+#[test]
+fn input_handling_stays_within_cycle_budget() {
+    let input = input(JournalEncoding::Abi as u8);
+    let (cycles, journal) = execute(DECODE_COMMIT_ELF, &input);
+    println!("input handling: {cycles} user cycles");
+    assert_eq!(journal, input.abi_encode());
+    assert!(
+        cycles <= INPUT_CYCLE_BUDGET,
+        "input handling took {cycles} user cycles, over the budget of {INPUT_CYCLE_BUDGET}"
+    );
+}
+
+#[test]
+fn keccak_accelerator_speeds_up_merkle_verification() {
+    let leaf = keccak256("leaf");
+    let proof: Vec<B256> = (0..MERKLE_DEPTH as u64)
+        .map(|level| keccak256(level.to_be_bytes()))
+        .collect();
+    let frame: Vec<u8> = [leaf]
+        .iter()
+        .chain(&proof)
+        .flat_map(|node| node.0)
+        .collect();
+
+    let env = ExecutorEnv::builder().write_frame(&frame).build().unwrap();
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, KECCAK_BENCH_ELF).unwrap();
+    let (accelerated, software, sha256, accelerated_cycles, software_cycles, sha256_cycles) =
+        <(
+            sol_data::FixedBytes<32>,
+            sol_data::FixedBytes<32>,
+            sol_data::FixedBytes<32>,
+            sol_data::Uint<64>,
+            sol_data::Uint<64>,
+            sol_data::Uint<64>,
+        )>::abi_decode(&session_info.journal.bytes)
+        .unwrap();
+
+    let depth = MERKLE_DEPTH as u64;
+    println!(
+        "{MERKLE_DEPTH}-level Merkle proof: {accelerated_cycles} cycles accelerated ({} per hash), \
+         {software_cycles} in software ({} per hash), {sha256_cycles} as a SHA-256 tree ({} per \
+         hash)",
+        accelerated_cycles / depth,
+        software_cycles / depth,
+        sha256_cycles / depth
+    );
+    // The host hashes in software; the guest must agree with it either way.
+    assert_eq!(accelerated, merkle_root(leaf, &proof));
+    assert_eq!(software, accelerated);
+    assert_eq!(sha256, merkle_root_with::<Sha256>(leaf, &proof));
+    assert!(
+        accelerated_cycles * KECCAK_SPEEDUP <= software_cycles,
+        "accelerated proof took {accelerated_cycles} cycles against {software_cycles} in software"
+    );
+}
+
+/// `(prehash, signature, signer)` vectors, signed on the host with keys `1..=count`.
+fn signature_vectors(count: usize) -> Vec<(B256, Signature, Address)> {
+    (1..=count as u8)
+        .map(|key| {
+            let signing_key =
+                k256::ecdsa::SigningKey::from_slice(&B256::with_last_byte(key).0).unwrap();
+            let prehash = keccak256([key]);
+            let (signature, recovery_id) =
+                signing_key.sign_prehash_recoverable(&prehash.0).unwrap();
+            let signature = Signature::from_signature_and_parity(signature, recovery_id.is_y_odd());
+            let signer = Address::from_public_key(signing_key.verifying_key());
+            (prehash, signature, signer)
+        })
+        .collect()
+}
+
+#[test]
+fn guest_recovers_the_same_signers_as_the_host() {
+    let mut cycles = Vec::new();
+    for count in SIGNATURE_COUNTS {
+        let vectors = signature_vectors(count);
+        let frame: Vec<u8> = vectors
+            .iter()
+            .flat_map(|(prehash, signature, _)| {
+                [prehash.to_vec(), signature.as_bytes().to_vec()].concat()
+            })
+            .collect();
+
+        let env = ExecutorEnv::builder().write_frame(&frame).build().unwrap();
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor().execute(env, ECDSA_BENCH_ELF).unwrap();
+        let (signers, recovery_cycles) =
+            <(sol_data::Array<sol_data::Address>, sol_data::Uint<64>)>::abi_decode(
+                &session_info.journal.bytes,
+            )
+            .unwrap();
+
+        for ((prehash, signature, signer), recovered) in vectors.iter().zip(&signers) {
+            assert_eq!(
+                signature.recover_address_from_prehash(prehash).unwrap(),
+                *signer
+            );
+            assert_eq!(recovered, signer);
+        }
+        println!("{count} signatures: {recovery_cycles} cycles");
+        cycles.push(recovery_cycles);
+    }
+
+    let (first, last) = (
+        SIGNATURE_COUNTS[0],
+        SIGNATURE_COUNTS[SIGNATURE_COUNTS.len() - 1],
+    );
+    let marginal = (cycles[cycles.len() - 1] - cycles[0]) / (last - first) as u64;
+    println!("each further signature: {marginal} cycles");
+    assert!(cycles.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[test]
+fn image_stays_within_size_budget() {
+    let binary = ProgramBinary::decode(COMPLIANCE_ELF).unwrap();
+    let program = Program::load_elf(binary.user_elf, GUEST_MAX_MEM as u32).unwrap();
+    let image = program.size_in_words() * 4;
+    println!("user image: {image} bytes");
+    assert!(
+        image <= IMAGE_BUDGET,
+        "user image is {image} bytes, over the budget of {IMAGE_BUDGET}"
+    );
+}
+
+#[test]
+fn batching_amortizes_setup_overhead() {
+    let input = input(JournalEncoding::Abi as u8);
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+    // NOTE: Use the executor to run tests without proving.
+    let single = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+    let (single_cycles, single_proving) = (single.cycles(), proving_cycles(&single));
+    println!("1 separate execution: {single_cycles} user cycles, {single_proving} proving cycles");
+
+    for count in BATCH_SIZES {
+        let pages = batch_pages(&vec![input.clone(); count]);
+        let mut env = ExecutorEnv::builder();
+        env.write_slice(&[pages.len() as u32]);
+        env.write_slice(&U256::MAX.to_be_bytes::<32>());
+        for page in &pages {
+            env.write_frame(page);
+        }
+        let session_info = default_executor()
+            .execute(env.build().unwrap(), BATCH_ELF)
+            .unwrap();
+        let journal = <BatchJournal as SolValue>::abi_decode(&session_info.journal.bytes).unwrap();
+
+        let mut expected = BatchFold::new(pages.len() as u32, U256::MAX);
+        for page in &pages {
+            expected.push_page(page).unwrap();
+        }
+        assert_eq!(journal.count as usize, count);
+        assert_eq!(journal, expected.finish());
+        let batched = proving_cycles(&session_info);
+        let separate = single_proving * count as u64;
+        println!(
+            "{count} decisions: {} user cycles and {batched} proving cycles in one execution, \
+             {separate} proving cycles in {count} separate ones",
+            session_info.cycles()
+        );
+        if count > 1 {
+            assert!(
+                batched < separate,
+                "batching {count} decisions took {batched} proving cycles against {separate}"
+            );
+        }
+    }
+}
+
+#[test]
+fn batch_guest_fails_on_missing_pages_and_skips_excess_ones() {
+    let pages = batch_pages(&vec![input(JournalEncoding::Abi as u8); 100]);
+    let run = |declared: u32, sent: &[Vec<u8>]| {
+        let mut env = ExecutorEnv::builder();
+        env.write_slice(&[declared]);
+        env.write_slice(&U256::MAX.to_be_bytes::<32>());
+        for page in sent {
+            env.write_frame(page);
+        }
+        default_executor().execute(env.build().unwrap(), BATCH_ELF)
+    };
+
+    // Declaring a page that never arrives leaves the guest reading past the end of its input.
+    assert!(run(pages.len() as u32, &pages[..1]).is_err());
+
+    let session_info = run(MAX_BATCH_PAGES + 1, &pages).unwrap();
+    let journal = <BatchJournal as SolValue>::abi_decode(&session_info.journal.bytes).unwrap();
+    assert!(journal.exceeded);
+    assert_eq!(journal.count, 0);
+}
+
+#[test]
+fn batch_guest_commits_a_journal_for_a_page_over_the_heap_cap() {
+    let pages = batch_pages(&vec![input(JournalEncoding::Abi as u8); 100]);
+    // A first page as sent, then a length prefix no heap could hold and nothing after it.
+    let mut env = ExecutorEnv::builder();
+    env.write_slice(&[3u32]);
+    env.write_slice(&U256::MAX.to_be_bytes::<32>());
+    env.write_frame(&pages[0]);
+    env.write_slice(&[u32::MAX]);
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor()
+        .execute(env.build().unwrap(), BATCH_ELF)
+        .unwrap();
+    let journal = <BatchJournal as SolValue>::abi_decode(&session_info.journal.bytes).unwrap();
+
+    let mut expected = BatchFold::new(3, U256::MAX);
+    expected.push_page(&pages[0]).unwrap();
+    expected.refuse_page();
+    assert!(journal.heapCapExceeded);
+    assert_eq!(journal.count as usize, MAX_PAGE_INPUTS);
+    assert_eq!(journal, expected.finish());
+}
+
+#[test]
+fn batch_guest_commits_the_aggregate_and_its_cap() {
+    let pages = batch_pages(&vec![input(JournalEncoding::Abi as u8); 10]);
+    let run = |cap: U256| {
+        let mut env = ExecutorEnv::builder();
+        env.write_slice(&[pages.len() as u32]);
+        env.write_slice(&cap.to_be_bytes::<32>());
+        for page in &pages {
+            env.write_frame(page);
+        }
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor()
+            .execute(env.build().unwrap(), BATCH_ELF)
+            .unwrap();
+        <BatchJournal as SolValue>::abi_decode(&session_info.journal.bytes).unwrap()
+    };
+    let expected = |cap: U256| {
+        let mut fold = BatchFold::new(pages.len() as u32, cap);
+        for page in &pages {
+            fold.push_page(page).unwrap();
+        }
+        fold.finish()
+    };
+
+    let uncapped = run(U256::MAX);
+    assert_eq!(uncapped, expected(U256::MAX));
+    assert!(!uncapped.aggregateAmount.is_zero());
+    for cap in [
+        uncapped.aggregateAmount,
+        uncapped.aggregateAmount - U256::from(1),
+    ] {
+        let journal = run(cap);
+        assert_eq!(journal, expected(cap));
+        assert_eq!(journal.capExceeded, cap < uncapped.aggregateAmount);
+    }
+}
+
+/// The sections of a profiled decision cover the whole session: what is left is writing the
+/// profile out and halting.
+#[cfg(feature = "profile")]
+#[test]
+fn profile_sections_add_up_to_the_session() {
+    use compliance_core::{CycleProfile, Section};
+
+    let mut stdout = Vec::new();
+    let env = ExecutorEnv::builder()
+        .write_frame(&input(JournalEncoding::Abi as u8).abi_encode())
+        .stdout(&mut stdout)
+        .build()
+        .unwrap();
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+    let profile = CycleProfile::decode(&stdout).expect("guest wrote no cycle profile");
+
+    for section in Section::ALL {
+        println!("{:<14}{:>10}", section.name(), profile.get(section));
+    }
+    println!("heap peak: {} bytes", profile.heap_peak);
+    let session = session_info.cycles();
+    let profiled = profile.total();
+    assert!(
+        profiled.abs_diff(session) <= session / 20,
+        "sections add up to {profiled} cycles, the session took {session}"
+    );
+    assert!(profile.get(Section::Override) > 0);
+    // The input is read onto the stack, so only encoding and hashing touch the heap.
+    assert!(profile.heap_peak > 0);
+}
@@ -0,0 +1,172 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks that the guest enforces exactly the policy of the host reference implementation.
+
+use alloy_primitives::{Address, B256, U256};
+use alloy_sol_types::SolValue;
+use compliance_core::{
+    evaluate, ComplianceInput, ComplianceJournal, JournalEncoding, GOVERNANCE_SET_LEN,
+    KYC_PROVIDER_SET_LEN, KYC_TIER_FULL,
+};
+use guests::{COMPLIANCE_ELF, COMPLIANCE_ID};
+use risc0_zkvm::{default_executor, sha::Digest, ExecutorEnv};
+
+/// Seed of the generated inputs. Change it to explore a different set; failures print the case.
+const SEED: u64 = 0x5eed_c0de_2024_0001;
+const CASES: usize = 128;
+
+/// SplitMix64, so the generated set is identical on every run and platform.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            chunk.copy_from_slice(&self.next_u64().to_le_bytes()[..chunk.len()]);
+        }
+    }
+
+    fn bool(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+
+    /// A tier in `0..=4`, covering every defined tier plus one above [KYC_TIER_FULL].
+    fn tier(&mut self) -> u8 {
+        (self.next_u64() % (KYC_TIER_FULL as u64 + 2)) as u8
+    }
+
+    /// A claims bitmap over the low four items, so required sets are met about a third of the time.
+    fn claims(&mut self) -> U256 {
+        U256::from(self.next_u64() & 0xf)
+    }
+
+    /// A KYC provider in `0..=3`, zero naming none, so provider sets of a few slots match some
+    /// of the time.
+    fn provider(&mut self) -> u16 {
+        (self.next_u64() % 4) as u16
+    }
+
+    fn input(&mut self, image_id: B256) -> ComplianceInput {
+        let mut user = Address::ZERO;
+        let mut product_id = B256::ZERO;
+        let mut quote_product = B256::ZERO;
+        let mut attester = Address::ZERO;
+        let mut decision_uuid = B256::ZERO;
+        let mut credential_hash = B256::ZERO;
+        let mut block_hash = B256::ZERO;
+        let mut attestation_signature_hash = B256::ZERO;
+        self.fill(user.as_mut_slice());
+        self.fill(product_id.as_mut_slice());
+        self.fill(quote_product.as_mut_slice());
+        self.fill(attester.as_mut_slice());
+        self.fill(decision_uuid.as_mut_slice());
+        self.fill(credential_hash.as_mut_slice());
+        self.fill(block_hash.as_mut_slice());
+        self.fill(attestation_signature_hash.as_mut_slice());
+        ComplianceInput {
+            user,
+            productId: product_id,
+            kycTier: self.tier(),
+            requiredTier: self.tier(),
+            amlPassed: self.bool(),
+            claims: self.claims(),
+            requiredClaims: self.claims(),
+            productPolicyVersion: self.next_u64() as u32,
+            // A pair about half the time, otherwise the product itself.
+            quoteProduct: if self.bool() {
+                quote_product
+            } else {
+                product_id
+            },
+            quoteRequiredTier: self.tier(),
+            quoteRequiredClaims: self.claims(),
+            quotePolicyVersion: self.next_u64() as u32,
+            riskScore: self.next_u64() as u8,
+            reviewBandLow: self.next_u64() as u8,
+            reviewBandHigh: self.next_u64() as u8,
+            // One id past the known regimes, so unknown ones are covered too.
+            regimeId: (self.next_u64() % 5) as u16,
+            settlementToken: Address::ZERO,
+            caseId: B256::ZERO,
+            tradeAmount: U256::from(100),
+            minTrade: U256::ZERO,
+            maxTrade: U256::MAX,
+            attester,
+            requiredAttestations: (self.next_u64() % 3) as u8,
+            governance: [Address::ZERO; GOVERNANCE_SET_LEN],
+            overrideR: B256::ZERO,
+            overrideYParityAndS: B256::ZERO,
+            journalVersion: self.bool() as u8,
+            now: self.next_u64(),
+            ttlSecs: self.next_u64(),
+            decisionUuid: decision_uuid,
+            credentialHash: credential_hash,
+            notBefore: self.next_u64(),
+            amlSources: self.claims(),
+            requiredAmlSources: self.claims(),
+            blockHash: block_hash,
+            kycProvider: self.provider(),
+            allowedKycProviders: [(); KYC_PROVIDER_SET_LEN].map(|()| self.provider()),
+            consentGiven: self.bool(),
+            // Past, future or unset alike.
+            consentAt: self.next_u64(),
+            consentRequired: self.bool(),
+            // Bound about half the time, so unbound attesters are covered too.
+            attestationSignatureHash: if self.bool() {
+                attestation_signature_hash
+            } else {
+                B256::ZERO
+            },
+            imageId: image_id,
+        }
+    }
+}
+
+#[test]
+fn guest_matches_reference_policy() {
+    let image_id = B256::from_slice(Digest::from(COMPLIANCE_ID).as_bytes());
+    let mut rng = Rng(SEED);
+
+    for case in 0..CASES {
+        let input = rng.input(image_id);
+
+        let env = ExecutorEnv::builder()
+            .write_frame(&input.abi_encode())
+            .build()
+            .unwrap();
+
+        // NOTE: Use the executor to run tests without proving.
+        let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+        let journal = ComplianceJournal::try_from(session_info.journal.bytes.as_slice()).unwrap();
+        assert_eq!(
+            journal,
+            evaluate(&input),
+            "case {case} (seed {SEED:#x}) diverged for {input:?}"
+        );
+        let encoding = JournalEncoding::from_version(input.journalVersion).unwrap();
+        assert_eq!(
+            session_info.journal.bytes,
+            evaluate(&input).encode(encoding)
+        );
+    }
+}
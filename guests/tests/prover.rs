@@ -0,0 +1,109 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The other guest tests only execute the guest. These prove it too, and check that the prover
+//! commits the same journal, byte for byte, as the executor did for the same input. The two must
+//! always match: the host previews decisions with the executor (`--dry-run`, `--compare-guest`)
+//! and the hook settles what the prover committed. Proving is slow, so the tests only build with
+//! the `prove` feature:
+//!
+//! ```bash
+//! cargo test --release -p guests --features prove --test prover
+//! ```
+#![cfg(feature = "prove")]
+
+use alloy_primitives::{Address, B256, U256};
+use alloy_sol_types::SolValue;
+use compliance_core::{
+    evaluate, ComplianceInput, ComplianceJournal, JournalEncoding, Regime, GOVERNANCE_SET_LEN,
+    KYC_PROVIDER_SET_LEN, KYC_TIER_FULL,
+};
+use guests::{COMPLIANCE_ELF, COMPLIANCE_ID};
+use risc0_zkvm::{default_executor, default_prover, sha::Digest, ExecutorEnv};
+
+fn input(journal_version: u8, aml_passed: bool) -> ComplianceInput {
+    ComplianceInput {
+        user: Address::repeat_byte(0x11),
+        productId: B256::repeat_byte(0x22),
+        kycTier: KYC_TIER_FULL,
+        requiredTier: KYC_TIER_FULL,
+        amlPassed: aml_passed,
+        claims: U256::MAX,
+        requiredClaims: U256::MAX,
+        productPolicyVersion: 3,
+        quoteProduct: B256::repeat_byte(0x22),
+        quoteRequiredTier: 0,
+        quoteRequiredClaims: U256::ZERO,
+        quotePolicyVersion: 3,
+        riskScore: 0,
+        reviewBandLow: 0,
+        reviewBandHigh: 0,
+        regimeId: Regime::Mica as u16,
+        settlementToken: Address::ZERO,
+        caseId: B256::repeat_byte(0x33),
+        tradeAmount: U256::from(100),
+        minTrade: U256::ZERO,
+        maxTrade: U256::MAX,
+        attester: Address::ZERO,
+        requiredAttestations: 0,
+        governance: [Address::ZERO; GOVERNANCE_SET_LEN],
+        overrideR: B256::ZERO,
+        overrideYParityAndS: B256::ZERO,
+        journalVersion: journal_version,
+        now: 1_700_000_000,
+        ttlSecs: 3600,
+        decisionUuid: B256::repeat_byte(0x44),
+        credentialHash: B256::ZERO,
+        notBefore: 0,
+        amlSources: U256::ZERO,
+        requiredAmlSources: U256::ZERO,
+        blockHash: B256::ZERO,
+        kycProvider: 7,
+        allowedKycProviders: [0; KYC_PROVIDER_SET_LEN],
+        consentGiven: true,
+        consentAt: 1_699_999_000,
+        consentRequired: true,
+        attestationSignatureHash: B256::ZERO,
+        imageId: B256::from_slice(Digest::from(COMPLIANCE_ID).as_bytes()),
+    }
+}
+
+#[test]
+fn prover_commits_the_executors_journal() {
+    for encoding in [JournalEncoding::Abi, JournalEncoding::Compact] {
+        for aml_passed in [true, false] {
+            let input = input(encoding as u8, aml_passed);
+            let env = || {
+                ExecutorEnv::builder()
+                    .write_frame(&input.abi_encode())
+                    .build()
+                    .unwrap()
+            };
+
+            let executed = default_executor().execute(env(), COMPLIANCE_ELF).unwrap();
+            let proven = default_prover().prove(env(), COMPLIANCE_ELF).unwrap();
+
+            proven.receipt.verify(COMPLIANCE_ID).unwrap();
+            assert_eq!(
+                proven.receipt.journal.bytes,
+                executed.journal.bytes,
+                "{} journal, aml passed {aml_passed}",
+                encoding.name()
+            );
+            let journal = ComplianceJournal::try_from(executed.journal.bytes.as_slice()).unwrap();
+            assert_eq!(journal, evaluate(&input));
+            assert_eq!(journal.allowed, aml_passed);
+        }
+    }
+}
@@ -0,0 +1,610 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Table of compliance rule cases run through the guest.
+//!
+//! Each row of [rule_cases!] becomes its own test named after the row. Adding a rule means adding
+//! an input column to [Case], or a `with` tweak of the input, and rows for it, not new test
+//! functions. [every_reason_code_has_a_case] fails until some row produces each [ReasonCode], so
+//! a new rule ships with at least one denial here.
+
+use alloy_primitives::{Address, Signature, B256, U256};
+use alloy_sol_types::SolValue;
+use compliance_core::{
+    override_hash, ComplianceInput, ComplianceJournal, ReasonCode, Regime,
+    AML_SOURCE_ADVERSE_MEDIA, AML_SOURCE_PEP, AML_SOURCE_SANCTIONS, CLAIM_MICA_APPROPRIATENESS,
+    CLAIM_SEC_ACCREDITED_INVESTOR, GOVERNANCE_SET_LEN, KYC_PROVIDER_SET_LEN,
+};
+use guests::{COMPLIANCE_ELF, COMPLIANCE_ID};
+use risc0_zkvm::{default_executor, sha::Digest, ExecutorEnv};
+
+/// Questionnaire items of the regimes the table exercises.
+const MICA: U256 = CLAIM_MICA_APPROPRIATENESS;
+const SEC: U256 = CLAIM_SEC_ACCREDITED_INVESTOR;
+
+/// AML list sources the table exercises.
+const SANCTIONS: U256 = AML_SOURCE_SANCTIONS;
+const PEP: U256 = AML_SOURCE_PEP;
+const ADVERSE_MEDIA: U256 = AML_SOURCE_ADVERSE_MEDIA;
+
+/// Input fields varied by the table; everything else is fixed.
+struct Case {
+    kyc_tier: u8,
+    required_tier: u8,
+    aml_passed: bool,
+    aml_sources: U256,
+    required_aml_sources: U256,
+    claims: U256,
+    required_claims: U256,
+    regime_id: u16,
+}
+
+/// Key of the governance member the override rows sign with.
+const GOVERNOR_KEY: u8 = 7;
+
+fn governor() -> k256::ecdsa::SigningKey {
+    k256::ecdsa::SigningKey::from_slice(&B256::with_last_byte(GOVERNOR_KEY).0).unwrap()
+}
+
+/// Sign the override of `input` with [governor], whether or not it is in the governance set.
+fn sign_override(input: &mut ComplianceInput) {
+    let (signature, recovery_id) = governor()
+        .sign_prehash_recoverable(&override_hash(input).0)
+        .unwrap();
+    let compact =
+        Signature::from_signature_and_parity(signature, recovery_id.is_y_odd()).as_erc2098();
+    input.overrideR = B256::from_slice(&compact[..32]);
+    input.overrideYParityAndS = B256::from_slice(&compact[32..]);
+}
+
+fn check(
+    name: &str,
+    case: Case,
+    tweak: fn(&mut ComplianceInput),
+    allowed: bool,
+    reasons: &[ReasonCode],
+) {
+    let image_id = B256::from_slice(Digest::from(COMPLIANCE_ID).as_bytes());
+    let mut input = ComplianceInput {
+        user: Address::from([1u8; 20]),
+        productId: B256::from([2u8; 32]),
+        kycTier: case.kyc_tier,
+        requiredTier: case.required_tier,
+        amlPassed: case.aml_passed,
+        claims: case.claims,
+        requiredClaims: case.required_claims,
+        productPolicyVersion: 1,
+        quoteProduct: B256::from([2u8; 32]),
+        quoteRequiredTier: 0,
+        quoteRequiredClaims: U256::ZERO,
+        quotePolicyVersion: 1,
+        riskScore: 0,
+        reviewBandLow: 0,
+        reviewBandHigh: 0,
+        regimeId: case.regime_id,
+        settlementToken: Address::ZERO,
+        caseId: B256::ZERO,
+        tradeAmount: U256::from(100),
+        minTrade: U256::ZERO,
+        maxTrade: U256::MAX,
+        attester: Address::ZERO,
+        requiredAttestations: 0,
+        governance: [Address::ZERO; GOVERNANCE_SET_LEN],
+        overrideR: B256::ZERO,
+        overrideYParityAndS: B256::ZERO,
+        journalVersion: 0,
+        now: 1_700_000_000,
+        ttlSecs: 0,
+        decisionUuid: B256::ZERO,
+        credentialHash: B256::ZERO,
+        notBefore: 0,
+        amlSources: case.aml_sources,
+        requiredAmlSources: case.required_aml_sources,
+        blockHash: B256::ZERO,
+        kycProvider: 0,
+        allowedKycProviders: [0; KYC_PROVIDER_SET_LEN],
+        consentGiven: false,
+        consentAt: 0,
+        consentRequired: false,
+        attestationSignatureHash: B256::ZERO,
+        imageId: image_id,
+    };
+    tweak(&mut input);
+
+    let env = ExecutorEnv::builder()
+        .write_frame(&input.abi_encode())
+        .build()
+        .unwrap();
+
+    // NOTE: Use the executor to run tests without proving.
+    let session_info = default_executor().execute(env, COMPLIANCE_ELF).unwrap();
+
+    let journal = ComplianceJournal::abi_decode(&session_info.journal.bytes).unwrap();
+    let expected_reasons = reasons.iter().fold(0, |mask, reason| mask | reason.bit());
+    assert_eq!(journal.allowed, allowed, "{name}: allowed");
+    assert_eq!(
+        journal.reasons, expected_reasons,
+        "{name}: reasons {:#b}, expected {reasons:?}",
+        journal.reasons
+    );
+    assert_eq!(journal.kycTier, case.kyc_tier, "{name}: committed kyc tier");
+    assert_eq!(journal.claims, case.claims, "{name}: committed claims");
+    assert_eq!(
+        journal.amlSources, case.aml_sources,
+        "{name}: committed aml sources"
+    );
+}
+
+macro_rules! rule_cases {
+    (@tweak) => {
+        |_| {}
+    };
+    (@tweak $tweak:expr) => {
+        $tweak
+    };
+    ($(
+        $name:ident: {
+            kyc_tier: $kyc_tier:expr,
+            required_tier: $required_tier:expr,
+            aml_passed: $aml_passed:expr,
+            aml_sources: $aml_sources:expr,
+            required_aml_sources: $required_aml_sources:expr,
+            claims: $claims:expr,
+            required_claims: $required_claims:expr,
+            regime_id: $regime_id:expr $(,)?
+        } $(with $tweak:expr)? => $allowed:expr, [$($reason:ident),* $(,)?];
+    )*) => {
+        /// Name and expected reasons of every row.
+        const CASES: &[(&str, &[ReasonCode])] =
+            &[$((stringify!($name), &[$(ReasonCode::$reason),*])),*];
+
+        $(
+            #[test]
+            fn $name() {
+                check(
+                    stringify!($name),
+                    Case {
+                        kyc_tier: $kyc_tier,
+                        required_tier: $required_tier,
+                        aml_passed: $aml_passed,
+                        aml_sources: U256::from($aml_sources),
+                        required_aml_sources: U256::from($required_aml_sources),
+                        claims: U256::from($claims),
+                        required_claims: U256::from($required_claims),
+                        regime_id: $regime_id,
+                    },
+                    rule_cases!(@tweak $($tweak)?),
+                    $allowed,
+                    &[$(ReasonCode::$reason),*],
+                );
+            }
+        )*
+    };
+}
+
+rule_cases! {
+    // Legacy KYC/AML booleans: `kyc_passed` is tier 1 against the default requirement of 1.
+    kyc_pass_aml_pass: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => true, [];
+    kyc_pass_aml_fail: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: false,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => false, [AmlFailed];
+    kyc_fail_aml_pass: {
+        kyc_tier: 0,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => false, [KycTierTooLow];
+    kyc_fail_aml_fail: {
+        kyc_tier: 0,
+        required_tier: 1,
+        aml_passed: false,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => false, [AmlFailed, KycTierTooLow];
+
+    // Tier gating.
+    no_kyc_without_requirement: {
+        kyc_tier: 0,
+        required_tier: 0,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => false, [KycTierTooLow];
+    basic_without_requirement: {
+        kyc_tier: 1,
+        required_tier: 0,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => true, [];
+    basic_below_intermediate: {
+        kyc_tier: 1,
+        required_tier: 2,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => false, [KycTierTooLow];
+    intermediate_meets_intermediate: {
+        kyc_tier: 2,
+        required_tier: 2,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => true, [];
+    full_above_intermediate: {
+        kyc_tier: 3,
+        required_tier: 2,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => true, [];
+    intermediate_below_full: {
+        kyc_tier: 2,
+        required_tier: 3,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => false, [KycTierTooLow];
+    full_meets_full: {
+        kyc_tier: 3,
+        required_tier: 3,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => true, [];
+    low_tier_and_aml_fail: {
+        kyc_tier: 1,
+        required_tier: 3,
+        aml_passed: false,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => false, [AmlFailed, KycTierTooLow];
+
+    // Questionnaire claims: every required bit must be answered; extra answers are ignored.
+    claims_cover_requirement: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0b0111,
+        required_claims: 0b0101,
+        regime_id: 0,
+    } => true, [];
+    claims_miss_one_requirement: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0b0111,
+        required_claims: 0b1001,
+        regime_id: 0,
+    } => false, [ClaimsMissing];
+    claims_missing_and_aml_fail: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: false,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0b0001,
+        regime_id: 0,
+    } => false, [AmlFailed, ClaimsMissing];
+
+    // AML source coverage: a passed screening only counts when it covered every list the product
+    // requires, and a failed one fails whatever it covered.
+    aml_sources_cover_requirement: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: SANCTIONS | PEP | ADVERSE_MEDIA,
+        required_aml_sources: SANCTIONS | PEP,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => true, [];
+    aml_sources_miss_pep: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: SANCTIONS | ADVERSE_MEDIA,
+        required_aml_sources: SANCTIONS | PEP,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => false, [AmlSourcesMissing];
+    aml_sources_unreported: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: SANCTIONS,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => false, [AmlSourcesMissing];
+    aml_sources_covered_but_aml_fail: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: false,
+        aml_sources: SANCTIONS | PEP,
+        required_aml_sources: SANCTIONS | PEP,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } => false, [AmlFailed];
+
+    // Regimes: each adds its own item to the product's requirements. The MiCA item does not
+    // satisfy SEC rules, and an unknown regime denies the trade outright.
+    mica_appropriateness_assessed: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: MICA,
+        required_claims: 0,
+        regime_id: Regime::Mica as u16,
+    } => true, [];
+    sec_requires_accreditation_not_mica: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: MICA,
+        required_claims: 0,
+        regime_id: Regime::Sec as u16,
+    } => false, [ClaimsMissing];
+    sec_accredited_investor: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: SEC,
+        required_claims: 0,
+        regime_id: Regime::Sec as u16,
+    } => true, [];
+    unknown_regime: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: MICA,
+        required_claims: 0,
+        regime_id: 99,
+    } => false, [RegimeUnknown];
+
+    // Rules on inputs the columns above do not vary, set by the row's `with` tweak.
+    governance_overrides_aml_fail: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: false,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } with |input| {
+        input.governance[0] = Address::from_public_key(governor().verifying_key());
+        sign_override(input);
+    } => true, [AmlFailed, Override];
+    override_outside_governance: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } with sign_override => false, [OverrideUnauthorized];
+    attestation_missing: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } with |input| input.requiredAttestations = 1 => false, [AttestationsMissing];
+    risk_score_in_review_band: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } with |input| {
+        input.riskScore = 50;
+        input.reviewBandLow = 40;
+        input.reviewBandHigh = 60;
+    } => false, [ReviewRequired];
+    zero_user: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } with |input| input.user = Address::ZERO => false, [UserZero];
+    trade_below_minimum: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } with |input| input.minTrade = U256::from(101) => false, [TradeSizeOutOfRange];
+    kyc_provider_not_allowed: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } with |input| {
+        input.kycProvider = 9;
+        input.allowedKycProviders = [3, 7, 0, 0];
+    } => false, [KycProviderNotAllowed];
+    consent_required_but_missing: {
+        kyc_tier: 1,
+        required_tier: 1,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } with |input| input.consentRequired = true => false, [ConsentMissing];
+    // The guest rejects the input rather than deciding, so the journal commits no KYC facts.
+    unknown_journal_version: {
+        kyc_tier: 0,
+        required_tier: 0,
+        aml_passed: true,
+        aml_sources: 0,
+        required_aml_sources: 0,
+        claims: 0,
+        required_claims: 0,
+        regime_id: 0,
+    } with |input| input.journalVersion = 5 => false, [InputInvalid];
+}
+
+/// Numeric value and name of every reason code. Both are part of the journal format: a code is
+/// never renumbered or renamed, and the value of a retired code is never reused.
+const PINNED: [(ReasonCode, u8, &str); 14] = [
+    (ReasonCode::AmlFailed, 0, "aml_failed"),
+    (ReasonCode::KycTierTooLow, 1, "kyc_tier_too_low"),
+    (ReasonCode::ClaimsMissing, 2, "claims_missing"),
+    (ReasonCode::Override, 3, "override"),
+    (ReasonCode::OverrideUnauthorized, 4, "override_unauthorized"),
+    (ReasonCode::AttestationsMissing, 5, "attestations_missing"),
+    (ReasonCode::ReviewRequired, 6, "review_required"),
+    (ReasonCode::RegimeUnknown, 7, "regime_unknown"),
+    (ReasonCode::UserZero, 8, "user_zero"),
+    (
+        ReasonCode::TradeSizeOutOfRange,
+        9,
+        "trade_size_out_of_range",
+    ),
+    (ReasonCode::AmlSourcesMissing, 10, "aml_sources_missing"),
+    (
+        ReasonCode::KycProviderNotAllowed,
+        11,
+        "kyc_provider_not_allowed",
+    ),
+    (ReasonCode::ConsentMissing, 12, "consent_missing"),
+    (ReasonCode::InputInvalid, 13, "input_invalid"),
+];
+
+/// Row of `code` in [PINNED]. The match names every variant, so a new code does not compile until
+/// it is pinned.
+const fn pinned_row(code: ReasonCode) -> usize {
+    match code {
+        ReasonCode::AmlFailed => 0,
+        ReasonCode::KycTierTooLow => 1,
+        ReasonCode::ClaimsMissing => 2,
+        ReasonCode::Override => 3,
+        ReasonCode::OverrideUnauthorized => 4,
+        ReasonCode::AttestationsMissing => 5,
+        ReasonCode::ReviewRequired => 6,
+        ReasonCode::RegimeUnknown => 7,
+        ReasonCode::UserZero => 8,
+        ReasonCode::TradeSizeOutOfRange => 9,
+        ReasonCode::AmlSourcesMissing => 10,
+        ReasonCode::KycProviderNotAllowed => 11,
+        ReasonCode::ConsentMissing => 12,
+        ReasonCode::InputInvalid => 13,
+    }
+}
+
+#[test]
+fn reason_codes_are_pinned() {
+    assert_eq!(ReasonCode::ALL, PINNED.map(|(code, _, _)| code));
+    for (row, (code, value, name)) in PINNED.into_iter().enumerate() {
+        assert_eq!(pinned_row(code), row, "{code:?} is pinned in another row");
+        assert_eq!(code as u8, value, "{code:?} was renumbered");
+        assert_eq!(code.name(), name, "{code:?} was renamed");
+    }
+}
+
+#[test]
+fn every_reason_code_has_a_case() {
+    for code in ReasonCode::ALL {
+        assert!(
+            CASES.iter().any(|(_, reasons)| reasons.contains(&code)),
+            "no rule case produces {}",
+            code.name()
+        );
+    }
+}